@@ -8,6 +8,8 @@
 //!
 
 use clap::Parser;
+use patina::compression::Decompressor;
+use patina_ffs_extractors::SectionDecompressor;
 use std::{
     fs::File,
     io::{self, Read},
@@ -27,6 +29,9 @@ struct Args {
     /// Flag to include the header in the output.
     #[arg(long, default_value_t = false)]
     header: bool,
+    /// Flag indicating the input was compressed on the target, such as by `AdvancedLogger::compressed_log`.
+    #[arg(short, long, default_value_t = false)]
+    compressed: bool,
 }
 
 fn main() -> io::Result<()> {
@@ -39,6 +44,13 @@ fn main() -> io::Result<()> {
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
 
+    if args.compressed {
+        buffer = SectionDecompressor::new().decompress(&buffer).map_err(|e| {
+            eprintln!("Error decompressing log data: {e:?}");
+            io::Error::new(io::ErrorKind::InvalidData, format!("{e:?}"))
+        })?;
+    }
+
     let mut parser = patina_adv_logger::parser::Parser::open(&buffer).map_err(|e| {
         eprintln!("Error opening log data: {e}");
         io::Error::new(io::ErrorKind::InvalidData, e)
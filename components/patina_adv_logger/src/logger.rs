@@ -10,10 +10,12 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 use crate::memory_log::{self, AdvancedLog, LogEntry};
+use alloc::vec::Vec;
 use core::{ffi::c_void, marker::Send, ptr};
 use log::Level;
 use patina::{
     component::service::{Service, perf_timer::ArchTimerFunctionality},
+    compression::Compressor,
     error::EfiError,
     log::Format,
     pi::hob::{Hob, PhaseHandoffInformationTable},
@@ -88,9 +90,7 @@ where
             })?;
         let hob_list = Hob::Handoff(hob_list_info);
         for hob in &hob_list {
-            if let Hob::GuidHob(guid_hob, data) = hob
-                && guid_hob.name == memory_log::ADV_LOGGER_HOB_GUID
-            {
+            if let Some(data) = hob.guid_data(&memory_log::ADV_LOGGER_HOB_GUID) {
                 // SAFETY: The HOB will have a address of the log info
                 // immediately following the HOB header.
                 unsafe {
@@ -150,6 +150,22 @@ where
     pub(crate) fn get_log_address(&self) -> Option<efi::PhysicalAddress> {
         self.memory_log.get().map(|log| log.get_address())
     }
+
+    /// Returns a copy of the memory log compressed with `compressor`.
+    ///
+    /// This is intended for storing the log or sending it to the host, where space
+    /// or bandwidth is scarcer than CPU time. The copy is in the format read by
+    /// `advlog_parser`, which restores it with `--compressed` when it was compressed
+    /// by a `patina_ffs_extractors::SectionCompressor`.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`EfiError::NotReady`] if the memory log has not been initialized,
+    /// or the error of `compressor`.
+    pub fn compressed_log(&self, compressor: &dyn Compressor) -> Result<Vec<u8>, EfiError> {
+        let memory_log = self.memory_log.get().ok_or(EfiError::NotReady)?;
+        compressor.compress(&memory_log.snapshot())
+    }
 }
 
 impl<S> log::Log for AdvancedLogger<'_, S>
@@ -260,6 +276,8 @@ mod tests {
     use alloc::boxed::Box;
     use patina::{
         component::service::{IntoService, perf_timer::ArchTimerFunctionality},
+        compression::NoCompression,
+        error::EfiError,
         log::Format,
         pi::hob::{GUID_EXTENSION, GuidHob, header},
         serial::uart::UartNull,
@@ -354,4 +372,18 @@ mod tests {
 
         // TODO: Need to mock the protocol interface but requires final component interface.
     }
+
+    #[test]
+    fn compressed_log_test() {
+        let logger = AdvancedLogger::new(patina::log::Format::Standard, &[], log::LevelFilter::Trace, UartNull {});
+        assert_eq!(logger.compressed_log(&NoCompression), Err(EfiError::NotReady));
+
+        // SAFETY: The hob list created is valid for this test.
+        unsafe { logger.init(create_adv_logger_hob_list()) }.unwrap();
+        logger.log_write(memory_log::DEBUG_LEVEL_INFO, b"compressed log test");
+
+        let log = logger.compressed_log(&NoCompression).unwrap();
+        let log = AdvancedLog::open_log(&log).unwrap();
+        assert!(log.iter().any(|entry| entry.get_message() == b"compressed log test"));
+    }
 }
@@ -9,9 +9,10 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+use alloc::vec::Vec;
 use core::{
     cell::UnsafeCell,
-    mem::size_of,
+    mem::{offset_of, size_of},
     ptr, slice,
     sync::atomic::{AtomicU32, AtomicU64, Ordering},
 };
@@ -261,6 +262,33 @@ impl<'a> AdvancedLog<'a> {
         self.header as *const AdvLoggerInfo as efi::PhysicalAddress
     }
 
+    /// Copies the header and the used portion of the log into a buffer that
+    /// [`Self::open_log`] accepts, so the log can be stored or sent elsewhere.
+    ///
+    /// Entries added while the copy is taken are not included. An entry whose
+    /// space was allocated but not yet written appears as zeroed data.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let log_buffer_offset = self.header.log_buffer_offset;
+        let log_current = self.header.log_current_offset.load(Ordering::Relaxed).min(self.header.full_size());
+
+        // SAFETY: The header is followed by the rest of the log_buffer_offset
+        //         bytes that precede the data, as validated when the log was
+        //         adopted or opened.
+        let header = unsafe {
+            slice::from_raw_parts(self.header as *const AdvLoggerInfo as *const u8, log_buffer_offset as usize)
+        };
+
+        let mut bytes = Vec::with_capacity(log_current as usize);
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&self.data.get()[..(log_current - log_buffer_offset) as usize]);
+
+        // The current offset may have moved on while the header was copied, so
+        // record the offset the copy was taken at.
+        let current_offset = offset_of!(AdvLoggerInfo, log_current_offset);
+        bytes[current_offset..current_offset + size_of::<u32>()].copy_from_slice(&log_current.to_le_bytes());
+        bytes
+    }
+
     // Allow unused as it is used in tests and intended for future general use.
     #[allow(dead_code)]
     pub fn discarded_size(&self) -> u32 {
@@ -606,4 +634,32 @@ mod tests {
 
         assert!(iter.next().is_none());
     }
+
+    #[test]
+    fn snapshot_test() {
+        let mut buff_box = Box::new([0_u64; 0x200]);
+        let buffer = buff_box.as_mut();
+        let address = buffer as *mut u64 as PhysicalAddress;
+
+        // SAFETY: We just allocated this memory so it's valid.
+        let log = unsafe { AdvancedLog::initialize_memory_log(address, size_of_val(buffer) as u32) }.unwrap();
+        for val in 0..10_u32 {
+            let data = val.to_be_bytes();
+            log.add_log_entry(LogEntry { level: 0, phase: 0, timestamp: 0, data: &data }).unwrap();
+        }
+
+        // Only the used portion of the log is copied.
+        let snapshot = log.snapshot();
+        assert_eq!(snapshot.len(), log.header.log_current_offset.load(Ordering::Relaxed) as usize);
+        assert!(snapshot.len() < size_of_val(buffer));
+
+        let copy = AdvancedLog::open_log(&snapshot).unwrap();
+        assert!(
+            copy.iter().map(|entry| entry.get_message()).eq((0..10_u32)
+                .map(|val| val.to_be_bytes())
+                .collect::<Vec<_>>()
+                .iter()
+                .map(|data| &data[..]))
+        );
+    }
 }
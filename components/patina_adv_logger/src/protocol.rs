@@ -10,6 +10,8 @@
 use patina::uefi_protocol::ProtocolInterface;
 use r_efi::efi;
 
+use crate::memory_log::AdvLoggerInfo;
+
 /// C struct for the Advanced Logger protocol version 2.
 #[repr(C)]
 pub struct AdvancedLoggerProtocol {
@@ -44,8 +46,45 @@ impl AdvancedLoggerProtocol {
     /// Current version of the Advanced Logger protocol.
     pub const VERSION: u32 = 2;
 
+    /// Returns the physical address and size in bytes of the Advanced Logger memory buffer, including its header.
+    ///
+    /// ## Safety
+    ///
+    /// The protocol must describe a valid memory buffer, as it does when installed by the
+    /// [`AdvancedLoggerComponent`](crate::component::AdvancedLoggerComponent) or another conforming implementation.
+    pub unsafe fn log_buffer(&self) -> (efi::PhysicalAddress, u64) {
+        // SAFETY: The caller guarantees that `log_info` points to a valid memory log header.
+        let header = unsafe { &*(self.log_info as *const AdvLoggerInfo) };
+        (self.log_info, header.full_size() as u64)
+    }
+
     /// Creates a new instance of the Advanced Logger protocol.
     pub(crate) const fn new(write_log: AdvancedLoggerWrite, log_info: efi::PhysicalAddress) -> Self {
         AdvancedLoggerProtocol { signature: Self::SIGNATURE, version: Self::VERSION, write_log, log_info }
     }
 }
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::memory_log::AdvancedLog;
+    use alloc::boxed::Box;
+
+    extern "efiapi" fn write_log(_: *const AdvancedLoggerProtocol, _: usize, _: *const u8, _: usize) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    #[test]
+    fn test_log_buffer() {
+        let mut buffer = Box::new([0_u64; 0x200]);
+        let address = buffer.as_mut_ptr() as efi::PhysicalAddress;
+        // SAFETY: The buffer is valid for 0x1000 bytes.
+        unsafe { AdvancedLog::initialize_memory_log(address, 0x1000) }.unwrap();
+
+        let protocol = AdvancedLoggerProtocol::new(write_log, address);
+        // SAFETY: The protocol describes the memory log initialized above.
+        assert_eq!(unsafe { protocol.log_buffer() }, (address, 0x1000));
+    }
+}
@@ -0,0 +1,388 @@
+//! Boot Journal Component
+//!
+//! Produces the [`BootJournal`] service and publishes the journal to the operating system at Ready to Boot. The
+//! journal can also be read from the debugger with the `journal` monitor command.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, vec::Vec};
+use core::{fmt::Write, str::SplitWhitespace};
+use patina::{
+    BinaryGuid,
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        IntoComponent,
+        params::Commands,
+        service::{IntoService, Service, perf_timer::ArchTimerFunctionality},
+    },
+    efi_types::EfiMemoryType,
+    error::EfiError,
+    guids::EVENT_GROUP_END_OF_DXE,
+    tpl_mutex::TplMutex,
+};
+use r_efi::{efi, system::EVENT_GROUP_READY_TO_BOOT};
+
+use crate::{
+    error::JournalError,
+    journal::{EntryKind, Journal, JournalEntry, JournalQuery, Severity, milestone},
+    service::BootJournal,
+};
+
+/// GUID of the configuration table that points to the published boot journal.
+///
+/// `{a6bcd1a5-5d3e-4c2f-9a56-0b0f2c4e7d91}`
+pub const BOOT_JOURNAL_TABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xa6bcd1a5, 0x5d3e, 0x4c2f, 0x9a, 0x56, &[0x0b, 0x0f, 0x2c, 0x4e, 0x7d, 0x91]);
+
+/// Subsystem GUID used for entries recorded by the boot journal component itself.
+///
+/// `{3f1c6e0b-8d2a-4b7e-b1c4-5e9a7d20f3a8}`
+pub const BOOT_JOURNAL_SUBSYSTEM_GUID: efi::Guid =
+    efi::Guid::from_fields(0x3f1c6e0b, 0x8d2a, 0x4b7e, 0xb1, 0xc4, &[0x5e, 0x9a, 0x7d, 0x20, 0xf3, 0xa8]);
+
+/// Usage of the `journal` monitor command.
+const MONITOR_USAGE: &str = "journal [severity=<info|warning|error|fatal>] [kind=<measurement|milestone|error>] \
+[subsystem=<guid>] [since=<ticks>] [until=<ticks>] - Prints the boot journal";
+
+/// The journal read by the `journal` monitor command, which cannot capture state.
+static JOURNAL: spin::Once<&'static BootJournalImpl> = spin::Once::new();
+
+/// A component that produces the [`BootJournal`] service.
+///
+/// The journal serialized size is bounded by `capacity` bytes. Entries recorded once the journal is full are dropped
+/// and counted in the journal header.
+#[derive(IntoComponent)]
+pub struct BootJournalProvider {
+    capacity: usize,
+}
+
+impl BootJournalProvider {
+    /// Creates a new boot journal provider whose published journal is at most `capacity` bytes.
+    pub const fn new(capacity: usize) -> Self {
+        Self { capacity }
+    }
+
+    #[coverage(off)] // Requires boot services; journal behavior is tested through `JournalState`.
+    fn entry_point(
+        self,
+        boot_services: StandardBootServices,
+        timer: Service<dyn ArchTimerFunctionality>,
+        mut commands: Commands,
+    ) -> patina::error::Result<()> {
+        let boot_services: &'static StandardBootServices = Box::leak(Box::new(boot_services));
+
+        let mut journal = Journal::new(self.capacity);
+        journal.set_timestamp_frequency(timer.perf_frequency());
+
+        let journal: &'static BootJournalImpl = Box::leak(Box::new(BootJournalImpl {
+            state: TplMutex::new(boot_services, Tpl::NOTIFY, JournalState::new(journal)),
+            timer,
+            capacity: self.capacity,
+            boot_services,
+        }));
+
+        if let Err(err) =
+            journal.record_milestone(&BOOT_JOURNAL_SUBSYSTEM_GUID, milestone::JOURNAL_STARTED, "JournalStarted")
+        {
+            log::warn!("Boot Journal: Failed to record start milestone: {err}");
+        }
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(on_end_of_dxe),
+            journal,
+            &EVENT_GROUP_END_OF_DXE,
+        )?;
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(on_ready_to_boot),
+            journal,
+            &EVENT_GROUP_READY_TO_BOOT,
+        )?;
+
+        JOURNAL.call_once(|| journal);
+        patina_debugger::add_monitor_command("journal", MONITOR_USAGE, write_journal);
+
+        commands.add_service(journal);
+        log::info!("Boot Journal: Initialized with a capacity of {:#x} bytes.", self.capacity);
+
+        Ok(())
+    }
+}
+
+/// The journal and, once published, the buffer handed to the operating system.
+struct JournalState {
+    journal: Journal,
+    published: Option<&'static mut [u8]>,
+}
+
+impl JournalState {
+    const fn new(journal: Journal) -> Self {
+        Self { journal, published: None }
+    }
+
+    /// Records an entry, keeping the published buffer in sync with the journal.
+    fn record(&mut self, entry: JournalEntry) -> Result<(), JournalError> {
+        let result = self.journal.record(entry);
+        // A dropped entry still changes the header, so the published buffer is refreshed either way.
+        self.sync()?;
+        result
+    }
+
+    /// Serializes the journal into `buffer` and keeps it updated with later entries.
+    fn publish(&mut self, buffer: &'static mut [u8]) -> Result<(), JournalError> {
+        self.journal.serialize_into(buffer)?;
+        self.published = Some(buffer);
+        Ok(())
+    }
+
+    /// Returns the address and size of the published buffer, if the journal has been published.
+    fn published_region(&self) -> Option<(efi::PhysicalAddress, usize)> {
+        self.published.as_deref().map(|buffer| (buffer.as_ptr() as efi::PhysicalAddress, buffer.len()))
+    }
+
+    fn sync(&mut self) -> Result<(), JournalError> {
+        if let Some(buffer) = self.published.as_deref_mut() {
+            self.journal.serialize_into(buffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// The [`BootJournal`] service implementation.
+#[derive(IntoService)]
+#[service(dyn BootJournal)]
+struct BootJournalImpl {
+    state: TplMutex<'static, JournalState, StandardBootServices>,
+    timer: Service<dyn ArchTimerFunctionality>,
+    capacity: usize,
+    boot_services: &'static StandardBootServices,
+}
+
+impl BootJournalImpl {
+    /// Allocates the runtime buffer for the journal and installs it as a configuration table.
+    #[coverage(off)] // Requires boot services.
+    fn publish(&self) -> Result<(), EfiError> {
+        let mut state = self.state.lock();
+        if state.published.is_some() {
+            return Ok(());
+        }
+
+        let buffer = self.boot_services.allocate_pool(EfiMemoryType::RuntimeServicesData, self.capacity)?;
+        // SAFETY: `allocate_pool` returned a valid allocation of `capacity` bytes that is never freed.
+        let buffer = unsafe { core::slice::from_raw_parts_mut(buffer, self.capacity) };
+        buffer.fill(0);
+        state.publish(buffer)?;
+
+        // SAFETY: The table is the serialized journal described by `BOOT_JOURNAL_TABLE_GUID`, and the runtime
+        // allocation backing it is never freed.
+        unsafe {
+            self.boot_services.install_configuration_table(
+                &BOOT_JOURNAL_TABLE_GUID,
+                state.published.as_deref_mut().map(|buffer| buffer.as_mut_ptr()),
+            )?
+        };
+        Ok(())
+    }
+}
+
+impl BootJournal for BootJournalImpl {
+    #[coverage(off)] // Requires the timer service.
+    fn timestamp(&self) -> u64 {
+        self.timer.cpu_count()
+    }
+
+    #[coverage(off)] // Requires the timer service.
+    fn timestamp_frequency(&self) -> u64 {
+        self.timer.perf_frequency()
+    }
+
+    #[coverage(off)] // Requires boot services; tested through `JournalState`.
+    fn record(&self, entry: JournalEntry) -> Result<(), JournalError> {
+        self.state.lock().record(entry)
+    }
+
+    #[coverage(off)] // Requires boot services; tested through `Journal::query`.
+    fn query(&self, query: &JournalQuery) -> Vec<JournalEntry> {
+        self.state.lock().journal.query(query).cloned().collect()
+    }
+
+    #[coverage(off)] // Requires boot services; tested through `JournalState::published_region`.
+    fn published_region(&self) -> Option<(efi::PhysicalAddress, usize)> {
+        self.state.lock().published_region()
+    }
+}
+
+/// The `journal` monitor command: prints the entries selected by the arguments, one per line.
+#[coverage(off)] // Requires boot services; argument parsing is tested through `parse_query`.
+fn write_journal(args: &mut SplitWhitespace<'_>, out: &mut dyn Write) {
+    let query = match parse_query(args) {
+        Ok(query) => query,
+        Err(arg) => {
+            let _ = write!(out, "Invalid argument '{arg}'.\nUsage: {MONITOR_USAGE}");
+            return;
+        }
+    };
+    let Some(journal) = JOURNAL.get() else {
+        let _ = out.write_str("The boot journal is not available.");
+        return;
+    };
+    // The debugger may have stopped while the journal was locked.
+    let Ok(state) = journal.state.try_lock() else {
+        let _ = out.write_str("The boot journal is locked.");
+        return;
+    };
+
+    let _ = writeln!(out, "Timestamp frequency: {} Hz", state.journal.timestamp_frequency());
+    for entry in state.journal.query(&query) {
+        let _ = writeln!(out, "{}", entry.as_entry_ref());
+    }
+}
+
+/// Parses the `journal` monitor command arguments into a query, returning the first invalid argument on failure.
+fn parse_query<'a>(args: &mut SplitWhitespace<'a>) -> Result<JournalQuery, &'a str> {
+    let (mut start, mut end) = (0, u64::MAX);
+    let mut query = JournalQuery::new();
+    for arg in args {
+        let (name, value) = arg.split_once('=').ok_or(arg)?;
+        match name {
+            "severity" => {
+                let severity = [Severity::Info, Severity::Warning, Severity::Error, Severity::Fatal]
+                    .into_iter()
+                    .find(|severity| severity.name().eq_ignore_ascii_case(value))
+                    .ok_or(arg)?;
+                query = query.with_min_severity(severity);
+            }
+            "kind" => {
+                let kind = [EntryKind::Measurement, EntryKind::Milestone, EntryKind::Error]
+                    .into_iter()
+                    .find(|kind| kind.name().eq_ignore_ascii_case(value))
+                    .ok_or(arg)?;
+                query = query.with_kind(kind);
+            }
+            "subsystem" => query = query.with_subsystem(BinaryGuid::try_from_string(value).map_err(|_| arg)?.0),
+            "since" => start = parse_ticks(value).ok_or(arg)?,
+            "until" => end = parse_ticks(value).ok_or(arg)?,
+            _ => return Err(arg),
+        }
+    }
+    Ok(query.with_time_range(start..=end))
+}
+
+/// Parses a timestamp in decimal, or in hexadecimal with a `0x` prefix.
+fn parse_ticks(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[coverage(off)] // Requires boot services.
+extern "efiapi" fn on_end_of_dxe(event: efi::Event, journal: &'static BootJournalImpl) {
+    let _ = journal.boot_services.close_event(event);
+    if let Err(err) = journal.record_milestone(&BOOT_JOURNAL_SUBSYSTEM_GUID, milestone::END_OF_DXE, "EndOfDxe") {
+        log::warn!("Boot Journal: Failed to record End of DXE milestone: {err}");
+    }
+}
+
+#[coverage(off)] // Requires boot services.
+extern "efiapi" fn on_ready_to_boot(event: efi::Event, journal: &'static BootJournalImpl) {
+    let _ = journal.boot_services.close_event(event);
+    if let Err(err) = journal.record_milestone(&BOOT_JOURNAL_SUBSYSTEM_GUID, milestone::READY_TO_BOOT, "ReadyToBoot") {
+        log::warn!("Boot Journal: Failed to record Ready to Boot milestone: {err}");
+    }
+    match journal.publish() {
+        Ok(()) => log::info!("Boot Journal: Published journal configuration table."),
+        Err(err) => log::error!("Boot Journal: Failed to publish journal: {err:?}"),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::journal::{JournalHeader, JournalReader, Severity};
+    use alloc::vec;
+
+    fn leaked_buffer(size: usize) -> &'static mut [u8] {
+        Box::leak(vec![0u8; size].into_boxed_slice())
+    }
+
+    #[test]
+    fn test_publish_serializes_existing_entries() {
+        let mut state = JournalState::new(Journal::new(0x200));
+        state
+            .record(JournalEntry::milestone(1, BOOT_JOURNAL_SUBSYSTEM_GUID, milestone::END_OF_DXE, "EndOfDxe"))
+            .unwrap();
+        assert_eq!(state.published_region(), None);
+        state.publish(leaked_buffer(0x200)).unwrap();
+
+        let reader = JournalReader::new(state.published.as_deref().unwrap()).unwrap();
+        assert_eq!(reader.header().entry_count, 1);
+        let buffer = state.published.as_deref().unwrap();
+        assert_eq!(state.published_region(), Some((buffer.as_ptr() as efi::PhysicalAddress, 0x200)));
+    }
+
+    #[test]
+    fn test_records_after_publish_update_buffer() {
+        let mut state = JournalState::new(Journal::new(0x200));
+        state.publish(leaked_buffer(0x200)).unwrap();
+        state.record(JournalEntry::error(5, BOOT_JOURNAL_SUBSYSTEM_GUID, Severity::Error, 0x10, "late")).unwrap();
+
+        let reader = JournalReader::new(state.published.as_deref().unwrap()).unwrap();
+        let entries: alloc::vec::Vec<_> = reader.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message(), Some("late"));
+    }
+
+    #[test]
+    fn test_dropped_entries_update_published_header() {
+        let capacity = core::mem::size_of::<JournalHeader>();
+        let mut state = JournalState::new(Journal::new(capacity));
+        state.publish(leaked_buffer(capacity)).unwrap();
+        assert_eq!(
+            state.record(JournalEntry::milestone(1, BOOT_JOURNAL_SUBSYSTEM_GUID, 1, "dropped")),
+            Err(JournalError::Full)
+        );
+
+        let reader = JournalReader::new(state.published.as_deref().unwrap()).unwrap();
+        assert_eq!(reader.header().dropped_count, 1);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let subsystem = "3F1C6E0B-8D2A-4B7E-B1C4-5E9A7D20F3A8";
+        assert_eq!(parse_query(&mut "".split_whitespace()), Ok(JournalQuery::new().with_time_range(0..=u64::MAX)));
+        assert_eq!(
+            parse_query(
+                &mut alloc::format!("severity=Error kind=error subsystem={subsystem} since=0x10 until=32")
+                    .split_whitespace()
+            ),
+            Ok(JournalQuery::new()
+                .with_min_severity(Severity::Error)
+                .with_kind(EntryKind::Error)
+                .with_subsystem(BOOT_JOURNAL_SUBSYSTEM_GUID)
+                .with_time_range(16..=32))
+        );
+
+        assert_eq!(parse_query(&mut "severity=loud".split_whitespace()), Err("severity=loud"));
+        assert_eq!(parse_query(&mut "since=soon".split_whitespace()), Err("since=soon"));
+        assert_eq!(parse_query(&mut "subsystem=1234".split_whitespace()), Err("subsystem=1234"));
+        assert_eq!(parse_query(&mut "errors".split_whitespace()), Err("errors"));
+    }
+
+    #[test]
+    fn test_publish_into_small_buffer_fails() {
+        let mut state = JournalState::new(Journal::new(0x200));
+        state.record(JournalEntry::milestone(1, BOOT_JOURNAL_SUBSYSTEM_GUID, 1, "one")).unwrap();
+        assert_eq!(state.publish(leaked_buffer(8)), Err(JournalError::BufferTooSmall));
+        assert!(state.published.is_none());
+    }
+}
@@ -0,0 +1,72 @@
+//! Error types for the boot journal.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::error::EfiError;
+
+/// Errors that can occur while recording, serializing, or parsing a boot journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalError {
+    /// The journal does not have enough capacity remaining for the entry. The entry was dropped.
+    Full,
+    /// The provided buffer is too small to hold the journal.
+    BufferTooSmall,
+    /// The buffer does not start with a journal header signature.
+    InvalidSignature,
+    /// The journal was written with a newer, unsupported layout version.
+    UnsupportedVersion,
+    /// The CRC32 stored in the journal header does not match the journal contents.
+    CrcMismatch,
+    /// An entry header is inconsistent with the journal or entry sizes.
+    MalformedEntry,
+    /// The journal has not been initialized.
+    NotInitialized,
+}
+
+impl core::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            JournalError::Full => write!(f, "Journal is full"),
+            JournalError::BufferTooSmall => write!(f, "Buffer too small for journal"),
+            JournalError::InvalidSignature => write!(f, "Invalid journal signature"),
+            JournalError::UnsupportedVersion => write!(f, "Unsupported journal version"),
+            JournalError::CrcMismatch => write!(f, "Journal CRC32 mismatch"),
+            JournalError::MalformedEntry => write!(f, "Malformed journal entry"),
+            JournalError::NotInitialized => write!(f, "Journal not initialized"),
+        }
+    }
+}
+
+impl From<JournalError> for EfiError {
+    fn from(value: JournalError) -> Self {
+        match value {
+            JournalError::Full => EfiError::OutOfResources,
+            JournalError::BufferTooSmall => EfiError::BufferTooSmall,
+            JournalError::InvalidSignature | JournalError::MalformedEntry => EfiError::InvalidParameter,
+            JournalError::UnsupportedVersion => EfiError::Unsupported,
+            JournalError::CrcMismatch => EfiError::CrcError,
+            JournalError::NotInitialized => EfiError::NotStarted,
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_error_to_efi_error() {
+        assert_eq!(EfiError::from(JournalError::Full), EfiError::OutOfResources);
+        assert_eq!(EfiError::from(JournalError::BufferTooSmall), EfiError::BufferTooSmall);
+        assert_eq!(EfiError::from(JournalError::InvalidSignature), EfiError::InvalidParameter);
+        assert_eq!(EfiError::from(JournalError::MalformedEntry), EfiError::InvalidParameter);
+        assert_eq!(EfiError::from(JournalError::UnsupportedVersion), EfiError::Unsupported);
+        assert_eq!(EfiError::from(JournalError::CrcMismatch), EfiError::CrcError);
+        assert_eq!(EfiError::from(JournalError::NotInitialized), EfiError::NotStarted);
+    }
+}
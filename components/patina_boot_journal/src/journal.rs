@@ -0,0 +1,833 @@
+//! Boot journal storage, serialization, and parsing.
+//!
+//! The [`Journal`] holds entries in timestamp order and serializes them into the published journal format. The
+//! [`JournalReader`] validates and parses a serialized journal, and is usable from host tooling as well as firmware.
+//! Both select entries with a [`JournalQuery`].
+//!
+//! ## Format
+//!
+//! ```text
+//! +---------------------+
+//! | JournalHeader       |  signature, version, sizes, entry count, CRC32
+//! +---------------------+
+//! | EntryHeader         |  size, kind, severity, code, timestamp, subsystem
+//! | payload (padded)    |
+//! +---------------------+
+//! | ...                 |
+//! +---------------------+
+//! ```
+//!
+//! The header `crc32` field covers the header (with `crc32` set to zero) and all entries.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+use core::{
+    mem::{offset_of, size_of},
+    ops::{Bound, RangeBounds},
+};
+use patina::BinaryGuid;
+use r_efi::efi;
+use zerocopy::{FromBytes, IntoBytes};
+use zerocopy_derive::*;
+
+use crate::error::JournalError;
+
+/// Signature of a serialized boot journal ("BJNL").
+pub const JOURNAL_SIGNATURE: u32 = u32::from_le_bytes(*b"BJNL");
+
+/// Current version of the serialized boot journal layout.
+pub const JOURNAL_VERSION: u16 = 1;
+
+/// Alignment of each entry in a serialized journal.
+const ENTRY_ALIGNMENT: usize = 8;
+
+/// Milestone codes recorded by the boot journal component. Platform-defined milestones should use codes at or above
+/// [`PLATFORM_BASE`](milestone::PLATFORM_BASE).
+pub mod milestone {
+    /// The boot journal component was initialized.
+    pub const JOURNAL_STARTED: u32 = 1;
+    /// The End of DXE event group was signaled.
+    pub const END_OF_DXE: u32 = 2;
+    /// The Ready to Boot event group was signaled.
+    pub const READY_TO_BOOT: u32 = 3;
+    /// The boot-time memory test completed.
+    pub const MEMORY_TEST_COMPLETE: u32 = 4;
+    /// First milestone code available for platform-defined milestones.
+    pub const PLATFORM_BASE: u32 = 0x8000_0000;
+}
+
+/// The kind of a journal entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EntryKind {
+    /// A measurement event. The entry code is the PCR index, and the payload is the 32-bit event type followed by the
+    /// measured digest.
+    Measurement = 1,
+    /// A boot milestone. The entry code is the milestone code, and the payload is the UTF-8 milestone name.
+    Milestone = 2,
+    /// An error record. The entry code is the status code value, and the payload is a UTF-8 message.
+    Error = 3,
+}
+
+impl EntryKind {
+    /// Returns the name of the kind.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            EntryKind::Measurement => "Measurement",
+            EntryKind::Milestone => "Milestone",
+            EntryKind::Error => "Error",
+        }
+    }
+}
+
+impl TryFrom<u8> for EntryKind {
+    type Error = JournalError;
+
+    fn try_from(value: u8) -> Result<Self, JournalError> {
+        match value {
+            1 => Ok(EntryKind::Measurement),
+            2 => Ok(EntryKind::Milestone),
+            3 => Ok(EntryKind::Error),
+            _ => Err(JournalError::MalformedEntry),
+        }
+    }
+}
+
+/// The severity of a journal entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Severity {
+    /// Informational entry.
+    Info = 0,
+    /// Something unexpected happened, but boot can continue normally.
+    Warning = 1,
+    /// An operation failed.
+    Error = 2,
+    /// A failure that prevents boot from continuing normally.
+    Fatal = 3,
+}
+
+impl Severity {
+    /// Returns the name of the severity.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+            Severity::Fatal => "Fatal",
+        }
+    }
+}
+
+impl TryFrom<u8> for Severity {
+    type Error = JournalError;
+
+    fn try_from(value: u8) -> Result<Self, JournalError> {
+        match value {
+            0 => Ok(Severity::Info),
+            1 => Ok(Severity::Warning),
+            2 => Ok(Severity::Error),
+            3 => Ok(Severity::Fatal),
+            _ => Err(JournalError::MalformedEntry),
+        }
+    }
+}
+
+/// Header of a serialized boot journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct JournalHeader {
+    /// Must be [`JOURNAL_SIGNATURE`].
+    pub signature: u32,
+    /// Layout version of the journal.
+    pub version: u16,
+    /// Size of this header in bytes; entries start at this offset.
+    pub header_size: u16,
+    /// Number of entries following the header.
+    pub entry_count: u32,
+    /// Total size of all entries in bytes.
+    pub entries_size: u32,
+    /// Number of entries dropped because the journal was full.
+    pub dropped_count: u32,
+    /// CRC32 of the header (with this field set to zero) and all entries.
+    pub crc32: u32,
+    /// Frequency of the entry timestamps in Hz, or `0` if unknown.
+    pub timestamp_frequency: u64,
+}
+
+/// Header of a single serialized journal entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct EntryHeader {
+    /// Total size of the entry, including this header and payload padding.
+    pub size: u32,
+    /// The [`EntryKind`] of the entry.
+    pub kind: u8,
+    /// The [`Severity`] of the entry.
+    pub severity: u8,
+    /// Reserved, must be zero.
+    pub reserved: u16,
+    /// Kind-specific code. See [`EntryKind`].
+    pub code: u32,
+    /// Size of the payload in bytes, excluding padding.
+    pub data_size: u32,
+    /// Timestamp of the entry, in ticks.
+    pub timestamp: u64,
+    /// GUID of the subsystem that recorded the entry.
+    pub subsystem: [u8; 16],
+}
+
+/// Returns the serialized size of an entry with a payload of `data_size` bytes.
+const fn entry_size(data_size: usize) -> usize {
+    (size_of::<EntryHeader>() + data_size).next_multiple_of(ENTRY_ALIGNMENT)
+}
+
+/// A journal entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// The kind of the entry.
+    pub kind: EntryKind,
+    /// The severity of the entry.
+    pub severity: Severity,
+    /// GUID of the subsystem that recorded the entry.
+    pub subsystem: efi::Guid,
+    /// Kind-specific code. See [`EntryKind`].
+    pub code: u32,
+    /// Timestamp of the entry, in ticks.
+    pub timestamp: u64,
+    /// Kind-specific payload. See [`EntryKind`].
+    pub data: Vec<u8>,
+}
+
+impl JournalEntry {
+    /// Creates a measurement entry for an event of `event_type` extended into `pcr_index`.
+    pub fn measurement(timestamp: u64, subsystem: efi::Guid, pcr_index: u32, event_type: u32, digest: &[u8]) -> Self {
+        let mut data = Vec::with_capacity(size_of::<u32>() + digest.len());
+        data.extend_from_slice(&event_type.to_le_bytes());
+        data.extend_from_slice(digest);
+        Self { kind: EntryKind::Measurement, severity: Severity::Info, subsystem, code: pcr_index, timestamp, data }
+    }
+
+    /// Creates a milestone entry.
+    pub fn milestone(timestamp: u64, subsystem: efi::Guid, milestone: u32, name: &str) -> Self {
+        Self {
+            kind: EntryKind::Milestone,
+            severity: Severity::Info,
+            subsystem,
+            code: milestone,
+            timestamp,
+            data: name.as_bytes().to_vec(),
+        }
+    }
+
+    /// Creates an error entry for `status_code` with a descriptive message.
+    pub fn error(timestamp: u64, subsystem: efi::Guid, severity: Severity, status_code: u32, message: &str) -> Self {
+        Self {
+            kind: EntryKind::Error,
+            severity,
+            subsystem,
+            code: status_code,
+            timestamp,
+            data: message.as_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the size of the entry once serialized, including padding.
+    pub fn serialized_size(&self) -> usize {
+        entry_size(self.data.len())
+    }
+
+    /// Borrows the entry as a [`JournalEntryRef`].
+    pub fn as_entry_ref(&self) -> JournalEntryRef<'_> {
+        JournalEntryRef {
+            kind: self.kind,
+            severity: self.severity,
+            subsystem: self.subsystem,
+            code: self.code,
+            timestamp: self.timestamp,
+            data: &self.data,
+        }
+    }
+
+    fn header(&self) -> EntryHeader {
+        EntryHeader {
+            size: self.serialized_size() as u32,
+            kind: self.kind as u8,
+            severity: self.severity as u8,
+            reserved: 0,
+            code: self.code,
+            data_size: self.data.len() as u32,
+            timestamp: self.timestamp,
+            subsystem: *self.subsystem.as_bytes(),
+        }
+    }
+}
+
+/// A journal entry borrowed from a [`Journal`] or a serialized journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalEntryRef<'a> {
+    /// The kind of the entry.
+    pub kind: EntryKind,
+    /// The severity of the entry.
+    pub severity: Severity,
+    /// GUID of the subsystem that recorded the entry.
+    pub subsystem: efi::Guid,
+    /// Kind-specific code. See [`EntryKind`].
+    pub code: u32,
+    /// Timestamp of the entry, in ticks.
+    pub timestamp: u64,
+    /// Kind-specific payload. See [`EntryKind`].
+    pub data: &'a [u8],
+}
+
+impl JournalEntryRef<'_> {
+    /// Returns the payload as a string for milestone and error entries, if it is valid UTF-8.
+    pub fn message(&self) -> Option<&str> {
+        match self.kind {
+            EntryKind::Milestone | EntryKind::Error => core::str::from_utf8(self.data).ok(),
+            EntryKind::Measurement => None,
+        }
+    }
+
+    /// Returns the `(event_type, digest)` pair for measurement entries.
+    pub fn measurement(&self) -> Option<(u32, &[u8])> {
+        if self.kind != EntryKind::Measurement || self.data.len() < size_of::<u32>() {
+            return None;
+        }
+        let (event_type, digest) = self.data.split_at(size_of::<u32>());
+        Some((u32::from_le_bytes(event_type.try_into().ok()?), digest))
+    }
+
+    /// Creates an owned copy of the entry.
+    pub fn to_owned(&self) -> JournalEntry {
+        JournalEntry {
+            kind: self.kind,
+            severity: self.severity,
+            subsystem: self.subsystem,
+            code: self.code,
+            timestamp: self.timestamp,
+            data: self.data.to_vec(),
+        }
+    }
+}
+
+impl core::fmt::Display for JournalEntryRef<'_> {
+    /// Formats the entry as a single line: timestamp, severity, kind, subsystem, code, and payload.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:>16} {:<7} {:<11} {} {:#010X} ",
+            self.timestamp,
+            self.severity.name(),
+            self.kind.name(),
+            BinaryGuid::from(self.subsystem),
+            self.code
+        )?;
+        match (self.message(), self.measurement()) {
+            (Some(message), _) => f.write_str(message),
+            (None, Some((event_type, digest))) => write!(f, "event type {event_type:#X}, {} byte digest", digest.len()),
+            (None, None) => write!(f, "{} bytes of data", self.data.len()),
+        }
+    }
+}
+
+/// Selects journal entries by subsystem, kind, severity, and timestamp.
+///
+/// An empty query selects every entry. Each criterion added narrows the selection, so a query for errors of one
+/// subsystem since a point in boot is built as:
+///
+/// ```rust,ignore
+/// let query = JournalQuery::new()
+///     .with_subsystem(MY_SUBSYSTEM_GUID)
+///     .with_min_severity(Severity::Error)
+///     .with_time_range(since..);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalQuery {
+    subsystem: Option<efi::Guid>,
+    kind: Option<EntryKind>,
+    min_severity: Severity,
+    start: Bound<u64>,
+    end: Bound<u64>,
+}
+
+impl JournalQuery {
+    /// Creates a query that selects every entry.
+    pub const fn new() -> Self {
+        Self {
+            subsystem: None,
+            kind: None,
+            min_severity: Severity::Info,
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+        }
+    }
+
+    /// Selects only entries recorded by `subsystem`.
+    pub const fn with_subsystem(mut self, subsystem: efi::Guid) -> Self {
+        self.subsystem = Some(subsystem);
+        self
+    }
+
+    /// Selects only entries of `kind`.
+    pub const fn with_kind(mut self, kind: EntryKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Selects only entries of `severity` or worse.
+    pub const fn with_min_severity(mut self, severity: Severity) -> Self {
+        self.min_severity = severity;
+        self
+    }
+
+    /// Selects only entries with a timestamp in `range`, in ticks of the journal timestamp frequency.
+    pub fn with_time_range(mut self, range: impl RangeBounds<u64>) -> Self {
+        self.start = range.start_bound().cloned();
+        self.end = range.end_bound().cloned();
+        self
+    }
+
+    /// Returns true if the query selects `entry`.
+    pub fn matches(&self, entry: &JournalEntryRef<'_>) -> bool {
+        self.subsystem.is_none_or(|subsystem| subsystem == entry.subsystem)
+            && self.kind.is_none_or(|kind| kind == entry.kind)
+            && entry.severity >= self.min_severity
+            && (self.start, self.end).contains(&entry.timestamp)
+    }
+}
+
+impl Default for JournalQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An in-memory, timestamp-ordered boot journal with a fixed serialized size budget.
+#[derive(Debug)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+    capacity: usize,
+    serialized_size: usize,
+    dropped_count: u32,
+    timestamp_frequency: u64,
+}
+
+impl Journal {
+    /// Creates an empty journal whose serialized form may not exceed `capacity` bytes.
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity,
+            serialized_size: size_of::<JournalHeader>(),
+            dropped_count: 0,
+            timestamp_frequency: 0,
+        }
+    }
+
+    /// Sets the frequency of the entry timestamps in Hz.
+    pub fn set_timestamp_frequency(&mut self, timestamp_frequency: u64) {
+        self.timestamp_frequency = timestamp_frequency;
+    }
+
+    /// Returns the frequency of the entry timestamps in Hz, or `0` if unknown.
+    pub fn timestamp_frequency(&self) -> u64 {
+        self.timestamp_frequency
+    }
+
+    /// Records an entry, keeping entries ordered by timestamp.
+    ///
+    /// Entries with equal timestamps keep the order in which they were recorded.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`JournalError::Full`] if recording the entry would exceed the journal capacity. The entry is dropped
+    /// and counted in the journal header.
+    pub fn record(&mut self, entry: JournalEntry) -> Result<(), JournalError> {
+        let entry_size = entry.serialized_size();
+        if self.serialized_size + entry_size > self.capacity || entry.data.len() > u32::MAX as usize {
+            self.dropped_count = self.dropped_count.saturating_add(1);
+            return Err(JournalError::Full);
+        }
+
+        let index = self.entries.partition_point(|e| e.timestamp <= entry.timestamp);
+        self.entries.insert(index, entry);
+        self.serialized_size += entry_size;
+        Ok(())
+    }
+
+    /// Returns an iterator over the entries in timestamp order.
+    pub fn entries(&self) -> impl Iterator<Item = &JournalEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns an iterator over the entries selected by `query`, in timestamp order.
+    pub fn query<'a>(&'a self, query: &'a JournalQuery) -> impl Iterator<Item = &'a JournalEntry> {
+        self.entries.iter().filter(|entry| query.matches(&entry.as_entry_ref()))
+    }
+
+    /// Returns the number of entries in the journal.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the journal has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of entries dropped because the journal was full.
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped_count
+    }
+
+    /// Returns the maximum serialized size of the journal in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the current serialized size of the journal in bytes.
+    pub fn serialized_size(&self) -> usize {
+        self.serialized_size
+    }
+
+    /// Serializes the journal into `buffer`, returning the number of bytes written.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`JournalError::BufferTooSmall`] if `buffer` is smaller than [`Self::serialized_size`].
+    pub fn serialize_into(&self, buffer: &mut [u8]) -> Result<usize, JournalError> {
+        let size = self.serialized_size;
+        let buffer = buffer.get_mut(..size).ok_or(JournalError::BufferTooSmall)?;
+
+        let mut header = JournalHeader {
+            signature: JOURNAL_SIGNATURE,
+            version: JOURNAL_VERSION,
+            header_size: size_of::<JournalHeader>() as u16,
+            entry_count: self.entries.len() as u32,
+            entries_size: (size - size_of::<JournalHeader>()) as u32,
+            dropped_count: self.dropped_count,
+            crc32: 0,
+            timestamp_frequency: self.timestamp_frequency,
+        };
+
+        let (header_bytes, mut remaining) = buffer.split_at_mut(size_of::<JournalHeader>());
+        for entry in &self.entries {
+            let (entry_bytes, rest) = remaining.split_at_mut(entry.serialized_size());
+            let (entry_header, payload) = entry_bytes.split_at_mut(size_of::<EntryHeader>());
+            entry.header().write_to(entry_header).map_err(|_| JournalError::BufferTooSmall)?;
+            let (data, padding) = payload.split_at_mut(entry.data.len());
+            data.copy_from_slice(&entry.data);
+            padding.fill(0);
+            remaining = rest;
+        }
+
+        header.write_to(header_bytes).map_err(|_| JournalError::BufferTooSmall)?;
+        header.crc32 = crc32fast::hash(buffer);
+        header.write_to(&mut buffer[..size_of::<JournalHeader>()]).map_err(|_| JournalError::BufferTooSmall)?;
+
+        Ok(size)
+    }
+
+    /// Serializes the journal into a newly allocated buffer.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = vec![0u8; self.serialized_size];
+        // The buffer is sized from the journal, so serialization cannot fail.
+        let _ = self.serialize_into(&mut buffer);
+        buffer
+    }
+}
+
+/// Validates and parses a serialized boot journal.
+#[derive(Debug, Clone, Copy)]
+pub struct JournalReader<'a> {
+    header: JournalHeader,
+    entries: &'a [u8],
+}
+
+impl<'a> JournalReader<'a> {
+    /// Validates the journal header and CRC32 of the serialized journal in `buffer`.
+    ///
+    /// ## Errors
+    ///
+    /// - [`JournalError::BufferTooSmall`] if `buffer` does not contain the complete journal.
+    /// - [`JournalError::InvalidSignature`] if `buffer` does not start with a journal header.
+    /// - [`JournalError::UnsupportedVersion`] if the journal layout is newer than this reader.
+    /// - [`JournalError::CrcMismatch`] if the journal contents do not match the header CRC32.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, JournalError> {
+        let (header, _) = JournalHeader::read_from_prefix(buffer).map_err(|_| JournalError::BufferTooSmall)?;
+
+        if header.signature != JOURNAL_SIGNATURE {
+            return Err(JournalError::InvalidSignature);
+        }
+        if header.version > JOURNAL_VERSION {
+            return Err(JournalError::UnsupportedVersion);
+        }
+
+        let header_size = header.header_size as usize;
+        if header_size < size_of::<JournalHeader>() {
+            return Err(JournalError::MalformedEntry);
+        }
+        let total_size = header_size + header.entries_size as usize;
+        let journal = buffer.get(..total_size).ok_or(JournalError::BufferTooSmall)?;
+
+        let crc_offset = offset_of!(JournalHeader, crc32);
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&journal[..crc_offset]);
+        hasher.update(&[0u8; size_of::<u32>()]);
+        hasher.update(&journal[crc_offset + size_of::<u32>()..]);
+        if hasher.finalize() != header.crc32 {
+            return Err(JournalError::CrcMismatch);
+        }
+
+        Ok(Self { header, entries: &journal[header_size..] })
+    }
+
+    /// Returns the journal header.
+    pub fn header(&self) -> &JournalHeader {
+        &self.header
+    }
+
+    /// Returns an iterator over the journal entries in timestamp order.
+    pub fn iter(&self) -> JournalIter<'a> {
+        JournalIter { data: self.entries, remaining: self.header.entry_count, error: false }
+    }
+
+    /// Returns an iterator over the journal entries selected by `query`, in timestamp order. An error parsing an entry
+    /// is returned whether or not the entry would have been selected, and ends iteration.
+    pub fn query(
+        &self,
+        query: JournalQuery,
+    ) -> impl Iterator<Item = Result<JournalEntryRef<'a>, JournalError>> + use<'a> {
+        self.iter().filter(move |entry| entry.as_ref().map_or(true, |entry| query.matches(entry)))
+    }
+}
+
+/// Iterator over the entries of a serialized journal.
+///
+/// Once an error occurs, iteration stops.
+pub struct JournalIter<'a> {
+    data: &'a [u8],
+    remaining: u32,
+    error: bool,
+}
+
+impl<'a> JournalIter<'a> {
+    fn parse_entry(&mut self) -> Result<JournalEntryRef<'a>, JournalError> {
+        let (header, _) = EntryHeader::read_from_prefix(self.data).map_err(|_| JournalError::MalformedEntry)?;
+        let size = header.size as usize;
+        let data_size = header.data_size as usize;
+        if size != entry_size(data_size) || size > self.data.len() {
+            return Err(JournalError::MalformedEntry);
+        }
+
+        let entry = JournalEntryRef {
+            kind: EntryKind::try_from(header.kind)?,
+            severity: Severity::try_from(header.severity)?,
+            subsystem: efi::Guid::from_bytes(&header.subsystem),
+            code: header.code,
+            timestamp: header.timestamp,
+            data: &self.data[size_of::<EntryHeader>()..size_of::<EntryHeader>() + data_size],
+        };
+        self.data = &self.data[size..];
+        Ok(entry)
+    }
+}
+
+impl<'a> Iterator for JournalIter<'a> {
+    type Item = Result<JournalEntryRef<'a>, JournalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error || self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let result = self.parse_entry();
+        self.error = result.is_err();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const SUBSYSTEM: efi::Guid =
+        efi::Guid::from_fields(0x6a1ee763, 0xd47a, 0x43b4, 0xaa, 0xbe, &[0xef, 0x1d, 0xe2, 0xab, 0x56, 0xfc]);
+
+    #[test]
+    fn test_entries_are_ordered_by_timestamp() {
+        let mut journal = Journal::new(0x1000);
+        journal.record(JournalEntry::milestone(30, SUBSYSTEM, milestone::READY_TO_BOOT, "ReadyToBoot")).unwrap();
+        journal.record(JournalEntry::milestone(10, SUBSYSTEM, milestone::JOURNAL_STARTED, "Started")).unwrap();
+        journal.record(JournalEntry::error(20, SUBSYSTEM, Severity::Error, 0x1234, "first")).unwrap();
+        journal.record(JournalEntry::error(20, SUBSYSTEM, Severity::Warning, 0x5678, "second")).unwrap();
+
+        let codes: Vec<u32> = journal.entries().map(|e| e.code).collect();
+        assert_eq!(codes, [milestone::JOURNAL_STARTED, 0x1234, 0x5678, milestone::READY_TO_BOOT]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut journal = Journal::new(0x1000);
+        journal.set_timestamp_frequency(1_000_000);
+        journal.record(JournalEntry::measurement(5, SUBSYSTEM, 7, 0x800000E0, &[0xAA; 32])).unwrap();
+        journal.record(JournalEntry::milestone(10, SUBSYSTEM, milestone::END_OF_DXE, "EndOfDxe")).unwrap();
+        journal.record(JournalEntry::error(15, SUBSYSTEM, Severity::Fatal, 0xA0000000, "fatal error")).unwrap();
+
+        let buffer = journal.serialize();
+        assert_eq!(buffer.len(), journal.serialized_size());
+        assert_eq!(buffer.len() % ENTRY_ALIGNMENT, 0);
+
+        let reader = JournalReader::new(&buffer).unwrap();
+        assert_eq!(reader.header().entry_count, 3);
+        assert_eq!(reader.header().timestamp_frequency, 1_000_000);
+
+        let entries: Vec<JournalEntryRef> = reader.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(entries.len(), 3);
+        for (parsed, original) in entries.iter().zip(journal.entries()) {
+            assert_eq!(*parsed, original.as_entry_ref());
+            assert_eq!(parsed.to_owned(), *original);
+        }
+
+        assert_eq!(entries[0].measurement(), Some((0x800000E0, &[0xAA; 32][..])));
+        assert_eq!(entries[0].message(), None);
+        assert_eq!(entries[1].message(), Some("EndOfDxe"));
+        assert_eq!(entries[2].message(), Some("fatal error"));
+        assert_eq!(entries[2].severity, Severity::Fatal);
+    }
+
+    #[test]
+    fn test_full_journal_drops_entries() {
+        let capacity = size_of::<JournalHeader>() + entry_size(4);
+        let mut journal = Journal::new(capacity);
+        journal.record(JournalEntry::milestone(1, SUBSYSTEM, 1, "one")).unwrap();
+        assert_eq!(journal.record(JournalEntry::milestone(2, SUBSYSTEM, 2, "two")), Err(JournalError::Full));
+        assert_eq!(journal.len(), 1);
+        assert_eq!(journal.dropped_count(), 1);
+
+        let buffer = journal.serialize();
+        assert_eq!(buffer.len(), capacity);
+        assert_eq!(JournalReader::new(&buffer).unwrap().header().dropped_count, 1);
+    }
+
+    #[test]
+    fn test_serialize_into_small_buffer() {
+        let mut journal = Journal::new(0x1000);
+        journal.record(JournalEntry::milestone(1, SUBSYSTEM, 1, "one")).unwrap();
+        let mut buffer = [0u8; size_of::<JournalHeader>()];
+        assert_eq!(journal.serialize_into(&mut buffer), Err(JournalError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_reader_detects_corruption() {
+        let mut journal = Journal::new(0x1000);
+        journal.record(JournalEntry::milestone(1, SUBSYSTEM, 1, "one")).unwrap();
+        let mut buffer = journal.serialize();
+
+        let last = buffer.len() - 1;
+        buffer[last] ^= 0xFF;
+        assert_eq!(JournalReader::new(&buffer).err(), Some(JournalError::CrcMismatch));
+
+        buffer[0] = 0;
+        assert_eq!(JournalReader::new(&buffer).err(), Some(JournalError::InvalidSignature));
+
+        assert_eq!(JournalReader::new(&buffer[..4]).err(), Some(JournalError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_reader_rejects_newer_version() {
+        let journal = Journal::new(0x1000);
+        let mut buffer = journal.serialize();
+        buffer[offset_of!(JournalHeader, version)] = (JOURNAL_VERSION + 1) as u8;
+        assert_eq!(JournalReader::new(&buffer).err(), Some(JournalError::UnsupportedVersion));
+    }
+
+    #[test]
+    fn test_reader_stops_on_malformed_entry() {
+        let mut journal = Journal::new(0x1000);
+        journal.record(JournalEntry::milestone(1, SUBSYSTEM, 1, "one")).unwrap();
+        journal.record(JournalEntry::milestone(2, SUBSYSTEM, 2, "two")).unwrap();
+        let mut buffer = journal.serialize();
+
+        // Corrupt the kind of the first entry and fix up the CRC so only the entry is invalid.
+        buffer[size_of::<JournalHeader>() + offset_of!(EntryHeader, kind)] = 0xFF;
+        let crc_offset = offset_of!(JournalHeader, crc32);
+        buffer[crc_offset..crc_offset + 4].fill(0);
+        let crc = crc32fast::hash(&buffer);
+        buffer[crc_offset..crc_offset + 4].copy_from_slice(&crc.to_le_bytes());
+
+        let reader = JournalReader::new(&buffer).unwrap();
+        let mut iter = reader.iter();
+        assert_eq!(iter.next(), Some(Err(JournalError::MalformedEntry)));
+        assert_eq!(iter.next(), None);
+    }
+
+    const OTHER_SUBSYSTEM: efi::Guid =
+        efi::Guid::from_fields(0x1d9c3f2e, 0x0b4a, 0x4c8e, 0x9f, 0x21, &[0x6e, 0x3a, 0x58, 0x0c, 0x7b, 0xd4]);
+
+    fn query_journal() -> Journal {
+        let mut journal = Journal::new(0x1000);
+        journal.record(JournalEntry::milestone(10, SUBSYSTEM, milestone::JOURNAL_STARTED, "Started")).unwrap();
+        journal.record(JournalEntry::error(20, SUBSYSTEM, Severity::Warning, 0x1, "warning")).unwrap();
+        journal.record(JournalEntry::error(30, OTHER_SUBSYSTEM, Severity::Error, 0x2, "error")).unwrap();
+        journal.record(JournalEntry::measurement(40, OTHER_SUBSYSTEM, 7, 0x800000E0, &[0xAA; 32])).unwrap();
+        journal.record(JournalEntry::error(50, SUBSYSTEM, Severity::Fatal, 0x3, "fatal")).unwrap();
+        journal
+    }
+
+    fn codes<'a>(entries: impl Iterator<Item = &'a JournalEntry>) -> Vec<u32> {
+        entries.map(|entry| entry.code).collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let journal = query_journal();
+
+        assert_eq!(journal.query(&JournalQuery::new()).count(), 5);
+        assert_eq!(codes(journal.query(&JournalQuery::new().with_min_severity(Severity::Error))), [0x2, 0x3]);
+        assert_eq!(codes(journal.query(&JournalQuery::new().with_subsystem(OTHER_SUBSYSTEM))), [0x2, 7]);
+        assert_eq!(codes(journal.query(&JournalQuery::new().with_kind(EntryKind::Measurement))), [7]);
+        assert_eq!(codes(journal.query(&JournalQuery::new().with_time_range(20..40))), [0x1, 0x2]);
+        assert_eq!(
+            codes(journal.query(&JournalQuery::new().with_time_range(..=20))),
+            [milestone::JOURNAL_STARTED, 0x1]
+        );
+
+        let query =
+            JournalQuery::new().with_subsystem(SUBSYSTEM).with_min_severity(Severity::Warning).with_time_range(30..);
+        assert_eq!(codes(journal.query(&query)), [0x3]);
+    }
+
+    #[test]
+    fn test_reader_query() {
+        let buffer = query_journal().serialize();
+        let reader = JournalReader::new(&buffer).unwrap();
+
+        let query = JournalQuery::new().with_min_severity(Severity::Warning).with_time_range(..50);
+        let entries: Vec<JournalEntryRef> = reader.query(query).collect::<Result<_, _>>().unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.code).collect::<Vec<_>>(), [0x1, 0x2]);
+    }
+
+    #[test]
+    fn test_entry_display() {
+        let journal = query_journal();
+        let lines: Vec<alloc::string::String> =
+            journal.entries().map(|entry| alloc::format!("{}", entry.as_entry_ref())).collect();
+
+        assert_eq!(
+            lines[1],
+            "              20 Warning Error       6A1EE763-D47A-43B4-AABE-EF1DE2AB56FC 0x00000001 warning"
+        );
+        assert!(lines[3].ends_with(
+            " Info    Measurement 1D9C3F2E-0B4A-4C8E-9F21-6E3A580C7BD4 0x00000007 event type 0x800000E0, 32 byte digest"
+        ));
+    }
+}
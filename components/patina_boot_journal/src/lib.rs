@@ -0,0 +1,47 @@
+//! A unified, timestamp-ordered firmware boot journal.
+//!
+//! The boot journal collects measurement events, boot milestones, and error records from every subsystem into a
+//! single journal ordered by timestamp. The journal is published to the operating system at Ready to Boot as a
+//! CRC32-protected buffer pointed to by the [`BOOT_JOURNAL_TABLE_GUID`](component::BOOT_JOURNAL_TABLE_GUID)
+//! configuration table.
+//!
+//! ## Integration Example
+//!
+//! ```rust,ignore
+//! Core::default()
+//!  // ...
+//!  .with_component(patina_boot_journal::component::BootJournalProvider::new(0x10000))
+//!  .start()
+//!  .unwrap();
+//! ```
+//!
+//! Components record entries through the [`BootJournal`](service::BootJournal) service:
+//!
+//! ```rust,ignore
+//! fn entry_point(journal: Service<dyn BootJournal>) -> patina::error::Result<()> {
+//!     journal.record_milestone(&MY_SUBSYSTEM_GUID, milestone::PLATFORM_BASE, "PlatformInitDone")?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! and read them back with a [`JournalQuery`](journal::JournalQuery):
+//!
+//! ```rust,ignore
+//! let errors = journal.query(&JournalQuery::new().with_min_severity(Severity::Error));
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg_attr(not(test), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+pub mod error;
+pub mod journal;
+pub mod service;
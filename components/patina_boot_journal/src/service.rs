@@ -0,0 +1,70 @@
+//! The boot journal service.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use r_efi::efi;
+
+use crate::{
+    error::JournalError,
+    journal::{JournalEntry, JournalQuery, Severity},
+};
+
+/// A service for recording entries into the boot journal and reading them back.
+///
+/// Components should consume this service as `Service<dyn BootJournal>` and record entries with the provided helper
+/// methods, which timestamp the entry with [`BootJournal::timestamp`]. Late-boot components, such as a diagnostics
+/// agent deciding whether to collect logs, select the entries recorded so far with [`BootJournal::query`].
+pub trait BootJournal {
+    /// Returns the current timestamp, in ticks of the journal timestamp frequency.
+    fn timestamp(&self) -> u64;
+
+    /// Returns the frequency of journal timestamps, in Hz.
+    fn timestamp_frequency(&self) -> u64;
+
+    /// Records an entry into the journal.
+    ///
+    /// Entries do not need to be recorded in timestamp order; entries imported from earlier boot phases may be
+    /// recorded with their original timestamps.
+    fn record(&self, entry: JournalEntry) -> Result<(), JournalError>;
+
+    /// Returns a copy of the entries selected by `query`, in timestamp order.
+    fn query(&self, query: &JournalQuery) -> Vec<JournalEntry>;
+
+    /// Returns the address and size of the buffer the journal is published to, once it has been published at Ready to
+    /// Boot.
+    fn published_region(&self) -> Option<(efi::PhysicalAddress, usize)> {
+        None
+    }
+
+    /// Records a boot milestone.
+    fn record_milestone(&self, subsystem: &efi::Guid, milestone: u32, name: &str) -> Result<(), JournalError> {
+        self.record(JournalEntry::milestone(self.timestamp(), *subsystem, milestone, name))
+    }
+
+    /// Records an error with a status code and descriptive message.
+    fn record_error(
+        &self,
+        subsystem: &efi::Guid,
+        severity: Severity,
+        status_code: u32,
+        message: &str,
+    ) -> Result<(), JournalError> {
+        self.record(JournalEntry::error(self.timestamp(), *subsystem, severity, status_code, message))
+    }
+
+    /// Records a measurement of `digest` extended into `pcr_index`.
+    fn record_measurement(
+        &self,
+        subsystem: &efi::Guid,
+        pcr_index: u32,
+        event_type: u32,
+        digest: &[u8],
+    ) -> Result<(), JournalError> {
+        self.record(JournalEntry::measurement(self.timestamp(), *subsystem, pcr_index, event_type, digest))
+    }
+}
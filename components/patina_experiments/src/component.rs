@@ -0,0 +1,157 @@
+//! Experiment Flags Component
+//!
+//! Produces the [`ExperimentFlags`] service. The experiment settings are read once the variable services can write
+//! non-volatile variables, and the boot is recorded as successful at Ready to Boot.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, vec::Vec};
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{IntoComponent, params::Commands, service::IntoService},
+    error::EfiError,
+    guids::VARIABLE_WRITE_ARCH_PROTOCOL,
+    runtime_services::{
+        RuntimeServices, StandardRuntimeServices,
+        variables::{self, VariableAttributes},
+    },
+    tpl_mutex::TplMutex,
+};
+use r_efi::{efi, system::EVENT_GROUP_READY_TO_BOOT};
+
+use crate::{
+    experiments::{EXPERIMENT_VARIABLE_GUID, Experiments, VariableStore},
+    service::{ExperimentFlags, ExperimentState},
+};
+
+/// Attributes of the experiment variables.
+const VARIABLE_ATTRIBUTES: VariableAttributes = VariableAttributes::from_bits(
+    efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS,
+)
+.unwrap();
+
+/// A component that produces the [`ExperimentFlags`] service.
+///
+/// Only the experiments registered with [`Self::with_experiment`] are tracked; every other experiment is disabled.
+/// Until the variable services can write non-volatile variables, every experiment is disabled, so subsystems that are
+/// set up before then always take their existing path.
+#[derive(IntoComponent, Default)]
+pub struct ExperimentFlagsProvider {
+    experiments: Vec<(efi::Guid, bool)>,
+}
+
+impl ExperimentFlagsProvider {
+    /// Creates a new experiment flags provider without experiments.
+    pub const fn new() -> Self {
+        Self { experiments: Vec::new() }
+    }
+
+    /// Registers `experiment`, which is enabled if no setting is stored for it and `enabled_by_default` is `true`.
+    pub fn with_experiment(mut self, experiment: efi::Guid, enabled_by_default: bool) -> Self {
+        self.experiments.retain(|(guid, _)| *guid != experiment);
+        self.experiments.push((experiment, enabled_by_default));
+        self
+    }
+
+    #[coverage(off)] // Requires boot services; experiment behavior is tested through `Experiments`.
+    fn entry_point(
+        self,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+        mut commands: Commands,
+    ) -> patina::error::Result<()> {
+        let boot_services: &'static StandardBootServices = Box::leak(Box::new(boot_services));
+        let flags: &'static ExperimentFlagsImpl = Box::leak(Box::new(ExperimentFlagsImpl {
+            experiments: TplMutex::new(boot_services, Tpl::NOTIFY, Experiments::new(self.experiments)),
+            runtime_services,
+            boot_services,
+        }));
+
+        let event =
+            boot_services.create_event(EventType::NOTIFY_SIGNAL, Tpl::CALLBACK, Some(on_variable_write), flags)?;
+        boot_services.register_protocol_notify(&VARIABLE_WRITE_ARCH_PROTOCOL, event)?;
+        // The protocol may already be installed, in which case the notification never fires.
+        boot_services.signal_event(event)?;
+
+        boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(on_ready_to_boot),
+            flags,
+            &EVENT_GROUP_READY_TO_BOOT,
+        )?;
+
+        commands.add_service(flags);
+        Ok(())
+    }
+}
+
+/// The [`ExperimentFlags`] service implementation.
+#[derive(IntoService)]
+#[service(dyn ExperimentFlags)]
+struct ExperimentFlagsImpl {
+    experiments: TplMutex<'static, Experiments, StandardBootServices>,
+    runtime_services: StandardRuntimeServices,
+    boot_services: &'static StandardBootServices,
+}
+
+impl ExperimentFlags for ExperimentFlagsImpl {
+    #[coverage(off)] // Requires boot services; tested through `Experiments::state`.
+    fn state(&self, experiment: &efi::Guid) -> ExperimentState {
+        self.experiments.lock().state(experiment)
+    }
+}
+
+/// Stores the experiment variables with the UEFI variable services.
+struct RuntimeVariableStore<'a, R: RuntimeServices>(&'a R);
+
+impl<R: RuntimeServices> VariableStore for RuntimeVariableStore<'_, R> {
+    #[coverage(off)] // Requires runtime services.
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, efi::Status> {
+        match variables::get_variable_bytes(self.0, name, &EXPERIMENT_VARIABLE_GUID) {
+            Ok((data, _)) => Ok(Some(data)),
+            Err(EfiError::NotFound) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    #[coverage(off)] // Requires runtime services.
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), efi::Status> {
+        Ok(variables::set_variable(self.0, name, &EXPERIMENT_VARIABLE_GUID, VARIABLE_ATTRIBUTES, data)?)
+    }
+
+    #[coverage(off)] // Requires runtime services.
+    fn delete(&self, name: &str) -> Result<(), efi::Status> {
+        match variables::delete_variable(self.0, name, &EXPERIMENT_VARIABLE_GUID) {
+            Ok(()) | Err(EfiError::NotFound) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+#[coverage(off)] // Requires boot services.
+extern "efiapi" fn on_variable_write(event: efi::Event, flags: &'static ExperimentFlagsImpl) {
+    // SAFETY: The protocol interface is not dereferenced; only its presence is checked.
+    if unsafe { flags.boot_services.locate_protocol_unchecked(&VARIABLE_WRITE_ARCH_PROTOCOL, core::ptr::null_mut()) }
+        .is_err()
+    {
+        return;
+    }
+    let _ = flags.boot_services.close_event(event);
+
+    if let Err(status) = flags.experiments.lock().resolve(&RuntimeVariableStore(&flags.runtime_services)) {
+        log::error!("Experiments: Failed to resolve experiments, all are disabled: {status:?}");
+    }
+}
+
+#[coverage(off)] // Requires boot services.
+extern "efiapi" fn on_ready_to_boot(event: efi::Event, flags: &'static ExperimentFlagsImpl) {
+    let _ = flags.boot_services.close_event(event);
+    if let Err(status) = flags.experiments.lock().boot_succeeded(&RuntimeVariableStore(&flags.runtime_services)) {
+        log::error!("Experiments: Failed to record the successful boot: {status:?}");
+    }
+}
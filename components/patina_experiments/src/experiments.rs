@@ -0,0 +1,348 @@
+//! Experiment settings and the per-boot decisions made from them.
+//!
+//! The settings are kept in three variables under [`EXPERIMENT_VARIABLE_GUID`]:
+//!
+//! - [`FLAGS_VARIABLE_NAME`] holds the persistent setting of each experiment.
+//! - [`OVERRIDE_VARIABLE_NAME`] holds settings that apply to the next boot only, for example to try an experiment
+//!   across a warm reset. It is deleted when it is read.
+//! - [`PENDING_VARIABLE_NAME`] lists the experiments enabled on the boot in progress. It is deleted once the boot
+//!   succeeds, so finding it on the next boot means that boot failed, and the experiments it lists fall back to
+//!   disabled.
+//!
+//! The settings variables are arrays of [`RECORD_SIZE`]-byte records: the experiment GUID followed by a [`Setting`]
+//! byte. The pending variable is an array of experiment GUIDs.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use patina::Guid;
+use r_efi::efi;
+
+use crate::service::ExperimentState;
+
+/// Vendor GUID of the experiment variables.
+///
+/// `{b3c1e6a2-4f5d-4e8b-9a17-6d2c0f8e5b43}`
+pub const EXPERIMENT_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xb3c1e6a2, 0x4f5d, 0x4e8b, 0x9a, 0x17, &[0x6d, 0x2c, 0x0f, 0x8e, 0x5b, 0x43]);
+
+/// Name of the variable holding the persistent experiment settings.
+pub const FLAGS_VARIABLE_NAME: &str = "ExperimentFlags";
+
+/// Name of the variable holding the experiment settings for the next boot only.
+pub const OVERRIDE_VARIABLE_NAME: &str = "ExperimentOverride";
+
+/// Name of the variable listing the experiments enabled on the boot in progress.
+pub const PENDING_VARIABLE_NAME: &str = "ExperimentPending";
+
+/// Size of a record in the settings variables.
+pub const RECORD_SIZE: usize = size_of::<efi::Guid>() + 1;
+
+/// The setting of an experiment, as stored in the settings variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Setting {
+    /// The experiment is disabled.
+    Disabled = 0,
+    /// The experiment is enabled.
+    Enabled = 1,
+    /// The experiment was disabled because a boot with it enabled failed. Not valid in the override variable.
+    FellBack = 2,
+}
+
+impl TryFrom<u8> for Setting {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Disabled),
+            1 => Ok(Self::Enabled),
+            2 => Ok(Self::FellBack),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Access to the experiment variables.
+pub(crate) trait VariableStore {
+    /// Reads a variable, returning `None` if it does not exist.
+    fn read(&self, name: &str) -> Result<Option<Vec<u8>>, efi::Status>;
+
+    /// Writes a variable.
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), efi::Status>;
+
+    /// Deletes a variable. Deleting a variable that does not exist succeeds.
+    fn delete(&self, name: &str) -> Result<(), efi::Status>;
+}
+
+/// The experiments registered by the platform and, once resolved, their state for this boot.
+pub(crate) struct Experiments {
+    defaults: Vec<(efi::Guid, bool)>,
+    states: Option<Vec<(efi::Guid, ExperimentState)>>,
+}
+
+impl Experiments {
+    /// Creates the experiments from their GUIDs and platform defaults.
+    pub(crate) const fn new(defaults: Vec<(efi::Guid, bool)>) -> Self {
+        Self { defaults, states: None }
+    }
+
+    /// Returns `true` once the state of every experiment has been decided.
+    pub(crate) fn is_resolved(&self) -> bool {
+        self.states.is_some()
+    }
+
+    /// Returns the state of `experiment` for this boot.
+    pub(crate) fn state(&self, experiment: &efi::Guid) -> ExperimentState {
+        match &self.states {
+            None => ExperimentState::Unresolved,
+            Some(states) => {
+                states.iter().find(|(guid, _)| guid == experiment).map_or(ExperimentState::Unknown, |(_, state)| *state)
+            }
+        }
+    }
+
+    /// Decides the state of every experiment for this boot from the settings in `store`.
+    ///
+    /// Experiments listed as pending by a boot that failed fall back to disabled, and the fall back is stored. The
+    /// override variable is consumed. The experiments enabled on this boot are stored as pending before this
+    /// succeeds; if that fails, the experiments stay unresolved, and so disabled, as a failure of this boot could not
+    /// be detected.
+    pub(crate) fn resolve(&mut self, store: &impl VariableStore) -> Result<(), efi::Status> {
+        if self.is_resolved() {
+            return Ok(());
+        }
+
+        let mut settings = read_settings(store, FLAGS_VARIABLE_NAME)?;
+        let overrides = read_settings(store, OVERRIDE_VARIABLE_NAME)?;
+        let failed = read_pending(store)?;
+
+        let mut settings_changed = false;
+        let mut states = Vec::with_capacity(self.defaults.len());
+        for &(experiment, default) in &self.defaults {
+            let state = if failed.contains(&experiment) {
+                log::warn!("Experiments: {} was enabled on a failed boot, falling back.", Guid::from_ref(&experiment));
+                set_setting(&mut settings, experiment, Setting::FellBack);
+                settings_changed = true;
+                ExperimentState::FellBack
+            } else if let Some(setting) = find_setting(&overrides, &experiment) {
+                ExperimentState::Overridden(setting == Setting::Enabled)
+            } else {
+                match find_setting(&settings, &experiment) {
+                    Some(Setting::Enabled) => ExperimentState::Configured(true),
+                    Some(Setting::Disabled) => ExperimentState::Configured(false),
+                    Some(Setting::FellBack) => ExperimentState::FellBack,
+                    None => ExperimentState::Default(default),
+                }
+            };
+            states.push((experiment, state));
+        }
+
+        if settings_changed {
+            store.write(FLAGS_VARIABLE_NAME, &encode_settings(&settings))?;
+        }
+        if !overrides.is_empty() {
+            store.delete(OVERRIDE_VARIABLE_NAME)?;
+        }
+
+        let pending: Vec<u8> =
+            states.iter().filter(|(_, state)| state.is_enabled()).flat_map(|(guid, _)| *guid.as_bytes()).collect();
+        if pending.is_empty() {
+            store.delete(PENDING_VARIABLE_NAME)?;
+        } else {
+            store.write(PENDING_VARIABLE_NAME, &pending)?;
+        }
+
+        for (experiment, state) in states.iter().filter(|(_, state)| state.is_enabled()) {
+            log::info!("Experiments: {} is enabled ({state:?}).", Guid::from_ref(experiment));
+        }
+        self.states = Some(states);
+        Ok(())
+    }
+
+    /// Records that this boot succeeded, so the experiments enabled on it do not fall back.
+    pub(crate) fn boot_succeeded(&self, store: &impl VariableStore) -> Result<(), efi::Status> {
+        if self.is_resolved() { store.delete(PENDING_VARIABLE_NAME) } else { Ok(()) }
+    }
+}
+
+/// Reads the records of a settings variable, skipping records with an unknown setting.
+fn read_settings(store: &impl VariableStore, name: &str) -> Result<Vec<(efi::Guid, Setting)>, efi::Status> {
+    let data = store.read(name)?.unwrap_or_default();
+    Ok(data
+        .chunks_exact(RECORD_SIZE)
+        .filter_map(|record| {
+            let (guid, setting) = record.split_first_chunk::<16>()?;
+            match Setting::try_from(setting[0]) {
+                Ok(setting) => Some((efi::Guid::from_bytes(guid), setting)),
+                Err(value) => {
+                    log::warn!("Experiments: Ignoring unknown setting {value:#x} in {name}.");
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+/// Reads the experiments listed as pending by the previous boot.
+fn read_pending(store: &impl VariableStore) -> Result<Vec<efi::Guid>, efi::Status> {
+    let data = store.read(PENDING_VARIABLE_NAME)?.unwrap_or_default();
+    Ok(data.chunks_exact(size_of::<efi::Guid>()).map(|guid| efi::Guid::from_bytes(guid.try_into().unwrap())).collect())
+}
+
+fn find_setting(settings: &[(efi::Guid, Setting)], experiment: &efi::Guid) -> Option<Setting> {
+    settings.iter().find(|(guid, _)| guid == experiment).map(|(_, setting)| *setting)
+}
+
+fn set_setting(settings: &mut Vec<(efi::Guid, Setting)>, experiment: efi::Guid, setting: Setting) {
+    match settings.iter_mut().find(|(guid, _)| *guid == experiment) {
+        Some(record) => record.1 = setting,
+        None => settings.push((experiment, setting)),
+    }
+}
+
+fn encode_settings(settings: &[(efi::Guid, Setting)]) -> Vec<u8> {
+    settings.iter().flat_map(|(guid, setting)| guid.as_bytes().iter().copied().chain([*setting as u8])).collect()
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::{collections::BTreeMap, string::String};
+    use core::cell::RefCell;
+
+    const NEW_ALLOCATOR: efi::Guid =
+        efi::Guid::from_fields(0x1f0e8a2d, 0x6b3c, 0x4d59, 0x8e, 0x71, &[0x2a, 0x9c, 0x5d, 0x04, 0xb6, 0xe3]);
+    const PARALLEL_EXTRACTION: efi::Guid =
+        efi::Guid::from_fields(0x7c4d2b91, 0x0e5a, 0x4f36, 0xb2, 0x8d, &[0x61, 0xf3, 0x9e, 0x27, 0xc0, 0x5a]);
+
+    #[derive(Default)]
+    struct MemoryStore {
+        variables: RefCell<BTreeMap<String, Vec<u8>>>,
+        read_only: bool,
+    }
+
+    impl MemoryStore {
+        fn get(&self, name: &str) -> Option<Vec<u8>> {
+            self.variables.borrow().get(name).cloned()
+        }
+    }
+
+    impl VariableStore for MemoryStore {
+        fn read(&self, name: &str) -> Result<Option<Vec<u8>>, efi::Status> {
+            Ok(self.get(name))
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> Result<(), efi::Status> {
+            if self.read_only {
+                return Err(efi::Status::WRITE_PROTECTED);
+            }
+            self.variables.borrow_mut().insert(name.into(), data.to_vec());
+            Ok(())
+        }
+
+        fn delete(&self, name: &str) -> Result<(), efi::Status> {
+            if self.read_only {
+                return Err(efi::Status::WRITE_PROTECTED);
+            }
+            self.variables.borrow_mut().remove(name);
+            Ok(())
+        }
+    }
+
+    fn experiments() -> Experiments {
+        Experiments::new(vec![(NEW_ALLOCATOR, false), (PARALLEL_EXTRACTION, true)])
+    }
+
+    /// Simulates a boot that resolves the experiments and optionally reaches Ready to Boot.
+    fn boot(store: &MemoryStore, succeeds: bool) -> Experiments {
+        let mut experiments = experiments();
+        experiments.resolve(store).unwrap();
+        if succeeds {
+            experiments.boot_succeeded(store).unwrap();
+        }
+        experiments
+    }
+
+    #[test]
+    fn test_defaults_and_settings() {
+        let store = MemoryStore::default();
+        let mut experiments = experiments();
+        assert_eq!(experiments.state(&NEW_ALLOCATOR), ExperimentState::Unresolved);
+        experiments.resolve(&store).unwrap();
+        assert_eq!(experiments.state(&NEW_ALLOCATOR), ExperimentState::Default(false));
+        assert_eq!(experiments.state(&PARALLEL_EXTRACTION), ExperimentState::Default(true));
+        assert_eq!(experiments.state(&efi::Guid::from_bytes(&[0; 16])), ExperimentState::Unknown);
+        assert_eq!(store.get(PENDING_VARIABLE_NAME), Some(PARALLEL_EXTRACTION.as_bytes().to_vec()));
+
+        store.write(FLAGS_VARIABLE_NAME, &encode_settings(&[(NEW_ALLOCATOR, Setting::Enabled)])).unwrap();
+        let experiments = boot(&store, true);
+        assert!(experiments.state(&NEW_ALLOCATOR).is_enabled());
+        assert_eq!(experiments.state(&NEW_ALLOCATOR), ExperimentState::Configured(true));
+        assert_eq!(store.get(PENDING_VARIABLE_NAME), None);
+    }
+
+    #[test]
+    fn test_failed_boot_falls_back() {
+        let store = MemoryStore::default();
+        store.write(FLAGS_VARIABLE_NAME, &encode_settings(&[(NEW_ALLOCATOR, Setting::Enabled)])).unwrap();
+
+        // The boot never reaches Ready to Boot, so the next boot disables both experiments.
+        boot(&store, false);
+        let experiments = boot(&store, true);
+        assert_eq!(experiments.state(&NEW_ALLOCATOR), ExperimentState::FellBack);
+        assert_eq!(experiments.state(&PARALLEL_EXTRACTION), ExperimentState::FellBack);
+        assert_eq!(
+            read_settings(&store, FLAGS_VARIABLE_NAME).unwrap(),
+            [(NEW_ALLOCATOR, Setting::FellBack), (PARALLEL_EXTRACTION, Setting::FellBack)]
+        );
+
+        // The fall back persists until the experiment is configured again.
+        assert_eq!(boot(&store, true).state(&NEW_ALLOCATOR), ExperimentState::FellBack);
+        store.write(FLAGS_VARIABLE_NAME, &encode_settings(&[(NEW_ALLOCATOR, Setting::Enabled)])).unwrap();
+        assert_eq!(boot(&store, true).state(&NEW_ALLOCATOR), ExperimentState::Configured(true));
+    }
+
+    #[test]
+    fn test_override_applies_to_one_boot() {
+        let store = MemoryStore::default();
+        let overrides = encode_settings(&[(NEW_ALLOCATOR, Setting::Enabled), (PARALLEL_EXTRACTION, Setting::Disabled)]);
+        store.write(OVERRIDE_VARIABLE_NAME, &overrides).unwrap();
+
+        let experiments = boot(&store, true);
+        assert_eq!(experiments.state(&NEW_ALLOCATOR), ExperimentState::Overridden(true));
+        assert_eq!(experiments.state(&PARALLEL_EXTRACTION), ExperimentState::Overridden(false));
+        assert_eq!(store.get(OVERRIDE_VARIABLE_NAME), None);
+        assert_eq!(boot(&store, true).state(&NEW_ALLOCATOR), ExperimentState::Default(false));
+
+        // An overridden experiment that fails its boot falls back as well.
+        store.write(OVERRIDE_VARIABLE_NAME, &overrides).unwrap();
+        boot(&store, false);
+        assert_eq!(boot(&store, true).state(&NEW_ALLOCATOR), ExperimentState::FellBack);
+    }
+
+    #[test]
+    fn test_unrecorded_experiments_stay_disabled() {
+        let store = MemoryStore { read_only: true, ..Default::default() };
+        let mut experiments = experiments();
+        assert_eq!(experiments.resolve(&store), Err(efi::Status::WRITE_PROTECTED));
+        assert_eq!(experiments.state(&PARALLEL_EXTRACTION), ExperimentState::Unresolved);
+    }
+
+    #[test]
+    fn test_unknown_settings_are_ignored() {
+        let store = MemoryStore::default();
+        let mut data = encode_settings(&[(NEW_ALLOCATOR, Setting::Enabled)]);
+        data.extend_from_slice(PARALLEL_EXTRACTION.as_bytes());
+        data.push(0x7f);
+        data.push(0xaa);
+        store.write(FLAGS_VARIABLE_NAME, &data).unwrap();
+        assert_eq!(read_settings(&store, FLAGS_VARIABLE_NAME).unwrap(), [(NEW_ALLOCATOR, Setting::Enabled)]);
+        assert_eq!(boot(&store, true).state(&PARALLEL_EXTRACTION), ExperimentState::Default(true));
+    }
+}
@@ -0,0 +1,45 @@
+//! Per-boot experiment flags.
+//!
+//! Experiment flags let platform teams gate new subsystems, such as a new pool allocator or parallel section
+//! extraction, per boot. Each experiment is identified by a GUID and decided once per boot from a persistent setting,
+//! a one-time override for the next boot, or the platform default. An experiment that was enabled on a boot that did
+//! not reach Ready to Boot falls back to disabled on the next boot, so a faulty subsystem cannot keep a system from
+//! booting. See [`experiments`] for the variables that hold the settings.
+//!
+//! ## Integration Example
+//!
+//! ```rust,ignore
+//! Core::default()
+//!  // ...
+//!  .with_component(
+//!      patina_experiments::component::ExperimentFlagsProvider::new().with_experiment(NEW_ALLOCATOR_GUID, false),
+//!  )
+//!  .start()
+//!  .unwrap();
+//! ```
+//!
+//! Components check their experiment through the [`ExperimentFlags`](service::ExperimentFlags) service:
+//!
+//! ```rust,ignore
+//! fn entry_point(experiments: Service<dyn ExperimentFlags>) -> patina::error::Result<()> {
+//!     if experiments.is_enabled(&NEW_ALLOCATOR_GUID) {
+//!         // Set up the new subsystem.
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg_attr(not(test), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+pub mod experiments;
+pub mod service;
@@ -0,0 +1,49 @@
+//! The experiment flags service.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use r_efi::efi;
+
+/// The state of an experiment for the current boot, and why it is in that state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExperimentState {
+    /// The experiment settings have not been read yet, because the variable services are not available. The
+    /// experiment is disabled.
+    Unresolved,
+    /// The experiment is not registered with the provider. The experiment is disabled.
+    Unknown,
+    /// No setting is stored for the experiment, so the platform default applies.
+    Default(bool),
+    /// The experiment is enabled or disabled by its persistent setting.
+    Configured(bool),
+    /// The experiment is enabled or disabled by a one-time override for this boot.
+    Overridden(bool),
+    /// The experiment was enabled on a boot that failed, so it was disabled until it is configured again.
+    FellBack,
+}
+
+impl ExperimentState {
+    /// Returns `true` if the experiment is enabled for this boot.
+    pub const fn is_enabled(self) -> bool {
+        matches!(self, Self::Default(true) | Self::Configured(true) | Self::Overridden(true))
+    }
+}
+
+/// A service for gating new subsystems on per-boot experiment flags.
+///
+/// Components should consume this service as `Service<dyn ExperimentFlags>` and decide once per boot whether to use
+/// the subsystem behind an experiment. Experiments are identified by GUID and must be registered with the
+/// [`ExperimentFlagsProvider`](crate::component::ExperimentFlagsProvider).
+pub trait ExperimentFlags {
+    /// Returns the state of `experiment` for this boot.
+    fn state(&self, experiment: &efi::Guid) -> ExperimentState;
+
+    /// Returns `true` if `experiment` is enabled for this boot.
+    fn is_enabled(&self, experiment: &efi::Guid) -> bool {
+        self.state(experiment).is_enabled()
+    }
+}
@@ -0,0 +1,355 @@
+//! Memory Test Component
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{format, vec::Vec};
+use patina::{
+    base::UEFI_PAGE_SIZE,
+    component::{
+        IntoComponent,
+        params::Config,
+        service::{
+            Service,
+            memory::{AccessType, AllocationOptions, MemoryManager, PageAllocationStrategy},
+        },
+    },
+    efi_types::EfiMemoryType,
+    pi::status_code::{EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_UNCORRECTABLE},
+};
+use patina_boot_journal::{
+    journal::{Severity, milestone},
+    service::BootJournal,
+};
+use r_efi::efi;
+
+use crate::{config::MemoryTestConfig, pattern};
+
+/// Subsystem GUID used for boot journal entries recorded by the memory test.
+///
+/// `{0d4b8e2a-61c7-4f39-a8e5-92f3c07b14d6}`
+pub const MEMORY_TEST_SUBSYSTEM_GUID: efi::Guid =
+    efi::Guid::from_fields(0x0d4b8e2a, 0x61c7, 0x4f39, 0xa8, 0xe5, &[0x92, 0xf3, 0xc0, 0x7b, 0x14, 0xd6]);
+
+/// Number of 64-bit words in a page.
+const WORDS_PER_PAGE: usize = UEFI_PAGE_SIZE / size_of::<u64>();
+
+/// The results of a memory test run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryTestReport {
+    /// The number of bytes tested.
+    pub tested_bytes: usize,
+    /// The base addresses of the pages that failed, in the order they were tested.
+    pub bad_pages: Vec<usize>,
+}
+
+/// A component that tests memory and quarantines pages that fail.
+///
+/// The test runs when the component is dispatched, so it should be registered ahead of components that allocate
+/// significant amounts of memory. Memory is allocated in chunks of [`MemoryTestConfig::chunk_pages`] pages and held
+/// until the test completes, so each chunk is fresh memory. Pages that pass are then freed.
+///
+/// Pages that fail are re-allocated as [`EfiMemoryType::UnusableMemory`] and marked not present, so neither the
+/// firmware nor the operating system uses them again this boot. The results are logged and, if the
+/// [`BootJournal`] service is available, recorded in the boot journal.
+#[derive(IntoComponent, Default)]
+pub struct MemoryTest;
+
+impl MemoryTest {
+    #[coverage(off)] // Tested through `run` and `record_report`.
+    fn entry_point(
+        self,
+        config: Config<MemoryTestConfig>,
+        memory_manager: Service<dyn MemoryManager>,
+        journal: Option<Service<dyn BootJournal>>,
+    ) -> patina::error::Result<()> {
+        if !config.enable_component {
+            log::info!("Memory Test: Component is not enabled, skipping.");
+            return Ok(());
+        }
+
+        log::info!("Memory Test: Testing up to {:#x} bytes with {:?} coverage.", config.max_bytes, config.coverage);
+        let report = run(&config, *memory_manager, |page| pattern::test_page(page, config.coverage));
+        log::info!(
+            "Memory Test: Tested {:#x} bytes, {} bad page(s) quarantined.",
+            report.tested_bytes,
+            report.bad_pages.len()
+        );
+
+        if let Some(journal) = journal {
+            record_report(&report, *journal);
+        }
+        Ok(())
+    }
+}
+
+/// Tests memory with `page_test` and quarantines the pages that fail.
+fn run(
+    config: &MemoryTestConfig,
+    memory_manager: &dyn MemoryManager,
+    mut page_test: impl FnMut(&mut [u64]) -> bool,
+) -> MemoryTestReport {
+    let total_pages = config.max_bytes / UEFI_PAGE_SIZE;
+    let chunk_pages = config.chunk_pages.max(1);
+
+    let mut report = MemoryTestReport::default();
+    let mut chunks = Vec::new();
+    let mut tested_pages = 0;
+    let mut reported_percent = 0;
+
+    while tested_pages < total_pages {
+        let page_count = chunk_pages.min(total_pages - tested_pages);
+        let Ok(allocation) = memory_manager
+            .allocate_pages(page_count, AllocationOptions::new().with_memory_type(EfiMemoryType::BootServicesData))
+        else {
+            log::info!("Memory Test: No more memory available after {:#x} bytes.", tested_pages * UEFI_PAGE_SIZE);
+            break;
+        };
+
+        let words = allocation.into_raw_slice::<u64>();
+        let address = words.addr();
+        chunks.push((address, page_count));
+
+        // SAFETY: The allocation is valid for `page_count` pages and is not otherwise referenced until it is freed.
+        let words = unsafe { &mut *words };
+        for (index, page) in words.chunks_exact_mut(WORDS_PER_PAGE).enumerate() {
+            if !page_test(page) {
+                let page_address = address + index * UEFI_PAGE_SIZE;
+                log::error!("Memory Test: Page at {page_address:#x} failed.");
+                report.bad_pages.push(page_address);
+            }
+        }
+
+        tested_pages += page_count;
+        let percent = tested_pages * 100 / total_pages;
+        if percent / 10 > reported_percent / 10 {
+            log::info!("Memory Test: {percent}% complete.");
+            reported_percent = percent;
+        }
+    }
+    report.tested_bytes = tested_pages * UEFI_PAGE_SIZE;
+
+    for (address, page_count) in chunks {
+        // SAFETY: The chunk was allocated above and is no longer referenced.
+        if let Err(err) = unsafe { memory_manager.free_pages(address, page_count) } {
+            log::error!("Memory Test: Failed to free {page_count:#x} pages at {address:#x}: {err:?}");
+        }
+    }
+
+    for &address in &report.bad_pages {
+        quarantine(memory_manager, address);
+    }
+
+    report
+}
+
+/// Re-allocates a failed page as unusable memory and removes access to it.
+fn quarantine(memory_manager: &dyn MemoryManager, address: usize) {
+    let options = AllocationOptions::new()
+        .with_strategy(PageAllocationStrategy::Address(address))
+        .with_memory_type(EfiMemoryType::UnusableMemory);
+
+    // The page is never freed or accessed, so the pointer is discarded.
+    match memory_manager.allocate_pages(1, options).map(|allocation| allocation.into_raw_ptr::<u8>()) {
+        Ok(_) => {}
+        Err(err) => {
+            log::error!("Memory Test: Failed to quarantine page at {address:#x}: {err:?}");
+            return;
+        }
+    }
+
+    // SAFETY: The page was just allocated and is never accessed.
+    if let Err(err) = unsafe { memory_manager.set_page_attributes(address, 1, AccessType::NoAccess, None) } {
+        log::warn!("Memory Test: Failed to remove access to page at {address:#x}: {err:?}");
+    }
+}
+
+/// Records each bad page and the completion of the test in the boot journal.
+fn record_report(report: &MemoryTestReport, journal: &dyn BootJournal) {
+    for address in &report.bad_pages {
+        let message = format!("Bad page at {address:#x} quarantined");
+        if let Err(err) = journal.record_error(
+            &MEMORY_TEST_SUBSYSTEM_GUID,
+            Severity::Error,
+            EFI_COMPUTING_UNIT_MEMORY | EFI_CU_MEMORY_EC_UNCORRECTABLE,
+            &message,
+        ) {
+            log::warn!("Memory Test: Failed to record bad page in the boot journal: {err}");
+        }
+    }
+
+    if let Err(err) =
+        journal.record_milestone(&MEMORY_TEST_SUBSYSTEM_GUID, milestone::MEMORY_TEST_COMPLETE, "MemoryTestComplete")
+    {
+        log::warn!("Memory Test: Failed to record completion in the boot journal: {err}");
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::config::CoverageLevel;
+    use core::cell::RefCell;
+    use patina::component::service::memory::{MockMemoryManager, PageAllocation, StdMemoryManager};
+    use patina_boot_journal::{
+        error::JournalError,
+        journal::{EntryKind, JournalEntry, JournalQuery},
+    };
+    use std::{
+        alloc::{Layout, alloc_zeroed},
+        sync::{Arc, Mutex},
+    };
+
+    fn config(max_pages: usize, chunk_pages: usize) -> MemoryTestConfig {
+        MemoryTestConfig {
+            enable_component: true,
+            coverage: CoverageLevel::Quick,
+            max_bytes: max_pages * UEFI_PAGE_SIZE,
+            chunk_pages,
+        }
+    }
+
+    fn page_allocation(address: usize, page_count: usize) -> PageAllocation {
+        // SAFETY: Test code - the address is page aligned and valid for `page_count` pages.
+        unsafe { PageAllocation::new(address, page_count, Box::leak(Box::new(StdMemoryManager::new()))).unwrap() }
+    }
+
+    #[derive(Debug, Default)]
+    struct Calls {
+        freed: Vec<(usize, usize)>,
+        quarantined: Vec<usize>,
+        no_access: Vec<usize>,
+    }
+
+    /// A memory manager backed by `available_pages` leaked pages that records frees and quarantines.
+    fn memory_manager(available_pages: usize) -> (MockMemoryManager, Arc<Mutex<Calls>>) {
+        let calls = Arc::new(Mutex::new(Calls::default()));
+        let base = if available_pages == 0 {
+            0
+        } else {
+            // SAFETY: Test code - the layout is non-zero in size.
+            unsafe { alloc_zeroed(Layout::from_size_align(available_pages * UEFI_PAGE_SIZE, UEFI_PAGE_SIZE).unwrap()) }
+                .expose_provenance()
+        };
+        let next = Mutex::new(0usize);
+
+        let mut mock = MockMemoryManager::new();
+        let quarantine_calls = calls.clone();
+        mock.expect_allocate_pages().returning(move |page_count, options| {
+            if let PageAllocationStrategy::Address(address) = options.strategy() {
+                assert_eq!(options.memory_type(), EfiMemoryType::UnusableMemory);
+                quarantine_calls.lock().unwrap().quarantined.push(address);
+                return Ok(page_allocation(address, page_count));
+            }
+            let mut next = next.lock().unwrap();
+            if *next + page_count > available_pages {
+                return Err(patina::component::service::memory::MemoryError::NoAvailableMemory);
+            }
+            let address = base + *next * UEFI_PAGE_SIZE;
+            *next += page_count;
+            Ok(page_allocation(address, page_count))
+        });
+        let free_calls = calls.clone();
+        mock.expect_free_pages().returning(move |address, page_count| {
+            free_calls.lock().unwrap().freed.push((address, page_count));
+            Ok(())
+        });
+        let attribute_calls = calls.clone();
+        mock.expect_set_page_attributes().returning(move |address, _, access, _| {
+            assert_eq!(access, AccessType::NoAccess);
+            attribute_calls.lock().unwrap().no_access.push(address);
+            Ok(())
+        });
+        (mock, calls)
+    }
+
+    #[test]
+    fn test_all_pages_pass() {
+        let (mock, calls) = memory_manager(8);
+        let report = run(&config(8, 3), &mock, |page| pattern::test_page(page, CoverageLevel::Quick));
+
+        assert_eq!(report, MemoryTestReport { tested_bytes: 8 * UEFI_PAGE_SIZE, bad_pages: Vec::new() });
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.freed.iter().map(|(_, count)| count).collect::<Vec<_>>(), [&3, &3, &2]);
+        assert!(calls.quarantined.is_empty());
+    }
+
+    #[test]
+    fn test_stops_when_memory_is_exhausted() {
+        let (mock, calls) = memory_manager(4);
+        let report = run(&config(16, 4), &mock, |_| true);
+
+        assert_eq!(report.tested_bytes, 4 * UEFI_PAGE_SIZE);
+        assert_eq!(calls.lock().unwrap().freed.len(), 1);
+    }
+
+    #[test]
+    fn test_bad_pages_are_quarantined() {
+        let (mock, calls) = memory_manager(6);
+        let mut page_index = 0;
+        let report = run(&config(6, 3), &mock, |_| {
+            page_index += 1;
+            page_index != 2 && page_index != 5
+        });
+
+        assert_eq!(report.bad_pages.len(), 2);
+        let calls = calls.lock().unwrap();
+        // Every chunk is freed whole before the bad pages are re-allocated.
+        let (first_chunk, _) = calls.freed[0];
+        let (second_chunk, _) = calls.freed[1];
+        assert_eq!(report.bad_pages, [first_chunk + UEFI_PAGE_SIZE, second_chunk + UEFI_PAGE_SIZE]);
+        assert_eq!(calls.quarantined, report.bad_pages);
+        assert_eq!(calls.no_access, report.bad_pages);
+    }
+
+    #[test]
+    fn test_zero_chunk_pages_tests_one_page_at_a_time() {
+        let (mock, calls) = memory_manager(2);
+        let report = run(&config(2, 0), &mock, |_| true);
+
+        assert_eq!(report.tested_bytes, 2 * UEFI_PAGE_SIZE);
+        assert_eq!(calls.lock().unwrap().freed.len(), 2);
+    }
+
+    #[derive(Default)]
+    struct RecordingJournal {
+        entries: RefCell<Vec<JournalEntry>>,
+    }
+
+    impl BootJournal for RecordingJournal {
+        fn timestamp(&self) -> u64 {
+            0
+        }
+
+        fn timestamp_frequency(&self) -> u64 {
+            0
+        }
+
+        fn record(&self, entry: JournalEntry) -> Result<(), JournalError> {
+            self.entries.borrow_mut().push(entry);
+            Ok(())
+        }
+
+        fn query(&self, query: &JournalQuery) -> Vec<JournalEntry> {
+            self.entries.borrow().iter().filter(|entry| query.matches(&entry.as_entry_ref())).cloned().collect()
+        }
+    }
+
+    #[test]
+    fn test_record_report() {
+        let journal = RecordingJournal::default();
+        let report = MemoryTestReport { tested_bytes: 0x10000, bad_pages: vec![0x1000, 0x5000] };
+        record_report(&report, &journal);
+
+        let entries = journal.entries.borrow();
+        let kinds = entries.iter().map(|entry| entry.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [EntryKind::Error, EntryKind::Error, EntryKind::Milestone]);
+        assert_eq!(entries[0].code, EFI_COMPUTING_UNIT_MEMORY | EFI_CU_MEMORY_EC_UNCORRECTABLE);
+        assert_eq!(entries[1].as_entry_ref().message(), Some("Bad page at 0x5000 quarantined"));
+        assert_eq!(entries[2].code, milestone::MEMORY_TEST_COMPLETE);
+    }
+}
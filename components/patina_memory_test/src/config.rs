@@ -0,0 +1,132 @@
+//! Memory Test Component Configuration
+//!
+//! ## Static Configuration Example
+//!
+//! ```rust,ignore
+//! Core::default()
+//!  // ...
+//!  .with_config(patina_memory_test::config::MemoryTestConfig {
+//!      enable_component: true,
+//!      coverage: patina_memory_test::config::CoverageLevel::Quick,
+//!      ..Default::default()
+//!  })
+//!  .with_component(patina_memory_test::component::MemoryTest)
+//!  .start()
+//!  .unwrap();
+//! ```
+//!
+//! ## Payload Configuration Example
+//!
+//! The configuration can also be changed without recompiling, from the `memory_test` entry of the platform
+//! [configuration payload](patina::component::config_payload):
+//!
+//! ```json5
+//! {
+//!     memory_test: { enable_component: true, coverage: "quick", max_bytes: 0x10000000 },
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::component::config_payload::{ConfigValue, FieldType, PayloadField};
+
+/// Default: component disabled unless explicitly enabled by the platform.
+pub const DEFAULT_ENABLE_COMPONENT: bool = false;
+/// Default: test 64 MiB of memory.
+pub const DEFAULT_MAX_BYTES: usize = 0x400_0000;
+/// Default: allocate and test memory in 1 MiB chunks.
+pub const DEFAULT_CHUNK_PAGES: usize = 0x100;
+
+/// How thoroughly each page is tested.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageLevel {
+    /// Writes and verifies two complementary patterns in the first and last word of each page.
+    ///
+    /// Finds pages that are missing or not decoded, at a small fraction of the cost of a full pass.
+    #[default]
+    Sparse,
+    /// Writes and verifies two complementary patterns in every word of each page.
+    Quick,
+    /// Writes and verifies all-zeros, all-ones, two complementary patterns, and an address-in-address pattern in
+    /// every word of each page.
+    Extensive,
+}
+
+impl CoverageLevel {
+    /// The names of the levels, in declaration order.
+    const NAMES: [&'static str; 3] = ["sparse", "quick", "extensive"];
+}
+
+impl PayloadField for CoverageLevel {
+    const FIELD_TYPE: FieldType = FieldType::Choice(&Self::NAMES);
+
+    fn from_value(value: &ConfigValue) -> Option<Self> {
+        let ConfigValue::String(name) = value else {
+            return None;
+        };
+        let index = Self::NAMES.iter().position(|level| level.eq_ignore_ascii_case(name))?;
+        [CoverageLevel::Sparse, CoverageLevel::Quick, CoverageLevel::Extensive].get(index).copied()
+    }
+}
+
+/// The configuration for the memory test component.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryTestConfig {
+    /// Indicates whether the memory test runs.
+    pub enable_component: bool,
+    /// How thoroughly each page is tested.
+    pub coverage: CoverageLevel,
+    /// The maximum number of bytes to test. Testing stops earlier if no more memory can be allocated.
+    pub max_bytes: usize,
+    /// The number of pages allocated and tested at a time.
+    pub chunk_pages: usize,
+}
+
+impl Default for MemoryTestConfig {
+    fn default() -> Self {
+        Self {
+            enable_component: DEFAULT_ENABLE_COMPONENT,
+            coverage: CoverageLevel::default(),
+            max_bytes: DEFAULT_MAX_BYTES,
+            chunk_pages: DEFAULT_CHUNK_PAGES,
+        }
+    }
+}
+
+patina::payload_config! {
+    MemoryTestConfig => "memory_test" {
+        enable_component: bool,
+        coverage: CoverageLevel,
+        max_bytes: usize,
+        chunk_pages: usize,
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use patina::component::{Storage, config_payload};
+
+    #[test]
+    fn test_payload_config() {
+        let mut storage = Storage::new();
+        let payload = config_payload::ConfigValue::Map(vec![(
+            "memory_test".into(),
+            ConfigValue::Map(vec![
+                ("enable_component".into(), ConfigValue::Bool(true)),
+                ("coverage".into(), ConfigValue::String("Extensive".into())),
+            ]),
+        )]);
+        assert_eq!(config_payload::apply(&payload, &mut storage), Ok(1));
+
+        let config = storage.get_config::<MemoryTestConfig>().unwrap();
+        assert!(config.enable_component);
+        assert_eq!(config.coverage, CoverageLevel::Extensive);
+        assert_eq!(config.max_bytes, DEFAULT_MAX_BYTES);
+    }
+}
@@ -0,0 +1,41 @@
+//! A boot-time memory test with bad-page quarantine.
+//!
+//! The memory test allocates memory in chunks, tests each page with the configured
+//! [`CoverageLevel`](config::CoverageLevel), and quarantines pages that fail so they are not used again this boot.
+//! Progress is logged as the test runs, and the results are recorded in the boot journal when the
+//! [`BootJournal`](patina_boot_journal::service::BootJournal) service is available.
+//!
+//! ## Scope
+//!
+//! Progress is only reported through the log; it is not written to the UEFI console or drawn over the boot logo
+//! (BGRT). The component is a pattern test and does not scrub ECC memory: ECC initialization and corrected-error
+//! handling remain with the platform's memory initialization code.
+//!
+//! ## Integration Example
+//!
+//! The component is disabled by default and is enabled through [`MemoryTestConfig`](config::MemoryTestConfig). It
+//! should be registered ahead of components that allocate significant amounts of memory.
+//!
+//! ```rust,ignore
+//! Core::default()
+//!  // ...
+//!  .with_config(patina_memory_test::config::MemoryTestConfig { enable_component: true, ..Default::default() })
+//!  .with_component(patina_memory_test::component::MemoryTest)
+//!  .start()
+//!  .unwrap();
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg_attr(not(test), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+pub mod config;
+mod pattern;
@@ -0,0 +1,152 @@
+//! Memory test patterns.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::config::CoverageLevel;
+
+/// Alternating bits, starting with bit 0 set.
+const PATTERN_A: u64 = 0x5555_5555_5555_5555;
+/// The complement of [`PATTERN_A`].
+const PATTERN_B: u64 = 0xAAAA_AAAA_AAAA_AAAA;
+
+/// Word-granular access to the memory under test.
+pub(crate) trait Words {
+    fn len(&self) -> usize;
+    fn read(&self, index: usize) -> u64;
+    fn write(&mut self, index: usize, value: u64);
+}
+
+impl Words for [u64] {
+    fn len(&self) -> usize {
+        <[u64]>::len(self)
+    }
+
+    fn read(&self, index: usize) -> u64 {
+        // SAFETY: The reference is valid and aligned. A volatile read keeps the compiler from eliding the access.
+        unsafe { core::ptr::read_volatile(&self[index]) }
+    }
+
+    fn write(&mut self, index: usize, value: u64) {
+        // SAFETY: The reference is valid and aligned. A volatile write keeps the compiler from eliding the access.
+        unsafe { core::ptr::write_volatile(&mut self[index], value) }
+    }
+}
+
+/// Tests one page of memory at the given coverage level, returning `true` if the page passed.
+///
+/// The contents of the page are destroyed.
+pub(crate) fn test_page<W: Words + ?Sized>(page: &mut W, coverage: CoverageLevel) -> bool {
+    let len = page.len();
+    if len == 0 {
+        return true;
+    }
+
+    match coverage {
+        CoverageLevel::Sparse => [PATTERN_A, PATTERN_B].into_iter().all(|pattern| {
+            let indices = [0, len - 1];
+            indices.iter().for_each(|&i| page.write(i, pattern));
+            indices.iter().all(|&i| page.read(i) == pattern)
+        }),
+        CoverageLevel::Quick => [PATTERN_A, PATTERN_B].into_iter().all(|pattern| fill_and_verify(page, |_| pattern)),
+        CoverageLevel::Extensive => {
+            [0, !0, PATTERN_A, PATTERN_B].into_iter().all(|pattern| fill_and_verify(page, |_| pattern))
+                // Each word holds its own index, so address lines that alias within the page are detected.
+                && fill_and_verify(page, |i| i as u64)
+        }
+    }
+}
+
+/// Writes `pattern(i)` to every word, then verifies every word.
+fn fill_and_verify<W: Words + ?Sized>(page: &mut W, pattern: impl Fn(usize) -> u64) -> bool {
+    (0..page.len()).for_each(|i| page.write(i, pattern(i)));
+    (0..page.len()).all(|i| page.read(i) == pattern(i))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    /// Memory with one bit stuck at a fixed value.
+    struct StuckBit {
+        words: Vec<u64>,
+        index: usize,
+        bit: u32,
+        value: bool,
+    }
+
+    impl Words for StuckBit {
+        fn len(&self) -> usize {
+            self.words.len()
+        }
+
+        fn read(&self, index: usize) -> u64 {
+            self.words[index]
+        }
+
+        fn write(&mut self, index: usize, value: u64) {
+            let mask = 1 << self.bit;
+            self.words[index] = match (index == self.index, self.value) {
+                (false, _) => value,
+                (true, true) => value | mask,
+                (true, false) => value & !mask,
+            };
+        }
+    }
+
+    /// Memory whose upper half aliases its lower half, like a missing address line.
+    struct Aliased(Vec<u64>);
+
+    impl Words for Aliased {
+        fn len(&self) -> usize {
+            self.0.len() * 2
+        }
+
+        fn read(&self, index: usize) -> u64 {
+            self.0[index % self.0.len()]
+        }
+
+        fn write(&mut self, index: usize, value: u64) {
+            let len = self.0.len();
+            self.0[index % len] = value;
+        }
+    }
+
+    const LEVELS: [CoverageLevel; 3] = [CoverageLevel::Sparse, CoverageLevel::Quick, CoverageLevel::Extensive];
+
+    #[test]
+    fn test_good_memory_passes() {
+        let mut page = vec![0u64; 512];
+        for level in LEVELS {
+            assert!(test_page(page.as_mut_slice(), level), "{level:?}");
+        }
+        assert!(test_page(&mut [][..], CoverageLevel::Extensive));
+    }
+
+    #[test]
+    fn test_stuck_bit_detection() {
+        for value in [true, false] {
+            // A stuck bit in the middle of the page is only found by levels that touch every word.
+            let mut page = StuckBit { words: vec![0; 512], index: 100, bit: 7, value };
+            assert!(test_page(&mut page, CoverageLevel::Sparse));
+            assert!(!test_page(&mut page, CoverageLevel::Quick));
+            assert!(!test_page(&mut page, CoverageLevel::Extensive));
+
+            let mut page = StuckBit { words: vec![0; 512], index: 511, bit: 62, value };
+            for level in LEVELS {
+                assert!(!test_page(&mut page, level), "{level:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_aliasing_detected_by_extensive_only() {
+        let mut page = Aliased(vec![0; 256]);
+        assert!(test_page(&mut page, CoverageLevel::Quick));
+        assert!(!test_page(&mut page, CoverageLevel::Extensive));
+    }
+}
@@ -141,4 +141,5 @@
 pub mod component;
 pub mod config;
 pub mod protocol;
+pub mod save_state;
 pub mod service;
@@ -0,0 +1,722 @@
+//! x86 SMRAM Save State Map
+//!
+//! On an SMI, the processor saves its state to the top of SMRAM, in the 1KB save state map at
+//! `SMBASE + SAVE_STATE_MAP_OFFSET`. Its layout depends on the processor: IA-32 processors write the legacy layout
+//! ([`SaveStateMap32`]) and Intel 64 processors write the EM64T layout ([`SaveStateMap64`]).
+//!
+//! A [`SaveState`] gives typed access to either layout, so MM handlers and the debugger can read and update the
+//! interrupted processor state by [`SaveStateRegister`] instead of by offset. Registers are always returned as
+//! [`u64`], zero-extended from the width saved in the map.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::error::EfiError;
+use zerocopy::FromBytes;
+use zerocopy_derive::*;
+
+/// The offset of the save state map from SMBASE.
+pub const SAVE_STATE_MAP_OFFSET: usize = 0xFC00;
+
+/// The size of the save state map in bytes.
+pub const SAVE_STATE_MAP_SIZE: usize = 0x400;
+
+/// The `io_restart` value that makes the processor re-execute the interrupted IO instruction on RSM.
+pub const IO_RESTART: u16 = 0xFF;
+
+/// The SMM revision level written by Intel 64 processors in the low word of `smm_rev_id`.
+const EM64T_REVISION_LEVEL: u32 = 0x64;
+
+/// Legacy (IA-32) save state map.
+///
+/// Offsets in comments are relative to `SMBASE + 0x8000`.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+pub struct SaveStateMap32 {
+    reserved1: [u8; 0x2F8], // 7c00h
+    /// SMBASE of the processor.
+    pub smbase: u32, // 7ef8h
+    /// SMM revision identifier.
+    pub smm_rev_id: u32, // 7efch
+    /// IO instruction restart. Set to [`IO_RESTART`] to re-execute the interrupted IO instruction.
+    pub io_restart: u16, // 7f00h
+    /// Auto HALT restart. Bit 0 is set if the processor was halted when the SMI was taken.
+    pub auto_halt_restart: u16, // 7f02h
+    reserved2: [u8; 0x9C],  // 7f04h
+    /// Memory address of the interrupted IO instruction's memory operand.
+    pub io_mem_addr: u32, // 7fa0h
+    /// IO instruction information. See [`IoInfo`].
+    pub io_misc: u32, // 7fa4h
+    /// ES selector.
+    pub es: u32, // 7fa8h
+    /// CS selector.
+    pub cs: u32, // 7fach
+    /// SS selector.
+    pub ss: u32, // 7fb0h
+    /// DS selector.
+    pub ds: u32, // 7fb4h
+    /// FS selector.
+    pub fs: u32, // 7fb8h
+    /// GS selector.
+    pub gs: u32, // 7fbch
+    reserved3: u32,         // 7fc0h
+    /// TR selector.
+    pub tr: u32, // 7fc4h
+    /// DR7.
+    pub dr7: u32, // 7fc8h
+    /// DR6.
+    pub dr6: u32, // 7fcch
+    /// EAX.
+    pub eax: u32, // 7fd0h
+    /// ECX.
+    pub ecx: u32, // 7fd4h
+    /// EDX.
+    pub edx: u32, // 7fd8h
+    /// EBX.
+    pub ebx: u32, // 7fdch
+    /// ESP.
+    pub esp: u32, // 7fe0h
+    /// EBP.
+    pub ebp: u32, // 7fe4h
+    /// ESI.
+    pub esi: u32, // 7fe8h
+    /// EDI.
+    pub edi: u32, // 7fech
+    /// EIP.
+    pub eip: u32, // 7ff0h
+    /// EFLAGS.
+    pub eflags: u32, // 7ff4h
+    /// CR3.
+    pub cr3: u32, // 7ff8h
+    /// CR0.
+    pub cr0: u32, // 7ffch
+}
+
+/// EM64T (Intel 64) save state map.
+///
+/// Offsets in comments are relative to `SMBASE + 0x8000`.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+pub struct SaveStateMap64 {
+    reserved1: [u8; 0x1D0], // 7c00h
+    /// Upper 32 bits of the GDT base.
+    pub gdt_base_hi: u32, // 7dd0h
+    /// Upper 32 bits of the LDT base.
+    pub ldt_base_hi: u32, // 7dd4h
+    /// Upper 32 bits of the IDT base.
+    pub idt_base_hi: u32, // 7dd8h
+    reserved2: [u8; 0xC],   // 7ddch
+    /// RIP of the interrupted IO instruction.
+    pub io_rip: u64, // 7de8h
+    reserved3: [u8; 0x50],  // 7df0h
+    /// CR4.
+    pub cr4: u32, // 7e40h
+    reserved4: [u8; 0x48],  // 7e44h
+    /// Lower 32 bits of the GDT base.
+    pub gdt_base_lo: u32, // 7e8ch
+    reserved5: u32,         // 7e90h
+    /// Lower 32 bits of the IDT base.
+    pub idt_base_lo: u32, // 7e94h
+    reserved6: u32,         // 7e98h
+    /// Lower 32 bits of the LDT base.
+    pub ldt_base_lo: u32, // 7e9ch
+    reserved7: [u8; 0x38],  // 7ea0h
+    /// EPT pointer, when EPT was enabled.
+    pub ept_vmx_control: u64, // 7ed8h
+    /// Bit 0 is set if EPT was enabled.
+    pub en_ept_vmx_control: u32, // 7ee0h
+    reserved8: [u8; 0x14],  // 7ee4h
+    /// SMBASE of the processor.
+    pub smbase: u32, // 7ef8h
+    /// SMM revision identifier.
+    pub smm_rev_id: u32, // 7efch
+    /// IO instruction restart. Set to [`IO_RESTART`] to re-execute the interrupted IO instruction.
+    pub io_restart: u16, // 7f00h
+    /// Auto HALT restart. Bit 0 is set if the processor was halted when the SMI was taken.
+    pub auto_halt_restart: u16, // 7f02h
+    reserved9: [u8; 0x18],  // 7f04h
+    /// R15.
+    pub r15: u64, // 7f1ch
+    /// R14.
+    pub r14: u64, // 7f24h
+    /// R13.
+    pub r13: u64, // 7f2ch
+    /// R12.
+    pub r12: u64, // 7f34h
+    /// R11.
+    pub r11: u64, // 7f3ch
+    /// R10.
+    pub r10: u64, // 7f44h
+    /// R9.
+    pub r9: u64, // 7f4ch
+    /// R8.
+    pub r8: u64, // 7f54h
+    /// RAX.
+    pub rax: u64, // 7f5ch
+    /// RCX.
+    pub rcx: u64, // 7f64h
+    /// RDX.
+    pub rdx: u64, // 7f6ch
+    /// RBX.
+    pub rbx: u64, // 7f74h
+    /// RSP.
+    pub rsp: u64, // 7f7ch
+    /// RBP.
+    pub rbp: u64, // 7f84h
+    /// RSI.
+    pub rsi: u64, // 7f8ch
+    /// RDI.
+    pub rdi: u64, // 7f94h
+    /// Memory address of the interrupted IO instruction's memory operand.
+    pub io_mem_addr: u64, // 7f9ch
+    /// IO instruction information. See [`IoInfo`].
+    pub io_misc: u32, // 7fa4h
+    /// ES selector.
+    pub es: u32, // 7fa8h
+    /// CS selector.
+    pub cs: u32, // 7fach
+    /// SS selector.
+    pub ss: u32, // 7fb0h
+    /// DS selector.
+    pub ds: u32, // 7fb4h
+    /// FS selector.
+    pub fs: u32, // 7fb8h
+    /// GS selector.
+    pub gs: u32, // 7fbch
+    /// LDTR selector.
+    pub ldtr: u32, // 7fc0h
+    /// TR selector.
+    pub tr: u32, // 7fc4h
+    /// DR7.
+    pub dr7: u64, // 7fc8h
+    /// DR6.
+    pub dr6: u64, // 7fd0h
+    /// RIP.
+    pub rip: u64, // 7fd8h
+    /// IA32_EFER.
+    pub ia32_efer: u64, // 7fe0h
+    /// RFLAGS.
+    pub rflags: u64, // 7fe8h
+    /// CR3.
+    pub cr3: u64, // 7ff0h
+    /// CR0.
+    pub cr0: u64, // 7ff8h
+}
+
+/// The layout of a save state map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateLayout {
+    /// The IA-32 layout, [`SaveStateMap32`].
+    Legacy,
+    /// The Intel 64 layout, [`SaveStateMap64`].
+    Em64t,
+}
+
+impl SaveStateLayout {
+    /// Returns the layout written by a processor with the SMM revision identifier `smm_rev_id`, which is at the same
+    /// offset in both layouts.
+    pub fn from_revision(smm_rev_id: u32) -> Self {
+        if smm_rev_id & 0xFFFF == EM64T_REVISION_LEVEL { Self::Em64t } else { Self::Legacy }
+    }
+}
+
+/// A register in the save state map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateRegister {
+    /// ES selector.
+    Es,
+    /// CS selector.
+    Cs,
+    /// SS selector.
+    Ss,
+    /// DS selector.
+    Ds,
+    /// FS selector.
+    Fs,
+    /// GS selector.
+    Gs,
+    /// LDTR selector. EM64T only.
+    Ldtr,
+    /// TR selector.
+    Tr,
+    /// GDT base. EM64T only.
+    GdtBase,
+    /// IDT base. EM64T only.
+    IdtBase,
+    /// LDT base. EM64T only.
+    LdtBase,
+    /// DR6.
+    Dr6,
+    /// DR7.
+    Dr7,
+    /// CR0.
+    Cr0,
+    /// CR3.
+    Cr3,
+    /// CR4. EM64T only.
+    Cr4,
+    /// RAX, or EAX in the legacy layout.
+    Rax,
+    /// RBX, or EBX in the legacy layout.
+    Rbx,
+    /// RCX, or ECX in the legacy layout.
+    Rcx,
+    /// RDX, or EDX in the legacy layout.
+    Rdx,
+    /// RSP, or ESP in the legacy layout.
+    Rsp,
+    /// RBP, or EBP in the legacy layout.
+    Rbp,
+    /// RSI, or ESI in the legacy layout.
+    Rsi,
+    /// RDI, or EDI in the legacy layout.
+    Rdi,
+    /// R8. EM64T only.
+    R8,
+    /// R9. EM64T only.
+    R9,
+    /// R10. EM64T only.
+    R10,
+    /// R11. EM64T only.
+    R11,
+    /// R12. EM64T only.
+    R12,
+    /// R13. EM64T only.
+    R13,
+    /// R14. EM64T only.
+    R14,
+    /// R15. EM64T only.
+    R15,
+    /// RIP, or EIP in the legacy layout.
+    Rip,
+    /// RFLAGS, or EFLAGS in the legacy layout.
+    Rflags,
+    /// IA32_EFER. EM64T only.
+    Efer,
+}
+
+/// The kind of IO instruction that was interrupted, from bits 4-7 of `io_misc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoType {
+    /// `OUT DX`.
+    OutDx,
+    /// `IN DX`.
+    InDx,
+    /// `OUTS`.
+    Outs,
+    /// `INS`.
+    Ins,
+    /// `REP OUTS`.
+    RepOuts,
+    /// `REP INS`.
+    RepIns,
+    /// `OUT` with an immediate port.
+    OutImmediate,
+    /// `IN` with an immediate port.
+    InImmediate,
+}
+
+impl IoType {
+    fn from_raw(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::OutDx),
+            1 => Some(Self::InDx),
+            2 => Some(Self::Outs),
+            3 => Some(Self::Ins),
+            6 => Some(Self::RepOuts),
+            7 => Some(Self::RepIns),
+            8 => Some(Self::OutImmediate),
+            9 => Some(Self::InImmediate),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the instruction reads from the port.
+    pub fn is_input(self) -> bool {
+        matches!(self, Self::InDx | Self::Ins | Self::RepIns | Self::InImmediate)
+    }
+}
+
+/// The IO instruction that caused the SMI, decoded from `io_misc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoInfo {
+    /// The IO port.
+    pub port: u16,
+    /// The access width in bytes: 1, 2, or 4.
+    pub width: u8,
+    /// The kind of IO instruction.
+    pub io_type: IoType,
+    /// The memory address of the string instruction operand.
+    pub mem_addr: u64,
+}
+
+impl IoInfo {
+    /// Decodes `io_misc`, returning `None` if the SMI was not caused by an IO instruction.
+    fn from_raw(io_misc: u32, mem_addr: u64) -> Option<Self> {
+        // Bit 0 is set when the SMI was caused by an IO instruction.
+        if io_misc & 1 == 0 {
+            return None;
+        }
+        let width = ((io_misc >> 1) & 0x7) as u8;
+        if !matches!(width, 1 | 2 | 4) {
+            return None;
+        }
+        Some(Self { port: (io_misc >> 16) as u16, width, io_type: IoType::from_raw((io_misc >> 4) & 0xF)?, mem_addr })
+    }
+}
+
+/// Typed access to the save state map of one processor.
+#[derive(Debug)]
+pub enum SaveState<'a> {
+    /// A map in the legacy layout.
+    Legacy(&'a mut SaveStateMap32),
+    /// A map in the EM64T layout.
+    Em64t(&'a mut SaveStateMap64),
+}
+
+impl<'a> SaveState<'a> {
+    /// Interprets `bytes` as a save state map in `layout`. Returns `None` if `bytes` is shorter than
+    /// [`SAVE_STATE_MAP_SIZE`].
+    pub fn new(bytes: &'a mut [u8], layout: SaveStateLayout) -> Option<Self> {
+        let bytes = bytes.get_mut(..SAVE_STATE_MAP_SIZE)?;
+        match layout {
+            SaveStateLayout::Legacy => SaveStateMap32::mut_from_bytes(bytes).ok().map(Self::Legacy),
+            SaveStateLayout::Em64t => SaveStateMap64::mut_from_bytes(bytes).ok().map(Self::Em64t),
+        }
+    }
+
+    /// Interprets `bytes` as a save state map, choosing the layout from its SMM revision identifier.
+    pub fn detect(bytes: &'a mut [u8]) -> Option<Self> {
+        let revision = SaveStateMap32::ref_from_prefix(bytes).ok()?.0.smm_rev_id;
+        Self::new(bytes, SaveStateLayout::from_revision(revision))
+    }
+
+    /// Returns the save state map of the processor whose SMBASE is `smbase`.
+    ///
+    /// # Safety
+    ///
+    /// `smbase` must be the SMBASE of a processor, and the caller must have exclusive access to its save state map for
+    /// `'a`. This is normally the case for the processor's own map while it handles an SMI.
+    pub unsafe fn from_smbase(smbase: usize, layout: SaveStateLayout) -> Option<Self> {
+        // SAFETY: The caller guarantees the save state map is valid and exclusively owned for 'a.
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut((smbase + SAVE_STATE_MAP_OFFSET) as *mut u8, SAVE_STATE_MAP_SIZE)
+        };
+        Self::new(bytes, layout)
+    }
+
+    /// Returns the layout of the map.
+    pub fn layout(&self) -> SaveStateLayout {
+        match self {
+            Self::Legacy(_) => SaveStateLayout::Legacy,
+            Self::Em64t(_) => SaveStateLayout::Em64t,
+        }
+    }
+
+    /// Returns the SMBASE of the processor.
+    pub fn smbase(&self) -> u32 {
+        match self {
+            Self::Legacy(map) => map.smbase,
+            Self::Em64t(map) => map.smbase,
+        }
+    }
+
+    /// Returns the SMM revision identifier.
+    pub fn smm_rev_id(&self) -> u32 {
+        match self {
+            Self::Legacy(map) => map.smm_rev_id,
+            Self::Em64t(map) => map.smm_rev_id,
+        }
+    }
+
+    /// Reads `register`.
+    ///
+    /// Returns [`EfiError::NotFound`] if the register is not saved in this layout.
+    pub fn read(&self, register: SaveStateRegister) -> Result<u64, EfiError> {
+        use SaveStateRegister as R;
+        let value = match self {
+            Self::Legacy(map) => {
+                (match register {
+                    R::Es => map.es,
+                    R::Cs => map.cs,
+                    R::Ss => map.ss,
+                    R::Ds => map.ds,
+                    R::Fs => map.fs,
+                    R::Gs => map.gs,
+                    R::Tr => map.tr,
+                    R::Dr6 => map.dr6,
+                    R::Dr7 => map.dr7,
+                    R::Cr0 => map.cr0,
+                    R::Cr3 => map.cr3,
+                    R::Rax => map.eax,
+                    R::Rbx => map.ebx,
+                    R::Rcx => map.ecx,
+                    R::Rdx => map.edx,
+                    R::Rsp => map.esp,
+                    R::Rbp => map.ebp,
+                    R::Rsi => map.esi,
+                    R::Rdi => map.edi,
+                    R::Rip => map.eip,
+                    R::Rflags => map.eflags,
+                    _ => return Err(EfiError::NotFound),
+                }) as u64
+            }
+            Self::Em64t(map) => match register {
+                R::Es => map.es as u64,
+                R::Cs => map.cs as u64,
+                R::Ss => map.ss as u64,
+                R::Ds => map.ds as u64,
+                R::Fs => map.fs as u64,
+                R::Gs => map.gs as u64,
+                R::Ldtr => map.ldtr as u64,
+                R::Tr => map.tr as u64,
+                R::GdtBase => (map.gdt_base_hi as u64) << 32 | map.gdt_base_lo as u64,
+                R::IdtBase => (map.idt_base_hi as u64) << 32 | map.idt_base_lo as u64,
+                R::LdtBase => (map.ldt_base_hi as u64) << 32 | map.ldt_base_lo as u64,
+                R::Dr6 => map.dr6,
+                R::Dr7 => map.dr7,
+                R::Cr0 => map.cr0,
+                R::Cr3 => map.cr3,
+                R::Cr4 => map.cr4 as u64,
+                R::Rax => map.rax,
+                R::Rbx => map.rbx,
+                R::Rcx => map.rcx,
+                R::Rdx => map.rdx,
+                R::Rsp => map.rsp,
+                R::Rbp => map.rbp,
+                R::Rsi => map.rsi,
+                R::Rdi => map.rdi,
+                R::R8 => map.r8,
+                R::R9 => map.r9,
+                R::R10 => map.r10,
+                R::R11 => map.r11,
+                R::R12 => map.r12,
+                R::R13 => map.r13,
+                R::R14 => map.r14,
+                R::R15 => map.r15,
+                R::Rip => map.rip,
+                R::Rflags => map.rflags,
+                R::Efer => map.ia32_efer,
+            },
+        };
+        Ok(value)
+    }
+
+    /// Writes `value` to `register`, which takes effect when the processor resumes.
+    ///
+    /// Returns [`EfiError::NotFound`] if the register is not saved in this layout, or [`EfiError::InvalidParameter`]
+    /// if `value` does not fit the width saved in the map.
+    pub fn write(&mut self, register: SaveStateRegister, value: u64) -> Result<(), EfiError> {
+        use SaveStateRegister as R;
+        let dword = || u32::try_from(value).map_err(|_| EfiError::InvalidParameter);
+        match self {
+            Self::Legacy(map) => match register {
+                R::Es => map.es = dword()?,
+                R::Cs => map.cs = dword()?,
+                R::Ss => map.ss = dword()?,
+                R::Ds => map.ds = dword()?,
+                R::Fs => map.fs = dword()?,
+                R::Gs => map.gs = dword()?,
+                R::Tr => map.tr = dword()?,
+                R::Dr6 => map.dr6 = dword()?,
+                R::Dr7 => map.dr7 = dword()?,
+                R::Cr0 => map.cr0 = dword()?,
+                R::Cr3 => map.cr3 = dword()?,
+                R::Rax => map.eax = dword()?,
+                R::Rbx => map.ebx = dword()?,
+                R::Rcx => map.ecx = dword()?,
+                R::Rdx => map.edx = dword()?,
+                R::Rsp => map.esp = dword()?,
+                R::Rbp => map.ebp = dword()?,
+                R::Rsi => map.esi = dword()?,
+                R::Rdi => map.edi = dword()?,
+                R::Rip => map.eip = dword()?,
+                R::Rflags => map.eflags = dword()?,
+                _ => return Err(EfiError::NotFound),
+            },
+            Self::Em64t(map) => match register {
+                R::Es => map.es = dword()?,
+                R::Cs => map.cs = dword()?,
+                R::Ss => map.ss = dword()?,
+                R::Ds => map.ds = dword()?,
+                R::Fs => map.fs = dword()?,
+                R::Gs => map.gs = dword()?,
+                R::Ldtr => map.ldtr = dword()?,
+                R::Tr => map.tr = dword()?,
+                R::GdtBase => (map.gdt_base_hi, map.gdt_base_lo) = ((value >> 32) as u32, value as u32),
+                R::IdtBase => (map.idt_base_hi, map.idt_base_lo) = ((value >> 32) as u32, value as u32),
+                R::LdtBase => (map.ldt_base_hi, map.ldt_base_lo) = ((value >> 32) as u32, value as u32),
+                R::Dr6 => map.dr6 = value,
+                R::Dr7 => map.dr7 = value,
+                R::Cr0 => map.cr0 = value,
+                R::Cr3 => map.cr3 = value,
+                R::Cr4 => map.cr4 = dword()?,
+                R::Rax => map.rax = value,
+                R::Rbx => map.rbx = value,
+                R::Rcx => map.rcx = value,
+                R::Rdx => map.rdx = value,
+                R::Rsp => map.rsp = value,
+                R::Rbp => map.rbp = value,
+                R::Rsi => map.rsi = value,
+                R::Rdi => map.rdi = value,
+                R::R8 => map.r8 = value,
+                R::R9 => map.r9 = value,
+                R::R10 => map.r10 = value,
+                R::R11 => map.r11 = value,
+                R::R12 => map.r12 = value,
+                R::R13 => map.r13 = value,
+                R::R14 => map.r14 = value,
+                R::R15 => map.r15 = value,
+                R::Rip => map.rip = value,
+                R::Rflags => map.rflags = value,
+                R::Efer => map.ia32_efer = value,
+            },
+        }
+        Ok(())
+    }
+
+    /// Returns the IO instruction that caused the SMI, or `None` if the SMI was not caused by an IO instruction.
+    pub fn io_info(&self) -> Option<IoInfo> {
+        match self {
+            Self::Legacy(map) => IoInfo::from_raw(map.io_misc, map.io_mem_addr as u64),
+            Self::Em64t(map) => IoInfo::from_raw(map.io_misc, map.io_mem_addr),
+        }
+    }
+
+    /// Returns true if the interrupted IO instruction will be re-executed on RSM.
+    pub fn io_restart(&self) -> bool {
+        let io_restart = match self {
+            Self::Legacy(map) => map.io_restart,
+            Self::Em64t(map) => map.io_restart,
+        };
+        io_restart == IO_RESTART
+    }
+
+    /// Sets whether the interrupted IO instruction is re-executed on RSM, for example after an MM handler emulated
+    /// a device that was not ready.
+    pub fn set_io_restart(&mut self, restart: bool) {
+        let io_restart = if restart { IO_RESTART } else { 0 };
+        match self {
+            Self::Legacy(map) => map.io_restart = io_restart,
+            Self::Em64t(map) => map.io_restart = io_restart,
+        }
+    }
+
+    /// Returns true if the processor was halted when the SMI was taken.
+    pub fn auto_halt_restart(&self) -> bool {
+        let auto_halt_restart = match self {
+            Self::Legacy(map) => map.auto_halt_restart,
+            Self::Em64t(map) => map.auto_halt_restart,
+        };
+        auto_halt_restart & 1 != 0
+    }
+
+    /// Sets whether a halted processor returns to the HLT instruction on RSM. Clearing it resumes at the instruction
+    /// after the HLT.
+    pub fn set_auto_halt_restart(&mut self, restart: bool) {
+        match self {
+            Self::Legacy(map) => map.auto_halt_restart = (map.auto_halt_restart & !1) | restart as u16,
+            Self::Em64t(map) => map.auto_halt_restart = (map.auto_halt_restart & !1) | restart as u16,
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    // Converts an offset in the comments of the map definitions to an offset in the map.
+    const fn at(offset: usize) -> usize {
+        offset - 0x7C00
+    }
+
+    fn put(bytes: &mut [u8], offset: usize, value: &[u8]) {
+        bytes[at(offset)..at(offset) + value.len()].copy_from_slice(value);
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(size_of::<SaveStateMap32>(), SAVE_STATE_MAP_SIZE);
+        assert_eq!(size_of::<SaveStateMap64>(), SAVE_STATE_MAP_SIZE);
+        assert_eq!(offset_of!(SaveStateMap32, smbase), at(0x7EF8));
+        assert_eq!(offset_of!(SaveStateMap32, io_mem_addr), at(0x7FA0));
+        assert_eq!(offset_of!(SaveStateMap32, cr0), at(0x7FFC));
+        assert_eq!(offset_of!(SaveStateMap64, io_rip), at(0x7DE8));
+        assert_eq!(offset_of!(SaveStateMap64, cr4), at(0x7E40));
+        assert_eq!(offset_of!(SaveStateMap64, smbase), at(0x7EF8));
+        assert_eq!(offset_of!(SaveStateMap64, r15), at(0x7F1C));
+        assert_eq!(offset_of!(SaveStateMap64, rax), at(0x7F5C));
+        assert_eq!(offset_of!(SaveStateMap64, io_misc), at(0x7FA4));
+        assert_eq!(offset_of!(SaveStateMap64, dr7), at(0x7FC8));
+        assert_eq!(offset_of!(SaveStateMap64, cr0), at(0x7FF8));
+    }
+
+    #[test]
+    fn test_em64t_registers() {
+        let mut bytes = [0u8; SAVE_STATE_MAP_SIZE];
+        put(&mut bytes, 0x7EFC, &0x30064u32.to_le_bytes());
+        put(&mut bytes, 0x7F5C, &0x1122_3344_5566_7788u64.to_le_bytes());
+        put(&mut bytes, 0x7DD0, &0xFFFF_F800u32.to_le_bytes());
+        put(&mut bytes, 0x7E8C, &0x1234_5000u32.to_le_bytes());
+
+        let mut state = SaveState::detect(&mut bytes).unwrap();
+        assert_eq!(state.layout(), SaveStateLayout::Em64t);
+        assert_eq!(state.read(SaveStateRegister::Rax), Ok(0x1122_3344_5566_7788));
+        assert_eq!(state.read(SaveStateRegister::GdtBase), Ok(0xFFFF_F800_1234_5000));
+
+        state.write(SaveStateRegister::R15, u64::MAX).unwrap();
+        state.write(SaveStateRegister::IdtBase, 0xFFFF_F800_0000_1000).unwrap();
+        assert_eq!(state.write(SaveStateRegister::Cs, 1 << 32), Err(EfiError::InvalidParameter));
+        assert_eq!(bytes[at(0x7F1C)..at(0x7F24)], [0xFF; 8]);
+        assert_eq!(bytes[at(0x7DD8)..at(0x7DDC)], 0xFFFF_F800u32.to_le_bytes());
+        assert_eq!(bytes[at(0x7E94)..at(0x7E98)], 0x1000u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_legacy_registers() {
+        let mut bytes = [0u8; SAVE_STATE_MAP_SIZE];
+        put(&mut bytes, 0x7EFC, &0x30000u32.to_le_bytes());
+        put(&mut bytes, 0x7FF0, &0xFFF0u32.to_le_bytes());
+
+        let mut state = SaveState::detect(&mut bytes).unwrap();
+        assert_eq!(state.layout(), SaveStateLayout::Legacy);
+        assert_eq!(state.read(SaveStateRegister::Rip), Ok(0xFFF0));
+        assert_eq!(state.read(SaveStateRegister::R8), Err(EfiError::NotFound));
+        assert_eq!(state.write(SaveStateRegister::Efer, 0), Err(EfiError::NotFound));
+        assert_eq!(state.write(SaveStateRegister::Rax, 1 << 32), Err(EfiError::InvalidParameter));
+
+        state.write(SaveStateRegister::Rax, 0xDEAD_BEEF).unwrap();
+        assert_eq!(bytes[at(0x7FD0)..at(0x7FD4)], 0xDEAD_BEEFu32.to_le_bytes());
+        assert!(SaveState::new(&mut bytes[..0x3FF], SaveStateLayout::Legacy).is_none());
+    }
+
+    #[test]
+    fn test_io_info() {
+        let mut bytes = [0u8; SAVE_STATE_MAP_SIZE];
+        // An `OUT 0xB2, AL` instruction.
+        put(&mut bytes, 0x7FA4, &(0x00B2_0000u32 | 8 << 4 | 1 << 1 | 1).to_le_bytes());
+        put(&mut bytes, 0x7F02, &1u16.to_le_bytes());
+
+        let mut state = SaveState::new(&mut bytes, SaveStateLayout::Em64t).unwrap();
+        let io = state.io_info().unwrap();
+        assert_eq!(io, IoInfo { port: 0xB2, width: 1, io_type: IoType::OutImmediate, mem_addr: 0 });
+        assert!(!io.io_type.is_input());
+        assert!(state.auto_halt_restart());
+        assert!(!state.io_restart());
+
+        state.set_io_restart(true);
+        state.set_auto_halt_restart(false);
+        assert!(state.io_restart());
+        assert!(!state.auto_halt_restart());
+        assert_eq!(bytes[at(0x7F00)..at(0x7F04)], [0xFF, 0, 0, 0]);
+
+        // Bit 0 is clear when the SMI was not caused by an IO instruction.
+        let mut bytes = [0u8; SAVE_STATE_MAP_SIZE];
+        assert_eq!(SaveState::new(&mut bytes, SaveStateLayout::Legacy).unwrap().io_info(), None);
+    }
+}
@@ -0,0 +1,225 @@
+//! Telemetry Component
+//!
+//! Installs the telemetry table and keeps it describing the memory log, the published boot journal, and the last crash
+//! record. The table is filled in at Ready to Boot, and again at Exit Boot Services once every region is final.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, vec::Vec};
+use patina::{
+    boot_services::{
+        BootServices, StandardBootServices,
+        event::{Event, EventType},
+        tpl::Tpl,
+    },
+    component::{IntoComponent, service::Service},
+    efi_types::EfiMemoryType,
+    error::EfiError,
+    runtime_services::{StandardRuntimeServices, variables},
+    tpl_mutex::TplMutex,
+};
+use patina_adv_logger::protocol::AdvancedLoggerProtocol;
+use patina_boot_journal::service::BootJournal;
+use r_efi::{efi, system::EVENT_GROUP_READY_TO_BOOT};
+
+use crate::{
+    crash::{self, CRASH_VARIABLE_NAME, CrashRecord, TELEMETRY_VARIABLE_GUID},
+    table::{self, BOOT_JOURNAL_REGION, CRASH_RECORD_REGION, MEMORY_LOG_REGION, RegionDescriptor},
+};
+
+/// Number of regions the telemetry table has room for.
+const MAX_REGIONS: usize = 3;
+
+/// A component that hands the memory log, boot journal, and last crash record to the operating system.
+///
+/// The boot journal is only described if the `BootJournal` service is available when this component is dispatched,
+/// so the `BootJournalProvider` should be registered first. The memory log is described if the Advanced Logger
+/// protocol is installed at Ready to Boot.
+#[derive(IntoComponent, Default)]
+pub struct TelemetryProvider;
+
+impl TelemetryProvider {
+    /// Creates a new telemetry provider.
+    pub const fn new() -> Self {
+        Self
+    }
+
+    #[coverage(off)] // Requires boot services; the table contents are tested through `TelemetryState`.
+    fn entry_point(
+        self,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+        journal: Option<Service<dyn BootJournal>>,
+    ) -> patina::error::Result<()> {
+        crash::init(&runtime_services);
+
+        let boot_services: &'static StandardBootServices = Box::leak(Box::new(boot_services));
+        let table = allocate_runtime_buffer(boot_services, table::table_size(MAX_REGIONS))?;
+        let table_address = table.as_mut_ptr();
+        let mut state = TelemetryState::new(table);
+        state.refresh(None)?;
+
+        // SAFETY: The table is the telemetry table described by `TELEMETRY_TABLE_GUID`, and the runtime allocation
+        // backing it is never freed.
+        unsafe { boot_services.install_configuration_table(&table::TELEMETRY_TABLE_GUID, Some(table_address))? };
+
+        let telemetry: &'static Telemetry = Box::leak(Box::new(Telemetry {
+            state: TplMutex::new(boot_services, Tpl::NOTIFY, state),
+            boot_services,
+            runtime_services,
+            journal,
+        }));
+
+        Event::new_in_group(
+            boot_services,
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            &EVENT_GROUP_READY_TO_BOOT,
+            move || telemetry.on_ready_to_boot(),
+        )?
+        .leak();
+        Event::new(boot_services, EventType::SIGNAL_EXIT_BOOT_SERVICES, Tpl::CALLBACK, move || telemetry.refresh())?
+            .leak();
+
+        log::info!("Telemetry: Installed the telemetry table at {table_address:p}.");
+        Ok(())
+    }
+}
+
+/// The telemetry table and the regions it describes, other than the boot journal.
+struct TelemetryState {
+    table: &'static mut [u8],
+    memory_log: Option<RegionDescriptor>,
+    crash_record: Option<RegionDescriptor>,
+    ready_to_boot: bool,
+}
+
+impl TelemetryState {
+    const fn new(table: &'static mut [u8]) -> Self {
+        Self { table, memory_log: None, crash_record: None, ready_to_boot: false }
+    }
+
+    /// Rewrites the table with the known regions and the published boot journal, if any.
+    fn refresh(&mut self, journal: Option<RegionDescriptor>) -> Result<(), EfiError> {
+        let regions: Vec<_> = [self.memory_log, journal, self.crash_record].into_iter().flatten().collect();
+        table::write_table(self.table, &regions).map(|_| ())
+    }
+}
+
+/// The state shared with the event notify functions.
+struct Telemetry {
+    state: TplMutex<'static, TelemetryState, StandardBootServices>,
+    boot_services: &'static StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+    journal: Option<Service<dyn BootJournal>>,
+}
+
+impl Telemetry {
+    /// Describes the memory log and last crash record, the first time Ready to Boot is signaled, and refreshes the
+    /// table.
+    #[coverage(off)] // Requires boot services.
+    fn on_ready_to_boot(&self) {
+        let first = !core::mem::replace(&mut self.state.lock().ready_to_boot, true);
+        if first {
+            let memory_log = self.memory_log();
+            let crash_record = self.crash_record();
+            let mut state = self.state.lock();
+            state.memory_log = memory_log;
+            state.crash_record = crash_record;
+        }
+        self.refresh();
+    }
+
+    /// Rewrites the table, picking up the boot journal once it is published.
+    #[coverage(off)] // Requires boot services.
+    fn refresh(&self) {
+        let journal = self
+            .journal
+            .as_ref()
+            .and_then(|journal| journal.published_region())
+            .map(|(address, size)| RegionDescriptor::new(&BOOT_JOURNAL_REGION, address, size as u64));
+        if let Err(err) = self.state.lock().refresh(journal) {
+            log::error!("Telemetry: Failed to update the telemetry table: {err:?}");
+        }
+    }
+
+    /// Returns the region of the memory log, if the Advanced Logger protocol is installed.
+    #[coverage(off)] // Requires boot services.
+    fn memory_log(&self) -> Option<RegionDescriptor> {
+        // SAFETY: The protocol is only read.
+        let protocol = unsafe { self.boot_services.locate_protocol::<AdvancedLoggerProtocol>(None) }.ok()?;
+        // SAFETY: An installed Advanced Logger protocol describes a valid memory buffer.
+        let (address, size) = unsafe { protocol.log_buffer() };
+        Some(RegionDescriptor::new(&MEMORY_LOG_REGION, address, size))
+    }
+
+    /// Copies the last crash record into runtime memory and returns its region, if a crash has been recorded.
+    #[coverage(off)] // Requires boot services.
+    fn crash_record(&self) -> Option<RegionDescriptor> {
+        let record = match variables::get_variable_bytes(
+            &self.runtime_services,
+            CRASH_VARIABLE_NAME,
+            &TELEMETRY_VARIABLE_GUID,
+        ) {
+            Ok((record, _)) => record,
+            Err(EfiError::NotFound) => return None,
+            Err(err) => {
+                log::warn!("Telemetry: Failed to read the last crash record: {err:?}");
+                return None;
+            }
+        };
+        match CrashRecord::parse(&record) {
+            Ok(crash) => log::info!("Telemetry: Last crash ({:?}): {}", crash.kind, crash.message),
+            Err(err) => log::warn!("Telemetry: The last crash record is invalid: {err:?}"),
+        }
+
+        let buffer = allocate_runtime_buffer(self.boot_services, record.len()).ok()?;
+        buffer.copy_from_slice(&record);
+        Some(RegionDescriptor::new(&CRASH_RECORD_REGION, buffer.as_ptr() as efi::PhysicalAddress, record.len() as u64))
+    }
+}
+
+/// Allocates a zeroed buffer of `size` bytes that is never freed, from memory the operating system preserves.
+#[coverage(off)] // Requires boot services.
+fn allocate_runtime_buffer(boot_services: &StandardBootServices, size: usize) -> Result<&'static mut [u8], EfiError> {
+    let buffer = boot_services.allocate_pool(EfiMemoryType::RuntimeServicesData, size)?;
+    // SAFETY: `allocate_pool` returned a valid allocation of `size` bytes that is never freed.
+    let buffer = unsafe { core::slice::from_raw_parts_mut(buffer, size) };
+    buffer.fill(0);
+    Ok(buffer)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::table::TableReader;
+    use alloc::vec;
+
+    fn leaked_buffer(size: usize) -> &'static mut [u8] {
+        vec![0xFF; size].leak()
+    }
+
+    #[test]
+    fn test_refresh_describes_known_regions() {
+        let mut state = TelemetryState::new(leaked_buffer(table::table_size(MAX_REGIONS)));
+        state.refresh(None).unwrap();
+        assert_eq!(TableReader::new(state.table).unwrap().regions().count(), 0);
+
+        state.memory_log = Some(RegionDescriptor::new(&MEMORY_LOG_REGION, 0x1000, 0x100));
+        state.crash_record = Some(RegionDescriptor::new(&CRASH_RECORD_REGION, 0x3000, 0x20));
+        state.refresh(None).unwrap();
+        let kinds: Vec<_> = TableReader::new(state.table).unwrap().regions().map(|region| region.kind()).collect();
+        assert_eq!(kinds, [MEMORY_LOG_REGION, CRASH_RECORD_REGION]);
+
+        state.refresh(Some(RegionDescriptor::new(&BOOT_JOURNAL_REGION, 0x2000, 0x400))).unwrap();
+        let reader = TableReader::new(state.table).unwrap();
+        let kinds: Vec<_> = reader.regions().map(|region| region.kind()).collect();
+        assert_eq!(kinds, [MEMORY_LOG_REGION, BOOT_JOURNAL_REGION, CRASH_RECORD_REGION]);
+        assert_eq!(reader.find(&BOOT_JOURNAL_REGION).unwrap().size, 0x400);
+    }
+}
@@ -0,0 +1,299 @@
+//! The last crash record.
+//!
+//! A crash is recorded from the crash path, such as the platform panic handler, into the [`CRASH_VARIABLE_NAME`]
+//! variable. The variable is non-volatile and accessible at runtime, so the record survives the reset that follows a
+//! crash and the operating system can read it with `GetVariable()`. On the next boot the
+//! [`TelemetryProvider`](crate::component::TelemetryProvider) also copies it into the
+//! [`CRASH_RECORD_REGION`](crate::table::CRASH_RECORD_REGION) of the telemetry table. The record is kept until the
+//! next crash replaces it or the operating system deletes the variable.
+//!
+//! ## Format
+//!
+//! A [`CrashRecordHeader`] followed by `message_size` bytes of UTF-8 text.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::string::String;
+use core::{mem::size_of, panic::PanicInfo};
+use patina::{
+    error::EfiError,
+    log::raw::RawBuffer,
+    runtime_services::{RuntimeServices, StandardRuntimeServices, variables::VariableAttributes},
+};
+use r_efi::efi;
+use zerocopy::{FromBytes, IntoBytes};
+use zerocopy_derive::*;
+
+/// Vendor GUID of the telemetry variables.
+///
+/// `{4a9e2c71-b5d3-4f08-8e6a-93c1d7b05f2e}`
+pub const TELEMETRY_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x4a9e2c71, 0xb5d3, 0x4f08, 0x8e, 0x6a, &[0x93, 0xc1, 0xd7, 0xb0, 0x5f, 0x2e]);
+
+/// Name of the variable holding the last crash record.
+pub const CRASH_VARIABLE_NAME: &str = "LastCrash";
+
+/// Signature of a crash record ("PCRS").
+pub const CRASH_RECORD_SIGNATURE: u32 = u32::from_le_bytes(*b"PCRS");
+
+/// Current version of the crash record layout.
+pub const CRASH_RECORD_VERSION: u16 = 1;
+
+/// Maximum size of a crash record, including its header. Longer messages are truncated.
+pub const MAX_CRASH_RECORD_SIZE: usize = 512;
+
+/// Attributes of the crash record variable.
+const CRASH_VARIABLE_ATTRIBUTES: VariableAttributes = VariableAttributes::from_bits(
+    efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS,
+)
+.unwrap();
+
+/// [`CRASH_VARIABLE_NAME`] as a null-terminated UCS-2 string, so that recording a crash does not allocate.
+const CRASH_VARIABLE_NAME_UCS2: [u16; CRASH_VARIABLE_NAME.len() + 1] = {
+    let mut name = [0; CRASH_VARIABLE_NAME.len() + 1];
+    let mut i = 0;
+    while i < CRASH_VARIABLE_NAME.len() {
+        name[i] = CRASH_VARIABLE_NAME.as_bytes()[i] as u16;
+        i += 1;
+    }
+    name
+};
+
+/// The runtime services crashes are recorded with, set by the [`TelemetryProvider`](crate::component::TelemetryProvider).
+static RUNTIME_SERVICES: spin::Once<StandardRuntimeServices> = spin::Once::new();
+
+/// Header of a serialized crash record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct CrashRecordHeader {
+    /// Must be [`CRASH_RECORD_SIGNATURE`].
+    pub signature: u32,
+    /// Layout version of the record.
+    pub version: u16,
+    /// Size of this header in bytes; the message starts at this offset.
+    pub header_size: u16,
+    /// The [`CrashKind`] of the crash.
+    pub kind: u32,
+    /// Size of the message in bytes.
+    pub message_size: u32,
+}
+
+/// What caused a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashKind {
+    /// A Rust panic.
+    Panic,
+    /// An unhandled CPU exception.
+    Exception,
+    /// A crash kind not known to this version.
+    Other(u32),
+}
+
+impl From<CrashKind> for u32 {
+    fn from(kind: CrashKind) -> Self {
+        match kind {
+            CrashKind::Panic => 1,
+            CrashKind::Exception => 2,
+            CrashKind::Other(kind) => kind,
+        }
+    }
+}
+
+impl From<u32> for CrashKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            1 => CrashKind::Panic,
+            2 => CrashKind::Exception,
+            kind => CrashKind::Other(kind),
+        }
+    }
+}
+
+/// A parsed crash record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashRecord {
+    /// What caused the crash.
+    pub kind: CrashKind,
+    /// Description of the crash, such as the panic location and message.
+    pub message: String,
+}
+
+impl CrashRecord {
+    /// Parses a serialized crash record.
+    ///
+    /// ## Errors
+    ///
+    /// - [`EfiError::BufferTooSmall`] if `buffer` does not contain the complete record.
+    /// - [`EfiError::InvalidParameter`] if `buffer` is not a crash record, or its message is not UTF-8.
+    /// - [`EfiError::Unsupported`] if the record layout is newer than this parser.
+    pub fn parse(buffer: &[u8]) -> Result<Self, EfiError> {
+        let (header, _) = CrashRecordHeader::read_from_prefix(buffer).map_err(|_| EfiError::BufferTooSmall)?;
+
+        if header.signature != CRASH_RECORD_SIGNATURE || (header.header_size as usize) < size_of::<CrashRecordHeader>()
+        {
+            return Err(EfiError::InvalidParameter);
+        }
+        if header.version > CRASH_RECORD_VERSION {
+            return Err(EfiError::Unsupported);
+        }
+
+        let start = header.header_size as usize;
+        let message = buffer.get(start..start + header.message_size as usize).ok_or(EfiError::BufferTooSmall)?;
+        let message = core::str::from_utf8(message).map_err(|_| EfiError::InvalidParameter)?;
+        Ok(Self { kind: header.kind.into(), message: message.into() })
+    }
+}
+
+/// Serializes a crash record into `buffer`, returning its size. A message that does not fit is truncated on a
+/// character boundary.
+///
+/// ## Errors
+///
+/// Returns [`EfiError::BufferTooSmall`] if `buffer` cannot hold the record header.
+pub fn write_record(kind: CrashKind, message: &str, buffer: &mut [u8]) -> Result<usize, EfiError> {
+    let (header_bytes, message_bytes) =
+        buffer.split_at_mut_checked(size_of::<CrashRecordHeader>()).ok_or(EfiError::BufferTooSmall)?;
+
+    let mut len = message.len().min(message_bytes.len());
+    while !message.is_char_boundary(len) {
+        len -= 1;
+    }
+    message_bytes[..len].copy_from_slice(&message.as_bytes()[..len]);
+
+    let header = CrashRecordHeader {
+        signature: CRASH_RECORD_SIGNATURE,
+        version: CRASH_RECORD_VERSION,
+        header_size: size_of::<CrashRecordHeader>() as u16,
+        kind: kind.into(),
+        message_size: len as u32,
+    };
+    header_bytes.copy_from_slice(header.as_bytes());
+    Ok(size_of::<CrashRecordHeader>() + len)
+}
+
+/// Sets the runtime services that crashes are recorded with.
+pub(crate) fn init(runtime_services: &StandardRuntimeServices) {
+    RUNTIME_SERVICES.call_once(|| runtime_services.clone());
+}
+
+/// Records a crash as the last crash record.
+///
+/// This does not allocate, so it can be called from crash paths. Crashes can only be recorded once the
+/// [`TelemetryProvider`](crate::component::TelemetryProvider) has been dispatched and the variable services can write
+/// non-volatile variables.
+///
+/// ## Errors
+///
+/// Returns [`EfiError::NotReady`] if the telemetry provider has not been dispatched, or the error of `SetVariable()`.
+pub fn record_crash(kind: CrashKind, message: &str) -> Result<(), EfiError> {
+    let runtime_services = RUNTIME_SERVICES.get().ok_or(EfiError::NotReady)?;
+    write_crash_variable(runtime_services, kind, message)
+}
+
+/// Records a panic as the last crash record, with its location and, if it has no arguments, its message.
+///
+/// Like [`patina::log::raw::RawBuffer`], this does not run arbitrary formatting code, so it is safe to call from the
+/// panic handler. See [`record_crash`].
+pub fn record_panic(info: &PanicInfo<'_>) -> Result<(), EfiError> {
+    let mut message = RawBuffer::<{ MAX_CRASH_RECORD_SIZE - size_of::<CrashRecordHeader>() }>::new();
+    message.panic_location(info);
+    if let Some(text) = info.message().as_str() {
+        message.str(": ").str(text);
+    }
+    record_crash(CrashKind::Panic, message.as_str())
+}
+
+/// Writes a crash record to the crash variable with `runtime_services`, without allocating.
+fn write_crash_variable(
+    runtime_services: &impl RuntimeServices,
+    kind: CrashKind,
+    message: &str,
+) -> Result<(), EfiError> {
+    let mut record = [0; MAX_CRASH_RECORD_SIZE];
+    let size = write_record(kind, message, &mut record)?;
+    let mut name = CRASH_VARIABLE_NAME_UCS2;
+    // SAFETY: `name` is a null-terminated UCS-2 string.
+    unsafe {
+        runtime_services.set_variable_unchecked(
+            &mut name,
+            &TELEMETRY_VARIABLE_GUID,
+            CRASH_VARIABLE_ATTRIBUTES.bits(),
+            &record[..size],
+        )
+    }
+    .map_err(Into::into)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use alloc::vec;
+    use patina::runtime_services::MockRuntimeServices;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut buffer = [0; 64];
+        let size = write_record(CrashKind::Exception, "#PF at 0x1000", &mut buffer).unwrap();
+        assert_eq!(size, size_of::<CrashRecordHeader>() + 13);
+        assert_eq!(
+            CrashRecord::parse(&buffer[..size]),
+            Ok(CrashRecord { kind: CrashKind::Exception, message: "#PF at 0x1000".into() })
+        );
+        assert_eq!(CrashRecord::parse(&buffer[..size - 1]), Err(EfiError::BufferTooSmall));
+        assert_eq!(CrashRecord::parse(&buffer[..4]), Err(EfiError::BufferTooSmall));
+
+        let mut bad = buffer;
+        bad[0] = 0;
+        assert_eq!(CrashRecord::parse(&bad), Err(EfiError::InvalidParameter));
+        let mut bad = buffer;
+        bad[4..6].copy_from_slice(&(CRASH_RECORD_VERSION + 1).to_le_bytes());
+        assert_eq!(CrashRecord::parse(&bad), Err(EfiError::Unsupported));
+        let mut bad = buffer;
+        bad[size_of::<CrashRecordHeader>()] = 0xFF;
+        assert_eq!(CrashRecord::parse(&bad), Err(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn test_write_record_truncates_on_char_boundary() {
+        let mut buffer = vec![0; size_of::<CrashRecordHeader>() + 4];
+        let size = write_record(CrashKind::Panic, "abc\u{e9}", &mut buffer).unwrap();
+        assert_eq!(CrashRecord::parse(&buffer[..size]).unwrap().message, "abc");
+        assert_eq!(write_record(CrashKind::Panic, "", &mut buffer[..8]), Err(EfiError::BufferTooSmall));
+        assert_eq!(CrashKind::from(7), CrashKind::Other(7));
+        assert_eq!(u32::from(CrashKind::Other(7)), 7);
+    }
+
+    #[test]
+    fn test_write_crash_variable() {
+        let written = Arc::new(Mutex::new(vec![]));
+        let mut runtime_services = MockRuntimeServices::new();
+        let record = written.clone();
+        runtime_services.expect_set_variable_unchecked().once().returning(move |name, namespace, attributes, data| {
+            assert_eq!(name, CRASH_VARIABLE_NAME.encode_utf16().chain([0]).collect::<vec::Vec<_>>());
+            assert_eq!(*namespace, TELEMETRY_VARIABLE_GUID);
+            assert_eq!(attributes, 0x7);
+            *record.lock().unwrap() = data.to_vec();
+            Ok(())
+        });
+
+        let message = "x".repeat(MAX_CRASH_RECORD_SIZE);
+        write_crash_variable(&runtime_services, CrashKind::Panic, &message).unwrap();
+        let written = written.lock().unwrap();
+        assert_eq!(written.len(), MAX_CRASH_RECORD_SIZE);
+        let record = CrashRecord::parse(&written).unwrap();
+        assert_eq!(record.kind, CrashKind::Panic);
+        assert_eq!(record.message.len(), MAX_CRASH_RECORD_SIZE - size_of::<CrashRecordHeader>());
+    }
+
+    #[test]
+    fn test_record_crash_requires_runtime_services() {
+        assert_eq!(record_crash(CrashKind::Panic, "early"), Err(EfiError::NotReady));
+    }
+}
@@ -0,0 +1,44 @@
+//! Operating system access to firmware logs and crash data.
+//!
+//! After boot, the operating system can retrieve the advanced logger memory log, the boot journal, and the record of
+//! the last firmware crash. The [`TelemetryProvider`](component::TelemetryProvider) component installs the
+//! [`TELEMETRY_TABLE_GUID`](table::TELEMETRY_TABLE_GUID) configuration table, a directory of the runtime memory
+//! regions that hold each of them, and the platform panic handler records crashes with [`crash::record_panic`] into the
+//! [`CRASH_VARIABLE_NAME`](crash::CRASH_VARIABLE_NAME) variable, which the operating system can read with the runtime
+//! variable services. With the `std` feature, the [`reader`] module reads both from the host.
+//!
+//! ## Integration Example
+//!
+//! ```rust,ignore
+//! Core::default()
+//!  // ...
+//!  .with_component(patina_boot_journal::component::BootJournalProvider::new(0x10000))
+//!  .with_component(patina_telemetry::component::TelemetryProvider::new())
+//!  .start()
+//!  .unwrap();
+//!
+//! #[panic_handler]
+//! fn panic(info: &core::panic::PanicInfo) -> ! {
+//!     log::error!("{}", info);
+//!     let _ = patina_telemetry::crash::record_panic(info);
+//!     loop {}
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+pub mod crash;
+pub mod table;
+
+#[cfg(any(test, feature = "std"))]
+pub mod reader;
@@ -0,0 +1,195 @@
+//! Host-side reader for the telemetry handed to the operating system.
+//!
+//! The [`TelemetryReader`] reads the telemetry table and the regions it describes from physical memory, for example
+//! through `/dev/mem` on Linux, given the address of the [`TELEMETRY_TABLE_GUID`](crate::table::TELEMETRY_TABLE_GUID)
+//! configuration table. [`read_crash_variable`] reads the last crash record from `efivarfs`, which does not need
+//! access to physical memory.
+//!
+//! ```rust,ignore
+//! let mut reader = TelemetryReader::open(File::open("/dev/mem")?, table_address)?;
+//! if let Some(log) = reader.memory_log()? {
+//!     patina_adv_logger::parser::Parser::open(&log)?.write_log(&mut std::io::stdout())?;
+//! }
+//! if let Some(crash) = read_crash_variable(Path::new("/sys/firmware/efi/efivars"))? {
+//!     println!("Last crash: {}", crash.message);
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate std;
+
+use alloc::{format, vec, vec::Vec};
+use core::mem::size_of;
+use patina::error::EfiError;
+use r_efi::efi;
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+use zerocopy::FromBytes;
+
+use crate::{
+    crash::{CRASH_VARIABLE_NAME, CrashRecord, TELEMETRY_VARIABLE_GUID},
+    table::{BOOT_JOURNAL_REGION, CRASH_RECORD_REGION, MEMORY_LOG_REGION, RegionDescriptor, TableHeader, TableReader},
+};
+
+/// Largest telemetry table the reader accepts, so that a corrupt header cannot make it read unbounded memory.
+const MAX_TABLE_SIZE: usize = 0x10000;
+
+/// Size of the attributes that prefix the data of an `efivarfs` file.
+const EFIVARFS_ATTRIBUTES_SIZE: usize = size_of::<u32>();
+
+/// Read access to physical memory. Offsets into the stream are physical addresses.
+pub trait PhysicalMemory {
+    /// Fills `buffer` with the memory at `address`.
+    fn read_at(&mut self, address: u64, buffer: &mut [u8]) -> io::Result<()>;
+}
+
+impl<T: Read + Seek> PhysicalMemory for T {
+    fn read_at(&mut self, address: u64, buffer: &mut [u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(address))?;
+        self.read_exact(buffer)
+    }
+}
+
+/// Reads the telemetry table and the regions it describes.
+pub struct TelemetryReader<M: PhysicalMemory> {
+    memory: M,
+    regions: Vec<RegionDescriptor>,
+}
+
+impl<M: PhysicalMemory> TelemetryReader<M> {
+    /// Reads the telemetry table at `table_address`.
+    pub fn open(mut memory: M, table_address: u64) -> io::Result<Self> {
+        let mut header = vec![0; size_of::<TableHeader>()];
+        memory.read_at(table_address, &mut header)?;
+        let (header, _) = TableHeader::read_from_prefix(&header).map_err(|_| invalid_data(EfiError::BufferTooSmall))?;
+
+        let size = TableReader::size(&header).map_err(invalid_data)?;
+        if size > MAX_TABLE_SIZE {
+            return Err(invalid_data(EfiError::BadBufferSize));
+        }
+        let mut table = vec![0; size];
+        memory.read_at(table_address, &mut table)?;
+        let regions = TableReader::new(&table).map_err(invalid_data)?.regions().collect();
+        Ok(Self { memory, regions })
+    }
+
+    /// Returns the regions described by the table.
+    pub fn regions(&self) -> &[RegionDescriptor] {
+        &self.regions
+    }
+
+    /// Reads the first region of `kind`, if the table describes one.
+    pub fn read_region(&mut self, kind: &efi::Guid) -> io::Result<Option<Vec<u8>>> {
+        let Some(region) = self.regions.iter().find(|region| region.kind() == *kind) else {
+            return Ok(None);
+        };
+        let size = usize::try_from(region.size).map_err(|_| invalid_data(EfiError::BadBufferSize))?;
+        let mut buffer = vec![0; size];
+        self.memory.read_at(region.address, &mut buffer)?;
+        Ok(Some(buffer))
+    }
+
+    /// Reads the advanced logger memory log, in the format read by `patina_adv_logger::parser::Parser`.
+    pub fn memory_log(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.read_region(&MEMORY_LOG_REGION)
+    }
+
+    /// Reads the boot journal, in the format read by `patina_boot_journal::journal::JournalReader`.
+    pub fn boot_journal(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.read_region(&BOOT_JOURNAL_REGION)
+    }
+
+    /// Reads and parses the last crash record.
+    pub fn last_crash(&mut self) -> io::Result<Option<CrashRecord>> {
+        self.read_region(&CRASH_RECORD_REGION)?
+            .map(|record| CrashRecord::parse(&record).map_err(invalid_data))
+            .transpose()
+    }
+}
+
+/// Reads the last crash record from the `efivarfs` mounted at `efivars`, such as `/sys/firmware/efi/efivars`.
+pub fn read_crash_variable(efivars: &Path) -> io::Result<Option<CrashRecord>> {
+    let name = format!("{CRASH_VARIABLE_NAME}-{:x}", patina::Guid::from_ref(&TELEMETRY_VARIABLE_GUID));
+    let data = match std::fs::read(efivars.join(name)) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let record = data.get(EFIVARFS_ATTRIBUTES_SIZE..).ok_or_else(|| invalid_data(EfiError::BufferTooSmall))?;
+    CrashRecord::parse(record).map(Some).map_err(invalid_data)
+}
+
+fn invalid_data(err: EfiError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{err:?}"))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        crash::{CrashKind, write_record},
+        table::{table_size, write_table},
+    };
+    use std::io::Cursor;
+
+    /// Returns memory holding a telemetry table at 0x100 that describes a memory log and a crash record.
+    fn memory() -> Cursor<Vec<u8>> {
+        let mut memory = vec![0; 0x400];
+        memory[0x200..0x210].copy_from_slice(b"memory log bytes");
+        let size = write_record(CrashKind::Panic, "panicked at core.rs:1:1", &mut memory[0x300..]).unwrap();
+        let regions = [
+            RegionDescriptor::new(&MEMORY_LOG_REGION, 0x200, 0x10),
+            RegionDescriptor::new(&CRASH_RECORD_REGION, 0x300, size as u64),
+        ];
+        write_table(&mut memory[0x100..0x100 + table_size(2)], &regions).unwrap();
+        Cursor::new(memory)
+    }
+
+    #[test]
+    fn test_reader_reads_regions() {
+        let mut reader = TelemetryReader::open(memory(), 0x100).unwrap();
+        assert_eq!(reader.regions().len(), 2);
+        assert_eq!(reader.memory_log().unwrap().unwrap(), b"memory log bytes");
+        assert_eq!(reader.boot_journal().unwrap(), None);
+        assert_eq!(
+            reader.last_crash().unwrap(),
+            Some(CrashRecord { kind: CrashKind::Panic, message: "panicked at core.rs:1:1".into() })
+        );
+    }
+
+    #[test]
+    fn test_reader_rejects_invalid_table() {
+        assert_eq!(TelemetryReader::open(memory(), 0x108).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(TelemetryReader::open(memory(), 0x3F8).err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut huge = memory();
+        huge.get_mut()[0x108..0x10C].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(TelemetryReader::open(huge, 0x100).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_read_crash_variable() {
+        let efivars = std::env::temp_dir().join(format!("patina_telemetry_efivars_{}", std::process::id()));
+        std::fs::create_dir_all(&efivars).unwrap();
+        assert_eq!(read_crash_variable(&efivars).unwrap(), None);
+
+        let mut data = vec![0; 0x100];
+        data[..EFIVARFS_ATTRIBUTES_SIZE].copy_from_slice(&7u32.to_le_bytes());
+        let size = write_record(CrashKind::Exception, "#GP", &mut data[EFIVARFS_ATTRIBUTES_SIZE..]).unwrap();
+        data.truncate(EFIVARFS_ATTRIBUTES_SIZE + size);
+        let path = efivars.join("LastCrash-4a9e2c71-b5d3-4f08-8e6a-93c1d7b05f2e");
+        std::fs::write(&path, &data).unwrap();
+
+        let crash = read_crash_variable(&efivars);
+        std::fs::remove_dir_all(&efivars).unwrap();
+        assert_eq!(crash.unwrap(), Some(CrashRecord { kind: CrashKind::Exception, message: "#GP".into() }));
+    }
+}
@@ -0,0 +1,255 @@
+//! The telemetry table: a directory of the firmware telemetry regions handed to the operating system.
+//!
+//! ## Format
+//!
+//! ```text
+//! +---------------------+
+//! | TableHeader         |  signature, version, header size, region count, descriptor size
+//! +---------------------+
+//! | RegionDescriptor    |  kind GUID, physical address, size
+//! +---------------------+
+//! | ...                 |
+//! +---------------------+
+//! ```
+//!
+//! Readers must use `header_size` and `descriptor_size` to locate the descriptors, so that later versions can extend
+//! both structures.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::mem::size_of;
+use patina::error::EfiError;
+use r_efi::efi;
+use zerocopy::{FromBytes, IntoBytes};
+use zerocopy_derive::*;
+
+/// GUID of the configuration table that points to the telemetry table.
+///
+/// `{d2b6a8f1-3c47-4e9d-8b15-7a0e6c3f2d84}`
+pub const TELEMETRY_TABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xd2b6a8f1, 0x3c47, 0x4e9d, 0x8b, 0x15, &[0x7a, 0x0e, 0x6c, 0x3f, 0x2d, 0x84]);
+
+/// Region kind of the advanced logger memory log, in the format read by `patina_adv_logger::parser::Parser`.
+///
+/// `{5f0e9c3a-2b71-4d8e-a64f-1c93b7d20e56}`
+pub const MEMORY_LOG_REGION: efi::Guid =
+    efi::Guid::from_fields(0x5f0e9c3a, 0x2b71, 0x4d8e, 0xa6, 0x4f, &[0x1c, 0x93, 0xb7, 0xd2, 0x0e, 0x56]);
+
+/// Region kind of the published boot journal, in the format read by `patina_boot_journal::journal::JournalReader`.
+///
+/// `{8c4d27e5-91fa-4b3c-b0d8-e25f6a7c1394}`
+pub const BOOT_JOURNAL_REGION: efi::Guid =
+    efi::Guid::from_fields(0x8c4d27e5, 0x91fa, 0x4b3c, 0xb0, 0xd8, &[0xe2, 0x5f, 0x6a, 0x7c, 0x13, 0x94]);
+
+/// Region kind of the last crash record, in the format read by [`CrashRecord::parse`](crate::crash::CrashRecord::parse).
+///
+/// `{e7a31b0c-64d2-4f85-9c2e-4b8d0f6a5e17}`
+pub const CRASH_RECORD_REGION: efi::Guid =
+    efi::Guid::from_fields(0xe7a31b0c, 0x64d2, 0x4f85, 0x9c, 0x2e, &[0x4b, 0x8d, 0x0f, 0x6a, 0x5e, 0x17]);
+
+/// Signature of a telemetry table ("PTEL").
+pub const TABLE_SIGNATURE: u32 = u32::from_le_bytes(*b"PTEL");
+
+/// Current version of the telemetry table layout.
+pub const TABLE_VERSION: u16 = 1;
+
+/// Header of the telemetry table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct TableHeader {
+    /// Must be [`TABLE_SIGNATURE`].
+    pub signature: u32,
+    /// Layout version of the table.
+    pub version: u16,
+    /// Size of this header in bytes; descriptors start at this offset.
+    pub header_size: u16,
+    /// Number of region descriptors following the header.
+    pub region_count: u32,
+    /// Size of each region descriptor in bytes.
+    pub descriptor_size: u32,
+}
+
+/// Describes a region of memory holding telemetry of one kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct RegionDescriptor {
+    /// GUID identifying the kind and format of the region, such as [`MEMORY_LOG_REGION`].
+    pub kind: [u8; 16],
+    /// Physical address of the region.
+    pub address: u64,
+    /// Size of the region in bytes.
+    pub size: u64,
+}
+
+impl RegionDescriptor {
+    /// Creates a descriptor of a region of `kind`.
+    pub fn new(kind: &efi::Guid, address: efi::PhysicalAddress, size: u64) -> Self {
+        Self { kind: *kind.as_bytes(), address, size }
+    }
+
+    /// Returns the kind of the region.
+    pub fn kind(&self) -> efi::Guid {
+        efi::Guid::from_bytes(&self.kind)
+    }
+}
+
+/// Returns the size of a telemetry table with `region_count` regions.
+pub const fn table_size(region_count: usize) -> usize {
+    size_of::<TableHeader>() + region_count * size_of::<RegionDescriptor>()
+}
+
+/// Writes a telemetry table describing `regions` into `buffer`, returning the size of the table.
+///
+/// ## Errors
+///
+/// Returns [`EfiError::BufferTooSmall`] if `buffer` cannot hold the table.
+pub fn write_table(buffer: &mut [u8], regions: &[RegionDescriptor]) -> Result<usize, EfiError> {
+    let size = table_size(regions.len());
+    let buffer = buffer.get_mut(..size).ok_or(EfiError::BufferTooSmall)?;
+    let header = TableHeader {
+        signature: TABLE_SIGNATURE,
+        version: TABLE_VERSION,
+        header_size: size_of::<TableHeader>() as u16,
+        region_count: regions.len() as u32,
+        descriptor_size: size_of::<RegionDescriptor>() as u32,
+    };
+    let (header_bytes, descriptors) = buffer.split_at_mut(size_of::<TableHeader>());
+    header_bytes.copy_from_slice(header.as_bytes());
+    descriptors.copy_from_slice(regions.as_bytes());
+    Ok(size)
+}
+
+/// Validates and parses a telemetry table.
+#[derive(Debug, Clone, Copy)]
+pub struct TableReader<'a> {
+    header: TableHeader,
+    descriptors: &'a [u8],
+}
+
+impl<'a> TableReader<'a> {
+    /// Validates the header of the telemetry table in `buffer`.
+    ///
+    /// ## Errors
+    ///
+    /// - [`EfiError::BufferTooSmall`] if `buffer` does not contain the complete table.
+    /// - [`EfiError::InvalidParameter`] if `buffer` does not start with a telemetry table header.
+    /// - [`EfiError::Unsupported`] if the table layout is newer than this reader.
+    pub fn new(buffer: &'a [u8]) -> Result<Self, EfiError> {
+        let (header, _) = TableHeader::read_from_prefix(buffer).map_err(|_| EfiError::BufferTooSmall)?;
+        let size = Self::size(&header)?;
+        let descriptors = buffer.get(header.header_size as usize..size).ok_or(EfiError::BufferTooSmall)?;
+        Ok(Self { header, descriptors })
+    }
+
+    /// Validates `header` and returns the size of the table it describes, including the header.
+    ///
+    /// ## Errors
+    ///
+    /// - [`EfiError::InvalidParameter`] if `header` is not a valid telemetry table header.
+    /// - [`EfiError::Unsupported`] if the table layout is newer than this reader.
+    pub fn size(header: &TableHeader) -> Result<usize, EfiError> {
+        if header.signature != TABLE_SIGNATURE {
+            return Err(EfiError::InvalidParameter);
+        }
+        if header.version > TABLE_VERSION {
+            return Err(EfiError::Unsupported);
+        }
+        if (header.header_size as usize) < size_of::<TableHeader>()
+            || (header.descriptor_size as usize) < size_of::<RegionDescriptor>()
+        {
+            return Err(EfiError::InvalidParameter);
+        }
+        (header.region_count as usize)
+            .checked_mul(header.descriptor_size as usize)
+            .and_then(|size| size.checked_add(header.header_size as usize))
+            .ok_or(EfiError::InvalidParameter)
+    }
+
+    /// Returns the table header.
+    pub fn header(&self) -> &TableHeader {
+        &self.header
+    }
+
+    /// Returns an iterator over the region descriptors.
+    pub fn regions(&self) -> impl Iterator<Item = RegionDescriptor> + 'a {
+        self.descriptors
+            .chunks_exact(self.header.descriptor_size as usize)
+            .filter_map(|descriptor| RegionDescriptor::read_from_prefix(descriptor).ok().map(|(region, _)| region))
+    }
+
+    /// Returns the first region of `kind`, if any.
+    pub fn find(&self, kind: &efi::Guid) -> Option<RegionDescriptor> {
+        self.regions().find(|region| region.kind() == *kind)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    #[test]
+    fn test_table_round_trip() {
+        let regions = [
+            RegionDescriptor::new(&MEMORY_LOG_REGION, 0x8000_0000, 0x2_0000),
+            RegionDescriptor::new(&CRASH_RECORD_REGION, 0x9000_0000, 0x40),
+        ];
+        let mut buffer = vec![0xFF; table_size(3)];
+        assert_eq!(write_table(&mut buffer, &regions), Ok(table_size(2)));
+
+        let reader = TableReader::new(&buffer).unwrap();
+        assert_eq!(reader.header().region_count, 2);
+        assert_eq!(reader.regions().collect::<Vec<_>>(), regions);
+        assert_eq!(reader.find(&CRASH_RECORD_REGION).map(|region| region.address), Some(0x9000_0000));
+        assert_eq!(reader.find(&BOOT_JOURNAL_REGION), None);
+        assert_eq!(reader.regions().next().unwrap().kind(), MEMORY_LOG_REGION);
+
+        assert_eq!(write_table(&mut buffer[..table_size(1)], &regions), Err(EfiError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_reader_rejects_invalid_tables() {
+        let mut buffer = vec![0; table_size(1)];
+        write_table(&mut buffer, &[RegionDescriptor::new(&MEMORY_LOG_REGION, 0x1000, 0x10)]).unwrap();
+
+        assert_eq!(TableReader::new(&buffer[..4]).unwrap_err(), EfiError::BufferTooSmall);
+        assert_eq!(TableReader::new(&buffer[..table_size(1) - 1]).unwrap_err(), EfiError::BufferTooSmall);
+
+        let mut bad = buffer.clone();
+        bad[0] = b'X';
+        assert_eq!(TableReader::new(&bad).unwrap_err(), EfiError::InvalidParameter);
+
+        let mut bad = buffer.clone();
+        bad[4..6].copy_from_slice(&(TABLE_VERSION + 1).to_le_bytes());
+        assert_eq!(TableReader::new(&bad).unwrap_err(), EfiError::Unsupported);
+
+        let mut bad = buffer.clone();
+        bad[12..16].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(TableReader::new(&bad).unwrap_err(), EfiError::InvalidParameter);
+    }
+
+    #[test]
+    fn test_reader_skips_extended_fields() {
+        // A later version with a larger header and larger descriptors.
+        let header = TableHeader {
+            signature: TABLE_SIGNATURE,
+            version: TABLE_VERSION,
+            header_size: size_of::<TableHeader>() as u16 + 8,
+            region_count: 1,
+            descriptor_size: size_of::<RegionDescriptor>() as u32 + 8,
+        };
+        let region = RegionDescriptor::new(&BOOT_JOURNAL_REGION, 0x2000, 0x100);
+        let mut buffer = header.as_bytes().to_vec();
+        buffer.extend_from_slice(&[0; 8]);
+        buffer.extend_from_slice(region.as_bytes());
+        buffer.extend_from_slice(&[0; 8]);
+
+        let reader = TableReader::new(&buffer).unwrap();
+        assert_eq!(reader.regions().collect::<Vec<_>>(), [region]);
+    }
+}
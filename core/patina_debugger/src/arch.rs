@@ -31,6 +31,9 @@ cfg_if::cfg_if! {
     }
 }
 
+#[cfg(any(target_arch = "aarch64", test))]
+mod aarch64_branch;
+
 /// Trait for architecture specific debugger implementations.
 ///
 /// This trait abstracts the architecture specifics for the debugger. As these
@@ -41,7 +44,8 @@ pub trait DebuggerArch {
     const DEFAULT_EXCEPTION_TYPES: &'static [usize];
     const BREAKPOINT_INSTRUCTION: &'static [u8];
     const GDB_TARGET_XML: &'static str;
-    const GDB_REGISTERS_XML: &'static str;
+    /// The register feature descriptions included by the target description, by annex name.
+    const GDB_FEATURES_XML: &'static [(&'static str, &'static str)];
 
     type PageTable: PageTable;
 
@@ -59,6 +63,13 @@ pub trait DebuggerArch {
     /// Enables the architecture specific single step.
     fn set_single_step(exception_info: &mut ExceptionInfo);
 
+    /// Returns the address of the instruction executed after the one at the exception
+    /// address, if it can be determined. This is used to single step by placing a
+    /// temporary breakpoint instead of using the architecture specific single step.
+    fn next_instruction_address(_context: &ExceptionContext) -> Option<u64> {
+        None
+    }
+
     /// Initializes the architecture specific state for the debugger.
     fn initialize();
 
@@ -97,3 +108,14 @@ pub trait UefiArchRegs: Sized {
         *self = Self::from_context(context);
     }
 }
+
+pub trait UefiArchRegId {
+    /// Reads the register from a UEFI context structure into the buffer, returning
+    /// the number of bytes written or 0 if the register is unavailable. Registers
+    /// not held by the context structure are read from the processor.
+    fn read_from_context(&self, context: &ExceptionContext, buf: &mut [u8]) -> Result<usize, ()>;
+
+    /// Writes the register to a UEFI context structure. Registers not held by the
+    /// context structure are written to the processor.
+    fn write_to_context(&self, context: &mut ExceptionContext, val: &[u8]) -> Result<(), ()>;
+}
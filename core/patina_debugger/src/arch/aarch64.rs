@@ -9,9 +9,9 @@ use gdbstub::arch::{RegId, Registers};
 use patina::{read_sysreg, write_sysreg};
 use patina_internal_cpu::interrupts::ExceptionContext;
 
-use crate::{ExceptionInfo, ExceptionType};
+use crate::{ExceptionInfo, ExceptionType, FaultCause};
 
-use super::{DebuggerArch, UefiArchRegs};
+use super::{DebuggerArch, UefiArchRegId, UefiArchRegs, aarch64_branch};
 use bitfield_struct::bitfield;
 
 pub enum Aarch64Arch {}
@@ -41,8 +41,33 @@ const OS_LOCK_STATUS_LOCKED: u64 = 0x2;
 
 const DAIF_DEBUG_MASK: u64 = 0x200;
 
+const CURRENT_EL_EL2: u64 = 0x8;
+
 static POKE_TEST_MARKER: AtomicBool = AtomicBool::new(false);
 
+/// Reads the system register of the EL the firmware runs at, given its EL2 and EL1 names.
+macro_rules! read_el_sysreg {
+    ($el2:ident, $el1:ident) => {
+        if at_el2() { read_sysreg!($el2) } else { read_sysreg!($el1) }
+    };
+}
+
+/// Writes the system register of the EL the firmware runs at, given its EL2 and EL1 names.
+macro_rules! write_el_sysreg {
+    ($el2:ident, $el1:ident, $value:expr) => {
+        if at_el2() {
+            write_sysreg!(reg $el2, $value, "isb sy")
+        } else {
+            write_sysreg!(reg $el1, $value, "isb sy")
+        }
+    };
+}
+
+/// Returns whether the firmware runs at EL2, rather than EL1.
+fn at_el2() -> bool {
+    read_sysreg!(CurrentEL) == CURRENT_EL_EL2
+}
+
 impl gdbstub::arch::Arch for Aarch64Arch {
     type Usize = u64;
     type Registers = Aarch64CoreRegs;
@@ -53,8 +78,12 @@ impl gdbstub::arch::Arch for Aarch64Arch {
 impl DebuggerArch for Aarch64Arch {
     const DEFAULT_EXCEPTION_TYPES: &'static [usize] = &[0]; // Synchronous exception
     const BREAKPOINT_INSTRUCTION: &'static [u8] = &[0x00, 0x00, 0x20, 0xD4]; // BRK #0
-    const GDB_TARGET_XML: &'static str = r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target><architecture>aarch64</architecture><xi:include href="registers.xml"/></target>"#;
-    const GDB_REGISTERS_XML: &'static str = include_str!("xml/aarch64_registers.xml");
+    const GDB_TARGET_XML: &'static str = r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target><architecture>aarch64</architecture><xi:include href="registers.xml"/><xi:include href="fpu.xml"/><xi:include href="sysregs.xml"/></target>"#;
+    const GDB_FEATURES_XML: &'static [(&'static str, &'static str)] = &[
+        ("registers.xml", include_str!("xml/aarch64_registers.xml")),
+        ("fpu.xml", include_str!("xml/aarch64_fpu.xml")),
+        ("sysregs.xml", include_str!("xml/aarch64_sysregs.xml")),
+    ];
 
     type PageTable = patina_paging::aarch64::AArch64PageTable<patina_paging::page_allocator::PageAllocatorStub>;
 
@@ -78,15 +107,16 @@ impl DebuggerArch for Aarch64Arch {
 
                     ExceptionType::Step
                 }
-                EC_BREAKPOINT_LOWER_EL
-                | EC_BREAKPOINT_CURRENT_EL
-                | EC_WATCHPOINT_LOWER_EL
-                | EC_WATCHPOINT_CURRENT_EL
-                | EC_BRK_INSTRUCTION => ExceptionType::Breakpoint,
-                EC_INST_ABORT_LOWER_EL
-                | EC_INST_ABORT_CURRENT_EL
-                | EC_DATA_ABORT_LOWER_EL
-                | EC_DATA_ABORT_CURRENT_EL => ExceptionType::AccessViolation(context.far as usize),
+                EC_WATCHPOINT_LOWER_EL | EC_WATCHPOINT_CURRENT_EL => {
+                    ExceptionType::Watchpoint(context.far, triggered_watchpoint_kind(context.far))
+                }
+                EC_BREAKPOINT_LOWER_EL | EC_BREAKPOINT_CURRENT_EL | EC_BRK_INSTRUCTION => ExceptionType::Breakpoint,
+                EC_INST_ABORT_LOWER_EL | EC_INST_ABORT_CURRENT_EL => {
+                    ExceptionType::AccessViolation(context.far as usize, FaultCause::InstructionAbort(context.esr))
+                }
+                EC_DATA_ABORT_LOWER_EL | EC_DATA_ABORT_CURRENT_EL => {
+                    ExceptionType::AccessViolation(context.far as usize, FaultCause::DataAbort(context.esr))
+                }
                 _ => ExceptionType::Other(exception_class),
             },
             instruction_pointer: context.elr,
@@ -104,12 +134,13 @@ impl DebuggerArch for Aarch64Arch {
             if unsafe { core::slice::from_raw_parts(elr, instruction_size) } == breakpoint_instruction {
                 exception_info.context.elr += instruction_size as u64;
             }
+        }
 
-            // Always clear the ICache since the debugger may have altered instructions.
-            // SAFETY: This is an architecturally defined mechanism to clear the ICache.
-            unsafe {
-                asm!("ic iallu", "isb sy");
-            }
+        // Always clear the ICache since the debugger may have altered instructions, including
+        // the temporary breakpoints used for stepping regardless of the exception type.
+        // SAFETY: This is an architecturally defined mechanism to clear the ICache.
+        unsafe {
+            asm!("ic iallu", "isb sy");
         }
     }
 
@@ -126,6 +157,18 @@ impl DebuggerArch for Aarch64Arch {
         write_sysreg!(reg mdscr_el1, mdscr_el1_reg);
     }
 
+    fn next_instruction_address(context: &ExceptionContext) -> Option<u64> {
+        let mut instruction = [0; aarch64_branch::INSTRUCTION_SIZE as usize];
+        if crate::memory::read_memory::<Self>(context.elr, &mut instruction, false) != Ok(instruction.len()) {
+            return None;
+        }
+
+        let regs = Aarch64CoreRegs::from_context(context).regs;
+        aarch64_branch::next_instruction(context.elr, u32::from_le_bytes(instruction), context.spsr, |index| {
+            regs.get(index as usize).copied().unwrap_or(0)
+        })
+    }
+
     fn initialize() {
         // Disable debug exceptions in DAIF while configuring
         let mut daif_reg = read_sysreg!(daif);
@@ -155,7 +198,9 @@ impl DebuggerArch for Aarch64Arch {
     }
 
     fn add_watchpoint(address: u64, length: u64, access_type: gdbstub::target::ext::breakpoints::WatchKind) -> bool {
-        let bas = Wcr::calculate_bas(length);
+        let Some((address, bas)) = Wcr::calculate_range(address, length) else {
+            return false;
+        };
         let lsc = Wcr::calculate_lsc(access_type);
 
         // Check for duplicates
@@ -190,7 +235,9 @@ impl DebuggerArch for Aarch64Arch {
     }
 
     fn remove_watchpoint(address: u64, length: u64, access_type: gdbstub::target::ext::breakpoints::WatchKind) -> bool {
-        let bas = Wcr::calculate_bas(length);
+        let Some((address, bas)) = Wcr::calculate_range(address, length) else {
+            return false;
+        };
         let lsc = Wcr::calculate_lsc(access_type);
 
         for i in 0..NUM_WATCHPOINTS {
@@ -214,11 +261,10 @@ impl DebuggerArch for Aarch64Arch {
     }
 
     fn get_page_table() -> Result<Self::PageTable, ()> {
-        // TODO: Check for EL1?
-        let ttbr0_el2 = read_sysreg!(ttbr0_el2);
+        let ttbr0 = read_el_sysreg!(ttbr0_el2, ttbr0_el1);
         unsafe {
             patina_paging::aarch64::AArch64PageTable::from_existing(
-                ttbr0_el2,
+                ttbr0,
                 patina_paging::page_allocator::PageAllocatorStub,
                 patina_paging::PagingType::Paging4Level,
             )
@@ -235,7 +281,7 @@ impl DebuggerArch for Aarch64Arch {
         }
 
         match tokens.next() {
-            Some("regs") => {
+            Some("regs") if at_el2() => {
                 print_sysreg!(ttbr0_el2, out);
                 print_sysreg!(mair_el2, out);
                 print_sysreg!(esr_el2, out);
@@ -246,10 +292,24 @@ impl DebuggerArch for Aarch64Arch {
                 print_sysreg!(daif, out);
                 print_sysreg!(hcr_el2, out);
             }
+            Some("regs") => {
+                print_sysreg!(ttbr0_el1, out);
+                print_sysreg!(mair_el1, out);
+                print_sysreg!(esr_el1, out);
+                print_sysreg!(far_el1, out);
+                print_sysreg!(tcr_el1, out);
+                print_sysreg!(sctlr_el1, out);
+                print_sysreg!(spsr_el1, out);
+                print_sysreg!(daif, out);
+            }
             Some("flush_tlb") => {
                 // SAFETY: This is the architecturally defined way to flush the TLB
                 unsafe {
-                    asm!("tlbi alle2", "dsb sy", "isb sy", options(nostack, nomem));
+                    if at_el2() {
+                        asm!("tlbi alle2", "dsb sy", "isb sy", options(nostack, nomem));
+                    } else {
+                        asm!("tlbi vmalle1", "dsb sy", "isb sy", options(nostack, nomem));
+                    }
                 }
             }
             _ => {
@@ -295,8 +355,6 @@ pub struct Aarch64CoreRegs {
     pub sp: u64,
     /// Instruction pointer
     pub pc: u64,
-    /// Floating point control
-    pub fpcr: u64,
     /// PE status
     pub cpsr: u32,
 }
@@ -323,7 +381,6 @@ impl Registers for Aarch64CoreRegs {
 
         write_bytes!(&self.sp.to_le_bytes());
         write_bytes!(&self.pc.to_le_bytes());
-        write_bytes!(&self.fpcr.to_le_bytes());
         write_bytes!(&self.cpsr.to_le_bytes());
     }
 
@@ -349,7 +406,6 @@ impl Registers for Aarch64CoreRegs {
 
         self.sp = read!(u64);
         self.pc = read!(u64);
-        self.fpcr = read!(u64);
         self.cpsr = read!(u32);
         Ok(())
     }
@@ -393,7 +449,6 @@ impl UefiArchRegs for Aarch64CoreRegs {
             ],
             sp: context.sp,
             pc: context.elr,
-            fpcr: context.fpsr,
             cpsr: context.spsr as u32,
         }
     }
@@ -432,21 +487,22 @@ impl UefiArchRegs for Aarch64CoreRegs {
         context.lr = self.regs[30];
         context.sp = self.sp;
         context.elr = self.pc;
-        context.fpsr = self.fpcr;
         context.spsr = self.cpsr as u64;
     }
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub enum Aarch64CoreRegId {
     Gpr(u8),
     Fp,
     Lr,
     Sp,
     Elr,
-    Fpsr,
     Spsr,
+    V(u8),
+    Fpsr,
+    Fpcr,
+    SysReg(u8),
 }
 
 impl RegId for Aarch64CoreRegId {
@@ -457,8 +513,11 @@ impl RegId for Aarch64CoreRegId {
             30 => (Aarch64CoreRegId::Lr, 8),
             31 => (Aarch64CoreRegId::Sp, 8),
             32 => (Aarch64CoreRegId::Elr, 8),
-            33 => (Aarch64CoreRegId::Fpsr, 8),
-            34 => (Aarch64CoreRegId::Spsr, 4),
+            33 => (Aarch64CoreRegId::Spsr, 4),
+            34..=65 => (Aarch64CoreRegId::V((id - 34) as u8), 16),
+            66 => (Aarch64CoreRegId::Fpsr, 4),
+            67 => (Aarch64CoreRegId::Fpcr, 4),
+            68..=76 => (Aarch64CoreRegId::SysReg((id - 68) as u8), 8),
             _ => return None,
         };
 
@@ -466,6 +525,138 @@ impl RegId for Aarch64CoreRegId {
     }
 }
 
+impl UefiArchRegId for Aarch64CoreRegId {
+    fn read_from_context(&self, context: &ExceptionContext, buf: &mut [u8]) -> Result<usize, ()> {
+        let regs = Aarch64CoreRegs::from_context(context);
+        let mut write = |value: &[u8]| -> Result<usize, ()> {
+            buf.get_mut(..value.len()).ok_or(())?.copy_from_slice(value);
+            Ok(value.len())
+        };
+
+        match *self {
+            Self::Gpr(index) => write(&regs.regs[index as usize].to_le_bytes()),
+            Self::Fp => write(&regs.regs[29].to_le_bytes()),
+            Self::Lr => write(&regs.regs[30].to_le_bytes()),
+            Self::Sp => write(&regs.sp.to_le_bytes()),
+            Self::Elr => write(&regs.pc.to_le_bytes()),
+            Self::Spsr => write(&regs.cpsr.to_le_bytes()),
+            Self::V(index) => {
+                let [low, high] = *vreg(&mut { *context }, index);
+                write(&(((high as u128) << 64) | low as u128).to_le_bytes())
+            }
+            Self::Fpsr => write(&(context.fpsr as u32).to_le_bytes()),
+            // FPCR is not saved in the exception context, but is not changed by the debugger.
+            Self::Fpcr => write(&(read_sysreg!(fpcr) as u32).to_le_bytes()),
+            Self::SysReg(index) => write(&read_system_register(context, index).ok_or(())?.to_le_bytes()),
+        }
+    }
+
+    fn write_to_context(&self, context: &mut ExceptionContext, val: &[u8]) -> Result<(), ()> {
+        let mut regs = Aarch64CoreRegs::from_context(context);
+        match *self {
+            Self::Gpr(index) => regs.regs[index as usize] = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Fp => regs.regs[29] = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Lr => regs.regs[30] = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Sp => regs.sp = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Elr => regs.pc = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Spsr => regs.cpsr = u32::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::V(index) => {
+                let value = u128::from_le_bytes(val.try_into().map_err(|_| ())?);
+                *vreg(context, index) = [value as u64, (value >> 64) as u64];
+                return Ok(());
+            }
+            Self::Fpsr => {
+                context.fpsr = u32::from_le_bytes(val.try_into().map_err(|_| ())?) as u64;
+                return Ok(());
+            }
+            Self::Fpcr => {
+                let value = u32::from_le_bytes(val.try_into().map_err(|_| ())?) as u64;
+                write_sysreg!(reg fpcr, value, "isb sy");
+                return Ok(());
+            }
+            Self::SysReg(index) => {
+                return write_system_register(index, u64::from_le_bytes(val.try_into().map_err(|_| ())?));
+            }
+        }
+
+        regs.write_to_context(context);
+        Ok(())
+    }
+}
+
+/// Returns the SIMD/FP register from the context.
+fn vreg(context: &mut ExceptionContext, index: u8) -> &mut [u64; 2] {
+    match index {
+        0 => &mut context.v0,
+        1 => &mut context.v1,
+        2 => &mut context.v2,
+        3 => &mut context.v3,
+        4 => &mut context.v4,
+        5 => &mut context.v5,
+        6 => &mut context.v6,
+        7 => &mut context.v7,
+        8 => &mut context.v8,
+        9 => &mut context.v9,
+        10 => &mut context.v10,
+        11 => &mut context.v11,
+        12 => &mut context.v12,
+        13 => &mut context.v13,
+        14 => &mut context.v14,
+        15 => &mut context.v15,
+        16 => &mut context.v16,
+        17 => &mut context.v17,
+        18 => &mut context.v18,
+        19 => &mut context.v19,
+        20 => &mut context.v20,
+        21 => &mut context.v21,
+        22 => &mut context.v22,
+        23 => &mut context.v23,
+        24 => &mut context.v24,
+        25 => &mut context.v25,
+        26 => &mut context.v26,
+        27 => &mut context.v27,
+        28 => &mut context.v28,
+        29 => &mut context.v29,
+        30 => &mut context.v30,
+        _ => &mut context.v31,
+    }
+}
+
+/// Reads the system register by its index in the system register feature. The
+/// registers are those of the EL the firmware runs at, and HCR_EL2 is only
+/// available at EL2. The syndrome and fault address are taken from the
+/// exception context, as the debugger may have taken exceptions of its own
+/// since.
+fn read_system_register(context: &ExceptionContext, index: u8) -> Option<u64> {
+    Some(match index {
+        0 => read_el_sysreg!(sctlr_el2, sctlr_el1),
+        1 => read_el_sysreg!(tcr_el2, tcr_el1),
+        2 => read_el_sysreg!(ttbr0_el2, ttbr0_el1),
+        3 => read_el_sysreg!(mair_el2, mair_el1),
+        4 => read_el_sysreg!(vbar_el2, vbar_el1),
+        5 if at_el2() => read_sysreg!(hcr_el2),
+        6 => context.esr,
+        7 => context.far,
+        8 => read_sysreg!(CurrentEL),
+        _ => return None,
+    })
+}
+
+/// Writes the system register by its index in the system register feature. The
+/// syndrome, fault address, and current EL are read only.
+fn write_system_register(index: u8, value: u64) -> Result<(), ()> {
+    match index {
+        0 => write_el_sysreg!(sctlr_el2, sctlr_el1, value),
+        1 => write_el_sysreg!(tcr_el2, tcr_el1, value),
+        2 => write_el_sysreg!(ttbr0_el2, ttbr0_el1, value),
+        3 => write_el_sysreg!(mair_el2, mair_el1, value),
+        4 => write_el_sysreg!(vbar_el2, vbar_el1, value),
+        5 if at_el2() => write_sysreg!(reg hcr_el2, value, "isb sy"),
+        _ => return Err(()),
+    }
+    Ok(())
+}
+
 #[bitfield(u64)]
 pub struct Wcr {
     pub enable: bool,
@@ -497,6 +688,17 @@ impl Wcr {
         0xFF_u64.shr(8 - 8_u64.min(length)) as u8
     }
 
+    /// Calculates the doubleword aligned value address and byte address select for a watched range. The range
+    /// must not cross a doubleword boundary.
+    pub fn calculate_range(address: u64, length: u64) -> Option<(u64, u8)> {
+        let offset = address & 0x7;
+        if length == 0 || offset + length > 8 {
+            return None;
+        }
+
+        Some((address - offset, Self::calculate_bas(length) << offset))
+    }
+
     pub fn calculate_lsc(access_type: gdbstub::target::ext::breakpoints::WatchKind) -> u8 {
         match access_type {
             gdbstub::target::ext::breakpoints::WatchKind::Write => 0b10,
@@ -506,6 +708,19 @@ impl Wcr {
     }
 }
 
+/// Finds the kind of the enabled watchpoint covering the faulting address. Accesses are reported for anything
+/// unexpected, since the client only uses the kind to describe the hit.
+fn triggered_watchpoint_kind(far: u64) -> gdbstub::target::ext::breakpoints::WatchKind {
+    (0..NUM_WATCHPOINTS)
+        .map(|i| (read_dbg_wcr(i), read_dbg_wvr(i)))
+        .find(|(wcr, wvr)| wcr.enable() && *wvr == far & !0x7)
+        .map_or(gdbstub::target::ext::breakpoints::WatchKind::ReadWrite, |(wcr, _)| match wcr.lsc() {
+            0b01 => gdbstub::target::ext::breakpoints::WatchKind::Read,
+            0b10 => gdbstub::target::ext::breakpoints::WatchKind::Write,
+            _ => gdbstub::target::ext::breakpoints::WatchKind::ReadWrite,
+        })
+}
+
 fn read_dbg_wcr(index: usize) -> Wcr {
     let value = match index {
         0 => read_sysreg!(dbgwcr0_el1),
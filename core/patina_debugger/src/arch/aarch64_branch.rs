@@ -0,0 +1,175 @@
+//! AArch64 Branch Decoding
+//!
+//! Computes the address of the instruction executed after an A64 instruction, given
+//! the processor state before it executes. This is used to single step with a
+//! temporary breakpoint on configurations where the hardware software-step
+//! mechanism is not available.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+/// Size of an A64 instruction.
+pub const INSTRUCTION_SIZE: u64 = 4;
+
+/// Returns the address of the next instruction executed after `instruction` at `pc`.
+///
+/// `nzcv` holds the condition flags in bits 31:28, as in the SPSR, and `reg` returns
+/// general purpose register Xn, with 31 naming the zero register. Returns `None` for
+/// instructions whose successor cannot be determined from this state, such as
+/// exception returns and pointer authenticated branches.
+pub fn next_instruction(pc: u64, instruction: u32, nzcv: u64, reg: impl Fn(u32) -> u64) -> Option<u64> {
+    let next = pc.wrapping_add(INSTRUCTION_SIZE);
+    let field = |shift: u32, bits: u32| (instruction >> shift) & ((1 << bits) - 1);
+    let offset = |shift: u32, bits: u32| pc.wrapping_add_signed(sign_extend(field(shift, bits), bits) * 4);
+    let rt = || reg(field(0, 5));
+
+    // B, BL
+    if instruction & 0x7C000000 == 0x14000000 {
+        return Some(offset(0, 26));
+    }
+
+    // B.cond, BC.cond
+    if instruction & 0xFF000000 == 0x54000000 {
+        return Some(if condition_holds(field(0, 4), nzcv) { offset(5, 19) } else { next });
+    }
+
+    // CBZ, CBNZ
+    if instruction & 0x7E000000 == 0x34000000 {
+        let value = if field(31, 1) == 1 { rt() } else { rt() as u32 as u64 };
+        let taken = (value == 0) != (field(24, 1) == 1);
+        return Some(if taken { offset(5, 19) } else { next });
+    }
+
+    // TBZ, TBNZ
+    if instruction & 0x7E000000 == 0x36000000 {
+        let bit = (field(31, 1) << 5) | field(19, 5);
+        let taken = ((rt() >> bit) & 1 == 0) != (field(24, 1) == 1);
+        return Some(if taken { offset(5, 14) } else { next });
+    }
+
+    // BR, BLR, RET
+    if instruction & 0xFF9FFC1F == 0xD61F0000 {
+        return Some(reg(field(5, 5)));
+    }
+
+    // The remaining unconditional branch (register) forms are exception returns and
+    // pointer authenticated branches, whose targets depend on state not held here.
+    if instruction & 0xFE000000 == 0xD6000000 {
+        return None;
+    }
+
+    Some(next)
+}
+
+/// Sign extends the low `bits` bits of `value`.
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value as i64) << shift) >> shift
+}
+
+/// Evaluates an A64 condition code against the NZCV flags.
+fn condition_holds(cond: u32, nzcv: u64) -> bool {
+    let n = nzcv & (1 << 31) != 0;
+    let z = nzcv & (1 << 30) != 0;
+    let c = nzcv & (1 << 29) != 0;
+    let v = nzcv & (1 << 28) != 0;
+
+    let result = match cond >> 1 {
+        0b000 => z,
+        0b001 => c,
+        0b010 => n,
+        0b011 => v,
+        0b100 => c && !z,
+        0b101 => n == v,
+        0b110 => n == v && !z,
+        _ => true,
+    };
+
+    // The odd condition codes invert the result, except for the "always" encodings.
+    if cond & 1 == 1 && cond != 0b1111 { !result } else { result }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const PC: u64 = 0x1000;
+    const Z: u64 = 1 << 30;
+    const N: u64 = 1 << 31;
+
+    fn regs(index: u32) -> u64 {
+        match index {
+            0 => 0,
+            1 => 0x8000_0000_0000_0004,
+            30 => 0x4321,
+            31 => 0,
+            n => n as u64 * 0x100,
+        }
+    }
+
+    fn next(instruction: u32, nzcv: u64) -> Option<u64> {
+        next_instruction(PC, instruction, nzcv, regs)
+    }
+
+    #[test]
+    fn test_sequential_instructions() {
+        // nop, add x0, x1, x2, ldr x0, [x1]
+        assert_eq!(next(0xD503201F, 0), Some(PC + 4));
+        assert_eq!(next(0x8B020020, 0), Some(PC + 4));
+        assert_eq!(next(0xF9400020, 0), Some(PC + 4));
+    }
+
+    #[test]
+    fn test_immediate_branches() {
+        // b #0x10, bl #-0x8
+        assert_eq!(next(0x14000004, 0), Some(PC + 0x10));
+        assert_eq!(next(0x97FFFFFE, 0), Some(PC - 0x8));
+    }
+
+    #[test]
+    fn test_conditional_branches() {
+        // b.eq #0x20
+        assert_eq!(next(0x54000100, Z), Some(PC + 0x20));
+        assert_eq!(next(0x54000100, 0), Some(PC + 4));
+        // b.ne #-0x4
+        assert_eq!(next(0x54FFFFE1, 0), Some(PC - 0x4));
+        assert_eq!(next(0x54FFFFE1, Z), Some(PC + 4));
+        // b.lt #0x8 is taken when N != V.
+        assert_eq!(next(0x5400004B, N), Some(PC + 0x8));
+        assert_eq!(next(0x5400004B, 0), Some(PC + 4));
+        // b.al and b.nv are always taken.
+        assert_eq!(next(0x5400004E, 0), Some(PC + 0x8));
+        assert_eq!(next(0x5400004F, 0), Some(PC + 0x8));
+    }
+
+    #[test]
+    fn test_compare_and_test_branches() {
+        // cbz x0, #0x8 / cbnz x0, #0x8
+        assert_eq!(next(0xB4000040, 0), Some(PC + 0x8));
+        assert_eq!(next(0xB5000040, 0), Some(PC + 4));
+        // cbz w1, #0x8 only considers the low 32 bits.
+        assert_eq!(next(0x34000041, 0), Some(PC + 4));
+        // tbnz x1, #63, #0xc / tbz x1, #2, #0xc
+        assert_eq!(next(0xB7F80061, 0), Some(PC + 0xC));
+        assert_eq!(next(0x36100061, 0), Some(PC + 4));
+        // tbz x1, #3, #-0x4
+        assert_eq!(next(0x361FFFE1, 0), Some(PC - 0x4));
+    }
+
+    #[test]
+    fn test_register_branches() {
+        // br x2, blr x3, ret
+        assert_eq!(next(0xD61F0040, 0), Some(0x200));
+        assert_eq!(next(0xD63F0060, 0), Some(0x300));
+        assert_eq!(next(0xD65F03C0, 0), Some(0x4321));
+        // eret, retaa, braa x2, x3
+        assert_eq!(next(0xD69F03E0, 0), None);
+        assert_eq!(next(0xD65F0BFF, 0), None);
+        assert_eq!(next(0xD71F0843, 0), None);
+    }
+}
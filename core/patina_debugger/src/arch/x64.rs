@@ -12,12 +12,16 @@ use patina_internal_cpu::interrupts::ExceptionContext;
 use patina_mtrr::Mtrr;
 use patina_paging::PagingType;
 
-use super::{DebuggerArch, UefiArchRegs};
-use crate::{ExceptionInfo, ExceptionType};
+use super::{DebuggerArch, UefiArchRegId, UefiArchRegs};
+use crate::{ExceptionInfo, ExceptionType, FaultCause};
 
 /// The "int 3" instruction.
 const INT_3: u8 = 0xCC;
 
+/// The model specific registers exposed in the MSR register feature, in register order: EFER, FS base, GS base,
+/// kernel GS base, PAT, and APIC base.
+const MSRS: [u32; 6] = [0xC000_0080, 0xC000_0100, 0xC000_0101, 0xC000_0102, 0x277, 0x1B];
+
 static POKE_TEST_MARKER: AtomicBool = AtomicBool::new(false);
 
 /// The uninhabitable type for implementing X64 architecture.
@@ -33,8 +37,13 @@ impl gdbstub::arch::Arch for X64Arch {
 impl DebuggerArch for X64Arch {
     const DEFAULT_EXCEPTION_TYPES: &'static [usize] = &[0, 1, 3, 4, 5, 6, 8, 11, 12, 13, 14, 17];
     const BREAKPOINT_INSTRUCTION: &'static [u8] = &[INT_3];
-    const GDB_TARGET_XML: &'static str = r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target><architecture>i386:x86-64</architecture><xi:include href="registers.xml"/></target>"#;
-    const GDB_REGISTERS_XML: &'static str = include_str!("xml/x64_registers.xml");
+    const GDB_TARGET_XML: &'static str = r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target><architecture>i386:x86-64</architecture><xi:include href="registers.xml"/><xi:include href="sse.xml"/><xi:include href="avx.xml"/><xi:include href="msr.xml"/></target>"#;
+    const GDB_FEATURES_XML: &'static [(&'static str, &'static str)] = &[
+        ("registers.xml", include_str!("xml/x64_registers.xml")),
+        ("sse.xml", include_str!("xml/x64_sse.xml")),
+        ("avx.xml", include_str!("xml/x64_avx.xml")),
+        ("msr.xml", include_str!("xml/x64_msr.xml")),
+    ];
 
     type PageTable = patina_paging::x64::X64PageTable<patina_paging::page_allocator::PageAllocatorStub>;
 
@@ -49,7 +58,10 @@ impl DebuggerArch for X64Arch {
             exception_type: match exception_type {
                 1 => {
                     context.rflags &= !0x100; // Clear the trap flag.
-                    ExceptionType::Step
+                    match X64HardwareBreakpoints::take_triggered() {
+                        Some((address, kind)) => ExceptionType::Watchpoint(address, kind),
+                        None => ExceptionType::Step,
+                    }
                 }
                 3 => {
                     // The "int 3" will still move the RIP forward. Step it back
@@ -58,7 +70,9 @@ impl DebuggerArch for X64Arch {
                     ExceptionType::Breakpoint
                 }
                 13 => ExceptionType::GeneralProtectionFault(context.exception_data),
-                14 => ExceptionType::AccessViolation(context.cr2 as usize),
+                14 => {
+                    ExceptionType::AccessViolation(context.cr2 as usize, FaultCause::PageFault(context.exception_data))
+                }
                 _ => ExceptionType::Other(exception_type),
             },
             instruction_pointer: context.rip,
@@ -96,11 +110,17 @@ impl DebuggerArch for X64Arch {
     }
 
     fn add_watchpoint(address: u64, length: u64, access_type: WatchKind) -> bool {
+        // The debug registers cannot break on reads alone, and only watch naturally aligned ranges of 1, 2, 4, or 8
+        // bytes.
+        if access_type == WatchKind::Read || !matches!(length, 1 | 2 | 4 | 8) || !address.is_multiple_of(length) {
+            return false;
+        }
+
         let mut hw_breakpoints = X64HardwareBreakpoints::read();
 
         // First check for duplicate watchpoints.
         for i in 0..=X64HardwareBreakpoints::MAX_INDEX {
-            if hw_breakpoints.get_enabled(i) && hw_breakpoints.get_address(i) == address {
+            if hw_breakpoints.matches(i, address, length, access_type) {
                 return true;
             }
         }
@@ -118,10 +138,10 @@ impl DebuggerArch for X64Arch {
         false
     }
 
-    fn remove_watchpoint(address: u64, _length: u64, _access_type: WatchKind) -> bool {
+    fn remove_watchpoint(address: u64, length: u64, access_type: WatchKind) -> bool {
         let mut hw_breakpoints = X64HardwareBreakpoints::read();
         for i in 0..=X64HardwareBreakpoints::MAX_INDEX {
-            if hw_breakpoints.get_enabled(i) && hw_breakpoints.get_address(i) == address {
+            if hw_breakpoints.matches(i, address, length, access_type) {
                 hw_breakpoints.set_enabled(i, false);
                 hw_breakpoints.flush();
                 return true;
@@ -372,8 +392,26 @@ impl UefiArchRegs for X64CoreRegs {
                 context.gs as u32,
             ],
             control: [context.cr0, context.cr2, context.cr3, context.cr4],
-            fpu: [0; 7],
-            st: [[0; 10]; 9],
+            fpu: [
+                context.fx_save_state.fcw as u32,
+                context.fx_save_state.fsw as u32,
+                context.fx_save_state.ftw as u32,
+                0,
+                context.fx_save_state.rip as u32,
+                0,
+                context.fx_save_state.data_offset as u32,
+            ],
+            st: [
+                u128::from(context.fx_save_state.opcode).to_le_bytes()[..10].try_into().unwrap_or_default(),
+                context.fx_save_state.st0mm0,
+                context.fx_save_state.st1mm1,
+                context.fx_save_state.st2mm2,
+                context.fx_save_state.st3mm3,
+                context.fx_save_state.st4mm4,
+                context.fx_save_state.st5mm5,
+                context.fx_save_state.st6mm6,
+                context.fx_save_state.st7mm7,
+            ],
         }
     }
 
@@ -413,7 +451,6 @@ impl UefiArchRegs for X64CoreRegs {
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub enum X64CoreRegId {
     Gpr(u8),
     Rip,
@@ -422,6 +459,10 @@ pub enum X64CoreRegId {
     Control(u8),
     Fpu(u8),
     St(u8),
+    Xmm(u8),
+    Mxcsr,
+    YmmHigh(u8),
+    Msr(u32),
 }
 
 impl RegId for X64CoreRegId {
@@ -432,8 +473,12 @@ impl RegId for X64CoreRegId {
             17 => (Self::Eflags, 8),
             18..=23 => (Self::Segment((id - 18) as u8), 4),
             24..=28 => (Self::Control((id - 24) as u8), 8),
-            29..=35 => (Self::Fpu((id - 24) as u8), 4),
-            36..=44 => (Self::St((id - 31) as u8), 10),
+            29..=35 => (Self::Fpu((id - 29) as u8), 4),
+            36..=44 => (Self::St((id - 36) as u8), 10),
+            45..=60 => (Self::Xmm((id - 45) as u8), 16),
+            61 => (Self::Mxcsr, 4),
+            62..=77 => (Self::YmmHigh((id - 62) as u8), 16),
+            78..=83 => (Self::Msr(MSRS[id - 78]), 8),
             _ => return None,
         };
 
@@ -441,6 +486,160 @@ impl RegId for X64CoreRegId {
     }
 }
 
+impl UefiArchRegId for X64CoreRegId {
+    fn read_from_context(&self, context: &ExceptionContext, buf: &mut [u8]) -> Result<usize, ()> {
+        let regs = X64CoreRegs::from_context(context);
+        let mut write = |value: &[u8]| -> Result<usize, ()> {
+            buf.get_mut(..value.len()).ok_or(())?.copy_from_slice(value);
+            Ok(value.len())
+        };
+
+        match *self {
+            Self::Gpr(index) => write(&regs.regs[index as usize].to_le_bytes()),
+            Self::Rip => write(&regs.rip.to_le_bytes()),
+            Self::Eflags => write(&regs.eflags.to_le_bytes()),
+            Self::Segment(index) => write(&regs.segments[index as usize].to_le_bytes()),
+            // CR8 is not part of the core register structure.
+            Self::Control(4) => write(&context.cr8.to_le_bytes()),
+            Self::Control(index) => write(&regs.control[index as usize].to_le_bytes()),
+            Self::Fpu(index) => write(&regs.fpu[index as usize].to_le_bytes()),
+            Self::St(index) => write(&regs.st[index as usize]),
+            Self::Xmm(index) => write(xmm(&mut { *context }, index)),
+            Self::Mxcsr => write(&context.fx_save_state.reserved_1[..4]),
+            Self::YmmHigh(index) => match read_ymm_high(index) {
+                Some(value) => write(&value),
+                None => Ok(0),
+            },
+            Self::Msr(msr) => write(&read_msr(msr).to_le_bytes()),
+        }
+    }
+
+    fn write_to_context(&self, context: &mut ExceptionContext, val: &[u8]) -> Result<(), ()> {
+        let mut regs = X64CoreRegs::from_context(context);
+        match *self {
+            Self::Gpr(index) => regs.regs[index as usize] = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Rip => regs.rip = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Eflags => regs.eflags = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Segment(index) => regs.segments[index as usize] = u32::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Control(4) => {
+                context.cr8 = u64::from_le_bytes(val.try_into().map_err(|_| ())?);
+                return Ok(());
+            }
+            Self::Control(index) => regs.control[index as usize] = u64::from_le_bytes(val.try_into().map_err(|_| ())?),
+            Self::Xmm(index) => {
+                *xmm(context, index) = val.try_into().map_err(|_| ())?;
+                return Ok(());
+            }
+            Self::Mxcsr => {
+                context.fx_save_state.reserved_1[..4].copy_from_slice(val.get(..4).ok_or(())?);
+                return Ok(());
+            }
+            Self::YmmHigh(index) => return write_ymm_high(index, val.try_into().map_err(|_| ())?),
+            Self::Msr(msr) => {
+                write_msr(msr, u64::from_le_bytes(val.try_into().map_err(|_| ())?));
+                return Ok(());
+            }
+            // The legacy x87 state is not writable.
+            Self::Fpu(_) | Self::St(_) => return Err(()),
+        }
+
+        regs.write_to_context(context);
+        Ok(())
+    }
+}
+
+/// Returns the XMM register from the FXSAVE area of the context. XMM8-15 follow
+/// XMM0-7 in the area reserved by the UEFI structure.
+fn xmm(context: &mut ExceptionContext, index: u8) -> &mut [u8; 16] {
+    let fx_save_state = &mut context.fx_save_state;
+    match index {
+        0 => &mut fx_save_state.xmm0,
+        1 => &mut fx_save_state.xmm1,
+        2 => &mut fx_save_state.xmm2,
+        3 => &mut fx_save_state.xmm3,
+        4 => &mut fx_save_state.xmm4,
+        5 => &mut fx_save_state.xmm5,
+        6 => &mut fx_save_state.xmm6,
+        7 => &mut fx_save_state.xmm7,
+        _ => {
+            let offset = (index as usize - 8) * 16;
+            (&mut fx_save_state.reserved_11[offset..offset + 16]).try_into().unwrap_or_else(|_| unreachable!())
+        }
+    }
+}
+
+/// Checks that the OS has enabled the AVX state, making the YMM registers accessible.
+fn avx_enabled() -> bool {
+    // SAFETY: CPUID leaf 1 is available on all x64 processors.
+    let cpuid = unsafe { core::arch::x86_64::__cpuid(1) };
+    if cpuid.ecx & (1 << 27) == 0 {
+        // XSAVE is not enabled, so XCR0 cannot be read.
+        return false;
+    }
+
+    let xcr0: u32;
+    // SAFETY: XGETBV is available as CR4.OSXSAVE is set.
+    unsafe { asm!("xgetbv", in("ecx") 0, out("eax") xcr0, out("edx") _, options(nomem, nostack)) };
+    xcr0 & 0x6 == 0x6
+}
+
+// The upper halves of the YMM registers are not saved in the exception context. The debugger is built without AVX,
+// so they are read from and written to the processor directly.
+
+/// Reads the upper 128 bits of a YMM register, if AVX is enabled.
+fn read_ymm_high(index: u8) -> Option<[u8; 16]> {
+    if !avx_enabled() {
+        return None;
+    }
+
+    let mut value = [0u8; 16];
+    let ptr = value.as_mut_ptr();
+    macro_rules! extract {
+        ($($n:literal),*) => {
+            match index {
+                // SAFETY: AVX is enabled and the pointer refers to a 16 byte buffer.
+                $($n => unsafe { asm!(concat!("vextractf128 xmmword ptr [{}], ymm", $n, ", 1"), in(reg) ptr, options(nostack)) },)*
+                _ => return None,
+            }
+        };
+    }
+    extract!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+    Some(value)
+}
+
+/// Writes the upper 128 bits of a YMM register. Fails if AVX is not enabled.
+fn write_ymm_high(index: u8, value: [u8; 16]) -> Result<(), ()> {
+    if !avx_enabled() {
+        return Err(());
+    }
+
+    let ptr = value.as_ptr();
+    macro_rules! insert {
+        ($($n:literal),*) => {
+            match index {
+                // SAFETY: AVX is enabled and the pointer refers to a 16 byte buffer.
+                $($n => unsafe { asm!(concat!("vinsertf128 ymm", $n, ", ymm", $n, ", xmmword ptr [{}], 1"), in(reg) ptr, options(nostack)) },)*
+                _ => return Err(()),
+            }
+        };
+    }
+    insert!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+    Ok(())
+}
+
+fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    // SAFETY: Only architectural MSRs present on all x64 processors are read.
+    unsafe { asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack)) };
+    ((high as u64) << 32) | low as u64
+}
+
+fn write_msr(msr: u32, value: u64) {
+    // SAFETY: Only architectural MSRs present on all x64 processors are written, at the request of the debugger
+    // client.
+    unsafe { asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack)) };
+}
+
 /// Structure for abstracting the x64 debug registers for hardware breakpoints.
 struct X64HardwareBreakpoints {
     dr7: u64,
@@ -474,6 +673,8 @@ impl X64HardwareBreakpoints {
     const DR7_LEN_OFFSET: usize = 18;
     /// Each LEN value is 4 bits appart.
     const DR7_LEN_STRIDE: usize = 4;
+    /// The low 4 bits of DR6 report which breakpoint conditions were met.
+    const DR6_TRIGGERED_MASK: u64 = 0xF;
 
     pub fn read() -> Self {
         let dr7: u64;
@@ -487,6 +688,31 @@ impl X64HardwareBreakpoints {
         unsafe { asm!("mov dr7, {}", in(reg) self.dr7) };
     }
 
+    /// Checks DR6 for a triggered watchpoint, returning its address and kind. DR6 is cleared since the processor
+    /// never clears it.
+    pub fn take_triggered() -> Option<(u64, WatchKind)> {
+        let dr6: u64;
+        // SAFETY: This is simply reading the DR6 register, which is safe.
+        unsafe { asm!("mov {}, dr6", out(reg) dr6) };
+        if dr6 & Self::DR6_TRIGGERED_MASK != 0 {
+            // SAFETY: This is simply clearing the status bits in DR6, which is safe in this debugger context.
+            unsafe { asm!("mov dr6, {}", in(reg) dr6 & !Self::DR6_TRIGGERED_MASK) };
+        }
+
+        let hw_breakpoints = Self::read();
+        (0..=Self::MAX_INDEX)
+            .find(|&index| dr6 & (1 << index) != 0 && hw_breakpoints.get_enabled(index))
+            .map(|index| (hw_breakpoints.get_address(index), hw_breakpoints.get_rw(index)))
+    }
+
+    /// Checks if the breakpoint at the index is an enabled watchpoint for the provided range and kind.
+    pub fn matches(&self, index: usize, address: u64, length: u64, kind: WatchKind) -> bool {
+        self.get_enabled(index)
+            && self.get_address(index) == address
+            && self.get_len(index) == length
+            && self.get_rw(index) == kind
+    }
+
     pub fn clear_all(&mut self) {
         self.dr7 &= !Self::DR7_ENABLE_MASK;
     }
@@ -506,6 +732,7 @@ impl X64HardwareBreakpoints {
     pub fn set_rw(&mut self, index: usize, kind: WatchKind) {
         self.dr7 &= !(Self::DR7_RW_MASK << (index * Self::DR7_RW_STRIDE + Self::DR7_RW_OFFSET));
         match kind {
+            // There is no read only condition, so reads are watched as accesses.
             WatchKind::Read | WatchKind::ReadWrite => {
                 self.dr7 |= 3 << (index * Self::DR7_RW_STRIDE + Self::DR7_RW_OFFSET);
             }
@@ -515,11 +742,28 @@ impl X64HardwareBreakpoints {
         }
     }
 
+    pub fn get_rw(&self, index: usize) -> WatchKind {
+        match (self.dr7 >> (index * Self::DR7_RW_STRIDE + Self::DR7_RW_OFFSET)) & Self::DR7_RW_MASK {
+            1 => WatchKind::Write,
+            _ => WatchKind::ReadWrite,
+        }
+    }
+
+    pub fn get_len(&self, index: usize) -> u64 {
+        match (self.dr7 >> (index * Self::DR7_LEN_STRIDE + Self::DR7_LEN_OFFSET)) & Self::DR7_LEN_MASK {
+            0 => 1,
+            1 => 2,
+            2 => 8,
+            _ => 4,
+        }
+    }
+
     pub fn set_len(&mut self, index: usize, len: u64) {
+        // Note that the 8 byte encoding is out of order.
         let len = match len {
             1 => 0,
             2 => 1,
-            4 => 2,
+            8 => 2,
             _ => 3,
         };
 
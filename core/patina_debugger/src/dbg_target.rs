@@ -13,21 +13,28 @@
 mod breakpoint;
 mod monitor;
 
-use gdbstub::target::{
-    Target, TargetError, TargetResult,
-    ext::{
-        self,
-        base::singlethread::{SingleThreadBase, SingleThreadResume, SingleThreadResumeOps},
-        breakpoints::{self, BreakpointsOps},
+pub(crate) use breakpoint::clear_step_breakpoint;
+
+use alloc::{format, string::String};
+use gdbstub::{
+    common::Signal,
+    stub::SingleThreadStopReason,
+    target::{
+        Target, TargetError, TargetResult,
+        ext::{
+            self,
+            base::singlethread::{SingleThreadBase, SingleThreadResume, SingleThreadResumeOps},
+            breakpoints::{self, BreakpointsOps},
+        },
     },
 };
 use spin::Mutex;
 
 use crate::{
-    ExceptionInfo,
-    arch::{DebuggerArch, SystemArch, UefiArchRegs},
+    ExceptionInfo, ExceptionType,
+    arch::{DebuggerArch, SystemArch, UefiArchRegId, UefiArchRegs},
     memory,
-    system::SystemState,
+    system::{self, SystemState},
 };
 
 /// Addresses that windbg will attempt to read in a loop, reads from these addresses
@@ -45,14 +52,39 @@ pub struct PatinaTarget {
     reboot: bool,
     /// Disables safety checks for the target.
     disable_checks: bool,
+    /// Steps by placing a breakpoint on the next instruction instead of using the
+    /// architecture specific single step.
+    software_step: bool,
+    /// Whether console output is forwarded to the client once resumed.
+    console_forwarding: bool,
     /// Tracks external system state.
     system_state: &'static Mutex<SystemState>,
 }
 
 impl PatinaTarget {
     /// Create a new Patina target.
-    pub fn new(exception_info: ExceptionInfo, system_state: &'static Mutex<SystemState>) -> Self {
-        PatinaTarget { exception_info, resume: false, reboot: false, disable_checks: false, system_state }
+    pub fn new(exception_info: ExceptionInfo, system_state: &'static Mutex<SystemState>, software_step: bool) -> Self {
+        PatinaTarget {
+            exception_info,
+            resume: false,
+            reboot: false,
+            disable_checks: false,
+            software_step,
+            console_forwarding: true,
+            system_state,
+        }
+    }
+
+    /// Sets whether console output is forwarded to the client, which the client may change with the `console`
+    /// monitor command.
+    pub fn with_console_forwarding(mut self, enabled: bool) -> Self {
+        self.console_forwarding = enabled;
+        self
+    }
+
+    /// Checks if console output should be forwarded to the client once resumed.
+    pub fn console_forwarding(&self) -> bool {
+        self.console_forwarding
     }
 
     /// Checks if the target has been resumed.
@@ -65,10 +97,52 @@ impl PatinaTarget {
         self.reboot
     }
 
+    /// The stop reason to report to the client for the exception that entered the debugger.
+    pub fn stop_reason(&self) -> SingleThreadStopReason<u64> {
+        // Windbg does not handle watch, breakpoint, or fault stop replies, so it is only told of the trap.
+        if cfg!(feature = "windbg_workarounds") {
+            return SingleThreadStopReason::SignalWithThread { tid: (), signal: Signal::SIGTRAP };
+        }
+
+        match self.exception_info.exception_type {
+            ExceptionType::Watchpoint(addr, kind) => SingleThreadStopReason::Watch { tid: (), kind, addr },
+            ExceptionType::Breakpoint => SingleThreadStopReason::SwBreak(()),
+            ExceptionType::AccessViolation(..) | ExceptionType::GeneralProtectionFault(_) => {
+                SingleThreadStopReason::SignalWithThread { tid: (), signal: Signal::SIGSEGV }
+            }
+            _ => SingleThreadStopReason::SignalWithThread { tid: (), signal: Signal::SIGTRAP },
+        }
+    }
+
+    /// Describes the fault that entered the debugger, such as the faulting address and its architecture specific
+    /// cause, so the client can show it with the stop. Breaks fully described by the stop reason have no description.
+    pub fn stop_description(&self) -> Option<String> {
+        match self.exception_info.exception_type {
+            ExceptionType::Step | ExceptionType::Breakpoint | ExceptionType::Watchpoint(..) => None,
+            ref exception_type => {
+                Some(format!("Stopped at {:#X}: {}\n", self.exception_info.instruction_pointer, exception_type))
+            }
+        }
+    }
+
     /// Consumes the target and returns the updated exception information.
     pub fn into_exception_info(self) -> ExceptionInfo {
         self.exception_info
     }
+
+    /// Places a breakpoint on the instruction executed after the current one, returning
+    /// false if the next instruction cannot be determined or the breakpoint cannot be set.
+    fn set_step_breakpoint(&self) -> bool {
+        let current = self.exception_info.instruction_pointer;
+        match SystemArch::next_instruction_address(&self.exception_info.context) {
+            // A branch to itself would be skipped as a hard-coded breakpoint on exit.
+            Some(next) if next != current => breakpoint::set_step_breakpoint(next, self.disable_checks).is_ok(),
+            _ => {
+                log::warn!("Cannot software step the instruction at {current:#x}.");
+                false
+            }
+        }
+    }
 }
 
 impl Target for PatinaTarget {
@@ -107,6 +181,21 @@ impl Target for PatinaTarget {
         Some(self)
     }
 
+    #[inline(always)]
+    fn support_libraries(&mut self) -> Option<ext::libraries::LibrariesOps<'_, Self>> {
+        Some(self)
+    }
+
+    #[inline(always)]
+    fn support_memory_map(&mut self) -> Option<ext::memory_map::MemoryMapOps<'_, Self>> {
+        // Only advertise a memory map when one can be provided, as the client will
+        // refuse to access memory outside of the reported regions.
+        match self.system_state.try_lock() {
+            Some(state) if state.memory_map.is_some() => Some(self),
+            _ => None,
+        }
+    }
+
     #[inline(always)]
     fn support_target_description_xml_override(
         &mut self,
@@ -166,6 +255,33 @@ impl SingleThreadBase for PatinaTarget {
     fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
         Some(self)
     }
+
+    #[inline(always)]
+    fn support_single_register_access(
+        &mut self,
+    ) -> Option<ext::base::single_register_access::SingleRegisterAccessOps<'_, (), Self>> {
+        Some(self)
+    }
+}
+
+impl ext::base::single_register_access::SingleRegisterAccess<()> for PatinaTarget {
+    fn read_register(
+        &mut self,
+        _tid: (),
+        reg_id: <Self::Arch as gdbstub::arch::Arch>::RegId,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        reg_id.read_from_context(&self.exception_info.context, buf).map_err(|_| TargetError::NonFatal)
+    }
+
+    fn write_register(
+        &mut self,
+        _tid: (),
+        reg_id: <Self::Arch as gdbstub::arch::Arch>::RegId,
+        val: &[u8],
+    ) -> TargetResult<(), Self> {
+        reg_id.write_to_context(&mut self.exception_info.context, val).map_err(|_| TargetError::NonFatal)
+    }
 }
 
 impl SingleThreadResume for PatinaTarget {
@@ -183,7 +299,9 @@ impl SingleThreadResume for PatinaTarget {
 
 impl ext::base::singlethread::SingleThreadSingleStep for PatinaTarget {
     fn step(&mut self, _signal: Option<gdbstub::common::Signal>) -> Result<(), Self::Error> {
-        SystemArch::set_single_step(&mut self.exception_info);
+        if !self.software_step || !self.set_step_breakpoint() {
+            SystemArch::set_single_step(&mut self.exception_info);
+        }
         self.resume = true;
         Ok(())
     }
@@ -212,8 +330,10 @@ impl ext::target_description_xml_override::TargetDescriptionXmlOverride for Pati
         let offset = offset as usize;
         let xml = match annex {
             b"target.xml" => SystemArch::GDB_TARGET_XML,
-            b"registers.xml" => SystemArch::GDB_REGISTERS_XML,
-            _ => return Err(TargetError::NonFatal),
+            _ => match SystemArch::GDB_FEATURES_XML.iter().find(|(name, _)| name.as_bytes() == annex) {
+                Some((_, xml)) => xml,
+                None => return Err(TargetError::NonFatal),
+            },
         };
 
         let bytes = xml.trim().as_bytes();
@@ -230,3 +350,43 @@ impl ext::target_description_xml_override::TargetDescriptionXmlOverride for Pati
         Ok(copy_len)
     }
 }
+
+impl ext::libraries::Libraries for PatinaTarget {
+    fn get_libraries(&self, offset: u64, length: usize, buf: &mut [u8]) -> TargetResult<usize, Self> {
+        let mut xml = String::new();
+        match self.system_state.try_lock() {
+            Some(state) => state.modules.write_library_list(&mut xml).map_err(|_| TargetError::NonFatal)?,
+            None => return Err(TargetError::NonFatal),
+        }
+
+        Ok(copy_xfer(xml.as_bytes(), offset, length, buf))
+    }
+}
+
+impl ext::memory_map::MemoryMap for PatinaTarget {
+    fn memory_map_xml(&self, offset: u64, length: usize, buf: &mut [u8]) -> TargetResult<usize, Self> {
+        let provider = match self.system_state.try_lock() {
+            Some(state) => state.memory_map.ok_or(TargetError::NonFatal)?,
+            None => return Err(TargetError::NonFatal),
+        };
+
+        // The provider is invoked without the system state lock held.
+        let mut xml = String::new();
+        system::write_memory_map(provider, &mut xml).map_err(|_| TargetError::NonFatal)?;
+        Ok(copy_xfer(xml.as_bytes(), offset, length, buf))
+    }
+}
+
+/// Copies the requested window of a qXfer object into `buf`, returning the number
+/// of bytes copied.
+fn copy_xfer(bytes: &[u8], offset: u64, length: usize, buf: &mut [u8]) -> usize {
+    let offset = offset as usize;
+    if offset >= bytes.len() {
+        return 0;
+    }
+
+    let end = (offset + length).min(bytes.len());
+    let copy_len = (end - offset).min(buf.len());
+    buf[..copy_len].copy_from_slice(&bytes[offset..offset + copy_len]);
+    copy_len
+}
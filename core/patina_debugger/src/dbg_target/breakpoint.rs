@@ -1,7 +1,8 @@
 //! Breakpoint implementations
 //!
 //! This module contains the implementation for setting/removing software and
-//! hardware execution breakpoints and data watchpoints.
+//! hardware execution breakpoints and data watchpoints, and the temporary breakpoint
+//! used for software single stepping.
 //!
 //! ## License
 //!
@@ -16,8 +17,9 @@ use gdbstub::{
 };
 
 use crate::{
+    MemoryRegionKind,
     arch::{DebuggerArch, SystemArch},
-    memory,
+    memory, system,
 };
 
 use super::PatinaTarget;
@@ -28,6 +30,9 @@ const BREAKPOINT_LENGTH: usize = SystemArch::BREAKPOINT_INSTRUCTION.len();
 static BREAKPOINTS: spin::Mutex<[Breakpoint; MAX_BREAKPOINTS]> =
     spin::Mutex::new([Breakpoint::empty(); MAX_BREAKPOINTS]);
 
+/// The temporary breakpoint placed on the next instruction for a software single step.
+static STEP_BREAKPOINT: spin::Mutex<Breakpoint> = spin::Mutex::new(Breakpoint::empty());
+
 #[derive(Copy, Clone)]
 struct Breakpoint {
     set: bool,
@@ -41,12 +46,50 @@ impl Breakpoint {
     }
 }
 
+/// Places the temporary breakpoint for a software single step at `addr`.
+///
+/// The breakpoint is saved and restored like any other software breakpoint, so it
+/// may share an address with a client breakpoint.
+pub(super) fn set_step_breakpoint(addr: u64, unsafe_read: bool) -> Result<(), ()> {
+    let mut step = STEP_BREAKPOINT.lock();
+    if step.set {
+        return Err(());
+    }
+
+    memory::read_memory::<SystemArch>(addr, &mut step.original, unsafe_read)?;
+    memory::write_memory::<SystemArch>(addr, SystemArch::BREAKPOINT_INSTRUCTION)?;
+    step.addr = addr;
+    step.set = true;
+    Ok(())
+}
+
+/// Removes the temporary breakpoint for a software single step, if one is set,
+/// returning its address.
+pub(crate) fn clear_step_breakpoint() -> Option<u64> {
+    let mut step = STEP_BREAKPOINT.lock();
+    if !step.set {
+        return None;
+    }
+
+    if memory::write_memory::<SystemArch>(step.addr, &step.original).is_err() {
+        log::error!("Failed to restore the instruction at {:#x} after a step.", step.addr);
+    }
+    step.set = false;
+    Some(step.addr)
+}
+
 impl breakpoints::SwBreakpoint for PatinaTarget {
     fn add_sw_breakpoint(
         &mut self,
         addr: u64,
         _kind: <Self::Arch as Arch>::BreakpointKind,
     ) -> TargetResult<bool, Self> {
+        // Writing a breakpoint instruction to device memory would act on the device.
+        let memory_map = self.system_state.try_lock().and_then(|state| state.memory_map);
+        if memory_map.and_then(|provider| system::memory_region_kind(provider, addr)) == Some(MemoryRegionKind::Mmio) {
+            return Ok(false);
+        }
+
         let mut breakpoints = BREAKPOINTS.lock();
         for bp in breakpoints.iter_mut() {
             if !bp.set {
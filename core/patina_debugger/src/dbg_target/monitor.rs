@@ -13,7 +13,10 @@
 use core::{fmt::Write, str::SplitWhitespace};
 use gdbstub::target::ext::{self, monitor_cmd::ConsoleOutput};
 
-use crate::arch::{DebuggerArch, SystemArch};
+use crate::{
+    arch::{DebuggerArch, SystemArch},
+    memory::{self, MAX_SEARCH_PATTERN_SIZE},
+};
 
 use super::PatinaTarget;
 
@@ -23,17 +26,33 @@ Patina Rust Debugger monitor commands:
     ? - Display information about the state of the machine.
     reboot - Prepares to reboot the machine on the next continue.
     mod ... - Commands for breaking on or quering modules.
+    find <pattern> <start> <len> - Search memory for a pattern.
+    panic - Display the message and frames of the last panic.
+    console [on|off] - Show or set whether firmware console output is forwarded.
     arch ... - Architecture specific commands.
 ";
 
 const MOD_HELP: &str = "
 Mod commands:
-    list [count] [index] - List loaded modules.
+    list [count] [index] - List loaded modules and their symbol server paths.
     break [module] - Set load breakpoint for a module.
+    entry [module|guid] - Set entry point breakpoint for a module name or file GUID.
     breakall - Break on all module loads.
     clear - clear all module breakpoints.
 ";
 
+const FIND_HELP: &str = "
+Usage: find <pattern> <start> <len>
+    Searches len bytes of memory from start for the pattern. start and len are hexadecimal.
+    Patterns:
+        4d5a90 - Hex bytes, in memory order.
+        \"_FVH\" - ASCII string.
+        u\"Patina\" - UTF-16 string.
+";
+
+/// Maximum number of matches reported by the find command.
+const MAX_FIND_MATCHES: usize = 32;
+
 impl ext::monitor_cmd::MonitorCmd for PatinaTarget {
     fn handle_monitor_cmd(&mut self, cmd: &[u8], out: ConsoleOutput<'_>) -> Result<(), Self::Error> {
         let cmd_str = core::str::from_utf8(cmd).map_err(|_| ())?;
@@ -67,6 +86,30 @@ impl ext::monitor_cmd::MonitorCmd for PatinaTarget {
             Some("mod") => {
                 self.module_cmd(&mut tokens, &mut buf);
             }
+            Some("find") => {
+                self.find_cmd(&mut tokens, &mut buf);
+            }
+            Some("panic") => match self.system_state.try_lock() {
+                Some(state) => {
+                    let _ = buf.write_str(state.panic.text().unwrap_or("No panic has occurred."));
+                }
+                None => {
+                    let _ = buf.write_str("ERROR: Failed to acquire system state lock!");
+                }
+            },
+            Some("console") => {
+                match tokens.next() {
+                    Some("on") => self.console_forwarding = true,
+                    Some("off") => self.console_forwarding = false,
+                    None => {}
+                    Some(_) => {
+                        let _ = buf.write_str("Usage: console [on|off]");
+                        return Ok(());
+                    }
+                }
+                let state = if self.console_forwarding { "on" } else { "off" };
+                let _ = write!(buf, "Console forwarding is {state}.");
+            }
             Some("reboot") | Some("R") => {
                 self.reboot = true;
                 let _ = buf.write_str("System will reboot on continue.");
@@ -132,8 +175,18 @@ impl PatinaTarget {
                     let _ = writeln!(out, "\t{module}");
                 }
             }
+            #[cfg(feature = "alloc")]
+            Some("entry") => {
+                for module in tokens.by_ref() {
+                    state.modules.add_entry_breakpoint(module);
+                }
+                let _ = out.write_str("Entry breakpoints:\n");
+                for module in state.modules.get_entry_breakpoints().iter() {
+                    let _ = writeln!(out, "\t{module}");
+                }
+            }
             #[cfg(not(feature = "alloc"))]
-            Some("break") => {
+            Some("break") | Some("entry") => {
                 let _ = out.write_str("Specific Module breakpoints only supported with 'alloc' feature.");
             }
             Some("clear") => {
@@ -145,7 +198,11 @@ impl PatinaTarget {
                 let start: usize = tokens.next().and_then(|token| token.parse().ok()).unwrap_or(0);
                 let mut printed = 0;
                 for module in state.modules.get_modules().iter().skip(start) {
-                    let _ = writeln!(out, "\t{}: {:#x} : {:#x}", module.name, module.base, module.size);
+                    let _ = write!(out, "\t{}: {:#x} : {:#x}", module.name, module.base, module.size);
+                    if let Some(path) = &module.symbol_server_path {
+                        let _ = write!(out, " : {path}");
+                    }
+                    let _ = writeln!(out);
                     printed += 1;
                     if printed >= count {
                         break;
@@ -161,6 +218,78 @@ impl PatinaTarget {
             }
         }
     }
+
+    fn find_cmd(&self, tokens: &mut SplitWhitespace<'_>, out: &mut dyn Write) {
+        let mut pattern_buffer = [0u8; MAX_SEARCH_PATTERN_SIZE];
+        let (Some(pattern), Some(start), Some(length)) = (
+            tokens.next().and_then(|token| parse_search_pattern(token, &mut pattern_buffer)),
+            tokens.next().and_then(parse_hex),
+            tokens.next().and_then(parse_hex),
+        ) else {
+            let _ = out.write_str(FIND_HELP);
+            return;
+        };
+
+        let mut count = 0;
+        let result = memory::search_memory::<SystemArch>(start, length, pattern, self.disable_checks, |address| {
+            let _ = writeln!(out, "\t{address:#x}");
+            count += 1;
+            count < MAX_FIND_MATCHES
+        });
+
+        match result {
+            Ok(skipped) => {
+                if count >= MAX_FIND_MATCHES {
+                    let _ = writeln!(out, "Stopped after {MAX_FIND_MATCHES} matches.");
+                } else {
+                    let _ = writeln!(out, "{count} matches.");
+                }
+                if skipped > 0 {
+                    let _ = writeln!(out, "Skipped {skipped:#x} bytes of unreadable memory.");
+                }
+            }
+            Err(_) => {
+                let _ = out.write_str("ERROR: Failed to search memory.");
+            }
+        }
+    }
+}
+
+/// Parses a hexadecimal number, with or without a `0x` prefix.
+fn parse_hex(token: &str) -> Option<u64> {
+    u64::from_str_radix(token.trim_start_matches("0x"), 16).ok()
+}
+
+/// Parses a find pattern into `buffer`, returning the pattern bytes.
+///
+/// Patterns are hex bytes in memory order (`4d5a90`), ASCII strings in double quotes (`"_FVH"`), or UTF-16 strings
+/// in double quotes prefixed with `u` (`u"Patina"`).
+fn parse_search_pattern<'a>(token: &str, buffer: &'a mut [u8; MAX_SEARCH_PATTERN_SIZE]) -> Option<&'a [u8]> {
+    let mut len = 0;
+    if let Some(text) = token.strip_prefix("u\"").and_then(|token| token.strip_suffix('"')) {
+        for unit in text.encode_utf16() {
+            buffer.get_mut(len..len + 2)?.copy_from_slice(&unit.to_le_bytes());
+            len += 2;
+        }
+    } else if let Some(text) = token.strip_prefix('"').and_then(|token| token.strip_suffix('"')) {
+        if !text.is_ascii() {
+            return None;
+        }
+        buffer.get_mut(..text.len())?.copy_from_slice(text.as_bytes());
+        len = text.len();
+    } else {
+        let hex = token.trim_start_matches("0x");
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        for pair in hex.as_bytes().chunks(2) {
+            let pair = core::str::from_utf8(pair).ok()?;
+            *buffer.get_mut(len)? = u8::from_str_radix(pair, 16).ok()?;
+            len += 1;
+        }
+    }
+
+    if len == 0 { None } else { Some(&buffer[..len]) }
 }
 
 /// A wrapper that batches writes. This is to reduce the number of packets
@@ -234,3 +363,37 @@ impl<const N: usize> Drop for MonitorBuffer<'_, N> {
         self.flush();
     }
 }
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    fn pattern(token: &str) -> Option<Vec<u8>> {
+        let mut buffer = [0u8; MAX_SEARCH_PATTERN_SIZE];
+        parse_search_pattern(token, &mut buffer).map(|pattern| pattern.to_vec())
+    }
+
+    #[test]
+    fn test_parse_search_pattern() {
+        assert_eq!(pattern("4d5a90"), Some(vec![0x4d, 0x5a, 0x90]));
+        assert_eq!(pattern("0x4D5A"), Some(vec![0x4d, 0x5a]));
+        assert_eq!(pattern("\"_FVH\""), Some(b"_FVH".to_vec()));
+        assert_eq!(pattern("u\"Pa\""), Some(vec![b'P', 0, b'a', 0]));
+
+        assert_eq!(pattern("4d5"), None);
+        assert_eq!(pattern("zz"), None);
+        assert_eq!(pattern("\"\""), None);
+        assert_eq!(pattern("\"unterminated"), None);
+        assert_eq!(pattern("\"\u{e9}\""), None);
+        assert_eq!(pattern(&"ab".repeat(MAX_SEARCH_PATTERN_SIZE + 1)), None);
+        assert_eq!(pattern(&format!("u\"{}\"", "a".repeat(MAX_SEARCH_PATTERN_SIZE / 2 + 1))), None);
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("0x1000"), Some(0x1000));
+        assert_eq!(parse_hex("fff"), Some(0xfff));
+        assert_eq!(parse_hex("0xg"), None);
+    }
+}
@@ -13,22 +13,48 @@
 
 #[cfg(feature = "alloc")]
 use alloc::boxed::Box;
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
 use gdbstub::{
+    common::Signal,
     conn::ConnectionExt,
     stub::{GdbStubBuilder, SingleThreadStopReason, state_machine::GdbStubStateMachine},
 };
-use patina::serial::SerialIO;
+use patina::{
+    BinaryGuid,
+    error::EfiError,
+    serial::SerialIO,
+    time::{Clock, Deadline, Delay},
+};
 use patina_internal_cpu::interrupts::{ExceptionType, HandlerType, InterruptHandler, InterruptManager};
 use spin::Mutex;
 
 use crate::{
-    DebugError, Debugger, DebuggerLoggingPolicy, ExceptionInfo,
+    DebugError, Debugger, DebuggerLoggingPolicy, ExceptionInfo, ModuleDebugInfo, UnhandledExceptionPolicy,
     arch::{DebuggerArch, SystemArch},
-    dbg_target::PatinaTarget,
+    dbg_target::{self, PatinaTarget},
+    host_io::{self, CallBuffer, Request},
     system::SystemState,
-    transport::{LoggingSuspender, SerialConnection},
+    transport::{ConsoleRateLimit, LoggingSuspender, SerialConnection, write_dropped_notice, write_output_packets},
 };
 
+/// Interval at which the transport is polled while waiting for a client to attach.
+const ATTACH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The debugger is active. This is the initial state unless late attach is configured.
+const ATTACHED: u8 = 0;
+/// The debugger is installed for late attach, but ReadyToBoot has not been reached.
+const DORMANT: u8 = 1;
+/// The debugger is installed for late attach and the transport is watched for the
+/// attach sequence.
+const LISTENING: u8 = 2;
+
+/// Maximum number of stack frames recorded in a panic report.
+const PANIC_REPORT_FRAMES: usize = 8;
+
 /// Length of the static buffer used for GDB communication.
 const GDB_BUFF_LEN: usize = 0x2000;
 
@@ -56,10 +82,31 @@ where
     transport: T,
     /// The exception types the debugger will register for.
     exception_types: &'static [usize],
+    /// Controls which exceptions without another handler break in.
+    unhandled_exception_policy: UnhandledExceptionPolicy,
     /// Controls what the debugger does with logging.
     log_policy: DebuggerLoggingPolicy,
     /// Whether initializing the transport should be skipped.
     no_transport_init: bool,
+    /// Whether to single step with a temporary breakpoint on the next instruction.
+    software_step: bool,
+    /// The byte sequence that activates a debugger installed for late attach.
+    late_attach: Option<&'static [u8]>,
+    /// Late attach state of the debugger, one of [`ATTACHED`], [`DORMANT`], or [`LISTENING`].
+    attach_state: AtomicU8,
+    /// Number of bytes of the late attach sequence received so far.
+    attach_progress: AtomicUsize,
+    /// Minimum time between periodic polls of the transport.
+    poll_interval: Duration,
+    /// System time of the last periodic poll, in nanoseconds.
+    last_poll: AtomicU64,
+    /// Whether a File-I/O call is waiting on the client, during which polling must
+    /// not consume data from the transport.
+    host_io_active: AtomicBool,
+    /// Whether console output is forwarded to a connected client.
+    console_forwarding: AtomicBool,
+    /// Limits the rate of console output forwarded to a connected client.
+    console_limit: ConsoleRateLimit,
     /// Internal mutable debugger config.
     config: spin::RwLock<DebuggerConfig>,
     /// Internal mutable debugger state.
@@ -76,7 +123,16 @@ where
 struct DebuggerConfig {
     enabled: bool,
     initial_break: bool,
-    initial_break_timeout: u32,
+    /// How long to wait for a client before skipping the initial breakpoint.
+    initial_break_timeout: Option<InitialBreakTimeout>,
+}
+
+/// Timeout for the initial breakpoint and the time services used to measure it.
+#[derive(Clone, Copy)]
+struct InitialBreakTimeout {
+    timeout: Duration,
+    clock: &'static dyn Clock,
+    delay: &'static dyn Delay,
 }
 
 /// Internal Debugger State
@@ -90,6 +146,8 @@ where
 {
     gdb: Option<GdbStubStateMachine<'a, PatinaTarget, SerialConnection<'a, T>>>,
     gdb_buffer: Option<&'a [u8; GDB_BUFF_LEN]>,
+    /// The first byte from a client that attached during the initial break window, to be passed to the GDB stub.
+    client_byte: Option<u8>,
 }
 
 impl<T: SerialIO> PatinaDebugger<T> {
@@ -102,9 +160,23 @@ impl<T: SerialIO> PatinaDebugger<T> {
             transport,
             log_policy: DebuggerLoggingPolicy::SuspendLogging,
             no_transport_init: false,
+            software_step: false,
+            late_attach: None,
+            attach_state: AtomicU8::new(ATTACHED),
+            attach_progress: AtomicUsize::new(0),
+            poll_interval: Duration::ZERO,
+            last_poll: AtomicU64::new(0),
+            host_io_active: AtomicBool::new(false),
+            console_forwarding: AtomicBool::new(true),
+            console_limit: ConsoleRateLimit::new(0),
             exception_types: SystemArch::DEFAULT_EXCEPTION_TYPES,
-            config: spin::RwLock::new(DebuggerConfig { enabled: false, initial_break: true, initial_break_timeout: 0 }),
-            internal: Mutex::new(DebuggerInternal { gdb_buffer: None, gdb: None }),
+            unhandled_exception_policy: UnhandledExceptionPolicy::Panic,
+            config: spin::RwLock::new(DebuggerConfig {
+                enabled: false,
+                initial_break: true,
+                initial_break_timeout: None,
+            }),
+            internal: Mutex::new(DebuggerInternal { gdb_buffer: None, gdb: None, client_byte: None }),
             system_state: Mutex::new(SystemState::new()),
         }
     }
@@ -119,7 +191,8 @@ impl<T: SerialIO> PatinaDebugger<T> {
     pub const fn with_force_enable(mut self, enabled: bool) -> Self {
         if enabled {
             // Intentionally ignoring initial_break config until configuration is thought out.
-            self.config = spin::RwLock::new(DebuggerConfig { enabled, initial_break: true, initial_break_timeout: 0 });
+            self.config =
+                spin::RwLock::new(DebuggerConfig { enabled, initial_break: true, initial_break_timeout: None });
         }
         self
     }
@@ -139,12 +212,111 @@ impl<T: SerialIO> PatinaDebugger<T> {
         self
     }
 
+    /// Sets the minimum time between periodic polls of the transport for a break
+    /// request, e.g. a Ctrl-C sent by the client to interrupt a running system. By
+    /// default, the transport is checked on every timer tick. A longer interval
+    /// reduces the cost of polling a slow transport.
+    pub const fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Stops console output written through [`crate::DebuggerConsole`] from being
+    /// forwarded to a connected client, which then drops it instead. Forwarding
+    /// can also be toggled from the client with the `console` monitor command.
+    pub const fn without_console_forwarding(mut self) -> Self {
+        self.console_forwarding = AtomicBool::new(false);
+        self
+    }
+
+    /// Limits console output forwarded to a connected client to `bytes_per_second`,
+    /// so a burst of logging cannot monopolize a slow transport. Output beyond the
+    /// limit is dropped, and the client is told how many bytes were lost once
+    /// output resumes. Up to one second of output may be sent at once. The
+    /// allowance is replenished by [`crate::poll_debugger_periodic`], so the
+    /// limit requires the periodic poll. By default, output is not limited.
+    pub const fn with_console_rate_limit(mut self, bytes_per_second: usize) -> Self {
+        self.console_limit = ConsoleRateLimit::new(bytes_per_second);
+        self
+    }
+
+    /// Single steps by placing a temporary breakpoint on the next instruction instead of
+    /// using the architecture's single step mechanism, for configurations where it is
+    /// unavailable, such as AArch64 at EL2. Instructions whose successor cannot be
+    /// determined, or architectures without software stepping, use the architectural
+    /// single step. Currently only supported on AArch64.
+    pub const fn with_software_stepping(mut self, enabled: bool) -> Self {
+        self.software_step = enabled;
+        self
+    }
+
+    /// Installs the debugger for late attach. Instead of initializing the transport
+    /// and breaking in during initialization, the debugger stays dormant: exceptions
+    /// are passed through as if no debugger were installed, and the transport is not
+    /// touched. Modules are still tracked so symbols are available once attached.
+    ///
+    /// From ReadyToBoot on, the transport is initialized and periodically polled for
+    /// `sequence`, after which the debugger activates and breaks in. An empty sequence
+    /// disables this, leaving [`crate::attach`] as the only way to activate the
+    /// debugger, e.g. from a boot menu entry or hotkey handler.
+    pub const fn with_late_attach(mut self, sequence: &'static [u8]) -> Self {
+        self.late_attach = Some(sequence);
+        self
+    }
+
     /// Customizes the exception types for which the debugger will be invoked.
     pub const fn with_exception_types(mut self, exception_types: &'static [usize]) -> Self {
         self.exception_types = exception_types;
         self
     }
 
+    /// Configures how the debugger handles exceptions for which no handler is
+    /// registered, see [`UnhandledExceptionPolicy`]. Unlike the exception types
+    /// configured with [`Self::with_exception_types`], these exceptions may still be
+    /// claimed by other handlers. By default, unhandled exceptions panic.
+    pub const fn with_unhandled_exception_policy(mut self, policy: UnhandledExceptionPolicy) -> Self {
+        self.unhandled_exception_policy = policy;
+        self
+    }
+
+    /// Returns `true` if the debugger is enabled, whether or not it has been attached.
+    fn configured(&self) -> bool {
+        self.config.read().enabled
+    }
+
+    /// Initializes the transport, unless configured otherwise.
+    fn init_transport(&self) {
+        if !self.no_transport_init {
+            self.transport.init();
+        }
+    }
+
+    /// Consumes data from the transport while listening for the late attach sequence,
+    /// activating the debugger once it has been received.
+    fn poll_attach_sequence(&'static self) {
+        let sequence = self.late_attach.unwrap_or_default();
+        let mut matched = self.attach_progress.load(Ordering::Relaxed);
+        while let Some(byte) = self.transport.try_read() {
+            matched = advance_attach_sequence(sequence, matched, byte);
+            if matched == sequence.len() {
+                self.attach_progress.store(0, Ordering::Relaxed);
+                self.attach();
+                return;
+            }
+        }
+        self.attach_progress.store(matched, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if an exception of the given type, which did not arrive through
+    /// one of the debugger's own exception handlers, should break in.
+    fn breaks_on_unhandled(&self, exception_type: ExceptionType) -> bool {
+        match self.unhandled_exception_policy {
+            UnhandledExceptionPolicy::Panic => false,
+            UnhandledExceptionPolicy::BreakIn => true,
+            UnhandledExceptionPolicy::BreakInOn(exception_types) => exception_types.contains(&exception_type),
+        }
+    }
+
     /// Enables the debugger.
     ///
     /// Allows runtime enablement of the debugger. This should be called before the Patina
@@ -157,6 +329,34 @@ impl<T: SerialIO> PatinaDebugger<T> {
         config.enabled = enabled;
     }
 
+    /// Limits how long the initial breakpoint waits for a debugger client.
+    ///
+    /// Instead of breaking unconditionally, the debugger polls the transport for up to
+    /// `timeout`, measured with `clock`, and only breaks if a client sends data in that
+    /// time, waiting between polls with `delay`. Otherwise boot continues without breaking.
+    ///
+    pub fn set_initial_break_timeout(&self, timeout: Duration, clock: &'static dyn Clock, delay: &'static dyn Delay) {
+        let mut config = self.config.write();
+        config.initial_break_timeout = Some(InitialBreakTimeout { timeout, clock, delay });
+    }
+
+    /// Polls the transport until a client sends data or the timeout expires.
+    /// Returns true if a client is attached. The byte received from the client is
+    /// kept for the GDB stub, so the client's first packet is not corrupted.
+    fn wait_for_client(&self, InitialBreakTimeout { timeout, clock, delay }: InitialBreakTimeout) -> bool {
+        let deadline = Deadline::new(clock, timeout);
+        loop {
+            if let Some(byte) = self.transport.try_read() {
+                self.internal.lock().client_byte = Some(byte);
+                return true;
+            }
+            if deadline.has_expired(clock) {
+                return false;
+            }
+            delay.delay(ATTACH_POLL_INTERVAL.min(deadline.remaining(clock)));
+        }
+    }
+
     /// Enters the debugger from an exception.
     fn enter_debugger(&'static self, exception_info: ExceptionInfo) -> Result<ExceptionInfo, DebugError> {
         let mut debug = match self.internal.try_lock() {
@@ -164,7 +364,11 @@ impl<T: SerialIO> PatinaDebugger<T> {
             None => return Err(DebugError::Reentry),
         };
 
-        let mut target = PatinaTarget::new(exception_info, &self.system_state);
+        let mut target = PatinaTarget::new(exception_info, &self.system_state, self.software_step)
+            .with_console_forwarding(self.console_forwarding.load(Ordering::Relaxed));
+
+        // A break following a panic is reported as an abort along with the panic report.
+        let panicked = self.system_state.try_lock().is_some_and(|mut state| state.panic.take_pending());
 
         // Either take the existing state machine, or start one if this is the first break.
         let mut gdb = match debug.gdb {
@@ -172,13 +376,20 @@ impl<T: SerialIO> PatinaDebugger<T> {
             None => {
                 let const_buffer = debug.gdb_buffer.ok_or(DebugError::NotInitialized)?;
 
-                // Flush any stale data from the transport.
-                while self.transport.try_read().is_some() {}
+                // Flush any stale data from the transport, unless a client attached during
+                // the initial break window and the rest of its first packet is pending.
+                let client_byte = debug.client_byte.take();
+                if client_byte.is_none() {
+                    while self.transport.try_read().is_some() {}
+                }
 
                 // Always start with a stop code. This is not to spec, but is a
                 // useful hint to the client that a break has occurred. This allows
                 // the debugger to reconnect on scenarios like reboots.
-                self.transport.write("$T05thread:01;#07".as_bytes());
+                match panicked {
+                    true => self.transport.write("$T06thread:01;#08".as_bytes()),
+                    false => self.transport.write("$T05thread:01;#07".as_bytes()),
+                }
 
                 // SAFETY: The buffer will only ever be used by the paired GDB stub
                 // within the internal state lock. Because there is no GDB stub at
@@ -187,7 +398,7 @@ impl<T: SerialIO> PatinaDebugger<T> {
                 let mut_buffer =
                     unsafe { core::slice::from_raw_parts_mut(const_buffer.as_ptr() as *mut u8, const_buffer.len()) };
 
-                let conn = SerialConnection::new(&self.transport);
+                let conn = SerialConnection::new(&self.transport).with_peeked_byte(client_byte);
 
                 let builder = GdbStubBuilder::new(conn)
                     .with_packet_buffer(mut_buffer)
@@ -221,12 +432,20 @@ impl<T: SerialIO> PatinaDebugger<T> {
                     }
                 }
                 GdbStubStateMachine::Running(gdb) => {
-                    // Windbg doesn't handle many stop reasons well, this could be improved in the future and
-                    // wrapped in the windbg workarounds feature.
-                    match gdb.report_stop(
-                        &mut target,
-                        SingleThreadStopReason::SignalWithThread { tid: (), signal: gdbstub::common::Signal::SIGTRAP },
-                    ) {
+                    // Windbg doesn't handle many stop reasons well, so most breaks are reported as a trap.
+                    let stop_reason = match panicked {
+                        true => {
+                            self.write_panic_report();
+                            SingleThreadStopReason::SignalWithThread { tid: (), signal: Signal::SIGABRT }
+                        }
+                        false => {
+                            if let Some(description) = target.stop_description() {
+                                write_output_packets(&self.transport, description.as_bytes());
+                            }
+                            target.stop_reason()
+                        }
+                    };
+                    match gdb.report_stop(&mut target, stop_reason) {
                         Ok(gdb) => gdb,
                         Err(e) => return Err(DebugError::GdbStubError(e)),
                     }
@@ -249,9 +468,19 @@ impl<T: SerialIO> PatinaDebugger<T> {
 
         // Target is resumed, store the state machine for the next break and
         // return the updated exception info.
+        self.console_forwarding.store(target.console_forwarding(), Ordering::Relaxed);
         debug.gdb = Some(gdb);
         Ok(target.into_exception_info())
     }
+
+    /// Sends the report of the last panic to the client as console output.
+    fn write_panic_report(&self) {
+        if let Some(state) = self.system_state.try_lock()
+            && let Some(text) = state.panic.text()
+        {
+            write_output_packets(&self.transport, text.as_bytes());
+        }
+    }
 }
 
 impl<T: SerialIO> Debugger for PatinaDebugger<T> {
@@ -264,18 +493,22 @@ impl<T: SerialIO> Debugger for PatinaDebugger<T> {
 
         log::info!("Initializing debugger.");
         let initial_breakpoint = config.initial_break;
-        let _initial_break_timeout = config.initial_break_timeout; // TODO
+        let initial_break_timeout = config.initial_break_timeout;
 
         // Drop the lock to prevent deadlock in the initial breakpoint.
         drop(config);
 
-        // Initialize the underlying transport.
-        if !self.no_transport_init {
-            self.transport.init();
-        }
+        // A debugger installed for late attach leaves the transport and processor
+        // state untouched until it is attached.
+        if self.late_attach.is_some() {
+            self.attach_state.store(DORMANT, Ordering::Release);
+        } else {
+            // Initialize the underlying transport.
+            self.init_transport();
 
-        // Initialize any architecture specifics.
-        SystemArch::initialize();
+            // Initialize any architecture specifics.
+            SystemArch::initialize();
+        }
 
         // Initialize the communication buffer.
         {
@@ -304,6 +537,27 @@ impl<T: SerialIO> Debugger for PatinaDebugger<T> {
             }
         }
 
+        if !matches!(self.unhandled_exception_policy, UnhandledExceptionPolicy::Panic) {
+            let _ = interrupt_manager.unregister_unhandled_exception_handler();
+            let res = interrupt_manager.register_unhandled_exception_handler(HandlerType::Handler(self));
+            if res.is_err() {
+                log::error!("Failed to register debugger unhandled exception handler: {res:?}");
+            }
+        }
+
+        if self.late_attach.is_some() {
+            log::info!("Debugger installed for late attach.");
+            return;
+        }
+
+        if let (true, Some(timeout)) = (initial_breakpoint, initial_break_timeout) {
+            log::info!("Waiting {:?} for a debugger to attach.", timeout.timeout);
+            if !self.wait_for_client(timeout) {
+                log::info!("No debugger attached, skipping initial breakpoint.");
+                return;
+            }
+        }
+
         if initial_breakpoint {
             log::error!("************************************");
             log::error!("***  Initial debug breakpoint!   ***");
@@ -314,33 +568,138 @@ impl<T: SerialIO> Debugger for PatinaDebugger<T> {
     }
 
     fn enabled(&'static self) -> bool {
-        self.config.read().enabled
+        self.configured() && self.attach_state.load(Ordering::Acquire) == ATTACHED
     }
 
-    fn notify_module_load(&'static self, module_name: &str, address: usize, length: usize) {
-        if !self.enabled() {
+    fn notify_ready_to_boot(&'static self) {
+        if !self.configured() || self.late_attach.is_none_or(|sequence| sequence.is_empty()) {
+            return;
+        }
+
+        if self.attach_state.compare_exchange(DORMANT, LISTENING, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            self.init_transport();
+            log::info!("Debugger listening for the attach sequence.");
+        }
+    }
+
+    fn attach(&'static self) {
+        if !self.configured() {
+            return;
+        }
+
+        match self.attach_state.load(Ordering::Acquire) {
+            ATTACHED => {}
+            state => {
+                if state == DORMANT {
+                    self.init_transport();
+                }
+                SystemArch::initialize();
+                self.attach_state.store(ATTACHED, Ordering::Release);
+                log::error!("Debugger attached.");
+            }
+        }
+
+        SystemArch::breakpoint();
+    }
+
+    fn notify_module_load(
+        &'static self,
+        module_name: &str,
+        address: usize,
+        length: usize,
+        debug_info: Option<ModuleDebugInfo<'_>>,
+    ) {
+        if !self.configured() {
             return;
         }
 
         let breakpoint = {
             let mut state = self.system_state.lock();
-            state.modules.add_module(module_name, address, length);
+            state.modules.add_module(module_name, address, length, debug_info.as_ref());
             state.modules.check_module_breakpoints(module_name)
         };
 
-        if breakpoint {
+        if breakpoint && self.enabled() {
             log::error!("MODULE BREAKPOINT! {module_name} - 0x{address:x} - 0x{length:x}");
             SystemArch::breakpoint();
         }
     }
 
+    fn notify_module_unload(&'static self, address: usize) {
+        if !self.configured() {
+            return;
+        }
+
+        self.system_state.lock().modules.remove_module(address);
+    }
+
+    fn notify_module_start(&'static self, module_name: &str, file_guid: Option<&BinaryGuid>, entry_point: usize) {
+        if !self.enabled() {
+            return;
+        }
+
+        let breakpoint = self.system_state.lock().modules.check_entry_breakpoints(module_name, file_guid);
+        if breakpoint {
+            log::error!("ENTRY BREAKPOINT! {module_name} - entry point 0x{entry_point:x}");
+            SystemArch::breakpoint();
+        }
+    }
+
+    fn add_entry_breakpoint(&'static self, module: &str) {
+        if !self.configured() {
+            return;
+        }
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")] {
+                self.system_state.lock().modules.add_entry_breakpoint(module);
+            }
+            else {
+                log::warn!("Entry breakpoints are only supported with the 'alloc' feature enabled. Will not add breakpoint: {module}");
+            }
+        }
+    }
+
+    fn notify_panic(&'static self, info: &PanicInfo<'_>) {
+        if !self.enabled() {
+            return;
+        }
+
+        // The panic may have occurred while the system state was held, in which case
+        // the break is still taken without a report.
+        if let Some(mut state) = self.system_state.try_lock() {
+            state.panic.record(|out| {
+                writeln!(out, "{info}")?;
+                writeln!(out, "Frames:")?;
+                // SAFETY: The stack is walked from this function, whose frame and
+                // those of its callers are live.
+                let _ = unsafe {
+                    patina_stacktrace::StackTrace::walk(|frame| {
+                        let _ = writeln!(out, "  {:>2} {:016X} {}", frame.index, frame.return_address, frame);
+                        frame.index + 1 < PANIC_REPORT_FRAMES
+                    })
+                };
+                Ok(())
+            });
+        }
+
+        log::error!("PANIC BREAKPOINT!");
+        SystemArch::breakpoint();
+    }
+
     fn poll_debugger(&'static self) {
         const CRTL_C: u8 = 3;
 
-        if !self.enabled() {
+        if !self.configured() || self.host_io_active.load(Ordering::Acquire) {
             return;
         }
 
+        match self.attach_state.load(Ordering::Acquire) {
+            ATTACHED => {}
+            LISTENING => return self.poll_attach_sequence(),
+            _ => return,
+        }
+
         while let Some(byte) = self.transport.try_read() {
             if byte == CRTL_C {
                 // Ctrl-C
@@ -349,18 +708,103 @@ impl<T: SerialIO> Debugger for PatinaDebugger<T> {
         }
     }
 
+    fn poll_debugger_periodic(&'static self, now: Duration) {
+        if !self.configured() || self.attach_state.load(Ordering::Acquire) == DORMANT {
+            return;
+        }
+
+        let now = now.as_nanos() as u64;
+        self.console_limit.refill(now);
+
+        let last_poll = self.last_poll.load(Ordering::Relaxed);
+        if now >= last_poll && now - last_poll < self.poll_interval.as_nanos() as u64 {
+            return;
+        }
+
+        self.last_poll.store(now, Ordering::Relaxed);
+        self.poll_debugger();
+    }
+
+    fn write_console(&'static self, buffer: &[u8]) {
+        if !self.enabled() {
+            self.transport.write(buffer);
+            return;
+        }
+
+        // The internal state is locked while broken in, in which case the output is
+        // dropped rather than corrupting the debugger session.
+        let connected = match self.internal.try_lock() {
+            Some(internal) => internal.gdb.is_some(),
+            None => return,
+        };
+
+        if !connected {
+            self.transport.write(buffer);
+            return;
+        }
+
+        if !self.console_forwarding.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let allowed = self.console_limit.take(buffer.len());
+        if allowed > 0 {
+            let dropped = self.console_limit.take_dropped();
+            if dropped > 0 {
+                write_dropped_notice(&self.transport, dropped);
+            }
+            write_output_packets(&self.transport, &buffer[..allowed]);
+        }
+        self.console_limit.record_dropped(buffer.len() - allowed);
+    }
+
+    fn set_memory_map_provider(&'static self, provider: crate::MemoryMapFn) {
+        if !self.configured() {
+            return;
+        }
+
+        self.system_state.lock().memory_map = Some(provider);
+    }
+
     fn add_monitor_command(
         &'static self,
         command: &'static str,
         description: &'static str,
         callback: crate::MonitorCommandFn,
     ) {
-        if !self.enabled() {
+        if !self.configured() {
             return;
         }
 
         self.system_state.lock().add_monitor_command(command, description, callback);
     }
+
+    fn host_io(&'static self, request: &Request, buffer: CallBuffer<'_>) -> Result<u64, EfiError> {
+        if !self.enabled() {
+            return Err(EfiError::NotReady);
+        }
+
+        // Calls can only be made while the client waits for the running target to
+        // stop. The internal state is locked while broken in.
+        let internal = self.internal.try_lock().ok_or(EfiError::NotReady)?;
+        if !matches!(internal.gdb, Some(GdbStubStateMachine::Running(_))) {
+            return Err(EfiError::NotReady);
+        }
+
+        // Packets are acknowledged unless the client was offered no-ack mode.
+        let ack = cfg!(feature = "windbg_workarounds");
+        let mut packet = [0u8; GDB_BUFF_LEN];
+        self.host_io_active.store(true, Ordering::Release);
+        let result = host_io::call(&self.transport, ack, request, buffer, &mut packet);
+        self.host_io_active.store(false, Ordering::Release);
+        drop(internal);
+
+        // The call was interrupted by the user, stop as the client expects.
+        if result == Err(EfiError::Aborted) {
+            SystemArch::breakpoint();
+        }
+        result
+    }
 }
 
 impl<T: SerialIO> InterruptHandler for PatinaDebugger<T> {
@@ -369,6 +813,20 @@ impl<T: SerialIO> InterruptHandler for PatinaDebugger<T> {
         exception_type: ExceptionType,
         context: &mut patina_internal_cpu::interrupts::ExceptionContext,
     ) {
+        // Until attached, exceptions are handled as if there were no debugger.
+        if self.attach_state.load(Ordering::Acquire) != ATTACHED {
+            patina_internal_cpu::interrupts::unhandled_exception(exception_type, context);
+        }
+
+        // Exceptions of types the debugger did not register for arrive only when no
+        // other handler exists, and only break in if selected by the policy.
+        if !self.exception_types.contains(&exception_type) {
+            if !self.breaks_on_unhandled(exception_type) {
+                patina_internal_cpu::interrupts::unhandled_exception(exception_type, context);
+            }
+            log::error!("Unhandled Exception! {exception_type:#X} Breaking into the debugger.");
+        }
+
         // Suspend or disable logging. If suspended, logging will resume when the struct is dropped.
         let _log_suspend;
         match self.log_policy {
@@ -389,8 +847,17 @@ impl<T: SerialIO> InterruptHandler for PatinaDebugger<T> {
             return;
         }
 
+        // Restore the instruction replaced for a software step before the client can inspect memory.
+        let step_address = dbg_target::clear_step_breakpoint();
+
         let mut exception_info = loop {
-            let exception_info = SystemArch::process_entry(exception_type as u64, context);
+            let mut exception_info = SystemArch::process_entry(exception_type as u64, context);
+            if exception_info.exception_type == crate::ExceptionType::Breakpoint
+                && step_address == Some(exception_info.instruction_pointer)
+            {
+                exception_info.exception_type = crate::ExceptionType::Step;
+            }
+
             let result = self.enter_debugger(exception_info);
 
             match result {
@@ -416,6 +883,18 @@ impl<T: SerialIO> InterruptHandler for PatinaDebugger<T> {
     }
 }
 
+/// Advances the match of the late attach `sequence` by a received byte, returning
+/// the number of bytes of the sequence matched so far.
+fn advance_attach_sequence(sequence: &[u8], matched: usize, byte: u8) -> usize {
+    if sequence.get(matched) == Some(&byte) {
+        matched + 1
+    } else if sequence.first() == Some(&byte) {
+        1
+    } else {
+        0
+    }
+}
+
 fn debugger_crash(error: DebugError, exception_type: ExceptionType) -> ! {
     // Always log crashes, the debugger will stop working anyways.
     log::set_max_level(log::LevelFilter::Error);
@@ -426,3 +905,135 @@ fn debugger_crash(error: DebugError, exception_type: ExceptionType) -> ! {
     #[allow(clippy::empty_loop)]
     loop {}
 }
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use patina::time::ManualClock;
+
+    /// Transport that has no data until it has been polled a set number of times.
+    struct AttachingTransport {
+        polls_until_attach: AtomicUsize,
+    }
+
+    impl SerialIO for AttachingTransport {
+        fn init(&self) {}
+
+        fn write(&self, _buffer: &[u8]) {}
+
+        fn read(&self) -> u8 {
+            b'+'
+        }
+
+        fn try_read(&self) -> Option<u8> {
+            match self.polls_until_attach.fetch_sub(1, Ordering::SeqCst) {
+                0 => Some(b'+'),
+                _ => None,
+            }
+        }
+    }
+
+    /// Transport that returns a fixed sequence of bytes.
+    struct ScriptedTransport {
+        data: &'static [u8],
+        position: AtomicUsize,
+    }
+
+    impl SerialIO for ScriptedTransport {
+        fn init(&self) {}
+
+        fn write(&self, _buffer: &[u8]) {}
+
+        fn read(&self) -> u8 {
+            self.try_read().unwrap_or(0)
+        }
+
+        fn try_read(&self) -> Option<u8> {
+            self.data.get(self.position.fetch_add(1, Ordering::SeqCst)).copied()
+        }
+    }
+
+    fn timeout(clock: &'static ManualClock, timeout: Duration) -> InitialBreakTimeout {
+        InitialBreakTimeout { timeout, clock, delay: clock }
+    }
+
+    #[test]
+    fn test_attach_window_expires_without_client() {
+        static CLOCK: ManualClock = ManualClock::new();
+        let debugger = PatinaDebugger::new(AttachingTransport { polls_until_attach: AtomicUsize::new(usize::MAX) });
+
+        assert!(!debugger.wait_for_client(timeout(&CLOCK, Duration::from_millis(95))));
+        assert_eq!(CLOCK.now(), Duration::from_millis(95));
+    }
+
+    #[test]
+    fn test_attach_window_ends_when_client_attaches() {
+        static CLOCK: ManualClock = ManualClock::new();
+        let debugger = PatinaDebugger::new(AttachingTransport { polls_until_attach: AtomicUsize::new(3) });
+
+        assert!(debugger.wait_for_client(timeout(&CLOCK, Duration::from_secs(5))));
+        assert_eq!(CLOCK.now(), ATTACH_POLL_INTERVAL * 3);
+        // The byte that ended the window is kept for the GDB stub.
+        assert_eq!(debugger.internal.lock().client_byte, Some(b'+'));
+    }
+
+    #[test]
+    fn test_attach_window_preserves_first_packet() {
+        static CLOCK: ManualClock = ManualClock::new();
+        let debugger = PatinaDebugger::new(ScriptedTransport { data: b"$g#67", position: AtomicUsize::new(0) });
+
+        assert!(debugger.wait_for_client(timeout(&CLOCK, Duration::from_secs(5))));
+        let client_byte = debugger.internal.lock().client_byte.take();
+        let mut conn = SerialConnection::new(&debugger.transport).with_peeked_byte(client_byte);
+        let packet: [u8; 5] = core::array::from_fn(|_| conn.read().unwrap());
+        assert_eq!(&packet, b"$g#67");
+    }
+
+    #[test]
+    fn test_advance_attach_sequence() {
+        let sequence = b"aab";
+        assert_eq!(advance_attach_sequence(sequence, 0, b'a'), 1);
+        assert_eq!(advance_attach_sequence(sequence, 1, b'a'), 2);
+        assert_eq!(advance_attach_sequence(sequence, 2, b'b'), 3);
+        // A mismatch restarts the match, counting the byte if it starts the sequence.
+        assert_eq!(advance_attach_sequence(sequence, 1, b'b'), 0);
+        assert_eq!(advance_attach_sequence(b"ab", 1, b'a'), 1);
+        assert_eq!(advance_attach_sequence(b"", 0, b'a'), 0);
+    }
+
+    #[test]
+    fn test_late_attach_listens_from_ready_to_boot() {
+        let transport = ScriptedTransport { data: b"xxpatpatin", position: AtomicUsize::new(0) };
+        let debugger: &'static PatinaDebugger<ScriptedTransport> =
+            Box::leak(Box::new(PatinaDebugger::new(transport).with_late_attach(b"patina")));
+        debugger.enable(true);
+        debugger.attach_state.store(DORMANT, Ordering::SeqCst);
+        assert!(!debugger.enabled());
+
+        // Nothing is read from the transport before ReadyToBoot.
+        debugger.poll_debugger();
+        assert_eq!(debugger.transport.position.load(Ordering::SeqCst), 0);
+
+        debugger.notify_ready_to_boot();
+        assert_eq!(debugger.attach_state.load(Ordering::SeqCst), LISTENING);
+
+        // A partial sequence is remembered across polls without attaching.
+        debugger.poll_debugger();
+        assert_eq!(debugger.attach_progress.load(Ordering::SeqCst), 5);
+        assert_eq!(debugger.attach_state.load(Ordering::SeqCst), LISTENING);
+        assert!(!debugger.enabled());
+    }
+
+    #[test]
+    fn test_ready_to_boot_without_late_attach() {
+        let transport = ScriptedTransport { data: b"", position: AtomicUsize::new(0) };
+        let debugger: &'static PatinaDebugger<ScriptedTransport> = Box::leak(Box::new(PatinaDebugger::new(transport)));
+        debugger.enable(true);
+
+        debugger.notify_ready_to_boot();
+        assert_eq!(debugger.attach_state.load(Ordering::SeqCst), ATTACHED);
+        assert!(debugger.enabled());
+    }
+}
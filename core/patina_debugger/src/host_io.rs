@@ -0,0 +1,544 @@
+//! GDB File-I/O
+//!
+//! Implements the target side of the GDB File-I/O remote protocol, which lets code
+//! running on the target open, read, and write files on the debug host through the
+//! debugger connection. This allows on-target tests to load test vectors from the
+//! host and to save logs or crash dumps to it.
+//!
+//! A call is made by sending an `F` request packet to the client while the target
+//! is running. The client accesses the buffers of the call with memory read and
+//! write packets, then completes the call with an `F` reply. Memory accesses are
+//! only served within the buffers of the call in progress.
+//!
+//! See <https://sourceware.org/gdb/current/onlinedocs/gdb.html/File_002dI_002fO-Remote-Protocol-Extension.html>
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use core::fmt::{self, Write};
+use patina::{error::EfiError, serial::SerialIO};
+
+/// Flags for [`HostFile::open`], as defined by the GDB File-I/O protocol.
+pub mod open_flags {
+    /// Open for reading only.
+    pub const O_RDONLY: u32 = 0x0;
+    /// Open for writing only.
+    pub const O_WRONLY: u32 = 0x1;
+    /// Open for reading and writing.
+    pub const O_RDWR: u32 = 0x2;
+    /// Append to the end of the file on each write.
+    pub const O_APPEND: u32 = 0x8;
+    /// Create the file if it does not exist.
+    pub const O_CREAT: u32 = 0x200;
+    /// Truncate the file to zero length.
+    pub const O_TRUNC: u32 = 0x400;
+    /// Fail if the file already exists. Only valid with [`O_CREAT`].
+    pub const O_EXCL: u32 = 0x800;
+}
+
+/// Maximum length of a host path, including the terminating NUL.
+const MAX_PATH_LEN: usize = 256;
+
+/// Maximum length of a request packet payload, which only carries the call name
+/// and its integer arguments.
+const REQUEST_LEN: usize = 96;
+
+/// Reference point for [`HostFile::seek`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostSeek {
+    /// Offset from the start of the file.
+    Start,
+    /// Offset from the current position.
+    Current,
+    /// Offset from the end of the file.
+    End,
+}
+
+/// A file on the debug host, accessed through the GDB File-I/O protocol.
+///
+/// Calls are only possible while a GDB client is attached and the target is
+/// running, and fail with [`EfiError::NotReady`] otherwise. Each call blocks until
+/// the client replies. A call interrupted with Ctrl-C on the host fails with
+/// [`EfiError::Aborted`] and breaks into the debugger. The file is closed when
+/// dropped.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// use patina_debugger::{HostFile, open_flags};
+///
+/// let mut vectors = [0u8; 512];
+/// if let Ok(file) = HostFile::open("test_vectors.bin", open_flags::O_RDONLY, 0) {
+///     let _ = file.read(&mut vectors);
+/// }
+///
+/// if let Ok(log) = HostFile::open("crash.log", open_flags::O_WRONLY | open_flags::O_CREAT | open_flags::O_TRUNC, 0o644) {
+///     let _ = log.write(b"crash details\n");
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub struct HostFile {
+    /// The host file descriptor.
+    fd: u64,
+}
+
+impl HostFile {
+    /// Opens the file at `path` on the host with the given [`open_flags`]. `mode`
+    /// holds the Unix permission bits used if the file is created.
+    pub fn open(path: &str, flags: u32, mode: u32) -> Result<Self, EfiError> {
+        let mut path_buffer = [0u8; MAX_PATH_LEN];
+        if path.is_empty() || path.len() >= MAX_PATH_LEN || path.as_bytes().contains(&0) {
+            return Err(EfiError::InvalidParameter);
+        }
+        path_buffer[..path.len()].copy_from_slice(path.as_bytes());
+        let path = &path_buffer[..path.len() + 1];
+
+        let request =
+            Request::new(format_args!("Fopen,{:x}/{:x},{:x},{:x}", path.as_ptr() as usize, path.len(), flags, mode))?;
+        let fd = crate::host_io(&request, CallBuffer::Read(path))?;
+        Ok(HostFile { fd })
+    }
+
+    /// Reads from the file into `buffer`, returning the number of bytes read. Zero
+    /// is returned at the end of the file.
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let request =
+            Request::new(format_args!("Fread,{:x},{:x},{:x}", self.fd, buffer.as_ptr() as usize, buffer.len()))?;
+        Ok(crate::host_io(&request, CallBuffer::Write(buffer))? as usize)
+    }
+
+    /// Writes `buffer` to the file, returning the number of bytes written.
+    pub fn write(&self, buffer: &[u8]) -> Result<usize, EfiError> {
+        let request =
+            Request::new(format_args!("Fwrite,{:x},{:x},{:x}", self.fd, buffer.as_ptr() as usize, buffer.len()))?;
+        Ok(crate::host_io(&request, CallBuffer::Read(buffer))? as usize)
+    }
+
+    /// Moves the file position to `offset` from `whence`, returning the new
+    /// position from the start of the file.
+    pub fn seek(&self, offset: i64, whence: HostSeek) -> Result<u64, EfiError> {
+        let whence = match whence {
+            HostSeek::Start => 0,
+            HostSeek::Current => 1,
+            HostSeek::End => 2,
+        };
+        let sign = if offset < 0 { "-" } else { "" };
+        let request = Request::new(format_args!("Flseek,{:x},{sign}{:x},{whence:x}", self.fd, offset.unsigned_abs()))?;
+        crate::host_io(&request, CallBuffer::None)
+    }
+}
+
+impl Drop for HostFile {
+    fn drop(&mut self) {
+        if let Ok(request) = Request::new(format_args!("Fclose,{:x}", self.fd)) {
+            let _ = crate::host_io(&request, CallBuffer::None);
+        }
+    }
+}
+
+/// The payload of a File-I/O request packet.
+pub(crate) struct Request {
+    data: [u8; REQUEST_LEN],
+    len: usize,
+}
+
+impl Request {
+    /// Formats a request packet payload.
+    fn new(args: fmt::Arguments<'_>) -> Result<Self, EfiError> {
+        let mut request = Request { data: [0; REQUEST_LEN], len: 0 };
+        request.write_fmt(args).map_err(|_| EfiError::BufferTooSmall)?;
+        Ok(request)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl Write for Request {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > REQUEST_LEN {
+            return Err(fmt::Error);
+        }
+        self.data[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Target memory the client may access during a call.
+pub(crate) enum CallBuffer<'a> {
+    /// The call has no buffer.
+    None,
+    /// Memory the client reads, such as a path or the data to write to a file.
+    Read(&'a [u8]),
+    /// Memory the client writes, such as the destination of a file read.
+    Write(&'a mut [u8]),
+}
+
+impl CallBuffer<'_> {
+    /// Returns the buffer contents for `length` bytes at `address`, if within the buffer.
+    fn range(&self, address: u64, length: usize) -> Option<&[u8]> {
+        let buffer: &[u8] = match self {
+            CallBuffer::None => return None,
+            CallBuffer::Read(buffer) => buffer,
+            CallBuffer::Write(buffer) => buffer,
+        };
+        let offset = usize::try_from(address.checked_sub(buffer.as_ptr() as u64)?).ok()?;
+        buffer.get(offset..offset.checked_add(length)?)
+    }
+
+    /// Returns the writable buffer for `length` bytes at `address`, if within the buffer.
+    fn range_mut(&mut self, address: u64, length: usize) -> Option<&mut [u8]> {
+        let CallBuffer::Write(buffer) = self else {
+            return None;
+        };
+        let offset = usize::try_from(address.checked_sub(buffer.as_ptr() as u64)?).ok()?;
+        buffer.get_mut(offset..offset.checked_add(length)?)
+    }
+}
+
+/// Makes a File-I/O call to the client, returning the result of the call.
+///
+/// `packet` receives packets from the client and must hold the largest packet
+/// the client may send. `ack` selects whether packets are acknowledged, which is
+/// the case unless no-ack mode was negotiated with the client.
+pub(crate) fn call<T: SerialIO>(
+    transport: &T,
+    ack: bool,
+    request: &Request,
+    mut buffer: CallBuffer<'_>,
+    packet: &mut [u8],
+) -> Result<u64, EfiError> {
+    let mut writer = PacketWriter::new(transport);
+    writer.push(request.as_bytes());
+    writer.finish();
+
+    loop {
+        let len = receive_packet(transport, ack, packet)?;
+        let Some((&mut command, args)) = packet[..len].split_first_mut() else {
+            PacketWriter::new(transport).finish();
+            continue;
+        };
+
+        match command {
+            b'F' => return parse_reply(args),
+            b'm' => read_memory(transport, &buffer, args),
+            b'M' | b'X' => {
+                let status = write_memory(&mut buffer, command == b'X', args);
+                let mut writer = PacketWriter::new(transport);
+                writer.push(if status.is_some() { b"OK" } else { b"E01" });
+                writer.finish();
+            }
+            // Other requests are not supported while a call is in progress.
+            _ => PacketWriter::new(transport).finish(),
+        }
+    }
+}
+
+/// Receives a packet from the client into `packet`, returning the payload length.
+/// Bytes outside of a packet, such as acknowledgements, are discarded.
+fn receive_packet<T: SerialIO>(transport: &T, ack: bool, packet: &mut [u8]) -> Result<usize, EfiError> {
+    loop {
+        while transport.read() != b'$' {}
+
+        let mut len = 0;
+        let mut checksum = 0u8;
+        loop {
+            let byte = transport.read();
+            if byte == b'#' {
+                break;
+            }
+            if len == packet.len() {
+                return Err(EfiError::BufferTooSmall);
+            }
+            packet[len] = byte;
+            checksum = checksum.wrapping_add(byte);
+            len += 1;
+        }
+
+        let expected = [transport.read(), transport.read()];
+        let valid = core::str::from_utf8(&expected).ok().and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(checksum);
+        if ack {
+            transport.write(if valid { b"+" } else { b"-" });
+        }
+        if valid {
+            return Ok(len);
+        }
+    }
+}
+
+/// Handles a memory read packet, `m<address>,<length>`.
+fn read_memory<T: SerialIO>(transport: &T, buffer: &CallBuffer<'_>, args: &[u8]) {
+    let mut writer = PacketWriter::new(transport);
+    match parse_address_length(args).and_then(|(address, length)| buffer.range(address, length)) {
+        Some(data) => {
+            for byte in data {
+                writer.push_hex(*byte);
+            }
+        }
+        None => writer.push(b"E01"),
+    }
+    writer.finish();
+}
+
+/// Handles a memory write packet, `M<address>,<length>:<hex data>` or
+/// `X<address>,<length>:<binary data>`. Returns `None` if the write failed.
+fn write_memory(buffer: &mut CallBuffer<'_>, binary: bool, args: &mut [u8]) -> Option<()> {
+    let separator = args.iter().position(|&c| c == b':')?;
+    let (header, data) = args.split_at_mut(separator);
+    let (address, length) = parse_address_length(header)?;
+    let destination = buffer.range_mut(address, length)?;
+    let data = &mut data[1..];
+
+    let mut written = 0;
+    let mut index = 0;
+    while index < data.len() {
+        let byte = match binary {
+            true if data[index] == b'}' => {
+                index += 1;
+                *data.get(index)? ^ 0x20
+            }
+            true => data[index],
+            false => {
+                let hex = core::str::from_utf8(data.get(index..index + 2)?).ok()?;
+                index += 1;
+                u8::from_str_radix(hex, 16).ok()?
+            }
+        };
+        *destination.get_mut(written)? = byte;
+        written += 1;
+        index += 1;
+    }
+
+    (written == length).then_some(())
+}
+
+/// Parses `<address>,<length>` in hex.
+fn parse_address_length(args: &[u8]) -> Option<(u64, usize)> {
+    let args = core::str::from_utf8(args).ok()?;
+    let (address, length) = args.split_once(',')?;
+    Some((u64::from_str_radix(address, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+}
+
+/// Parses a File-I/O reply, `F<result>[,<errno>[,C]][;<attachment>]`.
+fn parse_reply(reply: &[u8]) -> Result<u64, EfiError> {
+    let reply = core::str::from_utf8(reply).map_err(|_| EfiError::ProtocolError)?;
+    let reply = reply.split(';').next().unwrap_or_default();
+    let mut fields = reply.split(',');
+
+    let result = fields.next().unwrap_or_default();
+    let errno = fields.next();
+    if fields.next() == Some("C") {
+        return Err(EfiError::Aborted);
+    }
+
+    match result.strip_prefix('-') {
+        Some(_) => {
+            let errno = errno.and_then(|errno| u32::from_str_radix(errno, 16).ok()).unwrap_or(0);
+            Err(errno_to_error(errno))
+        }
+        None => u64::from_str_radix(result, 16).map_err(|_| EfiError::ProtocolError),
+    }
+}
+
+/// Converts a GDB File-I/O errno value to an error.
+fn errno_to_error(errno: u32) -> EfiError {
+    match errno {
+        // ENOENT, ENOTDIR
+        2 | 20 => EfiError::NotFound,
+        // EPERM, EACCES
+        1 | 13 => EfiError::AccessDenied,
+        // EROFS
+        30 => EfiError::WriteProtected,
+        // EFBIG, ENOSPC
+        27 | 28 => EfiError::VolumeFull,
+        // ENFILE, EMFILE
+        23 | 24 => EfiError::OutOfResources,
+        // EINTR
+        4 => EfiError::Aborted,
+        // EBADF, EEXIST, EISDIR, EINVAL, ESPIPE, ENAMETOOLONG
+        9 | 17 | 21 | 22 | 29 | 91 => EfiError::InvalidParameter,
+        _ => EfiError::DeviceError,
+    }
+}
+
+/// Writes a packet to the transport, computing the checksum as data is added.
+struct PacketWriter<'a, T: SerialIO> {
+    transport: &'a T,
+    chunk: [u8; 64],
+    len: usize,
+    checksum: u8,
+}
+
+impl<'a, T: SerialIO> PacketWriter<'a, T> {
+    fn new(transport: &'a T) -> Self {
+        transport.write(b"$");
+        PacketWriter { transport, chunk: [0; 64], len: 0, checksum: 0 }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        for &byte in data {
+            if self.len == self.chunk.len() {
+                self.transport.write(&self.chunk);
+                self.len = 0;
+            }
+            self.chunk[self.len] = byte;
+            self.len += 1;
+            self.checksum = self.checksum.wrapping_add(byte);
+        }
+    }
+
+    fn push_hex(&mut self, byte: u8) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        self.push(&[HEX[(byte >> 4) as usize], HEX[(byte & 0xF) as usize]]);
+    }
+
+    fn finish(self) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        self.transport.write(&self.chunk[..self.len]);
+        self.transport.write(&[b'#', HEX[(self.checksum >> 4) as usize], HEX[(self.checksum & 0xF) as usize]]);
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::{collections::VecDeque, format, vec::Vec};
+
+    /// Transport replaying scripted client data and capturing target output.
+    #[derive(Default)]
+    struct ScriptedClient {
+        input: spin::Mutex<VecDeque<u8>>,
+        output: spin::Mutex<Vec<u8>>,
+    }
+
+    impl ScriptedClient {
+        fn new(packets: &[&[u8]]) -> Self {
+            let client = ScriptedClient::default();
+            for payload in packets {
+                client.input.lock().extend(packet(payload));
+            }
+            client
+        }
+
+        fn output(&self) -> std::string::String {
+            std::string::String::from_utf8_lossy(&self.output.lock()).into_owned()
+        }
+    }
+
+    impl SerialIO for ScriptedClient {
+        fn init(&self) {}
+
+        fn write(&self, buffer: &[u8]) {
+            self.output.lock().extend_from_slice(buffer);
+        }
+
+        fn read(&self) -> u8 {
+            self.input.lock().pop_front().expect("client script exhausted")
+        }
+
+        fn try_read(&self) -> Option<u8> {
+            self.input.lock().pop_front()
+        }
+    }
+
+    fn packet(payload: &[u8]) -> Vec<u8> {
+        let checksum = payload.iter().fold(0u8, |sum, &c| sum.wrapping_add(c));
+        let mut packet = Vec::from(b"$".as_slice());
+        packet.extend_from_slice(payload);
+        packet.extend_from_slice(format!("#{checksum:02x}").as_bytes());
+        packet
+    }
+
+    fn call_with(
+        client: &ScriptedClient,
+        ack: bool,
+        request: &Request,
+        buffer: CallBuffer<'_>,
+    ) -> Result<u64, EfiError> {
+        let mut receive = [0u8; 256];
+        call(client, ack, request, buffer, &mut receive)
+    }
+
+    #[test]
+    fn test_open_serves_the_path_to_the_client() {
+        let path = b"vectors.bin\0";
+        let address = path.as_ptr() as usize;
+        let read_path = format!("m{address:x},{:x}", path.len());
+        let client = ScriptedClient::new(&[read_path.as_bytes(), b"F3"]);
+
+        let request = Request::new(format_args!("Fopen,{address:x}/{:x},0,0", path.len())).unwrap();
+        assert_eq!(call_with(&client, false, &request, CallBuffer::Read(path)), Ok(3));
+
+        let mut expected = packet(request.as_bytes());
+        expected.extend(packet(b"766563746f72732e62696e00"));
+        assert_eq!(client.output(), std::string::String::from_utf8(expected).unwrap());
+    }
+
+    #[test]
+    fn test_read_accepts_hex_and_binary_writes() {
+        let mut buffer = [0u8; 6];
+        let address = buffer.as_ptr() as usize;
+        let hex_write = format!("M{address:x},2:abcd");
+        let mut binary_write = format!("X{:x},4:", address + 2).into_bytes();
+        binary_write.extend_from_slice(&[0x01, b'}', b'#' ^ 0x20, 0x7f, b'}', b'}' ^ 0x20]);
+        let client = ScriptedClient::new(&[hex_write.as_bytes(), &binary_write, b"F6"]);
+
+        let request = Request::new(format_args!("Fread,3,{address:x},6")).unwrap();
+        assert_eq!(call_with(&client, true, &request, CallBuffer::Write(&mut buffer)), Ok(6));
+        assert_eq!(buffer, [0xab, 0xcd, 0x01, b'#', 0x7f, b'}']);
+        assert!(client.output().contains(&std::string::String::from_utf8(packet(b"OK")).unwrap()));
+    }
+
+    #[test]
+    fn test_memory_outside_the_call_buffer_is_refused() {
+        let data = [0u8; 4];
+        let outside = format!("m{:x},8", data.as_ptr() as usize);
+        let client = ScriptedClient::new(&[outside.as_bytes(), b"mzz", b"M0,1:00", b"Fa"]);
+
+        let request = Request::new(format_args!("Fwrite,3,0,4")).unwrap();
+        assert_eq!(call_with(&client, false, &request, CallBuffer::Read(&data)), Ok(10));
+        assert_eq!(client.output().matches("$E01#a6").count(), 3);
+    }
+
+    #[test]
+    fn test_corrupted_packets_are_rejected() {
+        let client = ScriptedClient::new(&[]);
+        client.input.lock().extend(b"+$F1#00");
+        client.input.lock().extend(packet(b"F1"));
+
+        let request = Request::new(format_args!("Fclose,3")).unwrap();
+        assert_eq!(call_with(&client, true, &request, CallBuffer::None), Ok(1));
+        assert!(client.output().ends_with("-+"));
+    }
+
+    #[test]
+    fn test_error_replies() {
+        let request = Request::new(format_args!("Fclose,3")).unwrap();
+        let reply = |payload: &[u8]| call_with(&ScriptedClient::new(&[payload]), false, &request, CallBuffer::None);
+
+        assert_eq!(reply(b"F-1,2"), Err(EfiError::NotFound));
+        assert_eq!(reply(b"F-1,d"), Err(EfiError::AccessDenied));
+        assert_eq!(reply(b"F-1,270f"), Err(EfiError::DeviceError));
+        assert_eq!(reply(b"F-1,4,C"), Err(EfiError::Aborted));
+        assert_eq!(reply(b"F10;attachment"), Ok(0x10));
+        assert_eq!(reply(b"Fxyz"), Err(EfiError::ProtocolError));
+    }
+
+    #[test]
+    fn test_requests_are_bounded() {
+        assert!(Request::new(format_args!("Fopen,{:x}", u64::MAX)).is_ok());
+        assert_eq!(Request::new(format_args!("{:0200}", 0)).err(), Some(EfiError::BufferTooSmall));
+        assert_eq!(HostFile::open("", open_flags::O_RDONLY, 0).err(), Some(EfiError::InvalidParameter));
+        assert_eq!(HostFile::open("bad\0path", open_flags::O_RDONLY, 0).err(), Some(EfiError::InvalidParameter));
+    }
+}
@@ -0,0 +1,544 @@
+//! Windows Kernel Debugger (KD) Serial Packet Layer
+//!
+//! This module implements the KDCOM serial packet protocol used by WinDbg when
+//! attached over a serial port (`windbg -k com:...`). It provides the framing,
+//! checksums, packet ID sequencing, and acknowledge/resend handshake that the KD
+//! state change and state manipulate APIs are carried over. It is the transport
+//! half of a KD backend that can be used in place of the GDB remote protocol.
+//!
+//! ## Packet Format
+//!
+//! All fields are little-endian.
+//!
+//! ```text
+//! Data packet    : "0000" | type (u16) | byte count (u16) | packet ID (u32) | checksum (u32) | data | 0xAA
+//! Control packet : "iiii" | type (u16) | 0 (u16)          | packet ID (u32) | 0 (u32)
+//! Break-in       : 'b'
+//! ```
+//!
+//! The checksum is the sum of the data bytes. Every data packet is answered with an
+//! acknowledge control packet carrying the same packet ID, or a resend control
+//! packet if it was corrupted. Each side alternates bit 0 of its packet ID after
+//! every acknowledged packet, so a retransmitted packet can be recognized and
+//! dropped.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use patina::serial::SerialIO;
+
+/// Leader bytes of a data packet.
+pub const PACKET_LEADER: u32 = 0x3030_3030;
+/// Leader bytes of a control packet.
+pub const CONTROL_PACKET_LEADER: u32 = 0x6969_6969;
+/// Single byte sent by the host to request a break-in.
+pub const BREAKIN_PACKET_BYTE: u8 = b'b';
+/// Byte following the data of every data packet.
+pub const PACKET_TRAILING_BYTE: u8 = 0xAA;
+/// Packet ID used for the first packet after a reset.
+pub const INITIAL_PACKET_ID: u32 = 0x8080_0000;
+/// Packet ID used for packets sent before the connection is synchronized.
+pub const SYNC_PACKET_ID: u32 = 0x0000_0800;
+/// Maximum number of data bytes in a packet.
+pub const PACKET_MAX_SIZE: usize = 4000;
+/// Size of a packet header, including the leader.
+pub const PACKET_HEADER_SIZE: usize = 16;
+
+/// Number of times a data packet is sent before giving up on an acknowledgement.
+const MAX_SEND_ATTEMPTS: usize = 8;
+
+/// KD packet types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum PacketType {
+    /// State manipulate request or response.
+    StateManipulate = 2,
+    /// Debug I/O, such as debug print output.
+    DebugIo = 3,
+    /// Acknowledges a data packet.
+    Acknowledge = 4,
+    /// Requests the last data packet be sent again.
+    Resend = 5,
+    /// Resets the packet ID sequence.
+    Reset = 6,
+    /// 64-bit state change notification, such as an exception.
+    StateChange64 = 7,
+    /// Polls for a break-in request.
+    PollBreakin = 8,
+    /// Trace I/O.
+    TraceIo = 9,
+    /// Control request.
+    ControlRequest = 10,
+    /// File I/O.
+    FileIo = 11,
+}
+
+impl TryFrom<u16> for PacketType {
+    type Error = KdError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Ok(match value {
+            2 => PacketType::StateManipulate,
+            3 => PacketType::DebugIo,
+            4 => PacketType::Acknowledge,
+            5 => PacketType::Resend,
+            6 => PacketType::Reset,
+            7 => PacketType::StateChange64,
+            8 => PacketType::PollBreakin,
+            9 => PacketType::TraceIo,
+            10 => PacketType::ControlRequest,
+            11 => PacketType::FileIo,
+            _ => return Err(KdError::UnknownPacketType(value)),
+        })
+    }
+}
+
+/// Errors from the KD packet layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdError {
+    /// The packet type is not a known KD packet type.
+    UnknownPacketType(u16),
+    /// The packet data does not fit in the packet or the provided buffer.
+    PacketTooLarge(usize),
+    /// The host did not acknowledge a packet after repeated attempts.
+    NotAcknowledged,
+    /// The host requested a break-in.
+    Breakin,
+    /// The host reset the packet ID sequence.
+    Reset,
+}
+
+/// A data packet received from the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceivedPacket {
+    /// The type of the packet.
+    pub packet_type: PacketType,
+    /// The number of data bytes written to the receive buffer.
+    pub length: usize,
+}
+
+/// Header of a packet as read from the wire, without its leader.
+struct PacketHeader {
+    packet_type: u16,
+    byte_count: u16,
+    packet_id: u32,
+    checksum: u32,
+}
+
+/// Result of reading a single packet from the transport.
+enum Incoming {
+    /// A data packet with a valid checksum.
+    Data { packet_type: u16, packet_id: u32, length: usize },
+    /// A data packet that failed validation and must be resent.
+    Corrupt,
+    /// A control packet.
+    Control { packet_type: u16, packet_id: u32 },
+    /// A break-in request.
+    Breakin,
+}
+
+/// Computes the KD checksum of packet data.
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |sum, &byte| sum.wrapping_add(byte as u32))
+}
+
+/// KD serial connection
+///
+/// Sends and receives KD packets over a [`SerialIO`] transport, handling the
+/// acknowledgement handshake and packet ID sequencing.
+///
+pub struct KdConnection<'a, T: SerialIO> {
+    /// Serial IO transport for connecting to the debugger.
+    transport: &'a T,
+    /// Packet ID of the next data packet sent to the host.
+    next_packet_id: u32,
+    /// Packet ID expected on the next data packet from the host.
+    remote_packet_id: u32,
+}
+
+impl<'a, T: SerialIO> KdConnection<'a, T> {
+    /// Create a new KdConnection.
+    pub fn new(transport: &'a T) -> Self {
+        KdConnection {
+            transport,
+            next_packet_id: INITIAL_PACKET_ID | SYNC_PACKET_ID,
+            remote_packet_id: INITIAL_PACKET_ID,
+        }
+    }
+
+    /// Resets the packet ID sequence on both sides of the connection.
+    pub fn reset(&mut self) {
+        self.next_packet_id = INITIAL_PACKET_ID;
+        self.remote_packet_id = INITIAL_PACKET_ID;
+        self.send_control(PacketType::Reset, 0);
+    }
+
+    /// Returns true if the host has sent a break-in request. Other pending bytes
+    /// are discarded.
+    pub fn poll_breakin(&self) -> bool {
+        let mut breakin = false;
+        while let Some(byte) = self.transport.try_read() {
+            breakin |= byte == BREAKIN_PACKET_BYTE;
+        }
+        breakin
+    }
+
+    /// Sends a data packet made of `header` followed by `data`, and waits for the
+    /// host to acknowledge it.
+    pub fn send_packet(&mut self, packet_type: PacketType, header: &[u8], data: &[u8]) -> Result<(), KdError> {
+        let length = header.len() + data.len();
+        if length > PACKET_MAX_SIZE {
+            return Err(KdError::PacketTooLarge(length));
+        }
+
+        let mut scratch = [0u8; 0];
+        for _ in 0..MAX_SEND_ATTEMPTS {
+            self.write_data_packet(packet_type, header, data);
+
+            // Wait for the acknowledgement. Data packets that arrive in the meantime are
+            // dropped without acknowledgement, so the host will send them again.
+            loop {
+                match self.read_packet(&mut scratch) {
+                    Incoming::Control { packet_type, packet_id } if packet_type == PacketType::Acknowledge as u16 => {
+                        if packet_id == (self.next_packet_id & !SYNC_PACKET_ID) {
+                            self.next_packet_id ^= 1;
+                            self.next_packet_id &= !SYNC_PACKET_ID;
+                            return Ok(());
+                        }
+                    }
+                    Incoming::Control { packet_type, .. } if packet_type == PacketType::Reset as u16 => {
+                        self.reset();
+                        break;
+                    }
+                    Incoming::Control { packet_type, .. } if packet_type == PacketType::Resend as u16 => break,
+                    Incoming::Breakin => return Err(KdError::Breakin),
+                    _ => continue,
+                }
+            }
+        }
+
+        Err(KdError::NotAcknowledged)
+    }
+
+    /// Waits for a data packet from the host and copies its data into `buffer`.
+    ///
+    /// Valid packets are acknowledged, corrupted packets are answered with a resend
+    /// request, and retransmissions of an already received packet are acknowledged
+    /// and dropped.
+    pub fn receive_packet(&mut self, buffer: &mut [u8]) -> Result<ReceivedPacket, KdError> {
+        loop {
+            match self.read_packet(buffer) {
+                Incoming::Data { packet_type, packet_id, length } => {
+                    self.send_control(PacketType::Acknowledge, packet_id);
+                    if packet_id != self.remote_packet_id {
+                        continue;
+                    }
+                    self.remote_packet_id ^= 1;
+                    return Ok(ReceivedPacket { packet_type: PacketType::try_from(packet_type)?, length });
+                }
+                Incoming::Corrupt => self.send_control(PacketType::Resend, 0),
+                Incoming::Control { packet_type, .. } if packet_type == PacketType::Reset as u16 => {
+                    self.reset();
+                    return Err(KdError::Reset);
+                }
+                Incoming::Control { .. } => continue,
+                Incoming::Breakin => return Err(KdError::Breakin),
+            }
+        }
+    }
+
+    /// Writes a data packet to the transport.
+    fn write_data_packet(&self, packet_type: PacketType, header: &[u8], data: &[u8]) {
+        let length = (header.len() + data.len()) as u16;
+        let sum = checksum(header).wrapping_add(checksum(data));
+        self.write_header(PACKET_LEADER, packet_type as u16, length, self.next_packet_id, sum);
+        self.transport.write(header);
+        self.transport.write(data);
+        self.transport.write(&[PACKET_TRAILING_BYTE]);
+    }
+
+    /// Writes a control packet to the transport.
+    fn send_control(&self, packet_type: PacketType, packet_id: u32) {
+        self.write_header(CONTROL_PACKET_LEADER, packet_type as u16, 0, packet_id, 0);
+    }
+
+    /// Writes a packet header to the transport.
+    fn write_header(&self, leader: u32, packet_type: u16, byte_count: u16, packet_id: u32, checksum: u32) {
+        let mut bytes = [0u8; PACKET_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&leader.to_le_bytes());
+        bytes[4..6].copy_from_slice(&packet_type.to_le_bytes());
+        bytes[6..8].copy_from_slice(&byte_count.to_le_bytes());
+        bytes[8..12].copy_from_slice(&packet_id.to_le_bytes());
+        bytes[12..16].copy_from_slice(&checksum.to_le_bytes());
+        self.transport.write(&bytes);
+    }
+
+    /// Reads the next packet from the transport, copying data packet contents into `buffer`.
+    fn read_packet(&self, buffer: &mut [u8]) -> Incoming {
+        let leader = match self.read_leader() {
+            Some(leader) => leader,
+            None => return Incoming::Breakin,
+        };
+
+        let header = self.read_header();
+        if leader == CONTROL_PACKET_LEADER {
+            return Incoming::Control { packet_type: header.packet_type, packet_id: header.packet_id };
+        }
+
+        let length = header.byte_count as usize;
+        if length > PACKET_MAX_SIZE || length > buffer.len() {
+            // Drain the oversized packet and its trailing byte so the stream stays in sync.
+            for _ in 0..=length.min(PACKET_MAX_SIZE) {
+                self.transport.read();
+            }
+            return Incoming::Corrupt;
+        }
+
+        for byte in buffer[..length].iter_mut() {
+            *byte = self.transport.read();
+        }
+
+        if self.transport.read() != PACKET_TRAILING_BYTE || checksum(&buffer[..length]) != header.checksum {
+            return Incoming::Corrupt;
+        }
+
+        Incoming::Data { packet_type: header.packet_type, packet_id: header.packet_id, length }
+    }
+
+    /// Reads bytes until a full packet leader is seen. Returns `None` if a break-in
+    /// byte is received outside of a leader.
+    fn read_leader(&self) -> Option<u32> {
+        let mut leader_byte = 0u8;
+        let mut count = 0;
+        while count < 4 {
+            let byte = self.transport.read();
+            match byte {
+                0x30 | 0x69 if count == 0 || byte == leader_byte => {
+                    leader_byte = byte;
+                    count += 1;
+                }
+                0x30 | 0x69 => {
+                    leader_byte = byte;
+                    count = 1;
+                }
+                BREAKIN_PACKET_BYTE if count == 0 => return None,
+                _ => count = 0,
+            }
+        }
+        Some(u32::from_le_bytes([leader_byte; 4]))
+    }
+
+    /// Reads the remainder of a packet header after the leader.
+    fn read_header(&self) -> PacketHeader {
+        let mut bytes = [0u8; PACKET_HEADER_SIZE - 4];
+        for byte in bytes.iter_mut() {
+            *byte = self.transport.read();
+        }
+        PacketHeader {
+            packet_type: u16::from_le_bytes([bytes[0], bytes[1]]),
+            byte_count: u16::from_le_bytes([bytes[2], bytes[3]]),
+            packet_id: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            checksum: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::VecDeque, vec::Vec};
+
+    /// Serial transport that replays scripted host bytes and records target output.
+    #[derive(Default)]
+    struct ScriptedSerial {
+        input: RefCell<VecDeque<u8>>,
+        output: RefCell<Vec<u8>>,
+    }
+
+    // SAFETY: The tests are single threaded.
+    unsafe impl Sync for ScriptedSerial {}
+
+    impl ScriptedSerial {
+        fn push(&self, bytes: &[u8]) {
+            self.input.borrow_mut().extend(bytes);
+        }
+
+        fn take_output(&self) -> Vec<u8> {
+            self.output.take()
+        }
+    }
+
+    impl SerialIO for ScriptedSerial {
+        fn init(&self) {}
+
+        fn write(&self, buffer: &[u8]) {
+            self.output.borrow_mut().extend_from_slice(buffer);
+        }
+
+        fn read(&self) -> u8 {
+            self.input.borrow_mut().pop_front().expect("target read past the end of the script")
+        }
+
+        fn try_read(&self) -> Option<u8> {
+            self.input.borrow_mut().pop_front()
+        }
+    }
+
+    fn header(leader: u32, packet_type: u16, byte_count: u16, packet_id: u32, checksum: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&leader.to_le_bytes());
+        bytes.extend_from_slice(&packet_type.to_le_bytes());
+        bytes.extend_from_slice(&byte_count.to_le_bytes());
+        bytes.extend_from_slice(&packet_id.to_le_bytes());
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    fn data_packet(packet_type: PacketType, packet_id: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = header(PACKET_LEADER, packet_type as u16, data.len() as u16, packet_id, checksum(data));
+        bytes.extend_from_slice(data);
+        bytes.push(PACKET_TRAILING_BYTE);
+        bytes
+    }
+
+    fn control_packet(packet_type: PacketType, packet_id: u32) -> Vec<u8> {
+        header(CONTROL_PACKET_LEADER, packet_type as u16, 0, packet_id, 0)
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(&[]), 0);
+        assert_eq!(checksum(&[1, 2, 3, 0xFF]), 0x105);
+    }
+
+    #[test]
+    fn test_send_packet_waits_for_ack() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+
+        serial.push(&control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID));
+        kd.send_packet(PacketType::StateChange64, &[1, 2], &[3]).unwrap();
+
+        let mut expected =
+            header(PACKET_LEADER, PacketType::StateChange64 as u16, 3, INITIAL_PACKET_ID | SYNC_PACKET_ID, 6);
+        expected.extend_from_slice(&[1, 2, 3, PACKET_TRAILING_BYTE]);
+        assert_eq!(serial.take_output(), expected);
+
+        // The next packet uses the alternate packet ID.
+        serial.push(&control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID | 1));
+        kd.send_packet(PacketType::DebugIo, &[], &[]).unwrap();
+        assert_eq!(serial.take_output(), data_packet(PacketType::DebugIo, INITIAL_PACKET_ID | 1, &[]));
+    }
+
+    #[test]
+    fn test_send_packet_resends_on_request() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+
+        serial.push(&control_packet(PacketType::Resend, 0));
+        serial.push(&control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID));
+        kd.send_packet(PacketType::DebugIo, &[], &[7]).unwrap();
+
+        let packet = data_packet(PacketType::DebugIo, INITIAL_PACKET_ID | SYNC_PACKET_ID, &[7]);
+        assert_eq!(serial.take_output(), [packet.clone(), packet].concat());
+    }
+
+    #[test]
+    fn test_send_packet_gives_up_without_ack() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+
+        for _ in 0..MAX_SEND_ATTEMPTS {
+            serial.push(&control_packet(PacketType::Resend, 0));
+        }
+        assert_eq!(kd.send_packet(PacketType::DebugIo, &[], &[]), Err(KdError::NotAcknowledged));
+    }
+
+    #[test]
+    fn test_send_packet_too_large() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+        let data = [0u8; PACKET_MAX_SIZE + 1];
+        assert_eq!(kd.send_packet(PacketType::DebugIo, &[], &data), Err(KdError::PacketTooLarge(PACKET_MAX_SIZE + 1)));
+        assert!(serial.take_output().is_empty());
+    }
+
+    #[test]
+    fn test_receive_packet_acks_and_drops_duplicates() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+        let mut buffer = [0u8; 16];
+
+        // Noise before the leader is skipped.
+        serial.push(&[0x00, 0x69, 0x00]);
+        serial.push(&data_packet(PacketType::StateManipulate, INITIAL_PACKET_ID, &[9, 8, 7]));
+        let packet = kd.receive_packet(&mut buffer).unwrap();
+        assert_eq!(packet, ReceivedPacket { packet_type: PacketType::StateManipulate, length: 3 });
+        assert_eq!(&buffer[..3], &[9, 8, 7]);
+        assert_eq!(serial.take_output(), control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID));
+
+        // A retransmission of the same packet is acknowledged but not delivered.
+        serial.push(&data_packet(PacketType::StateManipulate, INITIAL_PACKET_ID, &[9, 8, 7]));
+        serial.push(&data_packet(PacketType::StateManipulate, INITIAL_PACKET_ID | 1, &[1]));
+        let packet = kd.receive_packet(&mut buffer).unwrap();
+        assert_eq!(packet.length, 1);
+        assert_eq!(buffer[0], 1);
+        assert_eq!(
+            serial.take_output(),
+            [
+                control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID),
+                control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID | 1)
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_receive_packet_requests_resend_on_corruption() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+        let mut buffer = [0u8; 16];
+
+        let mut corrupt = data_packet(PacketType::StateManipulate, INITIAL_PACKET_ID, &[1, 2]);
+        corrupt[PACKET_HEADER_SIZE] = 0xFF;
+        serial.push(&corrupt);
+        serial.push(&data_packet(PacketType::StateManipulate, INITIAL_PACKET_ID, &[1, 2]));
+
+        assert_eq!(kd.receive_packet(&mut buffer).unwrap().length, 2);
+        assert_eq!(
+            serial.take_output(),
+            [control_packet(PacketType::Resend, 0), control_packet(PacketType::Acknowledge, INITIAL_PACKET_ID)]
+                .concat()
+        );
+    }
+
+    #[test]
+    fn test_receive_packet_breakin_and_reset() {
+        let serial = ScriptedSerial::default();
+        let mut kd = KdConnection::new(&serial);
+        let mut buffer = [0u8; 16];
+
+        serial.push(&[BREAKIN_PACKET_BYTE]);
+        assert_eq!(kd.receive_packet(&mut buffer), Err(KdError::Breakin));
+
+        serial.push(&control_packet(PacketType::Reset, 0));
+        assert_eq!(kd.receive_packet(&mut buffer), Err(KdError::Reset));
+        assert_eq!(serial.take_output(), control_packet(PacketType::Reset, 0));
+    }
+
+    #[test]
+    fn test_poll_breakin() {
+        let serial = ScriptedSerial::default();
+        let kd = KdConnection::new(&serial);
+
+        assert!(!kd.poll_breakin());
+        serial.push(&[0x00, BREAKIN_PACKET_BYTE, 0x00]);
+        assert!(kd.poll_breakin());
+        assert!(!kd.poll_breakin());
+    }
+}
@@ -79,6 +79,13 @@
 //! for more details. Notably, if the device is using the same transport for
 //! logging and debugger, it is advisable to use `.without_log_init()`.
 //!
+//! ## Transports
+//!
+//! The debugger communicates over any [`SerialIO`](patina::serial::SerialIO)
+//! implementation. Devices without a free UART can use [`TcpConnection`], which
+//! adapts a platform [`NetworkIo`] implementation over its NIC driver and TCP/IP
+//! stack, e.g. `PatinaDebugger::new(TcpConnection::new(network, 5555))`.
+//!
 //! ## Features
 //!
 //! `windbg_workarounds` - (Default) Enables workarounds for Windbg compatibility.
@@ -98,6 +105,8 @@
 mod arch;
 mod dbg_target;
 mod debugger;
+mod host_io;
+pub mod kd;
 mod memory;
 mod system;
 mod transport;
@@ -105,10 +114,15 @@ mod transport;
 extern crate alloc;
 
 pub use debugger::PatinaDebugger;
+pub use host_io::{HostFile, HostSeek, open_flags};
+pub use transport::{DebuggerConsole, NetworkIo, TcpConnection};
+#[cfg(feature = "usb_debug")]
+pub use transport::{EhciDebugPort, UsbDebugConnection, UsbDebugPort, XhciDebugCapability};
 
 #[cfg(not(test))]
 use arch::{DebuggerArch, SystemArch};
-use patina::serial::SerialIO;
+use core::{panic::PanicInfo, time::Duration};
+use patina::{BinaryGuid, error::EfiError, serial::SerialIO};
 use patina_internal_cpu::interrupts::{ExceptionContext, InterruptManager};
 
 /// Global instance of the debugger.
@@ -133,6 +147,41 @@ static DEBUGGER: spin::Once<&dyn Debugger> = spin::Once::new();
 /// or using the `write!` macro.
 pub type MonitorCommandFn = fn(&mut core::str::SplitWhitespace<'_>, &mut dyn core::fmt::Write);
 
+/// Kind of a memory region reported to the debugger client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegionKind {
+    /// Writable memory, in which the client may place software breakpoints.
+    Ram,
+    /// Device memory, such as MMIO registers. The client may read and write it,
+    /// but the debugger refuses software breakpoints here, as writing a breakpoint
+    /// instruction would act on the device. This is reported to the client as
+    /// `ram`, as the GDB memory map has no kind for writable memory that cannot
+    /// hold software breakpoints.
+    Mmio,
+    /// Read-only memory, such as flash-mapped firmware volumes. The client will
+    /// not write to it, and uses hardware breakpoints here.
+    Rom,
+}
+
+/// A region of the memory map reported to the debugger client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Base address of the region.
+    pub base: u64,
+    /// Length of the region in bytes.
+    pub length: u64,
+    /// Kind of the region.
+    pub kind: MemoryRegionKind,
+}
+
+/// Type for a memory map provider, see [set_memory_map_provider].
+///
+/// The function is invoked while the system is broken in, and should call the
+/// provided visitor for each accessible region of memory in ascending address
+/// order. It must not block on locks which may be held by the broken in code,
+/// and should report no regions if the memory map is not available.
+pub type MemoryMapFn = fn(&mut dyn FnMut(MemoryRegion));
+
 /// Trait for debugger interaction. This is required to allow for a global to the
 /// platform specific debugger implementation. For safety, these routines should
 /// only be invoked on the global instance of the debugger.
@@ -140,17 +189,53 @@ trait Debugger: Sync {
     /// Initializes the debugger.
     fn initialize(&'static self, interrupt_manager: &mut dyn InterruptManager);
 
-    /// Checks if the debugger is enabled.
+    /// Checks if the debugger is enabled and attached.
     fn enabled(&'static self) -> bool;
 
+    /// Notifies the debugger that ReadyToBoot has been reached.
+    fn notify_ready_to_boot(&'static self);
+
+    /// Activates a debugger installed for late attach and breaks in.
+    fn attach(&'static self);
+
     /// Notifies the debugger of a module load.
-    fn notify_module_load(&'static self, module_name: &str, _address: usize, _length: usize);
+    fn notify_module_load(
+        &'static self,
+        module_name: &str,
+        _address: usize,
+        _length: usize,
+        _debug_info: Option<ModuleDebugInfo<'_>>,
+    );
+
+    /// Notifies the debugger of a module unload.
+    fn notify_module_unload(&'static self, _address: usize);
+
+    /// Notifies the debugger that a module is about to start executing.
+    fn notify_module_start(&'static self, module_name: &str, _file_guid: Option<&BinaryGuid>, _entry_point: usize);
+
+    /// Arms a breakpoint on the entry point of a module.
+    fn add_entry_breakpoint(&'static self, module: &str);
+
+    /// Notifies the debugger of a panic.
+    fn notify_panic(&'static self, info: &PanicInfo<'_>);
 
     /// Polls the debugger for any pending interrupts.
     fn poll_debugger(&'static self);
 
+    /// Writes console output to the debugger transport.
+    fn write_console(&'static self, buffer: &[u8]);
+
+    /// Polls the debugger for any pending interrupts if the poll interval has elapsed.
+    fn poll_debugger_periodic(&'static self, now: Duration);
+
+    /// Sets the memory map provider of the debugger.
+    fn set_memory_map_provider(&'static self, provider: MemoryMapFn);
+
     /// Adds a monitor command to the debugger.
     fn add_monitor_command(&'static self, cmd: &'static str, description: &'static str, function: MonitorCommandFn);
+
+    /// Makes a File-I/O call to the debugger client.
+    fn host_io(&'static self, request: &host_io::Request, buffer: host_io::CallBuffer<'_>) -> Result<u64, EfiError>;
 }
 
 #[derive(Debug)]
@@ -172,6 +257,57 @@ enum DebugError {
     TransportFailure,
 }
 
+/// Debug information for a loaded module, used by the debugger client to locate
+/// the module's symbols.
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleDebugInfo<'a> {
+    /// Path of the symbol file recorded in the image's debug directory, e.g. the PDB path.
+    pub symbol_path: &'a str,
+    /// Offset of the first section from the image base.
+    pub first_section_offset: usize,
+    /// Identity of the symbol file the module was linked with, if recorded in the image.
+    pub symbol_id: Option<SymbolId<'a>>,
+}
+
+/// The identity of the symbol file a module was linked with. Symbol servers index
+/// symbol files by this identity, so it selects exactly matching symbols even when
+/// several builds share a file name.
+///
+/// It displays as the symbol server key: for a PDB, the GUID as 32 hexadecimal
+/// digits followed by the age in hexadecimal, and for a build-id, its bytes in
+/// lowercase hexadecimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolId<'a> {
+    /// The GUID and age from a CodeView (RSDS) debug directory entry, identifying a PDB.
+    Pdb {
+        /// The PDB GUID, in its in-memory (mixed-endian) byte order.
+        guid: [u8; 16],
+        /// The PDB age.
+        age: u32,
+    },
+    /// A GNU build-id, identifying a DWARF debug file.
+    BuildId(&'a [u8]),
+}
+
+impl core::fmt::Display for SymbolId<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SymbolId::Pdb { guid, age } => {
+                write!(
+                    f,
+                    "{:08X}{:04X}{:04X}",
+                    u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]),
+                    u16::from_le_bytes([guid[4], guid[5]]),
+                    u16::from_le_bytes([guid[6], guid[7]])
+                )?;
+                guid[8..].iter().try_for_each(|byte| write!(f, "{byte:02X}"))?;
+                write!(f, "{age:X}")
+            }
+            SymbolId::BuildId(bytes) => bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}")),
+        }
+    }
+}
+
 /// Policy for how the debugger will handle logging on the system.
 pub enum DebuggerLoggingPolicy {
     /// The debugger will suspend logging while broken in, but will not change the
@@ -183,10 +319,26 @@ pub enum DebuggerLoggingPolicy {
     DisableLogging,
     /// The debugger will not suspend logging while broken in and will allow log
     /// messages from the debugger itself. This should only be used if the debugger
-    /// and logging transports are separate.
+    /// and logging transports are separate, or if logging is written through a
+    /// [`DebuggerConsole`].
     FullLogging,
 }
 
+/// Policy for how the debugger handles CPU exceptions for which no other handler
+/// is registered, such as crashes in code not under the debugger's control.
+#[derive(Debug, Clone, Copy)]
+pub enum UnhandledExceptionPolicy {
+    /// The debugger does not handle unhandled exceptions. The exception context and
+    /// stack trace are dumped and the system panics.
+    Panic,
+    /// All unhandled exceptions break into the debugger, with the exception context
+    /// exposed as the current register state.
+    BreakIn,
+    /// Unhandled exceptions of the listed types break into the debugger, while those
+    /// of other types panic.
+    BreakInOn(&'static [usize]),
+}
+
 /// Sets the global instance of the debugger.
 pub fn set_debugger<T: SerialIO>(debugger: &'static PatinaDebugger<T>) {
     DEBUGGER.call_once(|| debugger);
@@ -228,8 +380,73 @@ pub fn breakpoint_unchecked() {
 /// Notifies the debugger of a module load at the provided address and length.
 /// This should be invoked before the module has begun execution.
 pub fn notify_module_load(module_name: &str, address: usize, length: usize) {
+    notify_module_load_with_debug_info(module_name, address, length, None);
+}
+
+/// Notifies the debugger of a module load, as [notify_module_load]. If provided, the
+/// debug information is reported to the client in the library list so it can load
+/// the module's symbols without manual `add-symbol-file` commands.
+pub fn notify_module_load_with_debug_info(
+    module_name: &str,
+    address: usize,
+    length: usize,
+    debug_info: Option<ModuleDebugInfo<'_>>,
+) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.notify_module_load(module_name, address, length, debug_info);
+    }
+}
+
+/// Notifies the debugger that the module loaded at the provided address has been
+/// unloaded.
+pub fn notify_module_unload(address: usize) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.notify_module_unload(address);
+    }
+}
+
+/// Notifies the debugger that a module is about to start executing at the provided
+/// entry point. This should be invoked immediately before the entry point is called.
+/// If an entry breakpoint is armed for the module name or file GUID, this will break
+/// into the debugger.
+pub fn notify_module_start(module_name: &str, file_guid: Option<&BinaryGuid>, entry_point: usize) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.notify_module_start(module_name, file_guid, entry_point);
+    }
+}
+
+/// Arms a breakpoint on the entry point of a module, identified by its file name
+/// (e.g. `MyDriver.efi`) or file GUID (e.g. `8F644FA9-E850-4DB1-9CE2-0B44698E8DA4`).
+/// The debugger will break immediately before the module's entry point is called,
+/// which allows debugging driver initialization without patching the driver. This
+/// should be called before the module is dispatched, and requires the `alloc`
+/// feature.
+pub fn add_entry_breakpoint(module: &str) {
     if let Some(debugger) = DEBUGGER.get() {
-        debugger.notify_module_load(module_name, address, length);
+        debugger.add_entry_breakpoint(module);
+    }
+}
+
+/// Notifies the debugger of a panic. This should be invoked from the panic handler.
+/// If the debugger is enabled, the panic message and the top frames of the stack
+/// are recorded and the debugger breaks in, reporting the panic to the client as
+/// an abort rather than a trap. The report remains available through the `panic`
+/// monitor command. If the debugger is not enabled, this routine has no effect.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// #[panic_handler]
+/// fn panic(info: &core::panic::PanicInfo) -> ! {
+///     log::error!("{}", info);
+///     patina_debugger::notify_panic(info);
+///     loop {}
+/// }
+/// ```
+///
+pub fn notify_panic(info: &PanicInfo<'_>) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.notify_panic(info);
     }
 }
 
@@ -241,7 +458,53 @@ pub fn poll_debugger() {
     }
 }
 
-/// Checks if the debugger is enabled.
+/// Polls the debugger for any pending interrupts from a periodic source, such as
+/// the system timer tick, where `now` is the current system time. The transport is
+/// only checked if the debugger's poll interval has elapsed since the last periodic
+/// poll, see [`PatinaDebugger::with_poll_interval`]. The routine may cause a debug
+/// break.
+pub fn poll_debugger_periodic(now: Duration) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.poll_debugger_periodic(now);
+    }
+}
+
+/// Writes console output to the debugger transport, see [`DebuggerConsole`].
+fn write_console(buffer: &[u8]) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.write_console(buffer);
+    }
+}
+
+/// Makes a File-I/O call to the debugger client, see [`HostFile`].
+fn host_io(request: &host_io::Request, buffer: host_io::CallBuffer<'_>) -> Result<u64, EfiError> {
+    match DEBUGGER.get() {
+        Some(debugger) => debugger.host_io(request, buffer),
+        None => Err(EfiError::NotReady),
+    }
+}
+
+/// Notifies the debugger that ReadyToBoot has been reached. A debugger installed
+/// for late attach starts listening for its attach sequence from this point, see
+/// [`PatinaDebugger::with_late_attach`].
+pub fn notify_ready_to_boot() {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.notify_ready_to_boot();
+    }
+}
+
+/// Activates a debugger installed for late attach, initializing its transport, and
+/// breaks in. This is intended for platform triggers such as a boot menu entry or a
+/// hotkey. If the debugger is already attached, this only breaks in. If the debugger
+/// is not enabled, this routine has no effect.
+pub fn attach() {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.attach();
+    }
+}
+
+/// Checks if the debugger is enabled. A debugger installed for late attach is not
+/// considered enabled until it has been attached.
 pub fn enabled() -> bool {
     match DEBUGGER.get() {
         Some(debugger) => debugger.enabled(),
@@ -268,6 +531,17 @@ pub fn add_monitor_command(cmd: &'static str, description: &'static str, functio
     }
 }
 
+/// Sets the function used to describe the memory map to the debugger client. The
+/// memory map lets the client know which regions may be accessed, and prevents it
+/// from writing software breakpoints into MMIO or flash-mapped regions. Without a
+/// provider, no memory map is reported. See [MemoryMapFn] for more details on the
+/// function expectations.
+pub fn set_memory_map_provider(provider: MemoryMapFn) {
+    if let Some(debugger) = DEBUGGER.get() {
+        debugger.set_memory_map_provider(provider);
+    }
+}
+
 /// Exception information for the debugger.
 #[allow(dead_code)]
 struct ExceptionInfo {
@@ -287,10 +561,13 @@ enum ExceptionType {
     Step,
     /// A break due to a breakpoint instruction.
     Breakpoint,
-    /// A break due to an invalid memory access. The accessed address is provided.
-    AccessViolation(usize),
+    /// A break due to an invalid memory access. The accessed address and the architecture specific cause are
+    /// provided.
+    AccessViolation(usize, FaultCause),
     /// A general protection fault. Exception data is provided.
     GeneralProtectionFault(u64),
+    /// A break due to a data watchpoint. The accessed address and the kind of the triggering watchpoint are provided.
+    Watchpoint(u64, gdbstub::target::ext::breakpoints::WatchKind),
     /// A break due to an exception type not handled by the debugger. The exception type is provided.
     Other(u64),
 }
@@ -300,15 +577,85 @@ impl core::fmt::Display for ExceptionType {
         match self {
             ExceptionType::Step => write!(f, "Debug Step"),
             ExceptionType::Breakpoint => write!(f, "Breakpoint"),
-            ExceptionType::AccessViolation(addr) => write!(f, "Access Violation at {addr:#X}"),
+            ExceptionType::AccessViolation(addr, cause) => write!(f, "Access Violation at {addr:#X}. {cause}"),
             ExceptionType::GeneralProtectionFault(data) => {
                 write!(f, "General Protection Fault. Exception data: {data:#X}")
             }
+            ExceptionType::Watchpoint(addr, kind) => write!(f, "Watchpoint ({kind:?}) at {addr:#X}"),
             ExceptionType::Other(exception_type) => write!(f, "Unknown. Architecture code: {exception_type:#X}"),
         }
     }
 }
 
+/// Architecture specific cause of an access violation, as reported by the processor.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[allow(dead_code)]
+enum FaultCause {
+    /// An x64 page fault. The error code pushed by the processor is provided.
+    PageFault(u64),
+    /// An AArch64 data abort. The exception syndrome register is provided.
+    DataAbort(u64),
+    /// An AArch64 instruction abort. The exception syndrome register is provided.
+    InstructionAbort(u64),
+}
+
+impl FaultCause {
+    /// Describes an AArch64 data or instruction fault status code.
+    fn abort_status(status: u64) -> (&'static str, Option<u64>) {
+        match status {
+            0x00..=0x03 => ("address size fault", Some(status & 0x3)),
+            0x04..=0x07 => ("translation fault", Some(status & 0x3)),
+            0x08..=0x0B => ("access flag fault", Some(status & 0x3)),
+            0x0C..=0x0F => ("permission fault", Some(status & 0x3)),
+            0x10 => ("synchronous external abort", None),
+            0x21 => ("alignment fault", None),
+            0x30 => ("TLB conflict abort", None),
+            _ => ("unknown fault", None),
+        }
+    }
+}
+
+impl core::fmt::Display for FaultCause {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            FaultCause::PageFault(error_code) => {
+                let access = match error_code {
+                    code if code & 0x10 != 0 => "instruction fetch",
+                    code if code & 0x2 != 0 => "write",
+                    _ => "read",
+                };
+                let problem = if error_code & 0x1 != 0 { "protection violation" } else { "page not present" };
+                write!(f, "Page Fault (error code {error_code:#X}): {access}, {problem}")?;
+                if error_code & 0x4 != 0 {
+                    write!(f, ", user mode")?;
+                }
+                if error_code & 0x8 != 0 {
+                    write!(f, ", reserved bit set")?;
+                }
+                Ok(())
+            }
+            FaultCause::DataAbort(esr) | FaultCause::InstructionAbort(esr) => {
+                let (status, level) = Self::abort_status(esr & 0x3F);
+                match self {
+                    FaultCause::DataAbort(_) => {
+                        let access = if esr & (1 << 6) != 0 { "write" } else { "read" };
+                        write!(f, "Data Abort (ESR {esr:#X}): {access}, {status}")?;
+                    }
+                    _ => write!(f, "Instruction Abort (ESR {esr:#X}): {status}")?,
+                }
+                if let Some(level) = level {
+                    write!(f, " at level {level}")?;
+                }
+                // FnV: the fault address register does not hold the faulting address.
+                if esr & (1 << 10) != 0 {
+                    write!(f, ", fault address not valid")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 #[coverage(off)]
 #[cfg(test)]
 mod tests {
@@ -345,4 +692,32 @@ mod tests {
         // Ensure that invoking a debug break when the debugger is enabled causes a panic.
         breakpoint();
     }
+
+    #[test]
+    fn test_fault_cause_description() {
+        assert_eq!(
+            ExceptionType::AccessViolation(0x1000, FaultCause::PageFault(0x2)).to_string(),
+            "Access Violation at 0x1000. Page Fault (error code 0x2): write, page not present"
+        );
+        assert_eq!(
+            FaultCause::PageFault(0x15).to_string(),
+            "Page Fault (error code 0x15): instruction fetch, protection violation, user mode"
+        );
+        assert_eq!(
+            FaultCause::PageFault(0x9).to_string(),
+            "Page Fault (error code 0x9): read, protection violation, reserved bit set"
+        );
+        assert_eq!(
+            FaultCause::DataAbort(0x9600_0045).to_string(),
+            "Data Abort (ESR 0x96000045): write, translation fault at level 1"
+        );
+        assert_eq!(
+            FaultCause::DataAbort(0x9600_0410).to_string(),
+            "Data Abort (ESR 0x96000410): read, synchronous external abort, fault address not valid"
+        );
+        assert_eq!(
+            FaultCause::InstructionAbort(0x8600_000F).to_string(),
+            "Instruction Abort (ESR 0x8600000F): permission fault at level 3"
+        );
+    }
 }
@@ -98,6 +98,64 @@ pub fn write_memory<Arch: DebuggerArch>(address: u64, buffer: &[u8]) -> Result<(
     Ok(())
 }
 
+/// Maximum length of a pattern for [`search_memory`].
+pub const MAX_SEARCH_PATTERN_SIZE: usize = 64;
+
+/// Searches `length` bytes of memory starting at `address` for `pattern`, calling `on_match` with the address of
+/// each match until it returns `false`.
+///
+/// Memory is read a page at a time through [`read_memory`], so each page is validated in the page tables before it
+/// is accessed. Pages that cannot be read are skipped, and the number of bytes skipped is returned. Matches that span
+/// a skipped page are not reported.
+///
+pub fn search_memory<Arch: DebuggerArch>(
+    address: u64,
+    length: u64,
+    pattern: &[u8],
+    unsafe_read: bool,
+    mut on_match: impl FnMut(u64) -> bool,
+) -> Result<u64, ()> {
+    if pattern.is_empty() || pattern.len() > MAX_SEARCH_PATTERN_SIZE {
+        return Err(());
+    }
+
+    // The buffer holds a page, preceded by the end of the previous page so matches that cross pages are found.
+    let mut buffer = [0u8; PAGE_SIZE as usize + MAX_SEARCH_PATTERN_SIZE];
+    let mut carry = 0;
+    let mut skipped = 0;
+    let end_address = address.saturating_add(length);
+    let mut current = address;
+    while current < end_address {
+        let end = (current & PAGE_MASK).saturating_add(PAGE_SIZE).min(end_address);
+        let len = (end - current) as usize;
+
+        match read_memory::<Arch>(current, &mut buffer[carry..carry + len], unsafe_read) {
+            Ok(read) => {
+                let window_len = carry + read;
+                let window_address = current - carry as u64;
+                for (offset, candidate) in buffer[..window_len].windows(pattern.len()).enumerate() {
+                    if candidate == pattern && !on_match(window_address + offset as u64) {
+                        return Ok(skipped);
+                    }
+                }
+
+                let next_carry = window_len.min(pattern.len() - 1);
+                buffer.copy_within(window_len - next_carry..window_len, 0);
+                carry = next_carry;
+                skipped += (len - read) as u64;
+            }
+            Err(_) => {
+                skipped += len as u64;
+                carry = 0;
+            }
+        }
+
+        current = end;
+    }
+
+    Ok(skipped)
+}
+
 /// Checks if the range is valid for access. This will check the page tables and
 /// attempt to read the memory at the address to ensure that it is accessible.
 fn check_range_access<Arch: DebuggerArch>(
@@ -182,7 +240,7 @@ mod tests {
             const DEFAULT_EXCEPTION_TYPES: &'static [usize] = &[];
             const BREAKPOINT_INSTRUCTION: &'static [u8] = &[];
             const GDB_TARGET_XML: &'static str = "";
-            const GDB_REGISTERS_XML: &'static str = "";
+            const GDB_FEATURES_XML: &'static [(&'static str, &'static str)] = &[];
             type PageTable = MockMemPageTable;
 
             fn breakpoint();
@@ -307,4 +365,93 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(buffer, data);
     }
+
+    fn search(address: u64, length: u64, pattern: &[u8], bad_page: Option<u64>) -> (Vec<u64>, u64) {
+        let _lock = PAGE_LOCK.lock().unwrap();
+        let poke_ctx = MockMemDebuggerArch::memory_poke_test_context();
+        poke_ctx.expect().returning(|_| Ok(()));
+        let ctx = MockMemDebuggerArch::get_page_table_context();
+        ctx.expect().returning(move || {
+            let mut mock_page_table = MockMemPageTable::new();
+            mock_page_table.expect_query_memory_region().returning(move |address, _| match bad_page {
+                Some(page) if page == address => Err(patina_paging::PtError::InvalidMemoryRange),
+                _ => Ok(MemoryAttributes::empty()),
+            });
+            Ok(mock_page_table)
+        });
+
+        let mut matches = Vec::new();
+        let skipped = search_memory::<MockMemDebuggerArch>(address, length, pattern, false, |address| {
+            matches.push(address);
+            true
+        })
+        .expect("Failed to search memory.");
+        (matches, skipped)
+    }
+
+    /// Returns a page-aligned region of three pages.
+    fn pages() -> (Vec<u8>, u64) {
+        let data = vec![0u8; 4 * PAGE_SIZE as usize];
+        let base = (data.as_ptr() as u64 + PAGE_SIZE - 1) & PAGE_MASK;
+        (data, base)
+    }
+
+    #[test]
+    fn test_search_memory_finds_matches_across_pages() {
+        let (mut data, base) = pages();
+        let offset = (base - data.as_ptr() as u64) as usize;
+        data[offset + 0x10..offset + 0x14].copy_from_slice(b"_FVH");
+        // Straddle the boundary between the first and second page.
+        data[offset + 0xffe..offset + 0x1002].copy_from_slice(b"_FVH");
+        data[offset + 0x2ffc..offset + 0x3000].copy_from_slice(b"_FVH");
+
+        let (matches, skipped) = search(base, 3 * PAGE_SIZE, b"_FVH", None);
+        assert_eq!(matches, [base + 0x10, base + 0xffe, base + 0x2ffc]);
+        assert_eq!(skipped, 0);
+
+        // The range bounds the search, including for matches that start inside it.
+        let (matches, _) = search(base + 0x11, 0x2fee, b"_FVH", None);
+        assert_eq!(matches, [base + 0xffe]);
+    }
+
+    #[test]
+    fn test_search_memory_skips_unreadable_pages() {
+        let (mut data, base) = pages();
+        let offset = (base - data.as_ptr() as u64) as usize;
+        data[offset + 0x1100..offset + 0x1102].copy_from_slice(&[0x4d, 0x5a]);
+        data[offset + 0x2100..offset + 0x2102].copy_from_slice(&[0x4d, 0x5a]);
+
+        let (matches, skipped) = search(base, 3 * PAGE_SIZE, &[0x4d, 0x5a], Some(base + PAGE_SIZE));
+        assert_eq!(matches, [base + 0x2100]);
+        assert_eq!(skipped, PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_search_memory_stops_when_requested() {
+        let (mut data, base) = pages();
+        let offset = (base - data.as_ptr() as u64) as usize;
+        data[offset..offset + 0x100].fill(0xa5);
+
+        let _lock = PAGE_LOCK.lock().unwrap();
+        let poke_ctx = MockMemDebuggerArch::memory_poke_test_context();
+        poke_ctx.expect().returning(|_| Ok(()));
+        let ctx = MockMemDebuggerArch::get_page_table_context();
+        ctx.expect().returning(|| {
+            let mut mock_page_table = MockMemPageTable::new();
+            mock_page_table.expect_query_memory_region().returning(|_, _| Ok(MemoryAttributes::empty()));
+            Ok(mock_page_table)
+        });
+
+        let mut count = 0;
+        search_memory::<MockMemDebuggerArch>(base, PAGE_SIZE, &[0xa5], false, |_| {
+            count += 1;
+            count < 3
+        })
+        .expect("Failed to search memory.");
+        assert_eq!(count, 3);
+
+        assert!(search_memory::<MockMemDebuggerArch>(base, PAGE_SIZE, &[], false, |_| true).is_err());
+        let long_pattern = [0u8; MAX_SEARCH_PATTERN_SIZE + 1];
+        assert!(search_memory::<MockMemDebuggerArch>(base, PAGE_SIZE, &long_pattern, false, |_| true).is_err());
+    }
 }
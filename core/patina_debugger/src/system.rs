@@ -8,21 +8,40 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
-use alloc::{string::String, vec::Vec};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
 
-use crate::MonitorCommandFn;
+use patina::{BinaryGuid, collections::ArrayString};
+
+use crate::{MemoryMapFn, MemoryRegion, MemoryRegionKind, ModuleDebugInfo, MonitorCommandFn, SymbolId};
+
+/// Size of the buffer holding the report of the last panic.
+pub(crate) const PANIC_REPORT_SIZE: usize = 1024;
 
 pub(crate) struct SystemState {
     /// Tracks modules state.
     pub modules: Modules,
     /// Tracks external monitor commands.
     pub monitor_commands: Vec<MonitorCallback>,
+    /// Report of the last panic.
+    pub panic: PanicReport,
+    /// Provides the memory map reported to the client.
+    pub memory_map: Option<MemoryMapFn>,
 }
 
 impl SystemState {
     /// Create a new system state.
     pub const fn new() -> Self {
-        SystemState { modules: Modules::new(), monitor_commands: Vec::new() }
+        SystemState {
+            modules: Modules::new(),
+            monitor_commands: Vec::new(),
+            panic: PanicReport::new(),
+            memory_map: None,
+        }
     }
 
     pub fn add_monitor_command(
@@ -66,22 +85,63 @@ pub(crate) struct ModuleInfo {
     pub name: String,
     pub base: usize,
     pub size: usize,
+    /// Path of the symbol file for the module, if known.
+    pub symbol_path: Option<String>,
+    /// Offset of the first section from the module base.
+    pub first_section_offset: usize,
+    /// Symbol server key of the symbol file, if known. See [`SymbolId`].
+    pub symbol_id: Option<String>,
+    /// Path of the symbol file under a symbol server root, if known.
+    pub symbol_server_path: Option<String>,
 }
 
 /// Manages loaded modules and module breakpoints.
 pub(crate) struct Modules {
     modules: Vec<ModuleInfo>,
     module_breakpoints: Vec<String>,
+    entry_breakpoints: Vec<String>,
     break_all: bool,
 }
 
 impl Modules {
     pub const fn new() -> Self {
-        Modules { modules: Vec::new(), module_breakpoints: Vec::new(), break_all: false }
+        Modules { modules: Vec::new(), module_breakpoints: Vec::new(), entry_breakpoints: Vec::new(), break_all: false }
+    }
+
+    pub fn add_module(&mut self, name: &str, base: usize, size: usize, debug_info: Option<&ModuleDebugInfo<'_>>) {
+        self.modules.push(ModuleInfo {
+            name: String::from(name),
+            base,
+            size,
+            symbol_path: debug_info.map(|info| String::from(info.symbol_path)),
+            first_section_offset: debug_info.map_or(0, |info| info.first_section_offset),
+            symbol_id: debug_info.and_then(|info| info.symbol_id).map(|id| id.to_string()),
+            symbol_server_path: debug_info
+                .and_then(|info| Some(symbol_server_path(info.symbol_path, &info.symbol_id?))),
+        });
+    }
+
+    /// Removes the module loaded at `base`. Returns `true` if the module was found.
+    pub fn remove_module(&mut self, base: usize) -> bool {
+        let count = self.modules.len();
+        self.modules.retain(|module| module.base != base);
+        self.modules.len() != count
     }
 
-    pub fn add_module(&mut self, name: &str, base: usize, size: usize) {
-        self.modules.push(ModuleInfo { name: String::from(name), base, size });
+    /// Writes the GDB library list XML describing the loaded modules. Each library
+    /// is located by the address of its first section, as GDB expects for PE images.
+    pub fn write_library_list(&self, out: &mut dyn Write) -> fmt::Result {
+        out.write_str("<library-list version=\"1.0\">")?;
+        for module in &self.modules {
+            out.write_str("<library name=\"")?;
+            write_xml_escaped(out, module.symbol_path.as_deref().unwrap_or(&module.name))?;
+            // GDB ignores attributes it does not know, so the symbol identity rides along for other host tooling.
+            if let Some(symbol_id) = &module.symbol_id {
+                write!(out, "\" build-id=\"{symbol_id}")?;
+            }
+            write!(out, "\"><segment address=\"{:#x}\"/></library>", module.base + module.first_section_offset)?;
+        }
+        out.write_str("</library-list>")
     }
 
     pub fn check_module_breakpoints(&self, name: &str) -> bool {
@@ -107,12 +167,32 @@ impl Modules {
         }
     }
 
+    /// Arms a breakpoint on the entry point of the module with the given name or file GUID.
+    #[cfg(feature = "alloc")]
+    pub fn add_entry_breakpoint(&mut self, module: &str) {
+        let trimmed = module.trim().trim_end_matches(".efi");
+        if !trimmed.is_empty() {
+            self.entry_breakpoints.push(String::from(trimmed));
+        }
+    }
+
+    /// Returns `true` if an entry breakpoint is armed for the module with the given
+    /// name or file GUID.
+    pub fn check_entry_breakpoints(&self, name: &str, file_guid: Option<&BinaryGuid>) -> bool {
+        let trimmed = name.trim_end_matches(".efi");
+        self.entry_breakpoints.iter().any(|module| {
+            module.eq_ignore_ascii_case(trimmed)
+                || matches!((BinaryGuid::try_from_string(module), file_guid), (Ok(guid), Some(file_guid)) if guid == *file_guid)
+        })
+    }
+
     pub fn break_on_all(&mut self) {
         self.break_all = true;
     }
 
     pub fn clear_module_breakpoints(&mut self) {
         self.module_breakpoints.clear();
+        self.entry_breakpoints.clear();
         self.break_all = false;
     }
 
@@ -124,6 +204,130 @@ impl Modules {
     pub fn get_module_breakpoints(&self) -> &Vec<String> {
         &self.module_breakpoints
     }
+
+    #[cfg(feature = "alloc")]
+    pub fn get_entry_breakpoints(&self) -> &Vec<String> {
+        &self.entry_breakpoints
+    }
+}
+
+/// Writes the GDB memory map XML for the regions reported by `provider`. Adjacent
+/// regions of the same kind are merged.
+pub(crate) fn write_memory_map(provider: MemoryMapFn, out: &mut dyn Write) -> fmt::Result {
+    fn write_region(out: &mut dyn Write, region: &MemoryRegion) -> fmt::Result {
+        let kind = match region.kind {
+            crate::MemoryRegionKind::Ram | crate::MemoryRegionKind::Mmio => "ram",
+            crate::MemoryRegionKind::Rom => "rom",
+        };
+        write!(out, "<memory type=\"{kind}\" start=\"{:#x}\" length=\"{:#x}\"/>", region.base, region.length)
+    }
+
+    out.write_str(concat!(
+        "<?xml version=\"1.0\"?>",
+        "<!DOCTYPE memory-map PUBLIC \"+//IDN gnu.org//DTD GDB Memory Map V1.0//EN\" ",
+        "\"http://sourceware.org/gdb/gdb-memory-map.dtd\">",
+        "<memory-map>"
+    ))?;
+
+    let mut result = Ok(());
+    let mut pending: Option<MemoryRegion> = None;
+    provider(&mut |region| {
+        if region.length == 0 || result.is_err() {
+            return;
+        }
+
+        match pending.as_mut() {
+            Some(current) if current.kind == region.kind && current.base + current.length == region.base => {
+                current.length += region.length;
+            }
+            _ => {
+                if let Some(current) = pending.replace(region) {
+                    result = write_region(out, &current);
+                }
+            }
+        }
+    });
+    result?;
+
+    if let Some(current) = pending {
+        write_region(out, &current)?;
+    }
+    out.write_str("</memory-map>")
+}
+
+/// Returns the kind of the region reported by `provider` that contains `addr`, or
+/// `None` if no region does.
+pub(crate) fn memory_region_kind(provider: MemoryMapFn, addr: u64) -> Option<MemoryRegionKind> {
+    let mut kind = None;
+    provider(&mut |region| {
+        if kind.is_none() && addr >= region.base && addr - region.base < region.length {
+            kind = Some(region.kind);
+        }
+    });
+    kind
+}
+
+/// Writes `text` with the XML special characters escaped.
+/// Returns the path of a symbol file under a symbol server root: `<file>/<id>/<file>`
+/// for a PDB, as in a symbol store, and `.build-id/<xx>/<rest>.debug` for a build-id,
+/// as in a GDB debug file directory.
+fn symbol_server_path(symbol_path: &str, symbol_id: &SymbolId<'_>) -> String {
+    let id = symbol_id.to_string();
+    match symbol_id {
+        SymbolId::Pdb { .. } => {
+            let file = symbol_path.rsplit(['/', '\\']).next().unwrap_or(symbol_path);
+            format!("{file}/{id}/{file}")
+        }
+        SymbolId::BuildId(_) => {
+            let (directory, rest) = id.split_at(id.len().min(2));
+            format!(".build-id/{directory}/{rest}.debug")
+        }
+    }
+}
+
+fn write_xml_escaped(out: &mut dyn Write, text: &str) -> fmt::Result {
+    for c in text.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '"' => out.write_str("&quot;")?,
+            '\'' => out.write_str("&apos;")?,
+            _ => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Report of the last panic, recorded without allocating so that it can be taken
+/// from a panic handler.
+pub(crate) struct PanicReport {
+    text: ArrayString<PANIC_REPORT_SIZE>,
+    /// Whether the report has not yet been sent to the client.
+    pending: bool,
+}
+
+impl PanicReport {
+    pub const fn new() -> Self {
+        PanicReport { text: ArrayString::new(), pending: false }
+    }
+
+    /// Replaces the report with the output of `write`, truncated to fit the buffer.
+    pub fn record(&mut self, write: impl FnOnce(&mut dyn Write) -> fmt::Result) {
+        self.text.clear();
+        self.pending = true;
+        let _ = write(&mut self.text);
+    }
+
+    /// Returns the report of the last panic, if any.
+    pub fn text(&self) -> Option<&str> {
+        (!self.text.is_empty()).then_some(self.text.as_str())
+    }
+
+    /// Returns `true` if a panic was recorded since the last call.
+    pub fn take_pending(&mut self) -> bool {
+        core::mem::take(&mut self.pending)
+    }
 }
 
 /// Stores the command and its associated callback function for monitor commands.
@@ -146,13 +350,76 @@ mod tests {
     #[test]
     fn test_add_module() {
         let mut modules = Modules::new();
-        modules.add_module("test_module", 0x1000, 0x2000);
+        modules.add_module("test_module", 0x1000, 0x2000, None);
         assert_eq!(modules.get_modules().len(), 1);
         assert_eq!(modules.get_modules()[0].name, "test_module");
         assert_eq!(modules.get_modules()[0].base, 0x1000);
         assert_eq!(modules.get_modules()[0].size, 0x2000);
     }
 
+    #[test]
+    fn test_remove_module() {
+        let mut modules = Modules::new();
+        modules.add_module("first", 0x1000, 0x1000, None);
+        modules.add_module("second", 0x2000, 0x1000, None);
+
+        assert!(!modules.remove_module(0x3000));
+        assert!(modules.remove_module(0x1000));
+        assert_eq!(modules.get_modules().len(), 1);
+        assert_eq!(modules.get_modules()[0].name, "second");
+    }
+
+    #[test]
+    fn test_write_library_list() {
+        let mut modules = Modules::new();
+        let debug_info =
+            ModuleDebugInfo { symbol_path: "c:\\build\\A&B.pdb", first_section_offset: 0x1000, symbol_id: None };
+        modules.add_module("a.efi", 0x10000, 0x4000, Some(&debug_info));
+        modules.add_module("b.efi", 0x20000, 0x4000, None);
+
+        let mut xml = String::new();
+        modules.write_library_list(&mut xml).unwrap();
+        assert_eq!(
+            xml,
+            "<library-list version=\"1.0\">\
+             <library name=\"c:\\build\\A&amp;B.pdb\"><segment address=\"0x11000\"/></library>\
+             <library name=\"b.efi\"><segment address=\"0x20000\"/></library>\
+             </library-list>"
+        );
+    }
+
+    #[test]
+    fn test_symbol_id() {
+        let guid = [0x78, 0x56, 0x34, 0x12, 0xBC, 0x9A, 0xF0, 0xDE, 1, 2, 3, 4, 5, 6, 7, 8];
+        let debug_info = ModuleDebugInfo {
+            symbol_path: "C:\\build\\DxeCore.pdb",
+            first_section_offset: 0x1000,
+            symbol_id: Some(SymbolId::Pdb { guid, age: 0x1A }),
+        };
+        let mut modules = Modules::new();
+        modules.add_module("DxeCore.efi", 0x10000, 0x4000, Some(&debug_info));
+
+        let module = &modules.get_modules()[0];
+        assert_eq!(module.symbol_id.as_deref(), Some("123456789ABCDEF001020304050607081A"));
+        assert_eq!(
+            module.symbol_server_path.as_deref(),
+            Some("DxeCore.pdb/123456789ABCDEF001020304050607081A/DxeCore.pdb")
+        );
+
+        let mut xml = String::new();
+        modules.write_library_list(&mut xml).unwrap();
+        assert_eq!(
+            xml,
+            "<library-list version=\"1.0\">\
+             <library name=\"C:\\build\\DxeCore.pdb\" build-id=\"123456789ABCDEF001020304050607081A\">\
+             <segment address=\"0x11000\"/></library>\
+             </library-list>"
+        );
+
+        let build_id = SymbolId::BuildId(&[0xAB, 0xCD, 0xEF, 0x01]);
+        assert_eq!(symbol_server_path("/build/module.debug", &build_id), ".build-id/ab/cdef01.debug");
+    }
+
     #[test]
     fn test_check_module_breakpoints() {
         let mut modules = Modules::new();
@@ -161,6 +428,28 @@ mod tests {
         assert!(!modules.check_module_breakpoints("other_module"));
     }
 
+    #[test]
+    fn test_check_entry_breakpoints() {
+        let guid =
+            BinaryGuid::from_fields(0x8F644FA9, 0xE850, 0x4DB1, 0x9C, 0xE2, &[0x0B, 0x44, 0x69, 0x8E, 0x8D, 0xA4]);
+        let other_guid = BinaryGuid::from_fields(0, 0, 0, 0, 0, &[0; 6]);
+
+        let mut modules = Modules::new();
+        modules.add_entry_breakpoint("NameDxe.efi");
+        modules.add_entry_breakpoint("8f644fa9-e850-4db1-9ce2-0b44698e8da4");
+
+        assert!(modules.check_entry_breakpoints("namedxe.efi", None));
+        assert!(modules.check_entry_breakpoints("GuidDxe.efi", Some(&guid)));
+        assert!(!modules.check_entry_breakpoints("GuidDxe.efi", Some(&other_guid)));
+        assert!(!modules.check_entry_breakpoints("GuidDxe.efi", None));
+
+        // Load breakpoints and entry breakpoints are independent.
+        assert!(!modules.check_module_breakpoints("NameDxe.efi"));
+
+        modules.clear_module_breakpoints();
+        assert!(!modules.check_entry_breakpoints("NameDxe.efi", None));
+    }
+
     #[test]
     fn test_break_on_all() {
         let mut modules = Modules::new();
@@ -203,4 +492,80 @@ mod tests {
 
         assert!(!system_state.handle_monitor_command("invalid", args, &mut out));
     }
+
+    #[test]
+    fn test_write_memory_map() {
+        use crate::MemoryRegionKind::{Mmio, Ram, Rom};
+
+        let provider: MemoryMapFn = |visit| {
+            visit(MemoryRegion { base: 0, length: 0x1000, kind: Ram });
+            visit(MemoryRegion { base: 0x1000, length: 0x3000, kind: Ram });
+            visit(MemoryRegion { base: 0x8000, length: 0, kind: Ram });
+            visit(MemoryRegion { base: 0x8000, length: 0x1000, kind: Ram });
+            visit(MemoryRegion { base: 0xFE00_0000, length: 0x1000, kind: Mmio });
+            visit(MemoryRegion { base: 0xFF00_0000, length: 0x100_0000, kind: Rom });
+        };
+
+        let mut xml = String::new();
+        write_memory_map(provider, &mut xml).unwrap();
+        assert!(xml.starts_with("<?xml version=\"1.0\"?><!DOCTYPE memory-map"));
+        assert!(xml.ends_with(
+            "<memory-map>\
+             <memory type=\"ram\" start=\"0x0\" length=\"0x4000\"/>\
+             <memory type=\"ram\" start=\"0x8000\" length=\"0x1000\"/>\
+             <memory type=\"ram\" start=\"0xfe000000\" length=\"0x1000\"/>\
+             <memory type=\"rom\" start=\"0xff000000\" length=\"0x1000000\"/>\
+             </memory-map>"
+        ));
+
+        let mut xml = String::new();
+        write_memory_map(|_| {}, &mut xml).unwrap();
+        assert!(xml.ends_with("<memory-map></memory-map>"));
+    }
+
+    #[test]
+    fn test_memory_region_kind() {
+        use crate::MemoryRegionKind::{Mmio, Ram, Rom};
+
+        let provider: MemoryMapFn = |visit| {
+            visit(MemoryRegion { base: 0, length: 0x1000, kind: Ram });
+            visit(MemoryRegion { base: 0xFE00_0000, length: 0x1000, kind: Mmio });
+            visit(MemoryRegion { base: 0xFF00_0000, length: 0x100_0000, kind: Rom });
+        };
+
+        assert_eq!(memory_region_kind(provider, 0xFFF), Some(Ram));
+        assert_eq!(memory_region_kind(provider, 0x1000), None);
+        assert_eq!(memory_region_kind(provider, 0xFE00_0FFF), Some(Mmio));
+        assert_eq!(memory_region_kind(provider, 0xFFFF_FFFF), Some(Rom));
+        assert_eq!(memory_region_kind(|_| {}, 0), None);
+    }
+
+    #[test]
+    fn test_panic_report() {
+        let mut report = PanicReport::new();
+        assert_eq!(report.text(), None);
+        assert!(!report.take_pending());
+
+        let line = 10;
+        report.record(|out| write!(out, "panicked at src/lib.rs:{line}"));
+        assert_eq!(report.text(), Some("panicked at src/lib.rs:10"));
+        assert!(report.take_pending());
+        assert!(!report.take_pending());
+
+        // A new panic replaces the previous report.
+        report.record(|out| out.write_str("second"));
+        assert_eq!(report.text(), Some("second"));
+        assert!(report.take_pending());
+    }
+
+    #[test]
+    fn test_panic_report_truncates_at_char_boundary() {
+        let mut report = PanicReport::new();
+        report.record(|out| {
+            out.write_str(&"a".repeat(PANIC_REPORT_SIZE - 1))?;
+            out.write_str("\u{e9}")
+        });
+        assert_eq!(report.text().unwrap().len(), PANIC_REPORT_SIZE - 1);
+        assert!(report.text().unwrap().chars().all(|c| c == 'a'));
+    }
 }
@@ -1,7 +1,9 @@
 //! Debugger Transport Implementations.
 //!
 //! This modules contains the implementation Connection traits for a SerialIO
-//! debugger transport as well as other related implementations.
+//! debugger transport, a TCP transport over a platform network interface, a USB
+//! debug transport when the `usb_debug` feature is enabled, as well as other
+//! related implementations.
 //!
 //! ## License
 //!
@@ -10,9 +12,20 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
-use core::result::Result;
+use core::{
+    fmt::Write,
+    result::Result,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 use gdbstub::conn::{Connection, ConnectionExt};
-use patina::serial::SerialIO;
+use patina::{error::EfiError, serial::SerialIO};
+use spin::Mutex;
+
+#[cfg(feature = "usb_debug")]
+mod usb;
+
+#[cfg(feature = "usb_debug")]
+pub use usb::{EhciDebugPort, UsbDebugConnection, UsbDebugPort, XhciDebugCapability};
 
 /// Serial Connection for use with GdbStub
 ///
@@ -30,6 +43,13 @@ impl<'a, T: SerialIO> SerialConnection<'a, T> {
     pub fn new(transport: &'a T) -> Self {
         SerialConnection { transport, peeked_byte: None }
     }
+
+    /// Returns `byte` from the next read, ahead of the data in the transport. This
+    /// passes on a byte that was already read from the transport.
+    pub fn with_peeked_byte(mut self, byte: Option<u8>) -> Self {
+        self.peeked_byte = byte;
+        self
+    }
 }
 
 impl<T: SerialIO> Connection for SerialConnection<'_, T> {
@@ -76,6 +96,303 @@ impl<T: SerialIO> ConnectionExt for SerialConnection<'_, T> {
     }
 }
 
+/// Network interface used by a [`TcpConnection`].
+///
+/// The platform implements this over its NIC or virtio-net driver and TCP/IP stack.
+/// The debugger accepts a single client connection at a time. None of these routines
+/// may block waiting for the client, and none may log, as they are called while the
+/// system is broken in.
+///
+pub trait NetworkIo: Sync {
+    /// Initializes the network interface and starts listening for a debugger
+    /// connection on the given TCP port.
+    fn listen(&self, port: u16) -> Result<(), EfiError>;
+
+    /// Sends data to the connected client, returning the number of bytes queued.
+    /// Returns `Ok(0)` if the transmit queue is full, and an error if no client is
+    /// connected.
+    fn send(&self, data: &[u8]) -> Result<usize, EfiError>;
+
+    /// Receives data from the connected client into the buffer, returning the
+    /// number of bytes received. Returns `Ok(0)` if no data is available.
+    fn receive(&self, buffer: &mut [u8]) -> Result<usize, EfiError>;
+}
+
+/// Size of the receive buffer of a [`TcpConnection`], which holds one Ethernet frame of data.
+const TCP_RECEIVE_BUFFER_SIZE: usize = 1500;
+
+/// TCP Connection for use with the Patina debugger
+///
+/// Implements SerialIO over a platform [`NetworkIo`] so the debugger can be reached
+/// over the network, e.g. `target remote <address>:<port>` in GDB, on devices without
+/// a free UART. Output written while no client is connected is discarded, as it
+/// would be on an unconnected serial port.
+///
+pub struct TcpConnection<N: NetworkIo> {
+    /// Network interface for connecting to the debugger.
+    network: N,
+    /// TCP port to listen on.
+    port: u16,
+    /// Data received from the network but not yet read by the debugger.
+    stream: ByteStream<TCP_RECEIVE_BUFFER_SIZE>,
+}
+
+/// Byte stream over a packet based transport, such as a TCP socket or a pair of USB
+/// bulk pipes.
+///
+/// Adapts the `send` and `receive` routines of the transport to the byte oriented
+/// SerialIO interface, buffering received packets of up to `N` bytes until the
+/// debugger reads them.
+///
+struct ByteStream<const N: usize> {
+    receive_buffer: Mutex<ReceiveBuffer<N>>,
+}
+
+/// Buffered data received from a packet based transport.
+struct ReceiveBuffer<const N: usize> {
+    data: [u8; N],
+    start: usize,
+    end: usize,
+}
+
+impl<const N: usize> ByteStream<N> {
+    const fn new() -> Self {
+        ByteStream { receive_buffer: Mutex::new(ReceiveBuffer { data: [0; N], start: 0, end: 0 }) }
+    }
+
+    /// Sends the buffer with `send`, which returns the number of bytes it queued.
+    /// The data is dropped if `send` fails, as it does when no peer is connected.
+    fn write(&self, buffer: &[u8], mut send: impl FnMut(&[u8]) -> Result<usize, EfiError>) {
+        let mut remaining = buffer;
+        while !remaining.is_empty() {
+            match send(remaining) {
+                Ok(sent) => remaining = &remaining[sent.min(remaining.len())..],
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Reads a byte, receiving more data with `receive` until one is available.
+    fn read(&self, mut receive: impl FnMut(&mut [u8]) -> Result<usize, EfiError>) -> u8 {
+        loop {
+            if let Some(byte) = self.try_read(&mut receive) {
+                return byte;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Returns the next buffered byte, first refilling the buffer with `receive` if
+    /// it is empty. `receive` returns the number of bytes it placed in the buffer.
+    fn try_read(&self, receive: impl FnOnce(&mut [u8]) -> Result<usize, EfiError>) -> Option<u8> {
+        let mut buffer = self.receive_buffer.lock();
+        if buffer.start == buffer.end {
+            let received = receive(&mut buffer.data).unwrap_or(0);
+            buffer.start = 0;
+            buffer.end = received.min(N);
+        }
+
+        if buffer.start == buffer.end {
+            return None;
+        }
+
+        let byte = buffer.data[buffer.start];
+        buffer.start += 1;
+        Some(byte)
+    }
+}
+
+impl<N: NetworkIo> TcpConnection<N> {
+    /// Create a new TcpConnection that listens on the given port.
+    pub const fn new(network: N, port: u16) -> Self {
+        TcpConnection { network, port, stream: ByteStream::new() }
+    }
+}
+
+impl<N: NetworkIo> SerialIO for TcpConnection<N> {
+    /// Start listening for a debugger connection.
+    fn init(&self) {
+        if let Err(err) = self.network.listen(self.port) {
+            log::error!("Debugger: Failed to listen on TCP port {}: {:?}", self.port, err);
+        }
+    }
+
+    /// Send the buffer to the connected client, if any.
+    fn write(&self, buffer: &[u8]) {
+        self.stream.write(buffer, |data| self.network.send(data));
+    }
+
+    /// Read a byte from the connected client, waiting until one is available.
+    fn read(&self) -> u8 {
+        self.stream.read(|data| self.network.receive(data))
+    }
+
+    /// Read a byte from the connected client, if one is available.
+    fn try_read(&self) -> Option<u8> {
+        self.stream.try_read(|data| self.network.receive(data))
+    }
+}
+
+/// Maximum number of output bytes carried by a single GDB `O` packet.
+const CONSOLE_PACKET_DATA_LEN: usize = 128;
+
+/// Size of an encoded GDB `O` packet: `$O`, two hex digits per byte, `#`, and the checksum.
+const CONSOLE_PACKET_LEN: usize = 2 + CONSOLE_PACKET_DATA_LEN * 2 + 3;
+
+/// Console for sharing the debugger transport with logging
+///
+/// Implements SerialIO on top of the installed debugger's transport so that a
+/// logger can share a single UART with the debugger. Before a debugger client has
+/// connected, output is written to the transport unmodified. Once a client is
+/// connected, output is wrapped in GDB console output (`O`) packets, which the
+/// client displays while the target is running. Output while broken in is dropped.
+/// Reads always return no data, as all input belongs to the debugger.
+///
+#[derive(Debug, Default)]
+pub struct DebuggerConsole;
+
+impl SerialIO for DebuggerConsole {
+    fn init(&self) {}
+
+    fn write(&self, buffer: &[u8]) {
+        crate::write_console(buffer);
+    }
+
+    fn read(&self) -> u8 {
+        // PANIC: Would loop forever, as the debugger owns all input.
+        panic!("DebuggerConsole does not support reads.");
+    }
+
+    fn try_read(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// Writes `data` to the transport as GDB console output packets.
+pub(crate) fn write_output_packets<T: SerialIO>(transport: &T, data: &[u8]) {
+    for chunk in data.chunks(CONSOLE_PACKET_DATA_LEN) {
+        let mut packet = [0u8; CONSOLE_PACKET_LEN];
+        let len = encode_output_packet(chunk, &mut packet);
+        transport.write(&packet[..len]);
+    }
+}
+
+/// Writes a console output packet telling the client how many bytes of console output were dropped.
+pub(crate) fn write_dropped_notice<T: SerialIO>(transport: &T, dropped: usize) {
+    let mut notice = NoticeBuffer { buffer: [0; 64], len: 0 };
+    let _ = writeln!(notice, "[{dropped} bytes of console output dropped]");
+    write_output_packets(transport, &notice.buffer[..notice.len]);
+}
+
+/// Fixed size buffer for formatting short notices without allocating.
+struct NoticeBuffer {
+    buffer: [u8; 64],
+    len: usize,
+}
+
+impl Write for NoticeBuffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        self.buffer.get_mut(self.len..end).ok_or(core::fmt::Error)?.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Limits the rate of console output forwarded to a debugger client.
+///
+/// Forwarded output draws from a budget of bytes, which is refilled at the configured rate as time passes, up to one
+/// second of output. Output beyond the budget is dropped and counted, so the client can be told how much was lost once
+/// output resumes. A rate of zero does not limit output.
+pub(crate) struct ConsoleRateLimit {
+    /// Bytes of output allowed per second, or zero for no limit.
+    bytes_per_second: usize,
+    /// Bytes of output that may be forwarded before the next refill.
+    budget: AtomicUsize,
+    /// System time of the last refill, in nanoseconds.
+    last_refill: AtomicU64,
+    /// Bytes of output dropped since the client was last told.
+    dropped: AtomicUsize,
+}
+
+impl ConsoleRateLimit {
+    /// Creates a limit of `bytes_per_second`, starting with a full budget.
+    pub(crate) const fn new(bytes_per_second: usize) -> Self {
+        ConsoleRateLimit {
+            bytes_per_second,
+            budget: AtomicUsize::new(bytes_per_second),
+            last_refill: AtomicU64::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Refills the budget for the time passed since the last refill, given the current system time in nanoseconds.
+    pub(crate) fn refill(&self, now: u64) {
+        if self.bytes_per_second == 0 {
+            return;
+        }
+
+        let last_refill = self.last_refill.load(Ordering::Relaxed);
+        let elapsed = now.saturating_sub(last_refill) as u128;
+        let refill = elapsed * self.bytes_per_second as u128 / 1_000_000_000;
+        if refill == 0 {
+            // Keep accumulating time until at least one byte is earned.
+            return;
+        }
+
+        self.last_refill.store(now, Ordering::Relaxed);
+        let refill = refill.min(self.bytes_per_second as u128) as usize;
+        let _ = self.budget.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |budget| {
+            Some(budget.saturating_add(refill).min(self.bytes_per_second))
+        });
+    }
+
+    /// Takes up to `len` bytes from the budget, returning how many bytes may be forwarded.
+    pub(crate) fn take(&self, len: usize) -> usize {
+        if self.bytes_per_second == 0 {
+            return len;
+        }
+
+        match self.budget.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |budget| Some(budget - budget.min(len))) {
+            Ok(budget) => budget.min(len),
+            Err(_) => 0,
+        }
+    }
+
+    /// Records that `len` bytes of output were dropped.
+    pub(crate) fn record_dropped(&self, len: usize) {
+        if len > 0 {
+            self.dropped.fetch_add(len, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of bytes dropped since the last call.
+    pub(crate) fn take_dropped(&self) -> usize {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Encodes `data` as a GDB `O` packet into `packet`, returning the packet length.
+/// `data` must be at most [`CONSOLE_PACKET_DATA_LEN`] bytes.
+fn encode_output_packet(data: &[u8], packet: &mut [u8; CONSOLE_PACKET_LEN]) -> usize {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    packet[0] = b'$';
+    packet[1] = b'O';
+    let mut len = 2;
+    for byte in data {
+        packet[len] = HEX[(byte >> 4) as usize];
+        packet[len + 1] = HEX[(byte & 0xF) as usize];
+        len += 2;
+    }
+
+    let checksum = packet[1..len].iter().fold(0u8, |sum, &c| sum.wrapping_add(c));
+    packet[len] = b'#';
+    packet[len + 1] = HEX[(checksum >> 4) as usize];
+    packet[len + 2] = HEX[(checksum & 0xF) as usize];
+    len + 3
+}
+
 /// Structure for suspending logging within a given scope.
 pub struct LoggingSuspender {
     level: log::LevelFilter,
@@ -203,6 +520,130 @@ mod tests {
         assert_eq!(result.unwrap(), None);
     }
 
+    mock! {
+        Network {}
+
+        impl NetworkIo for Network {
+            fn listen(&self, port: u16) -> Result<(), EfiError>;
+            fn send(&self, data: &[u8]) -> Result<usize, EfiError>;
+            fn receive(&self, buffer: &mut [u8]) -> Result<usize, EfiError>;
+        }
+    }
+
+    #[test]
+    fn test_tcp_init_listens_on_port() {
+        let mut mock = MockNetwork::new();
+        mock.expect_listen().with(mockall::predicate::eq(1234)).times(1).returning(|_| Ok(()));
+
+        let connection = TcpConnection::new(mock, 1234);
+        connection.init();
+    }
+
+    #[test]
+    fn test_tcp_write_retries_partial_sends() {
+        let mut mock = MockNetwork::new();
+        let mut sequence = mockall::Sequence::new();
+        mock.expect_send().withf(|data| data == b"$OK#9a").times(1).in_sequence(&mut sequence).returning(|_| Ok(2));
+        mock.expect_send().withf(|data| data == b"K#9a").times(1).in_sequence(&mut sequence).returning(|_| Ok(0));
+        mock.expect_send().withf(|data| data == b"K#9a").times(1).in_sequence(&mut sequence).returning(|_| Ok(4));
+
+        let connection = TcpConnection::new(mock, 1234);
+        connection.write(b"$OK#9a");
+    }
+
+    #[test]
+    fn test_tcp_write_without_client_is_dropped() {
+        let mut mock = MockNetwork::new();
+        mock.expect_send().times(1).returning(|_| Err(EfiError::NotReady));
+
+        let connection = TcpConnection::new(mock, 1234);
+        connection.write(b"$T05thread:01;#07");
+    }
+
+    #[test]
+    fn test_tcp_read_buffers_received_data() {
+        let mut mock = MockNetwork::new();
+        let mut sequence = mockall::Sequence::new();
+        mock.expect_receive().times(1).in_sequence(&mut sequence).returning(|_| Ok(0));
+        mock.expect_receive().times(1).in_sequence(&mut sequence).returning(|buffer| {
+            buffer[..3].copy_from_slice(b"$g#");
+            Ok(3)
+        });
+        mock.expect_receive().times(1).in_sequence(&mut sequence).returning(|_| Err(EfiError::NotReady));
+
+        let connection = TcpConnection::new(mock, 1234);
+        assert_eq!(connection.try_read(), None);
+        assert_eq!(connection.read(), b'$');
+        assert_eq!(connection.try_read(), Some(b'g'));
+        assert_eq!(connection.read(), b'#');
+        assert_eq!(connection.try_read(), None);
+    }
+
+    #[test]
+    fn test_encode_output_packet() {
+        let mut packet = [0u8; CONSOLE_PACKET_LEN];
+        let len = encode_output_packet(b"Hi\n", &mut packet);
+        assert_eq!(&packet[..len], b"$O48690a#bb");
+
+        let len = encode_output_packet(b"", &mut packet);
+        assert_eq!(&packet[..len], b"$O#4f");
+    }
+
+    #[test]
+    fn test_write_output_packets_splits_long_output() {
+        let data = [b'a'; CONSOLE_PACKET_DATA_LEN + 1];
+        let mut mock = MockSerial::new();
+        let mut sequence = mockall::Sequence::new();
+        mock.expect_write()
+            .withf(|packet| packet.len() == CONSOLE_PACKET_LEN && packet.starts_with(b"$O6161"))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_| ());
+        mock.expect_write().withf(|packet| packet == b"$O61#b6").times(1).in_sequence(&mut sequence).returning(|_| ());
+
+        write_output_packets(&mock, &data);
+    }
+
+    #[test]
+    fn test_console_rate_limit() {
+        const SECOND: u64 = 1_000_000_000;
+
+        let limit = ConsoleRateLimit::new(100);
+        assert_eq!(limit.take(60), 60);
+        assert_eq!(limit.take(60), 40);
+        assert_eq!(limit.take(10), 0);
+        limit.record_dropped(30);
+
+        // A quarter second earns 25 bytes.
+        limit.refill(SECOND / 4);
+        assert_eq!(limit.take(60), 25);
+        assert_eq!(limit.take_dropped(), 30);
+        assert_eq!(limit.take_dropped(), 0);
+
+        // Less than a byte's worth of time is kept for the next refill.
+        limit.refill(SECOND / 4 + 1);
+        assert_eq!(limit.take(1), 0);
+        limit.refill(SECOND / 4 + SECOND / 100);
+        assert_eq!(limit.take(10), 1);
+
+        // The budget holds at most one second of output.
+        limit.refill(10 * SECOND);
+        assert_eq!(limit.take(1000), 100);
+
+        let unlimited = ConsoleRateLimit::new(0);
+        assert_eq!(unlimited.take(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn test_write_dropped_notice() {
+        let mut mock = MockSerial::new();
+        let mut expected = [0u8; CONSOLE_PACKET_LEN];
+        let len = encode_output_packet(b"[42 bytes of console output dropped]\n", &mut expected);
+        mock.expect_write().withf(move |data| data == &expected[..len]).times(1).returning(|_| ());
+
+        write_dropped_notice(&mock, 42);
+    }
+
     #[test]
     fn test_logging_suspender() {
         // Get current log level
@@ -0,0 +1,167 @@
+//! USB Debug Transport.
+//!
+//! Implements a debugger transport over the USB debug device class, for systems
+//! that expose no UART. Two controller interfaces are supported:
+//!
+//! - [`XhciDebugCapability`]: The xHCI Debug Capability (DbC), where the host
+//!   controller itself enumerates as a debug device on one of its ports.
+//! - [`EhciDebugPort`]: The legacy EHCI debug port, which requires an external
+//!   debug device (such as a NET20DC) between the target and the debug host.
+//!
+//! Both are wrapped in a [`UsbDebugConnection`] to be used as the debugger transport.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+mod ehci;
+mod xhci;
+
+pub use ehci::EhciDebugPort;
+pub use xhci::XhciDebugCapability;
+
+use super::ByteStream;
+use patina::{error::EfiError, serial::SerialIO};
+
+/// Size of the receive buffer of a [`UsbDebugConnection`], which holds one
+/// SuperSpeed bulk packet.
+pub(crate) const USB_RECEIVE_BUFFER_SIZE: usize = 1024;
+
+/// Bulk pipe pair of a USB debug device used by a [`UsbDebugConnection`].
+///
+/// None of these routines may block waiting for the debug host, and none may log,
+/// as they are called while the system is broken in.
+///
+pub trait UsbDebugPort: Sync {
+    /// Takes ownership of the controller and brings up the debug device.
+    fn init(&self) -> Result<(), EfiError>;
+
+    /// Sends data to the debug host, returning the number of bytes sent. Returns
+    /// `Ok(0)` if the device is busy, and an error if no debug host is connected.
+    fn send(&self, data: &[u8]) -> Result<usize, EfiError>;
+
+    /// Receives data from the debug host into the buffer, returning the number of
+    /// bytes received. Returns `Ok(0)` if no data is available. The buffer is at
+    /// least one bulk packet in size.
+    fn receive(&self, buffer: &mut [u8]) -> Result<usize, EfiError>;
+}
+
+/// USB Connection for use with the Patina debugger
+///
+/// Implements SerialIO over a [`UsbDebugPort`] so the debugger can be reached over
+/// a USB debug cable. Output written while no debug host is connected is discarded,
+/// as it would be on an unconnected serial port.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// static DEBUGGER: patina_debugger::PatinaDebugger<UsbDebugConnection<XhciDebugCapability>> =
+///     patina_debugger::PatinaDebugger::new(UsbDebugConnection::new(XhciDebugCapability::new(XHCI_MMIO_BASE)));
+/// ```
+///
+pub struct UsbDebugConnection<P: UsbDebugPort> {
+    /// USB debug port for connecting to the debugger.
+    port: P,
+    /// Data received from the debug host but not yet read by the debugger.
+    stream: ByteStream<USB_RECEIVE_BUFFER_SIZE>,
+}
+
+impl<P: UsbDebugPort> UsbDebugConnection<P> {
+    /// Create a new UsbDebugConnection over the given debug port.
+    pub const fn new(port: P) -> Self {
+        UsbDebugConnection { port, stream: ByteStream::new() }
+    }
+}
+
+impl<P: UsbDebugPort> SerialIO for UsbDebugConnection<P> {
+    /// Bring up the USB debug device.
+    fn init(&self) {
+        if let Err(err) = self.port.init() {
+            log::error!("Debugger: Failed to initialize USB debug port: {:?}", err);
+        }
+    }
+
+    /// Send the buffer to the debug host, if any.
+    fn write(&self, buffer: &[u8]) {
+        self.stream.write(buffer, |data| self.port.send(data));
+    }
+
+    /// Read a byte from the debug host, waiting until one is available.
+    fn read(&self) -> u8 {
+        self.stream.read(|data| self.port.receive(data))
+    }
+
+    /// Read a byte from the debug host, if one is available.
+    fn try_read(&self) -> Option<u8> {
+        self.stream.try_read(|data| self.port.receive(data))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use mockall::mock;
+
+    mock! {
+        Port {}
+
+        impl UsbDebugPort for Port {
+            fn init(&self) -> Result<(), EfiError>;
+            fn send(&self, data: &[u8]) -> Result<usize, EfiError>;
+            fn receive(&self, buffer: &mut [u8]) -> Result<usize, EfiError>;
+        }
+    }
+
+    #[test]
+    fn test_usb_init_failure_is_not_fatal() {
+        let mut mock = MockPort::new();
+        mock.expect_init().times(1).returning(|| Err(EfiError::NotFound));
+
+        let connection = UsbDebugConnection::new(mock);
+        connection.init();
+    }
+
+    #[test]
+    fn test_usb_write_splits_into_packets() {
+        let mut mock = MockPort::new();
+        let mut sequence = mockall::Sequence::new();
+        mock.expect_send().withf(|data| data == b"$OK#9a").times(1).in_sequence(&mut sequence).returning(|_| Ok(0));
+        mock.expect_send().withf(|data| data == b"$OK#9a").times(1).in_sequence(&mut sequence).returning(|_| Ok(4));
+        mock.expect_send().withf(|data| data == b"9a").times(1).in_sequence(&mut sequence).returning(|_| Ok(2));
+
+        let connection = UsbDebugConnection::new(mock);
+        connection.write(b"$OK#9a");
+    }
+
+    #[test]
+    fn test_usb_write_without_host_is_dropped() {
+        let mut mock = MockPort::new();
+        mock.expect_send().times(1).returning(|_| Err(EfiError::NotReady));
+
+        let connection = UsbDebugConnection::new(mock);
+        connection.write(b"$T05thread:01;#07");
+    }
+
+    #[test]
+    fn test_usb_read_buffers_received_data() {
+        let mut mock = MockPort::new();
+        let mut sequence = mockall::Sequence::new();
+        mock.expect_receive().times(1).in_sequence(&mut sequence).returning(|_| Ok(0));
+        mock.expect_receive().times(1).in_sequence(&mut sequence).returning(|buffer| {
+            assert_eq!(buffer.len(), USB_RECEIVE_BUFFER_SIZE);
+            buffer[..2].copy_from_slice(b"+$");
+            Ok(2)
+        });
+        mock.expect_receive().times(1).in_sequence(&mut sequence).returning(|_| Err(EfiError::DeviceError));
+
+        let connection = UsbDebugConnection::new(mock);
+        assert_eq!(connection.try_read(), None);
+        assert_eq!(connection.read(), b'+');
+        assert_eq!(connection.try_read(), Some(b'$'));
+        assert_eq!(connection.try_read(), None);
+    }
+}
@@ -0,0 +1,350 @@
+//! EHCI Debug Port.
+//!
+//! Drives the debug port described in appendix C of the EHCI specification. The
+//! debug port issues single USB transactions of up to eight bytes to a debug device
+//! attached to one root port, independent of the rest of the host controller.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use super::UsbDebugPort;
+use patina::error::EfiError;
+use spin::Mutex;
+
+// Debug port register offsets.
+const CONTROL_OFFSET: usize = 0x00;
+const PIDS_OFFSET: usize = 0x04;
+const DATA_OFFSET: usize = 0x08;
+const ADDRESS_OFFSET: usize = 0x10;
+
+// Control/status register fields.
+const CONTROL_OWNER: u32 = 1 << 30;
+const CONTROL_ENABLED: u32 = 1 << 28;
+const CONTROL_DONE: u32 = 1 << 16;
+const CONTROL_IN_USE: u32 = 1 << 10;
+const CONTROL_ERROR: u32 = 1 << 6;
+const CONTROL_GO: u32 = 1 << 5;
+const CONTROL_WRITE: u32 = 1 << 4;
+const CONTROL_LENGTH_MASK: u32 = 0xF;
+const CONTROL_CLAIM: u32 = CONTROL_OWNER | CONTROL_ENABLED | CONTROL_IN_USE;
+
+// USB packet identifiers.
+const PID_OUT: u8 = 0xE1;
+const PID_IN: u8 = 0x69;
+const PID_SETUP: u8 = 0x2D;
+const PID_DATA0: u8 = 0xC3;
+const PID_DATA1: u8 = 0x4B;
+const PID_NAK: u8 = 0x5A;
+const PID_STALL: u8 = 0x1E;
+
+// Standard device requests used to configure the debug device.
+const REQUEST_SET_FEATURE: u8 = 3;
+const REQUEST_SET_ADDRESS: u8 = 5;
+const REQUEST_GET_DESCRIPTOR: u8 = 6;
+const REQUEST_TYPE_DEVICE_TO_HOST: u8 = 0x80;
+const REQUEST_TYPE_HOST_TO_DEVICE: u8 = 0x00;
+const DESCRIPTOR_TYPE_DEBUG: u8 = 10;
+const DEBUG_DESCRIPTOR_LENGTH: usize = 4;
+const FEATURE_DEBUG_MODE: u16 = 6;
+
+/// Maximum data carried by a single debug port transaction.
+const MAX_PACKET_SIZE: usize = 8;
+
+/// Address assigned to the debug device, by convention the highest device address.
+const DEBUG_DEVICE_ADDRESS: u8 = 127;
+
+/// Number of polls of the control register before a transaction is abandoned.
+const DONE_POLL_LIMIT: usize = 1_000_000;
+
+/// Number of times a NAKed transaction is retried during device configuration.
+const NAK_RETRY_LIMIT: usize = 1_000;
+
+/// Outcome of a failed debug port transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransactionError {
+    /// The device is not ready and the transaction should be retried.
+    Nak,
+    /// The device rejected the request.
+    Stall,
+    /// The transaction failed on the bus or never completed.
+    Failed,
+}
+
+/// Debug device state discovered during initialization.
+struct EhciState {
+    /// Whether the debug device has been configured.
+    configured: bool,
+    /// Bulk IN endpoint of the debug device.
+    in_endpoint: u8,
+    /// Bulk OUT endpoint of the debug device.
+    out_endpoint: u8,
+    /// Data PID of the next bulk OUT transaction.
+    out_data_pid: u8,
+}
+
+/// EHCI debug port for use with a [`UsbDebugConnection`](super::UsbDebugConnection).
+///
+/// `base` is the address of the debug port registers: the memory BAR of the EHCI
+/// controller selected by the debug port PCI capability, plus the offset it reports.
+/// The platform must have started the host controller and reset the root port the
+/// debug device is attached to before the debugger is initialized. A debug device
+/// that was already configured by earlier firmware is reused as is.
+///
+pub struct EhciDebugPort {
+    base: usize,
+    state: Mutex<EhciState>,
+}
+
+impl EhciDebugPort {
+    /// Create a new EhciDebugPort for the debug port registers at `base`.
+    pub const fn new(base: usize) -> Self {
+        EhciDebugPort {
+            base,
+            state: Mutex::new(EhciState {
+                configured: false,
+                in_endpoint: 0,
+                out_endpoint: 0,
+                out_data_pid: PID_DATA0,
+            }),
+        }
+    }
+
+    fn read_register(&self, offset: usize) -> u32 {
+        // SAFETY: The platform provided the address of the debug port register block.
+        unsafe { ((self.base + offset) as *const u32).read_volatile() }
+    }
+
+    fn write_register(&self, offset: usize, value: u32) {
+        // SAFETY: The platform provided the address of the debug port register block.
+        unsafe { ((self.base + offset) as *mut u32).write_volatile(value) }
+    }
+
+    /// Loads up to eight bytes into the data buffer registers.
+    fn write_data(&self, data: &[u8]) {
+        let mut bytes = [0u8; MAX_PACKET_SIZE];
+        bytes[..data.len()].copy_from_slice(data);
+        self.write_register(DATA_OFFSET, u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        self.write_register(DATA_OFFSET + 4, u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]));
+    }
+
+    /// Copies received bytes out of the data buffer registers.
+    fn read_data(&self, buffer: &mut [u8]) {
+        let low = self.read_register(DATA_OFFSET).to_le_bytes();
+        let high = self.read_register(DATA_OFFSET + 4).to_le_bytes();
+        let bytes = [low[0], low[1], low[2], low[3], high[0], high[1], high[2], high[3]];
+        let len = buffer.len().min(MAX_PACKET_SIZE);
+        buffer[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    /// Runs a single transaction, returning the number of bytes transferred.
+    fn transact(
+        &self,
+        device: u8,
+        endpoint: u8,
+        token_pid: u8,
+        data_pid: u8,
+        write: bool,
+        length: usize,
+    ) -> Result<usize, TransactionError> {
+        let mut control = CONTROL_CLAIM | (length as u32 & CONTROL_LENGTH_MASK);
+        if write {
+            control |= CONTROL_WRITE;
+        }
+
+        self.write_register(ADDRESS_OFFSET, ((device as u32) << 8) | endpoint as u32);
+        self.write_register(PIDS_OFFSET, ((data_pid as u32) << 8) | token_pid as u32);
+        self.write_register(CONTROL_OFFSET, control | CONTROL_GO);
+
+        let mut status = 0;
+        for _ in 0..DONE_POLL_LIMIT {
+            status = self.read_register(CONTROL_OFFSET);
+            if status & CONTROL_DONE != 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+
+        if status & CONTROL_DONE == 0 {
+            return Err(TransactionError::Failed);
+        }
+
+        // DONE is write-one-to-clear.
+        self.write_register(CONTROL_OFFSET, status);
+        if status & CONTROL_ERROR != 0 {
+            return Err(TransactionError::Failed);
+        }
+
+        match (self.read_register(PIDS_OFFSET) >> 16) as u8 {
+            PID_NAK => Err(TransactionError::Nak),
+            PID_STALL => Err(TransactionError::Stall),
+            _ => Ok((status & CONTROL_LENGTH_MASK) as usize),
+        }
+    }
+
+    fn bulk_out(&self, device: u8, endpoint: u8, data_pid: u8, data: &[u8]) -> Result<usize, TransactionError> {
+        self.write_data(data);
+        self.transact(device, endpoint, PID_OUT, data_pid, true, data.len())
+    }
+
+    fn bulk_in(&self, device: u8, endpoint: u8, buffer: &mut [u8]) -> Result<usize, TransactionError> {
+        let length = buffer.len().min(MAX_PACKET_SIZE);
+        let received = self.transact(device, endpoint, PID_IN, PID_DATA0, false, length)?;
+        self.read_data(&mut buffer[..received.min(length)]);
+        Ok(received)
+    }
+
+    /// Issues a control request to endpoint zero, returning the length of the data
+    /// stage. Requests without a data stage pass an empty buffer, which reads the
+    /// status stage instead.
+    fn control_transfer(&self, device: u8, setup: [u8; 8], buffer: &mut [u8]) -> Result<usize, TransactionError> {
+        retry_on_nak(|| {
+            self.write_data(&setup);
+            self.transact(device, 0, PID_SETUP, PID_DATA0, true, setup.len())
+        })?;
+        retry_on_nak(|| self.bulk_in(device, 0, buffer))
+    }
+}
+
+impl UsbDebugPort for EhciDebugPort {
+    fn init(&self) -> Result<(), EfiError> {
+        let mut state = self.state.lock();
+        state.configured = false;
+
+        self.write_register(CONTROL_OFFSET, CONTROL_CLAIM);
+        if self.read_register(CONTROL_OFFSET) & CONTROL_ENABLED == 0 {
+            return Err(EfiError::NotReady);
+        }
+
+        // The debug device is either still at the default address or was already
+        // moved to the debug address by earlier firmware.
+        let mut descriptor = [0u8; DEBUG_DESCRIPTOR_LENGTH];
+        let get_descriptor = setup_packet(
+            REQUEST_TYPE_DEVICE_TO_HOST,
+            REQUEST_GET_DESCRIPTOR,
+            (DESCRIPTOR_TYPE_DEBUG as u16) << 8,
+            DEBUG_DESCRIPTOR_LENGTH as u16,
+        );
+        let device = [0, DEBUG_DEVICE_ADDRESS]
+            .into_iter()
+            .find(|&device| {
+                self.control_transfer(device, get_descriptor, &mut descriptor) == Ok(DEBUG_DESCRIPTOR_LENGTH)
+                    && descriptor[1] == DESCRIPTOR_TYPE_DEBUG
+            })
+            .ok_or(EfiError::NotFound)?;
+
+        if device != DEBUG_DEVICE_ADDRESS {
+            let set_address =
+                setup_packet(REQUEST_TYPE_HOST_TO_DEVICE, REQUEST_SET_ADDRESS, DEBUG_DEVICE_ADDRESS as u16, 0);
+            self.control_transfer(device, set_address, &mut []).map_err(|_| EfiError::DeviceError)?;
+        }
+
+        let set_debug_mode = setup_packet(REQUEST_TYPE_HOST_TO_DEVICE, REQUEST_SET_FEATURE, FEATURE_DEBUG_MODE, 0);
+        self.control_transfer(DEBUG_DEVICE_ADDRESS, set_debug_mode, &mut []).map_err(|_| EfiError::DeviceError)?;
+
+        state.in_endpoint = descriptor[2] & 0xF;
+        state.out_endpoint = descriptor[3] & 0xF;
+        state.out_data_pid = PID_DATA0;
+        state.configured = true;
+        Ok(())
+    }
+
+    fn send(&self, data: &[u8]) -> Result<usize, EfiError> {
+        let mut state = self.state.lock();
+        if !state.configured {
+            return Err(EfiError::NotReady);
+        }
+
+        let chunk = &data[..data.len().min(MAX_PACKET_SIZE)];
+        match self.bulk_out(DEBUG_DEVICE_ADDRESS, state.out_endpoint, state.out_data_pid, chunk) {
+            Ok(_) => {
+                state.out_data_pid = toggle_data_pid(state.out_data_pid);
+                Ok(chunk.len())
+            }
+            Err(TransactionError::Nak) => Ok(0),
+            Err(_) => Err(EfiError::DeviceError),
+        }
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let state = self.state.lock();
+        if !state.configured {
+            return Ok(0);
+        }
+
+        match self.bulk_in(DEBUG_DEVICE_ADDRESS, state.in_endpoint, buffer) {
+            Ok(received) => Ok(received),
+            Err(TransactionError::Nak) => Ok(0),
+            Err(_) => Err(EfiError::DeviceError),
+        }
+    }
+}
+
+/// Retries a transaction while the device responds with NAK.
+fn retry_on_nak(mut transaction: impl FnMut() -> Result<usize, TransactionError>) -> Result<usize, TransactionError> {
+    let mut result = Err(TransactionError::Nak);
+    for _ in 0..NAK_RETRY_LIMIT {
+        result = transaction();
+        if result != Err(TransactionError::Nak) {
+            break;
+        }
+    }
+    result
+}
+
+/// Builds the setup packet of a standard device request.
+fn setup_packet(request_type: u8, request: u8, value: u16, length: u16) -> [u8; 8] {
+    let value = value.to_le_bytes();
+    let length = length.to_le_bytes();
+    [request_type, request, value[0], value[1], 0, 0, length[0], length[1]]
+}
+
+fn toggle_data_pid(pid: u8) -> u8 {
+    if pid == PID_DATA0 { PID_DATA1 } else { PID_DATA0 }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_packet_encoding() {
+        assert_eq!(
+            setup_packet(REQUEST_TYPE_DEVICE_TO_HOST, REQUEST_GET_DESCRIPTOR, 0x0A00, 4),
+            [0x80, 0x06, 0x00, 0x0A, 0x00, 0x00, 0x04, 0x00]
+        );
+        assert_eq!(
+            setup_packet(REQUEST_TYPE_HOST_TO_DEVICE, REQUEST_SET_ADDRESS, 127, 0),
+            [0x00, 0x05, 0x7F, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_data_pid_toggles() {
+        assert_eq!(toggle_data_pid(PID_DATA0), PID_DATA1);
+        assert_eq!(toggle_data_pid(PID_DATA1), PID_DATA0);
+    }
+
+    #[test]
+    fn test_retry_on_nak_gives_up() {
+        let mut attempts = 0;
+        let result = retry_on_nak(|| {
+            attempts += 1;
+            Err(TransactionError::Nak)
+        });
+        assert_eq!(result, Err(TransactionError::Nak));
+        assert_eq!(attempts, NAK_RETRY_LIMIT);
+
+        let mut attempts = 0;
+        let result = retry_on_nak(|| {
+            attempts += 1;
+            if attempts < 3 { Err(TransactionError::Nak) } else { Ok(4) }
+        });
+        assert_eq!(result, Ok(4));
+        assert_eq!(attempts, 3);
+    }
+}
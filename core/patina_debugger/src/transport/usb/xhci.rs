@@ -0,0 +1,789 @@
+//! xHCI Debug Capability.
+//!
+//! Drives the Debug Capability (DbC) described in section 7.6 of the xHCI
+//! specification. When enabled, the DbC presents one root port of the host
+//! controller to the debug host as a USB debug device with a single pair of bulk
+//! endpoints, independent of the rest of the host controller. A USB 3 A-to-A debug
+//! cable is needed between the target and the debug host.
+//!
+//! The DbC identifies itself with the vendor and product IDs that the Linux
+//! `usb_debug` driver binds to, so the connection appears as a `/dev/ttyUSB` device
+//! on a Linux debug host.
+//!
+//! A transfer that fails, for instance with a stall or a transaction error, halts
+//! its endpoint until the debug host clears the halt. The failed TRB is turned into
+//! a no-op so that the DbC does not retry it when the endpoint restarts, and no
+//! transfers are queued to the endpoint while it is halted. Data sent or received
+//! by the failed transfer is lost, which GDB recovers from by retransmitting.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use core::{
+    ptr::{self, addr_of_mut},
+    sync::atomic::{Ordering, fence},
+};
+
+use super::{USB_RECEIVE_BUFFER_SIZE, UsbDebugPort};
+use patina::error::EfiError;
+use spin::Mutex;
+
+// Host controller capability registers.
+const HCCPARAMS1_OFFSET: usize = 0x10;
+const CAPABILITY_ID_DEBUG: u32 = 10;
+
+// Debug capability register offsets.
+const DCDB_OFFSET: usize = 0x04;
+const DCERSTSZ_OFFSET: usize = 0x08;
+const DCERSTBA_OFFSET: usize = 0x10;
+const DCERDP_OFFSET: usize = 0x18;
+const DCCTRL_OFFSET: usize = 0x20;
+const DCPORTSC_OFFSET: usize = 0x28;
+const DCCP_OFFSET: usize = 0x30;
+const DCDDI1_OFFSET: usize = 0x38;
+const DCDDI2_OFFSET: usize = 0x3C;
+
+// DCCTRL fields.
+const DCCTRL_RUN: u32 = 1 << 0;
+const DCCTRL_LINK_STATUS_EVENT_ENABLE: u32 = 1 << 1;
+const DCCTRL_HALT_OUT: u32 = 1 << 2;
+const DCCTRL_HALT_IN: u32 = 1 << 3;
+const DCCTRL_RUN_CHANGE: u32 = 1 << 4;
+const DCCTRL_ENABLE: u32 = 1 << 31;
+const DCCTRL_MAX_BURST_SHIFT: u32 = 16;
+
+// DCPORTSC change fields, which are write-one-to-clear.
+const DCPORTSC_CHANGE_BITS: u32 = (1 << 17) | (1 << 21) | (1 << 22) | (1 << 23);
+
+// Doorbell targets.
+const DOORBELL_OUT: u32 = 0;
+const DOORBELL_IN: u32 = 1 << 8;
+
+// Transfer request block fields.
+const TRB_CYCLE: u32 = 1 << 0;
+const TRB_TOGGLE_CYCLE: u32 = 1 << 1;
+const TRB_INTERRUPT_ON_SHORT_PACKET: u32 = 1 << 2;
+const TRB_INTERRUPT_ON_COMPLETION: u32 = 1 << 5;
+const TRB_TYPE_SHIFT: u32 = 10;
+const TRB_TYPE_NORMAL: u32 = 1;
+const TRB_TYPE_LINK: u32 = 6;
+const TRB_TYPE_NOOP: u32 = 8;
+const TRB_TYPE_TRANSFER_EVENT: u32 = 32;
+const COMPLETION_SUCCESS: u32 = 1;
+const COMPLETION_SHORT_PACKET: u32 = 13;
+
+// Endpoint IDs reported in debug capability transfer events.
+const ENDPOINT_ID_OUT: u32 = 2;
+const ENDPOINT_ID_IN: u32 = 3;
+
+// Endpoint context fields.
+const ENDPOINT_TYPE_BULK_OUT: u32 = 2;
+const ENDPOINT_TYPE_BULK_IN: u32 = 6;
+const ENDPOINT_ERROR_COUNT: u32 = 3;
+
+/// Maximum bulk packet size of the debug device.
+const MAX_PACKET_SIZE: usize = USB_RECEIVE_BUFFER_SIZE;
+
+// Identity of the debug device, matching the Linux `usb_debug` driver.
+const DBC_PROTOCOL_GDB: u32 = 1;
+const DBC_VENDOR_ID: u32 = 0x1D6B;
+const DBC_PRODUCT_ID: u32 = 0x0010;
+const DBC_DEVICE_REVISION: u32 = 0x0010;
+
+const MANUFACTURER: &str = "Patina";
+const PRODUCT: &str = "Patina Debugger";
+const SERIAL_NUMBER: &str = "0001";
+
+/// Number of TRBs in each transfer ring, including the link TRB.
+const TRANSFER_RING_SIZE: usize = 16;
+/// Number of TRBs in the event ring.
+const EVENT_RING_SIZE: usize = 16;
+/// Size of a string descriptor slot.
+const STRING_DESCRIPTOR_SIZE: usize = 64;
+/// Size of the debug capability context in dwords: the info context followed by
+/// the OUT and IN endpoint contexts, each 64 bytes.
+const CONTEXT_DWORDS: usize = 48;
+const OUT_CONTEXT_DWORD: usize = 16;
+const IN_CONTEXT_DWORD: usize = 32;
+
+/// Number of polls of DCCTRL while waiting for the DbC to start or stop.
+const ENABLE_POLL_LIMIT: usize = 1_000_000;
+
+/// A transfer request block.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Trb {
+    parameter: u64,
+    status: u32,
+    control: u32,
+}
+
+impl Trb {
+    const ZERO: Trb = Trb { parameter: 0, status: 0, control: 0 };
+}
+
+/// Wrapper aligning a structure for use by the controller.
+#[repr(C, align(64))]
+struct Aligned<T>(T);
+
+/// Memory shared with the controller.
+///
+/// The DbC accesses this memory by physical address, so the debugger must run with
+/// this memory identity mapped.
+#[repr(C, align(4096))]
+struct DbcMemory {
+    event_ring: Aligned<[Trb; EVENT_RING_SIZE]>,
+    out_ring: Aligned<[Trb; TRANSFER_RING_SIZE]>,
+    in_ring: Aligned<[Trb; TRANSFER_RING_SIZE]>,
+    /// Event ring segment table with a single entry: the segment base and size.
+    segment_table: Aligned<[u64; 2]>,
+    context: Aligned<[u32; CONTEXT_DWORDS]>,
+    /// String zero, manufacturer, product, and serial number descriptors.
+    strings: Aligned<[[u8; STRING_DESCRIPTOR_SIZE]; 4]>,
+    out_buffer: Aligned<[u8; MAX_PACKET_SIZE]>,
+    in_buffer: Aligned<[u8; MAX_PACKET_SIZE]>,
+}
+
+impl DbcMemory {
+    const fn new() -> Self {
+        DbcMemory {
+            event_ring: Aligned([Trb::ZERO; EVENT_RING_SIZE]),
+            out_ring: Aligned([Trb::ZERO; TRANSFER_RING_SIZE]),
+            in_ring: Aligned([Trb::ZERO; TRANSFER_RING_SIZE]),
+            segment_table: Aligned([0; 2]),
+            context: Aligned([0; CONTEXT_DWORDS]),
+            strings: Aligned([[0; STRING_DESCRIPTOR_SIZE]; 4]),
+            out_buffer: Aligned([0; MAX_PACKET_SIZE]),
+            in_buffer: Aligned([0; MAX_PACKET_SIZE]),
+        }
+    }
+}
+
+/// Producer or consumer position in a ring.
+#[derive(Debug, Clone, Copy)]
+struct RingPosition {
+    index: usize,
+    cycle: bool,
+}
+
+impl RingPosition {
+    const START: RingPosition = RingPosition { index: 0, cycle: true };
+}
+
+/// Debug capability state.
+struct XhciState {
+    /// Address of the debug capability registers, or zero if not yet located.
+    dbc_base: usize,
+    out_ring: RingPosition,
+    in_ring: RingPosition,
+    event_ring: RingPosition,
+    /// Whether an OUT transfer has been queued and not yet completed.
+    out_pending: bool,
+    /// Whether an IN transfer has been queued and not yet completed.
+    in_pending: bool,
+    /// Length of a completed IN transfer not yet returned to the caller.
+    in_received: Option<usize>,
+    memory: DbcMemory,
+}
+
+/// xHCI Debug Capability for use with a [`UsbDebugConnection`](super::UsbDebugConnection).
+///
+/// `xhci_base` is the address of the host controller's MMIO registers. The DbC is
+/// located through the extended capability list during initialization. The memory
+/// shared with the controller is embedded in this structure, so it must be placed
+/// in a static, as is already the case for the debugger, and be identity mapped.
+/// The DbC runs independently of the host controller, so this can be used while
+/// the platform or an OS driver owns the rest of the controller.
+///
+pub struct XhciDebugCapability {
+    xhci_base: usize,
+    state: Mutex<XhciState>,
+}
+
+impl XhciDebugCapability {
+    /// Create a new XhciDebugCapability for the host controller at `xhci_base`.
+    pub const fn new(xhci_base: usize) -> Self {
+        XhciDebugCapability {
+            xhci_base,
+            state: Mutex::new(XhciState {
+                dbc_base: 0,
+                out_ring: RingPosition::START,
+                in_ring: RingPosition::START,
+                event_ring: RingPosition::START,
+                out_pending: false,
+                in_pending: false,
+                in_received: None,
+                memory: DbcMemory::new(),
+            }),
+        }
+    }
+
+    /// Walks the extended capability list for the debug capability.
+    fn find_debug_capability(&self) -> Option<usize> {
+        let mut offset = ((read_register(self.xhci_base + HCCPARAMS1_OFFSET) >> 16) as usize) << 2;
+        while offset != 0 {
+            let capability = read_register(self.xhci_base + offset);
+            if capability & 0xFF == CAPABILITY_ID_DEBUG {
+                return Some(self.xhci_base + offset);
+            }
+
+            let next = ((capability >> 8) & 0xFF) as usize;
+            if next == 0 {
+                break;
+            }
+            offset += next << 2;
+        }
+        None
+    }
+}
+
+impl XhciState {
+    /// Initializes the rings and contexts shared with the controller.
+    fn reset_memory(&mut self, max_burst: u32) {
+        self.out_ring = RingPosition::START;
+        self.in_ring = RingPosition::START;
+        self.event_ring = RingPosition::START;
+        self.out_pending = false;
+        self.in_pending = false;
+        self.in_received = None;
+
+        let memory = &mut self.memory;
+        for trb in memory.event_ring.0.iter_mut().chain(memory.out_ring.0.iter_mut()).chain(memory.in_ring.0.iter_mut())
+        {
+            write_trb(trb, 0, 0, 0);
+        }
+
+        let event_ring = address_of(&memory.event_ring.0);
+        // SAFETY: The segment table is owned by this structure.
+        unsafe { ptr::write_volatile(&mut memory.segment_table.0, [event_ring, EVENT_RING_SIZE as u64]) };
+
+        let mut strings = [[0u8; STRING_DESCRIPTOR_SIZE]; 4];
+        let mut lengths = 0u32;
+        for (index, text) in [None, Some(MANUFACTURER), Some(PRODUCT), Some(SERIAL_NUMBER)].into_iter().enumerate() {
+            let length = match text {
+                Some(text) => string_descriptor(text, &mut strings[index]),
+                None => language_descriptor(&mut strings[index]),
+            };
+            lengths |= (length as u32) << (index * 8);
+        }
+        // SAFETY: The string descriptors are owned by this structure.
+        unsafe { ptr::write_volatile(&mut memory.strings.0, strings) };
+
+        let mut context = [0u32; CONTEXT_DWORDS];
+        for (index, string) in memory.strings.0.iter().enumerate() {
+            let address = address_of(string);
+            context[index * 2] = address as u32;
+            context[index * 2 + 1] = (address >> 32) as u32;
+        }
+        context[8] = lengths;
+        write_endpoint_context(
+            &mut context[OUT_CONTEXT_DWORD..],
+            ENDPOINT_TYPE_BULK_OUT,
+            max_burst,
+            address_of(&memory.out_ring.0),
+        );
+        write_endpoint_context(
+            &mut context[IN_CONTEXT_DWORD..],
+            ENDPOINT_TYPE_BULK_IN,
+            max_burst,
+            address_of(&memory.in_ring.0),
+        );
+        // SAFETY: The context is owned by this structure.
+        unsafe { ptr::write_volatile(&mut memory.context.0, context) };
+    }
+
+    /// Returns true if the controller has halted the endpoint selected by `halt_bit`
+    /// in DCCTRL after a failed transfer. The debug host clears the halt.
+    fn is_halted(&self, halt_bit: u32) -> bool {
+        read_register(self.dbc_base + DCCTRL_OFFSET) & halt_bit != 0
+    }
+
+    /// Returns true if a debug host has configured the debug device, acknowledging
+    /// any connection state changes.
+    fn update_connection(&mut self) -> bool {
+        if self.dbc_base == 0 {
+            return false;
+        }
+
+        let control = read_register(self.dbc_base + DCCTRL_OFFSET);
+        if control & DCCTRL_RUN_CHANGE != 0 {
+            write_register(self.dbc_base + DCCTRL_OFFSET, control);
+        }
+
+        let port_status = read_register(self.dbc_base + DCPORTSC_OFFSET);
+        if port_status & DCPORTSC_CHANGE_BITS != 0 {
+            write_register(self.dbc_base + DCPORTSC_OFFSET, port_status);
+        }
+
+        control & DCCTRL_RUN != 0
+    }
+
+    /// Consumes all pending events, recording completed transfers.
+    fn process_events(&mut self) {
+        let mut consumed = false;
+        loop {
+            let trb = &mut self.memory.event_ring.0[self.event_ring.index] as *mut Trb;
+            // SAFETY: The event ring is owned by this structure. The control field is
+            // read first, as it holds the cycle bit that hands the TRB to software.
+            let control = unsafe { addr_of_mut!((*trb).control).read_volatile() };
+            if (control & TRB_CYCLE != 0) != self.event_ring.cycle {
+                break;
+            }
+            fence(Ordering::Acquire);
+            // SAFETY: As above.
+            let status = unsafe { addr_of_mut!((*trb).status).read_volatile() };
+
+            if (control >> TRB_TYPE_SHIFT) & 0x3F == TRB_TYPE_TRANSFER_EVENT {
+                let completion = status >> 24;
+                let succeeded = completion == COMPLETION_SUCCESS || completion == COMPLETION_SHORT_PACKET;
+                // SAFETY: As above.
+                let failed_trb = unsafe { addr_of_mut!((*trb).parameter).read_volatile() };
+                match (control >> 16) & 0x1F {
+                    ENDPOINT_ID_OUT => {
+                        self.out_pending = false;
+                        if !succeeded {
+                            cancel_transfer(&mut self.memory.out_ring.0, failed_trb);
+                        }
+                    }
+                    ENDPOINT_ID_IN => {
+                        self.in_pending = false;
+                        let residual = (status & 0xFF_FFFF) as usize;
+                        self.in_received = Some(if succeeded { MAX_PACKET_SIZE.saturating_sub(residual) } else { 0 });
+                        if !succeeded {
+                            cancel_transfer(&mut self.memory.in_ring.0, failed_trb);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            self.event_ring.index += 1;
+            if self.event_ring.index == EVENT_RING_SIZE {
+                self.event_ring.index = 0;
+                self.event_ring.cycle = !self.event_ring.cycle;
+            }
+            consumed = true;
+        }
+
+        if consumed {
+            let dequeue = address_of(&self.memory.event_ring.0[self.event_ring.index]);
+            write_register_64(self.dbc_base + DCERDP_OFFSET, dequeue);
+        }
+    }
+}
+
+impl UsbDebugPort for XhciDebugCapability {
+    fn init(&self) -> Result<(), EfiError> {
+        let mut state = self.state.lock();
+        let dbc = self.find_debug_capability().ok_or(EfiError::Unsupported)?;
+        state.dbc_base = dbc;
+
+        // Stop the DbC before reprogramming it.
+        write_register(dbc + DCCTRL_OFFSET, 0);
+        if !poll_register(dbc + DCCTRL_OFFSET, DCCTRL_ENABLE, 0) {
+            return Err(EfiError::Timeout);
+        }
+
+        let max_burst = (read_register(dbc + DCCTRL_OFFSET) >> DCCTRL_MAX_BURST_SHIFT) & 0xFF;
+        state.reset_memory(max_burst);
+
+        write_register(dbc + DCERSTSZ_OFFSET, 1);
+        write_register_64(dbc + DCERSTBA_OFFSET, address_of(&state.memory.segment_table.0));
+        write_register_64(dbc + DCERDP_OFFSET, address_of(&state.memory.event_ring.0));
+        write_register_64(dbc + DCCP_OFFSET, address_of(&state.memory.context.0));
+        write_register(dbc + DCDDI1_OFFSET, (DBC_VENDOR_ID << 16) | DBC_PROTOCOL_GDB);
+        write_register(dbc + DCDDI2_OFFSET, (DBC_DEVICE_REVISION << 16) | DBC_PRODUCT_ID);
+
+        fence(Ordering::SeqCst);
+        write_register(dbc + DCCTRL_OFFSET, DCCTRL_ENABLE | DCCTRL_LINK_STATUS_EVENT_ENABLE);
+        if !poll_register(dbc + DCCTRL_OFFSET, DCCTRL_ENABLE, DCCTRL_ENABLE) {
+            return Err(EfiError::DeviceError);
+        }
+
+        Ok(())
+    }
+
+    fn send(&self, data: &[u8]) -> Result<usize, EfiError> {
+        let mut state = self.state.lock();
+        if !state.update_connection() {
+            return Err(EfiError::NotReady);
+        }
+
+        state.process_events();
+        // Drop the data rather than wait for a debug host that may never clear the halt.
+        if state.is_halted(DCCTRL_HALT_OUT) {
+            return Err(EfiError::NotReady);
+        }
+        if state.out_pending {
+            return Ok(0);
+        }
+
+        let length = data.len().min(MAX_PACKET_SIZE);
+        let state = &mut *state;
+        for (target, byte) in state.memory.out_buffer.0.iter_mut().zip(&data[..length]) {
+            // SAFETY: The buffer is owned by this structure.
+            unsafe { ptr::write_volatile(target, *byte) };
+        }
+
+        let buffer = address_of(&state.memory.out_buffer.0);
+        enqueue_transfer(&mut state.memory.out_ring.0, &mut state.out_ring, buffer, length as u32, 0);
+        state.out_pending = true;
+
+        fence(Ordering::SeqCst);
+        write_register(state.dbc_base + DCDB_OFFSET, DOORBELL_OUT);
+        Ok(length)
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<usize, EfiError> {
+        let mut state = self.state.lock();
+        if !state.update_connection() {
+            return Ok(0);
+        }
+
+        state.process_events();
+        let state = &mut *state;
+        let received = state.in_received.take().unwrap_or(0).min(buffer.len());
+        for (target, byte) in buffer[..received].iter_mut().zip(state.memory.in_buffer.0.iter()) {
+            // SAFETY: The buffer is owned by this structure and no transfer is queued to it.
+            *target = unsafe { ptr::read_volatile(byte) };
+        }
+
+        if !state.in_pending && !state.is_halted(DCCTRL_HALT_IN) {
+            let in_buffer = address_of(&state.memory.in_buffer.0);
+            enqueue_transfer(
+                &mut state.memory.in_ring.0,
+                &mut state.in_ring,
+                in_buffer,
+                MAX_PACKET_SIZE as u32,
+                TRB_INTERRUPT_ON_SHORT_PACKET,
+            );
+            state.in_pending = true;
+
+            fence(Ordering::SeqCst);
+            write_register(state.dbc_base + DCDB_OFFSET, DOORBELL_IN);
+        }
+
+        Ok(received)
+    }
+}
+
+/// Queues a normal TRB for `length` bytes at `buffer`, following the link TRB back
+/// to the start of the ring when the end is reached.
+fn enqueue_transfer(
+    ring: &mut [Trb; TRANSFER_RING_SIZE],
+    position: &mut RingPosition,
+    buffer: u64,
+    length: u32,
+    flags: u32,
+) {
+    let cycle = if position.cycle { TRB_CYCLE } else { 0 };
+    let control = (TRB_TYPE_NORMAL << TRB_TYPE_SHIFT) | TRB_INTERRUPT_ON_COMPLETION | flags | cycle;
+    write_trb(&mut ring[position.index], buffer, length, control);
+
+    position.index += 1;
+    if position.index == TRANSFER_RING_SIZE - 1 {
+        let start = address_of(ring);
+        let control = (TRB_TYPE_LINK << TRB_TYPE_SHIFT) | TRB_TOGGLE_CYCLE | cycle;
+        write_trb(&mut ring[position.index], start, 0, control);
+        position.index = 0;
+        position.cycle = !position.cycle;
+    }
+}
+
+/// Turns the transfer TRB at address `trb` in `ring` into a no-op, keeping its cycle
+/// bit, so that the DbC skips it when its halted endpoint restarts from that TRB.
+fn cancel_transfer(ring: &mut [Trb; TRANSFER_RING_SIZE], trb: u64) {
+    let start = address_of(ring);
+    let Some(index) = trb.checked_sub(start).map(|offset| offset as usize / size_of::<Trb>()) else {
+        return;
+    };
+    if index >= TRANSFER_RING_SIZE - 1 {
+        return;
+    }
+
+    let cycle = ring[index].control & TRB_CYCLE;
+    write_trb(&mut ring[index], 0, 0, (TRB_TYPE_NOOP << TRB_TYPE_SHIFT) | cycle);
+}
+
+/// Writes a TRB, writing the control field holding the cycle bit last so the
+/// controller never sees a partially written TRB.
+fn write_trb(trb: &mut Trb, parameter: u64, status: u32, control: u32) {
+    let trb = trb as *mut Trb;
+    // SAFETY: The TRB is a valid, exclusively borrowed location.
+    unsafe {
+        addr_of_mut!((*trb).parameter).write_volatile(parameter);
+        addr_of_mut!((*trb).status).write_volatile(status);
+        fence(Ordering::Release);
+        addr_of_mut!((*trb).control).write_volatile(control);
+    }
+}
+
+/// Fills in a bulk endpoint context whose transfer ring starts at `ring`.
+fn write_endpoint_context(context: &mut [u32], endpoint_type: u32, max_burst: u32, ring: u64) {
+    context[1] = (ENDPOINT_ERROR_COUNT << 1)
+        | (endpoint_type << 3)
+        | ((max_burst & 0xFF) << 8)
+        | ((MAX_PACKET_SIZE as u32) << 16);
+    context[2] = ring as u32 | TRB_CYCLE;
+    context[3] = (ring >> 32) as u32;
+    context[4] = MAX_PACKET_SIZE as u32;
+}
+
+/// Encodes `text` as a USB string descriptor, returning the descriptor length.
+fn string_descriptor(text: &str, descriptor: &mut [u8; STRING_DESCRIPTOR_SIZE]) -> u8 {
+    let mut length = 2;
+    for unit in text.encode_utf16() {
+        if length + 2 > STRING_DESCRIPTOR_SIZE {
+            break;
+        }
+        descriptor[length..length + 2].copy_from_slice(&unit.to_le_bytes());
+        length += 2;
+    }
+    descriptor[0] = length as u8;
+    descriptor[1] = 3;
+    length as u8
+}
+
+/// Encodes string descriptor zero, listing US English as the only language.
+fn language_descriptor(descriptor: &mut [u8; STRING_DESCRIPTOR_SIZE]) -> u8 {
+    descriptor[..4].copy_from_slice(&[4, 3, 0x09, 0x04]);
+    4
+}
+
+fn address_of<T>(value: &T) -> u64 {
+    value as *const T as u64
+}
+
+fn read_register(address: usize) -> u32 {
+    // SAFETY: The platform provided the address of the host controller registers.
+    unsafe { (address as *const u32).read_volatile() }
+}
+
+fn write_register(address: usize, value: u32) {
+    // SAFETY: The platform provided the address of the host controller registers.
+    unsafe { (address as *mut u32).write_volatile(value) }
+}
+
+fn write_register_64(address: usize, value: u64) {
+    write_register(address, value as u32);
+    write_register(address + 4, (value >> 32) as u32);
+}
+
+/// Polls the register until the masked value matches, returning false on timeout.
+fn poll_register(address: usize, mask: u32, value: u32) -> bool {
+    for _ in 0..ENABLE_POLL_LIMIT {
+        if read_register(address) & mask == value {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    false
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::boxed::Box;
+
+    /// Fake host controller register block, with the debug capability at 0x40.
+    #[repr(C, align(64))]
+    struct Registers([u32; 64]);
+
+    const DBC_OFFSET: usize = 0x40;
+
+    fn fake_controller() -> (Box<Registers>, Box<XhciDebugCapability>) {
+        let mut registers = Box::new(Registers([0; 64]));
+        // xECP points at a legacy support capability at 0x30, followed by the DbC.
+        registers.0[HCCPARAMS1_OFFSET / 4] = (0x30 / 4) << 16;
+        registers.0[0x30 / 4] = (4 << 8) | 1;
+        registers.0[DBC_OFFSET / 4] = CAPABILITY_ID_DEBUG;
+        let base = registers.0.as_ptr() as usize;
+        (registers, Box::new(XhciDebugCapability::new(base)))
+    }
+
+    fn register(registers: &Registers, offset: usize) -> u32 {
+        // SAFETY: The offset is within the fake register block.
+        unsafe { ptr::read_volatile(&registers.0[(DBC_OFFSET + offset) / 4]) }
+    }
+
+    fn set_register(registers: &mut Registers, offset: usize, value: u32) {
+        // SAFETY: The offset is within the fake register block.
+        unsafe { ptr::write_volatile(&mut registers.0[(DBC_OFFSET + offset) / 4], value) }
+    }
+
+    fn post_event(dbc: &XhciDebugCapability, index: usize, endpoint: u32, trb: u64, status: u32) {
+        let mut state = dbc.state.lock();
+        let control = (TRB_TYPE_TRANSFER_EVENT << TRB_TYPE_SHIFT) | (endpoint << 16) | TRB_CYCLE;
+        write_trb(&mut state.memory.event_ring.0[index], trb, status, control);
+    }
+
+    #[test]
+    fn test_init_programs_debug_capability() {
+        let (registers, dbc) = fake_controller();
+        assert_eq!(dbc.find_debug_capability(), Some(registers.0.as_ptr() as usize + DBC_OFFSET));
+        assert_eq!(dbc.init(), Ok(()));
+
+        let state = dbc.state.lock();
+        assert_eq!(register(&registers, DCERSTSZ_OFFSET), 1);
+        assert_eq!(register(&registers, DCCP_OFFSET) as u64, address_of(&state.memory.context.0) as u32 as u64);
+        assert_eq!(register(&registers, DCDDI1_OFFSET), 0x1D6B_0001);
+        assert_eq!(register(&registers, DCDDI2_OFFSET), 0x0010_0010);
+        assert_eq!(register(&registers, DCCTRL_OFFSET), DCCTRL_ENABLE | DCCTRL_LINK_STATUS_EVENT_ENABLE);
+        assert_eq!(state.memory.segment_table.0, [address_of(&state.memory.event_ring.0), EVENT_RING_SIZE as u64]);
+
+        let context = &state.memory.context.0;
+        assert_eq!(context[8], 4 | (14 << 8) | (32 << 16) | (10 << 24));
+        assert_eq!(context[OUT_CONTEXT_DWORD + 1], (3 << 1) | (2 << 3) | (1024 << 16));
+        assert_eq!(context[IN_CONTEXT_DWORD + 1], (3 << 1) | (6 << 3) | (1024 << 16));
+        assert_eq!(context[OUT_CONTEXT_DWORD + 2], address_of(&state.memory.out_ring.0) as u32 | 1);
+    }
+
+    #[test]
+    fn test_init_without_debug_capability() {
+        let mut registers = Box::new(Registers([0; 64]));
+        registers.0[HCCPARAMS1_OFFSET / 4] = (0x30 / 4) << 16;
+        registers.0[0x30 / 4] = 1;
+        let dbc = XhciDebugCapability::new(registers.0.as_ptr() as usize);
+        assert_eq!(dbc.init(), Err(EfiError::Unsupported));
+    }
+
+    #[test]
+    fn test_send_and_receive_transfers() {
+        let (mut registers, dbc) = fake_controller();
+        dbc.init().unwrap();
+
+        // Nothing moves until a debug host configures the device.
+        assert_eq!(dbc.send(b"hello"), Err(EfiError::NotReady));
+        set_register(&mut registers, DCCTRL_OFFSET, DCCTRL_ENABLE | DCCTRL_RUN);
+
+        assert_eq!(dbc.send(b"hello"), Ok(5));
+        {
+            let state = dbc.state.lock();
+            let trb = state.memory.out_ring.0[0];
+            assert_eq!(trb.parameter, address_of(&state.memory.out_buffer.0));
+            assert_eq!(trb.status, 5);
+            assert_eq!(trb.control, (TRB_TYPE_NORMAL << TRB_TYPE_SHIFT) | TRB_INTERRUPT_ON_COMPLETION | TRB_CYCLE);
+            assert_eq!(&state.memory.out_buffer.0[..5], b"hello");
+        }
+        assert_eq!(register(&registers, DCDB_OFFSET), DOORBELL_OUT);
+
+        // The OUT buffer is busy until the transfer completes.
+        assert_eq!(dbc.send(b"world"), Ok(0));
+        post_event(&dbc, 0, ENDPOINT_ID_OUT, 0, COMPLETION_SUCCESS << 24);
+        assert_eq!(dbc.send(b"world"), Ok(5));
+        {
+            let state = dbc.state.lock();
+            assert_eq!(register(&registers, DCERDP_OFFSET), address_of(&state.memory.event_ring.0[1]) as u32);
+        }
+
+        // The first receive queues an IN transfer.
+        let mut buffer = [0u8; USB_RECEIVE_BUFFER_SIZE];
+        assert_eq!(dbc.receive(&mut buffer), Ok(0));
+        assert_eq!(register(&registers, DCDB_OFFSET), DOORBELL_IN);
+        assert_eq!(dbc.receive(&mut buffer), Ok(0));
+
+        dbc.state.lock().memory.in_buffer.0[..3].copy_from_slice(b"$g#");
+        post_event(&dbc, 1, ENDPOINT_ID_IN, 0, (COMPLETION_SHORT_PACKET << 24) | (MAX_PACKET_SIZE as u32 - 3));
+        assert_eq!(dbc.receive(&mut buffer), Ok(3));
+        assert_eq!(&buffer[..3], b"$g#");
+        assert!(dbc.state.lock().in_pending);
+    }
+
+    #[test]
+    fn test_halted_endpoints_recover() {
+        const COMPLETION_STALL: u32 = 6;
+        let (mut registers, dbc) = fake_controller();
+        dbc.init().unwrap();
+        set_register(&mut registers, DCCTRL_OFFSET, DCCTRL_ENABLE | DCCTRL_RUN);
+
+        let mut buffer = [0u8; USB_RECEIVE_BUFFER_SIZE];
+        assert_eq!(dbc.send(b"hello"), Ok(5));
+        assert_eq!(dbc.receive(&mut buffer), Ok(0));
+        let (out_trb, in_trb) = {
+            let state = dbc.state.lock();
+            (address_of(&state.memory.out_ring.0[0]), address_of(&state.memory.in_ring.0[0]))
+        };
+
+        // Both transfers stall, and the controller halts both endpoints.
+        post_event(&dbc, 0, ENDPOINT_ID_OUT, out_trb, COMPLETION_STALL << 24);
+        post_event(&dbc, 1, ENDPOINT_ID_IN, in_trb, COMPLETION_STALL << 24);
+        set_register(&mut registers, DCCTRL_OFFSET, DCCTRL_ENABLE | DCCTRL_RUN | DCCTRL_HALT_OUT | DCCTRL_HALT_IN);
+        set_register(&mut registers, DCDB_OFFSET, u32::MAX);
+
+        // Output is dropped and no transfers are queued while the endpoints are halted.
+        assert_eq!(dbc.send(b"world"), Err(EfiError::NotReady));
+        assert_eq!(dbc.receive(&mut buffer), Ok(0));
+        assert_eq!(register(&registers, DCDB_OFFSET), u32::MAX);
+        {
+            let state = dbc.state.lock();
+            let noop = Trb { parameter: 0, status: 0, control: (TRB_TYPE_NOOP << TRB_TYPE_SHIFT) | TRB_CYCLE };
+            assert_eq!(state.memory.out_ring.0[0], noop);
+            assert_eq!(state.memory.in_ring.0[0], noop);
+            assert!(!state.out_pending);
+            assert!(!state.in_pending);
+        }
+
+        // Once the debug host clears the halts, transfers are queued after the no-ops.
+        set_register(&mut registers, DCCTRL_OFFSET, DCCTRL_ENABLE | DCCTRL_RUN);
+        assert_eq!(dbc.send(b"world"), Ok(5));
+        assert_eq!(register(&registers, DCDB_OFFSET), DOORBELL_OUT);
+        assert_eq!(dbc.receive(&mut buffer), Ok(0));
+        assert_eq!(register(&registers, DCDB_OFFSET), DOORBELL_IN);
+        let state = dbc.state.lock();
+        assert_eq!(state.memory.out_ring.0[1].status, 5);
+        assert_eq!(state.memory.in_ring.0[1].status, MAX_PACKET_SIZE as u32);
+        assert_eq!(state.out_ring.index, 2);
+        assert_eq!(state.in_ring.index, 2);
+    }
+
+    #[test]
+    fn test_cancel_transfer_ignores_foreign_trbs() {
+        let mut ring = [Trb::ZERO; TRANSFER_RING_SIZE];
+        let mut position = RingPosition::START;
+        enqueue_transfer(&mut ring, &mut position, 0x1000, 8, 0);
+        let queued = ring;
+        let (start, link) = (address_of(&ring), address_of(&ring[TRANSFER_RING_SIZE - 1]));
+
+        cancel_transfer(&mut ring, start - 1);
+        cancel_transfer(&mut ring, link);
+        cancel_transfer(&mut ring, u64::MAX);
+        assert_eq!(ring, queued);
+    }
+
+    #[test]
+    fn test_transfer_ring_wraps_through_link_trb() {
+        let mut ring = [Trb::ZERO; TRANSFER_RING_SIZE];
+        let mut position = RingPosition::START;
+        for _ in 0..TRANSFER_RING_SIZE - 1 {
+            enqueue_transfer(&mut ring, &mut position, 0x1000, 8, 0);
+        }
+
+        assert_eq!(position.index, 0);
+        assert!(!position.cycle);
+        let link = ring[TRANSFER_RING_SIZE - 1];
+        assert_eq!(link.parameter, address_of(&ring));
+        assert_eq!(link.control, (TRB_TYPE_LINK << TRB_TYPE_SHIFT) | TRB_TOGGLE_CYCLE | TRB_CYCLE);
+
+        enqueue_transfer(&mut ring, &mut position, 0x2000, 8, 0);
+        assert_eq!(ring[0].parameter, 0x2000);
+        assert_eq!(ring[0].control & TRB_CYCLE, 0);
+    }
+
+    #[test]
+    fn test_string_descriptor_encoding() {
+        let mut descriptor = [0u8; STRING_DESCRIPTOR_SIZE];
+        assert_eq!(string_descriptor("Hi", &mut descriptor), 6);
+        assert_eq!(&descriptor[..6], &[6, 3, b'H', 0, b'i', 0]);
+
+        let mut descriptor = [0u8; STRING_DESCRIPTOR_SIZE];
+        let long = "x".repeat(STRING_DESCRIPTOR_SIZE);
+        assert_eq!(string_descriptor(&long, &mut descriptor), 64);
+    }
+}
@@ -20,6 +20,8 @@ use patina::{error::EfiError, pi::protocols::cpu_arch::EfiSystemContext};
 
 mod exception_handling;
 
+pub use exception_handling::unhandled_exception;
+
 // The aarch64 module contains all exception handlers and architecture specific code, of little testing value.
 #[coverage(off)]
 #[cfg(any(target_arch = "aarch64", test))]
@@ -111,40 +113,22 @@ pub type ExceptionType = usize;
 
 /// This macro pretty prints registers in groups of four per line.
 /// The expected input is a list of name, value pairs.
+///
+/// Lines are built with [`patina::log::raw::RawBuffer`] rather than `core::fmt`, so that dumping the registers of a
+/// fault cannot itself fault.
 #[macro_export]
 macro_rules! log_registers {
     ( $( $name:expr, $value:expr ),+ $(,)? ) => {
-        let registers = [$(($name, $value),)+];
+        let registers: &[(&str, u64)] = &[$(($name, $value as u64),)+];
         for chunk in registers.chunks(4) {
-            match chunk {
-                [c1, c2, c3, c4] => {
-                    log::error!(
-                        "{:>4}:  {:#018X}   {:>4}:  {:#018X}   {:>4}:  {:#018X}   {:>4}:  {:#018X}",
-                        c1.0, c1.1, c2.0, c2.1, c3.0, c3.1, c4.0, c4.1
-                    );
-                },
-                [c1, c2, c3] => {
-                    log::error!(
-                        "{:>4}:  {:#018X}   {:>4}:  {:#018X}   {:>4}:  {:#018X}",
-                        c1.0, c1.1, c2.0, c2.1, c3.0, c3.1
-                    );
-                },
-                [c1, c2] => {
-                    log::error!(
-                        "{:>4}:  {:#018X}   {:>4}:  {:#018X}",
-                        c1.0, c1.1, c2.0, c2.1,
-                    );
-                },
-                [c1] => {
-                    log::error!(
-                        "{:>4}:  {:#018X}",
-                        c1.0, c1.1
-                    );
-                },
-                _ => {
-                    log::error!("");
+            let mut line = patina::log::raw::RawBuffer::<128>::new();
+            for (index, (name, value)) in chunk.iter().enumerate() {
+                if index > 0 {
+                    line.str("   ");
                 }
+                line.str_right(name, 4).str(":  0x").hex(*value, 16);
             }
+            log::error!("{}", line.as_str());
         }
     };
 }
@@ -182,6 +166,17 @@ pub trait InterruptManager {
     fn unregister_exception_handler(&self, exception_type: ExceptionType) -> Result<(), EfiError> {
         exception_handling::unregister_exception_handler(exception_type)
     }
+
+    /// Registers a callback for exceptions of any type which has no registered
+    /// callback, in place of dumping the exception and panicking.
+    fn register_unhandled_exception_handler(&self, handler: HandlerType) -> Result<(), EfiError> {
+        exception_handling::register_unhandled_exception_handler(handler)
+    }
+
+    /// Removes the registered callback for unhandled exceptions.
+    fn unregister_unhandled_exception_handler(&self) -> Result<(), EfiError> {
+        exception_handling::unregister_unhandled_exception_handler()
+    }
 }
 
 /// Type for storing the handler for a given exception.
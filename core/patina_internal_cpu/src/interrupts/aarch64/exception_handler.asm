@@ -101,6 +101,7 @@ sp_el0_end:
 
   .global exception_handlers_start
   .global exception_handler
+  .global exception_handler_return
 
   .set GP_CONTEXT_SIZE,    (32 *  8)
   .set FP_CONTEXT_SIZE,    (32 * 16)
@@ -158,7 +159,11 @@ exception_handlers_start:
   # the exception was taken, we may have to switch back and forth between
   # SP_EL0 and SP_ELx to record the correct value for SP in the context struct.
   #
-  .ifnc   \sp, SPx
+  # Exceptions taken from a lower EL are expanded with \sp set to Lower, in
+  # which case the interrupted code ran on the stack pointer of that EL, so
+  # that is recorded instead.
+  #
+  .ifc    \sp, SP0
   msr     SPsel, xzr
   .endif
 
@@ -183,13 +188,32 @@ exception_handlers_start:
   stp      x28, x29, [sp, #0xe0]
   add      x28, sp, #(GP_CONTEXT_SIZE + FP_CONTEXT_SIZE + SYS_CONTEXT_SIZE)
 
-  .ifnc    \sp, SPx
+  .ifc     \sp, SP0
   msr      SPsel, #1
   mov      x7, sp
   msr      SPsel, xzr
+  .else
+  .ifc     \sp, Lower
+  # The lower EL ran on SP_EL1 only if EL2 took the exception from EL1h,
+  # which SPSR.M[0] records. Otherwise it ran on SP_EL0.
+  mrs      x7, CurrentEL
+  cmp      x7, #0x8
+  b.eq     1f
+  mrs      x7, spsr_el1
+  b        2f
+1:
+  mrs      x7, spsr_el2
+2:
+  tbz      x7, #0, 3f
+  mrs      x7, sp_el1
+  b        4f
+3:
+  mrs      x7, sp_el0
+4:
   .else
   mov      x7, x28
   .endif
+  .endif
 
   stp      x30,  x7, [sp, #0xf0]
 
@@ -244,38 +268,38 @@ SErrorSPx:
 #
   .org ARM_VECTOR_LOW_A64_SYNC
 SynchronousExceptionA64:
-  ExceptionEntry  EXCEPT_AARCH64_SYNCHRONOUS_EXCEPTIONS
+  ExceptionEntry  EXCEPT_AARCH64_SYNCHRONOUS_EXCEPTIONS, Lower
 
   .org ARM_VECTOR_LOW_A64_IRQ
 IrqA64:
-  ExceptionEntry  EXCEPT_AARCH64_IRQ
+  ExceptionEntry  EXCEPT_AARCH64_IRQ, Lower
 
   .org ARM_VECTOR_LOW_A64_FIQ
 FiqA64:
-  ExceptionEntry  EXCEPT_AARCH64_FIQ
+  ExceptionEntry  EXCEPT_AARCH64_FIQ, Lower
 
   .org ARM_VECTOR_LOW_A64_SERR
 SErrorA64:
-  ExceptionEntry  EXCEPT_AARCH64_SERROR
+  ExceptionEntry  EXCEPT_AARCH64_SERROR, Lower
 
 #
 # Lower EL using AArch32 : 0x600 - 0x780
 #
   .org ARM_VECTOR_LOW_A32_SYNC
 SynchronousExceptionA32:
-  ExceptionEntry  EXCEPT_AARCH64_SYNCHRONOUS_EXCEPTIONS
+  ExceptionEntry  EXCEPT_AARCH64_SYNCHRONOUS_EXCEPTIONS, Lower
 
   .org ARM_VECTOR_LOW_A32_IRQ
 IrqA32:
-  ExceptionEntry  EXCEPT_AARCH64_IRQ
+  ExceptionEntry  EXCEPT_AARCH64_IRQ, Lower
 
   .org ARM_VECTOR_LOW_A32_FIQ
 FiqA32:
-  ExceptionEntry  EXCEPT_AARCH64_FIQ
+  ExceptionEntry  EXCEPT_AARCH64_FIQ, Lower
 
   .org ARM_VECTOR_LOW_A32_SERR
 SErrorA32:
-  ExceptionEntry  EXCEPT_AARCH64_SERROR
+  ExceptionEntry  EXCEPT_AARCH64_SERROR, Lower
 
   .org 0x800;
   .section .text.exception_handlers_start,"ax";
@@ -283,10 +307,22 @@ SErrorA32:
 
 common_exception_routine:
 
+  # The exception registers are banked by EL, so read those of the EL the
+  # firmware runs at, EL1 or EL2.
+  mrs      x2, CurrentEL
+  cmp      x2, #0x8
+  b.ne     read_el1_registers
   mrs      x2, elr_el2
   mrs      x3, spsr_el2
   mrs      x5, esr_el2
   mrs      x6, far_el2
+  b        save_registers
+read_el1_registers:
+  mrs      x2, elr_el1
+  mrs      x3, spsr_el1
+  mrs      x5, esr_el1
+  mrs      x6, far_el1
+save_registers:
   mrs      x4, fpsr
 
   # Save the SYS regs
@@ -319,6 +355,10 @@ common_exception_routine:
   # Call into rust routine.
   bl       exception_handler
 
+  # The stack walker recognizes this return address to continue the walk from
+  # the saved context, which SP still points to.
+exception_handler_return:
+
   # Pop as many GP regs as we can before entering the critical section below
   ldp      x2,  x3,  [sp, #0x10]
   ldp      x4,  x5,  [sp, #0x20]
@@ -366,8 +406,16 @@ common_exception_routine:
   msr   daifset, #3
   isb
 
+  mrs      x28, CurrentEL
+  cmp      x28, #0x8
+  b.ne     write_el1_registers
   msr      elr_el2, x29
   msr      spsr_el2, x30
+  b        restore_registers
+write_el1_registers:
+  msr      elr_el1, x29
+  msr      spsr_el1, x30
+restore_registers:
 
   # pop remaining GP regs and return from exception.
   ldr      x30, [sp, #0xf0 - 0xe0]
@@ -33,6 +33,7 @@ cfg_if::cfg_if! {
         unsafe extern "C" {
             static exception_handlers_start: u64;
             static sp_el0_end: u64;
+            static exception_handler_return: u8;
         }
     }
 }
@@ -121,9 +122,12 @@ fn initialize_exception() -> Result<(), EfiError> {
         sp_el0_reg &= !0x0F;
         write_sysreg!(reg sp_el0, sp_el0_reg);
 
-        let mut hcr = read_sysreg!(hcr_el2);
-        hcr |= 1 << 27; // Enable TGE
-        write_sysreg!(reg hcr_el2, hcr);
+        // HCR_EL2 only exists for firmware running at EL2.
+        if get_current_el() == 0x08 {
+            let mut hcr = read_sysreg!(hcr_el2);
+            hcr |= 1 << 27; // Enable TGE
+            write_sysreg!(reg hcr_el2, hcr);
+        }
     }
 
     // Program VBar
@@ -139,6 +143,17 @@ fn initialize_exception() -> Result<(), EfiError> {
         };
     }
 
+    // Let stack traces taken in exception handlers continue into the interrupted code. The entry routine passes SP as
+    // the context pointer, so the context sits at SP when the handler returns.
+    #[cfg(all(not(test), target_arch = "aarch64"))]
+    StackTrace::register_trap_frame(patina_stacktrace::TrapFrameLayout {
+        return_address: &raw const exception_handler_return as u64,
+        context_offset: 0,
+        pc_offset: core::mem::offset_of!(ExceptionContextAArch64, elr) as u64,
+        sp_offset: core::mem::offset_of!(ExceptionContextAArch64, sp) as u64,
+        fp_offset: core::mem::offset_of!(ExceptionContextAArch64, fp) as u64,
+    });
+
     let fiq = get_fiq_state();
 
     disable_interrupts();
@@ -163,6 +178,8 @@ extern "efiapi" fn synchronous_exception_handler(_exception_type: isize, context
 
     log::error!("");
     log::error!("EXCEPTION: Synchronous Exception");
+    // SPSR.M[3:2] holds the EL the exception was taken from.
+    log::error!("Taken from EL{}", (aarch64_context.spsr >> 2) & 0x3);
 
     log::error!("");
 
@@ -210,19 +227,22 @@ fn dump_pte(far: u64) {
     // Needed because attributes on expressions are not stable.
     // https://github.com/rust-lang/rust/issues/15701
     #[allow(clippy::needless_late_init)]
-    let ttbr0_el2;
+    let ttbr0;
     cfg_if::cfg_if! {
         if #[cfg(all(not(test), target_arch = "aarch64"))]  {
-            ttbr0_el2 = read_sysreg!(ttbr0_el2);
+            ttbr0 = match get_current_el() {
+                0x08 => read_sysreg!(ttbr0_el2),
+                _ => read_sysreg!(ttbr0_el1),
+            };
         } else {
-            ttbr0_el2 = 0u64;
+            ttbr0 = 0u64;
         }
     }
 
     // SAFETY: TTBR0 must be valid as it is the current page table base.
     if let Ok(pt) = unsafe {
         patina_paging::aarch64::AArch64PageTable::from_existing(
-            ttbr0_el2,
+            ttbr0,
             patina_paging::page_allocator::PageAllocatorStub,
             PagingType::Paging4Level,
         )
@@ -33,6 +33,9 @@ static EXCEPTION_HANDLERS: [RwLock<HandlerType>; NUM_EXCEPTION_TYPES] = {
     [INIT; NUM_EXCEPTION_TYPES]
 };
 
+// The handler invoked for exceptions which have no handler registered for their type.
+static UNHANDLED_EXCEPTION_HANDLER: RwLock<HandlerType> = RwLock::new(HandlerType::None);
+
 /// Registers a handler callback for the provided exception type.
 ///
 /// # Errors
@@ -79,6 +82,70 @@ pub(crate) fn unregister_exception_handler(exception_type: ExceptionType) -> Res
     Ok(())
 }
 
+/// Registers a handler callback for exceptions of any type which has no registered handler.
+///
+/// # Errors
+///
+/// Returns [`InvalidParameter`](EfiError::InvalidParameter) if the handler is None.
+/// Returns [`AlreadyStarted`](EfiError::AlreadyStarted) if a callback has already been registered.
+///
+pub(crate) fn register_unhandled_exception_handler(handler: HandlerType) -> Result<(), EfiError> {
+    if handler.is_none() {
+        return Err(EfiError::InvalidParameter);
+    }
+
+    let mut entry = UNHANDLED_EXCEPTION_HANDLER.write();
+    if !(*entry).is_none() {
+        return Err(EfiError::AlreadyStarted);
+    }
+
+    *entry = handler;
+    Ok(())
+}
+
+/// Removes the handler callback for unhandled exceptions.
+///
+/// # Errors
+///
+/// Returns [`InvalidParameter`](EfiError::InvalidParameter) if no callback currently exists.
+///
+pub(crate) fn unregister_unhandled_exception_handler() -> Result<(), EfiError> {
+    let mut entry = UNHANDLED_EXCEPTION_HANDLER.write();
+    if (*entry).is_none() {
+        return Err(EfiError::InvalidParameter);
+    }
+
+    *entry = HandlerType::None;
+    Ok(())
+}
+
+/// Dumps the exception context and stack trace and panics. This is the handling of
+/// an exception for which no handler is registered, and may be used by handlers
+/// which decline to handle an exception.
+pub fn unhandled_exception(exception_type: ExceptionType, context: &mut ExceptionContext) -> ! {
+    log::error!("Unhandled Exception! {exception_type:#X}");
+    log::error!("");
+    context.dump_system_context_registers();
+    log::error!("");
+    context.dump_stack_trace();
+    panic!("Unhandled Exception! {exception_type:#X}");
+}
+
+/// Invokes the handler for the exception. Returns false if the handler is None.
+fn invoke_handler(handler: &HandlerType, exception_type: ExceptionType, context: &mut ExceptionContext) -> bool {
+    match handler {
+        HandlerType::UefiRoutine(handler) => {
+            let efi_system_context = context.create_efi_system_context();
+            handler(exception_type as EfiExceptionType, efi_system_context);
+        }
+        HandlerType::Handler(handler) => {
+            handler.handle_interrupt(exception_type, context);
+        }
+        HandlerType::None => return false,
+    }
+    true
+}
+
 // This function does actually have coverage but no_mangle functions confuse the coverage tool.
 #[coverage(off)]
 /// The architecture agnostic entry of the exception handler stack.
@@ -88,30 +155,22 @@ pub(crate) fn unregister_exception_handler(exception_type: ExceptionType) -> Res
 ///
 /// # Panics
 ///
-/// Panics if no callback has been registered for a given exception or the handler
-/// read lock cannot be acquired.
+/// Panics if no callback has been registered for a given exception nor for unhandled
+/// exceptions, or the handler read lock cannot be acquired.
 ///
 #[unsafe(no_mangle)]
 extern "efiapi" fn exception_handler(exception_type: usize, context: &mut ExceptionContext) {
     let handler_lock =
         EXCEPTION_HANDLERS[exception_type].try_read().expect("Failed to read lock in exception handler!");
 
-    match *handler_lock {
-        HandlerType::UefiRoutine(handler) => {
-            let efi_system_context = context.create_efi_system_context();
-            handler(exception_type as EfiExceptionType, efi_system_context);
-        }
-        HandlerType::Handler(handler) => {
-            handler.handle_interrupt(exception_type, context);
-        }
-        HandlerType::None => {
-            log::error!("Unhandled Exception! {exception_type:#X}");
-            log::error!("");
-            context.dump_system_context_registers();
-            log::error!("");
-            context.dump_stack_trace();
-            panic!("Unhandled Exception! {exception_type:#X}");
-        }
+    if invoke_handler(&handler_lock, exception_type, context) {
+        return;
+    }
+
+    let unhandled_lock =
+        UNHANDLED_EXCEPTION_HANDLER.try_read().expect("Failed to read lock in unhandled exception handler!");
+    if !invoke_handler(&unhandled_lock, exception_type, context) {
+        unhandled_exception(exception_type, context);
     }
 }
 
@@ -142,6 +201,17 @@ mod tests {
         }
     }
 
+    struct UnhandledTestHandler {
+        pub invoked: AtomicBool,
+    }
+
+    impl crate::interrupts::InterruptHandler for UnhandledTestHandler {
+        fn handle_interrupt(&'static self, exception_type: usize, _context: &mut ExceptionContext) {
+            assert!(exception_type == 2);
+            self.invoked.store(true, core::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
     extern "efiapi" fn test_callback(exception_type: EfiExceptionType, _context: EfiSystemContext) {
         assert!(exception_type == CALLBACK_EXCEPTION as EfiExceptionType);
         // SAFETY: This is a test only static mutable variable.
@@ -203,6 +273,33 @@ mod tests {
         interrupt_manager.unregister_exception_handler(HANDLER_EXCEPTION).expect("Failed to unregister handler!");
     }
 
+    #[test]
+    #[serial(exception_handlers)]
+    fn test_unhandled_exception_handler() {
+        const UNHANDLED_EXCEPTION: usize = 2;
+
+        let mut context = ExceptionContext(crate::interrupts::stub::ExceptionContextStub {});
+        let handler = Box::leak(Box::new(UnhandledTestHandler { invoked: AtomicBool::new(false) }));
+
+        register_unhandled_exception_handler(HandlerType::None).expect_err("Allowed none handler registration!");
+        register_unhandled_exception_handler(HandlerType::Handler(handler))
+            .expect("Failed to register unhandled exception handler!");
+        register_unhandled_exception_handler(HandlerType::Handler(handler)).expect_err("Allowed double register!");
+
+        exception_handler(UNHANDLED_EXCEPTION, &mut context);
+        assert!(handler.invoked.load(core::sync::atomic::Ordering::SeqCst));
+
+        unregister_unhandled_exception_handler().expect("Failed to unregister handler!");
+        unregister_unhandled_exception_handler().expect_err("Allowed double unregister!");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unhandled Exception! 0x2")]
+    fn test_unhandled_exception_panics() {
+        let mut context = ExceptionContext(crate::interrupts::stub::ExceptionContextStub {});
+        unhandled_exception(2, &mut context);
+    }
+
     #[test]
     fn test_invalid_input() {
         register_exception_handler(NUM_EXCEPTION_TYPES, HandlerType::UefiRoutine(test_callback))
@@ -7,20 +7,32 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
-use core::arch::global_asm;
+use core::{arch::global_asm, mem::offset_of};
 use lazy_static::lazy_static;
 use patina::base::SIZE_4GB;
+use patina_stacktrace::{StackTrace, TrapFrameLayout};
 use x86_64::{
     VirtAddr,
     structures::idt::{InterruptDescriptorTable, InterruptStackFrame},
 };
 
+use crate::interrupts::ExceptionContextX64;
+
 global_asm!(include_str!("interrupt_handler.asm"));
 // Use efiapi for the consistent calling convention.
 unsafe extern "efiapi" {
     fn AsmGetVectorAddress(index: usize) -> u64;
 }
 
+unsafe extern "C" {
+    /// The return address of the call from `common_interrupt_entry` into `exception_handler`.
+    static AsmExceptionHandlerReturn: u8;
+}
+
+/// The distance from RSP at `AsmExceptionHandlerReturn` to the saved context, which is the parameter space the entry
+/// reserves for the call.
+const CONTEXT_OFFSET: u64 = 4 * 8 + 8;
+
 // The x86_64 crate requires the IDT to be static, which makes sense as the IDT
 // can live beyond any code lifetime.
 lazy_static! {
@@ -85,6 +97,15 @@ pub fn initialize_idt() {
     #[cfg(target_os = "uefi")]
     IDT.load();
     log::info!("Loaded IDT");
+
+    // Let stack traces taken in exception handlers continue into the interrupted code.
+    StackTrace::register_trap_frame(TrapFrameLayout {
+        return_address: &raw const AsmExceptionHandlerReturn as u64,
+        context_offset: CONTEXT_OFFSET,
+        pc_offset: offset_of!(ExceptionContextX64, rip) as u64,
+        sp_offset: offset_of!(ExceptionContextX64, rsp) as u64,
+        fp_offset: offset_of!(ExceptionContextX64, rbp) as u64,
+    });
 }
 
 /// Handler for double faults.
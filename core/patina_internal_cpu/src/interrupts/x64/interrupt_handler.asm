@@ -12,6 +12,7 @@
 .global exception_handler
 .global common_interrupt_entry
 .global AsmIdtVectorBegin
+.global AsmExceptionHandlerReturn
 
 .align 8
 # These need to be of a fixed length so that they can be indexed into. For this
@@ -207,6 +208,10 @@ stack_normalized:
 
     sub     rsp, 4 * 8 + 8 # max parameter space + 8 for 16 bytes alignment.
     call    exception_handler
+
+    # The stack walker recognizes this return address to continue the walk from
+    # the saved context, which is 4 * 8 + 8 bytes above RSP.
+AsmExceptionHandlerReturn:
     add     rsp, 4 * 8 + 8
 
     #
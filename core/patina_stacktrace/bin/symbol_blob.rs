@@ -0,0 +1,157 @@
+//! Executable for generating symbol blobs from built `.efi` files.
+//!
+//! Function names and start RVAs are collected from the COFF symbol table and
+//! the export table of the image, then written as a symbol blob. See
+//! `patina_stacktrace::symbols` for the blob format.
+//!
+//! Symbols in a separate PDB are not read. An image without a COFF symbol table
+//! or exports, such as a stripped release build, is rejected rather than given an
+//! empty blob.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![feature(coverage_attribute)]
+
+use clap::Parser;
+use goblin::pe::{PE, symbol::IMAGE_SYM_DTYPE_FUNCTION};
+use patina_stacktrace::symbols::{build_symbol_blob, symbol_blob_footer};
+use std::{
+    fs,
+    io::{self},
+    path::PathBuf,
+};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Path for the input `.efi` file.
+    input_path: PathBuf,
+    /// Optional path for the output file. Defaults to the input path with a `.sym` extension, or with `--append`,
+    /// the input path itself.
+    #[arg(short, long)]
+    output_path: Option<PathBuf>,
+    /// Flag to write the image followed by the symbol blob and its footer instead of the blob alone.
+    #[arg(short, long, default_value_t = false)]
+    append: bool,
+}
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+
+    let image = fs::read(&args.input_path)?;
+    let symbols = collect_symbols(&image).inspect_err(|e| {
+        eprintln!("Error reading symbols from {}: {e}", args.input_path.display());
+    })?;
+
+    let count = symbols.len();
+    let blob = build_symbol_blob(symbols);
+
+    let output_path = match (args.output_path, args.append) {
+        (Some(path), _) => path,
+        (None, true) => args.input_path.clone(),
+        (None, false) => args.input_path.with_extension("sym"),
+    };
+
+    let output = if args.append { [image.as_slice(), &blob, &symbol_blob_footer(&blob)].concat() } else { blob };
+    fs::write(&output_path, output)?;
+
+    println!("Wrote {count} symbols to {}", output_path.display());
+    Ok(())
+}
+
+/// Collects the start RVA and name of every function defined in the image.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the image cannot be parsed or defines no function symbols.
+fn collect_symbols(image: &[u8]) -> io::Result<Vec<(u32, String)>> {
+    let invalid_data = |e: goblin::error::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+    let pe = PE::parse(image).map_err(invalid_data)?;
+    let mut symbols = Vec::new();
+
+    let coff_header = &pe.header.coff_header;
+    if let (Some(table), Some(strings)) =
+        (coff_header.symbols(image).map_err(invalid_data)?, coff_header.strings(image).map_err(invalid_data)?)
+    {
+        for (_, inline_name, symbol) in table.iter() {
+            // Section numbers are 1-based, with non-positive values for absolute and debug symbols.
+            if symbol.derived_type() != IMAGE_SYM_DTYPE_FUNCTION || symbol.section_number <= 0 {
+                continue;
+            }
+            let Some(section) = pe.sections.get(symbol.section_number as usize - 1) else {
+                continue;
+            };
+            let Some(name) = inline_name.or_else(|| symbol.name(&strings).ok()) else {
+                continue;
+            };
+            symbols.push((section.virtual_address + symbol.value, name.to_string()));
+        }
+    }
+
+    for export in pe.exports.iter().filter(|export| export.reexport.is_none()) {
+        if let Some(name) = export.name {
+            symbols.push((export.rva as u32, name.to_string()));
+        }
+    }
+
+    if symbols.is_empty() {
+        let pdb = pe
+            .debug_data
+            .and_then(|debug_data| debug_data.codeview_pdb70_debug_info)
+            .map(|codeview| String::from_utf8_lossy(codeview.filename).trim_end_matches('\0').to_string());
+        let message = match pdb {
+            Some(pdb) => format!("no function symbols found; the image is stripped and its symbols are in {pdb}"),
+            None => "no function symbols found; the image has no COFF symbol table or exports".to_string(),
+        };
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+
+    Ok(symbols)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    /// Builds a PE32+ image with no sections, symbol table, or data directories.
+    fn stripped_image() -> Vec<u8> {
+        const PE_OFFSET: usize = 0x40;
+        const OPTIONAL_HEADER: usize = PE_OFFSET + 24;
+
+        let mut image = vec![0u8; 0x200];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3c..0x40].copy_from_slice(&(PE_OFFSET as u32).to_le_bytes());
+        image[PE_OFFSET..PE_OFFSET + 4].copy_from_slice(b"PE\0\0");
+        // COFF header: x64 machine, no sections, and a PE32+ optional header.
+        image[PE_OFFSET + 4..PE_OFFSET + 6].copy_from_slice(&0x8664u16.to_le_bytes());
+        image[PE_OFFSET + 20..PE_OFFSET + 22].copy_from_slice(&240u16.to_le_bytes());
+        image[PE_OFFSET + 22..PE_OFFSET + 24].copy_from_slice(&0x22u16.to_le_bytes());
+
+        let mut field = |offset: usize, value: &[u8]| {
+            image[OPTIONAL_HEADER + offset..OPTIONAL_HEADER + offset + value.len()].copy_from_slice(value)
+        };
+        field(0, &0x20bu16.to_le_bytes()); // Magic
+        field(32, &0x1000u32.to_le_bytes()); // SectionAlignment
+        field(36, &0x200u32.to_le_bytes()); // FileAlignment
+        field(56, &0x1000u32.to_le_bytes()); // SizeOfImage
+        field(60, &0x200u32.to_le_bytes()); // SizeOfHeaders
+        field(68, &10u16.to_le_bytes()); // Subsystem: EFI application
+        field(108, &16u32.to_le_bytes()); // NumberOfRvaAndSizes
+        image
+    }
+
+    #[test]
+    fn test_stripped_image_is_rejected() {
+        let err = collect_symbols(&stripped_image()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("no function symbols found"), "{err}");
+    }
+
+    #[test]
+    fn test_invalid_image_is_rejected() {
+        let err = collect_symbols(b"not an image").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,67 @@
+//! Resolution of stack frames against DWARF debug information, as produced by
+//! GCC and Clang builds.
+//!
+//! The debug file may be an ELF image with embedded DWARF, or a separate
+//! `.debug` file split from it. Images converted from ELF by the EDK II
+//! toolchains are linked at their RVAs, so a frame's RVA is used directly as
+//! the ELF address. PE images carrying DWARF are offset by their image base.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use std::path::Path;
+
+use crate::{InlinedFrame, Symbol};
+
+/// Look up the symbol for `rva` in the DWARF debug file at `path`. Coverage is
+/// off because this function depends on external debug files.
+#[coverage(off)]
+pub(crate) fn lookup(path: &Path, rva: u32) -> Result<Symbol, String> {
+    let loader = addr2line::Loader::new(path).map_err(|e| format!("Failed to load DWARF from {:?}: {}", path, e))?;
+    let address = loader.relative_address_base() + u64::from(rva);
+
+    // The frames are ordered innermost first, ending with the function the
+    // code physically lies in. Report that one, with the rest as inlined.
+    let mut frames =
+        loader.find_frames(address).map_err(|e| format!("Failed to find frames for RVA 0x{:X}: {}", rva, e))?;
+    let mut inlined = Vec::new();
+    let mut outermost = None;
+    while let Ok(Some(frame)) = frames.next() {
+        if let Some(inner) = outermost.replace(frame) {
+            inlined.push(inlined_frame(inner));
+        }
+    }
+
+    let symbol = loader.find_symbol_info(address);
+    if outermost.is_none() && symbol.is_none() {
+        return Err(format!("No frames found for RVA 0x{:X}", rva));
+    }
+
+    let location = outermost.as_ref().and_then(|frame| frame.location.as_ref());
+    let function = outermost
+        .as_ref()
+        .and_then(|frame| frame.function.as_ref())
+        .and_then(|function| function.demangle().ok())
+        .map(|function| function.into_owned())
+        .or_else(|| symbol.as_ref().map(|symbol| addr2line::demangle_auto(symbol.name().into(), None).into_owned()));
+
+    Ok(Symbol {
+        file: location.and_then(|location| location.file).map(str::to_string),
+        line: location.and_then(|location| location.line),
+        function,
+        offset: symbol.map_or(0, |symbol| address.saturating_sub(symbol.address()) as u32),
+        inlined,
+    })
+}
+
+/// Convert a frame of a function inlined at the call site.
+fn inlined_frame<R: addr2line::gimli::Reader>(frame: addr2line::Frame<'_, R>) -> InlinedFrame {
+    InlinedFrame {
+        function: frame.function.as_ref().and_then(|function| function.demangle().ok()).map(|name| name.into_owned()),
+        file: frame.location.as_ref().and_then(|location| location.file).map(str::to_string),
+        line: frame.location.as_ref().and_then(|location| location.line),
+    }
+}
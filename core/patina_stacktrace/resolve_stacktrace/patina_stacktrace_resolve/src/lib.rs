@@ -0,0 +1,61 @@
+#![feature(coverage_attribute)]
+//! Offline symbol resolution for stack traces dumped by `patina_stacktrace`.
+//!
+//! The stack trace library prints each frame as `module+<rva>` because debug
+//! information is not available to the firmware. This crate turns that text
+//! back into source locations and function names:
+//!
+//! 1. [`StackTrace::parse`] extracts the frames and any build ID lines from
+//!    the trace text. Log prefixes, header lines, and unrelated lines are
+//!    skipped. [`StackTrace::parse_addresses`] instead accepts a bare list of
+//!    call sites, mapping absolute addresses to modules with a [`LoadMap`],
+//!    and [`StackTrace::scan`] finds every trace in a long log.
+//! 2. A [`Resolver`] looks up each frame in the debug files returned by its
+//!    [`SymbolProvider`]s, optionally backed by a [`PdbCache`]. Debug files
+//!    can come from a local directory ([`SymbolDirectory`]) or be downloaded
+//!    from a symbol server ([`SymbolServer`]). Both PDBs and
+//!    DWARF, embedded in an ELF image or split into a `.debug` file, are
+//!    supported, chosen per module from the debug file found.
+//! 3. The result is a [`ResolvedFrame`] per frame, leaving presentation to
+//!    the caller. A [`SourceLocator`] can read the source lines around each
+//!    resolved location from a local checkout.
+//!
+//! ```no_run
+//! use patina_stacktrace_resolve::{Resolver, StackTrace, SymbolDirectory};
+//!
+//! let log = std::fs::read_to_string("boot.log").unwrap();
+//! let trace = StackTrace::parse(log.lines());
+//! let resolver = Resolver::new().with_provider(SymbolDirectory::new("Build/DEBUG"));
+//!
+//! for frame in resolver.resolve(&trace) {
+//!     match &frame.symbol {
+//!         Ok(symbol) => println!("{} {}", frame.frame.frame_number, symbol.function.as_deref().unwrap_or("<unknown>")),
+//!         Err(error) => println!("{} {}", frame.frame.frame_number, error),
+//!     }
+//! }
+//! ```
+//!
+//! The `resolve_stacktrace` tool is an interactive front-end over this crate.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+mod dwarf;
+mod parse;
+mod resolve;
+mod source;
+mod symsrv;
+
+pub use parse::{LoadMap, LoadedModule, LoggedTrace, StackFrame, StackTrace};
+pub use resolve::{InlinedFrame, PdbCache, ResolvedFrame, Resolver, Symbol, SymbolDirectory, SymbolProvider};
+pub use source::{SourceLocator, SourceSnippet};
+pub use symsrv::SymbolServer;
+
+/// Format a PDB signature the way symbol stores key it: the GUID as 32 upper
+/// case hex digits followed by the age in hex without leading zeros.
+fn pdb_signature(guid: &str, age: u32) -> String {
+    format!("{}{:X}", guid.replace('-', "").to_uppercase(), age)
+}
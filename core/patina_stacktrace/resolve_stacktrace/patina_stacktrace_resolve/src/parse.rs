@@ -0,0 +1,686 @@
+//! Parsing of the stack trace text dumped by `patina_stacktrace`.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use std::collections::HashMap;
+
+use crate::pdb_signature;
+
+/// Marks a trace line that records the PDB signature a module was built with,
+/// e.g. `build-id qemu_q35_dxe_core 3F2504E04F8911D39A0C0305E82C33011`.
+const BUILD_ID_MARKER: &str = "build-id";
+
+/// Marks a line of the module table that follows the frames of a trace,
+/// listing an image's base, size, build ID, and name, e.g.
+/// `module 000000007E8D4000 00020000 3F2504E04F8911D39A0C0305E82C33011 DxeCore`.
+const MODULE_MARKER: &str = "module";
+
+/// The call site module of a frame whose image was not found, in which case
+/// the call site is the absolute address.
+const NO_MODULE: &str = "<no module>";
+
+/// Starts each stack trace in a log, as printed by `StackTrace::dump_with`.
+const DUMP_HEADER: &str = "Dumping stack trace with";
+
+/// Ends each stack trace in a log.
+const DUMP_FOOTER: &str = "Finished dumping stack trace";
+
+/// Mark the lines that report why the system crashed: unhandled exceptions,
+/// panics, and the debugger's panic breakpoint.
+const CRASH_MARKERS: &[&str] = &["Unhandled Exception!", "panicked at", "PANIC"];
+
+/// A single frame of a stack trace, as printed by `patina_stacktrace`.
+///
+/// Frames parsed from an address list by [`StackTrace::parse_addresses`] have
+/// no child stack pointer, and a return address only if it was given as an
+/// absolute address. Those columns are empty when unknown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// The frame number column.
+    pub frame_number: String,
+    /// The child stack pointer column.
+    pub child_stack_pointer: String,
+    /// The return address column.
+    pub return_address: String,
+    /// The module the call site lies in. Empty for an absolute address outside
+    /// every module of the load map.
+    pub module_name: String,
+    /// The call site, relative to the start of the module.
+    pub start_rva: u32,
+}
+
+/// The frames and build IDs parsed from a stack trace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackTrace {
+    /// The frames of the trace, in the order they were printed.
+    pub frames: Vec<StackFrame>,
+    /// The PDB signature each module was built with, keyed by module name.
+    pub build_ids: HashMap<String, String>,
+    /// The modules listed in the module table of the trace.
+    pub load_map: LoadMap,
+}
+
+impl StackTrace {
+    /// Parse the stack trace text into its frames and build IDs.
+    ///
+    /// Lines may carry a log prefix. Header lines and lines that are not part
+    /// of the trace are skipped, so a whole log can be passed in.
+    ///
+    /// Frames whose image was not found on the target are mapped to a module
+    /// by their absolute address if the module table printed with the trace
+    /// lists an image containing it.
+    pub fn parse<I>(lines: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut trace = Self::default();
+        let mut modules = vec![];
+        let mut unmapped = vec![];
+        for line in lines {
+            let line = line.as_ref().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Skip header line
+            if line.contains("Return Address") {
+                continue;
+            }
+
+            if let Some((module_name, signature)) = parse_build_id(line) {
+                trace.build_ids.insert(module_name, signature);
+            } else if let Some((module, signature)) = parse_module(line) {
+                if let Some(signature) = signature {
+                    trace.build_ids.insert(module.name.clone(), signature);
+                }
+                modules.push(module);
+            } else if let Some((frame, address)) = create_unmapped_stack_frame(line) {
+                unmapped.push((trace.frames.len(), address));
+                trace.frames.push(frame);
+            } else if let Some(frame) = create_stack_frame(line) {
+                trace.frames.push(frame);
+            }
+        }
+
+        trace.load_map = LoadMap::new(modules);
+        for (index, address) in unmapped {
+            if let Some((module, offset)) = trace.load_map.lookup(address)
+                && let Ok(offset) = u32::try_from(offset)
+            {
+                trace.frames[index].module_name = module.name.clone();
+                trace.frames[index].start_rva = offset;
+            }
+        }
+        trace
+    }
+
+    /// Parse a bare list of call sites, one per line, such as the raw return
+    /// addresses logged on a watchdog reset.
+    ///
+    /// The last word of each line is the call site: either `module+<rva>` as
+    /// printed in a stack trace, or an absolute address that is mapped to a
+    /// module with `load_map`. Lines may carry a log prefix, and lines whose
+    /// last word is neither are skipped. Build ID lines are recognized as in
+    /// [`StackTrace::parse`].
+    pub fn parse_addresses<I>(lines: I, load_map: &LoadMap) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut trace = Self::default();
+        for line in lines {
+            let line = line.as_ref().trim();
+            if let Some((module_name, signature)) = parse_build_id(line) {
+                trace.build_ids.insert(module_name, signature);
+            } else if let Some(word) = line.split_whitespace().last()
+                && let Some(frame) = create_address_frame(trace.frames.len(), word, load_map)
+            {
+                trace.frames.push(frame);
+            }
+        }
+        trace
+    }
+}
+
+/// A stack trace found in a log by [`StackTrace::scan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedTrace {
+    /// The line of the log the trace starts on, counting from 1.
+    pub line: usize,
+    /// The last line before the trace that reports a crash, such as an
+    /// unhandled exception or a panic, if any.
+    pub reason: Option<String>,
+    /// Whether the end of the trace was found. A trace cut short, for example
+    /// by a reset, may be missing frames.
+    pub complete: bool,
+    /// The frames and build IDs of the trace.
+    pub trace: StackTrace,
+}
+
+impl StackTrace {
+    /// Find every stack trace in a log, such as a serial capture of several
+    /// boots, and parse each one.
+    ///
+    /// Traces are found by the header and footer lines `patina_stacktrace`
+    /// prints around them, and are returned in the order they appear. A trace
+    /// without a footer ends at the next trace or crash. Lines between traces
+    /// are only examined for the crash that caused the next trace.
+    pub fn scan<I>(lines: I) -> Vec<LoggedTrace>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut traces = vec![];
+        let mut reason = None;
+        let mut current: Option<(LoggedTrace, Vec<String>)> = None;
+        for (index, line) in lines.into_iter().enumerate() {
+            let line = line.as_ref();
+            if line.contains(DUMP_HEADER) {
+                traces.extend(current.take().map(finish_trace));
+                let trace =
+                    LoggedTrace { line: index + 1, reason: reason.take(), complete: false, trace: Self::default() };
+                current = Some((trace, vec![]));
+            } else if CRASH_MARKERS.iter().any(|marker| line.contains(marker)) {
+                // A trace never reports a crash, so one that does was cut short.
+                traces.extend(current.take().map(finish_trace));
+                reason = Some(line.trim().to_string());
+            } else if let Some((trace, trace_lines)) = current.as_mut() {
+                if line.contains(DUMP_FOOTER) {
+                    trace.complete = true;
+                    traces.extend(current.take().map(finish_trace));
+                } else {
+                    trace_lines.push(line.to_string());
+                }
+            }
+        }
+        traces.extend(current.map(finish_trace));
+        traces
+    }
+}
+
+/// Parse the lines collected for a trace found by [`StackTrace::scan`].
+fn finish_trace((mut trace, lines): (LoggedTrace, Vec<String>)) -> LoggedTrace {
+    trace.trace = StackTrace::parse(lines);
+    trace
+}
+
+/// A module loaded at a known address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedModule {
+    /// The module name, as used for its debug files.
+    pub name: String,
+    /// The address the module was loaded at.
+    pub base: u64,
+    /// The size of the loaded module, if known.
+    pub size: Option<u64>,
+}
+
+/// The load addresses of modules, used to map absolute addresses to modules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadMap {
+    /// The loaded modules, sorted by base address.
+    modules: Vec<LoadedModule>,
+}
+
+impl LoadMap {
+    /// Creates a load map from a list of loaded modules.
+    pub fn new(mut modules: Vec<LoadedModule>) -> Self {
+        modules.sort_by_key(|module| module.base);
+        Self { modules }
+    }
+
+    /// Parse a load map with one `<module> <base> [<size>]` line per module.
+    ///
+    /// Addresses and sizes are hexadecimal, with or without a `0x` prefix. The
+    /// module may be given as a path to the image, such as `Build/DxeCore.efi`;
+    /// its directory and extension are dropped. Empty lines, `#` comments, and
+    /// malformed lines are skipped.
+    pub fn parse<I>(lines: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let modules = lines
+            .into_iter()
+            .filter_map(|line| {
+                let line = line.as_ref().trim();
+                if line.starts_with('#') {
+                    return None;
+                }
+                let mut parts = line.split_whitespace();
+                let name = module_name_from_path(parts.next()?);
+                let base = parse_hex(parts.next()?)?;
+                let size = match parts.next() {
+                    Some(size) => Some(parse_hex(size)?),
+                    None => None,
+                };
+                Some(LoadedModule { name: name.to_string(), base, size })
+            })
+            .collect();
+        Self::new(modules)
+    }
+
+    /// Returns the module containing `address` and the offset of the address
+    /// within it. Without a size, a module is taken to extend to the next
+    /// module's base.
+    pub fn lookup(&self, address: u64) -> Option<(&LoadedModule, u64)> {
+        let index = self.modules.partition_point(|module| module.base <= address).checked_sub(1)?;
+        let module = &self.modules[index];
+        let offset = address - module.base;
+        match module.size {
+            Some(size) if offset >= size => None,
+            _ => Some((module, offset)),
+        }
+    }
+}
+
+/// Returns the module name for an image path, without its directory or
+/// extension.
+fn module_name_from_path(path: &str) -> &str {
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem)
+}
+
+/// Parse a hexadecimal number with an optional `0x` prefix.
+fn parse_hex(text: &str) -> Option<u64> {
+    let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// Convert a call site from an address list into a `StackFrame` numbered
+/// `index`.
+fn create_address_frame(index: usize, call_site: &str, load_map: &LoadMap) -> Option<StackFrame> {
+    let frame = |return_address: String, module_name: &str, start_rva| StackFrame {
+        frame_number: index.to_string(),
+        child_stack_pointer: String::new(),
+        return_address,
+        module_name: module_name.to_string(),
+        start_rva,
+    };
+
+    if let Some((module_name, rva)) = call_site.rsplit_once('+') {
+        let rva = u32::try_from(parse_hex(rva)?).ok()?;
+        return (!module_name.is_empty()).then(|| frame(String::new(), module_name, rva));
+    }
+
+    // Require a prefix or a full-width address, so that short words that
+    // happen to be hexadecimal, such as `add`, are not taken as addresses.
+    if !call_site.starts_with("0x") && !call_site.starts_with("0X") && call_site.len() < 8 {
+        return None;
+    }
+    let address = parse_hex(call_site)?;
+    let return_address = format!("{:016X}", address);
+    match load_map.lookup(address) {
+        Some((module, offset)) => Some(frame(return_address, &module.name, u32::try_from(offset).ok()?)),
+        None => Some(frame(return_address, "", 0)),
+    }
+}
+
+/// Convert a single textual stack trace line into a structured `StackFrame`.
+fn create_stack_frame(line: &str) -> Option<StackFrame> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 4 {
+        return None;
+    }
+
+    let idx = parts.len();
+
+    // Parse each column backwards
+    let (module_name, start_rva_str) = parts[idx - 1].rsplit_once('+')?;
+    let start_rva_str = start_rva_str.strip_prefix("0x").unwrap_or(start_rva_str);
+    let start_rva = u32::from_str_radix(start_rva_str, 16).ok()?;
+    let return_address = parts[idx - 2].to_string();
+    let child_stack_pointer = parts[idx - 3].to_string();
+    let frame_number = parts[idx - 4].to_string();
+
+    Some(StackFrame {
+        frame_number,
+        child_stack_pointer,
+        return_address,
+        module_name: module_name.to_string(),
+        start_rva,
+    })
+}
+
+/// Convert a trace line for a frame outside every image found on the target,
+/// whose call site is `<no module>+<address>`, into a `StackFrame` without a
+/// module and the absolute address of its call site.
+fn create_unmapped_stack_frame(line: &str) -> Option<(StackFrame, u64)> {
+    let (columns, call_site) = line.rsplit_once(NO_MODULE)?;
+    let address = parse_hex(call_site.strip_prefix('+')?)?;
+    let parts: Vec<&str> = columns.split_whitespace().collect();
+    let [.., frame_number, child_stack_pointer, return_address] = parts[..] else {
+        return None;
+    };
+
+    let frame = StackFrame {
+        frame_number: frame_number.to_string(),
+        child_stack_pointer: child_stack_pointer.to_string(),
+        return_address: return_address.to_string(),
+        module_name: String::new(),
+        start_rva: 0,
+    };
+    Some((frame, address))
+}
+
+/// Parse a build ID line into the module name and its normalized PDB signature.
+/// Any log prefix before the marker is ignored.
+fn parse_build_id(line: &str) -> Option<(String, String)> {
+    let mut parts = line.split_whitespace().skip_while(|part| *part != BUILD_ID_MARKER).skip(1);
+    let module_name = parts.next()?;
+    let signature = normalize_signature(parts.next()?)?;
+    Some((module_name.to_string(), signature))
+}
+
+/// Parse a module table line into the module and its normalized PDB
+/// signature, if known. Any log prefix before the marker is ignored.
+fn parse_module(line: &str) -> Option<(LoadedModule, Option<String>)> {
+    let mut parts = line.split_whitespace().skip_while(|part| *part != MODULE_MARKER).skip(1);
+    let base = parse_hex(parts.next()?)?;
+    let size = parse_hex(parts.next()?)?;
+    let signature = match parts.next()? {
+        "-" => None,
+        signature => Some(normalize_signature(signature)?),
+    };
+    let name = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((LoadedModule { name: name.to_string(), base, size: Some(size) }, signature))
+}
+
+/// Normalize a printed build ID, with or without GUID separators, into the
+/// PDB signature symbol stores key it by.
+fn normalize_signature(build_id: &str) -> Option<String> {
+    let signature = build_id.replace('-', "").to_uppercase();
+
+    // 32 hex digits of GUID followed by 1 to 8 hex digits of age.
+    if !(33..=40).contains(&signature.len()) || !signature.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let age = u32::from_str_radix(&signature[32..], 16).ok()?;
+
+    Some(pdb_signature(&signature[..32], age))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_stack_frame_valid() {
+        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3";
+        let frame = create_stack_frame(line).expect("Should parse valid frame");
+
+        assert_eq!(frame.frame_number, "00");
+        assert_eq!(frame.child_stack_pointer, "000000cd7bbfe830");
+        assert_eq!(frame.return_address, "00007ff6ddd0b4ae");
+        assert_eq!(frame.module_name, "DxeCore");
+        assert_eq!(frame.start_rva, 0x45a3);
+    }
+
+    #[test]
+    fn test_create_stack_frame_hex_variations() {
+        let line = "05 000000cd7bbfe900 00007ff6ddd0ffff TestModule+0xABCD";
+        let frame = create_stack_frame(line).expect("Should parse uppercase hex");
+        assert_eq!(frame.start_rva, 0xABCD);
+
+        let line2 = "05 000000cd7bbfe900 00007ff6ddd0ffff TestModule+0xabcd";
+        let frame2 = create_stack_frame(line2).expect("Should parse lowercase hex");
+        assert_eq!(frame2.start_rva, 0xabcd);
+    }
+
+    #[test]
+    fn test_create_stack_frame_invalid_too_few_parts() {
+        let line = "00 000000cd7bbfe830 DxeCore+0x45a3";
+        assert!(create_stack_frame(line).is_none());
+    }
+
+    #[test]
+    fn test_create_stack_frame_invalid_no_plus() {
+        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore0x45a3";
+        assert!(create_stack_frame(line).is_none());
+    }
+
+    #[test]
+    fn test_create_stack_frame_invalid_hex() {
+        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0xGGGG";
+        assert!(create_stack_frame(line).is_none());
+    }
+
+    #[test]
+    fn test_create_stack_frame_empty() {
+        assert!(create_stack_frame("").is_none());
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let lines = [
+            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3",
+            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000",
+            "02 000000cd7bbfea00 00007ff6ddd67890 AnotherMod+0x2000",
+        ];
+
+        let frames = StackTrace::parse(lines).frames;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].frame_number, "00");
+        assert_eq!(frames[1].frame_number, "01");
+        assert_eq!(frames[2].frame_number, "02");
+    }
+
+    #[test]
+    fn test_parse_with_header() {
+        let lines = [
+            "# Child-SP          Return Address       Call Site",
+            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3",
+            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000",
+        ];
+
+        let frames = StackTrace::parse(lines).frames;
+        // Header should be filtered out
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].frame_number, "00");
+    }
+
+    #[test]
+    fn test_parse_with_empty_lines() {
+        let lines = [
+            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3",
+            "",
+            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000",
+            "   ",
+        ];
+
+        let frames = StackTrace::parse(lines).frames;
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_filters_invalid() {
+        let lines = [
+            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3",
+            "invalid line",
+            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000",
+        ];
+
+        let frames = StackTrace::parse(lines).frames;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].frame_number, "00");
+        assert_eq!(frames[1].frame_number, "01");
+    }
+
+    #[test]
+    fn test_parse_empty_input() {
+        let trace = StackTrace::parse(Vec::<String>::new());
+        assert!(trace.frames.is_empty());
+        assert!(trace.build_ids.is_empty());
+    }
+
+    #[test]
+    fn test_parse_build_id() {
+        let (module_name, signature) =
+            parse_build_id("WARN - build-id DxeCore 3f2504e0-4f89-11d3-9a0c-0305e82c3301-0002").unwrap();
+        assert_eq!(module_name, "DxeCore");
+        assert_eq!(signature, "3F2504E04F8911D39A0C0305E82C33012");
+
+        let (_, signature) = parse_build_id("build-id DxeCore 3F2504E04F8911D39A0C0305E82C33011").unwrap();
+        assert_eq!(signature, "3F2504E04F8911D39A0C0305E82C33011");
+    }
+
+    #[test]
+    fn test_parse_build_id_invalid() {
+        assert!(parse_build_id("build-id DxeCore").is_none());
+        assert!(parse_build_id("build-id DxeCore 3F2504E04F8911D39A0C0305E82C3301").is_none());
+        assert!(parse_build_id("build-id DxeCore 3F2504E04F8911D39A0C0305E82C3301G1").is_none());
+        assert!(parse_build_id("00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3").is_none());
+    }
+
+    #[test]
+    fn test_parse_with_build_id() {
+        let lines = [
+            "build-id DxeCore 3F2504E04F8911D39A0C0305E82C33011",
+            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3",
+        ];
+
+        let trace = StackTrace::parse(lines);
+        assert_eq!(trace.build_ids.get("DxeCore").map(String::as_str), Some("3F2504E04F8911D39A0C0305E82C33011"));
+        assert_eq!(trace.frames.len(), 1);
+        assert_eq!(trace.frames[0].module_name, "DxeCore");
+    }
+
+    #[test]
+    fn test_parse_module_table() {
+        let lines = [
+            "WARN -      0 000000007FF0FF00      000000007E8D5123       DxeCore+1123",
+            "WARN -      1 000000007FF0FF80      000000007EA01200       <no module>+7EA01200",
+            "WARN -      2 000000007FF0FFC0      00000000DEAD0000       <no module>+DEAD0000",
+            "WARN - Modules:",
+            "WARN -   module 000000007E8D4000 00020000 3F2504E04F8911D39A0C0305E82C33011 DxeCore",
+            "WARN -   module 000000007EA00000 00004000 - RuntimeDxe",
+            "WARN - Loading module RuntimeDxe",
+        ];
+
+        let trace = StackTrace::parse(lines);
+        assert_eq!(trace.build_ids.get("DxeCore").map(String::as_str), Some("3F2504E04F8911D39A0C0305E82C33011"));
+        assert!(!trace.build_ids.contains_key("RuntimeDxe"));
+        assert_eq!(
+            trace.load_map.lookup(0x7EA00010).map(|(module, offset)| (module.name.as_str(), offset)),
+            Some(("RuntimeDxe", 0x10))
+        );
+
+        assert_eq!(trace.frames.len(), 3);
+        assert_eq!(trace.frames[0].module_name, "DxeCore");
+        assert_eq!(trace.frames[1].frame_number, "1");
+        assert_eq!(trace.frames[1].return_address, "000000007EA01200");
+        assert_eq!(trace.frames[1].module_name, "RuntimeDxe");
+        assert_eq!(trace.frames[1].start_rva, 0x1200);
+        // An address outside every listed module stays without a module.
+        assert!(trace.frames[2].module_name.is_empty());
+    }
+
+    #[test]
+    fn test_parse_load_map() {
+        let load_map = LoadMap::parse([
+            "# module base size",
+            "Build/X64/DxeCore.efi 0x7E8D4000 0x20000",
+            "RuntimeDxe 7E800000",
+            "malformed",
+        ]);
+        assert_eq!(
+            load_map.lookup(0x7E8D4A10).map(|(module, offset)| (module.name.as_str(), offset)),
+            Some(("DxeCore", 0xA10))
+        );
+        assert_eq!(load_map.lookup(0x7E8F4000), None);
+        assert_eq!(
+            load_map.lookup(0x7E8D3FFF).map(|(module, offset)| (module.name.as_str(), offset)),
+            Some(("RuntimeDxe", 0xD3FFF))
+        );
+        assert_eq!(load_map.lookup(0x1000), None);
+    }
+
+    #[test]
+    fn test_parse_addresses() {
+        let load_map =
+            LoadMap::new(vec![LoadedModule { name: "DxeCore".to_string(), base: 0x7E8D4000, size: Some(0x20000) }]);
+        let lines = [
+            "build-id DxeCore 3F2504E04F8911D39A0C0305E82C33011",
+            "INFO - Return address: 0x7E8D4A10",
+            "TestMod+0x1000",
+            "00000000DEAD0000",
+            "watchdog reset",
+            "watchdog fired at cafe",
+        ];
+
+        let trace = StackTrace::parse_addresses(lines, &load_map);
+        assert_eq!(trace.build_ids.len(), 1);
+        assert_eq!(trace.frames.len(), 3);
+        assert_eq!(trace.frames[0].frame_number, "0");
+        assert_eq!(trace.frames[0].module_name, "DxeCore");
+        assert_eq!(trace.frames[0].start_rva, 0xA10);
+        assert_eq!(trace.frames[0].return_address, "000000007E8D4A10");
+        assert_eq!(trace.frames[1].module_name, "TestMod");
+        assert_eq!(trace.frames[1].start_rva, 0x1000);
+        assert!(trace.frames[1].return_address.is_empty());
+        assert!(trace.frames[2].module_name.is_empty());
+        assert_eq!(trace.frames[2].frame_number, "2");
+    }
+
+    #[test]
+    fn test_stack_frame_debug() {
+        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3";
+        let frame = create_stack_frame(line).unwrap();
+
+        let debug_str = format!("{:?}", frame);
+        assert!(debug_str.contains("StackFrame"));
+    }
+
+    #[test]
+    fn test_scan() {
+        let log = [
+            "INFO - Loading driver",
+            "ERROR - Unhandled Exception! 0xE",
+            "ERROR - RIP: 0x000000007E8D4A10",
+            "WARN - Dumping stack trace with PC: 7E8D4A10, SP: 7E2796C0",
+            "WARN -       # Child-SP              Return Address         Call Site",
+            "WARN - build-id DxeCore 3F2504E04F8911D39A0C0305E82C33011",
+            "WARN -       0 000001007E2796C0      000001007E27BBDC       DxeCore+185DC",
+            "WARN -       1 000001007E2796F0      000001007E34FB58       DxeCore+1BDC",
+            "WARN - Finished dumping stack trace",
+            "INFO - Rebooting",
+            "WARN - Dumping stack trace with PC: 7E8D4A10, SP: 7E2796C0",
+            "WARN -       0 000001007E2796C0      000001007E27BBDC       DxeCore+185DC",
+            "ERROR - panicked at src/lib.rs:10:5",
+            "WARN - Dumping stack trace with PC: 7E8D4A10, SP: 7E2796C0",
+            "WARN -       0 000001007E2796C0      000001007E27BBDC       RuntimeDxe+100",
+        ];
+
+        let traces = StackTrace::scan(log);
+        assert_eq!(traces.len(), 3);
+
+        assert_eq!(traces[0].line, 4);
+        assert_eq!(traces[0].reason.as_deref(), Some("ERROR - Unhandled Exception! 0xE"));
+        assert!(traces[0].complete);
+        assert_eq!(traces[0].trace.frames.len(), 2);
+        assert_eq!(traces[0].trace.build_ids.len(), 1);
+
+        // A trace cut short ends at the next crash, which belongs to the next
+        // trace.
+        assert_eq!(traces[1].line, 11);
+        assert_eq!(traces[1].reason, None);
+        assert!(!traces[1].complete);
+        assert_eq!(traces[1].trace.frames.len(), 1);
+
+        assert_eq!(traces[2].line, 14);
+        assert_eq!(traces[2].reason.as_deref(), Some("ERROR - panicked at src/lib.rs:10:5"));
+        assert!(!traces[2].complete);
+        assert_eq!(traces[2].trace.frames[0].module_name, "RuntimeDxe");
+
+        assert!(StackTrace::scan(["INFO - Booted"]).is_empty());
+    }
+}
@@ -0,0 +1,389 @@
+//! Resolution of parsed stack frames against debug files.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use pdb_addr2line::pdb;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{StackFrame, StackTrace, dwarf, pdb_signature};
+
+/// Signature at the start of a PDB, which is an MSF 7.0 container.
+const PDB_MAGIC: &[u8] = b"Microsoft C/C++ MSF 7.00\r\n\x1aDS\0\0\0";
+
+/// Extensions of the debug files searched for in a [`SymbolDirectory`], in
+/// order of preference. EDK II GCC builds emit the ELF image as `<module>.dll`.
+const DEBUG_FILE_EXTENSIONS: &[&str] = &["pdb", "debug", "elf", "dll"];
+
+/// A source of debug files to resolve stack frames against.
+pub trait SymbolProvider {
+    /// Returns the path of the debug file for `module_name`, or `None` if this
+    /// provider has none for it. `signature` is the build ID recorded for the
+    /// module in the trace, if any.
+    fn symbol_path(&self, module_name: &str, signature: Option<&str>) -> Option<PathBuf>;
+
+    /// Returns the path of the debug file for `module_name` as
+    /// [`symbol_path`](Self::symbol_path) does, also reporting why a debug
+    /// file this provider should have could not be obtained, such as a failed
+    /// download. The resolver reports the error as a warning and moves on to
+    /// the next provider.
+    fn fetch(&self, module_name: &str, signature: Option<&str>) -> Result<Option<PathBuf>, String> {
+        Ok(self.symbol_path(module_name, signature))
+    }
+}
+
+/// Provides debug files from a flat directory, such as a build output
+/// directory. A module's PDB (`<module>.pdb`) is preferred, followed by a split
+/// DWARF file (`<module>.debug`) and an ELF image with embedded DWARF
+/// (`<module>.elf` or `<module>.dll`).
+#[derive(Debug, Clone)]
+pub struct SymbolDirectory {
+    path: PathBuf,
+}
+
+impl SymbolDirectory {
+    /// Creates a provider over the given directory.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SymbolProvider for SymbolDirectory {
+    fn symbol_path(&self, module_name: &str, _signature: Option<&str>) -> Option<PathBuf> {
+        DEBUG_FILE_EXTENSIONS
+            .iter()
+            .map(|extension| self.path.join(format!("{module_name}.{extension}")))
+            .find(|path| path.is_file())
+    }
+}
+
+/// A local PDB cache laid out like a symbol store:
+/// `<cache>/<module>.pdb/<signature>/<module>.pdb`.
+///
+/// PDB directories are usually overwritten by the next build. Caching each PDB
+/// under its own signature lets later traces from the same build resolve
+/// after the directory has been rebuilt.
+#[derive(Debug, Clone)]
+pub struct PdbCache {
+    path: PathBuf,
+}
+
+impl PdbCache {
+    /// Creates a cache rooted at the given directory.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Location of a module's PDB in the cache.
+    pub(crate) fn cached_pdb_path(&self, module_name: &str, signature: &str) -> PathBuf {
+        let file_name = format!("{module_name}.pdb");
+        self.path.join(&file_name).join(signature).join(file_name)
+    }
+
+    /// Copy a PDB into the cache under its signature, unless it is already
+    /// present.
+    #[coverage(off)]
+    pub fn store(&self, module_name: &str, signature: &str, pdb_path: &Path) -> io::Result<()> {
+        let cached_path = self.cached_pdb_path(module_name, signature);
+        if cached_path.is_file() {
+            return Ok(());
+        }
+
+        cached_path.parent().map_or(Ok(()), fs::create_dir_all)?;
+        fs::copy(pdb_path, &cached_path).map(|_| ())
+    }
+}
+
+impl SymbolProvider for PdbCache {
+    fn symbol_path(&self, module_name: &str, signature: Option<&str>) -> Option<PathBuf> {
+        let pdb_path = self.cached_pdb_path(module_name, signature?);
+        pdb_path.is_file().then_some(pdb_path)
+    }
+}
+
+/// The symbol information a stack frame resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The source file of the call site, if known.
+    pub file: Option<String>,
+    /// The source line of the call site, if known.
+    pub line: Option<u32>,
+    /// The demangled name of the function containing the call site, if known.
+    pub function: Option<String>,
+    /// The offset of the call site from the start of the function.
+    pub offset: u32,
+    /// The functions inlined into `function` at the call site, innermost
+    /// first. The location of each is where the code lies within it, so the
+    /// innermost entry holds the location that actually executed.
+    pub inlined: Vec<InlinedFrame>,
+}
+
+/// A function inlined at a call site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlinedFrame {
+    /// The demangled name of the inlined function, if known.
+    pub function: Option<String>,
+    /// The source file within the inlined function, if known.
+    pub file: Option<String>,
+    /// The source line within the inlined function, if known.
+    pub line: Option<u32>,
+}
+
+/// A stack frame together with the result of resolving it.
+#[derive(Debug, Clone)]
+pub struct ResolvedFrame {
+    /// The frame as parsed from the trace.
+    pub frame: StackFrame,
+    /// The resolved symbol, or a description of why resolution failed.
+    pub symbol: Result<Symbol, String>,
+    /// Problems that did not prevent resolution but may make it inaccurate,
+    /// such as a PDB whose signature differs from the build ID in the trace.
+    pub warnings: Vec<String>,
+    /// Whether the frame was resolved from a PDB whose signature differs from
+    /// the build ID in the trace, so that the symbol is likely wrong.
+    pub stale_pdb: bool,
+}
+
+/// Resolves stack frames against the debug files of a set of providers.
+///
+/// The format is chosen per module from the debug file found: PDBs are read
+/// directly, anything else is read as DWARF.
+///
+/// When a [`PdbCache`] is configured, a module with a build ID is resolved
+/// from its cached PDB first, and every PDB read from a provider is added to
+/// the cache.
+#[derive(Default)]
+pub struct Resolver {
+    providers: Vec<Box<dyn SymbolProvider>>,
+    cache: Option<PdbCache>,
+}
+
+impl Resolver {
+    /// Creates a resolver without any symbol providers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a symbol provider. Providers are searched in the order they were
+    /// added.
+    pub fn with_provider(mut self, provider: impl SymbolProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Sets the PDB cache.
+    pub fn with_cache(mut self, cache: PdbCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Resolve every frame of the trace.
+    pub fn resolve(&self, trace: &StackTrace) -> Vec<ResolvedFrame> {
+        trace
+            .frames
+            .iter()
+            .map(|frame| self.resolve_frame(frame, trace.build_ids.get(&frame.module_name).map(String::as_str)))
+            .collect()
+    }
+
+    /// Resolve a single frame. `expected_signature` is the build ID recorded
+    /// for its module; the signature of the PDB is validated against it before
+    /// resolving, and a PDB with a different signature is flagged as stale
+    /// rather than trusted.
+    pub fn resolve_frame(&self, frame: &StackFrame, expected_signature: Option<&str>) -> ResolvedFrame {
+        let mut warnings = Vec::new();
+        let mut stale_pdb = false;
+        let symbol = self.lookup(frame, expected_signature, &mut warnings, &mut stale_pdb);
+        ResolvedFrame { frame: frame.clone(), symbol, warnings, stale_pdb }
+    }
+
+    /// Look up the debug info for a frame in the first debug file found for
+    /// its module.
+    fn lookup(
+        &self,
+        frame: &StackFrame,
+        expected_signature: Option<&str>,
+        warnings: &mut Vec<String>,
+        stale_pdb: &mut bool,
+    ) -> Result<Symbol, String> {
+        if frame.module_name.is_empty() {
+            return Err(format!("Address {} is outside every module in the load map", frame.return_address));
+        }
+
+        let cached_path =
+            self.cache.as_ref().and_then(|cache| cache.symbol_path(&frame.module_name, expected_signature));
+        let from_cache = cached_path.is_some();
+        let path = cached_path
+            .or_else(|| {
+                self.providers.iter().find_map(|provider| {
+                    provider.fetch(&frame.module_name, expected_signature).unwrap_or_else(|e| {
+                        warnings.push(e);
+                        None
+                    })
+                })
+            })
+            .ok_or_else(|| format!("No debug file found for {}", frame.module_name))?;
+
+        if is_pdb(&path)? {
+            self.lookup_pdb(frame, &path, expected_signature, from_cache, warnings, stale_pdb)
+        } else {
+            dwarf::lookup(&path, frame.start_rva)
+        }
+    }
+
+    /// Look up the debug info for a frame in a PDB. Coverage is off because
+    /// this function depends on external PDB files.
+    #[coverage(off)]
+    fn lookup_pdb(
+        &self,
+        frame: &StackFrame,
+        pdb_path: &Path,
+        expected_signature: Option<&str>,
+        from_cache: bool,
+        warnings: &mut Vec<String>,
+        stale_pdb: &mut bool,
+    ) -> Result<Symbol, String> {
+        let file = File::open(pdb_path).map_err(|_| format!("Failed to open {:?}", pdb_path))?;
+        let mut pdb =
+            pdb::PDB::open(BufReader::new(file)).map_err(|_| format!("Failed to parse PDB {:?}", pdb_path))?;
+
+        let signature = read_pdb_signature(&mut pdb);
+        if let (Some(cache), Some(signature), false) = (&self.cache, &signature, from_cache)
+            && let Err(e) = cache.store(&frame.module_name, signature, pdb_path)
+        {
+            warnings.push(format!("failed to cache {:?}: {}", pdb_path, e));
+        }
+
+        if let Some(expected_signature) = expected_signature
+            && signature.as_deref() != Some(expected_signature)
+        {
+            *stale_pdb = true;
+            warnings.push(format!(
+                "{:?} has signature {} but the trace was built with {}; symbols may be wrong",
+                pdb_path,
+                signature.as_deref().unwrap_or("<unknown>"),
+                expected_signature
+            ));
+        }
+
+        let context_data = pdb_addr2line::ContextPdbData::try_from_pdb(pdb)
+            .map_err(|_| format!("Failed to create context data from PDB {:?}", pdb_path))?;
+        let context =
+            context_data.make_context().map_err(|_| format!("Failed to create context from PDB {:?}", pdb_path))?;
+
+        let Ok(Some(frames)) = context.find_frames(frame.start_rva) else {
+            return Err(format!("Failed to find frames in context for {:?}", frame.start_rva));
+        };
+        // The frames are ordered innermost first, ending with the function the
+        // code physically lies in.
+        let (outer, inlined) =
+            frames.frames.split_last().ok_or_else(|| format!("No frames found for RVA 0x{:X}", frame.start_rva))?;
+
+        Ok(Symbol {
+            file: outer.file.as_deref().map(str::to_string),
+            line: outer.line,
+            function: outer.function.clone(),
+            offset: frame.start_rva - frames.start_rva,
+            inlined: inlined
+                .iter()
+                .map(|inlined| InlinedFrame {
+                    function: inlined.function.clone(),
+                    file: inlined.file.as_deref().map(str::to_string),
+                    line: inlined.line,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Returns `true` if the debug file at `path` is a PDB.
+fn is_pdb(path: &Path) -> Result<bool, String> {
+    let mut magic = [0u8; PDB_MAGIC.len()];
+    let file = File::open(path).map_err(|_| format!("Failed to open {:?}", path))?;
+    // A file too short to hold the signature is not a PDB.
+    match file.take(magic.len() as u64).read_exact(&mut magic) {
+        Ok(()) => Ok(magic == PDB_MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(_) => Err(format!("Failed to read {:?}", path)),
+    }
+}
+
+/// Read the signature of an opened PDB. The DBI age is preferred since that is
+/// the age the linker records in the image's CodeView entry.
+#[coverage(off)]
+fn read_pdb_signature<'s, S: pdb::Source<'s> + 's>(pdb: &mut pdb::PDB<'s, S>) -> Option<String> {
+    let information = pdb.pdb_information().ok()?;
+    let age = pdb.debug_information().ok().and_then(|dbi| dbi.age()).unwrap_or(information.age);
+    Some(pdb_signature(&information.guid.to_string(), age))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::LoadMap;
+
+    #[test]
+    fn test_cached_pdb_path() {
+        let path = PdbCache::new("cache").cached_pdb_path("DxeCore", "3F2504E04F8911D39A0C0305E82C33011");
+        assert_eq!(
+            path,
+            Path::new("cache").join("DxeCore.pdb").join("3F2504E04F8911D39A0C0305E82C33011").join("DxeCore.pdb")
+        );
+    }
+
+    #[test]
+    fn test_cache_requires_signature() {
+        assert!(PdbCache::new("cache").symbol_path("DxeCore", None).is_none());
+    }
+
+    #[test]
+    fn test_resolve_without_pdb() {
+        let trace = StackTrace::parse(["00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3"]);
+        let resolved = Resolver::new().with_provider(SymbolDirectory::new("does-not-exist")).resolve(&trace);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].frame, trace.frames[0]);
+        assert_eq!(resolved[0].symbol, Err("No debug file found for DxeCore".to_string()));
+        assert!(resolved[0].warnings.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_unmapped_address() {
+        let trace = StackTrace::parse_addresses(["0x00000000DEAD0000"], &LoadMap::default());
+        let resolved = Resolver::new().resolve(&trace);
+        assert_eq!(
+            resolved[0].symbol,
+            Err("Address 00000000DEAD0000 is outside every module in the load map".to_string())
+        );
+    }
+
+    #[test]
+    fn test_symbol_directory_prefers_pdb() {
+        let directory = std::env::temp_dir().join(format!("patina_stacktrace_resolve_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let provider = SymbolDirectory::new(&directory);
+        assert_eq!(provider.symbol_path("DxeCore", None), None);
+
+        fs::write(directory.join("DxeCore.dll"), b"\x7fELF").unwrap();
+        assert_eq!(provider.symbol_path("DxeCore", None), Some(directory.join("DxeCore.dll")));
+        assert!(!is_pdb(&directory.join("DxeCore.dll")).unwrap());
+
+        fs::write(directory.join("DxeCore.debug"), b"").unwrap();
+        assert_eq!(provider.symbol_path("DxeCore", None), Some(directory.join("DxeCore.debug")));
+        assert!(!is_pdb(&directory.join("DxeCore.debug")).unwrap());
+
+        fs::write(directory.join("DxeCore.pdb"), PDB_MAGIC).unwrap();
+        assert_eq!(provider.symbol_path("DxeCore", None), Some(directory.join("DxeCore.pdb")));
+        assert!(is_pdb(&directory.join("DxeCore.pdb")).unwrap());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+//! Source line context for resolved frames.
+//!
+//! Debug files record the source paths of the machine that built the image,
+//! such as `C:\src\patina\sdk\patina\src\log.rs` for a Windows build. A
+//! [`SourceLocator`] maps those paths onto a local checkout so that a few lines
+//! of source around each resolved line can be shown with the trace, as
+//! [`SourceSnippet`]s.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// A way of mapping a build path onto the local file system.
+#[derive(Debug, Clone)]
+enum SourceRoot {
+    /// Replace the build path prefix `from` with the local directory `to`.
+    Remap { from: Vec<String>, to: PathBuf },
+    /// Look for the build path under a local directory, dropping leading
+    /// components of the build path until a file is found.
+    Search(PathBuf),
+}
+
+/// Finds the local copy of source files named in debug information.
+///
+/// Roots are tried in the order they were added. Without any roots, source
+/// files are only found at the paths recorded at build time.
+#[derive(Debug, Clone, Default)]
+pub struct SourceLocator {
+    roots: Vec<SourceRoot>,
+}
+
+impl SourceLocator {
+    /// Creates a locator without any source roots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a root from the `--source-root` syntax of `resolve_stacktrace`:
+    /// `<build prefix>=<directory>` remaps a build path prefix, and a bare
+    /// `<directory>` is searched for the build path.
+    pub fn with_root(self, root: &str) -> Self {
+        match root.split_once('=') {
+            Some((from, to)) => self.with_remap(from, to),
+            None => self.with_search_root(root),
+        }
+    }
+
+    /// Adds a root mapping source paths that start with the build directory
+    /// `from` to the same paths under the local directory `to`. Prefixes are
+    /// compared by path component, ignoring the separator and ASCII case, so a
+    /// Windows build path can be remapped on any host.
+    pub fn with_remap(mut self, from: &str, to: impl Into<PathBuf>) -> Self {
+        let from = components(from).map(str::to_string).collect();
+        self.roots.push(SourceRoot::Remap { from, to: to.into() });
+        self
+    }
+
+    /// Adds a root searched for source files. The longest trailing part of a
+    /// source path that exists under `directory` is used, so the root can be a
+    /// checkout of the built repository without knowing where it was built.
+    pub fn with_search_root(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.roots.push(SourceRoot::Search(directory.into()));
+        self
+    }
+
+    /// Returns the local path of the source file `file` named in debug
+    /// information, or `None` if it cannot be found.
+    pub fn locate(&self, file: &str) -> Option<PathBuf> {
+        self.candidates(file).into_iter().find(|path| path.is_file())
+    }
+
+    /// Reads the lines around `line` of the source file `file`, with up to
+    /// `context` lines on either side.
+    pub fn snippet(&self, file: &str, line: u32, context: u32) -> Option<SourceSnippet> {
+        let path = self.locate(file)?;
+        let text = fs::read_to_string(&path).ok()?;
+        SourceSnippet::from_text(path, &text, line, context)
+    }
+
+    /// Paths where the source file `file` may be, in order of preference. The
+    /// path recorded at build time comes last, so a configured root wins over
+    /// a stale file left at the build location.
+    fn candidates(&self, file: &str) -> Vec<PathBuf> {
+        let parts: Vec<&str> = components(file).collect();
+        let mut candidates = vec![];
+        for root in &self.roots {
+            match root {
+                SourceRoot::Remap { from, to } => {
+                    let matches = from.len() <= parts.len()
+                        && from.iter().zip(&parts).all(|(prefix, part)| prefix.eq_ignore_ascii_case(part));
+                    if matches {
+                        candidates.push(join(to, &parts[from.len()..]));
+                    }
+                }
+                SourceRoot::Search(directory) => {
+                    // A drive letter is never part of the path under the root.
+                    let relative = (0..parts.len()).filter(|&start| !parts[start].ends_with(':'));
+                    candidates.extend(relative.map(|start| join(directory, &parts[start..])));
+                }
+            }
+        }
+        candidates.push(PathBuf::from(file));
+        candidates
+    }
+}
+
+/// Split a build path into its components, accepting either separator.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".")
+}
+
+/// Join path components onto a local directory.
+fn join(directory: &Path, parts: &[&str]) -> PathBuf {
+    parts.iter().fold(directory.to_path_buf(), |path, part| path.join(part))
+}
+
+/// A few lines of a source file around a resolved line.
+///
+/// Displays as one line of source per line, each prefixed with its line
+/// number, and the resolved line marked with `>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSnippet {
+    /// The local path the source was read from.
+    pub path: PathBuf,
+    /// The resolved line.
+    pub line: u32,
+    /// The number of the first line in `lines`.
+    pub first_line: u32,
+    /// The source lines, without line endings.
+    pub lines: Vec<String>,
+}
+
+impl SourceSnippet {
+    /// Takes the lines around `line` of the source text `text`, with up to
+    /// `context` lines on either side. Returns `None` if `text` does not have
+    /// the line, which usually means the source does not match the build.
+    pub fn from_text(path: impl Into<PathBuf>, text: &str, line: u32, context: u32) -> Option<Self> {
+        let line_count = text.lines().count();
+        if line == 0 || line as usize > line_count {
+            return None;
+        }
+        let first_line = line.saturating_sub(context).max(1);
+        let last_line = line.saturating_add(context).min(line_count as u32);
+        let lines = text
+            .lines()
+            .skip(first_line as usize - 1)
+            .take((last_line - first_line + 1) as usize)
+            .map(|text| text.trim_end().to_string())
+            .collect();
+        Some(Self { path: path.into(), line, first_line, lines })
+    }
+}
+
+impl fmt::Display for SourceSnippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let last_line = self.first_line as usize + self.lines.len().saturating_sub(1);
+        let width = last_line.to_string().len();
+        for (number, text) in (self.first_line..).zip(&self.lines) {
+            let marker = if number == self.line { '>' } else { ' ' };
+            writeln!(f, "{} {:>width$} | {}", marker, number, text, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const BUILD_PATH: &str = r"C:\src\patina\sdk\patina\src\log.rs";
+
+    #[test]
+    fn test_candidates() {
+        let locator = SourceLocator::new().with_root(r"c:\SRC\patina=/home/user/patina").with_root("/checkout");
+        assert_eq!(
+            locator.candidates(BUILD_PATH),
+            [
+                PathBuf::from("/home/user/patina/sdk/patina/src/log.rs"),
+                PathBuf::from("/checkout/src/patina/sdk/patina/src/log.rs"),
+                PathBuf::from("/checkout/patina/sdk/patina/src/log.rs"),
+                PathBuf::from("/checkout/sdk/patina/src/log.rs"),
+                PathBuf::from("/checkout/patina/src/log.rs"),
+                PathBuf::from("/checkout/src/log.rs"),
+                PathBuf::from("/checkout/log.rs"),
+                PathBuf::from(BUILD_PATH),
+            ]
+        );
+
+        // A remap only applies to paths under its prefix.
+        let locator = SourceLocator::new().with_remap("/build/patina", "/home/user/patina");
+        assert_eq!(locator.candidates("/build/patina2/lib.rs"), [PathBuf::from("/build/patina2/lib.rs")]);
+    }
+
+    #[test]
+    fn test_locate() {
+        let directory = std::env::temp_dir().join(format!("patina_stacktrace_source_{}", std::process::id()));
+        fs::create_dir_all(directory.join("sdk/patina/src")).unwrap();
+        fs::write(directory.join("sdk/patina/src/log.rs"), "fn main() {}\n").unwrap();
+
+        let locator = SourceLocator::new().with_search_root(&directory);
+        assert_eq!(locator.locate(BUILD_PATH), Some(directory.join("sdk/patina/src/log.rs")));
+        assert_eq!(locator.snippet(BUILD_PATH, 1, 3).unwrap().lines, ["fn main() {}"]);
+        assert_eq!(locator.locate(r"C:\src\patina\sdk\patina\src\lib.rs"), None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_snippet() {
+        let text = (1..=12).map(|line| format!("line {line}  ")).collect::<Vec<_>>().join("\n");
+
+        let snippet = SourceSnippet::from_text("log.rs", &text, 10, 1).unwrap();
+        assert_eq!(snippet.first_line, 9);
+        assert_eq!(snippet.lines, ["line 9", "line 10", "line 11"]);
+        assert_eq!(snippet.to_string(), "   9 | line 9\n> 10 | line 10\n  11 | line 11\n");
+
+        // The context is clipped to the file.
+        let snippet = SourceSnippet::from_text("log.rs", &text, 2, 3).unwrap();
+        assert_eq!(snippet.first_line, 1);
+        assert_eq!(snippet.lines.len(), 5);
+        assert_eq!(SourceSnippet::from_text("log.rs", &text, 12, 3).unwrap().lines.len(), 4);
+
+        assert_eq!(SourceSnippet::from_text("log.rs", &text, 0, 3), None);
+        assert_eq!(SourceSnippet::from_text("log.rs", &text, 13, 3), None);
+    }
+}
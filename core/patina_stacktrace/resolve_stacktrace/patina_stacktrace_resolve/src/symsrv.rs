@@ -0,0 +1,130 @@
+//! Download of PDBs from symbol servers.
+//!
+//! A symbol server, such as a SymSrv store published over HTTP or an Azure
+//! DevOps symbol server, serves each PDB at
+//! `<url>/<module>.pdb/<signature>/<module>.pdb`, the same layout as the
+//! [`PdbCache`]. A [`SymbolServer`] downloads the PDBs missing from a cache
+//! into it, so each PDB is fetched once and later traces resolve offline.
+//!
+//! PDBs are looked up by signature, so only modules with a build ID in the
+//! trace can be fetched. Compressed (`.pd_`) files and `file.ptr` redirections
+//! are not supported.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{PdbCache, SymbolProvider};
+
+/// How long to wait for a symbol server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Provides PDBs from a symbol server over HTTP, downloading them into a
+/// [`PdbCache`].
+pub struct SymbolServer {
+    url: String,
+    cache: PdbCache,
+    agent: ureq::Agent,
+}
+
+impl SymbolServer {
+    /// Creates a provider for the symbol server at `url`, downloading into
+    /// `cache`.
+    pub fn new(url: impl Into<String>, cache: PdbCache) -> Self {
+        let url = url.into().trim_end_matches('/').to_string();
+        Self { url, cache, agent: ureq::AgentBuilder::new().timeout_connect(CONNECT_TIMEOUT).build() }
+    }
+
+    /// Location of a module's PDB on the server.
+    fn pdb_url(&self, module_name: &str, signature: &str) -> String {
+        format!("{0}/{1}.pdb/{2}/{1}.pdb", self.url, module_name, signature)
+    }
+
+    /// Download `url` to `path`, returning `false` if the server does not have
+    /// it. The file is written under a temporary name and renamed once
+    /// complete, so an interrupted download never leaves a truncated PDB in
+    /// the cache. Coverage is off because this function depends on a server.
+    #[coverage(off)]
+    fn download(&self, url: &str, path: &Path) -> Result<bool, String> {
+        let response = match self.agent.get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(false),
+            Err(e) => return Err(format!("failed to download {}: {}", url, e)),
+        };
+
+        let partial_path = path.with_extension("pdb.partial");
+        let write = || -> io::Result<()> {
+            path.parent().map_or(Ok(()), fs::create_dir_all)?;
+            io::copy(&mut response.into_reader(), &mut File::create(&partial_path)?)?;
+            fs::rename(&partial_path, path)
+        };
+        write().map(|()| true).map_err(|e| {
+            let _ = fs::remove_file(&partial_path);
+            format!("failed to download {} to {:?}: {}", url, path, e)
+        })
+    }
+}
+
+impl SymbolProvider for SymbolServer {
+    fn symbol_path(&self, module_name: &str, signature: Option<&str>) -> Option<PathBuf> {
+        self.fetch(module_name, signature).ok().flatten()
+    }
+
+    fn fetch(&self, module_name: &str, signature: Option<&str>) -> Result<Option<PathBuf>, String> {
+        let Some(signature) = signature else {
+            return Ok(None);
+        };
+        let path = self.cache.cached_pdb_path(module_name, signature);
+        if path.is_file() {
+            return Ok(Some(path));
+        }
+        Ok(self.download(&self.pdb_url(module_name, signature), &path)?.then_some(path))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: &str = "3F2504E04F8911D39A0C0305E82C33011";
+
+    #[test]
+    fn test_pdb_url() {
+        let server = SymbolServer::new("https://symbols.example.com/symbols/", PdbCache::new("cache"));
+        assert_eq!(
+            server.pdb_url("DxeCore", SIGNATURE),
+            format!("https://symbols.example.com/symbols/DxeCore.pdb/{SIGNATURE}/DxeCore.pdb")
+        );
+    }
+
+    #[test]
+    fn test_fetch_requires_signature() {
+        let server = SymbolServer::new("http://127.0.0.1:9", PdbCache::new("cache"));
+        assert_eq!(server.fetch("DxeCore", None), Ok(None));
+    }
+
+    #[test]
+    fn test_fetch_prefers_cache() {
+        let directory = std::env::temp_dir().join(format!("patina_stacktrace_symsrv_{}", std::process::id()));
+        let cache = PdbCache::new(&directory);
+        let cached_path = cache.cached_pdb_path("DxeCore", SIGNATURE);
+        fs::create_dir_all(cached_path.parent().unwrap()).unwrap();
+        fs::write(&cached_path, b"").unwrap();
+
+        // The server is never contacted for a cached PDB.
+        let server = SymbolServer::new("http://127.0.0.1:9", cache);
+        assert_eq!(server.fetch("DxeCore", Some(SIGNATURE)), Ok(Some(cached_path)));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
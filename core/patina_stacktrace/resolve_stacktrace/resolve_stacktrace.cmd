@@ -8,6 +8,6 @@ REM Remove trailing backslash (optional)
 if "%SCRIPT_DIR:~-1%"=="\" set SCRIPT_DIR=%SCRIPT_DIR:~0,-1%
 
 REM Run cargo with the manifest path in the same directory as the script
-cargo run --quiet --manifest-path "%SCRIPT_DIR%\Cargo.toml"
+cargo run --quiet --manifest-path "%SCRIPT_DIR%\Cargo.toml" -- %*
 
 endlocal
@@ -6,4 +6,4 @@ set -euo pipefail
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 
 # Run cargo using the Cargo.toml in that directory
-cargo run --quiet --manifest-path "$SCRIPT_DIR/Cargo.toml"
+cargo run --quiet --manifest-path "$SCRIPT_DIR/Cargo.toml" -- "$@"
@@ -1,197 +1,297 @@
 #![feature(coverage_attribute)]
-//! A tool that resolves raw stack traces using offline PDB parsing. It reads
+//! A tool that resolves raw stack traces using offline PDB or DWARF parsing. It reads
 //! symbols for each frame and prints the resolved stack trace showing source
 //! file locations, demangled function names, and instruction offsets.
 //!
 //! This tool is meant to be invoked via `./resolve_stacktrace.cmd` or
-//! `./resolve_stacktrace.sh`.
+//! `./resolve_stacktrace.sh`, which forward their arguments. With no arguments
+//! and a terminal on stdin it prompts for its inputs. Otherwise it runs without
+//! prompts, reading the stack trace from `--input` or from stdin, so it can be
+//! used in scripts and CI pipelines.
+//!
+//! The parsing and resolution logic lives in the `patina_stacktrace_resolve`
+//! library so it can be embedded in other tools; this binary only gathers the
+//! inputs and prints the results.
 //!
 //! For more details, see the `README.md` in the stack trace module.
+use clap::{Parser, ValueEnum};
 use comfy_table::{Cell, ContentArrangement, Table, presets::UTF8_FULL};
-use pdb_addr2line::pdb;
+use patina_stacktrace_resolve::{
+    InlinedFrame, LoadMap, LoggedTrace, PdbCache, ResolvedFrame, Resolver, SourceLocator, SourceSnippet, StackTrace,
+    SymbolDirectory, SymbolServer,
+};
 use std::{
-    fs::File,
-    io::{self, BufReader, Write},
-    path::{Path, PathBuf},
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
 };
 
-#[derive(Debug)]
-struct StackFrame {
-    frame_number: String,
-    child_stack_pointer: String,
-    return_address: String,
-    module_name: String,
-    start_rva: u32,
-
-    file: Option<String>,
-    line: Option<u32>,
-    function: Option<String>,
-    offset: u32,
-
-    // If any error occurred when resolving stack frame information, store it here
-    error: Option<String>,
+#[derive(Parser, Debug)]
+struct Args {
+    /// Directory containing the debug files. Defaults to the STACKTRACE_PDB_DIR
+    /// environment variable.
+    #[arg(long)]
+    pdb_dir: Option<PathBuf>,
+    /// Directory of the PDB cache. Defaults to the STACKTRACE_PDB_CACHE
+    /// environment variable; no cache is used if neither is set, unless PDBs
+    /// are downloaded from a symbol server.
+    #[arg(long)]
+    pdb_cache: Option<PathBuf>,
+    /// URL of a symbol server to download missing PDBs from. May be repeated;
+    /// servers are tried in order. Defaults to the `;`-separated URLs in the
+    /// STACKTRACE_SYMBOL_SERVER environment variable.
+    #[arg(long = "symbol-server")]
+    symbol_servers: Vec<String>,
+    /// File containing the stack trace, such as a boot log. Use `-` or omit it
+    /// to read from stdin.
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+    /// Read the input as a bare list of call sites, one per line, instead of
+    /// a stack trace. Each is `module+<rva>` or an absolute address.
+    #[arg(long)]
+    addresses: bool,
+    /// File listing the load address of each module, one `<module> <base>
+    /// [<size>]` line per module, used to map absolute addresses to modules.
+    /// Implies --addresses.
+    #[arg(long)]
+    load_map: Option<PathBuf>,
+    /// Scan the input, such as a whole serial capture, for every stack trace
+    /// the firmware dumped, and report each crash separately.
+    #[arg(long, conflicts_with_all = ["addresses", "load_map"])]
+    scan: bool,
+    /// Where to find the sources of the build, to show source lines around
+    /// each resolved line. `<build prefix>=<directory>` maps source paths
+    /// under the build prefix into the directory; a bare `<directory>`, such
+    /// as a checkout of the built repository, is searched for each source
+    /// path. May be repeated. Implies --source-context 2.
+    #[arg(long = "source-root")]
+    source_roots: Vec<String>,
+    /// Show this many lines of source on either side of each resolved line.
+    /// Sources are read from the paths recorded in the debug files unless
+    /// --source-root is given.
+    #[arg(long, value_name = "LINES")]
+    source_context: Option<u32>,
+    /// Output format of the resolved stack trace.
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    format: Format,
 }
 
-/// Look up debug info for each parsed stack frame and attach file, line, and
-/// symbol data. Coverage is off because this function depends on external PDB
-/// files
-#[coverage(off)]
-fn resolve_stack_frames(pdb_directory: &Path, mut stack_frames: Vec<StackFrame>) -> Vec<StackFrame> {
-    for stack_frame in &mut stack_frames {
-        let mut pdb_path: PathBuf = pdb_directory.join(&stack_frame.module_name);
-        pdb_path.set_extension("pdb");
-
-        let Ok(file) = File::open(&pdb_path) else {
-            stack_frame.error = Some(format!("Failed to open {:?}", pdb_path));
-            continue;
-        };
-
-        let reader = BufReader::new(file);
-        let Ok(pdb) = pdb::PDB::open(reader) else {
-            stack_frame.error = Some(format!("Failed to parse PDB {:?}", pdb_path));
-            continue;
-        };
-
-        let Ok(context_data) = pdb_addr2line::ContextPdbData::try_from_pdb(pdb) else {
-            stack_frame.error = Some(format!("Failed to create context data from PDB {:?}", pdb_path));
-            continue;
-        };
-
-        let Ok(context) = context_data.make_context() else {
-            stack_frame.error = Some(format!("Failed to create context from PDB {:?}", pdb_path));
-            continue;
-        };
-
-        let Ok(Some(frames)) = context.find_frames(stack_frame.start_rva) else {
-            stack_frame.error = Some(format!("Failed to find frames in context for {:?}", stack_frame.start_rva));
-            continue;
-        };
+/// The output formats of the resolved stack trace.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    /// A table, as shown in the README.
+    Table,
+    /// A JSON object with one record per frame.
+    Json,
+    /// One line per frame.
+    Text,
+}
 
-        let Some(frame) = frames.frames.last() else {
-            stack_frame.error = Some(format!("No frames found for RVA 0x{:X}", stack_frame.start_rva));
-            continue;
-        };
+/// Lines of source shown on either side of each resolved line when only
+/// --source-root is given.
+const DEFAULT_SOURCE_CONTEXT: u32 = 2;
 
-        let function_start_rva = frames.start_rva;
-        let file = frame.file.as_deref().unwrap_or("<unknown>").to_string();
-        let line = frame.line.unwrap_or(0);
-        let function = frame.function.as_deref().unwrap_or("<unknown>").to_string();
-        let offset = stack_frame.start_rva - function_start_rva;
+/// Where to read source lines from, and how many to show around each
+/// resolved line.
+struct SourceContext {
+    locator: SourceLocator,
+    lines: u32,
+}
 
-        stack_frame.file = Some(file);
-        stack_frame.line = Some(line);
-        stack_frame.function = Some(function);
-        stack_frame.offset = offset;
+impl SourceContext {
+    /// Returns the source context requested on the command line, or `None` if
+    /// source lines are not shown.
+    fn from_args(source_roots: &[String], source_context: Option<u32>) -> Option<Self> {
+        let lines = source_context.or((!source_roots.is_empty()).then_some(DEFAULT_SOURCE_CONTEXT))?;
+        let locator = source_roots.iter().fold(SourceLocator::new(), |locator, root| locator.with_root(root));
+        Some(Self { locator, lines })
     }
-
-    stack_frames
 }
 
-/// Convert a single textual stack trace line into a structured `StackFrame`.
-fn create_stack_frame(line: &str) -> Option<StackFrame> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() < 4 {
-        return None;
-    }
+/// Read a directory path from `flag`, falling back to the `env` environment
+/// variable. Empty values are treated as unset.
+fn directory(flag: Option<PathBuf>, env: &str) -> Option<PathBuf> {
+    flag.filter(|dir| !dir.as_os_str().is_empty())
+        .or_else(|| std::env::var_os(env).filter(|dir| !dir.is_empty()).map(PathBuf::from))
+}
 
-    let idx = parts.len();
-
-    // Parse each column backwards
-    let (module_name, start_rva_str) = parts[idx - 1].rsplit_once('+')?;
-    let start_rva_str = start_rva_str.strip_prefix("0x").unwrap_or(start_rva_str);
-    let start_rva = u32::from_str_radix(start_rva_str, 16).ok()?;
-    let return_address = parts[idx - 2].to_string();
-    let child_stack_pointer = parts[idx - 3].to_string();
-    let frame_number = parts[idx - 4].to_string();
-
-    Some(StackFrame {
-        frame_number,
-        child_stack_pointer,
-        return_address,
-        module_name: module_name.to_string(),
-        start_rva,
-        file: None,     // filled by resolver
-        line: None,     // filled by resolver
-        function: None, // filled by resolver
-        offset: 0,      // filled by resolver
-        error: None,    // filled by resolver
-    })
+/// Read the symbol server URLs from `flag`, falling back to the `;`-separated
+/// URLs in the `env` environment variable.
+fn symbol_servers(flag: Vec<String>, env: &str) -> Vec<String> {
+    let servers = if flag.is_empty() {
+        std::env::var(env).unwrap_or_default().split(';').map(String::from).collect()
+    } else {
+        flag
+    };
+    servers.into_iter().map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect()
 }
 
-/// Collect the PDB directory and stack trace text from stdin. Coverage is off
-/// because this is I/O code.
+/// Prompt for the PDB directory on stdin. The path is empty if the user leaves
+/// it empty. Coverage is off because this is I/O code.
 #[coverage(off)]
-fn read_inputs() -> Result<(PathBuf, Vec<String>), String> {
+fn prompt_pdb_directory() -> Result<PathBuf, String> {
     let mut pdb_directory = String::new();
     print!("Enter the PDB directory path (leave empty to use STACKTRACE_PDB_DIR env): ");
     io::stdout().flush().map_err(|e| format!("Failed to flush stdout: {}", e))?;
     io::stdin().read_line(&mut pdb_directory).map_err(|e| format!("Failed to read PDB directory from stdin: {}", e))?;
 
-    let mut pdb_directory = pdb_directory.trim().to_owned();
-    if pdb_directory.is_empty() {
-        // Read from STACKTRACE_PDB_DIR environment variable
-        if let Ok(env_dir) = std::env::var("STACKTRACE_PDB_DIR") {
-            if !env_dir.is_empty() {
-                pdb_directory = env_dir;
-            }
-        } else {
-            return Err("PDB directory not provided or set in STACKTRACE_PDB_DIR".to_string());
-        }
-    }
-
-    if pdb_directory.is_empty() {
-        return Err("PDB directory path cannot be empty".to_string());
-    }
-
-    let pdb_directory = PathBuf::from(pdb_directory);
+    Ok(PathBuf::from(pdb_directory.trim()))
+}
 
+/// Read stack trace lines typed at the terminal, up to the first empty line.
+/// Coverage is off because this is I/O code.
+#[coverage(off)]
+fn prompt_stacktrace() -> Result<Vec<String>, String> {
     println!("Enter stack trace lines (press Enter twice to finish):");
     let mut stacktrace = vec![];
-    loop {
-        let mut line = String::new();
-        let bytes_read = io::stdin()
-            .read_line(&mut line)
-            .map_err(|e| format!("Failed to read stack trace line from stdin: {}", e))?;
-
-        if bytes_read == 0 {
-            break;
-        }
-
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("Failed to read stack trace line from stdin: {}", e))?;
         let trimmed = line.trim_end();
         if trimmed.is_empty() {
             break;
         }
-
         stacktrace.push(trimmed.to_string());
     }
+    Ok(stacktrace)
+}
 
-    Ok((pdb_directory, stacktrace))
+/// Read the whole stack trace from `input`, or from stdin if `input` is `None`
+/// or `-`. Coverage is off because this is I/O code.
+#[coverage(off)]
+fn read_stacktrace(input: Option<&PathBuf>) -> Result<Vec<String>, String> {
+    let text = match input.filter(|path| path.as_os_str() != "-") {
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        None => io::read_to_string(io::stdin()).map_err(|e| format!("Failed to read stack trace from stdin: {}", e))?,
+    };
+    Ok(text.lines().map(str::to_string).collect())
 }
 
-/// Parse the stack trace text into a list of stack frames, skipping headers.
-fn create_stack_frames(stack_frames: Vec<String>) -> Vec<StackFrame> {
-    stack_frames
-        .iter()
-        .filter_map(|line| {
-            let line = line.trim();
-            if line.is_empty() {
-                return None;
-            }
+/// Read the load map from `path`. Coverage is off because this is I/O code.
+#[coverage(off)]
+fn read_load_map(path: &PathBuf) -> Result<LoadMap, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(LoadMap::parse(text.lines()))
+}
 
-            // Skip header line
-            if line.contains("Return Address") {
-                return None;
-            }
+/// Returns `value`, or `-` if it is empty, for the space-separated text format.
+fn text_column(value: &str) -> &str {
+    if value.is_empty() { "-" } else { value }
+}
 
-            create_stack_frame(line)
+/// Returns `value`, or `None` if it is empty, for the JSON format.
+fn json_column(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Returns the source lines around a resolved location, if they are shown and
+/// the source file can be found.
+fn source_snippet(source: Option<&SourceContext>, file: Option<&str>, line: Option<u32>) -> Option<SourceSnippet> {
+    let source = source?;
+    source.locator.snippet(file?, line?, source.lines)
+}
+
+/// Returns the location column of the table format: the source path and line,
+/// followed by the source lines around it if they are shown.
+fn table_location(source_path: &str, line: u32, snippet: Option<SourceSnippet>) -> String {
+    match snippet {
+        Some(snippet) => format!("{} @ {}\n{}", source_path, line, snippet.to_string().trim_end()),
+        None => format!("{} @ {}", source_path, line),
+    }
+}
+
+/// Appends the source lines around a resolved location to the text format,
+/// indented under the frame.
+fn push_snippet(text: &mut String, snippet: Option<SourceSnippet>) {
+    if let Some(snippet) = snippet {
+        for line in snippet.to_string().lines() {
+            text.push_str(&format!("    {}\n", line));
+        }
+    }
+}
+
+/// Returns the source lines around the call site of a resolved frame.
+fn resolved_snippet(resolved: &ResolvedFrame, source: Option<&SourceContext>) -> Option<SourceSnippet> {
+    let symbol = resolved.symbol.as_ref().ok()?;
+    source_snippet(source, symbol.file.as_deref(), symbol.line)
+}
+
+/// Returns a source snippet as a JSON value.
+fn json_snippet(snippet: Option<SourceSnippet>) -> serde_json::Value {
+    snippet.map_or(serde_json::Value::Null, |snippet| {
+        serde_json::json!({
+            "path": snippet.path,
+            "first_line": snippet.first_line,
+            "lines": snippet.lines,
         })
-        .collect()
+    })
 }
 
-/// Render the resolved stack frames as a formatted table for display. Coverage
-/// is off because this function do not return a value.
-#[coverage(off)]
-fn dump_stack_frames(stack_frames: Vec<StackFrame>) {
+/// Returns the source path, line, function and offset to display for a frame.
+fn display_symbol(resolved: &ResolvedFrame) -> (&str, u32, &str, u32) {
+    match &resolved.symbol {
+        Ok(symbol) => (
+            symbol.file.as_deref().unwrap_or("<unknown>"),
+            symbol.line.unwrap_or(0),
+            symbol.function.as_deref().unwrap_or("<unknown>"),
+            symbol.offset,
+        ),
+        Err(error) => (error.as_str(), 0, "<unknown>", 0),
+    }
+}
+
+/// Returns the functions inlined at a frame's call site, innermost first.
+fn inlined_frames(resolved: &ResolvedFrame) -> &[InlinedFrame] {
+    resolved.symbol.as_ref().map_or(&[], |symbol| symbol.inlined.as_slice())
+}
+
+/// Returns the source path, line and function to display for a function
+/// inlined at a call site.
+fn display_inlined(inlined: &InlinedFrame) -> (&str, u32, &str) {
+    (
+        inlined.file.as_deref().unwrap_or("<unknown>"),
+        inlined.line.unwrap_or(0),
+        inlined.function.as_deref().unwrap_or("<unknown>"),
+    )
+}
+
+/// Returns the marker shown before the call sites of a frame resolved from a
+/// PDB that does not match the build ID in the trace.
+fn stale_marker(resolved: &ResolvedFrame) -> &'static str {
+    if resolved.stale_pdb { "[stale PDB] " } else { "" }
+}
+
+/// Returns the rows of the table format. As in a gdb backtrace, each function
+/// inlined at a call site gets its own `[inlined]` row above the frame,
+/// innermost first, so the top row is where the code actually was.
+fn table_rows(stack_frames: &[ResolvedFrame], source: Option<&SourceContext>) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    for resolved in stack_frames {
+        let frame = &resolved.frame;
+        for inlined in inlined_frames(resolved) {
+            let (source_path, line, function) = display_inlined(inlined);
+            let snippet = source_snippet(source, inlined.file.as_deref(), inlined.line);
+            rows.push(vec![
+                frame.frame_number.clone(),
+                table_location(source_path, line, snippet),
+                String::new(),
+                String::new(),
+                format!("[inlined] {}{}!{}", stale_marker(resolved), frame.module_name, function),
+            ]);
+        }
+        let (source_path, line, function, offset) = display_symbol(resolved);
+        rows.push(vec![
+            frame.frame_number.clone(),
+            table_location(source_path, line, resolved_snippet(resolved, source)),
+            frame.child_stack_pointer.clone(),
+            frame.return_address.clone(),
+            format!("{}{}!{}+0x{:X}", stale_marker(resolved), frame.module_name, function, offset),
+        ]);
+    }
+    rows
+}
+
+/// Render the resolved stack frames as a formatted table.
+fn render_table(stack_frames: &[ResolvedFrame], source: Option<&SourceContext>) -> String {
     let mut table = Table::new();
     table.load_preset(UTF8_FULL).set_content_arrangement(ContentArrangement::DynamicFullWidth).set_header(vec![
         Cell::new("#").add_attribute(comfy_table::Attribute::Bold),
@@ -200,33 +300,232 @@ fn dump_stack_frames(stack_frames: Vec<StackFrame>) {
         Cell::new("Return Address").add_attribute(comfy_table::Attribute::Bold),
         Cell::new("Call Site").add_attribute(comfy_table::Attribute::Bold),
     ]);
+    for row in table_rows(stack_frames, source) {
+        table.add_row(row);
+    }
 
-    for frame in &stack_frames {
-        let source_path = frame.file.as_deref().unwrap_or(frame.error.as_deref().unwrap_or("<unknown>"));
-        let source_path = format!("{} @ {}", source_path, frame.line.unwrap_or(0));
-        let call_site =
-            format!("{}!{}+0x{:X}", frame.module_name, frame.function.as_deref().unwrap_or("<unknown>"), frame.offset);
+    table.to_string()
+}
 
-        table.add_row(vec![
-            frame.frame_number.clone(),
+/// Render the resolved stack frames as one line per frame, preceded by an
+/// `[inlined]` line for each function inlined at the call site as in the
+/// table format. Source lines, if shown, are indented under each line.
+fn render_text(stack_frames: &[ResolvedFrame], source: Option<&SourceContext>) -> String {
+    let mut text = String::new();
+    for resolved in stack_frames {
+        let frame = &resolved.frame;
+        for inlined in inlined_frames(resolved) {
+            let (source_path, line, function) = display_inlined(inlined);
+            text.push_str(&format!(
+                "{} [inlined] {}{}!{} {} @ {}\n",
+                frame.frame_number,
+                stale_marker(resolved),
+                frame.module_name,
+                function,
+                source_path,
+                line
+            ));
+            push_snippet(&mut text, source_snippet(source, inlined.file.as_deref(), inlined.line));
+        }
+        let (source_path, line, function, offset) = display_symbol(resolved);
+        text.push_str(&format!(
+            "{} {} {} {}{}!{}+0x{:X} {} @ {}\n",
+            frame.frame_number,
+            text_column(&frame.child_stack_pointer),
+            text_column(&frame.return_address),
+            stale_marker(resolved),
+            frame.module_name,
+            function,
+            offset,
             source_path,
-            frame.child_stack_pointer.clone(),
-            frame.return_address.clone(),
-            call_site,
-        ]);
+            line
+        ));
+        push_snippet(&mut text, resolved_snippet(resolved, source));
     }
+    text
+}
 
-    println!("{table}");
+/// Version of the JSON output, incremented when fields are removed or change
+/// meaning. Adding fields does not change the version.
+const JSON_FORMAT_VERSION: u32 = 1;
+
+/// Render the resolved stack frames as a JSON object holding the format
+/// version and one record per frame. Unlike the other formats, unknown values
+/// are `null` and resolution failures are reported in `error`. Addresses and
+/// offsets are hexadecimal strings, as printed in the trace. Source lines, if
+/// shown, are in `source`.
+fn render_json(stack_frames: &[ResolvedFrame], source: Option<&SourceContext>) -> String {
+    let output = serde_json::json!({ "version": JSON_FORMAT_VERSION, "frames": json_frames(stack_frames, source) });
+    serde_json::to_string_pretty(&output).expect("JSON values always serialize")
 }
 
-/// Entry point: read inputs, resolve frames, and print the resolved table.
+/// Returns the JSON records of the resolved stack frames.
+fn json_frames(stack_frames: &[ResolvedFrame], source: Option<&SourceContext>) -> Vec<serde_json::Value> {
+    stack_frames
+        .iter()
+        .map(|resolved| {
+            let frame = &resolved.frame;
+            let symbol = resolved.symbol.as_ref().ok();
+            let inlined: Vec<_> = inlined_frames(resolved)
+                .iter()
+                .map(|inlined| {
+                    serde_json::json!({
+                        "function": inlined.function,
+                        "file": inlined.file,
+                        "line": inlined.line,
+                        "source": json_snippet(source_snippet(source, inlined.file.as_deref(), inlined.line)),
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "frame": frame.frame_number,
+                "child_sp": json_column(&frame.child_stack_pointer),
+                "return_address": json_column(&frame.return_address),
+                "module": frame.module_name,
+                "rva": format!("0x{:X}", frame.start_rva),
+                "function": symbol.and_then(|symbol| symbol.function.as_ref()),
+                "offset": symbol.map(|symbol| format!("0x{:X}", symbol.offset)),
+                "file": symbol.and_then(|symbol| symbol.file.as_ref()),
+                "line": symbol.and_then(|symbol| symbol.line),
+                "source": json_snippet(resolved_snippet(resolved, source)),
+                "inlined": inlined,
+                "error": resolved.symbol.as_ref().err(),
+                "stale_pdb": resolved.stale_pdb,
+                "warnings": resolved.warnings,
+            })
+        })
+        .collect()
+}
+
+/// Returns the line introducing a crash found by --scan in the table and text
+/// formats.
+fn crash_heading(number: usize, crash: &LoggedTrace) -> String {
+    let reason = crash.reason.as_deref().unwrap_or("no crash message found");
+    let incomplete = if crash.complete { "" } else { " (trace incomplete)" };
+    format!("Crash {} at line {}: {}{}", number, crash.line, reason, incomplete)
+}
+
+/// Render the crashes found by --scan, each with its resolved stack frames.
+/// The JSON format holds one record per crash, each with the `frames` of the
+/// single trace format.
+fn render_crashes(
+    crashes: &[(LoggedTrace, Vec<ResolvedFrame>)],
+    format: Format,
+    source: Option<&SourceContext>,
+) -> String {
+    if format == Format::Json {
+        let crashes: Vec<_> = crashes
+            .iter()
+            .map(|(crash, stack_frames)| {
+                serde_json::json!({
+                    "line": crash.line,
+                    "reason": crash.reason,
+                    "complete": crash.complete,
+                    "frames": json_frames(stack_frames, source),
+                })
+            })
+            .collect();
+        let output = serde_json::json!({ "version": JSON_FORMAT_VERSION, "crashes": crashes });
+        return serde_json::to_string_pretty(&output).expect("JSON values always serialize");
+    }
+
+    let mut text = String::new();
+    for (index, (crash, stack_frames)) in crashes.iter().enumerate() {
+        let body = match format {
+            Format::Table => render_table(stack_frames, source) + "\n",
+            _ => render_text(stack_frames, source),
+        };
+        text.push_str(&format!("{}\n{}\n", crash_heading(index + 1, crash), body));
+    }
+    text
+}
+
+/// Print each module's warnings once to stderr. Coverage is off because this
+/// function does not return a value.
+#[coverage(off)]
+fn print_warnings(stack_frames: &[ResolvedFrame]) {
+    let mut warned_modules = vec![];
+    for resolved in stack_frames {
+        let module_name = &resolved.frame.module_name;
+        if !resolved.warnings.is_empty() && !warned_modules.contains(&module_name) {
+            warned_modules.push(module_name);
+            for warning in &resolved.warnings {
+                eprintln!("Warning: {}: {}", module_name, warning);
+            }
+        }
+    }
+}
+
+/// Entry point: gather inputs, resolve frames, and print them in the requested
+/// format.
 fn main() -> Result<(), String> {
-    let (pdb_directory, stacktrace) = read_inputs()?;
+    let args = Args::parse();
+
+    // Prompt only when nothing was given on the command line and a user is at
+    // the terminal, so that piped input never blocks on a prompt.
+    let interactive = args.input.is_none() && io::stdin().is_terminal();
+
+    // A PDB directory is optional when PDBs can be downloaded instead.
+    let symbol_servers = symbol_servers(args.symbol_servers, "STACKTRACE_SYMBOL_SERVER");
+    let pdb_directory = if interactive && args.pdb_dir.is_none() && symbol_servers.is_empty() {
+        Some(prompt_pdb_directory()?)
+    } else {
+        args.pdb_dir
+    };
+    let pdb_directory = directory(pdb_directory, "STACKTRACE_PDB_DIR");
+    if pdb_directory.is_none() && symbol_servers.is_empty() {
+        return Err("PDB directory not provided with --pdb-dir or set in STACKTRACE_PDB_DIR".to_string());
+    }
+
+    // Downloaded PDBs need somewhere to go, so a symbol server implies a cache.
+    let cache_directory = directory(args.pdb_cache, "STACKTRACE_PDB_CACHE")
+        .or_else(|| (!symbol_servers.is_empty()).then(|| std::env::temp_dir().join("patina_symbols")));
+
+    let stacktrace = if interactive { prompt_stacktrace()? } else { read_stacktrace(args.input.as_ref())? };
+
+    let mut resolver = Resolver::new();
+    if let Some(pdb_directory) = pdb_directory {
+        resolver = resolver.with_provider(SymbolDirectory::new(pdb_directory));
+    }
+    if let Some(cache_directory) = cache_directory {
+        let cache = PdbCache::new(cache_directory);
+        for url in symbol_servers {
+            resolver = resolver.with_provider(SymbolServer::new(url, cache.clone()));
+        }
+        resolver = resolver.with_cache(cache);
+    }
 
-    let stack_frames = create_stack_frames(stacktrace);
-    let stack_frames = resolve_stack_frames(&pdb_directory, stack_frames);
+    let source = SourceContext::from_args(&args.source_roots, args.source_context);
+    if args.scan {
+        let crashes: Vec<_> = StackTrace::scan(stacktrace)
+            .into_iter()
+            .map(|crash| {
+                let stack_frames = resolver.resolve(&crash.trace);
+                (crash, stack_frames)
+            })
+            .collect();
+        if crashes.is_empty() {
+            eprintln!("No stack traces found.");
+        }
+        print!("{}", render_crashes(&crashes, args.format, source.as_ref()));
+        for (_, stack_frames) in &crashes {
+            print_warnings(stack_frames);
+        }
+        return Ok(());
+    }
 
-    dump_stack_frames(stack_frames);
+    let trace = match args.load_map {
+        Some(path) => StackTrace::parse_addresses(stacktrace, &read_load_map(&path)?),
+        None if args.addresses => StackTrace::parse_addresses(stacktrace, &LoadMap::default()),
+        None => StackTrace::parse(stacktrace),
+    };
+    let stack_frames = resolver.resolve(&trace);
+    match args.format {
+        Format::Table => println!("{}", render_table(&stack_frames, source.as_ref())),
+        Format::Json => println!("{}", render_json(&stack_frames, source.as_ref())),
+        Format::Text => print!("{}", render_text(&stack_frames, source.as_ref())),
+    }
+    print_warnings(&stack_frames);
 
     Ok(())
 }
@@ -235,127 +534,219 @@ fn main() -> Result<(), String> {
 #[coverage(off)]
 mod tests {
     use super::*;
+    use patina_stacktrace_resolve::{StackFrame, Symbol};
+
+    fn resolved_frames() -> Vec<ResolvedFrame> {
+        let frame = |frame_number: &str, start_rva| StackFrame {
+            frame_number: frame_number.to_string(),
+            child_stack_pointer: "000001007E2796C0".to_string(),
+            return_address: "000001007E27BBDC".to_string(),
+            module_name: "qemu_sbsa_dxe_core".to_string(),
+            start_rva,
+        };
 
-    #[test]
-    fn test_create_stack_frame_valid() {
-        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3";
-        let frame = create_stack_frame(line).expect("Should parse valid frame");
-
-        assert_eq!(frame.frame_number, "00");
-        assert_eq!(frame.child_stack_pointer, "000000cd7bbfe830");
-        assert_eq!(frame.return_address, "00007ff6ddd0b4ae");
-        assert_eq!(frame.module_name, "DxeCore");
-        assert_eq!(frame.start_rva, 0x45a3);
-        assert!(frame.file.is_none());
-        assert!(frame.line.is_none());
-        assert!(frame.function.is_none());
-        assert_eq!(frame.offset, 0);
-        assert!(frame.error.is_none());
+        vec![
+            ResolvedFrame {
+                frame: frame("0", 0x185DC),
+                symbol: Ok(Symbol {
+                    file: Some("stacktrace.rs".to_string()),
+                    line: Some(144),
+                    function: Some("dump".to_string()),
+                    offset: 0xC,
+                    inlined: vec![InlinedFrame {
+                        function: Some("walk".to_string()),
+                        file: Some("unwind.rs".to_string()),
+                        line: Some(30),
+                    }],
+                }),
+                warnings: vec![],
+                stale_pdb: false,
+            },
+            ResolvedFrame {
+                frame: frame("1", 0x1BDC),
+                symbol: Err("No debug file found for qemu_sbsa_dxe_core".to_string()),
+                warnings: vec!["signature mismatch".to_string()],
+                stale_pdb: false,
+            },
+        ]
     }
 
     #[test]
-    fn test_create_stack_frame_hex_variations() {
-        let line = "05 000000cd7bbfe900 00007ff6ddd0ffff TestModule+0xABCD";
-        let frame = create_stack_frame(line).expect("Should parse uppercase hex");
-        assert_eq!(frame.start_rva, 0xABCD);
-
-        let line2 = "05 000000cd7bbfe900 00007ff6ddd0ffff TestModule+0xabcd";
-        let frame2 = create_stack_frame(line2).expect("Should parse lowercase hex");
-        assert_eq!(frame2.start_rva, 0xabcd);
+    fn test_args() {
+        let args = Args::parse_from(["resolve_stacktrace", "--pdb-dir", "Build", "-i", "boot.log", "--format", "json"]);
+        assert_eq!(args.pdb_dir, Some(PathBuf::from("Build")));
+        assert_eq!(args.input, Some(PathBuf::from("boot.log")));
+        assert_eq!(args.format, Format::Json);
+
+        let args = Args::parse_from([
+            "resolve_stacktrace",
+            "--symbol-server",
+            "https://symbols.example.com",
+            "--symbol-server",
+            "http://localhost:8080/symbols",
+        ]);
+        assert_eq!(args.symbol_servers, ["https://symbols.example.com", "http://localhost:8080/symbols"]);
+
+        let args = Args::parse_from(["resolve_stacktrace", "--addresses", "--load-map", "modules.txt"]);
+        assert!(args.addresses);
+        assert_eq!(args.load_map, Some(PathBuf::from("modules.txt")));
+
+        let args = Args::parse_from(["resolve_stacktrace", "--source-root", "C:\\src=/src", "--source-context", "5"]);
+        assert_eq!(args.source_roots, ["C:\\src=/src"]);
+        assert_eq!(args.source_context, Some(5));
+
+        let args = Args::parse_from(["resolve_stacktrace", "--scan", "-i", "serial.log"]);
+        assert!(args.scan);
+        assert!(Args::try_parse_from(["resolve_stacktrace", "--scan", "--addresses"]).is_err());
+
+        let args = Args::parse_from(["resolve_stacktrace"]);
+        assert_eq!(args.format, Format::Table);
+        assert!(!args.addresses);
+        assert!(args.symbol_servers.is_empty());
+        assert!(SourceContext::from_args(&args.source_roots, args.source_context).is_none());
+        assert!(Args::try_parse_from(["resolve_stacktrace", "--format", "xml"]).is_err());
     }
 
     #[test]
-    fn test_create_stack_frame_invalid_too_few_parts() {
-        let line = "00 000000cd7bbfe830 DxeCore+0x45a3";
-        assert!(create_stack_frame(line).is_none());
+    fn test_symbol_servers() {
+        let flag = vec![" https://symbols.example.com ".to_string(), String::new()];
+        assert_eq!(symbol_servers(flag, "STACKTRACE_TEST_UNSET"), ["https://symbols.example.com"]);
+        assert!(symbol_servers(vec![], "STACKTRACE_TEST_UNSET").is_empty());
     }
 
     #[test]
-    fn test_create_stack_frame_invalid_no_plus() {
-        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore0x45a3";
-        assert!(create_stack_frame(line).is_none());
+    fn test_render_text() {
+        assert_eq!(
+            render_text(&resolved_frames(), None),
+            "0 [inlined] qemu_sbsa_dxe_core!walk unwind.rs @ 30\n\
+             0 000001007E2796C0 000001007E27BBDC qemu_sbsa_dxe_core!dump+0xC stacktrace.rs @ 144\n\
+             1 000001007E2796C0 000001007E27BBDC qemu_sbsa_dxe_core!<unknown>+0x0 \
+             No debug file found for qemu_sbsa_dxe_core @ 0\n"
+        );
     }
 
     #[test]
-    fn test_create_stack_frame_invalid_hex() {
-        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0xGGGG";
-        assert!(create_stack_frame(line).is_none());
+    fn test_table_rows() {
+        let rows = table_rows(&resolved_frames(), None);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], ["0", "unwind.rs @ 30", "", "", "[inlined] qemu_sbsa_dxe_core!walk"]);
+        assert_eq!(
+            rows[1],
+            ["0", "stacktrace.rs @ 144", "000001007E2796C0", "000001007E27BBDC", "qemu_sbsa_dxe_core!dump+0xC"]
+        );
+        assert_eq!(rows[2][0], "1");
+        assert_eq!(rows[2][4], "qemu_sbsa_dxe_core!<unknown>+0x0");
     }
 
     #[test]
-    fn test_create_stack_frame_empty() {
-        assert!(create_stack_frame("").is_none());
+    fn test_render_json() {
+        let json: serde_json::Value = serde_json::from_str(&render_json(&resolved_frames(), None)).unwrap();
+        assert_eq!(json["version"], JSON_FORMAT_VERSION);
+        let frames = &json["frames"];
+        assert_eq!(frames[0]["function"], "dump");
+        assert_eq!(frames[0]["offset"], "0xC");
+        assert_eq!(frames[0]["rva"], "0x185DC");
+        assert_eq!(frames[0]["line"], 144);
+        assert_eq!(frames[0]["inlined"][0]["function"], "walk");
+        assert_eq!(frames[0]["inlined"][0]["line"], 30);
+        assert!(frames[0]["error"].is_null());
+        assert!(frames[1]["function"].is_null());
+        assert_eq!(frames[1]["inlined"], serde_json::json!([]));
+        assert_eq!(frames[1]["error"], "No debug file found for qemu_sbsa_dxe_core");
+        assert_eq!(frames[1]["warnings"][0], "signature mismatch");
+        assert_eq!(frames[1]["stale_pdb"], false);
     }
 
     #[test]
-    fn test_create_stack_frames_batch() {
-        let lines = vec![
-            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3".to_string(),
-            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000".to_string(),
-            "02 000000cd7bbfea00 00007ff6ddd67890 AnotherMod+0x2000".to_string(),
-        ];
-
-        let frames = create_stack_frames(lines);
-        assert_eq!(frames.len(), 3);
-        assert_eq!(frames[0].frame_number, "00");
-        assert_eq!(frames[1].frame_number, "01");
-        assert_eq!(frames[2].frame_number, "02");
+    fn test_address_frames() {
+        let mut stack_frames = resolved_frames();
+        stack_frames[0].frame.child_stack_pointer.clear();
+        stack_frames[0].frame.return_address.clear();
+
+        assert!(render_text(&stack_frames, None).contains("\n0 - - qemu_sbsa_dxe_core!dump+0xC"));
+        let json: serde_json::Value = serde_json::from_str(&render_json(&stack_frames, None)).unwrap();
+        assert!(json["frames"][0]["child_sp"].is_null());
+        assert!(json["frames"][0]["return_address"].is_null());
+        assert_eq!(json["frames"][1]["return_address"], "000001007E27BBDC");
     }
 
     #[test]
-    fn test_create_stack_frames_with_header() {
-        let lines = vec![
-            "# Child-SP          Return Address       Call Site".to_string(),
-            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3".to_string(),
-            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000".to_string(),
-        ];
-
-        let frames = create_stack_frames(lines);
-        // Header should be filtered out
-        assert_eq!(frames.len(), 2);
-        assert_eq!(frames[0].frame_number, "00");
+    fn test_stale_pdb_is_flagged() {
+        let mut stack_frames = resolved_frames();
+        stack_frames[0].stale_pdb = true;
+
+        let rows = table_rows(&stack_frames, None);
+        assert_eq!(rows[0][4], "[inlined] [stale PDB] qemu_sbsa_dxe_core!walk");
+        assert_eq!(rows[1][4], "[stale PDB] qemu_sbsa_dxe_core!dump+0xC");
+        assert!(render_text(&stack_frames, None).starts_with(
+            "0 [inlined] [stale PDB] qemu_sbsa_dxe_core!walk unwind.rs @ 30\n\
+             0 000001007E2796C0 000001007E27BBDC [stale PDB] qemu_sbsa_dxe_core!dump+0xC"
+        ));
+
+        let json: serde_json::Value = serde_json::from_str(&render_json(&stack_frames, None)).unwrap();
+        assert_eq!(json["frames"][0]["stale_pdb"], true);
     }
 
     #[test]
-    fn test_create_stack_frames_with_empty_lines() {
-        let lines = vec![
-            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3".to_string(),
-            "".to_string(),
-            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000".to_string(),
-            "   ".to_string(),
-        ];
-
-        let frames = create_stack_frames(lines);
-        assert_eq!(frames.len(), 2);
+    fn test_source_context() {
+        assert_eq!(SourceContext::from_args(&["/src".to_string()], None).unwrap().lines, DEFAULT_SOURCE_CONTEXT);
+        assert_eq!(SourceContext::from_args(&[], Some(1)).unwrap().lines, 1);
+
+        let directory = std::env::temp_dir().join(format!("resolve_stacktrace_source_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let lines = |count| (1..=count).map(|line| format!("line {line}")).collect::<Vec<_>>().join("\n");
+        fs::write(directory.join("stacktrace.rs"), lines(150)).unwrap();
+        fs::write(directory.join("unwind.rs"), lines(40)).unwrap();
+
+        let source = SourceContext::from_args(&[directory.display().to_string()], Some(1)).unwrap();
+        let stack_frames = resolved_frames();
+
+        let rows = table_rows(&stack_frames, Some(&source));
+        assert_eq!(rows[0][1], "unwind.rs @ 30\n  29 | line 29\n> 30 | line 30\n  31 | line 31");
+        assert_eq!(rows[1][1], "stacktrace.rs @ 144\n  143 | line 143\n> 144 | line 144\n  145 | line 145");
+        assert!(rows[2][1].starts_with("No debug file found"));
+
+        assert!(
+            render_text(&stack_frames, Some(&source)).contains(
+                "qemu_sbsa_dxe_core!dump+0xC stacktrace.rs @ 144\n      143 | line 143\n    > 144 | line 144\n"
+            )
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&render_json(&stack_frames, Some(&source))).unwrap();
+        assert_eq!(json["frames"][0]["source"]["first_line"], 143);
+        assert_eq!(json["frames"][0]["source"]["lines"][1], "line 144");
+        assert_eq!(json["frames"][0]["inlined"][0]["source"]["first_line"], 29);
+        assert!(json["frames"][1]["source"].is_null());
+
+        fs::remove_dir_all(&directory).unwrap();
     }
 
     #[test]
-    fn test_create_stack_frames_filters_invalid() {
-        let lines = vec![
-            "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3".to_string(),
-            "invalid line".to_string(),
-            "01 000000cd7bbfe900 00007ff6ddd12345 TestMod+0x1000".to_string(),
+    fn test_render_crashes() {
+        let crash = |line, reason: Option<&str>, complete| LoggedTrace {
+            line,
+            reason: reason.map(str::to_string),
+            complete,
+            trace: StackTrace::default(),
+        };
+        let crashes = vec![
+            (crash(120, Some("ERROR - Unhandled Exception! 0xE"), true), resolved_frames()),
+            (crash(4000, None, false), resolved_frames()[1..].to_vec()),
         ];
 
-        let frames = create_stack_frames(lines);
-        assert_eq!(frames.len(), 2);
-        assert_eq!(frames[0].frame_number, "00");
-        assert_eq!(frames[1].frame_number, "01");
-    }
-
-    #[test]
-    fn test_create_stack_frames_empty_input() {
-        let lines: Vec<String> = vec![];
-        let frames = create_stack_frames(lines);
-        assert_eq!(frames.len(), 0);
-    }
-
-    #[test]
-    fn test_stack_frame_debug() {
-        let line = "00 000000cd7bbfe830 00007ff6ddd0b4ae DxeCore+0x45a3";
-        let frame = create_stack_frame(line).unwrap();
-
-        let debug_str = format!("{:?}", frame);
-        assert!(debug_str.contains("StackFrame"));
+        let text = render_crashes(&crashes, Format::Text, None);
+        assert!(text.starts_with(
+            "Crash 1 at line 120: ERROR - Unhandled Exception! 0xE\n0 [inlined] qemu_sbsa_dxe_core!walk unwind.rs @ 30\n"
+        ));
+        assert!(text.contains("\n\nCrash 2 at line 4000: no crash message found (trace incomplete)\n1 "));
+
+        let json: serde_json::Value = serde_json::from_str(&render_crashes(&crashes, Format::Json, None)).unwrap();
+        assert_eq!(json["version"], JSON_FORMAT_VERSION);
+        assert_eq!(json["crashes"][0]["line"], 120);
+        assert_eq!(json["crashes"][0]["reason"], "ERROR - Unhandled Exception! 0xE");
+        assert_eq!(json["crashes"][0]["frames"][0]["function"], "dump");
+        assert!(json["crashes"][1]["reason"].is_null());
+        assert_eq!(json["crashes"][1]["complete"], false);
+        assert_eq!(json["crashes"][1]["frames"].as_array().unwrap().len(), 1);
     }
 }
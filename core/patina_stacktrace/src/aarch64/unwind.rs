@@ -66,6 +66,17 @@ use crate::{
     stacktrace::StackFrame,
 };
 
+/// Number of virtual address bits in use. Firmware runs in the lower half of
+/// a 48-bit address space, so higher bits of a signed return address hold its
+/// pointer authentication code.
+const VIRTUAL_ADDRESS_BITS: u32 = 48;
+
+/// Removes the pointer authentication code from a return address signed with
+/// `pacibsp`.
+fn strip_pac(address: u64) -> u64 {
+    address & ((1 << VIRTUAL_ADDRESS_BITS) - 1)
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum FrameChainMode {
@@ -463,6 +474,7 @@ impl UnwindCode {
     ) -> StResult<StackFrame> {
         let mut prev_sp = stack_frame.sp;
         let mut prev_pc = stack_frame.pc;
+        let mut prev_fp = stack_frame.fp;
 
         let mut integer_save_size = reg_i;
         if cr == FrameChainMode::UnchainedSavedLr {
@@ -490,11 +502,6 @@ impl UnwindCode {
 
         log::debug!("    > IN(packed): {}", stack_frame); // debug
 
-        if cr == FrameChainMode::ChainedWithPac {
-            log::error!("   > PAC-sign return address encountered");
-            return Err(Error::UnexpectedUnwindCode { module: None });
-        }
-
         let mut sav_slot = 0;
         let mut save_predec_done = false; // This account for pre-decrement stack save operation
 
@@ -567,7 +574,16 @@ impl UnwindCode {
                 }
 
                 log::debug!("    > set_fp");
-                // fp_set = true;
+
+                // Either way, the <x29,lr> frame record sits at the bottom of
+                // the frame.
+                // SAFETY: `prev_sp` points to the active stack frame supplied by the
+                // caller, and a chained packed prolog stores FP and LR contiguously
+                // at SP once the locals are allocated.
+                (prev_fp, prev_pc) = unsafe { (read_pointer64(prev_sp)?, read_pointer64(prev_sp + 8)?) };
+                if cr == FrameChainMode::ChainedWithPac {
+                    prev_pc = strip_pac(prev_pc);
+                }
             } else {
                 // +0028 add  sp,sp,#20           ; Actual=add   sp,sp,#0x20
                 // +002C ldr  lr,[sp,#0x0]        ; Actual=ldr   lr,[sp],#0x10
@@ -582,7 +598,7 @@ impl UnwindCode {
 
         prev_sp += frame_size as u64;
 
-        let prev_stack_frame = StackFrame { sp: prev_sp, pc: prev_pc, ..*stack_frame };
+        let prev_stack_frame = StackFrame { sp: prev_sp, pc: prev_pc, fp: prev_fp };
         log::debug!("    > OUT(packed): {}", prev_stack_frame); // debug
         Ok(prev_stack_frame)
     }
@@ -599,6 +615,7 @@ impl UnwindCode {
         let mut prev_sp = stack_frame.sp;
         let mut prev_pc = stack_frame.pc;
         let mut prev_fp = stack_frame.fp;
+        let mut pac_signed = false;
 
         log::debug!("    > IN(unpacked): {}", stack_frame); // debug
 
@@ -886,6 +903,7 @@ impl UnwindCode {
 
                 log::debug!("    > {}", UnwindCode::PacSignLr); // debug
 
+                pac_signed = true;
                 i += 1;
             } else if byte == 0b11100111 {
                 // Reserved1 -> 11100111
@@ -1001,6 +1019,10 @@ impl UnwindCode {
             log::debug!("    > prev_pc: {prev_pc:016X} prev_sp: {prev_sp:016X} prev_fp: {prev_fp:016X}"); // debug
         }
 
+        if pac_signed {
+            prev_pc = strip_pac(prev_pc);
+        }
+
         let prev_stack_frame = StackFrame { sp: prev_sp, pc: prev_pc, fp: prev_fp };
         log::debug!("    > OUT(unpacked): {}", prev_stack_frame); // debug
         Ok(prev_stack_frame)
@@ -1129,13 +1151,15 @@ mod tests {
     }
 
     #[test]
-    fn get_previous_stack_frame_packed_cr_two_errors() {
-        let stack = [0u64; 1];
+    fn get_previous_stack_frame_packed_cr_two_strips_pac() {
+        let stack = [0x8000_1000u64, 0x002A_0000_8000_2468, 0, 0]; // saved fp, PAC-signed lr
         let frame = StackFrame { sp: stack.as_ptr() as u64, pc: 0x1234, fp: 0 };
-        match UnwindCode::get_previous_stack_frame_packed(16, FrameChainMode::ChainedWithPac, 0, 0, 0, &frame) {
-            Err(err) => assert_eq!(err, Error::UnexpectedUnwindCode { module: None }),
-            Ok(_) => panic!("expected error for PAC-signed return address"),
-        }
+        let prev =
+            UnwindCode::get_previous_stack_frame_packed(32, FrameChainMode::ChainedWithPac, 0, 0, 0, &frame).unwrap();
+
+        assert_eq!(prev.pc, 0x8000_2468);
+        assert_eq!(prev.fp, 0x8000_1000);
+        assert_eq!(prev.sp, frame.sp + 32);
     }
 
     #[test]
@@ -1284,7 +1308,7 @@ mod tests {
         let lr_from_save_regx = 0x7777_7777_7777_7777u64;
         stack_words[8] = lr_from_save_regx;
 
-        let lr_from_save_lr_pair = 0x8888_8888_8888_8888u64;
+        let lr_from_save_lr_pair = 0x0000_8888_8888_8888u64; // below bit 48, so PacSignLr leaves it intact
         stack_words[11] = lr_from_save_lr_pair;
 
         stack_words[saved_fp_regular_index] = 0;
@@ -1424,17 +1448,30 @@ mod tests {
         let frame_size = 96; // 0x60
         let mut stack = vec![0u64; 64];
         let base_ptr = stack.as_mut_ptr() as u64;
-        stack[6] = 0x1111_2222_3333_4444;
-        stack[7] = 0x9999_AAAA_BBBB_CCCC;
+        stack[0] = 0x1111_2222_3333_4444;
+        stack[1] = 0x9999_AAAA_BBBB_CCCC;
 
         let frame = StackFrame { sp: base_ptr, pc: 0x1010_2020_3030_4040, fp: 0 };
         let prev = UnwindCode::get_previous_stack_frame_packed(frame_size, FrameChainMode::Chained, 0, 0, 0, &frame)
             .expect("packed unwind should succeed");
 
-        assert_eq!(prev.pc, frame.pc);
+        assert_eq!(prev.pc, 0x9999_AAAA_BBBB_CCCC);
+        assert_eq!(prev.fp, 0x1111_2222_3333_4444);
         assert_eq!(prev.sp, frame.sp + frame_size as u64);
     }
 
+    #[test]
+    fn get_previous_stack_frame_unpacked_strips_pac() {
+        let stack = [0x8000_1000u64, 0x0055_0000_8000_2468];
+        let frame = StackFrame { sp: stack.as_ptr() as u64, pc: 0x7777, fp: 0 };
+        let codes = [0x81, 0xFC, 0xE4]; // SaveFpLrX(1), PacSignLr, End
+        let prev = UnwindCode::get_previous_stack_frame_unpacked(&codes, &frame).unwrap();
+
+        assert_eq!(prev.pc, 0x8000_2468);
+        assert_eq!(prev.fp, 0x8000_1000);
+        assert_eq!(prev.sp, frame.sp + 16);
+    }
+
     #[test]
     fn get_previous_stack_frame_unpacked_handles_endc() {
         let codes = [0xE5];
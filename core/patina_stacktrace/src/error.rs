@@ -8,7 +8,7 @@
 use core::fmt;
 
 /// The error type for stacktrace operations.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
     /// Attempted to read past the end of a backing buffer while decoding structured data.
     OutOfBoundsRead {
@@ -71,6 +71,12 @@ pub enum Error {
         /// Total bytes available in the buffer.
         available: usize,
     },
+
+    /// The frame pointer does not lead to a plausible frame record, so the frame-pointer chain cannot be followed.
+    InvalidFramePointer {
+        /// The frame pointer that was rejected.
+        fp: u64,
+    },
 }
 
 impl fmt::Display for Error {
@@ -122,6 +128,9 @@ impl fmt::Display for Error {
                     requested, available
                 )
             }
+            Error::InvalidFramePointer { fp } => {
+                write!(fmt, "Frame pointer {fp:X} does not lead to a valid frame record")
+            }
         }
     }
 }
@@ -228,6 +237,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn invalid_frame_pointer_display() {
+        assert_display(
+            Error::InvalidFramePointer { fp: 0x7E96F930 },
+            "Frame pointer 7E96F930 does not lead to a valid frame record",
+        );
+    }
+
     #[test]
     fn with_module_exercises_all_paths() {
         let fallback = Some("fallback");
@@ -47,10 +47,16 @@
 //!
 //! `RUSTFLAGS=-Cforce-unwind-tables`
 //!
+//! Frames in images without usable unwind information are unwound by following
+//! the frame-pointer chain instead. Traces taken in an exception handler
+//! continue into the interrupted code once the exception entry stub is
+//! registered with `StackTrace::register_trap_frame`.
+//!
 //! ## Public API
 //!
 //! The primary public API is the `dump()/dump_with()` function in the
-//! `StackTrace` module.
+//! `StackTrace` module. `walk()/walk_with()` visit the same frames without
+//! logging, for callers that want to report them elsewhere.
 //!
 //! ```ignore
 //!    /// Dumps the stack trace for the given PC, SP, and FP values.
@@ -124,6 +130,7 @@ mod byte_reader;
 pub mod error;
 mod pe;
 mod stacktrace;
+pub mod symbols;
 
 cfg_if::cfg_if! {
     if #[cfg(test)] {
@@ -136,4 +143,5 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use stacktrace::{StackFrame, StackTrace};
+pub use pe::BuildId;
+pub use stacktrace::{Frame, StackFrame, StackTrace, TrapFrameLayout};
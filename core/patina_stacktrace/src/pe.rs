@@ -24,8 +24,39 @@ const DEBUG_RECORD_SIZE: usize = 0x10;
 const DEBUG_RECORD_TYPE_OFFSET: usize = 0xC;
 const DEBUG_RECORD_TYPE_CODEVIEW: u32 = 0x2; // 2 => The Visual C++ debug information.
 const CODEVIEW_PDB70_SIGNATURE: u32 = 0x5344_5352; // RSDS
+const CODEVIEW_PDB_GUID_OFFSET: usize = 0x4;
+const CODEVIEW_PDB_AGE_OFFSET: usize = 0x14;
 const CODEVIEW_PDB_FILE_NAME_OFFSET: usize = 0x18;
 
+/// The PDB GUID and age recorded in an image's CodeView entry, identifying the
+/// PDB the image was linked with.
+///
+/// It is displayed as in a symbol store path: the GUID as 32 hexadecimal
+/// digits without separators, followed by the age in hexadecimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildId {
+    /// The PDB GUID, in its in-memory (mixed-endian) byte order.
+    pub guid: [u8; 16],
+
+    /// The PDB age.
+    pub age: u32,
+}
+
+impl fmt::Display for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let g = &self.guid;
+        write!(
+            f,
+            "{:08X}{:04X}{:04X}",
+            u32::from_le_bytes([g[0], g[1], g[2], g[3]]),
+            u16::from_le_bytes([g[4], g[5]]),
+            u16::from_le_bytes([g[6], g[7]])
+        )?;
+        g[8..].iter().try_for_each(|byte| write!(f, "{byte:02X}"))?;
+        write!(f, "{:X}", self.age)
+    }
+}
+
 /// Provides in-memory PE file parsing utilities.
 #[derive(Clone)]
 pub struct PE<'a> {
@@ -38,6 +69,9 @@ pub struct PE<'a> {
     /// Image name extracted from the loaded PE image.
     pub image_name: Option<&'static str>,
 
+    /// PDB GUID and age extracted from the loaded PE image.
+    pub build_id: Option<BuildId>,
+
     /// Loaded image memory as a byte slice.
     pub(crate) bytes: &'a [u8],
 }
@@ -98,20 +132,25 @@ impl PE<'_> {
                     let debug_directory_size =
                         page.read32(pe_header_offset + DEBUG_DIRECTORY_POINTER_PE64_OFFSET + 4).unwrap_or(0) as usize;
 
-                    // Identify the image name.
-                    let image_name = if debug_directory_size != 0 {
+                    // Identify the image name and the PDB it was linked with.
+                    let (image_name, build_id) = if debug_directory_size != 0 {
                         // SAFETY: `rip` still denotes the mapped image base, and the computed
                         // debug-directory range lies within that mapping per PE header offsets.
-                        unsafe { Self::get_image_name(rip, debug_directory_rva, debug_directory_size) }
+                        unsafe {
+                            (
+                                Self::get_image_name(rip, debug_directory_rva, debug_directory_size),
+                                Self::get_build_id(rip, debug_directory_rva, debug_directory_size),
+                            )
+                        }
                     } else {
-                        None
+                        (None, None)
                     };
 
                     // SAFETY: The caller ensures the mapped image remains readable;
                     // `rip` is still page-aligned and within that mapping.
                     let bytes = unsafe { core::slice::from_raw_parts(rip as *const u8, size_of_image as usize) };
 
-                    return Ok(Self { base_address: rip, _size_of_image: size_of_image, image_name, bytes });
+                    return Ok(Self { base_address: rip, _size_of_image: size_of_image, image_name, build_id, bytes });
                 }
             }
 
@@ -123,15 +162,16 @@ impl PE<'_> {
         Err(Error::ImageNotFound { rip: original_rip })
     }
 
-    /// Private helper that locates the image name in memory.
+    /// Private helper that locates the CodeView PDB 7.0 record of the image in
+    /// memory.
     // SAFETY: `page_base` must reference the same mapped image passed to
     // `locate_image`. The caller guarantees that the debug directory and its
     // derived ranges are readable for the duration of this routine.
-    unsafe fn get_image_name(
+    unsafe fn get_codeview_record(
         page_base: u64,
         debug_directory_rva: usize,
         debug_directory_size: usize,
-    ) -> Option<&'static str> {
+    ) -> Option<&'static [u8]> {
         // Convert the debug data section into a slice to make it easier to interpret the fields.
         // SAFETY: The caller guarantees that `page_base + debug_directory_rva` points to
         // a readable region of length `debug_directory_size`.
@@ -160,32 +200,37 @@ impl PE<'_> {
             return None;
         };
 
-        if debug_data_rva == 0 || debug_data_size == 0 {
+        // The record must at least hold the signature, GUID, and age.
+        if debug_data_rva == 0 || (debug_data_size as usize) < CODEVIEW_PDB_FILE_NAME_OFFSET {
             return None;
         };
 
-        let debug_data = page_base + debug_data_rva as u64;
+        // SAFETY: The caller guarantees that the CodeView record, including the
+        // file-name payload, is fully mapped and readable.
+        let record = unsafe {
+            core::slice::from_raw_parts((page_base + debug_data_rva as u64) as *const u8, debug_data_size as usize)
+        };
 
         // Check the CodeView signature.
-        // SAFETY: `debug_data` is within the caller-provided PE image and points to
-        // the beginning of the CodeView structure.
-        let codeview_signature = unsafe { *(debug_data as *const u32) };
-        if codeview_signature != CODEVIEW_PDB70_SIGNATURE {
+        if record.read32(0).ok()? != CODEVIEW_PDB70_SIGNATURE {
             return None;
         }
 
-        // Extract the PDB file path.
-        // SAFETY: The caller guarantees that the CodeView record, including the
-        // file-name payload, is fully mapped and readable.
-        let file_name_bytes = unsafe {
-            core::slice::from_raw_parts(
-                (debug_data + CODEVIEW_PDB_FILE_NAME_OFFSET as u64) as *const u8,
-                debug_data_size as usize - CODEVIEW_PDB_FILE_NAME_OFFSET,
-            )
-        };
+        Some(record)
+    }
+
+    /// Private helper that locates the image name in memory.
+    // SAFETY: Same requirements as `get_codeview_record`.
+    unsafe fn get_image_name(
+        page_base: u64,
+        debug_directory_rva: usize,
+        debug_directory_size: usize,
+    ) -> Option<&'static str> {
+        // SAFETY: The caller upholds the requirements of `get_codeview_record`.
+        let record = unsafe { Self::get_codeview_record(page_base, debug_directory_rva, debug_directory_size) }?;
 
         // Extract the PDB file name. This should be the image name.
-        let Ok(file_name) = core::str::from_utf8(file_name_bytes) else {
+        let Ok(file_name) = core::str::from_utf8(&record[CODEVIEW_PDB_FILE_NAME_OFFSET..]) else {
             return None;
         };
         if let Some(file_name_with_ext) = file_name.rsplit('\\').next()
@@ -194,11 +239,21 @@ impl PE<'_> {
             return Some(file_name);
         }
 
-        // log::info!("Pdb file name : {}", file_name);
-
         Some(file_name)
     }
 
+    /// Private helper that reads the PDB GUID and age the image was linked
+    /// with from its CodeView record.
+    // SAFETY: Same requirements as `get_codeview_record`.
+    unsafe fn get_build_id(page_base: u64, debug_directory_rva: usize, debug_directory_size: usize) -> Option<BuildId> {
+        // SAFETY: The caller upholds the requirements of `get_codeview_record`.
+        let record = unsafe { Self::get_codeview_record(page_base, debug_directory_rva, debug_directory_size) }?;
+
+        let guid = record[CODEVIEW_PDB_GUID_OFFSET..CODEVIEW_PDB_AGE_OFFSET].try_into().ok()?;
+        let age = record.read32(CODEVIEW_PDB_AGE_OFFSET).ok()?;
+        Some(BuildId { guid, age })
+    }
+
     // SAFETY: `self.bytes` refers to raw image memory supplied by the runtime.
     // The caller must ensure that the PE headers referenced by this method are
     // readable for the duration of the call.
@@ -285,6 +340,14 @@ mod tests {
         let debug_data_offset = debug_data_rva as usize;
         bytes[debug_data_offset..debug_data_offset + 4].copy_from_slice(&CODEVIEW_PDB70_SIGNATURE.to_le_bytes());
 
+        // PDB GUID {3F2504E0-4F89-11D3-9A0C-0305E82C3301} and age 1
+        let guid_off = debug_data_offset + CODEVIEW_PDB_GUID_OFFSET;
+        bytes[guid_off..guid_off + 16].copy_from_slice(&[
+            0xE0, 0x04, 0x25, 0x3F, 0x89, 0x4F, 0xD3, 0x11, 0x9A, 0x0C, 0x03, 0x05, 0xE8, 0x2C, 0x33, 0x01,
+        ]);
+        let age_off = debug_data_offset + CODEVIEW_PDB_AGE_OFFSET;
+        bytes[age_off..age_off + 4].copy_from_slice(&1u32.to_le_bytes());
+
         // Insert a fake PDB path (RSDS... + "C:\\path\\app.exe\0")
         let fake_pdb_path = b"C:\\path\\app.exe\0";
         let name_off = debug_data_offset + CODEVIEW_PDB_FILE_NAME_OFFSET;
@@ -298,7 +361,13 @@ mod tests {
         let bytes = make_fake_pe_image();
         let base = bytes.as_ptr() as u64;
 
-        let pe = PE { base_address: base, _size_of_image: bytes.len() as u32, image_name: Some("fake"), bytes: &bytes };
+        let pe = PE {
+            base_address: base,
+            _size_of_image: bytes.len() as u32,
+            image_name: Some("fake"),
+            build_id: None,
+            bytes: &bytes,
+        };
 
         // Since we didn't define exception table fields, expect an error.
         // SAFETY: Test creates a fake PE image structure for validation; `pe.bytes` points to a valid slice
@@ -315,6 +384,16 @@ mod tests {
         assert_eq!(image_name, Some("app"));
     }
 
+    #[test]
+    fn test_get_build_id_success() {
+        let bytes = make_fake_pe_image();
+        let base = bytes.as_ptr() as u64;
+        // SAFETY: Test creates a fake PE image with valid debug directory at specified RVA.
+        let build_id = unsafe { PE::get_build_id(base, 0x400, 0x1C) }.unwrap();
+        assert_eq!(build_id.age, 1);
+        assert_eq!(build_id.to_string(), "3F2504E04F8911D39A0C0305E82C33011");
+    }
+
     #[test]
     fn test_get_image_name_failure_invalid_signature() {
         let mut bytes = make_fake_pe_image();
@@ -325,5 +404,7 @@ mod tests {
         // SAFETY: Test creates a fake PE image with corrupted signature for validation.
         let image_name = unsafe { PE::get_image_name(base, 0x400, 0x1C) };
         assert_eq!(image_name, None);
+        // SAFETY: Test creates a fake PE image with corrupted signature for validation.
+        assert_eq!(unsafe { PE::get_build_id(base, 0x400, 0x1C) }, None);
     }
 }
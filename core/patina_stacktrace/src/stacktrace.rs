@@ -1,4 +1,8 @@
-use crate::{error::StResult, pe::PE};
+use crate::{
+    byte_reader::read_pointer64,
+    error::{Error, StResult},
+    pe::{BuildId, PE},
+};
 use core::{
     arch::asm,
     fmt::{self, Display, Formatter},
@@ -33,7 +37,94 @@ impl Display for StackFrame {
     }
 }
 
+/// Describes where an exception entry stub saves the interrupted context, so
+/// that a walk through an exception handler continues into the code the
+/// exception interrupted.
+///
+/// Registered once by the code that installs the entry stub, with
+/// [`StackTrace::register_trap_frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapFrameLayout {
+    /// The address in the entry stub that the exception handler returns to.
+    pub return_address: u64,
+
+    /// The offset of the saved context from the entry stub's stack pointer at
+    /// `return_address`.
+    pub context_offset: u64,
+
+    /// The offset of the interrupted PC within the saved context.
+    pub pc_offset: u64,
+
+    /// The offset of the interrupted SP within the saved context.
+    pub sp_offset: u64,
+
+    /// The offset of the interrupted FP within the saved context.
+    pub fp_offset: u64,
+}
+
+impl TrapFrameLayout {
+    /// Reads the interrupted frame from the context saved by the entry stub,
+    /// given the stub's stack pointer. Returns the address of the saved context
+    /// along with the frame.
+    ///
+    /// # Safety
+    ///
+    /// `sp` must be the stack pointer of a live entry stub frame at
+    /// `return_address`, so the saved context is readable.
+    unsafe fn interrupted_frame(&self, sp: u64) -> StResult<(u64, StackFrame)> {
+        let context = sp + self.context_offset;
+        // SAFETY: The caller guarantees the saved context at `context` is
+        // readable, and the offsets lie within it.
+        let frame = unsafe {
+            StackFrame {
+                pc: read_pointer64(context + self.pc_offset)?,
+                sp: read_pointer64(context + self.sp_offset)?,
+                fp: read_pointer64(context + self.fp_offset)?,
+            }
+        };
+        Ok((context, frame))
+    }
+}
+
+/// The exception entry stub registered with [`StackTrace::register_trap_frame`].
+static TRAP_FRAME: spin::Once<TrapFrameLayout> = spin::Once::new();
+
+/// The largest distance between a frame pointer and the stack pointer or the
+/// next frame pointer accepted when following the frame-pointer chain.
+const MAX_FRAME_SIZE: u64 = 0x10_0000;
+
 impl StackFrame {
+    /// Unwinds the frame by following the frame-pointer chain instead of unwind
+    /// info. Both x64 (RBP) and AArch64 (x29) frame records hold the caller's
+    /// frame pointer followed by the return address, and the caller's stack
+    /// pointer is just past the record.
+    ///
+    /// The frame pointer must lie above the stack pointer and the saved frame
+    /// pointer above the record, each within [`MAX_FRAME_SIZE`], so that a
+    /// register not used as a frame pointer ends the walk rather than sending it
+    /// through arbitrary memory. A saved frame pointer of zero marks the
+    /// outermost frame.
+    ///
+    /// # Safety
+    ///
+    /// A frame pointer that passes the checks above must reference readable
+    /// stack memory.
+    unsafe fn unwind_frame_pointer(&self) -> StResult<StackFrame> {
+        let fp = self.fp;
+        if fp == 0 || !fp.is_multiple_of(8) || fp < self.sp || fp - self.sp > MAX_FRAME_SIZE {
+            return Err(Error::InvalidFramePointer { fp });
+        }
+
+        // SAFETY: `fp` is non-null, aligned, and within the stack above `sp`,
+        // which the caller guarantees is readable.
+        let (prev_fp, prev_pc) = unsafe { (read_pointer64(fp)?, read_pointer64(fp + 8)?) };
+        if prev_fp != 0 && (prev_fp <= fp || prev_fp - fp > MAX_FRAME_SIZE) {
+            return Err(Error::InvalidFramePointer { fp: prev_fp });
+        }
+
+        Ok(StackFrame { pc: prev_pc, sp: fp + 16, fp: prev_fp })
+    }
+
     fn end_of_stack(&self) -> bool {
         cfg_if::cfg_if! {
             if #[cfg(target_arch = "aarch64")] {
@@ -45,6 +136,110 @@ impl StackFrame {
     }
 }
 
+/// A single frame visited while walking the stack.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    /// The position of the frame, starting from zero for the innermost frame.
+    pub index: usize,
+
+    /// The stack pointer (SP) of the frame.
+    pub sp: u64,
+
+    /// The address the frame returns to.
+    pub return_address: u64,
+
+    /// The name of the image containing the frame's PC, if known.
+    pub image_name: Option<&'static str>,
+
+    /// The PDB GUID and age of the image containing the frame's PC, if known.
+    pub build_id: Option<BuildId>,
+
+    /// The load address of the image containing the frame's PC, if known.
+    pub image_base: Option<u64>,
+
+    /// The size of the image containing the frame's PC in memory, or zero when
+    /// the image is not known.
+    pub image_size: u32,
+
+    /// The offset of the frame's PC within its image, or the PC itself when the
+    /// image is not known.
+    pub pc_rva: u64,
+
+    /// Whether the return address was found by following the frame-pointer
+    /// chain because the frame's image has no usable unwind info.
+    pub frame_pointer: bool,
+
+    /// The address of the saved context when the frame is the exception entry
+    /// stub, in which case the return address is the interrupted PC and the
+    /// next frame is the interrupted code.
+    pub trap_frame: Option<u64>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{:X}", self.image_name.unwrap_or("<no module>"), self.pc_rva)
+    }
+}
+
+/// The most images listed in the module table of a stack dump.
+const MAX_DUMP_MODULES: usize = 32;
+
+/// The images seen while dumping a stack trace, listed after the frames so
+/// that absolute addresses can be mapped back to their images offline.
+struct ModuleTable {
+    frames: [Option<Frame>; MAX_DUMP_MODULES],
+    count: usize,
+}
+
+impl ModuleTable {
+    const fn new() -> Self {
+        Self { frames: [None; MAX_DUMP_MODULES], count: 0 }
+    }
+
+    /// Records the image of `frame`, unless it is unknown or already recorded.
+    fn add(&mut self, frame: &Frame) {
+        let Some(base) = frame.image_base else {
+            return;
+        };
+        let known = self.frames[..self.count].iter().flatten().any(|module| module.image_base == Some(base));
+        if !known && self.count < MAX_DUMP_MODULES {
+            self.frames[self.count] = Some(*frame);
+            self.count += 1;
+        }
+    }
+
+    /// Logs one `module <base> <size> <build id> <name>` line per image, with
+    /// `-` for an unknown build ID.
+    fn dump(&self) {
+        if self.count == 0 {
+            return;
+        }
+        log::warn!("Modules:");
+        for frame in self.frames[..self.count].iter().flatten() {
+            let build_id = BuildIdOrDash(frame.build_id);
+            log::warn!(
+                "  module {:016X} {:08X} {} {}",
+                frame.image_base.unwrap_or_default(),
+                frame.image_size,
+                build_id,
+                frame.image_name.unwrap_or("<unknown>")
+            );
+        }
+    }
+}
+
+/// Displays a build ID, or `-` when it is not known.
+struct BuildIdOrDash(Option<BuildId>);
+
+impl Display for BuildIdOrDash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(build_id) => write!(f, "{build_id}"),
+            None => f.write_str("-"),
+        }
+    }
+}
+
 /// A structure representing a stack trace.
 pub struct StackTrace;
 
@@ -69,36 +264,134 @@ impl StackTrace {
     /// 6 0000005E2AEFFD10      00007FFB8FF95AEC       kernel32+12310
     /// 7 0000005E2AEFFD50      0000000000000000       ntdll+75AEC
     /// ```
+    ///
+    /// Each frame in a different image than the frame before it is preceded by
+    /// a `build-id <module> <PDB GUID and age>` line, which lets offline
+    /// resolvers confirm that they use the PDB the image was linked with. A
+    /// frame unwound through the frame-pointer chain is preceded by a note
+    /// saying so, and an exception entry stub frame is followed by a
+    /// `-- TrapFrame @ <context> --` line marking where the interrupted code
+    /// begins.
     #[coverage(off)]
     #[inline(never)]
-    pub unsafe fn dump_with(mut stack_frame: StackFrame) -> StResult<()> {
-        let mut i = 0;
-
+    pub unsafe fn dump_with(stack_frame: StackFrame) -> StResult<()> {
         log::warn!("Dumping stack trace with {}", stack_frame);
 
         log::warn!("      # Child-SP              Return Address         Call Site");
 
-        loop {
-            let no_name = "<no module>";
+        let mut previous_image = None;
+        let mut modules = ModuleTable::new();
+        // SAFETY: The caller upholds the requirements of `dump_with`, which are the
+        // same as those of `walk_with`.
+        let result = unsafe {
+            StackTrace::walk_with(stack_frame, |frame| {
+                modules.add(frame);
+                if let (Some(image_name), Some(build_id)) = (frame.image_name, frame.build_id)
+                    && previous_image != Some(image_name)
+                {
+                    log::warn!("build-id {} {}", image_name, build_id);
+                }
+                previous_image = frame.image_name;
+                if frame.frame_pointer {
+                    log::warn!("No unwind info for frame {}, following the frame-pointer chain", frame.index);
+                }
+                log::warn!(
+                    "     {:>2} {:016X}      {:016X}       {}+{:X}",
+                    frame.index,
+                    frame.sp,
+                    frame.return_address,
+                    frame.image_name.unwrap_or("<no module>"),
+                    frame.pc_rva
+                );
+                if let Some(context) = frame.trap_frame {
+                    log::warn!("        -- TrapFrame @ {:016X} --", context);
+                }
+                log::debug!("======================================================================="); // debug
+                true
+            })
+        };
+
+        modules.dump();
+        result?;
+
+        log::warn!("Finished dumping stack trace");
+        Ok(())
+    }
 
-            // SAFETY: The caller of `dump_with` supplies a valid PC captured from
+    /// Walks the stack for the given PC, SP, and FP values, calling `visit` for
+    /// each frame from the innermost outwards. The walk stops when the end of the
+    /// stack is reached or `visit` returns `false`.
+    ///
+    /// Frames in images without usable unwind info, such as third-party option
+    /// ROMs built without `.pdata`, are unwound by following the frame-pointer
+    /// chain instead, and are marked with [`Frame::frame_pointer`]. The walk only
+    /// fails if neither method can unwind a frame.
+    ///
+    /// When the walk reaches the exception entry stub registered with
+    /// [`StackTrace::register_trap_frame`], it continues from the interrupted
+    /// context saved by the stub, and marks the stub frame with
+    /// [`Frame::trap_frame`].
+    ///
+    /// # Safety
+    ///
+    /// The caller is responsible for validating the provided PC, SP, and FP
+    /// values, as for [`StackTrace::dump_with`].
+    #[coverage(off)]
+    #[inline(never)]
+    pub unsafe fn walk_with(mut stack_frame: StackFrame, mut visit: impl FnMut(&Frame) -> bool) -> StResult<()> {
+        let mut index = 0;
+
+        loop {
+            // SAFETY: The caller of `walk_with` supplies a valid PC captured from
             // a live stack frame. We rely on that guarantee to probe memory for the
             // surrounding PE image without triggering undefined behavior.
-            let image = unsafe { PE::locate_image(stack_frame.pc) }?;
-            log::debug!("{image}");
+            let image = unsafe { PE::locate_image(stack_frame.pc) };
 
-            let runtime_function = RuntimeFunction::find_function(&image, &mut stack_frame)?;
-            let unwind_info = runtime_function.get_unwind_info()?;
-            let prev_stack_frame = unwind_info.get_previous_stack_frame(&stack_frame)?;
+            let trap_frame = TRAP_FRAME.get().filter(|layout| layout.return_address == stack_frame.pc);
+            let (trap_frame, unwound) = match trap_frame {
+                Some(layout) => {
+                    // SAFETY: The PC is the stub's return address, so the stack
+                    // pointer is that of the live stub frame holding the context.
+                    match unsafe { layout.interrupted_frame(stack_frame.sp) } {
+                        Ok((context, prev_stack_frame)) => (Some(context), Ok(prev_stack_frame)),
+                        Err(err) => (None, Err(err)),
+                    }
+                }
+                None => (None, Self::unwind(image.as_ref(), &mut stack_frame)),
+            };
 
-            let pc_rva = stack_frame.pc - image.base_address;
-            let image_name = image.image_name.unwrap_or(no_name);
-            log::warn!(
-                "     {i:>2} {:016X}      {:016X}       {image_name}+{pc_rva:X}",
-                stack_frame.sp,
-                prev_stack_frame.pc
-            );
-            log::debug!("======================================================================="); // debug
+            let (prev_stack_frame, frame_pointer) = match unwound {
+                Ok(prev_stack_frame) => (prev_stack_frame, false),
+                Err(err) => {
+                    log::debug!("{err}. Falling back to the frame-pointer chain.");
+                    // SAFETY: The caller of `walk_with` guarantees the FP is either
+                    // a valid frame pointer or rejected by the range checks.
+                    match unsafe { stack_frame.unwind_frame_pointer() } {
+                        Ok(prev_stack_frame) => (prev_stack_frame, true),
+                        Err(fp_err) => {
+                            log::debug!("{fp_err}");
+                            return Err(err);
+                        }
+                    }
+                }
+            };
+
+            let image = image.ok();
+            let frame = Frame {
+                index,
+                sp: stack_frame.sp,
+                return_address: prev_stack_frame.pc,
+                image_name: image.as_ref().and_then(|image| image.image_name),
+                build_id: image.as_ref().and_then(|image| image.build_id),
+                image_base: image.as_ref().map(|image| image.base_address),
+                image_size: image.as_ref().map_or(0, |image| image._size_of_image),
+                pc_rva: stack_frame.pc - image.as_ref().map_or(0, |image| image.base_address),
+                frame_pointer,
+                trap_frame,
+            };
+            if !visit(&frame) {
+                break;
+            }
 
             if prev_stack_frame.pc == stack_frame.pc {
                 log::error!("PC didn't change. Possible stack corruption detected. Stopping stack trace.");
@@ -108,16 +401,32 @@ impl StackTrace {
             stack_frame = prev_stack_frame;
 
             if stack_frame.end_of_stack() {
-                log::warn!("Finished dumping stack trace");
                 break;
             }
 
-            i += 1;
+            index += 1;
         }
 
         Ok(())
     }
 
+    /// Unwinds a frame using the unwind info of its image.
+    fn unwind(image: Result<&PE, &Error>, stack_frame: &mut StackFrame) -> StResult<StackFrame> {
+        let image = image.map_err(Clone::clone)?;
+        log::debug!("{image}");
+
+        let runtime_function = RuntimeFunction::find_function(image, stack_frame)?;
+        let unwind_info = runtime_function.get_unwind_info()?;
+        unwind_info.get_previous_stack_frame(stack_frame)
+    }
+
+    /// Registers the exception entry stub, so that walks through an exception
+    /// handler continue into the interrupted code. Only the first registration
+    /// takes effect.
+    pub fn register_trap_frame(layout: TrapFrameLayout) {
+        TRAP_FRAME.call_once(|| layout);
+    }
+
     /// Dumps the stack trace. This function reads the PC, SP, and FP values and
     /// attempts to dump the call stack.
     ///
@@ -141,57 +450,84 @@ impl StackTrace {
     #[coverage(off)]
     #[inline(never)]
     pub unsafe fn dump() -> StResult<()> {
-        let mut stack_frame = StackFrame::default();
-
-        cfg_if::cfg_if! {
-            if #[cfg(target_arch = "aarch64")] {
-                // SAFETY: Inline assembly reads the current program counter
-                // (PC), stack pointer (SP), and frame pointer (FP). It does not
-                // modify memory or violate Rust safety invariants. The caller
-                // must ensure that using these register values is safe.
-                // SAFETY: Reading PC/SP/FP does not mutate memory and the hardware
-                // guarantees those registers exist on aarch64.
-                unsafe {
-                    asm!(
-                        "adr {pc}, .",   // Get current PC (program counter)
-                        "mov {sp}, sp",  // Get current SP (stack pointer)
-                        "mov {fp}, x29", // Get current FP (frame pointer)
-                        pc = out(reg) stack_frame.pc,
-                        sp = out(reg) stack_frame.sp,
-                        fp = out(reg) stack_frame.fp,
-                    );
-                }
-            } else {
-                // SAFETY: Inline assembly reads the current program counter
-                // (PC), stack pointer (SP), and frame pointer (FP) on x86_64.
-                // It does not modify the memory or violate Rust safety
-                // invariants. The caller must ensure that using these register
-                // values is safe.
-                // SAFETY: Reading PC/SP/FP does not mutate memory and the hardware
-                // guarantees those registers exist on x86_64.
-                unsafe {
-                    asm!(
-                        "lea {pc}, [rip]", // Get current PC (program counter)
-                        "mov {sp}, rsp",   // Get current SP (stack pointer)
-                        "mov {fp}, rbp",   // Get current FP (frame pointer) - Not used
-                        pc = out(reg) stack_frame.pc,
-                        sp = out(reg) stack_frame.sp,
-                        fp = out(reg) stack_frame.fp,
-                    );
-                }
-            }
-        }
+        let stack_frame = current_frame();
 
         // SAFETY: `stack_frame` originates from trusted register snapshots; all
         // invariants for `dump_with` are upheld locally before forwarding.
         unsafe { StackTrace::dump_with(stack_frame) }
     }
+
+    /// Walks the stack from the caller, calling `visit` for each frame. See
+    /// [`StackTrace::walk_with`].
+    ///
+    /// # Safety
+    ///
+    /// The caller is responsible for the validity of the current machine state,
+    /// as for [`StackTrace::dump`].
+    #[coverage(off)]
+    #[inline(never)]
+    pub unsafe fn walk(visit: impl FnMut(&Frame) -> bool) -> StResult<()> {
+        let stack_frame = current_frame();
+
+        // SAFETY: `stack_frame` originates from trusted register snapshots; all
+        // invariants for `walk_with` are upheld locally before forwarding.
+        unsafe { StackTrace::walk_with(stack_frame, visit) }
+    }
+}
+
+/// Reads the PC, SP, and FP of the calling function.
+#[coverage(off)]
+#[inline(always)]
+fn current_frame() -> StackFrame {
+    let mut stack_frame = StackFrame::default();
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "aarch64")] {
+            // SAFETY: Inline assembly reads the current program counter
+            // (PC), stack pointer (SP), and frame pointer (FP). It does not
+            // modify memory or violate Rust safety invariants. The caller
+            // must ensure that using these register values is safe.
+            // SAFETY: Reading PC/SP/FP does not mutate memory and the hardware
+            // guarantees those registers exist on aarch64.
+            unsafe {
+                asm!(
+                    "adr {pc}, .",   // Get current PC (program counter)
+                    "mov {sp}, sp",  // Get current SP (stack pointer)
+                    "mov {fp}, x29", // Get current FP (frame pointer)
+                    pc = out(reg) stack_frame.pc,
+                    sp = out(reg) stack_frame.sp,
+                    fp = out(reg) stack_frame.fp,
+                );
+            }
+        } else {
+            // SAFETY: Inline assembly reads the current program counter
+            // (PC), stack pointer (SP), and frame pointer (FP) on x86_64.
+            // It does not modify the memory or violate Rust safety
+            // invariants. The caller must ensure that using these register
+            // values is safe.
+            // SAFETY: Reading PC/SP/FP does not mutate memory and the hardware
+            // guarantees those registers exist on x86_64.
+            unsafe {
+                asm!(
+                    "lea {pc}, [rip]", // Get current PC (program counter)
+                    "mov {sp}, rsp",   // Get current SP (stack pointer)
+                    "mov {fp}, rbp",   // Get current FP (frame pointer) - Not used
+                    pc = out(reg) stack_frame.pc,
+                    sp = out(reg) stack_frame.sp,
+                    fp = out(reg) stack_frame.fp,
+                );
+            }
+        }
+    }
+
+    stack_frame
 }
 
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
-    use super::StackFrame;
+    use super::{Frame, MAX_DUMP_MODULES, MAX_FRAME_SIZE, ModuleTable, StackFrame, TrapFrameLayout};
+    use crate::error::Error;
 
     #[test]
     fn display_formats_hex_values() {
@@ -222,4 +558,110 @@ mod tests {
         frame.pc = 0;
         assert!(frame.end_of_stack());
     }
+
+    #[test]
+    fn frame_display_formats_call_site() {
+        let frame = Frame {
+            index: 0,
+            sp: 0x1000,
+            return_address: 0x2000,
+            image_name: Some("DxeCore"),
+            build_id: None,
+            image_base: Some(0x7E8D_4000),
+            image_size: 0x2_0000,
+            pc_rva: 0x4B0,
+            frame_pointer: false,
+            trap_frame: None,
+        };
+        assert_eq!(format!("{frame}"), "DxeCore+4B0");
+
+        let frame = Frame { image_name: None, ..frame };
+        assert_eq!(format!("{frame}"), "<no module>+4B0");
+    }
+
+    #[test]
+    fn module_table_lists_each_image_once() {
+        let frame = Frame {
+            index: 0,
+            sp: 0x1000,
+            return_address: 0x2000,
+            image_name: Some("DxeCore"),
+            build_id: None,
+            image_base: Some(0x7E8D_4000),
+            image_size: 0x2_0000,
+            pc_rva: 0x4B0,
+            frame_pointer: false,
+            trap_frame: None,
+        };
+
+        let mut modules = ModuleTable::new();
+        modules.add(&frame);
+        modules.add(&Frame { index: 1, pc_rva: 0x800, ..frame });
+        modules.add(&Frame { index: 2, image_name: None, image_base: None, image_size: 0, ..frame });
+        assert_eq!(modules.count, 1);
+
+        for base in 0..MAX_DUMP_MODULES as u64 + 1 {
+            modules.add(&Frame { image_base: Some(base * 0x1000), ..frame });
+        }
+        assert_eq!(modules.count, MAX_DUMP_MODULES);
+    }
+
+    #[test]
+    fn unwind_frame_pointer_follows_frame_records() {
+        let mut stack = Box::new([0u64; 8]);
+        let base = stack.as_ptr() as u64;
+        // Frame record at stack[2] links to the record at stack[6].
+        stack[2] = base + 6 * 8;
+        stack[3] = 0x1000;
+        stack[6] = 0;
+        stack[7] = 0x2000;
+
+        let frame = StackFrame { pc: 0x500, sp: base, fp: base + 2 * 8 };
+        // SAFETY: The frame pointers reference the live `stack` array.
+        let caller = unsafe { frame.unwind_frame_pointer() }.ok().unwrap();
+        assert_eq!((caller.pc, caller.sp, caller.fp), (0x1000, base + 4 * 8, base + 6 * 8));
+
+        // SAFETY: As above.
+        let outermost = unsafe { caller.unwind_frame_pointer() }.ok().unwrap();
+        assert_eq!((outermost.pc, outermost.sp, outermost.fp), (0x2000, base + 8 * 8, 0));
+    }
+
+    #[test]
+    fn unwind_frame_pointer_rejects_implausible_chains() {
+        let mut stack = Box::new([0u64; 4]);
+        let base = stack.as_ptr() as u64;
+
+        // The checks on the frame pointer itself reject it before it is read.
+        for fp in [0, base + 4, base - 16, base + MAX_FRAME_SIZE + 8] {
+            let frame = StackFrame { pc: 0x500, sp: base, fp };
+            // SAFETY: Rejected frame pointers are never dereferenced.
+            assert_eq!(unsafe { frame.unwind_frame_pointer() }.err(), Some(Error::InvalidFramePointer { fp }));
+        }
+
+        // A saved frame pointer must move towards the stack base.
+        let frame = StackFrame { pc: 0x500, sp: base, fp: base + 16 };
+        for saved_fp in [base, base + 16, base + 16 + MAX_FRAME_SIZE + 8] {
+            stack[2] = saved_fp;
+            // SAFETY: The frame pointer references the live `stack` array.
+            let result = unsafe { frame.unwind_frame_pointer() };
+            assert_eq!(result.err(), Some(Error::InvalidFramePointer { fp: saved_fp }));
+        }
+    }
+
+    #[test]
+    fn trap_frame_reads_interrupted_context() {
+        // A saved context of 6 words, 2 words above the stub's stack pointer.
+        let mut stack = Box::new([0u64; 8]);
+        stack[3] = 0x7E96_F930; // FP
+        stack[5] = 0x7E96_F900; // SP
+        stack[7] = 0x7E98_2668; // PC
+        let sp = stack.as_ptr() as u64;
+
+        let layout =
+            TrapFrameLayout { return_address: 0x1000, context_offset: 16, pc_offset: 40, sp_offset: 24, fp_offset: 8 };
+        // SAFETY: The saved context lies within the live `stack` array.
+        let (context, frame) = unsafe { layout.interrupted_frame(sp) }.ok().unwrap();
+        assert_eq!(context, sp + 16);
+        assert_eq!((frame.pc, frame.sp, frame.fp), (0x7E98_2668, 0x7E96_F900, 0x7E96_F930));
+    }
 }
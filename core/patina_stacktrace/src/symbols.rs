@@ -0,0 +1,248 @@
+//! Symbol Blob
+//!
+//! A symbol blob is a compact table of function start RVAs and names for a
+//! single image. It is generated at build time by the `symbol_blob` tool from
+//! the built `.efi` file so that stack frames can be named on target without
+//! carrying full PDBs in flash. The blob can either be appended to the image
+//! file or placed on its own in an FFS file.
+//!
+//! ## Format
+//!
+//! All fields are little-endian.
+//!
+//! ```text
+//! Header  : signature "PSYM" (u32) | version (u16) | reserved (u16) | count (u32) | strings size (u32)
+//! Entries : count x { rva (u32) | name offset (u32) }, sorted by RVA
+//! Strings : NUL-terminated UTF-8 names, referenced by offset from the start of the strings
+//! ```
+//!
+//! When the blob is appended to an image, it is followed by a footer holding
+//! the blob size (u32) and the signature (u32), so it can be found from the end
+//! of the file.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    byte_reader::ByteReader,
+    error::{Error, StResult},
+};
+
+/// Signature at the start of a symbol blob and at the end of its footer.
+pub const SYMBOL_BLOB_SIGNATURE: u32 = u32::from_le_bytes(*b"PSYM");
+
+/// Version of the symbol blob format produced by this crate.
+pub const SYMBOL_BLOB_VERSION: u16 = 1;
+
+/// Size of the symbol blob header.
+pub const SYMBOL_BLOB_HEADER_SIZE: usize = 16;
+
+/// Size of the footer that follows a symbol blob appended to an image.
+pub const SYMBOL_BLOB_FOOTER_SIZE: usize = 8;
+
+/// Size of each entry in the symbol table.
+const ENTRY_SIZE: usize = 8;
+
+/// A parsed view of a symbol blob.
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolBlob<'a> {
+    entries: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> SymbolBlob<'a> {
+    /// Parses a symbol blob that starts at the beginning of `bytes`.
+    pub fn parse(bytes: &'a [u8]) -> StResult<Self> {
+        if bytes.read32(0)? != SYMBOL_BLOB_SIGNATURE {
+            return Err(Error::Malformed { module: None, reason: "Invalid symbol blob signature" });
+        }
+        if bytes.read16(4)? != SYMBOL_BLOB_VERSION {
+            return Err(Error::Malformed { module: None, reason: "Unsupported symbol blob version" });
+        }
+
+        let count = bytes.read32(8)? as usize;
+        let strings_size = bytes.read32(12)? as usize;
+
+        let entries_end = count
+            .checked_mul(ENTRY_SIZE)
+            .and_then(|size| size.checked_add(SYMBOL_BLOB_HEADER_SIZE))
+            .ok_or(Error::Malformed { module: None, reason: "Symbol count overflows the blob" })?;
+        let strings_end = entries_end
+            .checked_add(strings_size)
+            .ok_or(Error::Malformed { module: None, reason: "String table size overflows the blob" })?;
+        if strings_end > bytes.len() {
+            return Err(Error::OutOfBoundsRead { module: None, index: strings_end });
+        }
+
+        Ok(Self { entries: &bytes[SYMBOL_BLOB_HEADER_SIZE..entries_end], strings: &bytes[entries_end..strings_end] })
+    }
+
+    /// Parses a symbol blob appended to the end of an image file.
+    pub fn from_appended(file: &'a [u8]) -> StResult<Self> {
+        let footer = file
+            .len()
+            .checked_sub(SYMBOL_BLOB_FOOTER_SIZE)
+            .ok_or(Error::Malformed { module: None, reason: "File too small for a symbol blob footer" })?;
+        if file.read32(footer + 4)? != SYMBOL_BLOB_SIGNATURE {
+            return Err(Error::Malformed { module: None, reason: "No symbol blob footer" });
+        }
+
+        let start = footer
+            .checked_sub(file.read32(footer)? as usize)
+            .ok_or(Error::Malformed { module: None, reason: "Symbol blob size exceeds the file" })?;
+        Self::parse(&file[start..footer])
+    }
+
+    /// Returns the number of symbols in the blob.
+    pub fn len(&self) -> usize {
+        self.entries.len() / ENTRY_SIZE
+    }
+
+    /// Returns true if the blob holds no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the start RVA and name of the symbol at `index`.
+    pub fn get(&self, index: usize) -> Option<(u32, &'a str)> {
+        let entry = index.checked_mul(ENTRY_SIZE)?;
+        let rva = self.entries.read32(entry).ok()?;
+        let name_offset = self.entries.read32(entry + 4).ok()? as usize;
+
+        let name = self.strings.get(name_offset..)?;
+        let name = &name[..name.iter().position(|&b| b == 0)?];
+        Some((rva, core::str::from_utf8(name).ok()?))
+    }
+
+    /// Finds the symbol containing `rva`, returning its name and the offset of
+    /// `rva` from the symbol start. The containing symbol is the last one that
+    /// starts at or before `rva`.
+    pub fn lookup(&self, rva: u32) -> Option<(&'a str, u32)> {
+        // Count the symbols starting at or before the RVA.
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.get(mid)?.0 <= rva {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        let (start, name) = self.get(low.checked_sub(1)?)?;
+        Some((name, rva - start))
+    }
+}
+
+/// Builds a symbol blob from start RVA and name pairs. Symbols are sorted by
+/// RVA, and only the first name is kept for symbols sharing an RVA.
+pub fn build_symbol_blob(mut symbols: Vec<(u32, String)>) -> Vec<u8> {
+    symbols.sort_by_key(|(rva, _)| *rva);
+    symbols.dedup_by_key(|(rva, _)| *rva);
+
+    let mut entries = Vec::with_capacity(symbols.len() * ENTRY_SIZE);
+    let mut strings = Vec::new();
+    for (rva, name) in &symbols {
+        entries.extend_from_slice(&rva.to_le_bytes());
+        entries.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+    }
+
+    let mut blob = Vec::with_capacity(SYMBOL_BLOB_HEADER_SIZE + entries.len() + strings.len());
+    blob.extend_from_slice(&SYMBOL_BLOB_SIGNATURE.to_le_bytes());
+    blob.extend_from_slice(&SYMBOL_BLOB_VERSION.to_le_bytes());
+    blob.extend_from_slice(&0u16.to_le_bytes());
+    blob.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    blob.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+    blob.extend_from_slice(&entries);
+    blob.extend_from_slice(&strings);
+    blob
+}
+
+/// Builds the footer that follows a symbol blob appended to an image.
+pub fn symbol_blob_footer(blob: &[u8]) -> [u8; SYMBOL_BLOB_FOOTER_SIZE] {
+    let mut footer = [0; SYMBOL_BLOB_FOOTER_SIZE];
+    footer[..4].copy_from_slice(&(blob.len() as u32).to_le_bytes());
+    footer[4..].copy_from_slice(&SYMBOL_BLOB_SIGNATURE.to_le_bytes());
+    footer
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    fn sample_blob() -> Vec<u8> {
+        build_symbol_blob(vec![
+            (0x3000, "func3".to_string()),
+            (0x1000, "func1".to_string()),
+            (0x2000, "func2".to_string()),
+            (0x2000, "func2_alias".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_symbol_blob_round_trip() {
+        let bytes = sample_blob();
+        let blob = SymbolBlob::parse(&bytes).unwrap();
+
+        assert_eq!(blob.len(), 3);
+        assert_eq!(blob.get(0), Some((0x1000, "func1")));
+        assert_eq!(blob.get(1), Some((0x2000, "func2")));
+        assert_eq!(blob.get(2), Some((0x3000, "func3")));
+        assert_eq!(blob.get(3), None);
+    }
+
+    #[test]
+    fn test_symbol_blob_lookup() {
+        let bytes = sample_blob();
+        let blob = SymbolBlob::parse(&bytes).unwrap();
+
+        assert_eq!(blob.lookup(0xFFF), None);
+        assert_eq!(blob.lookup(0x1000), Some(("func1", 0)));
+        assert_eq!(blob.lookup(0x1FFF), Some(("func1", 0xFFF)));
+        assert_eq!(blob.lookup(0x2010), Some(("func2", 0x10)));
+        assert_eq!(blob.lookup(0x9000), Some(("func3", 0x6000)));
+    }
+
+    #[test]
+    fn test_symbol_blob_empty() {
+        let bytes = build_symbol_blob(vec![]);
+        let blob = SymbolBlob::parse(&bytes).unwrap();
+        assert!(blob.is_empty());
+        assert_eq!(blob.lookup(0x1000), None);
+    }
+
+    #[test]
+    fn test_symbol_blob_from_appended() {
+        let blob = sample_blob();
+        let mut file = vec![0xCC; 0x200];
+        file.extend_from_slice(&blob);
+        file.extend_from_slice(&symbol_blob_footer(&blob));
+
+        let parsed = SymbolBlob::from_appended(&file).unwrap();
+        assert_eq!(parsed.lookup(0x3004), Some(("func3", 4)));
+
+        assert!(SymbolBlob::from_appended(&file[..file.len() - 1]).is_err());
+        assert!(SymbolBlob::from_appended(&[]).is_err());
+    }
+
+    #[test]
+    fn test_symbol_blob_invalid() {
+        let mut bytes = sample_blob();
+        assert!(SymbolBlob::parse(&bytes[..bytes.len() - 1]).is_err());
+
+        bytes[4] = 2;
+        assert!(SymbolBlob::parse(&bytes).is_err());
+
+        bytes[0] = 0;
+        assert!(SymbolBlob::parse(&bytes).is_err());
+    }
+}
@@ -86,6 +86,13 @@ impl<'a> UnwindInfo<'a> {
         UnwindCode::get_stack_pointer_offset(self.unwind_codes).map_err(|err| err.with_module(self.image_name))
     }
 
+    /// Calculates where the function prolog saved the frame pointer (RBP), as an
+    /// offset from the stack pointer after the prolog. Returns `None` if the
+    /// function leaves RBP untouched.
+    pub fn get_frame_pointer_offset(&self) -> StResult<Option<usize>> {
+        UnwindCode::get_frame_pointer_offset(self.unwind_codes).map_err(|err| err.with_module(self.image_name))
+    }
+
     /// Calculates the parameters for the previous stack frame.
     ///
     /// # Safety
@@ -104,10 +111,23 @@ impl<'a> UnwindInfo<'a> {
         let prev_rip = unsafe { read_pointer64(prev_rsp)? };
         prev_rsp += 8; // pop the return address
 
-        Ok(StackFrame { sp: prev_rsp, pc: prev_rip, ..*stack_frame })
+        // RBP is non-volatile, so it still holds the caller's value unless the
+        // prolog saved it.
+        let prev_rbp = match self.get_frame_pointer_offset()? {
+            // SAFETY: The unwind metadata places the saved RBP inside the frame
+            // being unwound, which lies between `rsp` and the return address read
+            // above.
+            Some(rbp_offset) => unsafe { read_pointer64(rsp + rbp_offset as u64)? },
+            None => stack_frame.fp,
+        };
+
+        Ok(StackFrame { sp: prev_rsp, pc: prev_rip, fp: prev_rbp })
     }
 }
 
+/// The unwind code register number of RBP.
+const RBP: u8 = 5;
+
 /// `UnwindCode`
 /// Source: https://learn.microsoft.com/en-us/cpp/build/exception-handling-x64?view=msvc-170#struct-unwind_code
 #[allow(dead_code)] // Enum variants are used for testing the parsed bytes. Ignore their presence in release build
@@ -155,7 +175,24 @@ impl UnwindCode {
     /// Parses unwind codes and calculates the stack-pointer offset produced by
     /// the function prolog.
     pub fn get_stack_pointer_offset(bytes: &[u8]) -> StResult<usize> {
+        Self::get_prolog_offsets(bytes).map(|(offset, _)| offset)
+    }
+
+    /// Parses unwind codes and calculates where the function prolog saved the
+    /// frame pointer (RBP), relative to the stack pointer after the prolog.
+    pub fn get_frame_pointer_offset(bytes: &[u8]) -> StResult<Option<usize>> {
+        Self::get_prolog_offsets(bytes).map(|(_, rbp_offset)| rbp_offset)
+    }
+
+    /// Parses unwind codes and returns the stack-pointer offset produced by the
+    /// function prolog along with the location of the saved RBP, if any.
+    ///
+    /// Unwind codes are recorded in reverse prolog order, so the offset
+    /// accumulated before a push is the distance from the final stack pointer to
+    /// the pushed register.
+    fn get_prolog_offsets(bytes: &[u8]) -> StResult<(usize, Option<usize>)> {
         let mut offset = 0usize;
+        let mut rbp_offset = None;
         let byte_count = bytes.len();
         let mut index = 0;
         while index < byte_count {
@@ -165,7 +202,13 @@ impl UnwindCode {
             let opinfo = opcode_opinfo >> 4;
 
             match opcode {
-                0 => offset += 8, // PushNonVolatile
+                0 => {
+                    // PushNonVolatile
+                    if opinfo == RBP {
+                        rbp_offset = Some(offset);
+                    }
+                    offset += 8;
+                }
                 1 => {
                     // AllocLarge
                     let size = match opinfo {
@@ -187,17 +230,23 @@ impl UnwindCode {
                 3 => (),                                // SetFP
                 4 => {
                     // SaveNonVolatile - do not contribute to rsp but still we should consume the bytes
-                    bytes.read16_with(&mut index)?;
+                    let save_offset = bytes.read16_with(&mut index)? as usize * 8;
+                    if opinfo == RBP {
+                        rbp_offset = Some(save_offset);
+                    }
                 }
                 5 => {
                     // SaveNonVolatileFar - do not contribute to rsp but still we should consume the bytes
-                    bytes.read32_with(&mut index)?;
+                    let save_offset = bytes.read32_with(&mut index)? as usize;
+                    if opinfo == RBP {
+                        rbp_offset = Some(save_offset);
+                    }
                 }
                 6..=10 => (), // These opcodes do not contribute to rsp offset
                 _ => panic!("Unexpected opcode"),
             };
         }
-        Ok(offset)
+        Ok((offset, rbp_offset))
     }
 
     /// Test function that parses all unwind codes.
@@ -441,4 +490,50 @@ mod tests {
 
         drop(stack_words);
     }
+
+    #[test]
+    fn frame_pointer_offset_push_rbp() {
+        let codes = [
+            0x08, 0x22, // alloc_small, opinfo = 2 -> 24.
+            0x04, 0x30, // Push rbx.
+            0x01, 0x50, // Push rbp.
+        ];
+        let bytes = build_unwind_bytes(1, 0, 8, 3, 0, 0, &codes);
+        let ui = UnwindInfo::parse(&bytes, Some("push_rbp")).unwrap();
+        assert_eq!(ui.get_frame_pointer_offset().unwrap(), Some(32));
+
+        // SaveNonVolatile and SaveNonVolatileFar record the slot directly.
+        let codes = [0x08, 0x54, 0x03, 0x00, 0x04, 0x32];
+        let bytes = build_unwind_bytes(1, 0, 8, 3, 0, 0, &codes);
+        let ui = UnwindInfo::parse(&bytes, Some("save_rbp")).unwrap();
+        assert_eq!(ui.get_frame_pointer_offset().unwrap(), Some(24));
+
+        let codes = [0x08, 0x55, 0x28, 0x00, 0x00, 0x00];
+        let bytes = build_unwind_bytes(1, 0, 8, 3, 0, 0, &codes);
+        let ui = UnwindInfo::parse(&bytes, Some("save_rbp_far")).unwrap();
+        assert_eq!(ui.get_frame_pointer_offset().unwrap(), Some(0x28));
+
+        let codes = [0x04, 0x00, 0x02, 0x30];
+        let bytes = build_unwind_bytes(1, 0, 6, 2, 0, 0, &codes);
+        let ui = UnwindInfo::parse(&bytes, Some("no_rbp")).unwrap();
+        assert_eq!(ui.get_frame_pointer_offset().unwrap(), None);
+    }
+
+    #[test]
+    fn previous_stack_frame_restores_saved_rbp() {
+        let codes = [0x04, 0x22, 0x01, 0x50]; // alloc_small 24, push rbp.
+        let bytes = build_unwind_bytes(1, 0, 6, 2, 0, 0, &codes);
+        let ui = UnwindInfo::parse(&bytes, Some("frame")).unwrap();
+
+        let mut stack_words = Box::new([0u64; 5]);
+        stack_words[3] = 0x7000; // Saved rbp.
+        stack_words[4] = 0x1122_3344; // Return address.
+        let stack_base = stack_words.as_ptr() as u64;
+
+        let current_frame = StackFrame { sp: stack_base, pc: 0x1000, fp: 0x2000 };
+        let previous = ui.get_previous_stack_frame(&current_frame).unwrap();
+        assert_eq!(previous.pc, 0x1122_3344);
+        assert_eq!(previous.sp, stack_base + 40);
+        assert_eq!(previous.fp, 0x7000);
+    }
 }
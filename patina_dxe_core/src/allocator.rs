@@ -6,7 +6,9 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+mod emergency_heap;
 mod fixed_size_block_allocator;
+mod heap_snapshot;
 mod uefi_allocator;
 
 #[cfg(test)]
@@ -15,7 +17,7 @@ mod usage_tests;
 
 use core::{
     ffi::c_void,
-    fmt::Debug,
+    fmt::{self, Debug},
     mem,
     ops::Range,
     ptr::NonNull,
@@ -25,6 +27,7 @@ use core::{
 extern crate alloc;
 use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
 use mu_rust_helpers::function;
+use spin::RwLock;
 
 use crate::{
     GCD, config_tables,
@@ -35,6 +38,8 @@ use crate::{
     systemtables::EfiSystemTable,
     tpl_mutex,
 };
+pub(crate) use heap_snapshot::heap_monitor_command;
+pub use heap_snapshot::{HeapDiff, HeapSnapshot};
 use patina::pi::{
     dxe_services::{self, GcdMemoryType, MemorySpaceDescriptor},
     hob::{self, EFiMemoryTypeInformation, Hob, HobList, MEMORY_TYPE_INFO_HOB_GUID},
@@ -42,6 +47,8 @@ use patina::pi::{
 use r_efi::{efi, system::TPL_HIGH_LEVEL};
 pub use uefi_allocator::UefiAllocator;
 
+use emergency_heap::{CrashSafeAllocator, EMERGENCY_HEAP_SIZE};
+
 use patina::{
     base::{SIZE_4KB, UEFI_PAGE_MASK, UEFI_PAGE_SIZE},
     error::EfiError,
@@ -59,6 +66,23 @@ const PRIVATE_ALLOCATOR_TRACKING_GUID: efi::Guid =
 
 pub(crate) const DEFAULT_PAGE_ALLOCATION_GRANULARITY: usize = SIZE_4KB;
 
+// The first OEM defined memory type. Types from here to 0x7FFFFFFF are OEM defined, and types from 0x80000000 up are
+// OS defined.
+const CUSTOM_MEMORY_TYPE_START: efi::MemoryType = 0x7000_0000;
+
+// Custom memory types reported as standard memory types in the exported memory map.
+static MEMORY_TYPE_HANDOFF_MAP: RwLock<Vec<MemoryTypeMapping>> = RwLock::new(Vec::new());
+
+/// Maps a custom memory type used by the platform to the standard memory type reported for it in the UEFI memory
+/// map, so that the OS is handed a map containing only the types defined by the UEFI specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryTypeMapping {
+    /// An OEM (0x70000000-0x7FFFFFFF) or OS (0x80000000-0xFFFFFFFF) defined memory type.
+    pub custom: efi::MemoryType,
+    /// The standard memory type reported in its place.
+    pub standard: efi::MemoryType,
+}
+
 // Per the UEFI spec, AARCH64 runtime pages need to be allocated on 64KB boundaries in units of 64KB to accommodate
 // OSes that use 16KB or 64KB page sizes. Other architectures use 4KB pages, so we don't have any additional
 // granularity requirements for them.
@@ -70,11 +94,10 @@ cfg_if::cfg_if! {
     }
 }
 
-// The boot services data allocator is special as it is used as the GlobalAllocator instance for the DXE Rust core.
+// The boot services data allocator is special as it backs the GlobalAllocator instance for the DXE Rust core.
 // This means that any rust heap allocations (e.g. Box::new()) will come from this allocator unless explicitly directed
 // to a different allocator. This allocator does not need to be public since all dynamic allocations will implicitly
 // allocate from it.
-#[cfg_attr(target_os = "uefi", global_allocator)]
 pub(crate) static EFI_BOOT_SERVICES_DATA_ALLOCATOR: UefiAllocator = UefiAllocator::new(
     &GCD,
     NonNull::from_ref(GCD.memory_type_info(efi::BOOT_SERVICES_DATA)),
@@ -82,6 +105,12 @@ pub(crate) static EFI_BOOT_SERVICES_DATA_ALLOCATOR: UefiAllocator = UefiAllocato
     DEFAULT_PAGE_ALLOCATION_GRANULARITY,
 );
 
+// The GlobalAllocator instance for the DXE Rust core. Allocations are served from the boot services data allocator
+// until a crash path calls `use_emergency_heap`, after which they are served from a reserved emergency heap.
+#[cfg_attr(target_os = "uefi", global_allocator)]
+static GLOBAL_ALLOCATOR: CrashSafeAllocator<UefiAllocator, EMERGENCY_HEAP_SIZE> =
+    CrashSafeAllocator::new(&EFI_BOOT_SERVICES_DATA_ALLOCATOR);
+
 // The following allocators are directly used by the core. These allocators are declared static so that they can easily
 // be used in the core without e.g. the overhead of acquiring a lock to retrieve them from the allocator map that all
 // the other allocators use.
@@ -392,9 +421,31 @@ impl AllocatorMap {
     }
 }
 
+/// Switches all further Rust heap allocations to a small reserved emergency heap.
+///
+/// This should be called first thing in the platform panic handler, and from any other crash path, so that crash
+/// reporting (formatting, stack traces, the debugger) can still allocate when the main heap is exhausted or corrupted.
+/// The main heap is not used again once this is called, so it must only be called on paths which do not return to
+/// normal execution.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// #[panic_handler]
+/// fn panic(info: &core::panic::PanicInfo) -> ! {
+///     patina_dxe_core::use_emergency_heap();
+///     log::error!("{}", info);
+///     loop {}
+/// }
+/// ```
+pub fn use_emergency_heap() {
+    GLOBAL_ALLOCATOR.enter_crash_mode();
+}
+
 #[cfg(target_os = "uefi")]
 #[alloc_error_handler]
 fn alloc_error_handler(layout: alloc::alloc::Layout) -> ! {
+    use_emergency_heap();
     panic!("allocation error: {:?}", layout)
 }
 
@@ -615,6 +666,44 @@ fn merge_blocks(
     previous_blocks
 }
 
+/// Sets the mappings of custom memory types to standard memory types applied to the memory map, replacing any
+/// previous mappings. Entries which do not map a custom type to a standard type, or which map a custom type that is
+/// already mapped, are logged and ignored.
+pub(crate) fn set_memory_type_handoff_map(mappings: &[MemoryTypeMapping]) {
+    let mut handoff_map = MEMORY_TYPE_HANDOFF_MAP.write();
+    handoff_map.clear();
+    for mapping in mappings {
+        if mapping.custom < CUSTOM_MEMORY_TYPE_START || mapping.standard > efi::UNACCEPTED_MEMORY_TYPE {
+            log::error!(
+                "Ignoring memory type mapping {:#x} -> {:#x}: must map an OEM or OS memory type to a standard type.",
+                mapping.custom,
+                mapping.standard
+            );
+        } else if handoff_map.iter().any(|existing| existing.custom == mapping.custom) {
+            log::error!(
+                "Ignoring memory type mapping {:#x} -> {:#x}: already mapped.",
+                mapping.custom,
+                mapping.standard
+            );
+        } else {
+            log::info!(
+                "Memory type {:#x} will be reported as {:#x} in the memory map.",
+                mapping.custom,
+                mapping.standard
+            );
+            handoff_map.push(*mapping);
+        }
+    }
+}
+
+/// Returns the memory type reported in the memory map for the given memory type.
+fn handoff_memory_type(handoff_map: &[MemoryTypeMapping], memory_type: efi::MemoryType) -> efi::MemoryType {
+    match handoff_map.iter().find(|mapping| mapping.custom == memory_type) {
+        Some(mapping) => mapping.standard,
+        None => memory_type,
+    }
+}
+
 /// Get the memory map descriptors from the GCD.
 ///
 /// ## Arguments
@@ -638,6 +727,8 @@ pub(crate) fn get_memory_map_descriptors(active_attributes: bool) -> Result<Vec<
     //Note: get_memory_descriptors is should already be ordered, so sort is unnecessary.
     //descriptors.sort_unstable_by(|a, b|a.physical_start.cmp(&b.physical_start));
 
+    let handoff_map = MEMORY_TYPE_HANDOFF_MAP.read();
+
     Ok(descriptors
         .iter()
         .filter_map(|descriptor| {
@@ -671,6 +762,7 @@ pub(crate) fn get_memory_map_descriptors(active_attributes: bool) -> Result<Vec<
                     _ => None,
                 }
             })?;
+            let memory_type = handoff_memory_type(&handoff_map, memory_type);
 
             let number_of_pages = uefi_size_to_pages!(descriptor.length as usize) as u64;
             if number_of_pages == 0 {
@@ -715,6 +807,56 @@ pub(crate) fn get_memory_map_descriptors(active_attributes: bool) -> Result<Vec<
         .fold(merged_descriptors, merge_blocks))
 }
 
+/// Writes the UEFI memory map for debugger monitor commands. Nothing is written if building the map would wait on the
+/// GCD or allocator locks, since the debugger may have interrupted the lock holder.
+pub(crate) fn write_memory_map(out: &mut dyn fmt::Write) -> fmt::Result {
+    if GCD.is_memory_locked() || ALLOCATORS.try_lock().is_none() {
+        return out.write_str("Memory map is locked.");
+    }
+
+    match get_memory_map_descriptors(false) {
+        Ok(descriptors) => write!(out, "{:?}", MemoryDescriptorSlice(&descriptors)),
+        Err(err) => write!(out, "Failed to get the memory map: {err:?}"),
+    }
+}
+
+/// Writes the UEFI memory map for debugger monitor commands, listing below each descriptor the loaded images and the
+/// pool memory within it. Nothing is written if building the map would wait on the GCD, allocator, or image locks.
+pub(crate) fn write_annotated_memory_map(out: &mut dyn fmt::Write) -> fmt::Result {
+    if GCD.is_memory_locked() || ALLOCATORS.try_lock().is_none() {
+        return out.write_str("Memory map is locked.");
+    }
+
+    let descriptors = match get_memory_map_descriptors(false) {
+        Ok(descriptors) => descriptors,
+        Err(err) => return write!(out, "Failed to get the memory map: {err:?}"),
+    };
+    let pool_ranges: Vec<Range<u64>> =
+        ALLOCATORS.lock().iter().flat_map(|allocator| allocator.get_memory_ranges()).collect();
+    let Some(image_ranges) = crate::image::loaded_image_ranges() else {
+        return out.write_str("Image data is locked.");
+    };
+
+    writeln!(
+        out,
+        "{:<24} {:<20} {:<15} {:<15} {:<20}",
+        "Type", "Physical Start", "Virtual Start", "Number of Pages", "Attributes"
+    )?;
+    for descriptor in &descriptors {
+        writeln!(out, "{:?}", MemoryDescriptorRef(descriptor))?;
+        let start = descriptor.physical_start;
+        let end = start + descriptor.number_of_pages * UEFI_PAGE_SIZE as u64;
+        let overlaps = |range: &Range<u64>| range.start < end && start < range.end;
+        for (range, name) in image_ranges.iter().filter(|(range, _)| overlaps(range)) {
+            writeln!(out, "    image {:#x}-{:#x} {name}", range.start, range.end)?;
+        }
+        for range in pool_ranges.iter().filter(|range| overlaps(range)) {
+            writeln!(out, "    pool  {:#x}-{:#x}", range.start, range.end)?;
+        }
+    }
+    Ok(())
+}
+
 extern "efiapi" fn get_memory_map(
     memory_map_size: *mut usize,
     memory_map: *mut efi::MemoryDescriptor,
@@ -1090,22 +1232,15 @@ pub fn init_memory_support(hob_list: &HobList) {
     // After this point the GCD and existing allocations are fully processed and it is safe to arbitrarily allocate.
 
     // If memory type info HOB is available, then pre-allocate the corresponding buckets.
-    if let Some(memory_type_info) = hob_list.iter().find_map(|x| {
-        match x {
-            patina::pi::hob::Hob::GuidHob(hob, data) if hob.name == MEMORY_TYPE_INFO_HOB_GUID => {
-                let memory_type_slice_ptr = data.as_ptr() as *const EFiMemoryTypeInformation;
-                let memory_type_slice_len = data.len() / mem::size_of::<EFiMemoryTypeInformation>();
-
-                // Safety: this structure comes from the hob list, so it must be 8-byte aligned (meets alignment
-                // requirement for EfiMemoryTypeInformation), and length is calculated above to fit within the
-                // Guid HOB data. Assert if alignment is not as expected.
-                assert_eq!(memory_type_slice_ptr.align_offset(mem::align_of::<EFiMemoryTypeInformation>()), 0);
-                let memory_type_info = unsafe { slice::from_raw_parts(memory_type_slice_ptr, memory_type_slice_len) };
-
-                Some(memory_type_info)
-            }
-            _ => None,
-        }
+    if let Some(memory_type_info) = hob_list.find_guid_hob(MEMORY_TYPE_INFO_HOB_GUID).map(|data| {
+        let memory_type_slice_ptr = data.as_ptr() as *const EFiMemoryTypeInformation;
+        let memory_type_slice_len = data.len() / mem::size_of::<EFiMemoryTypeInformation>();
+
+        // Safety: this structure comes from the hob list, so it must be 8-byte aligned (meets alignment
+        // requirement for EfiMemoryTypeInformation), and length is calculated above to fit within the
+        // Guid HOB data. Assert if alignment is not as expected.
+        assert_eq!(memory_type_slice_ptr.align_offset(mem::align_of::<EFiMemoryTypeInformation>()), 0);
+        unsafe { slice::from_raw_parts(memory_type_slice_ptr, memory_type_slice_len) }
     }) {
         for bucket in memory_type_info {
             if bucket.number_of_pages == 0 {
@@ -1872,6 +2007,111 @@ mod tests {
         })
     }
 
+    #[test]
+    fn set_memory_type_handoff_map_should_ignore_invalid_mappings() {
+        with_locked_state(0x1000000, || {
+            set_memory_type_handoff_map(&[
+                MemoryTypeMapping { custom: 0x71234567, standard: efi::RESERVED_MEMORY_TYPE },
+                MemoryTypeMapping { custom: efi::LOADER_DATA, standard: efi::RESERVED_MEMORY_TYPE },
+                MemoryTypeMapping { custom: 0x81234567, standard: 0x71234567 },
+                MemoryTypeMapping { custom: 0x71234567, standard: efi::ACPI_MEMORY_NVS },
+            ]);
+            assert_eq!(
+                *MEMORY_TYPE_HANDOFF_MAP.read(),
+                [MemoryTypeMapping { custom: 0x71234567, standard: efi::RESERVED_MEMORY_TYPE }]
+            );
+
+            let handoff_map = MEMORY_TYPE_HANDOFF_MAP.read();
+            assert_eq!(handoff_memory_type(&handoff_map, 0x71234567), efi::RESERVED_MEMORY_TYPE);
+            assert_eq!(handoff_memory_type(&handoff_map, 0x81234567), 0x81234567);
+            assert_eq!(handoff_memory_type(&handoff_map, efi::LOADER_DATA), efi::LOADER_DATA);
+            drop(handoff_map);
+
+            set_memory_type_handoff_map(&[]);
+            assert!(MEMORY_TYPE_HANDOFF_MAP.read().is_empty());
+        });
+    }
+
+    #[test]
+    fn get_memory_map_descriptors_should_apply_the_handoff_map() {
+        with_locked_state(0x1000000, || {
+            let mut buffer_ptr: *mut u8 = core::ptr::null_mut();
+            assert_eq!(
+                allocate_pages(
+                    efi::ALLOCATE_ANY_PAGES,
+                    0x71234567,
+                    0x10,
+                    core::ptr::addr_of_mut!(buffer_ptr) as *mut efi::PhysicalAddress
+                ),
+                efi::Status::SUCCESS
+            );
+            let address = buffer_ptr as u64;
+            let contains = |descriptors: &[efi::MemoryDescriptor], memory_type| {
+                descriptors.iter().any(|descriptor| {
+                    descriptor.r#type == memory_type
+                        && descriptor.physical_start <= address
+                        && address < descriptor.physical_start + descriptor.number_of_pages * UEFI_PAGE_SIZE as u64
+                })
+            };
+
+            assert!(contains(&get_memory_map_descriptors(false).unwrap(), 0x71234567));
+
+            set_memory_type_handoff_map(&[MemoryTypeMapping {
+                custom: 0x71234567,
+                standard: efi::RUNTIME_SERVICES_DATA,
+            }]);
+            let descriptors = get_memory_map_descriptors(false).unwrap();
+            set_memory_type_handoff_map(&[]);
+
+            assert!(!descriptors.iter().any(|descriptor| descriptor.r#type == 0x71234567));
+            assert!(contains(&descriptors, efi::RUNTIME_SERVICES_DATA));
+            let descriptor =
+                descriptors.iter().find(|descriptor| descriptor.r#type == efi::RUNTIME_SERVICES_DATA).unwrap();
+            assert_ne!(descriptor.attribute & efi::MEMORY_RUNTIME, 0);
+        });
+    }
+
+    #[test]
+    fn write_memory_map_should_describe_the_memory_map() {
+        with_locked_state(0x1000000, || {
+            let mut out = std::string::String::new();
+            write_memory_map(&mut out).unwrap();
+            assert!(out.starts_with("Type"));
+            assert!(out.contains("Conventional Memory"));
+
+            let _guard = ALLOCATORS.lock();
+            let mut out = std::string::String::new();
+            write_memory_map(&mut out).unwrap();
+            assert_eq!(out, "Memory map is locked.");
+        });
+    }
+
+    #[test]
+    fn write_annotated_memory_map_should_list_pool_ranges() {
+        with_locked_state(0x1000000, || {
+            let mut buffer: *mut c_void = core::ptr::null_mut();
+            assert_eq!(
+                allocate_pool(efi::BOOT_SERVICES_DATA, 0x10, core::ptr::addr_of_mut!(buffer)),
+                efi::Status::SUCCESS
+            );
+
+            let mut out = std::string::String::new();
+            write_annotated_memory_map(&mut out).unwrap();
+            assert!(out.starts_with("Type"));
+            let pool_line = out
+                .lines()
+                .skip_while(|line| !line.starts_with("BootServicesData"))
+                .find(|line| line.starts_with("    pool  "))
+                .expect("pool range should be listed under its descriptor");
+            assert!(pool_line.contains("0x"));
+
+            let _guard = ALLOCATORS.lock();
+            let mut out = std::string::String::new();
+            write_annotated_memory_map(&mut out).unwrap();
+            assert_eq!(out, "Memory map is locked.");
+        });
+    }
+
     #[test]
     fn terminate_map_should_validate_the_map_key() {
         with_locked_state(0x1000000, || {
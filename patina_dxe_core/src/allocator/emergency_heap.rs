@@ -0,0 +1,196 @@
+//! Emergency Heap
+//!
+//! A small statically reserved heap used by crash paths (panic handling, exception dumps, the debugger) once the
+//! core has entered crash mode. Crash reporting frequently needs to allocate while formatting, and an allocation from
+//! an exhausted or corrupted main heap (or one whose lock is held by the code that crashed) would otherwise turn a
+//! diagnosable crash into a silent hang.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// Size of the heap reserved for crash paths.
+pub(crate) const EMERGENCY_HEAP_SIZE: usize = 0x10000;
+
+// Backing storage for the emergency heap, aligned so that page aligned requests can be satisfied.
+#[repr(C, align(4096))]
+struct Arena<const SIZE: usize>(UnsafeCell<[u8; SIZE]>);
+
+/// A bump allocator over a fixed arena.
+///
+/// Memory is only returned to the heap when the most recent allocation is freed. This is sufficient for crash paths,
+/// which allocate a bounded amount and never return to normal execution.
+pub(crate) struct EmergencyHeap<const SIZE: usize> {
+    arena: Arena<SIZE>,
+    next: AtomicUsize,
+}
+
+// SAFETY: the arena is only handed out in disjoint ranges reserved through the atomic `next` offset.
+unsafe impl<const SIZE: usize> Sync for EmergencyHeap<SIZE> {}
+
+impl<const SIZE: usize> EmergencyHeap<SIZE> {
+    /// Creates a new, empty emergency heap.
+    pub(crate) const fn new() -> Self {
+        Self { arena: Arena(UnsafeCell::new([0; SIZE])), next: AtomicUsize::new(0) }
+    }
+
+    fn base(&self) -> usize {
+        self.arena.0.get() as usize
+    }
+
+    /// Returns true if the pointer lies within the emergency heap.
+    pub(crate) fn contains(&self, ptr: *mut u8) -> bool {
+        (self.base()..self.base() + SIZE).contains(&(ptr as usize))
+    }
+
+    /// Allocates from the emergency heap, returning null if it is exhausted.
+    pub(crate) fn allocate(&self, layout: Layout) -> *mut u8 {
+        let base = self.base();
+        let mut current = self.next.load(Ordering::Acquire);
+        loop {
+            let Some(start) = (base + current).checked_next_multiple_of(layout.align()) else {
+                return ptr::null_mut();
+            };
+            let offset = start - base;
+            let end = match offset.checked_add(layout.size()) {
+                Some(end) if end <= SIZE => end,
+                _ => return ptr::null_mut(),
+            };
+            match self.next.compare_exchange_weak(current, end, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return start as *mut u8,
+                Err(next) => current = next,
+            }
+        }
+    }
+
+    /// Frees an allocation from the emergency heap. Only the most recent allocation is reclaimed.
+    pub(crate) fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        let offset = ptr as usize - self.base();
+        let _ = self.next.compare_exchange(offset + layout.size(), offset, Ordering::AcqRel, Ordering::Relaxed);
+    }
+}
+
+/// A global allocator which serves allocations from the main heap until crash mode is entered, after which all
+/// allocations are served from an [`EmergencyHeap`].
+///
+/// Once in crash mode the main heap is never touched again: memory it handed out before the crash is leaked when
+/// freed rather than risking a walk of corrupted allocator state or a deadlock on its lock.
+pub(crate) struct CrashSafeAllocator<A: GlobalAlloc + 'static, const SIZE: usize> {
+    heap: &'static A,
+    emergency: EmergencyHeap<SIZE>,
+    crash_mode: AtomicBool,
+}
+
+impl<A: GlobalAlloc + 'static, const SIZE: usize> CrashSafeAllocator<A, SIZE> {
+    /// Creates a new allocator over the given main heap.
+    pub(crate) const fn new(heap: &'static A) -> Self {
+        Self { heap, emergency: EmergencyHeap::new(), crash_mode: AtomicBool::new(false) }
+    }
+
+    /// Switches all further allocations to the emergency heap.
+    pub(crate) fn enter_crash_mode(&self) {
+        if !self.crash_mode.swap(true, Ordering::AcqRel) {
+            log::error!("Allocations now served from the {:#x} byte emergency heap.", SIZE);
+        }
+    }
+
+    /// Returns true if crash mode has been entered.
+    pub(crate) fn in_crash_mode(&self) -> bool {
+        self.crash_mode.load(Ordering::Acquire)
+    }
+}
+
+unsafe impl<A: GlobalAlloc + 'static, const SIZE: usize> GlobalAlloc for CrashSafeAllocator<A, SIZE> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.in_crash_mode() {
+            return self.emergency.allocate(layout);
+        }
+        // SAFETY: the caller's guarantees on the layout are forwarded to the main heap.
+        unsafe { self.heap.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if self.emergency.contains(ptr) {
+            self.emergency.deallocate(ptr, layout);
+        } else if !self.in_crash_mode() {
+            // SAFETY: the pointer was not allocated from the emergency heap, so it came from the main heap.
+            unsafe { self.heap.dealloc(ptr, layout) }
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+
+    #[test]
+    fn test_emergency_heap_allocates_aligned_until_exhausted() {
+        let heap = EmergencyHeap::<0x100>::new();
+
+        let first = heap.allocate(Layout::from_size_align(3, 1).unwrap());
+        assert!(heap.contains(first));
+        let second = heap.allocate(Layout::from_size_align(16, 16).unwrap());
+        assert!(heap.contains(second));
+        assert_eq!(second as usize % 16, 0);
+        assert_eq!(heap.next.load(Ordering::Relaxed), 32);
+
+        assert!(heap.allocate(Layout::from_size_align(0x100, 1).unwrap()).is_null());
+        assert!(!heap.allocate(Layout::from_size_align(0xe0, 1).unwrap()).is_null());
+        assert!(heap.allocate(Layout::from_size_align(1, 1).unwrap()).is_null());
+    }
+
+    #[test]
+    fn test_emergency_heap_reclaims_last_allocation() {
+        let heap = EmergencyHeap::<0x100>::new();
+        let layout = Layout::from_size_align(0x20, 8).unwrap();
+
+        let first = heap.allocate(layout);
+        let second = heap.allocate(layout);
+
+        // Freeing out of order leaks the allocation.
+        heap.deallocate(first, layout);
+        assert_eq!(heap.next.load(Ordering::Relaxed), 0x40);
+
+        heap.deallocate(second, layout);
+        assert_eq!(heap.next.load(Ordering::Relaxed), 0x20);
+        assert_eq!(heap.allocate(layout), second);
+    }
+
+    #[test]
+    fn test_crash_mode_routes_to_emergency_heap() {
+        static SYSTEM: std::alloc::System = std::alloc::System;
+        let allocator = CrashSafeAllocator::<_, 0x1000>::new(&SYSTEM);
+        let layout = Layout::from_size_align(0x40, 8).unwrap();
+
+        // SAFETY: the layout is non-zero in size, and each pointer is freed with the layout it was allocated with.
+        unsafe {
+            let before = allocator.alloc(layout);
+            assert!(!before.is_null());
+            assert!(!allocator.emergency.contains(before));
+            assert_eq!(allocator.emergency.next.load(Ordering::Relaxed), 0);
+
+            allocator.enter_crash_mode();
+            assert!(allocator.in_crash_mode());
+
+            let after = allocator.alloc(layout);
+            assert!(allocator.emergency.contains(after));
+            assert_eq!(allocator.emergency.next.load(Ordering::Relaxed), 0x40);
+
+            // Main heap memory is leaked once crashed, emergency heap memory is reclaimed.
+            allocator.dealloc(before, layout);
+            allocator.dealloc(after, layout);
+            assert_eq!(allocator.emergency.next.load(Ordering::Relaxed), 0);
+        }
+    }
+}
@@ -106,7 +106,7 @@ impl Iterator for AllocatorIterator {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct AllocationStatistics {
     /// The number of calls to `alloc()`.
     ///
@@ -147,6 +147,50 @@ impl AllocationStatistics {
     }
 }
 
+/// Occupancy of one fixed-size block list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketOccupancy {
+    /// The size of the blocks in this list.
+    pub block_size: usize,
+
+    /// The number of blocks handed out and not yet freed.
+    pub in_use: usize,
+
+    /// The number of freed blocks kept in the list for reuse.
+    pub free: usize,
+}
+
+impl BucketOccupancy {
+    pub(crate) const fn empty_buckets() -> [Self; BLOCK_SIZES.len()] {
+        let mut buckets = [Self { block_size: 0, in_use: 0, free: 0 }; BLOCK_SIZES.len()];
+        let mut index = 0;
+        while index < BLOCK_SIZES.len() {
+            buckets[index].block_size = BLOCK_SIZES[index];
+            index += 1;
+        }
+        buckets
+    }
+}
+
+/// A point-in-time copy of the state of a [`FixedSizeBlockAllocator`].
+#[derive(Debug, Clone, Copy)]
+pub struct AllocatorSnapshot {
+    /// The memory type managed by the allocator.
+    pub memory_type: efi::MemoryType,
+
+    /// The allocator's usage statistics.
+    pub stats: AllocationStatistics,
+
+    /// The occupancy of each fixed-size block list, smallest block size first.
+    pub buckets: [BucketOccupancy; BLOCK_SIZES.len()],
+
+    /// The total size of the backing linked-list heaps.
+    pub heap_size: usize,
+
+    /// The bytes in use in the backing linked-list heaps, including fixed-size blocks carved from them.
+    pub heap_used: usize,
+}
+
 /// Fixed Size Block Allocator
 ///
 /// Implements an expandable memory allocator using fixed-sized blocks for speed backed by a linked-list allocator
@@ -174,6 +218,9 @@ pub struct FixedSizeBlockAllocator {
     /// Statistics about the allocator's usage.
     stats: AllocationStatistics,
 
+    /// Occupancy of the fixed-size block lists. Each index corresponds to a block size in `BLOCK_SIZES`.
+    buckets: [BucketOccupancy; BLOCK_SIZES.len()],
+
     /// The page allocation granularity used by this allocator. This is expected to be one of the following:
     /// - `SIZE_4KB` for all allocators except AARCH64 runtime memory allocators
     /// - `SIZE_64KB` for AARCH64 runtime memory allocators
@@ -190,6 +237,7 @@ impl FixedSizeBlockAllocator {
             allocators: None,
             reserved_range: None,
             stats: AllocationStatistics::new(),
+            buckets: BucketOccupancy::empty_buckets(),
             page_allocation_granularity,
         }
     }
@@ -204,6 +252,7 @@ impl FixedSizeBlockAllocator {
         self.reserved_range = None;
         self.memory_type_info_mut().number_of_pages = 0;
         self.stats = AllocationStatistics::new();
+        self.buckets = BucketOccupancy::empty_buckets();
     }
 
     /// Expand the memory available to this allocator with a new contiguous region of memory, setting up a new allocator
@@ -298,6 +347,8 @@ impl FixedSizeBlockAllocator {
                 match self.list_heads[index].take() {
                     Some(node) => {
                         self.list_heads[index] = node.next.take();
+                        self.buckets[index].free = self.buckets[index].free.saturating_sub(1);
+                        self.buckets[index].in_use += 1;
                         let ptr: NonNull<u8> = NonNull::from(node).cast();
                         Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
                     }
@@ -310,7 +361,9 @@ impl FixedSizeBlockAllocator {
                             Ok(layout) => layout,
                             Err(_) => return Err(FixedSizeBlockAllocatorError::InvalidLayout),
                         };
-                        self.fallback_alloc(layout)
+                        let block = self.fallback_alloc(layout)?;
+                        self.buckets[index].in_use += 1;
+                        Ok(block)
                     }
                 }
             }
@@ -347,6 +400,8 @@ impl FixedSizeBlockAllocator {
                     new_node_ptr.write(new_node);
                     self.list_heads[index] = Some(&mut *new_node_ptr);
                 }
+                self.buckets[index].in_use = self.buckets[index].in_use.saturating_sub(1);
+                self.buckets[index].free += 1;
             }
             None => {
                 self.fallback_dealloc(ptr, layout);
@@ -462,6 +517,22 @@ impl FixedSizeBlockAllocator {
         &self.stats
     }
 
+    /// Returns a copy of the current state of this allocator.
+    pub fn snapshot(&self) -> AllocatorSnapshot {
+        let (heap_size, heap_used) = AllocatorIterator::new(self.allocators).fold((0, 0), |(size, used), node| {
+            // This is safe because the node is a valid pointer to an AllocatorListNode
+            let allocator = unsafe { &(*node).allocator };
+            (size + allocator.size(), used + allocator.used())
+        });
+        AllocatorSnapshot {
+            memory_type: self.memory_type(),
+            stats: self.stats,
+            buckets: self.buckets,
+            heap_size,
+            heap_used,
+        }
+    }
+
     /// Re-calculates the number of pages allocated for this memory type and updates the memory type info.
     fn update_memory_type_info(&mut self) {
         let stats = self.stats();
@@ -740,6 +811,11 @@ impl SpinLockedFixedSizeBlockAllocator {
     pub fn stats(&self) -> AllocationStatistics {
         *self.inner.lock().stats()
     }
+
+    /// Returns a copy of the current state of this allocator, or `None` if the allocator is locked.
+    pub fn try_snapshot(&self) -> Option<AllocatorSnapshot> {
+        self.inner.try_lock().map(|allocator| allocator.snapshot())
+    }
 }
 
 unsafe impl GlobalAlloc for SpinLockedFixedSizeBlockAllocator {
@@ -1271,6 +1347,43 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_snapshot() {
+        with_granularity_modulation(|granularity| {
+            with_locked_state(|| {
+                // Create a static GCD
+                static GCD: SpinLockedGcd = SpinLockedGcd::new(None);
+
+                // Allocate some space on the heap with the global allocator (std) to be used by expand().
+                init_gcd(&GCD, 0x400000);
+
+                let fsb = SpinLockedFixedSizeBlockAllocator::new(
+                    &GCD,
+                    1 as _,
+                    memory_type_info(efi::RUNTIME_SERVICES_DATA),
+                    granularity,
+                );
+
+                let layout = Layout::from_size_align(0x10, 0x8).unwrap();
+                let first = unsafe { fsb.alloc(layout) };
+                let _second = unsafe { fsb.alloc(layout) };
+                unsafe { fsb.dealloc(first, layout) };
+                let _large = unsafe { fsb.alloc(Layout::from_size_align(0x2000, 0x8).unwrap()) };
+
+                let snapshot = fsb.try_snapshot().unwrap();
+                assert_eq!(snapshot.memory_type, efi::RUNTIME_SERVICES_DATA);
+                let bucket = snapshot.buckets[list_index(&layout).unwrap()];
+                assert_eq!((bucket.block_size, bucket.in_use, bucket.free), (0x10, 1, 1));
+                assert!(snapshot.buckets.iter().filter(|bucket| bucket.block_size != 0x10).all(|b| b.in_use == 0));
+                assert!(snapshot.heap_used >= 0x2020 && snapshot.heap_used < snapshot.heap_size);
+
+                let guard = fsb.lock();
+                assert!(fsb.try_snapshot().is_none());
+                drop(guard);
+            });
+        });
+    }
+
     #[test]
     fn test_deallocate() {
         with_granularity_modulation(|granularity| {
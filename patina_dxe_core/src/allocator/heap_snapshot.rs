@@ -0,0 +1,335 @@
+//! Heap snapshots for measuring pool growth.
+//!
+//! A [`HeapSnapshot`] copies the usage statistics and fixed-size block occupancy of every pool allocator. Comparing
+//! two snapshots taken at different points in boot shows which memory types grew and by how much. The `heap` debugger
+//! monitor command keeps one snapshot as a baseline and diffs the current state against it.
+//!
+//! Snapshots hold their data inline and capturing one does not allocate, so they can be taken while the debugger has
+//! interrupted an allocation. Per-call-site totals are not collected, as the allocators do not record call sites.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::fmt::{self, Display};
+
+use patina::collections::ArrayVec;
+use r_efi::efi;
+use spin::Mutex;
+
+use super::{
+    ALLOCATORS,
+    fixed_size_block_allocator::{AllocatorSnapshot, BucketOccupancy},
+    memory_type_to_str,
+};
+
+/// The number of pool allocators a snapshot holds. Allocators beyond this are counted but not captured.
+const MAX_ALLOCATORS: usize = 16;
+
+// The snapshots used by the `heap` monitor command. They are kept in a static so the command does not place them on
+// the stack while the system is broken in.
+static MONITOR_STATE: Mutex<MonitorState> =
+    Mutex::new(MonitorState { baseline: HeapSnapshot::new(), baseline_recorded: false, current: HeapSnapshot::new() });
+
+struct MonitorState {
+    // The baseline recorded by `heap snapshot`.
+    baseline: HeapSnapshot,
+    baseline_recorded: bool,
+    // The state captured by the current command.
+    current: HeapSnapshot,
+}
+
+/// The state of every pool allocator at one point in time.
+#[derive(Debug, Clone)]
+pub struct HeapSnapshot {
+    allocators: ArrayVec<AllocatorSnapshot, MAX_ALLOCATORS>,
+    // The number of allocators that did not fit in `allocators`.
+    omitted: usize,
+}
+
+impl HeapSnapshot {
+    /// Creates an empty snapshot, to be filled by [`HeapSnapshot::refresh`].
+    pub const fn new() -> Self {
+        Self { allocators: ArrayVec::new(), omitted: 0 }
+    }
+
+    /// Captures the state of every pool allocator. Returns `None` if any allocator is locked, which happens when the
+    /// debugger interrupted an allocation.
+    pub fn capture() -> Option<Self> {
+        let mut snapshot = Self::new();
+        snapshot.refresh().then_some(snapshot)
+    }
+
+    /// Replaces this snapshot with the current state of every pool allocator, reusing its storage. Returns `false` and
+    /// leaves the snapshot empty if any allocator is locked.
+    pub fn refresh(&mut self) -> bool {
+        self.allocators.clear();
+        self.omitted = 0;
+        let Some(allocators) = ALLOCATORS.try_lock() else {
+            return false;
+        };
+        for allocator in allocators.iter() {
+            let Some(snapshot) = allocator.try_snapshot() else {
+                self.allocators.clear();
+                self.omitted = 0;
+                return false;
+            };
+            if self.allocators.push(snapshot).is_err() {
+                self.omitted += 1;
+            }
+        }
+        true
+    }
+
+    /// Returns the changes from this snapshot to the `later` one, which display one line per memory type that changed.
+    pub fn diff<'a>(&'a self, later: &'a HeapSnapshot) -> HeapDiff<'a> {
+        HeapDiff { earlier: self, later }
+    }
+
+    fn find(&self, memory_type: efi::MemoryType) -> Option<&AllocatorSnapshot> {
+        self.allocators.iter().find(|allocator| allocator.memory_type == memory_type)
+    }
+}
+
+impl Display for HeapSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for allocator in self.allocators.iter() {
+            memory_type_to_str(f, allocator.memory_type)?;
+            writeln!(
+                f,
+                "heap {:#x} of {:#x} bytes used, {} pages, {} allocations, {} frees",
+                allocator.heap_used,
+                allocator.heap_size,
+                allocator.stats.claimed_pages,
+                allocator.stats.pool_allocation_calls,
+                allocator.stats.pool_free_calls,
+            )?;
+            for bucket in allocator.buckets.iter().filter(|bucket| bucket.in_use != 0 || bucket.free != 0) {
+                writeln!(
+                    f,
+                    "    {:>4}-byte blocks: {} in use, {} free",
+                    bucket.block_size, bucket.in_use, bucket.free
+                )?;
+            }
+        }
+        write_omitted(f, self.omitted)
+    }
+}
+
+impl Default for HeapSnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Notes the allocators that did not fit in a snapshot.
+fn write_omitted(f: &mut fmt::Formatter<'_>, omitted: usize) -> fmt::Result {
+    match omitted {
+        0 => Ok(()),
+        omitted => writeln!(f, "{omitted} more allocators not captured"),
+    }
+}
+
+/// The changes between two [`HeapSnapshot`]s.
+pub struct HeapDiff<'a> {
+    earlier: &'a HeapSnapshot,
+    later: &'a HeapSnapshot,
+}
+
+impl Display for HeapDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut changed = false;
+        for later in self.later.allocators.iter() {
+            // An allocator created after the earlier snapshot grew from nothing.
+            let earlier = self.earlier.find(later.memory_type).copied().unwrap_or(AllocatorSnapshot {
+                heap_size: 0,
+                heap_used: 0,
+                stats: Default::default(),
+                buckets: BucketOccupancy::empty_buckets(),
+                ..*later
+            });
+
+            let buckets =
+                || earlier.buckets.iter().zip(&later.buckets).filter(|(earlier, later)| earlier.in_use != later.in_use);
+            let calls =
+                |snapshot: &AllocatorSnapshot| (snapshot.stats.pool_allocation_calls, snapshot.stats.pool_free_calls);
+            if earlier.heap_used == later.heap_used
+                && earlier.stats.claimed_pages == later.stats.claimed_pages
+                && calls(&earlier) == calls(later)
+                && buckets().next().is_none()
+            {
+                continue;
+            }
+
+            changed = true;
+            memory_type_to_str(f, later.memory_type)?;
+            writeln!(
+                f,
+                "heap {:#x} bytes used ({:#x}), {} pages ({}), {} allocations, {} frees",
+                Delta(earlier.heap_used, later.heap_used),
+                later.heap_used,
+                Delta(earlier.stats.claimed_pages, later.stats.claimed_pages),
+                later.stats.claimed_pages,
+                later.stats.pool_allocation_calls.wrapping_sub(earlier.stats.pool_allocation_calls),
+                later.stats.pool_free_calls.wrapping_sub(earlier.stats.pool_free_calls),
+            )?;
+            for (earlier, later) in buckets() {
+                writeln!(
+                    f,
+                    "    {:>4}-byte blocks in use: {} ({})",
+                    later.block_size,
+                    Delta(earlier.in_use, later.in_use),
+                    later.in_use
+                )?;
+            }
+        }
+
+        if !changed {
+            f.write_str("No heap changes.\n")?;
+        }
+        write_omitted(f, self.later.omitted)
+    }
+}
+
+/// Displays the signed change from the first count to the second, in decimal or with `{:#x}` in hexadecimal.
+struct Delta(usize, usize);
+
+impl Delta {
+    fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        write_magnitude: fn(usize, &mut fmt::Formatter<'_>) -> fmt::Result,
+    ) -> fmt::Result {
+        match self.1.checked_sub(self.0) {
+            Some(growth) => {
+                f.write_str("+")?;
+                write_magnitude(growth, f)
+            }
+            None => {
+                f.write_str("-")?;
+                write_magnitude(self.0 - self.1, f)
+            }
+        }
+    }
+}
+
+impl Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, |magnitude, f| Display::fmt(&magnitude, f))
+    }
+}
+
+impl fmt::LowerHex for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, |magnitude, f| fmt::LowerHex::fmt(&magnitude, f))
+    }
+}
+
+/// Runs the `heap` debugger monitor command: `heap` prints the current heap state, `heap snapshot` records it as the
+/// baseline, and `heap diff` prints the changes since the baseline.
+pub(crate) fn heap_monitor_command(command: Option<&str>, out: &mut dyn fmt::Write) -> fmt::Result {
+    let Some(mut state) = MONITOR_STATE.try_lock() else {
+        return out.write_str("Heap snapshot is locked.");
+    };
+    let MonitorState { baseline, baseline_recorded, current } = &mut *state;
+    if !current.refresh() {
+        return out.write_str("Heap is locked.");
+    }
+
+    match command {
+        None => write!(out, "{current}"),
+        Some("snapshot") => {
+            core::mem::swap(baseline, current);
+            *baseline_recorded = true;
+            out.write_str("Heap snapshot recorded.")
+        }
+        Some("diff") if *baseline_recorded => write!(out, "{}", baseline.diff(current)),
+        Some("diff") => out.write_str("No heap snapshot. Record one with 'heap snapshot'."),
+        Some(_) => out.write_str("Usage: heap [snapshot|diff]"),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::{
+        allocator::{STATIC_ALLOCATORS, fixed_size_block_allocator::AllocationStatistics},
+        test_support,
+    };
+    use alloc::string::ToString;
+
+    fn heap_snapshot(allocators: &[AllocatorSnapshot]) -> HeapSnapshot {
+        let mut snapshot = HeapSnapshot::new();
+        assert_eq!(snapshot.allocators.extend_from_slice_truncated(allocators), allocators.len());
+        snapshot
+    }
+
+    fn snapshot(memory_type: efi::MemoryType, heap_used: usize, blocks_in_use: usize) -> AllocatorSnapshot {
+        let mut buckets = BucketOccupancy::empty_buckets();
+        buckets[1].in_use = blocks_in_use;
+        AllocatorSnapshot {
+            memory_type,
+            stats: AllocationStatistics {
+                pool_allocation_calls: blocks_in_use,
+                claimed_pages: 256,
+                ..Default::default()
+            },
+            buckets,
+            heap_size: 0x10_0000,
+            heap_used,
+        }
+    }
+
+    #[test]
+    fn test_heap_diff() {
+        let earlier = heap_snapshot(&[snapshot(efi::BOOT_SERVICES_DATA, 0x3000, 2)]);
+        let later =
+            heap_snapshot(&[snapshot(efi::BOOT_SERVICES_DATA, 0x2000, 5), snapshot(efi::ACPI_MEMORY_NVS, 0x100, 0)]);
+
+        assert_eq!(
+            earlier.diff(&later).to_string(),
+            "BootServicesData         heap -0x1000 bytes used (0x2000), +0 pages (256), 3 allocations, 0 frees\n\
+             \x20     16-byte blocks in use: +3 (5)\n\
+             ACPI Memory NVS          heap +0x100 bytes used (0x100), +256 pages (256), 0 allocations, 0 frees\n"
+        );
+        assert_eq!(later.diff(&later).to_string(), "No heap changes.\n");
+    }
+
+    #[test]
+    fn test_heap_snapshot_display() {
+        let mut snapshot = heap_snapshot(&[snapshot(efi::LOADER_DATA, 0x2000, 5)]);
+        assert_eq!(
+            snapshot.to_string(),
+            "Loader Data              heap 0x2000 of 0x100000 bytes used, 256 pages, 5 allocations, 0 frees\n\
+             \x20     16-byte blocks: 5 in use, 0 free\n"
+        );
+
+        snapshot.omitted = 2;
+        assert!(snapshot.to_string().ends_with("0 free\n2 more allocators not captured\n"));
+        assert_eq!(HeapSnapshot::new().to_string(), "");
+    }
+
+    #[test]
+    fn test_heap_snapshot_refresh_replaces_contents() {
+        test_support::with_global_lock(|| {
+            unsafe {
+                test_support::init_test_gcd(None);
+                test_support::init_test_protocol_db();
+                test_support::reset_allocators();
+            }
+
+            let mut snapshot = heap_snapshot(&[snapshot(efi::ACPI_MEMORY_NVS, 0x2000, 5)]);
+            snapshot.omitted = 2;
+            assert!(snapshot.refresh());
+            assert_eq!(snapshot.allocators.len(), STATIC_ALLOCATORS.len());
+            assert_eq!(snapshot.omitted, 0);
+            assert!(snapshot.find(efi::BOOT_SERVICES_DATA).is_some());
+            assert!(snapshot.find(efi::ACPI_MEMORY_NVS).is_none());
+        })
+        .unwrap();
+    }
+}
@@ -14,7 +14,7 @@ use r_efi::efi;
 
 use super::{
     AllocationStrategy,
-    fixed_size_block_allocator::{AllocationStatistics, SpinLockedFixedSizeBlockAllocator},
+    fixed_size_block_allocator::{AllocationStatistics, AllocatorSnapshot, SpinLockedFixedSizeBlockAllocator},
 };
 use core::{
     alloc::{Allocator, GlobalAlloc, Layout},
@@ -233,6 +233,13 @@ impl UefiAllocator {
     pub fn stats(&self) -> AllocationStatistics {
         self.allocator.stats()
     }
+
+    /// Returns a copy of the allocator state, or `None` if the allocator is locked.
+    ///
+    /// See [`SpinLockedFixedSizeBlockAllocator::try_snapshot`]
+    pub fn try_snapshot(&self) -> Option<AllocatorSnapshot> {
+        self.allocator.try_snapshot()
+    }
 }
 
 unsafe impl GlobalAlloc for UefiAllocator {
@@ -10,10 +10,13 @@
 //!
 extern crate alloc;
 
-use crate::tpl_mutex::TplMutex;
+use crate::{
+    dispatcher::{DispatchDecision, FileAuthentication},
+    tpl_mutex::TplMutex,
+};
 use patina::{
     boot_services::StandardBootServices,
-    component::{IntoComponent, Storage, service::IntoService},
+    component::{IntoComponent, Storage, config_payload, service::IntoService},
     pi::hob::HobList,
     runtime_services::StandardRuntimeServices,
 };
@@ -51,6 +54,16 @@ pub trait ComponentInfo: Sized {
     /// A platform callback to register services with the core.
     #[inline(always)]
     fn services<'a>(_add: Add<'a, Service>) {}
+
+    /// A platform callback to decide whether a firmware file found by the dispatcher is used, based on its
+    /// authentication state.
+    ///
+    /// Defaults to [FileAuthentication::default_decision], which follows the EDK II handling of the Security
+    /// Architectural Protocol result.
+    #[inline(always)]
+    fn dispatch_policy<'a>(file: &FileAuthentication<'a>) -> DispatchDecision {
+        file.default_decision()
+    }
 }
 
 /// A marker to limit [Add] methods to only adding [Component](patina::component::Component)s
@@ -168,6 +181,21 @@ impl ComponentDispatcher {
         self.storage.set_runtime_services(rs);
     }
 
+    /// Applies a configuration payload to the configs in storage. See [`config_payload`] for the payload format.
+    pub(crate) fn apply_config_payload(&mut self, payload: &[u8]) {
+        let value = match config_payload::parse(payload) {
+            Ok(value) => value,
+            Err(err) => {
+                log::error!("Ignoring malformed configuration payload: {err}");
+                return;
+            }
+        };
+        match config_payload::apply(&value, &mut self.storage) {
+            Ok(count) => log::info!("Applied configuration payload to {count} configs."),
+            Err(err) => log::error!("Ignoring invalid configuration payload: {err}"),
+        }
+    }
+
     /// Parses the HOB list producing a `Hob\<T\>` struct for each guided HOB found with a registered parser.
     pub(crate) fn insert_hobs(&mut self, hob_list: &HobList<'_>) {
         for hob in hob_list.iter() {
@@ -23,6 +23,7 @@ use patina::pi::fw_fs::{self, ffs};
 use patina_ffs::{
     FirmwareFileSystemError,
     section::{SectionExtractor, SectionHeader},
+    signed::SignedContentsExtractor,
 };
 
 /// Component to install the UEFI Decompress Protocol.
@@ -40,7 +41,8 @@ impl DecompressProtocolInstaller {
     }
 }
 
-/// Section extractor that provides UEFI decompression, with an optional additional [SectionExtractor] implementation.
+/// Section extractor that provides UEFI decompression and strips signed contents wrappers, with an optional additional
+/// [SectionExtractor] implementation.
 #[derive(Default)]
 pub struct CoreExtractor(Option<&'static dyn SectionExtractor>);
 
@@ -108,6 +110,13 @@ impl SectionExtractor for CoreExtractor {
             Err(err) => return Err(err),
             Ok(buffer) => return Ok(buffer),
         }
+        // Strip signed contents wrappers so the signed sections can be dispatched. The signature is verified by the
+        // security subsystem, not here.
+        match SignedContentsExtractor.extract(section) {
+            Err(FirmwareFileSystemError::Unsupported) => (),
+            Err(err) => return Err(err),
+            Ok(buffer) => return Ok(buffer),
+        }
         self.0.as_ref().map_or(Err(FirmwareFileSystemError::Unsupported), |extractor| extractor.extract(section))
     }
 }
@@ -19,10 +19,16 @@ use patina::{
         logging::{perf_function_begin, perf_function_end},
         measurement::create_performance_measurement,
     },
-    pi::{fw_fs::ffs, protocols::firmware_volume_block},
+    pi::{
+        fw_fs::{self, ffs},
+        protocols::{firmware_volume_block, security},
+    },
 };
 use patina_ffs::{
-    section::{Section, SectionExtractor},
+    FirmwareFileSystemError,
+    file::FileRef,
+    section::{Section, SectionExtractor, SectionHeader},
+    signed::ContentsSignature,
     volume::VolumeRef,
 };
 use patina_internal_depex::{AssociatedDependency, Depex, Opcode};
@@ -69,6 +75,64 @@ const ALL_ARCH_DEPEX: &[Opcode] = &[
     Opcode::End,
 ];
 
+/// The kind of firmware file described by a [FileAuthentication].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// A driver image.
+    Driver,
+    /// A firmware volume image, installed as a new firmware volume when dispatched.
+    FirmwareVolumeImage,
+}
+
+/// The authentication state of a firmware file the dispatcher is about to use.
+#[derive(Debug, Clone, Copy)]
+pub struct FileAuthentication<'a> {
+    /// The name of the file.
+    pub file_name: efi::Guid,
+    /// The kind of the file.
+    pub kind: FileKind,
+    /// The authentication status aggregated from the GUID-defined sections of the file (`AUTH_STATUS_*` bits from
+    /// [patina::pi::protocols::security]).
+    pub authentication_status: u32,
+    /// The status returned for the file by the Security Architectural Protocols.
+    pub security_status: efi::Status,
+    /// The signatures of the signed contents sections of the file, such as a signed firmware volume image, for the
+    /// platform to verify. The dispatcher does not verify them itself.
+    pub signatures: &'a [ContentsSignature],
+}
+
+impl FileAuthentication<'_> {
+    /// The decision made when the platform does not provide a dispatch policy.
+    ///
+    /// This matches the EDK II handling of the Security Architectural Protocol result: a file that authenticated is
+    /// dispatched and a file that returned `EFI_SECURITY_VIOLATION` is left untrusted. A driver that returned any other
+    /// status is never used. A firmware volume image that returned any other status, such as an error building its
+    /// device path, is deferred and authenticated again on the next dispatch pass.
+    pub fn default_decision(&self) -> DispatchDecision {
+        match (self.security_status, self.kind) {
+            (efi::Status::SUCCESS, _) => DispatchDecision::Dispatch,
+            (efi::Status::SECURITY_VIOLATION, _) | (_, FileKind::FirmwareVolumeImage) => DispatchDecision::Defer,
+            (_, FileKind::Driver) => DispatchDecision::Deny,
+        }
+    }
+}
+
+/// What the dispatcher does with a firmware file, as decided by [ComponentInfo::dispatch_policy](crate::ComponentInfo::dispatch_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchDecision {
+    /// Dispatch the file.
+    Dispatch,
+    /// Leave the file untrusted. It is dispatched if it is later trusted with the `Trust()` DXE service.
+    Defer,
+    /// Drop the file. It is never dispatched.
+    Deny,
+    /// Dispatch the file regardless of its authentication state, logging that state.
+    Audit,
+}
+
+/// A platform dispatch policy. See [ComponentInfo::dispatch_policy](crate::ComponentInfo::dispatch_policy).
+pub type DispatchPolicy = fn(&FileAuthentication<'_>) -> DispatchDecision;
+
 struct PendingDriver {
     firmware_volume_handle: efi::Handle,
     device_path: *mut efi::protocols::device_path::Protocol,
@@ -77,6 +141,8 @@ struct PendingDriver {
     pe32: Section,
     image_handle: Option<efi::Handle>,
     security_status: efi::Status,
+    authentication_status: u32,
+    signatures: Vec<ContentsSignature>,
 }
 
 struct PendingFirmwareVolumeImage {
@@ -84,36 +150,78 @@ struct PendingFirmwareVolumeImage {
     file_name: efi::Guid,
     depex: Option<Depex>,
     fv_sections: Vec<Section>,
+    authentication_status: u32,
+    signatures: Vec<ContentsSignature>,
 }
 
 impl PendingFirmwareVolumeImage {
     // authenticate the pending firmware volume via the Security Architectural Protocol
-    fn evaluate_auth(&self) -> Result<(), EfiError> {
+    fn evaluate_auth(&self) -> efi::Status {
         let security_protocol = unsafe {
-            match PROTOCOL_DB.locate_protocol(patina::pi::protocols::security::PROTOCOL_GUID) {
-                Ok(protocol) => (protocol as *mut patina::pi::protocols::security::Protocol)
-                    .as_ref()
-                    .expect("Security Protocol should not be null"),
+            match PROTOCOL_DB.locate_protocol(security::PROTOCOL_GUID) {
+                Ok(protocol) => {
+                    (protocol as *mut security::Protocol).as_ref().expect("Security Protocol should not be null")
+                }
                 //If security protocol is not located, then assume it has not yet been produced and implicitly trust the
                 //Firmware Volume.
-                Err(_) => return Ok(()),
+                Err(_) => return efi::Status::SUCCESS,
             }
         };
-        let file_path = device_path_bytes_for_fv_file(self.parent_fv_handle, self.file_name)
-            .map_err(|status| EfiError::status_to_result(status).unwrap_err())?;
-
-        //Important Note: the present section extraction implementation does not support section extraction-based
-        //authentication status, so it is hard-coded to zero here. The primary security handlers for the main usage
-        //scenarios (TPM measurement and UEFI Secure Boot) do not use it.
-        let status = (security_protocol.file_authentication_state)(
-            security_protocol as *const _ as *mut patina::pi::protocols::security::Protocol,
-            0,
+        let file_path = match device_path_bytes_for_fv_file(self.parent_fv_handle, self.file_name) {
+            Ok(file_path) => file_path,
+            Err(status) => return status,
+        };
+
+        (security_protocol.file_authentication_state)(
+            security_protocol as *const _ as *mut security::Protocol,
+            self.authentication_status,
             file_path.as_ptr() as *const _ as *mut efi::protocols::device_path::Protocol,
-        );
-        EfiError::status_to_result(status)
+        )
     }
 }
 
+// Aggregates the authentication status of the GUID-defined sections of a file.
+//
+// Only sections with GUIDED_SECTION_AUTH_STATUS_VALID set contribute. A CRC32 section fails extraction if its checksum
+// does not match, so an extracted CRC32 section was tested and passed and adds no status bits, as in EDK II's CRC32
+// GUIDed section extraction. The other section extractors do not report the result of any verification they
+// perform, so their sections are reported as signed but not tested, as EDK II does for sections it extracts without a
+// GUIDed section extraction protocol.
+fn aggregate_authentication_status<'a>(sections: impl IntoIterator<Item = &'a Section>) -> u32 {
+    sections.into_iter().fold(0, |status, section| match section.header() {
+        SectionHeader::GuidDefined(header, _, _)
+            if header.attributes & ffs::section::header::GUIDED_SECTION_AUTH_STATUS_VALID != 0
+                && header.section_definition_guid != fw_fs::guid::CRC32_SECTION =>
+        {
+            status | security::AUTH_STATUS_IMAGE_SIGNED | security::AUTH_STATUS_NOT_TESTED
+        }
+        _ => status,
+    })
+}
+
+// Extracts the sections of a file in an FV. A section that could not be extracted in time only costs this file, not
+// the rest of the FV, so a timeout is logged and reported as `None`.
+fn extract_file_sections(
+    file: &FileRef,
+    extractor: &dyn SectionExtractor,
+) -> Result<Option<Vec<Section>>, FirmwareFileSystemError> {
+    match file.sections_with_extractor(extractor) {
+        Ok(sections) => Ok(Some(sections)),
+        Err(FirmwareFileSystemError::Timeout) => {
+            log::error!("Extracting {:?} timed out - skipping.", guid_fmt!(file.name()));
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Collects the signatures of the signed contents sections of a file, so the dispatch policy can verify them.
+fn contents_signatures<'a>(
+    sections: impl IntoIterator<Item = &'a Section>,
+) -> Result<Vec<ContentsSignature>, FirmwareFileSystemError> {
+    sections.into_iter().filter_map(|section| ContentsSignature::from_section(section).transpose()).collect()
+}
+
 #[derive(Debug, Eq, PartialEq)]
 struct OrdGuid(efi::Guid);
 
@@ -128,7 +236,6 @@ impl Ord for OrdGuid {
     }
 }
 
-#[derive(Default)]
 struct DispatcherContext {
     executing: bool,
     arch_protocols_available: bool,
@@ -139,6 +246,7 @@ struct DispatcherContext {
     associated_after: BTreeMap<OrdGuid, Vec<PendingDriver>>,
     processed_fvs: BTreeSet<efi::Handle>,
     section_extractor: CoreExtractor,
+    dispatch_policy: DispatchPolicy,
 }
 
 impl DispatcherContext {
@@ -153,6 +261,7 @@ impl DispatcherContext {
             associated_after: BTreeMap::new(),
             processed_fvs: BTreeSet::new(),
             section_extractor: CoreExtractor::new(),
+            dispatch_policy: |file| file.default_decision(),
         }
     }
 }
@@ -212,6 +321,7 @@ pub fn dispatch() -> Result<bool, EfiError> {
     }
     log::info!("Depex evaluation complete, scheduled {:} drivers", scheduled.len());
 
+    let dispatch_policy = DISPATCHER_CONTEXT.lock().dispatch_policy;
     let mut dispatch_attempted = false;
     for mut driver in scheduled {
         if driver.image_handle.is_none() {
@@ -230,26 +340,41 @@ pub fn dispatch() -> Result<bool, EfiError> {
         }
 
         if let Some(image_handle) = driver.image_handle {
-            match driver.security_status {
-                efi::Status::SUCCESS => {
+            let file = FileAuthentication {
+                file_name: driver.file_name,
+                kind: FileKind::Driver,
+                authentication_status: driver.authentication_status,
+                security_status: driver.security_status,
+                signatures: &driver.signatures,
+            };
+            match dispatch_policy(&file) {
+                decision @ (DispatchDecision::Dispatch | DispatchDecision::Audit) => {
+                    if decision == DispatchDecision::Audit {
+                        log::warn!(
+                            "Auditing driver: {:?} with authentication status: {:#x} and security status: {:x?}",
+                            guid_fmt!(driver.file_name),
+                            file.authentication_status,
+                            file.security_status
+                        );
+                    }
                     dispatch_attempted = true;
                     // Note: ignore error result of core_start_image here - an image returning an error code is expected in some
                     // cases, and a debug output for that is already implemented in core_start_image.
                     let _status = core_start_image(image_handle);
                 }
-                efi::Status::SECURITY_VIOLATION => {
+                DispatchDecision::Defer => {
                     log::info!(
                         "Deferring driver: {:?} due to security status: {:x?}",
                         guid_fmt!(driver.file_name),
-                        efi::Status::SECURITY_VIOLATION
+                        driver.security_status
                     );
                     DISPATCHER_CONTEXT.lock().pending_drivers.push(driver);
                 }
-                unexpected_status => {
+                DispatchDecision::Deny => {
                     log::info!(
                         "Dropping driver: {:?} due to security status: {:x?}",
                         guid_fmt!(driver.file_name),
-                        unexpected_status
+                        driver.security_status
                     );
                 }
             }
@@ -266,7 +391,29 @@ pub fn dispatch() -> Result<bool, EfiError> {
                 None => true,
             };
 
-            if depex_satisfied && candidate.evaluate_auth().is_ok() {
+            if !depex_satisfied {
+                dispatcher.pending_firmware_volume_images.push(candidate);
+                continue;
+            }
+
+            let file = FileAuthentication {
+                file_name: candidate.file_name,
+                kind: FileKind::FirmwareVolumeImage,
+                authentication_status: candidate.authentication_status,
+                security_status: candidate.evaluate_auth(),
+                signatures: &candidate.signatures,
+            };
+            let decision = (dispatcher.dispatch_policy)(&file);
+            if decision == DispatchDecision::Audit {
+                log::warn!(
+                    "Auditing firmware volume image: {:?} with authentication status: {:#x} and security status: {:x?}",
+                    guid_fmt!(candidate.file_name),
+                    file.authentication_status,
+                    file.security_status
+                );
+            }
+
+            if matches!(decision, DispatchDecision::Dispatch | DispatchDecision::Audit) {
                 for section in candidate.fv_sections {
                     let fv_data = Box::from(section.try_content_as_slice()?);
                     dispatcher.fv_section_data.push(fv_data);
@@ -287,8 +434,14 @@ pub fn dispatch() -> Result<bool, EfiError> {
                         );
                     }
                 }
-            } else {
+            } else if decision == DispatchDecision::Defer {
                 dispatcher.pending_firmware_volume_images.push(candidate)
+            } else {
+                log::info!(
+                    "Dropping firmware volume image: {:?} due to security status: {:x?}",
+                    guid_fmt!(candidate.file_name),
+                    file.security_status
+                );
             }
         }
     }
@@ -348,7 +501,9 @@ fn add_fv_handles(new_handles: Vec<efi::Handle>) -> Result<(), EfiError> {
                 if file.file_type_raw() == ffs::file::raw::r#type::DRIVER {
                     let file = file.clone();
                     let file_name = file.name();
-                    let sections = file.sections_with_extractor(&dispatcher.section_extractor)?;
+                    let Some(sections) = extract_file_sections(&file, &dispatcher.section_extractor)? else {
+                        continue;
+                    };
 
                     let depex = sections
                         .iter()
@@ -359,6 +514,9 @@ fn add_fv_handles(new_handles: Vec<efi::Handle>) -> Result<(), EfiError> {
                         .transpose()?
                         .map(Depex::from);
 
+                    let authentication_status = aggregate_authentication_status(&sections);
+                    let signatures = contents_signatures(&sections)?;
+
                     if let Some(pe32_section) =
                         sections.into_iter().find(|x| x.section_type() == Some(ffs::section::Type::Pe32))
                     {
@@ -414,6 +572,8 @@ fn add_fv_handles(new_handles: Vec<efi::Handle>) -> Result<(), EfiError> {
                             depex,
                             image_handle: None,
                             security_status: efi::Status::NOT_READY,
+                            authentication_status,
+                            signatures,
                         });
                     } else {
                         log::warn!("driver {:?} does not contain a PE32 section.", guid_fmt!(file_name));
@@ -423,7 +583,9 @@ fn add_fv_handles(new_handles: Vec<efi::Handle>) -> Result<(), EfiError> {
                     let file = file.clone();
                     let file_name = file.name();
 
-                    let sections = file.sections_with_extractor(&dispatcher.section_extractor)?;
+                    let Some(sections) = extract_file_sections(&file, &dispatcher.section_extractor)? else {
+                        continue;
+                    };
 
                     let depex = sections
                         .iter()
@@ -434,6 +596,9 @@ fn add_fv_handles(new_handles: Vec<efi::Handle>) -> Result<(), EfiError> {
                         .transpose()?
                         .map(Depex::from);
 
+                    let authentication_status = aggregate_authentication_status(&sections);
+                    let signatures = contents_signatures(&sections)?;
+
                     let fv_sections = sections
                         .into_iter()
                         .filter(|s| s.section_type() == Some(ffs::section::Type::FirmwareVolumeImage))
@@ -445,6 +610,8 @@ fn add_fv_handles(new_handles: Vec<efi::Handle>) -> Result<(), EfiError> {
                             file_name,
                             depex,
                             fv_sections,
+                            authentication_status,
+                            signatures,
                         });
                     } else {
                         log::warn!(
@@ -517,6 +684,10 @@ pub fn register_section_extractor(extractor: &'static dyn SectionExtractor) {
     DISPATCHER_CONTEXT.lock().section_extractor.set_extractor(extractor);
 }
 
+pub fn register_dispatch_policy(policy: DispatchPolicy) {
+    DISPATCHER_CONTEXT.lock().dispatch_policy = policy;
+}
+
 pub fn display_discovered_not_dispatched() {
     for driver in &DISPATCHER_CONTEXT.lock().pending_drivers {
         log::warn!("Driver {:?} found but not dispatched.", guid_fmt!(driver.file_name));
@@ -659,7 +830,7 @@ mod tests {
         set_logger();
         with_locked_state(|| {
             init_dispatcher();
-            register_section_extractor(Box::leak(Box::new(patina_ffs_extractors::BrotliSectionExtractor)));
+            register_section_extractor(Box::leak(Box::new(patina_ffs_extractors::BrotliSectionExtractor::new())));
         });
     }
 
@@ -806,6 +977,133 @@ mod tests {
         let _dropped_fv = unsafe { Box::from_raw(fv_raw) };
     }
 
+    #[test]
+    fn test_add_fv_handle_skips_files_that_time_out() {
+        struct TimingOutExtractor;
+        impl SectionExtractor for TimingOutExtractor {
+            fn extract(&self, _: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+                Err(FirmwareFileSystemError::Timeout)
+            }
+        }
+
+        fn driver(name: u8, guided: bool) -> patina_ffs::file::File {
+            let pe32 = Section::new_from_header_with_data(
+                SectionHeader::Standard(ffs::section::raw_type::PE32, 4),
+                b"pe32".to_vec(),
+            )
+            .unwrap();
+            let section = if guided {
+                patina_ffs_extractors::SectionBuilder::new()
+                    .build(patina_ffs_extractors::GuidedSectionFormat::Crc32, &pe32.serialize().unwrap())
+                    .unwrap()
+            } else {
+                pe32
+            };
+            let mut file =
+                patina_ffs::file::File::new(efi::Guid::from_bytes(&[name; 16]), ffs::file::raw::r#type::DRIVER);
+            file.sections_mut().push(section);
+            file
+        }
+
+        set_logger();
+        let mut volume = patina_ffs::volume::Volume::new(vec![patina::pi::fw_fs::fv::BlockMapEntry {
+            num_blocks: 4,
+            length: 0x1000,
+        }]);
+        volume.files_mut().push(driver(1, true));
+        volume.files_mut().push(driver(2, false));
+        let fv_raw = Box::into_raw(volume.serialize().unwrap().into_boxed_slice());
+
+        with_locked_state(|| {
+            register_section_extractor(&TimingOutExtractor);
+            // Safety: fv is leaked to ensure it is not freed and remains valid for the duration of the program.
+            let handle =
+                unsafe { crate::fv::core_install_firmware_volume(fv_raw.expose_provenance() as u64, None).unwrap() };
+            add_fv_handles(vec![handle]).expect("A timed out file should not fail the FV");
+
+            // Only the driver whose sections could be extracted is pending.
+            let pending = &DISPATCHER_CONTEXT.lock().pending_drivers;
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].file_name, efi::Guid::from_bytes(&[2; 16]));
+        });
+
+        let _dropped_fv = unsafe { Box::from_raw(fv_raw) };
+    }
+
+    #[test]
+    fn test_dispatch_policy_deny_drops_fv_image() {
+        set_logger();
+        let mut file = File::open(test_collateral!("NESTEDFV.Fv")).unwrap();
+        let mut fv: Vec<u8> = Vec::new();
+        file.read_to_end(&mut fv).expect("failed to read test file");
+        let fv = fv.into_boxed_slice();
+        let fv_raw = Box::into_raw(fv);
+
+        with_locked_state(|| {
+            register_dispatch_policy(|_| DispatchDecision::Deny);
+            // Safety: fv is leaked to ensure it is not freed and remains valid for the duration of the program.
+            let handle =
+                unsafe { crate::fv::core_install_firmware_volume(fv_raw.expose_provenance() as u64, None).unwrap() };
+            add_fv_handles(vec![handle]).expect("Failed to add FV handle");
+
+            assert_eq!(core_dispatcher(), Err(EfiError::NotFound));
+            assert!(DISPATCHER_CONTEXT.lock().pending_firmware_volume_images.is_empty());
+        });
+
+        let _dropped_fv = unsafe { Box::from_raw(fv_raw) };
+    }
+
+    #[test]
+    fn test_default_dispatch_decision() {
+        let decision = |kind, security_status| {
+            FileAuthentication {
+                file_name: efi::Guid::from_bytes(&[0; 16]),
+                kind,
+                authentication_status: 0,
+                security_status,
+                signatures: &[],
+            }
+            .default_decision()
+        };
+
+        for kind in [FileKind::Driver, FileKind::FirmwareVolumeImage] {
+            assert_eq!(decision(kind, efi::Status::SUCCESS), DispatchDecision::Dispatch);
+            assert_eq!(decision(kind, efi::Status::SECURITY_VIOLATION), DispatchDecision::Defer);
+        }
+        assert_eq!(decision(FileKind::Driver, efi::Status::ACCESS_DENIED), DispatchDecision::Deny);
+        assert_eq!(decision(FileKind::Driver, efi::Status::NOT_READY), DispatchDecision::Deny);
+        assert_eq!(decision(FileKind::FirmwareVolumeImage, efi::Status::ACCESS_DENIED), DispatchDecision::Defer);
+        assert_eq!(decision(FileKind::FirmwareVolumeImage, efi::Status::NOT_FOUND), DispatchDecision::Defer);
+    }
+
+    #[test]
+    fn test_aggregate_authentication_status() {
+        let guided_section = |section_definition_guid, attributes| {
+            let header = ffs::section::header::GuidDefined {
+                section_definition_guid,
+                data_offset: (core::mem::size_of::<ffs::section::Header>()
+                    + core::mem::size_of::<ffs::section::header::GuidDefined>()) as u16,
+                attributes,
+            };
+            Section::new_from_header_with_data(SectionHeader::GuidDefined(header, Vec::new(), 0), Vec::new()).unwrap()
+        };
+        let raw_section =
+            Section::new_from_header_with_data(SectionHeader::Standard(ffs::section::raw_type::RAW, 4), vec![0; 4])
+                .unwrap();
+        let other_guid = efi::Guid::from_bytes(&[0xa5; 16]);
+        let unauthenticated = guided_section(other_guid, ffs::section::header::GUIDED_SECTION_PROCESSING_REQUIRED);
+        let authenticated = guided_section(other_guid, ffs::section::header::GUIDED_SECTION_AUTH_STATUS_VALID);
+        let crc32 = guided_section(fw_fs::guid::CRC32_SECTION, ffs::section::header::GUIDED_SECTION_AUTH_STATUS_VALID);
+
+        assert_eq!(aggregate_authentication_status(&[raw_section.clone(), unauthenticated.clone()]), 0);
+        // A verified CRC32 section is tested and passed, not signed.
+        assert_eq!(aggregate_authentication_status(&[crc32.clone(), raw_section.clone()]), 0);
+        assert_eq!(
+            aggregate_authentication_status(&[unauthenticated, crc32, authenticated, raw_section]),
+            security::AUTH_STATUS_IMAGE_SIGNED | security::AUTH_STATUS_NOT_TESTED
+        );
+    }
+
     #[test]
     fn test_display_discovered_not_dispatched_does_not_fail() {
         set_logger();
@@ -984,4 +1282,40 @@ mod tests {
             assert!(SECURITY_CALL_EXECUTED.load(core::sync::atomic::Ordering::SeqCst));
         })
     }
+
+    #[test]
+    fn test_default_dispatch_policy_retries_fv_image_on_authentication_error() {
+        set_logger();
+        let mut file = File::open(test_collateral!("NESTEDFV.Fv")).unwrap();
+        let mut fv: Vec<u8> = Vec::new();
+        file.read_to_end(&mut fv).expect("failed to read test file");
+
+        with_locked_state(|| {
+            extern "efiapi" fn not_found_file_authentication_state(
+                _this: *mut patina::pi::protocols::security::Protocol,
+                _authentication_status: u32,
+                _file: *mut efi::protocols::device_path::Protocol,
+            ) -> efi::Status {
+                efi::Status::NOT_FOUND
+            }
+
+            let security_protocol = patina::pi::protocols::security::Protocol {
+                file_authentication_state: not_found_file_authentication_state,
+            };
+            PROTOCOL_DB
+                .install_protocol_interface(
+                    None,
+                    patina::pi::protocols::security::PROTOCOL_GUID,
+                    &security_protocol as *const _ as *mut _,
+                )
+                .unwrap();
+            // Safety: fv is leaked to ensure it is not freed and remains valid for the duration of the program.
+            let handle = unsafe { crate::fv::core_install_firmware_volume(fv.as_ptr() as u64, None).unwrap() };
+            add_fv_handles(vec![handle]).expect("Failed to add FV handle");
+
+            // The image is neither installed nor dropped, so a later pass authenticates it again.
+            assert_eq!(core_dispatcher(), Err(EfiError::NotFound));
+            assert_eq!(DISPATCHER_CONTEXT.lock().pending_firmware_volume_images.len(), 1);
+        })
+    }
 }
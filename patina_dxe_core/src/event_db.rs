@@ -482,10 +482,6 @@ impl EventDb {
     }
 
     fn timer_tick(&mut self, current_time: u64) {
-        // Poll the debugger before processing any events. This has no effect if
-        // the debugger is not enabled.
-        patina_debugger::poll_debugger();
-
         let events: Vec<usize> = self.events.keys().rev().cloned().collect();
         for event in events {
             let current_event = if let Some(current) = self.events.get_mut(&event) {
@@ -9,6 +9,7 @@
 use core::{
     ffi::c_void,
     sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
 };
 
 use r_efi::efi;
@@ -292,6 +293,10 @@ extern "efiapi" fn timer_tick(time: u64) {
     let old_tpl = raise_tpl(efi::TPL_HIGH_LEVEL);
     SYSTEM_TIME.fetch_add(time, Ordering::SeqCst);
     let current_time = SYSTEM_TIME.load(Ordering::SeqCst);
+    // Check for a debugger break request before processing any events. This is done
+    // without the event database locked so that it remains usable while broken in, and
+    // has no effect if the debugger is not enabled. System time is in 100ns units.
+    patina_debugger::poll_debugger_periodic(Duration::from_nanos(current_time.saturating_mul(100)));
     EVENT_DB.timer_tick(current_time);
     restore_tpl(old_tpl); //implicitly dispatches timer notifies if any.
 }
@@ -310,6 +315,13 @@ extern "efiapi" fn timer_available_callback(event: efi::Event, _context: *mut c_
     }
 }
 
+extern "efiapi" fn debugger_ready_to_boot_callback(event: efi::Event, _context: *mut c_void) {
+    patina_debugger::notify_ready_to_boot();
+    if let Err(status_err) = EVENT_DB.close_event(event) {
+        log::warn!("Could not close event for debugger_ready_to_boot_callback due to error {status_err:?}");
+    }
+}
+
 // indicates that eventing subsystem is fully initialized.
 static EVENT_DB_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
@@ -346,6 +358,17 @@ pub fn init_events_support(bs: &mut efi::BootServices) {
         .register_protocol_notify(timer::PROTOCOL_GUID, event)
         .expect("Failed to register protocol notify on timer arch callback.");
 
+    //set up call back to let a debugger installed for late attach start listening at ReadyToBoot.
+    if let Err(status) = EVENT_DB.create_event(
+        efi::EVT_NOTIFY_SIGNAL,
+        efi::TPL_CALLBACK,
+        Some(debugger_ready_to_boot_callback),
+        None,
+        Some(efi::EVENT_GROUP_READY_TO_BOOT),
+    ) {
+        log::error!("Failed to register the debugger ReadyToBoot callback. Status {status:#X?}");
+    }
+
     //Indicate eventing is initialized
     EVENT_DB_INITIALIZED.store(true, Ordering::SeqCst);
 }
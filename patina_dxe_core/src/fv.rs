@@ -12,7 +12,7 @@ use core::{
     slice,
 };
 
-use alloc::{boxed::Box, collections::BTreeMap};
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
 use patina::pi::{
     self,
     fw_fs::{ffs, fv, fvb},
@@ -831,6 +831,30 @@ pub fn parse_hob_fvs(hob_list: &hob::HobList) -> Result<(), efi::Status> {
     Ok(())
 }
 
+/// Returns the content of the first RAW section of the file named `file_name` in the FVs defined in the HOB list.
+pub fn read_hob_fv_raw_file(hob_list: &hob::HobList, file_name: &efi::Guid) -> Option<Vec<u8>> {
+    hob_list.iter().find_map(|h| {
+        let hob::Hob::FirmwareVolume(fv) = h else {
+            return None;
+        };
+        // Safety: base addresses of FirmwareVolume HOBs are assumed to be valid and accessible.
+        let fv_slice = unsafe { slice::from_raw_parts(fv.base_address as *const u8, fv.length as usize) };
+        read_raw_file(fv_slice, file_name)
+    })
+}
+
+/// Returns the content of the first RAW section of the file named `file_name` in an FV, extracting encapsulated
+/// sections with the registered section extractor.
+fn read_raw_file(fv_slice: &[u8], file_name: &efi::Guid) -> Option<Vec<u8>> {
+    let file = VolumeRef::new(fv_slice).ok()?.files().filter_map(Result::ok).find(|file| file.name() == *file_name)?;
+    let sections = file
+        .sections_with_extractor(&PRIVATE_FV_DATA.lock().section_extractor)
+        .inspect_err(|err| log::error!("Failed to read sections of file {:?}: {:?}", file_name, err))
+        .ok()?;
+    let section = sections.iter().find(|section| section.section_type() == Some(ffs::section::Type::Raw))?;
+    section.try_content_as_slice().ok().map(<[u8]>::to_vec)
+}
+
 /// Registers a section extractor to be used when reading sections from files in firmware volumes.
 pub fn register_section_extractor(extractor: &'static dyn SectionExtractor) {
     PRIVATE_FV_DATA.lock().section_extractor.set_extractor(extractor);
@@ -1543,7 +1567,7 @@ mod tests {
             PRIVATE_FV_DATA
                 .lock()
                 .section_extractor
-                .set_extractor(Box::leak(Box::new(patina_ffs_extractors::BrotliSectionExtractor)));
+                .set_extractor(Box::leak(Box::new(patina_ffs_extractors::BrotliSectionExtractor::new())));
 
             let mut fv_interface = Box::from(pi::protocols::firmware_volume::Protocol {
                 get_volume_attributes: fv_get_volume_attributes,
@@ -1609,4 +1633,33 @@ mod tests {
         })
         .expect("Failed to read Firmware Volume Section");
     }
+
+    #[test]
+    fn test_read_raw_file() {
+        use patina_ffs::{
+            file::File,
+            section::{Section, SectionHeader},
+            volume::Volume,
+        };
+
+        let name = efi::Guid::from_bytes(&[0x5a; 16]);
+        let mut file = File::new(name, ffs::file::raw::r#type::FREEFORM);
+        file.sections_mut().push(
+            Section::new_from_header_with_data(
+                SectionHeader::Standard(ffs::section::raw_type::RAW, 7),
+                b"{a: 1 }".to_vec(),
+            )
+            .unwrap(),
+        );
+        let mut volume = Volume::new(vec![fv::BlockMapEntry { num_blocks: 1, length: 0x1000 }]);
+        volume.files_mut().push(file);
+        let fv_bytes = volume.serialize().unwrap();
+
+        test_support::with_global_lock(|| {
+            assert_eq!(read_raw_file(&fv_bytes, &name).as_deref(), Some(&b"{a: 1 }"[..]));
+            assert_eq!(read_raw_file(&fv_bytes, &efi::Guid::from_bytes(&[0xa5; 16])), None);
+            assert_eq!(read_raw_file(&[0; 16], &name), None);
+        })
+        .unwrap();
+    }
 }
@@ -1146,6 +1146,15 @@ impl GCD {
         self.memory_blocks.len()
     }
 
+    /// Calls `visit` for each memory space descriptor, in ascending address order.
+    pub fn for_each_memory_descriptor(&self, mut visit: impl FnMut(&dxe_services::MemorySpaceDescriptor)) {
+        let mut current = self.memory_blocks.first_idx();
+        while let Some(idx) = current {
+            visit(self.memory_blocks.get_with_idx(idx).expect("idx is valid from next_idx").as_ref());
+            current = self.memory_blocks.next_idx(idx);
+        }
+    }
+
     //Note: truncated strings here are expected and are for alignment with EDK2 reference prints.
     const GCD_MEMORY_TYPE_NAMES: [&'static str; 8] = [
         "NonExist ", // EfiGcdMemoryTypeNonExistent
@@ -2622,6 +2631,18 @@ impl SpinLockedGcd {
         self.memory.lock().memory_descriptor_count()
     }
 
+    /// Calls `visit` for each memory space descriptor, in ascending address order. Unlike the other accessors, this
+    /// does not wait for the GCD lock, so it is safe to call while the system is broken into the debugger.
+    ///
+    /// Returns [`EfiError::NotReady`] if the GCD is locked.
+    pub fn try_for_each_memory_descriptor(
+        &self,
+        visit: impl FnMut(&dxe_services::MemorySpaceDescriptor),
+    ) -> Result<(), EfiError> {
+        self.memory.try_lock().ok_or(EfiError::NotReady)?.for_each_memory_descriptor(visit);
+        Ok(())
+    }
+
     /// Acquires lock and delegates to [`IoGCD::add_io_space`]
     pub fn add_io_space(
         &self,
@@ -2664,6 +2685,11 @@ impl SpinLockedGcd {
     pub fn io_descriptor_count(&self) -> usize {
         self.io.lock().io_descriptor_count()
     }
+
+    /// Returns true if the memory GCD lock is currently held.
+    pub fn is_memory_locked(&self) -> bool {
+        self.memory.try_lock().is_none()
+    }
 }
 
 impl Display for SpinLockedGcd {
@@ -5247,6 +5273,42 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_try_for_each_memory_descriptor() {
+        with_locked_state(|| {
+            static GCD: SpinLockedGcd = SpinLockedGcd::new(None);
+            GCD.init(48, 16);
+
+            let mem = unsafe { get_memory(MEMORY_BLOCK_SLICE_SIZE * 3) };
+            let address = align_up(mem.as_ptr() as usize, 0x1000).unwrap();
+
+            // SAFETY: We just allocated this memory to use in the test
+            unsafe {
+                GCD.init_memory_blocks(
+                    dxe_services::GcdMemoryType::SystemMemory,
+                    address,
+                    MEMORY_BLOCK_SLICE_SIZE,
+                    efi::MEMORY_WB,
+                )
+                .unwrap();
+
+                GCD.add_memory_space(GcdMemoryType::MemoryMappedIo, 0x8000, 0x2000, efi::MEMORY_UC).unwrap();
+            }
+
+            let mut descriptors: Vec<MemorySpaceDescriptor> = Vec::new();
+            GCD.try_for_each_memory_descriptor(|desc| descriptors.push(*desc)).unwrap();
+            assert_eq!(descriptors.len(), GCD.memory_descriptor_count());
+            assert!(descriptors.windows(2).all(|pair| pair[0].base_address + pair[0].length == pair[1].base_address));
+            assert!(descriptors.iter().any(|d| d.base_address == 0x8000
+                && d.length == 0x2000
+                && d.memory_type == GcdMemoryType::MemoryMappedIo));
+
+            // The GCD is not waited on while it is in use.
+            let _lock = GCD.memory.lock();
+            assert_eq!(GCD.try_for_each_memory_descriptor(|_| {}), Err(EfiError::NotReady));
+        });
+    }
+
     #[test]
     fn test_descriptor_iterator() {
         with_locked_state(|| {
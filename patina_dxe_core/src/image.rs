@@ -7,8 +7,9 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 use alloc::{boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec};
-use core::{convert::TryInto, ffi::c_void, mem::transmute, slice, slice::from_raw_parts};
+use core::{convert::TryInto, ffi::c_void, fmt, mem::transmute, slice, slice::from_raw_parts};
 use patina::{
+    BinaryGuid,
     base::{DEFAULT_CACHE_ATTR, UEFI_PAGE_SIZE, align_up},
     error::EfiError,
     guids,
@@ -991,10 +992,20 @@ pub fn core_load_image(
     );
 
     // Notify the debugger of the image load.
-    patina_debugger::notify_module_load(
+    let debug_info = private_info.pe_info.debug_path.as_deref().map(|symbol_path| patina_debugger::ModuleDebugInfo {
+        symbol_path,
+        first_section_offset: private_info
+            .pe_info
+            .sections
+            .first()
+            .map_or(0, |section| section.virtual_address as usize),
+        symbol_id: private_info.pe_info.pdb_signature.map(|(guid, age)| patina_debugger::SymbolId::Pdb { guid, age }),
+    });
+    patina_debugger::notify_module_load_with_debug_info(
         private_info.pe_info.filename.as_ref().unwrap_or(&String::from("")),
         private_info.image_info.image_base as usize,
         private_info.image_info.image_size as usize,
+        debug_info,
     );
 
     // install the loaded_image device path protocol for the new image. If input device path is not null, then make a
@@ -1165,6 +1176,12 @@ pub fn core_start_image(image_handle: efi::Handle) -> Result<(), efi::Status> {
         if let Some(private_info) = private_data.private_image_data.get_mut(&image_handle) {
             private_info.started = true;
             let entry_point = private_info.entry_point;
+            let module_name = private_info.pe_info.filename.clone().unwrap_or_default();
+            let file_path = private_info.image_info.file_path;
+            let file_guid = match file_path.is_null() {
+                true => None,
+                false => get_file_guid_from_device_path(file_path).ok().map(BinaryGuid),
+            };
 
             // save a pointer to the yielder so that exit() can use it.
             private_data.image_start_contexts.push(yielder as *const Yielder<_, _>);
@@ -1174,6 +1191,10 @@ pub fn core_start_image(image_handle: efi::Handle) -> Result<(), efi::Status> {
             // drop our reference to the private data (i.e. release the lock).
             drop(private_data);
 
+            // give the debugger a chance to break before the entry point runs.
+            patina_debugger::notify_module_start(&module_name, file_guid.as_ref(), entry_point as usize);
+            drop(module_name);
+
             // invoke the entry point. Code on the other side of this pointer is
             // FFI, which is inherently unsafe, but it's not  "technically" unsafe
             // from a rust standpoint since r_efi doesn't define the ImageEntryPoint
@@ -1235,6 +1256,7 @@ pub fn core_unload_image(image_handle: efi::Handle, force_unload: bool) -> Resul
         private_data.private_image_data.get(&image_handle).ok_or(efi::Status::INVALID_PARAMETER)?;
     let unload_function = private_image_data.image_info.unload;
     let started = private_image_data.started;
+    let image_base = private_image_data.image_info.image_base as usize;
     drop(private_data); // release the image lock while unload logic executes as this function may be re-entrant.
 
     // if the image has been started, request that it unload, and don't unload it if
@@ -1260,6 +1282,7 @@ pub fn core_unload_image(image_handle: efi::Handle, force_unload: bool) -> Resul
     let handles = PROTOCOL_DB.locate_handles(None).unwrap_or_default();
 
     core_remove_debug_image_info_entry(image_handle);
+    patina_debugger::notify_module_unload(image_base);
 
     // close any protocols opened by this image.
     for handle in handles {
@@ -1384,6 +1407,45 @@ extern "efiapi" fn exit(
     efi::Status::ACCESS_DENIED
 }
 
+/// Writes the loaded images for debugger monitor commands. Nothing is written if the image data is locked, since the
+/// debugger may have interrupted the lock holder.
+pub(crate) fn write_loaded_images(out: &mut dyn fmt::Write) -> fmt::Result {
+    let Some(private_data) = PRIVATE_IMAGE_DATA.try_lock() else {
+        return out.write_str("Image data is locked.");
+    };
+
+    for (handle, image_data) in private_data.private_image_data.iter() {
+        writeln!(
+            out,
+            "{:p} base {:#x} size {:#x} entry {:#x} {} {}",
+            *handle,
+            image_data.image_info.image_base as usize,
+            image_data.image_info.image_size,
+            image_data.entry_point as usize,
+            if image_data.started { "started" } else { "loaded" },
+            image_data.pe_info.filename.as_deref().unwrap_or("<unknown>")
+        )?;
+    }
+    writeln!(out, "{} images.", private_data.private_image_data.len())
+}
+
+/// Returns the memory range and file name of each loaded image, or `None` if the image data is locked, since the
+/// debugger may have interrupted the lock holder.
+pub(crate) fn loaded_image_ranges() -> Option<Vec<(core::ops::Range<u64>, String)>> {
+    let private_data = PRIVATE_IMAGE_DATA.try_lock()?;
+    Some(
+        private_data
+            .private_image_data
+            .values()
+            .map(|image_data| {
+                let base = image_data.image_info.image_base as u64;
+                let name = image_data.pe_info.filename.clone().unwrap_or_else(|| String::from("<unknown>"));
+                (base..base + image_data.image_info.image_size, name)
+            })
+            .collect(),
+    )
+}
+
 /// Initializes image services for the DXE core.
 pub fn init_image_support(hob_list: &HobList, system_table: &mut EfiSystemTable) {
     // initialize system table entry in private global.
@@ -1416,7 +1478,7 @@ pub fn init_image_support(hob_list: &HobList, system_table: &mut EfiSystemTable)
 #[coverage(off)]
 mod tests {
     extern crate std;
-    use super::{empty_image_info, get_buffer_by_file_path, load_image};
+    use super::{empty_image_info, get_buffer_by_file_path, load_image, loaded_image_ranges, write_loaded_images};
     use crate::{
         image::{PRIVATE_IMAGE_DATA, exit, start_image, unload_image},
         protocol_db,
@@ -1512,6 +1574,48 @@ mod tests {
         });
     }
 
+    #[test]
+    fn write_loaded_images_should_describe_loaded_images() {
+        with_locked_state(|| {
+            let mut test_file =
+                File::open(test_collateral!("test_image_msvc_hii.pe32")).expect("failed to open test file.");
+            let mut image: Vec<u8> = Vec::new();
+            test_file.read_to_end(&mut image).expect("failed to read test file");
+
+            let mut image_handle: efi::Handle = core::ptr::null_mut();
+            let status = load_image(
+                false.into(),
+                protocol_db::DXE_CORE_HANDLE,
+                core::ptr::null_mut(),
+                image.as_mut_ptr() as *mut c_void,
+                image.len(),
+                core::ptr::addr_of_mut!(image_handle),
+            );
+            assert_eq!(status, efi::Status::SUCCESS);
+
+            let mut out = std::string::String::new();
+            write_loaded_images(&mut out).unwrap();
+            assert!(out.starts_with(&std::format!("{image_handle:p} base ")));
+            assert!(out.contains(" loaded "));
+            assert!(out.ends_with("1 images.\n"));
+
+            let ranges = loaded_image_ranges().unwrap();
+            assert_eq!(ranges.len(), 1);
+            let (image_base, image_size) = {
+                let private_data = PRIVATE_IMAGE_DATA.lock();
+                let image_info = &private_data.private_image_data[&image_handle].image_info;
+                (image_info.image_base as u64, image_info.image_size)
+            };
+            assert_eq!(ranges[0].0, image_base..image_base + image_size);
+
+            let _guard = PRIVATE_IMAGE_DATA.lock();
+            let mut out = std::string::String::new();
+            write_loaded_images(&mut out).unwrap();
+            assert_eq!(out, "Image data is locked.");
+            assert!(loaded_image_ranges().is_none());
+        });
+    }
+
     #[test]
     fn load_image_should_pass_for_subsystem_efi_application() {
         with_locked_state(|| {
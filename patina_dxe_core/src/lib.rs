@@ -93,8 +93,10 @@ mod tpl_mutex;
 #[cfg(test)]
 pub use {component_dispatcher::MockComponentInfo, cpu::MockCpuInfo};
 
+pub use allocator::{HeapDiff, HeapSnapshot, MemoryTypeMapping, use_emergency_heap};
 pub use component_dispatcher::{Add, Component, ComponentInfo, Config, Service};
 pub use cpu::{CpuInfo, GicBases};
+pub use dispatcher::{DispatchDecision, FileAuthentication, FileKind};
 
 use spin::Once;
 
@@ -123,6 +125,7 @@ use patina::{
         measurement::create_performance_measurement,
     },
     pi::{
+        dxe_services::{GcdMemoryType, MemorySpaceDescriptor},
         hob::{HobList, get_c_hob_list_size},
         protocols::{bds, status_code},
         status_code::{EFI_PROGRESS_CODE, EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_HANDOFF_TO_NEXT},
@@ -181,6 +184,15 @@ pub trait MemoryInfo {
     fn prioritize_32_bit_memory() -> bool {
         false
     }
+
+    /// Custom memory types to report as standard memory types in the UEFI memory map.
+    ///
+    /// Platforms that use OEM or OS defined memory types internally can use this to hand the OS a memory map that
+    /// contains only the types defined by the UEFI specification. Invalid mappings are logged and ignored.
+    #[inline(always)]
+    fn memory_type_handoff_map() -> &'static [MemoryTypeMapping] {
+        &[]
+    }
 }
 
 /// A trait to be implemented by the platform to provide configuration values and types to be used directly by the
@@ -231,6 +243,9 @@ pub trait PlatformInfo {
 /// only in efiapi functions where no reference to the core is otherwise available.
 static __SELF: Once<NonZeroUsize> = Once::new();
 
+/// The relocated HOB list, for debugger monitor commands that cannot reach the core instance.
+static HOB_LIST: Once<&'static HobList<'static>> = Once::new();
+
 /// Platform configured DXE Core responsible for the DXE phase of UEFI booting.
 ///
 /// This struct is generic over the [PlatformInfo] trait, which is used to provide platform-specific configuration to
@@ -357,10 +372,11 @@ impl<P: PlatformInfo> Core<P> {
     }
 
     /// Initializes the core with the given configuration, including GCD initialization, enabling allocations.
-    fn init_memory(&self, physical_hob_list: *const c_void) {
+    fn init_memory(&'static self, physical_hob_list: *const c_void) {
         log::info!("DXE Core Crate v{}", env!("CARGO_PKG_VERSION"));
 
         GCD.prioritize_32_bit_memory(P::MemoryInfo::prioritize_32_bit_memory());
+        allocator::set_memory_type_handoff_map(P::MemoryInfo::memory_type_handoff_map());
 
         let (cpu, mut interrupt_manager) =
             cpu::initialize_cpu_subsystem().expect("Failed to initialize CPU subsystem!");
@@ -392,13 +408,84 @@ impl<P: PlatformInfo> Core<P> {
         // the initial free memory may not be enough to contain the HOB list. We need to relocate the HOBs because
         // the initial HOB list is not in mapped memory as passed from pre-DXE.
         hob_list.relocate_hobs();
-        assert!(self.set_hob_list(hob_list).is_ok());
+        let hob_list = self.set_hob_list(hob_list).expect("HOB list should only be set once.");
+        HOB_LIST.call_once(|| hob_list);
+
+        // Apply platform constant overrides before anything reads them, and record the effective values.
+        patina::platform_constant::apply_hob_list_overrides(hob_list);
+        patina::platform_constant::log_manifest();
 
         // Add custom monitor commands to the debugger before initializing so that
         // they are available in the initial breakpoint.
         patina_debugger::add_monitor_command("gcd", "Prints the GCD", |_, out| {
             let _ = write!(out, "GCD -\n{GCD}");
         });
+        patina_debugger::add_monitor_command(
+            "memmap",
+            "memmap [owners] - Prints the UEFI memory map, optionally with the images and pools in each range",
+            |args, out| match args.next() {
+                Some("owners") => {
+                    let _ = allocator::write_annotated_memory_map(out);
+                }
+                _ => {
+                    let _ = allocator::write_memory_map(out);
+                }
+            },
+        );
+        patina_debugger::add_monitor_command(
+            "heap",
+            "heap [snapshot|diff] - Prints pool allocator usage, records it, or prints the changes since the record",
+            |args, out| {
+                let _ = allocator::heap_monitor_command(args.next(), out);
+            },
+        );
+        patina_debugger::add_monitor_command("hoblist", "Prints the HOB list", |_, out| match HOB_LIST.get() {
+            Some(hob_list) => {
+                let _ = write!(out, "{hob_list:#x?}");
+            }
+            None => {
+                let _ = out.write_str("HOB list is not available.");
+            }
+        });
+        patina_debugger::add_monitor_command("modules", "Lists loaded images", |_, out| {
+            let _ = image::write_loaded_images(out);
+        });
+        patina_debugger::add_monitor_command("handles", "Lists handles and their protocols", |_, out| {
+            let _ = PROTOCOL_DB.write_handles(out);
+        });
+        patina_debugger::add_monitor_command(
+            "protocols",
+            "protocols <guid> - Lists handles with a protocol",
+            |args, out| match args.next().map(patina::BinaryGuid::try_from_string) {
+                Some(Ok(guid)) => {
+                    let _ = PROTOCOL_DB.write_protocol_handles(guid.0, out);
+                }
+                _ => {
+                    let _ =
+                        out.write_str("Usage: protocols <guid>, e.g. protocols 5B1B31A1-9562-11D2-8E3F-00A0C969723B");
+                }
+            },
+        );
+        patina_debugger::add_monitor_command(
+            "openinfo",
+            "openinfo <handle> - Lists open protocol information for a handle",
+            |args, out| match args.next().and_then(|arg| usize::from_str_radix(arg.trim_start_matches("0x"), 16).ok()) {
+                Some(handle) => {
+                    let _ = PROTOCOL_DB.write_open_protocol_information(handle as efi::Handle, out);
+                }
+                None => {
+                    let _ = out.write_str("Usage: openinfo <handle>");
+                }
+            },
+        );
+
+        patina_debugger::set_memory_map_provider(|visit| {
+            let _ = GCD.try_for_each_memory_descriptor(|descriptor| {
+                if let Some(region) = debugger_memory_region(descriptor) {
+                    visit(region);
+                }
+            });
+        });
 
         // Initialize the debugger if it is enabled.
         patina_debugger::initialize(&mut interrupt_manager);
@@ -535,12 +622,19 @@ impl<P: PlatformInfo> Core<P> {
         log::info!("Finished.");
 
         dispatcher::register_section_extractor(&self.section_extractor);
+        dispatcher::register_dispatch_policy(P::ComponentInfo::dispatch_policy);
         fv::register_section_extractor(&self.section_extractor);
 
         log::info!("Parsing FVs from FV HOBs");
         fv::parse_hob_fvs(self.hob_list())?;
         log::info!("Finished.");
 
+        if let Some(payload) = fv::read_hob_fv_raw_file(self.hob_list(), &patina::guids::PLATFORM_CONFIG_FILE) {
+            log::info!("Applying configuration payload");
+            self.component_dispatcher.lock().apply_config_payload(&payload);
+            log::info!("Finished.");
+        }
+
         log::info!("Dispatching Drivers");
         self.core_dispatcher()?;
         self.component_dispatcher.lock().lock_configs();
@@ -582,6 +676,30 @@ fn core_display_missing_arch_protocols() {
     }
 }
 
+/// Returns the region reported to the debugger client for a GCD memory space descriptor, or `None` if the range must
+/// not be accessed by the client.
+///
+/// - `SystemMemory`, `MoreReliable`, `Persistent` and `Reserved` ranges are decoded memory, reported as `Ram`.
+/// - `MemoryMappedIo` ranges allocated to the DXE core are the firmware volumes reserved from the HOB list, which are
+///   flash-mapped, and are reported as `Rom`.
+/// - Other `MemoryMappedIo` ranges are device memory, reported as `Mmio` so the client can write device registers but
+///   not place software breakpoints.
+/// - `NonExistent` ranges have no decoder and `Unaccepted` ranges fault until accepted, so neither is reported.
+fn debugger_memory_region(descriptor: &MemorySpaceDescriptor) -> Option<patina_debugger::MemoryRegion> {
+    let kind = match descriptor.memory_type {
+        GcdMemoryType::SystemMemory
+        | GcdMemoryType::MoreReliable
+        | GcdMemoryType::Persistent
+        | GcdMemoryType::Reserved => patina_debugger::MemoryRegionKind::Ram,
+        GcdMemoryType::MemoryMappedIo if descriptor.image_handle == protocol_db::DXE_CORE_HANDLE => {
+            patina_debugger::MemoryRegionKind::Rom
+        }
+        GcdMemoryType::MemoryMappedIo => patina_debugger::MemoryRegionKind::Mmio,
+        GcdMemoryType::NonExistent | GcdMemoryType::Unaccepted => return None,
+    };
+    Some(patina_debugger::MemoryRegion { base: descriptor.base_address, length: descriptor.length, kind })
+}
+
 fn call_bds() -> ! {
     // Enable status code capability in Firmware Performance DXE.
     match protocols::PROTOCOL_DB.locate_protocol(status_code::PROTOCOL_GUID) {
@@ -640,6 +758,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_debugger_memory_region() {
+        use patina_debugger::{MemoryRegion, MemoryRegionKind};
+
+        let descriptor = |memory_type, image_handle| MemorySpaceDescriptor {
+            base_address: 0x1000,
+            length: 0x2000,
+            memory_type,
+            image_handle,
+            ..Default::default()
+        };
+        let region = |kind| Some(MemoryRegion { base: 0x1000, length: 0x2000, kind });
+
+        for memory_type in [
+            GcdMemoryType::SystemMemory,
+            GcdMemoryType::MoreReliable,
+            GcdMemoryType::Persistent,
+            GcdMemoryType::Reserved,
+        ] {
+            assert_eq!(
+                debugger_memory_region(&descriptor(memory_type, ptr::null_mut())),
+                region(MemoryRegionKind::Ram)
+            );
+        }
+
+        // Device memory stays writable, whether or not it is allocated to a driver.
+        assert_eq!(
+            debugger_memory_region(&descriptor(GcdMemoryType::MemoryMappedIo, ptr::null_mut())),
+            region(MemoryRegionKind::Mmio)
+        );
+        assert_eq!(
+            debugger_memory_region(&descriptor(GcdMemoryType::MemoryMappedIo, 0x20 as efi::Handle)),
+            region(MemoryRegionKind::Mmio)
+        );
+
+        // Firmware volumes from the HOB list are allocated to the DXE core.
+        assert_eq!(
+            debugger_memory_region(&descriptor(GcdMemoryType::MemoryMappedIo, protocol_db::DXE_CORE_HANDLE)),
+            region(MemoryRegionKind::Rom)
+        );
+
+        assert_eq!(debugger_memory_region(&descriptor(GcdMemoryType::NonExistent, ptr::null_mut())), None);
+        assert_eq!(debugger_memory_region(&descriptor(GcdMemoryType::Unaccepted, ptr::null_mut())), None);
+    }
+
     #[test]
     fn test_trait_defaults_do_not_change() {
         /// A simple test to acknowledge that the default implementations of the trait default implementations
@@ -77,6 +77,10 @@ pub struct UefiPeInfo {
     pub sections: Vec<goblin::pe::section_table::SectionTable>,
     /// The filename, if present, from debug_data
     pub filename: Option<String>,
+    /// The full symbol file path, if present, from debug_data
+    pub debug_path: Option<String>,
+    /// The PDB GUID and age, if present, from debug_data
+    pub pdb_signature: Option<([u8; 16], u32)>,
     /// The relocation directory, if present.
     pub reloc_dir: Option<goblin::pe::data_directories::DataDirectory>,
     /// Whether the NX_COMPAT DLL Characteristic flag is set
@@ -122,6 +126,8 @@ impl UefiPeInfo {
             // Parse the filename from the debug data if it exists.
             if let Some(codeview_data) = &parsed_te.debug_data.codeview_pdb70_debug_info {
                 pe.filename = UefiPeInfo::read_filename(codeview_data.filename)?;
+                pe.debug_path = UefiPeInfo::read_debug_path(codeview_data.filename);
+                pe.pdb_signature = Some((codeview_data.signature, codeview_data.age));
             };
 
             Ok(pe)
@@ -167,13 +173,22 @@ impl UefiPeInfo {
         if let Some(debug_data) = parsed_pe.debug_data {
             if let Some(codeview_data) = debug_data.codeview_pdb70_debug_info {
                 pe.filename = UefiPeInfo::read_filename(codeview_data.filename)?;
+                pe.debug_path = UefiPeInfo::read_debug_path(codeview_data.filename);
+                pe.pdb_signature = Some((codeview_data.signature, codeview_data.age));
             } else if let Some(codeview_data) = debug_data.codeview_pdb20_debug_info {
                 pe.filename = UefiPeInfo::read_filename(codeview_data.filename)?;
+                pe.debug_path = UefiPeInfo::read_debug_path(codeview_data.filename);
             }
         }
         Ok(pe)
     }
 
+    /// Parses a bytes buffer containing the full symbol file path.
+    fn read_debug_path(bytes: &[u8]) -> Option<String> {
+        let path_end = bytes.iter().position(|&c| c == b'\0').unwrap_or(bytes.len());
+        (path_end != 0).then(|| String::from_utf8_lossy(&bytes[..path_end]).into_owned())
+    }
+
     /// Parses a bytes buffer containing the filename.
     fn read_filename(bytes: &[u8]) -> error::Result<Option<String>> {
         let filename_end = bytes.iter().position(|&c| c == b'\0').unwrap_or(bytes.len());
@@ -537,6 +552,16 @@ mod tests {
         // Although the file name is "DisplayEngine512BFileAlignment.efi", the file
         // name inside the debug data is "DisplayEngine.pdb"
         assert_eq!(image_info.filename, Some(String::from("DisplayEngine.efi")));
+        assert_eq!(
+            image_info.debug_path.as_deref(),
+            Some(
+                "C:\\src\\mu_tiano_platforms\\Build\\QemuQ35Pkg\\DEBUG_VS2022\\X64\\MsGraphicsPkg\\DisplayEngineDxe\\DisplayEngineDxe\\DEBUG\\DisplayEngine.pdb"
+            )
+        );
+        assert_eq!(
+            image_info.pdb_signature,
+            Some(([0xB6, 0xD4, 0x8F, 0x5E, 0xE5, 0xDE, 0xC9, 0x4D, 0x9C, 0x75, 0x20, 0xFF, 0x03, 0x14, 0xA4, 0x54], 7))
+        );
         assert_eq!(image_info.size_of_image, 0x19000);
         assert_eq!(image_info.entry_point_offset, 0x11EC);
     }
@@ -612,8 +637,10 @@ mod tests {
         load_image(&image_info, image, &mut loaded_image).unwrap();
         let loaded_image_info = UefiPeInfo::parse(&loaded_image).unwrap();
 
-        //debug information is not included when loading an image in the present implementation, so filename will not be present.
+        //debug information is not included when loading an image in the present implementation, so filename and debug path will not be present.
         image_info.filename = None;
+        image_info.debug_path = None;
+        image_info.pdb_signature = None;
         assert_eq!(image_info, loaded_image_info);
     }
 
@@ -15,8 +15,8 @@ use alloc::{
     vec,
     vec::Vec,
 };
-use core::{cmp::Ordering, ffi::c_void, hash::Hasher};
-use patina::error::EfiError;
+use core::{cmp::Ordering, ffi::c_void, fmt, hash::Hasher};
+use patina::{Guid, error::EfiError};
 use r_efi::efi;
 
 use crate::tpl_mutex;
@@ -864,6 +864,94 @@ impl SpinLockedProtocolDb {
     pub fn get_child_handles(&self, parent_handle: efi::Handle) -> Vec<efi::Handle> {
         self.lock().get_child_handles(parent_handle)
     }
+
+    /// Writes every handle, in creation order, and the protocols installed on it.
+    ///
+    /// This and the other `write_` routines are intended for debugger monitor commands. They do not block if the
+    /// database is locked, since the debugger may have interrupted the holder of the lock.
+    pub fn write_handles(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        let Some(db) = self.inner.try_lock() else {
+            return out.write_str("Protocol database is locked.");
+        };
+
+        let mut handles: Vec<_> = db.handles.iter().collect();
+        handles.sort_by_key(|(_, handle)| handle.order);
+        for (key, handle) in handles.iter() {
+            writeln!(out, "{key:#x}")?;
+            for (guid, instance) in handle.iter() {
+                writeln!(out, "    {} {:p}", Guid::from_ref(&guid.0), instance.interface)?;
+            }
+        }
+        writeln!(out, "{} handles.", handles.len())
+    }
+
+    /// Writes every handle on which `protocol` is installed, in creation order, and its interface.
+    pub fn write_protocol_handles(&self, protocol: efi::Guid, out: &mut dyn fmt::Write) -> fmt::Result {
+        let Some(db) = self.inner.try_lock() else {
+            return out.write_str("Protocol database is locked.");
+        };
+
+        let mut handles: Vec<_> = db
+            .handles
+            .iter()
+            .filter_map(|(key, handle)| handle.get(&OrdGuid(protocol)).map(|instance| (key, handle.order, instance)))
+            .collect();
+        handles.sort_by_key(|(_, order, _)| *order);
+        for (key, _, instance) in handles.iter() {
+            writeln!(out, "{key:#x} {:p}", instance.interface)?;
+        }
+        writeln!(out, "{} handles support {}.", handles.len(), Guid::from_ref(&protocol))
+    }
+
+    /// Writes the open protocol information for each protocol installed on `handle`.
+    pub fn write_open_protocol_information(&self, handle: efi::Handle, out: &mut dyn fmt::Write) -> fmt::Result {
+        let Some(db) = self.inner.try_lock() else {
+            return out.write_str("Protocol database is locked.");
+        };
+
+        let Some(handle_data) = db.handles.get(&(handle as usize)) else {
+            return writeln!(out, "Handle {handle:p} not found.");
+        };
+        for (guid, instance) in handle_data.iter() {
+            writeln!(out, "{} {:p}", Guid::from_ref(&guid.0), instance.interface)?;
+            for usage in instance.usage.iter() {
+                writeln!(
+                    out,
+                    "    agent {:p} controller {:p} {} count {}",
+                    usage.agent_handle.unwrap_or(core::ptr::null_mut()),
+                    usage.controller_handle.unwrap_or(core::ptr::null_mut()),
+                    OpenAttributes(usage.attributes),
+                    usage.open_count
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Displays EFI_OPEN_PROTOCOL attributes by name.
+struct OpenAttributes(u32);
+
+impl fmt::Display for OpenAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [(u32, &str); 6] = [
+            (efi::OPEN_PROTOCOL_BY_HANDLE_PROTOCOL, "BY_HANDLE_PROTOCOL"),
+            (efi::OPEN_PROTOCOL_GET_PROTOCOL, "GET_PROTOCOL"),
+            (efi::OPEN_PROTOCOL_TEST_PROTOCOL, "TEST_PROTOCOL"),
+            (efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER, "BY_CHILD_CONTROLLER"),
+            (efi::OPEN_PROTOCOL_BY_DRIVER, "BY_DRIVER"),
+            (efi::OPEN_PROTOCOL_EXCLUSIVE, "EXCLUSIVE"),
+        ];
+
+        let mut separator = "";
+        for (attribute, name) in NAMES {
+            if self.0 & attribute != 0 {
+                write!(f, "{separator}{name}")?;
+                separator = "|";
+            }
+        }
+        if separator.is_empty() { write!(f, "{:#x}", self.0) } else { Ok(()) }
+    }
 }
 
 unsafe impl Send for SpinLockedProtocolDb {}
@@ -1778,6 +1866,68 @@ mod tests {
         });
     }
 
+    #[test]
+    fn write_routines_should_describe_handles_and_usage() {
+        with_locked_state(|| {
+            static SPIN_LOCKED_PROTOCOL_DB: SpinLockedProtocolDb = SpinLockedProtocolDb::new();
+
+            let guid1 =
+                efi::Guid::from_fields(0x0e896c7a, 0x57dc, 0x4987, 0xbc, 0x22, &[0xab, 0xc3, 0xa8, 0x26, 0x32, 0x10]);
+            let guid2 =
+                efi::Guid::from_fields(0x98d32ea1, 0xe980, 0x46b5, 0xbb, 0x2e, &[0x3d, 0x1a, 0xc1, 0xd3, 0xae, 0x8b]);
+            let interface1: *mut c_void = 0x1234 as *mut c_void;
+
+            let (handle, _) = SPIN_LOCKED_PROTOCOL_DB.install_protocol_interface(None, guid1, interface1).unwrap();
+            let (agent, _) = SPIN_LOCKED_PROTOCOL_DB.install_protocol_interface(None, guid2, interface1).unwrap();
+            SPIN_LOCKED_PROTOCOL_DB
+                .add_protocol_usage(
+                    handle,
+                    guid1,
+                    Some(agent),
+                    Some(handle),
+                    efi::OPEN_PROTOCOL_BY_DRIVER | efi::OPEN_PROTOCOL_EXCLUSIVE,
+                )
+                .unwrap();
+
+            let mut out = std::string::String::new();
+            SPIN_LOCKED_PROTOCOL_DB.write_handles(&mut out).unwrap();
+            assert_eq!(
+                out,
+                std::format!(
+                    "{:#x}\n    0E896C7A-57DC-4987-BC22-ABC3A8263210 0x1234\n\
+                     {:#x}\n    98D32EA1-E980-46B5-BB2E-3D1AC1D3AE8B 0x1234\n2 handles.\n",
+                    handle as usize,
+                    agent as usize
+                )
+            );
+
+            let mut out = std::string::String::new();
+            SPIN_LOCKED_PROTOCOL_DB.write_protocol_handles(guid2, &mut out).unwrap();
+            assert_eq!(
+                out,
+                std::format!("{:#x} 0x1234\n1 handles support 98D32EA1-E980-46B5-BB2E-3D1AC1D3AE8B.\n", agent as usize)
+            );
+
+            let mut out = std::string::String::new();
+            SPIN_LOCKED_PROTOCOL_DB.write_open_protocol_information(handle, &mut out).unwrap();
+            assert_eq!(
+                out,
+                std::format!(
+                    "0E896C7A-57DC-4987-BC22-ABC3A8263210 0x1234\n    agent {agent:p} controller {handle:p} BY_DRIVER|EXCLUSIVE count 1\n"
+                )
+            );
+
+            let mut out = std::string::String::new();
+            SPIN_LOCKED_PROTOCOL_DB.write_open_protocol_information(0x5678 as efi::Handle, &mut out).unwrap();
+            assert_eq!(out, "Handle 0x5678 not found.\n");
+
+            let _guard = SPIN_LOCKED_PROTOCOL_DB.lock();
+            let mut out = std::string::String::new();
+            SPIN_LOCKED_PROTOCOL_DB.write_handles(&mut out).unwrap();
+            assert_eq!(out, "Protocol database is locked.");
+        });
+    }
+
     #[test]
     fn get_open_protocol_information_should_return_all_open_protocol_info() {
         with_locked_state(|| {
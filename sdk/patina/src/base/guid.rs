@@ -19,7 +19,7 @@
 //!
 //! ### Use `OwnedGuid` when:
 //!
-//! - Creating GUIDs from string literals or user input via [`OwnedGuid::try_from_string`]
+//! - Creating GUIDs from string literals or user input via [`OwnedGuid::try_from_string`] or [`OwnedGuid::parse_str`]
 //! - Storing GUIDs in structs or collections that need to own their data
 //! - Returning GUIDs from functions where you can't guarantee the lifetime of source data
 //! - Working with GUIDs that need to live beyond the scope of their creation
@@ -76,6 +76,21 @@
 //! # Ok::<(), GuidError>(())
 //! ```
 //!
+//! GUIDs display in uppercase registry format. `{:x}` formats them in lowercase, and the alternate flag encloses them
+//! in braces:
+//!
+//! ```rust
+//! use patina::{Guid, guid};
+//! use r_efi::efi;
+//!
+//! // Checked at compile time.
+//! const MY_PROTOCOL: efi::Guid = guid!("550e8400-e29b-41d4-a716-446655440000");
+//!
+//! let guid = Guid::from_ref(&MY_PROTOCOL);
+//! assert_eq!(format!("{guid}"), "550E8400-E29B-41D4-A716-446655440000");
+//! assert_eq!(format!("{guid:#x}"), "{550e8400-e29b-41d4-a716-446655440000}");
+//! ```
+//!
 //! ## License
 //!
 //! Copyright (C) Microsoft Corporation.
@@ -369,6 +384,18 @@ impl core::fmt::Display for BinaryGuid {
     }
 }
 
+impl core::fmt::LowerHex for BinaryGuid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::LowerHex::fmt(&self.as_guid(), f)
+    }
+}
+
+impl core::fmt::UpperHex for BinaryGuid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::UpperHex::fmt(&self.as_guid(), f)
+    }
+}
+
 impl<'a> Guid<'a> {
     /// Create a new Guid from an `efi::Guid` reference
     pub fn from_ref(guid: &'a efi::Guid) -> Self {
@@ -488,21 +515,62 @@ impl OwnedGuid {
             Err(_) => panic!("Invalid GUID string"),
         }
     }
+
+    /// Parses a GUID in registry format (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`), optionally enclosed in braces.
+    ///
+    /// ```rust
+    /// use patina::Guid;
+    ///
+    /// let guid = Guid::parse_str("{8BE4DF61-93CA-11D2-AA0D-00E098032B8C}")?;
+    /// assert_eq!(guid, Guid::parse_str("8be4df61-93ca-11d2-aa0d-00e098032b8c")?);
+    /// # Ok::<(), patina::GuidError>(())
+    /// ```
+    pub fn parse_str(s: &str) -> core::result::Result<OwnedGuid, GuidError> {
+        let s = s.trim();
+        Self::try_from_string(s.strip_prefix('{').and_then(|s| s.strip_suffix('}')).unwrap_or(s))
+    }
 }
-impl core::fmt::Display for Guid<'_> {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+
+impl Guid<'_> {
+    /// Writes the GUID in registry format with the requested case. The alternate flag (`#`) encloses it in braces.
+    fn write_registry_format(&self, f: &mut core::fmt::Formatter<'_>, lowercase: bool) -> core::fmt::Result {
         let hex_chars = self.to_canonical_string();
 
+        if f.alternate() {
+            write!(f, "{{")?;
+        }
         // Format as: XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX
         for (i, &c) in hex_chars.iter().enumerate() {
             if DASH_POSITIONS.contains(&i) {
                 write!(f, "-")?;
             }
-            write!(f, "{}", c)?;
+            write!(f, "{}", if lowercase { c.to_ascii_lowercase() } else { c })?;
+        }
+        if f.alternate() {
+            write!(f, "}}")?;
         }
         Ok(())
     }
 }
+impl core::fmt::Display for Guid<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_registry_format(f, false)
+    }
+}
+
+/// Formats the GUID in lowercase registry format, `{:#x}` encloses it in braces.
+impl core::fmt::LowerHex for Guid<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_registry_format(f, true)
+    }
+}
+
+/// Formats the GUID in uppercase registry format, `{:#X}` encloses it in braces.
+impl core::fmt::UpperHex for Guid<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.write_registry_format(f, false)
+    }
+}
 
 impl core::fmt::Debug for Guid<'_> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -560,6 +628,14 @@ impl<'a> TryFrom<&'a str> for OwnedGuid {
     }
 }
 
+impl core::str::FromStr for OwnedGuid {
+    type Err = GuidError;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        OwnedGuid::parse_str(s)
+    }
+}
+
 /// Macro to generate the boilerplate for parsing ASCII hex strings (as byte slices) to their numeric values.
 macro_rules! parse_hex {
     ($chars:expr, $i:expr, $count:expr, $ty:ty) => {{
@@ -917,6 +993,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn hex_format() {
+        let r_efi_guid = create_test_r_efi_guid();
+        let guid = Guid::from_ref(&r_efi_guid);
+
+        assert_eq!(format!("{guid:x}"), TEST_GUID_STRING);
+        assert_eq!(format!("{guid:X}"), TEST_GUID_STRING_UPPER);
+        assert_eq!(format!("{guid:#x}"), format!("{{{TEST_GUID_STRING}}}"));
+        assert_eq!(format!("{guid:#X}"), format!("{{{TEST_GUID_STRING_UPPER}}}"));
+        assert_eq!(format!("{:x}", BinaryGuid(r_efi_guid)), TEST_GUID_STRING);
+    }
+
+    #[test]
+    fn parse_str_registry_format() {
+        let expected = OwnedGuid::from_string(TEST_GUID_STRING);
+        let braced = format!("{{{TEST_GUID_STRING_UPPER}}}");
+        for input in
+            [TEST_GUID_STRING, TEST_GUID_STRING_UPPER, braced.as_str(), " {550e8400-e29b-41d4-a716-446655440000} "]
+        {
+            assert_eq!(Guid::parse_str(input), Ok(expected.clone()));
+            assert_eq!(input.parse::<OwnedGuid>(), Ok(expected.clone()));
+        }
+
+        // Formatting round trips through parsing.
+        assert_eq!(Guid::parse_str(&format!("{expected:#x}")), Ok(expected.clone()));
+        assert!(Guid::parse_str("{550e8400-e29b-41d4-a716-446655440000").is_err());
+        assert!(Guid::parse_str("{}").is_err());
+    }
+
+    #[test]
+    fn guid_macro() {
+        const GUID: efi::Guid = crate::guid!("550e8400-e29b-41d4-a716-446655440000");
+        assert_eq!(GUID, create_test_r_efi_guid());
+    }
+
     #[test]
     fn debug_format() {
         let r_efi_guid = create_test_r_efi_guid();
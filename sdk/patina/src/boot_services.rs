@@ -277,7 +277,7 @@ pub trait BootServices {
     ///
     /// See [`BootServices::raise_tpl`] and [`BootServices::restore_tpl`] for more details.
     fn raise_tpl_guarded<'a>(&'a self, tpl: Tpl) -> TplGuard<'a, Self> {
-        TplGuard { boot_services: self, retore_tpl: self.raise_tpl(tpl) }
+        TplGuard::raise(self, tpl)
     }
 
     /// Raises a task’s priority level and returns its previous level.
@@ -329,6 +329,9 @@ pub trait BootServices {
     /// Installs a protocol interface on a device handle.
     /// If the handle does not exist, it is created and added to the list of handles in the system.
     ///
+    /// Use [`InstalledProtocol`](protocol_handler::InstalledProtocol) to uninstall the protocol when it goes out of
+    /// scope.
+    ///
     /// [UEFI Spec Documentation: 7.3.2. EFI_BOOT_SERVICES.InstallProtocolInterface()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-installprotocolinterface)
     ///
     /// ## Example
@@ -569,6 +572,8 @@ pub trait BootServices {
     /// Queries a handle to determine if it supports a specified protocol.
     /// If the protocol is supported by the handle, it opens the protocol on behalf of the calling agent.
     ///
+    /// Use [`ProtocolGuard`](protocol_handler::ProtocolGuard) to close the protocol when it goes out of scope.
+    ///
     /// [UEFI Spec Documentation: 7.3.9. EFI_BOOT_SERVICES.OpenProtocol()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-openprotocol)
     ///
     /// # Safety
@@ -1992,12 +1997,37 @@ mod tests {
         }
 
         let guard = boot_services.raise_tpl_guarded(Tpl::NOTIFY);
-        assert_eq!(Tpl::APPLICATION, guard.retore_tpl);
+        assert_eq!(Tpl::APPLICATION, guard.restore_tpl);
         assert_eq!(efi::TPL_NOTIFY, CURRENT_TPL.load(Ordering::Relaxed));
         drop(guard);
         assert_eq!(efi::TPL_APPLICATION, CURRENT_TPL.load(Ordering::Relaxed));
     }
 
+    #[test]
+    fn test_tpl_guard_restores_on_error() {
+        let boot_services = boot_services!(raise_tpl = efi_raise_tpl, restore_tpl = efi_restore_tpl);
+
+        static CURRENT_TPL: AtomicUsize = AtomicUsize::new(efi::TPL_APPLICATION);
+
+        extern "efiapi" fn efi_raise_tpl(tpl: efi::Tpl) -> efi::Tpl {
+            CURRENT_TPL.swap(tpl, Ordering::Relaxed)
+        }
+
+        extern "efiapi" fn efi_restore_tpl(tpl: efi::Tpl) {
+            CURRENT_TPL.store(tpl, Ordering::Relaxed);
+        }
+
+        let fails = || -> Result<(), efi::Status> {
+            let guard = TplGuard::raise(&boot_services, Tpl::CALLBACK);
+            assert_eq!(Tpl::APPLICATION, guard.previous_tpl());
+            assert_eq!(efi::TPL_CALLBACK, CURRENT_TPL.load(Ordering::Relaxed));
+            Err(efi::Status::DEVICE_ERROR)?;
+            unreachable!()
+        };
+        assert_eq!(Err(efi::Status::DEVICE_ERROR), fails());
+        assert_eq!(efi::TPL_APPLICATION, CURRENT_TPL.load(Ordering::Relaxed));
+    }
+
     #[test]
     #[should_panic = "Boot services function raise_tpl is not initialized."]
     fn test_raise_tpl_not_init() {
@@ -2736,6 +2766,127 @@ mod tests {
         boot_services.close_protocol(1_usize as _, &TestProtocol::PROTOCOL_GUID, 2_usize as _, 3_usize as _).unwrap();
     }
 
+    #[test]
+    fn test_protocol_guard_closes_on_drop() {
+        let boot_services = boot_services!(open_protocol = efi_open_protocol, close_protocol = efi_close_protocol);
+
+        static CLOSED: AtomicUsize = AtomicUsize::new(0);
+
+        extern "efiapi" fn efi_open_protocol(
+            handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            interface: *mut *mut c_void,
+            _agent_handle: efi::Handle,
+            _controller_handle: efi::Handle,
+            _attributes: u32,
+        ) -> efi::Status {
+            assert_eq!(1, handle as usize);
+            // SAFETY: Test mock - writing a leaked protocol interface pointer to the output parameter.
+            unsafe { ptr::write(interface, Box::new(TestProtocol(12)).into_mut_ptr() as _) };
+            efi::Status::SUCCESS
+        }
+
+        extern "efiapi" fn efi_close_protocol(
+            handle: efi::Handle,
+            protocol: *mut efi::Guid,
+            agent_handle: efi::Handle,
+            controller_handle: efi::Handle,
+        ) -> efi::Status {
+            assert_eq!(1, handle as usize);
+            // SAFETY: Test mock - reading protocol GUID to verify close_protocol request.
+            assert_eq!(TestProtocol::PROTOCOL_GUID, unsafe { ptr::read(protocol) });
+            assert_eq!(2, agent_handle as usize);
+            assert_eq!(3, controller_handle as usize);
+            CLOSED.fetch_add(1, Ordering::Relaxed);
+            efi::Status::SUCCESS
+        }
+
+        // SAFETY: Test code - the interface is only referenced through the guard.
+        let mut protocol = unsafe {
+            protocol_handler::ProtocolGuard::<TestProtocol, _>::open(
+                &boot_services,
+                1_usize as _,
+                2_usize as _,
+                3_usize as _,
+                efi::OPEN_PROTOCOL_BY_DRIVER,
+            )
+        }
+        .unwrap();
+        assert_eq!(12, protocol.0);
+        protocol.0 = 13;
+        assert_eq!(1, protocol.handle() as usize);
+        assert_eq!(0, CLOSED.load(Ordering::Relaxed));
+        drop(protocol);
+        assert_eq!(1, CLOSED.load(Ordering::Relaxed));
+
+        // SAFETY: Test code - the interface is only referenced through the guard.
+        let protocol = unsafe {
+            protocol_handler::ProtocolGuard::<TestProtocol, _>::open(
+                &boot_services,
+                1_usize as _,
+                2_usize as _,
+                3_usize as _,
+                efi::OPEN_PROTOCOL_BY_DRIVER,
+            )
+        }
+        .unwrap();
+        protocol.close().unwrap();
+        assert_eq!(2, CLOSED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_installed_protocol_uninstalls_on_drop() {
+        let boot_services = boot_services!(
+            install_protocol_interface = efi_install_protocol_interface,
+            uninstall_protocol_interface = efi_uninstall_protocol_interface
+        );
+
+        static INSTALLED: AtomicUsize = AtomicUsize::new(0);
+
+        extern "efiapi" fn efi_install_protocol_interface(
+            handle: *mut efi::Handle,
+            _guid: *mut efi::Guid,
+            _interface_type: u32,
+            interface: *mut c_void,
+        ) -> efi::Status {
+            // SAFETY: Test mock - writing output parameter.
+            unsafe { ptr::write(handle, 17_usize as _) };
+            INSTALLED.store(interface as usize, Ordering::Relaxed);
+            efi::Status::SUCCESS
+        }
+
+        extern "efiapi" fn efi_uninstall_protocol_interface(
+            handle: efi::Handle,
+            protocol: *mut efi::Guid,
+            interface: *mut c_void,
+        ) -> efi::Status {
+            assert_eq!(17, handle as usize);
+            // SAFETY: Test mock - reading protocol GUID parameter to verify correctness.
+            assert_eq!(TestProtocol::PROTOCOL_GUID, unsafe { ptr::read(protocol) });
+            assert_eq!(INSTALLED.swap(0, Ordering::Relaxed), interface as usize);
+            efi::Status::SUCCESS
+        }
+
+        let installed =
+            protocol_handler::InstalledProtocol::install(&boot_services, None, Box::new(TestProtocol(42))).unwrap();
+        assert_eq!(17, installed.handle() as usize);
+        assert_ne!(0, INSTALLED.load(Ordering::Relaxed));
+        drop(installed);
+        assert_eq!(0, INSTALLED.load(Ordering::Relaxed));
+
+        let installed =
+            protocol_handler::InstalledProtocol::install(&boot_services, None, Box::new(TestProtocol(7))).unwrap();
+        assert_eq!(7, installed.uninstall().unwrap().0);
+        assert_eq!(0, INSTALLED.load(Ordering::Relaxed));
+
+        let installed =
+            protocol_handler::InstalledProtocol::install(&boot_services, None, Box::new(TestProtocol(8))).unwrap();
+        let (handle, key) = installed.leak();
+        assert_eq!(17, handle as usize);
+        assert_eq!(key.ptr_value, INSTALLED.load(Ordering::Relaxed));
+        assert_eq!(8, boot_services.uninstall_protocol_interface(handle, key).unwrap().0);
+    }
+
     #[test]
     #[should_panic = "Boot services function open_protocol_information is not initialized."]
     fn test_open_protocol_information_not_init() {
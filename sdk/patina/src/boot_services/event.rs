@@ -7,10 +7,13 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
-use core::ops;
+use alloc::boxed::Box;
+use core::{ffi::c_void, ops, ptr::NonNull, time::Duration};
 
 use r_efi::efi;
 
+use super::{BootServices, tpl::Tpl};
+
 /// Function signature for event notify function.
 pub type EventNotifyCallback<T> = extern "efiapi" fn(efi::Event, T);
 
@@ -31,6 +34,27 @@ pub enum EventTimerType {
     Relative = efi::TIMER_RELATIVE,
 }
 
+/// When a timer event set with [`Event::set_timer`] is signaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerKind {
+    /// The event is signaled once, after the duration has elapsed.
+    Relative(Duration),
+    /// The event is signaled each time the duration elapses, until the timer is cancelled or the event is closed.
+    Periodic(Duration),
+}
+
+impl TimerKind {
+    /// Returns the timer type and trigger time, in 100ns units, to pass to
+    /// [`BootServices::set_timer`](super::BootServices::set_timer). Durations too long to represent are clamped.
+    pub fn to_timer(self) -> (EventTimerType, u64) {
+        let (timer_type, duration) = match self {
+            TimerKind::Relative(duration) => (EventTimerType::Relative, duration),
+            TimerKind::Periodic(duration) => (EventTimerType::Periodic, duration),
+        };
+        (timer_type, u64::try_from(duration.as_nanos() / 100).unwrap_or(u64::MAX))
+    }
+}
+
 impl From<EventTimerType> for u32 {
     fn from(val: EventTimerType) -> Self {
         val as u32
@@ -94,3 +118,297 @@ impl From<EventType> for u32 {
         val.0
     }
 }
+
+/// An event whose notify function is a Rust closure. The event is closed when dropped.
+///
+/// The closure is owned by the event and is only dropped after the event is closed. Because the event may outlive
+/// the `Event`, either through [`core::mem::forget`] or because it could not be closed, the closure must be
+/// `'static`. Share state with it through `'static` references or reference counting. Events that must stay open for
+/// the rest of boot, such as Ready to Boot notifications, can be kept with [`Event::leak`].
+///
+/// ```ignore
+/// let count = Rc::new(Cell::new(0));
+/// let event = Event::timer(&boot_services, Tpl::CALLBACK, {
+///     let count = count.clone();
+///     move || count.set(count.get() + 1)
+/// })?;
+/// event.set_timer(TimerKind::Periodic(Duration::from_millis(10)))?;
+/// ```
+///
+/// A closure that borrows from the stack is rejected:
+///
+/// ```compile_fail
+/// # use patina::boot_services::{BootServices, event::Event, tpl::Tpl};
+/// fn count_ticks(boot_services: &impl BootServices) {
+///     let mut count = 0;
+///     let _event = Event::timer(boot_services, Tpl::CALLBACK, || count += 1);
+/// }
+/// ```
+#[must_use = "if unused the event will immediately be closed"]
+pub struct Event<'a, B: BootServices + ?Sized> {
+    event: efi::Event,
+    notify: NonNull<dyn FnMut()>,
+    boot_services: &'a B,
+}
+
+impl<'a, B: BootServices + ?Sized> Event<'a, B> {
+    /// Creates an event of `event_type` that calls `notify` at `notify_tpl`.
+    ///
+    /// [UEFI Spec Documentation: 7.1.1. EFI_BOOT_SERVICES.CreateEvent()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-createevent)
+    pub fn new<F: FnMut() + 'static>(
+        boot_services: &'a B,
+        event_type: EventType,
+        notify_tpl: Tpl,
+        notify: F,
+    ) -> Result<Self, efi::Status> {
+        let context = Box::into_raw(Box::new(notify));
+        // SAFETY: `context` points to a live `F` until the event is closed, and `notify_closure::<F>` only
+        // dereferences it as an `F`.
+        let event = unsafe {
+            boot_services.create_event_unchecked(
+                event_type,
+                notify_tpl,
+                Some(notify_closure::<F>),
+                context as *mut c_void,
+            )
+        };
+        Self::from_parts(boot_services, event, context)
+    }
+
+    /// Creates an event of `event_type` in `event_group` that calls `notify` at `notify_tpl`.
+    ///
+    /// [UEFI Spec Documentation: 7.1.2. EFI_BOOT_SERVICES.CreateEventEx()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-createeventex)
+    pub fn new_in_group<F: FnMut() + 'static>(
+        boot_services: &'a B,
+        event_type: EventType,
+        notify_tpl: Tpl,
+        event_group: &'static efi::Guid,
+        notify: F,
+    ) -> Result<Self, efi::Status> {
+        let context = Box::into_raw(Box::new(notify));
+        // SAFETY: `context` points to a live `F` until the event is closed, and `notify_closure::<F>` only
+        // dereferences it as an `F`.
+        let event = unsafe {
+            boot_services.create_event_ex_unchecked(
+                event_type,
+                notify_tpl,
+                notify_closure::<F>,
+                context as *mut c_void,
+                event_group,
+            )
+        };
+        Self::from_parts(boot_services, event, context)
+    }
+
+    /// Creates a timer event that calls `notify` at `notify_tpl` each time the timer expires. The timer is started
+    /// with [`Event::set_timer`].
+    pub fn timer<F: FnMut() + 'static>(boot_services: &'a B, notify_tpl: Tpl, notify: F) -> Result<Self, efi::Status> {
+        Self::new(boot_services, EventType::TIMER | EventType::NOTIFY_SIGNAL, notify_tpl, notify)
+    }
+
+    fn from_parts<F: FnMut() + 'static>(
+        boot_services: &'a B,
+        event: Result<efi::Event, efi::Status>,
+        context: *mut F,
+    ) -> Result<Self, efi::Status> {
+        match event {
+            Ok(event) => {
+                // SAFETY: `context` was created by `Box::into_raw` and is not null.
+                let notify = unsafe { NonNull::new_unchecked(context as *mut dyn FnMut()) };
+                Ok(Self { event, notify, boot_services })
+            }
+            Err(status) => {
+                // SAFETY: The event was not created, so nothing else refers to `context`.
+                drop(unsafe { Box::from_raw(context) });
+                Err(status)
+            }
+        }
+    }
+
+    /// Returns the raw event, for example to wait on it. The event must not be closed through the raw event.
+    pub fn as_raw(&self) -> efi::Event {
+        self.event
+    }
+
+    /// Signals the event.
+    ///
+    /// [UEFI Spec Documentation: 7.1.4. EFI_BOOT_SERVICES.SignalEvent()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-signalevent)
+    pub fn signal(&self) -> Result<(), efi::Status> {
+        self.boot_services.signal_event(self.event)
+    }
+
+    /// Starts the timer of a timer event, replacing any timer already set.
+    ///
+    /// [UEFI Spec Documentation: 7.1.7. EFI_BOOT_SERVICES.SetTimer()](https://uefi.org/specs/UEFI/2.10/07_Services_Boot_Services.html#efi-boot-services-settimer)
+    pub fn set_timer(&self, timer: TimerKind) -> Result<(), efi::Status> {
+        let (timer_type, trigger_time) = timer.to_timer();
+        self.boot_services.set_timer(self.event, timer_type, trigger_time)
+    }
+
+    /// Cancels the timer of a timer event.
+    pub fn cancel_timer(&self) -> Result<(), efi::Status> {
+        self.boot_services.set_timer(self.event, EventTimerType::Cancel, 0)
+    }
+
+    /// Leaks the event, such that it is never closed and its closure is never dropped. Returns the raw event.
+    pub fn leak(self) -> efi::Event {
+        let event = self.event;
+        core::mem::forget(self);
+        event
+    }
+}
+
+impl<B: BootServices + ?Sized> Drop for Event<'_, B> {
+    fn drop(&mut self) {
+        // If the event could not be closed, it may still be signaled, so its closure must stay alive.
+        if self.boot_services.close_event(self.event).is_ok() {
+            // SAFETY: The event is closed, so nothing else refers to the closure, which was created by
+            // `Box::into_raw`.
+            drop(unsafe { Box::from_raw(self.notify.as_ptr()) });
+        }
+    }
+}
+
+impl<B: BootServices + ?Sized> core::fmt::Debug for Event<'_, B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Event").field("event", &self.event).finish_non_exhaustive()
+    }
+}
+
+extern "efiapi" fn notify_closure<F: FnMut()>(_event: efi::Event, context: *mut c_void) {
+    // SAFETY: `context` is the `F` owned by the `Event`, which stays alive while the event can be signaled. Notify
+    // functions of the same event are not nested, so no other reference to it exists.
+    let notify = unsafe { &mut *(context as *mut F) };
+    notify();
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::boot_services::MockBootServices;
+    use core::cell::Cell;
+    use std::rc::Rc;
+
+    type Registered = Rc<Cell<Option<(EventNotifyCallback<*mut c_void>, *mut c_void)>>>;
+
+    /// Returns boot services that record the notify function and context of the created event.
+    fn boot_services(registered: &Registered) -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        let registered = registered.clone();
+        boot_services.expect_create_event_unchecked::<c_void>().once().returning_st(
+            move |event_type, notify_tpl, notify, context| {
+                assert_eq!(event_type, EventType::TIMER | EventType::NOTIFY_SIGNAL);
+                assert_eq!(notify_tpl, Tpl::CALLBACK);
+                registered.set(Some((notify.unwrap(), context)));
+                Ok(0x1234 as efi::Event)
+            },
+        );
+        boot_services
+    }
+
+    fn signal(registered: &Registered) {
+        let (notify, context) = registered.get().unwrap();
+        notify(0x1234 as efi::Event, context);
+    }
+
+    #[test]
+    fn test_event_calls_closure_until_dropped() {
+        let registered = Registered::default();
+        let mut boot_services = boot_services(&registered);
+        boot_services
+            .expect_set_timer()
+            .withf(|_, timer_type, trigger_time| matches!(timer_type, EventTimerType::Periodic) && *trigger_time == 10)
+            .once()
+            .return_const(Ok(()));
+        boot_services.expect_close_event().once().return_const(Ok(()));
+
+        let count = Rc::new(Cell::new(0));
+        let count_ref = count.clone();
+        let dropped = Rc::new(Cell::new(false));
+        let guard = DropFlag(dropped.clone());
+        let event = Event::timer(&boot_services, Tpl::CALLBACK, move || {
+            let _ = &guard;
+            count_ref.set(count_ref.get() + 1);
+        })
+        .unwrap();
+        event.set_timer(TimerKind::Periodic(Duration::from_micros(1))).unwrap();
+        signal(&registered);
+        signal(&registered);
+        assert_eq!(count.get(), 2);
+        assert!(!dropped.get());
+        drop(event);
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn test_event_keeps_closure_if_close_fails() {
+        let registered = Registered::default();
+        let mut boot_services = boot_services(&registered);
+        boot_services.expect_close_event().once().return_const(Err(efi::Status::INVALID_PARAMETER));
+
+        let dropped = Rc::new(Cell::new(false));
+        let guard = DropFlag(dropped.clone());
+        drop(
+            Event::timer(&boot_services, Tpl::CALLBACK, move || {
+                let _ = &guard;
+            })
+            .unwrap(),
+        );
+        assert!(!dropped.get());
+    }
+
+    #[test]
+    fn test_forgotten_event_keeps_closure_alive() {
+        let registered = Registered::default();
+        let boot_services = boot_services(&registered);
+
+        let count = Rc::new(Cell::new(0));
+        let count_ref = count.clone();
+        let dropped = Rc::new(Cell::new(false));
+        let guard = DropFlag(dropped.clone());
+        let event = Event::timer(&boot_services, Tpl::CALLBACK, move || {
+            let _ = &guard;
+            count_ref.set(count_ref.get() + 1);
+        })
+        .unwrap();
+        // Forgetting the event leaves it registered, so the closure it owns must stay valid.
+        core::mem::forget(event);
+        signal(&registered);
+        assert_eq!(count.get(), 1);
+        assert!(!dropped.get());
+    }
+
+    #[test]
+    fn test_event_creation_failure_drops_closure() {
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_create_event_unchecked::<c_void>()
+            .once()
+            .returning(|_, _, _, _| Err(efi::Status::OUT_OF_RESOURCES));
+
+        let dropped = Rc::new(Cell::new(false));
+        let guard = DropFlag(dropped.clone());
+        let result = Event::timer(&boot_services, Tpl::CALLBACK, move || {
+            let _ = &guard;
+        });
+        assert_eq!(result.unwrap_err(), efi::Status::OUT_OF_RESOURCES);
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn test_timer_kind() {
+        assert!(matches!(TimerKind::Relative(Duration::from_millis(1)).to_timer(), (EventTimerType::Relative, 10_000)));
+        assert!(matches!(TimerKind::Periodic(Duration::from_nanos(250)).to_timer(), (EventTimerType::Periodic, 2)));
+        assert!(matches!(TimerKind::Relative(Duration::MAX).to_timer(), (EventTimerType::Relative, u64::MAX)));
+    }
+
+    struct DropFlag(Rc<Cell<bool>>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+}
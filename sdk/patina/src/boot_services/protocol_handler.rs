@@ -1,4 +1,4 @@
-//! This module provides type definitions for Protocol Handles
+//! This module provides type definitions for Protocol Handles and guards over the protocol database.
 //!
 //! ## License
 //!
@@ -6,10 +6,21 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
-use core::{ffi::c_void, ptr::NonNull};
+use core::{
+    ffi::c_void,
+    fmt::{self, Debug},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
 
 use r_efi::efi;
 
+use super::{
+    BootServices,
+    c_ptr::{CMutRef, PtrMetadata},
+};
+use crate::uefi_protocol::ProtocolInterface;
+
 /// Represents a registration handle for protocol notifications in the UEFI system.
 pub type Registration = NonNull<c_void>;
 
@@ -33,3 +44,203 @@ impl From<HandleSearchType> for efi::LocateSearchType {
         }
     }
 }
+
+/// A protocol interface opened on a handle, closed with [`BootServices::close_protocol`] when the guard is dropped.
+///
+/// The guard dereferences to the typed interface, so no casting from the raw interface pointer is needed.
+///
+/// ```rust,ignore
+/// // SAFETY: No other reference to the loaded image protocol of `handle` exists.
+/// let loaded_image = unsafe {
+///     ProtocolGuard::<efi::protocols::loaded_image::Protocol, _>::open(
+///         boot_services,
+///         handle,
+///         image_handle,
+///         ptr::null_mut(),
+///         efi::OPEN_PROTOCOL_GET_PROTOCOL,
+///     )?
+/// };
+/// log::info!("Image base: {:p}", loaded_image.image_base);
+/// ```
+#[must_use = "if unused the protocol will immediately be closed"]
+pub struct ProtocolGuard<'a, T, B>
+where
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    boot_services: &'a B,
+    interface: &'a mut T,
+    handle: efi::Handle,
+    agent_handle: efi::Handle,
+    controller_handle: efi::Handle,
+}
+
+impl<'a, T, B> ProtocolGuard<'a, T, B>
+where
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    /// Opens the `T` protocol on `handle` on behalf of `agent_handle` and `controller_handle`.
+    ///
+    /// # Safety
+    ///
+    /// Do not create more than one mutable reference to the interface.
+    pub unsafe fn open(
+        boot_services: &'a B,
+        handle: efi::Handle,
+        agent_handle: efi::Handle,
+        controller_handle: efi::Handle,
+        attribute: u32,
+    ) -> Result<Self, efi::Status> {
+        // SAFETY: The caller guarantees that no other mutable reference to the interface exists.
+        let interface =
+            unsafe { boot_services.open_protocol::<T>(handle, agent_handle, controller_handle, attribute)? };
+        Ok(Self { boot_services, interface, handle, agent_handle, controller_handle })
+    }
+
+    /// Returns the handle the protocol is opened on.
+    pub fn handle(&self) -> efi::Handle {
+        self.handle
+    }
+
+    /// Closes the protocol, returning the error of [`BootServices::close_protocol`] that dropping the guard ignores.
+    pub fn close(self) -> Result<(), efi::Status> {
+        let this = core::mem::ManuallyDrop::new(self);
+        this.boot_services.close_protocol(this.handle, &T::PROTOCOL_GUID, this.agent_handle, this.controller_handle)
+    }
+}
+
+impl<T, B> Deref for ProtocolGuard<'_, T, B>
+where
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.interface
+    }
+}
+
+impl<T, B> DerefMut for ProtocolGuard<'_, T, B>
+where
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.interface
+    }
+}
+
+impl<T, B> Drop for ProtocolGuard<'_, T, B>
+where
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    fn drop(&mut self) {
+        if let Err(status) =
+            self.boot_services.close_protocol(self.handle, &T::PROTOCOL_GUID, self.agent_handle, self.controller_handle)
+        {
+            log::warn!("Failed to close protocol {:?} on handle {:p}: {status:?}", T::PROTOCOL_GUID, self.handle);
+        }
+    }
+}
+
+impl<T, B> Debug for ProtocolGuard<'_, T, B>
+where
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolGuard")
+            .field("protocol", &T::PROTOCOL_GUID)
+            .field("handle", &self.handle)
+            .field("agent_handle", &self.agent_handle)
+            .field("controller_handle", &self.controller_handle)
+            .finish()
+    }
+}
+
+/// A protocol interface installed on a handle, uninstalled with [`BootServices::uninstall_protocol_interface`] when
+/// the guard is dropped.
+///
+/// If uninstalling fails, the interface stays installed and its memory is leaked rather than freed while in use.
+///
+/// ```rust,ignore
+/// let installed = InstalledProtocol::install(boot_services, None, Box::new(MyProtocol::new()))?;
+/// // Uninstalled when `installed` goes out of scope, or kept with `installed.leak()`.
+/// ```
+#[must_use = "if unused the protocol will immediately be uninstalled"]
+pub struct InstalledProtocol<'a, T, R, B>
+where
+    R: CMutRef<'static, Type = T> + 'static,
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    boot_services: &'a B,
+    handle: efi::Handle,
+    key: Option<PtrMetadata<'static, R>>,
+}
+
+impl<'a, T, R, B> InstalledProtocol<'a, T, R, B>
+where
+    R: CMutRef<'static, Type = T> + 'static,
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    /// Installs `protocol_interface` on `handle`, or on a new handle if `handle` is `None`.
+    pub fn install(
+        boot_services: &'a B,
+        handle: Option<efi::Handle>,
+        protocol_interface: R,
+    ) -> Result<Self, efi::Status> {
+        let (handle, key) = boot_services.install_protocol_interface(handle, protocol_interface)?;
+        Ok(Self { boot_services, handle, key: Some(key) })
+    }
+
+    /// Returns the handle the protocol is installed on.
+    pub fn handle(&self) -> efi::Handle {
+        self.handle
+    }
+
+    /// Uninstalls the protocol and returns the interface.
+    ///
+    /// On error, the interface stays installed and its memory is leaked.
+    pub fn uninstall(mut self) -> Result<R, efi::Status> {
+        let key = self.key.take().expect("The key is only taken when the guard is consumed.");
+        self.boot_services.uninstall_protocol_interface(self.handle, key)
+    }
+
+    /// Keeps the protocol installed for the rest of boot, returning its handle and the key needed to uninstall it with
+    /// [`BootServices::uninstall_protocol_interface`].
+    pub fn leak(mut self) -> (efi::Handle, PtrMetadata<'static, R>) {
+        let key = self.key.take().expect("The key is only taken when the guard is consumed.");
+        (self.handle, key)
+    }
+}
+
+impl<T, R, B> Drop for InstalledProtocol<'_, T, R, B>
+where
+    R: CMutRef<'static, Type = T> + 'static,
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take()
+            && let Err(status) = self.boot_services.uninstall_protocol_interface(self.handle, key)
+        {
+            log::warn!("Failed to uninstall protocol {:?} from handle {:p}: {status:?}", T::PROTOCOL_GUID, self.handle);
+        }
+    }
+}
+
+impl<T, R, B> Debug for InstalledProtocol<'_, T, R, B>
+where
+    R: CMutRef<'static, Type = T> + 'static,
+    T: ProtocolInterface + 'static,
+    B: BootServices + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstalledProtocol").field("protocol", &T::PROTOCOL_GUID).field("handle", &self.handle).finish()
+    }
+}
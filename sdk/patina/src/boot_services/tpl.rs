@@ -12,16 +12,32 @@ use super::BootServices;
 
 /// This is a structure restore the [`Tpl`] at the end of its scope or when dropped.
 ///
+/// Holding the guard instead of pairing [`BootServices::raise_tpl`] with [`BootServices::restore_tpl`] by hand means
+/// the previous level is also restored on early returns and `?` error paths.
+///
 /// See [`BootServices::raise_tpl_guarded`] for more details.
 #[must_use = "if unused the Tpl will immediately restored"]
 pub struct TplGuard<'a, T: BootServices + ?Sized> {
     pub(crate) boot_services: &'a T,
-    pub(crate) retore_tpl: Tpl,
+    pub(crate) restore_tpl: Tpl,
+}
+
+impl<'a, T: BootServices + ?Sized> TplGuard<'a, T> {
+    /// Raises the task priority level to `tpl`. When the returned guard goes out of scope, the previous level is
+    /// restored.
+    pub fn raise(boot_services: &'a T, tpl: Tpl) -> Self {
+        TplGuard { boot_services, restore_tpl: boot_services.raise_tpl(tpl) }
+    }
+
+    /// Returns the level that will be restored when the guard is dropped.
+    pub fn previous_tpl(&self) -> Tpl {
+        self.restore_tpl
+    }
 }
 
 impl<T: BootServices + ?Sized> Drop for TplGuard<'_, T> {
     fn drop(&mut self) {
-        self.boot_services.restore_tpl(self.retore_tpl);
+        self.boot_services.restore_tpl(self.restore_tpl);
     }
 }
 
@@ -47,6 +63,11 @@ impl Tpl {
     /// If code requires more processing, it needs to signal an event to wait to obtain control again at whatever level it requires.
     /// This level is typically used to process low level IO to or from a device.
     pub const NOTIFY: Tpl = Tpl(efi::TPL_NOTIFY);
+
+    /// The highest priority level.
+    /// Interrupts are disabled at this level, so code executing at this level cannot be interrupted.
+    /// It should only be held for short critical sections.
+    pub const HIGH_LEVEL: Tpl = Tpl(efi::TPL_HIGH_LEVEL);
 }
 
 impl From<Tpl> for usize {
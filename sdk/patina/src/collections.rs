@@ -0,0 +1,57 @@
+//! Fixed-capacity collections for contexts where heap use is forbidden.
+//!
+//! Early boot code runs before the allocator exists, and crash paths such as panic and exception handlers cannot
+//! trust it. The collections in this module keep their elements inline, with the capacity fixed by a const generic
+//! parameter. They never allocate, their constructors are `const` so they can be placed in statics, and they report
+//! a full collection to the caller instead of panicking:
+//!
+//! - [`ArrayVec`] is a vector of up to `N` elements. Pushing to a full vector hands the element back.
+//! - [`ArrayString`] is a UTF-8 string of up to `N` bytes. Writes that do not fit are truncated at a character
+//!   boundary, so the contents are always valid UTF-8.
+//! - [`RingBuffer`] holds the most recent `N` elements. Pushing to a full buffer evicts the oldest element, which
+//!   suits breadcrumb and history buffers where the latest entries matter most.
+//!
+//! ## TPL Safety
+//!
+//! The collections are not synchronized. A collection only touched at a single TPL can be used directly, but one
+//! shared between TPLs, such as a buffer filled from an event notification and drained at `TPL_APPLICATION`, must be
+//! wrapped in a lock that raises to the highest TPL it is used from. Use
+//! [`TplSpinLock`](crate::sync::TplSpinLock) for short critical sections and in crash paths, and
+//! [`TplMutex`](crate::tpl_mutex::TplMutex) otherwise. Because no operation allocates or calls boot services, every
+//! operation is safe to perform at `TPL_HIGH_LEVEL`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use core::fmt::Write;
+//! use patina::collections::{ArrayString, ArrayVec, RingBuffer};
+//!
+//! let mut handles: ArrayVec<u32, 2> = ArrayVec::new();
+//! assert_eq!(handles.push(1), Ok(()));
+//! assert_eq!(handles.push(2), Ok(()));
+//! assert_eq!(handles.push(3), Err(3));
+//!
+//! let mut message: ArrayString<16> = ArrayString::new();
+//! let _ = write!(message, "status {:#x}", 0x8000_0000_0000_000Eu64);
+//! assert_eq!(message.as_str(), "status 0x8000000");
+//!
+//! let mut history: RingBuffer<&str, 2> = RingBuffer::new();
+//! history.push("dispatch");
+//! history.push("connect");
+//! assert_eq!(history.push("ready to boot"), Some("dispatch"));
+//! assert!(history.iter().eq(["connect", "ready to boot"].iter()));
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+mod array_string;
+mod array_vec;
+mod ring_buffer;
+
+pub use array_string::ArrayString;
+pub use array_vec::ArrayVec;
+pub use ring_buffer::{Iter as RingBufferIter, RingBuffer};
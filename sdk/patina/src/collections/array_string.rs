@@ -0,0 +1,207 @@
+//! A UTF-8 string with a fixed, inline capacity.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{fmt, ops::Deref};
+
+use crate::error::EfiError;
+
+/// A string holding up to `N` bytes of UTF-8 inline.
+///
+/// The string never allocates and always holds valid UTF-8. [`push_str`](Self::push_str) fails without modifying
+/// the string when the input does not fit, while the [`fmt::Write`] implementation keeps as much of the input as
+/// fits, truncated at a character boundary, and then reports [`fmt::Error`]. The latter suits crash reports, where a
+/// truncated message is better than none.
+#[derive(Clone, Copy)]
+pub struct ArrayString<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ArrayString<N> {
+    /// Creates an empty string.
+    pub const fn new() -> Self {
+        Self { bytes: [0; N], len: 0 }
+    }
+
+    /// Returns the maximum length of the string in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the length of the string in bytes.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the string is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the string is at capacity.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Returns the contents of the string.
+    pub fn as_str(&self) -> &str {
+        // SAFETY: The buffer is only ever written with complete UTF-8 characters.
+        unsafe { core::str::from_utf8_unchecked(&self.bytes[..self.len]) }
+    }
+
+    /// Appends `s` to the string.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`EfiError::BufferTooSmall`] and leaves the string unchanged if `s` does not fit.
+    pub fn push_str(&mut self, s: &str) -> Result<(), EfiError> {
+        if s.len() > N - self.len {
+            return Err(EfiError::BufferTooSmall);
+        }
+        self.append(s);
+        Ok(())
+    }
+
+    /// Appends `c` to the string.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`EfiError::BufferTooSmall`] and leaves the string unchanged if `c` does not fit.
+    pub fn push(&mut self, c: char) -> Result<(), EfiError> {
+        self.push_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Shortens the string to `len` bytes, or to the closest character boundary below it. Has no effect if `len` is
+    /// not less than the current length.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = Self::floor_char_boundary(self.as_str(), len);
+        }
+    }
+
+    /// Removes the contents of the string.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends the longest prefix of `s` that fits and ends on a character boundary, returning its length.
+    fn append(&mut self, s: &str) -> usize {
+        let count = Self::floor_char_boundary(s, N - self.len);
+        self.bytes[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        count
+    }
+
+    fn floor_char_boundary(s: &str, index: usize) -> usize {
+        let mut index = index.min(s.len());
+        while !s.is_char_boundary(index) {
+            index -= 1;
+        }
+        index
+    }
+}
+
+impl<const N: usize> Default for ArrayString<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for ArrayString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Write for ArrayString<N> {
+    /// Appends as much of `s` as fits, returning [`fmt::Error`] if it was truncated.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.append(s) < s.len() { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+impl<const N: usize> fmt::Display for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> PartialEq for ArrayString<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const N: usize> Eq for ArrayString<N> {}
+
+impl<const N: usize> PartialEq<str> for ArrayString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn test_push_str() {
+        let mut s: ArrayString<8> = ArrayString::new();
+        assert!(s.is_empty());
+        assert_eq!(s.push_str("patina"), Ok(()));
+        assert_eq!(s.push_str("abc"), Err(EfiError::BufferTooSmall));
+        assert_eq!(s.as_str(), "patina");
+        assert_eq!(s.push('!'), Ok(()));
+        assert_eq!(s.push('\u{e9}'), Err(EfiError::BufferTooSmall));
+        assert_eq!(s.len(), 7);
+        assert!(s.ends_with('!'));
+    }
+
+    #[test]
+    fn test_write_truncates_at_char_boundary() {
+        let mut s: ArrayString<5> = ArrayString::new();
+        assert!(write!(s, "ab\u{e9}\u{e9}").is_err());
+        assert_eq!(s.as_str(), "ab\u{e9}");
+        assert!(!s.is_full());
+
+        s.clear();
+        assert!(write!(s, "{}", 12345).is_ok());
+        assert!(s.is_full());
+        assert!(s.write_str("6").is_err());
+        assert_eq!(&s, "12345");
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut s: ArrayString<8> = ArrayString::new();
+        s.push_str("a\u{e9}b").unwrap();
+        s.truncate(2);
+        assert_eq!(s.as_str(), "a");
+        s.truncate(5);
+        assert_eq!(s.as_str(), "a");
+    }
+
+    #[test]
+    fn test_formatting() {
+        let mut s: ArrayString<8> = ArrayString::default();
+        s.push_str("x\"y").unwrap();
+        assert_eq!(std::format!("{s}"), "x\"y");
+        assert_eq!(std::format!("{s:?}"), "\"x\\\"y\"");
+        assert_eq!(s, s.clone());
+    }
+}
@@ -0,0 +1,249 @@
+//! A vector with a fixed, inline capacity.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr, slice,
+};
+
+/// A vector holding up to `N` elements inline.
+///
+/// Elements are stored in the vector itself, so it never allocates. Operations that would exceed the capacity
+/// return the rejected element instead of panicking. The vector dereferences to a slice of its elements.
+pub struct ArrayVec<T, const N: usize> {
+    items: [MaybeUninit<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    /// Creates an empty vector.
+    pub const fn new() -> Self {
+        Self { items: [const { MaybeUninit::uninit() }; N], len: 0 }
+    }
+
+    /// Returns the maximum number of elements the vector can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the vector.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the vector holds no elements.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the vector is at capacity.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Returns the number of elements that can still be pushed.
+    pub const fn remaining_capacity(&self) -> usize {
+        N - self.len
+    }
+
+    /// Appends `item` to the end of the vector.
+    ///
+    /// ## Errors
+    ///
+    /// Returns `item` back if the vector is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        self.items[self.len].write(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the last element, or `None` if the vector is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: The element at the old last index was initialized and is no longer counted in `len`, so it is
+        // read exactly once.
+        Some(unsafe { self.items[self.len].assume_init_read() })
+    }
+
+    /// Shortens the vector to `len` elements, dropping the rest. Has no effect if `len` is not less than the current
+    /// length.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(self.items[len..].as_mut_ptr() as *mut T, self.len - len);
+        // Update the length first so that a panicking destructor cannot cause a double drop.
+        self.len = len;
+        // SAFETY: The elements in `tail` were initialized and are no longer counted in `len`.
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    /// Removes all elements from the vector.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Returns the elements as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: The first `len` elements are initialized.
+        unsafe { slice::from_raw_parts(self.items.as_ptr() as *const T, self.len) }
+    }
+
+    /// Returns the elements as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: The first `len` elements are initialized.
+        unsafe { slice::from_raw_parts_mut(self.items.as_mut_ptr() as *mut T, self.len) }
+    }
+}
+
+impl<T: Copy, const N: usize> ArrayVec<T, N> {
+    /// Appends as many elements of `items` as fit, returning the number appended.
+    pub fn extend_from_slice_truncated(&mut self, items: &[T]) -> usize {
+        let count = items.len().min(self.remaining_capacity());
+        for (slot, item) in self.items[self.len..self.len + count].iter_mut().zip(items) {
+            slot.write(*item);
+        }
+        self.len += count;
+        count
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Default for ArrayVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for ArrayVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut clone = Self::new();
+        for item in self.iter() {
+            // Cannot fail, the clone has the same capacity.
+            let _ = clone.push(item.clone());
+        }
+        clone
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for ArrayVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for ArrayVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for ArrayVec<T, N> {}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, vec::Vec};
+
+    #[test]
+    fn test_push_until_full() {
+        let mut vec: ArrayVec<u32, 3> = ArrayVec::new();
+        assert!(vec.is_empty());
+        assert_eq!(vec.capacity(), 3);
+
+        assert_eq!(vec.push(1), Ok(()));
+        assert_eq!(vec.push(2), Ok(()));
+        assert_eq!(vec.push(3), Ok(()));
+        assert!(vec.is_full());
+        assert_eq!(vec.push(4), Err(4));
+        assert_eq!(vec.as_slice(), &[1, 2, 3]);
+
+        assert_eq!(vec.pop(), Some(3));
+        assert_eq!(vec.remaining_capacity(), 1);
+        vec[0] = 10;
+        assert_eq!(&*vec, &[10, 2]);
+    }
+
+    #[test]
+    fn test_pop_empty() {
+        let mut vec: ArrayVec<u32, 1> = ArrayVec::default();
+        assert_eq!(vec.pop(), None);
+        let mut vec: ArrayVec<u32, 0> = ArrayVec::new();
+        assert_eq!(vec.push(1), Err(1));
+    }
+
+    #[test]
+    fn test_extend_from_slice_truncated() {
+        let mut vec: ArrayVec<u8, 4> = ArrayVec::new();
+        assert_eq!(vec.extend_from_slice_truncated(b"ab"), 2);
+        assert_eq!(vec.extend_from_slice_truncated(b"cdef"), 2);
+        assert_eq!(vec.extend_from_slice_truncated(b"g"), 0);
+        assert_eq!(vec.as_slice(), b"abcd");
+    }
+
+    #[test]
+    fn test_elements_are_dropped() {
+        let item = Rc::new(());
+        {
+            let mut vec: ArrayVec<Rc<()>, 4> = ArrayVec::new();
+            for _ in 0..4 {
+                vec.push(item.clone()).unwrap();
+            }
+            assert_eq!(Rc::strong_count(&item), 5);
+
+            vec.truncate(2);
+            assert_eq!(Rc::strong_count(&item), 3);
+
+            let clone = vec.clone();
+            assert_eq!(clone.len(), 2);
+            assert_eq!(Rc::strong_count(&item), 5);
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn test_debug_and_eq() {
+        let mut a: ArrayVec<u32, 4> = ArrayVec::new();
+        let mut b: ArrayVec<u32, 4> = ArrayVec::new();
+        a.push(1).unwrap();
+        b.push(1).unwrap();
+        assert_eq!(a, b);
+        b.clear();
+        assert_ne!(a, b);
+        assert_eq!(std::format!("{a:?}"), "[1]");
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), [1]);
+    }
+}
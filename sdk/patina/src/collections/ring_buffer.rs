@@ -0,0 +1,252 @@
+//! A ring buffer with a fixed, inline capacity.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{fmt, iter::FusedIterator, mem::MaybeUninit};
+
+/// A ring buffer holding the most recent `N` elements inline.
+///
+/// Pushing to a full buffer evicts and returns the oldest element, so the buffer always holds the latest history.
+/// Elements are iterated from oldest to newest.
+pub struct RingBuffer<T, const N: usize> {
+    items: [MaybeUninit<T>; N],
+    /// Index of the oldest element.
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// Creates an empty buffer.
+    pub const fn new() -> Self {
+        Self { items: [const { MaybeUninit::uninit() }; N], head: 0, len: 0 }
+    }
+
+    /// Returns the maximum number of elements the buffer can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements in the buffer.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no elements.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the buffer is at capacity, so that the next push evicts an element.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Appends `item` as the newest element.
+    ///
+    /// Returns the oldest element if the buffer was full and it had to be evicted. A buffer with no capacity returns
+    /// `item` itself.
+    pub fn push(&mut self, item: T) -> Option<T> {
+        if N == 0 {
+            return Some(item);
+        }
+        if self.is_full() {
+            // SAFETY: The buffer is full, so the slot at `head` holds the initialized oldest element. It is replaced
+            // immediately, so it is read exactly once.
+            let evicted = unsafe { self.items[self.head].assume_init_read() };
+            self.items[self.head].write(item);
+            self.head = (self.head + 1) % N;
+            return Some(evicted);
+        }
+        self.items[(self.head + self.len) % N].write(item);
+        self.len += 1;
+        None
+    }
+
+    /// Removes and returns the oldest element, or `None` if the buffer is empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        // SAFETY: The buffer is not empty, so the slot at `head` is initialized. It is no longer counted once `head`
+        // advances, so it is read exactly once.
+        let item = unsafe { self.items[self.head].assume_init_read() };
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(item)
+    }
+
+    /// Returns the oldest element, or `None` if the buffer is empty.
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// Returns the newest element, or `None` if the buffer is empty.
+    pub fn back(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    /// Returns the element `index` positions after the oldest, or `None` if out of range.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        // SAFETY: `index` is within `len`, so the slot is initialized.
+        Some(unsafe { self.items[(self.head + index) % N].assume_init_ref() })
+    }
+
+    /// Removes all elements from the buffer.
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+        self.head = 0;
+    }
+
+    /// Returns an iterator over the elements from oldest to newest.
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter { buffer: self, front: 0, back: self.len }
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for RingBuffer<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a RingBuffer<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a [`RingBuffer`], from oldest to newest.
+pub struct Iter<'a, T, const N: usize> {
+    buffer: &'a RingBuffer<T, N>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.buffer.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Iter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.buffer.get(self.back)
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Iter<'_, T, N> {}
+
+impl<T, const N: usize> FusedIterator for Iter<'_, T, N> {}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, vec::Vec};
+
+    #[test]
+    fn test_push_evicts_oldest() {
+        let mut ring: RingBuffer<u32, 3> = RingBuffer::new();
+        assert!(ring.is_empty());
+        assert_eq!(ring.push(1), None);
+        assert_eq!(ring.push(2), None);
+        assert_eq!(ring.push(3), None);
+        assert!(ring.is_full());
+        assert_eq!(ring.push(4), Some(1));
+        assert_eq!(ring.push(5), Some(2));
+
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), [3, 4, 5]);
+        assert_eq!(ring.iter().rev().copied().collect::<Vec<_>>(), [5, 4, 3]);
+        assert_eq!(ring.front(), Some(&3));
+        assert_eq!(ring.back(), Some(&5));
+        assert_eq!(ring.get(3), None);
+        assert_eq!(ring.iter().len(), 3);
+    }
+
+    #[test]
+    fn test_pop_front_wraps() {
+        let mut ring: RingBuffer<u32, 2> = RingBuffer::default();
+        assert_eq!(ring.pop_front(), None);
+        for value in 0..5 {
+            ring.push(value);
+        }
+        assert_eq!(ring.pop_front(), Some(3));
+        ring.push(5);
+        ring.push(6);
+        assert_eq!(ring.pop_front(), Some(5));
+        assert_eq!(ring.pop_front(), Some(6));
+        assert_eq!(ring.pop_front(), None);
+        assert_eq!(ring.back(), None);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut ring: RingBuffer<u32, 0> = RingBuffer::new();
+        assert_eq!(ring.push(1), Some(1));
+        assert!(ring.is_empty());
+        assert_eq!(ring.iter().next(), None);
+    }
+
+    #[test]
+    fn test_elements_are_dropped() {
+        let item = Rc::new(());
+        {
+            let mut ring: RingBuffer<Rc<()>, 2> = RingBuffer::new();
+            ring.push(item.clone());
+            ring.push(item.clone());
+            drop(ring.push(item.clone()));
+            assert_eq!(Rc::strong_count(&item), 3);
+            ring.clear();
+            assert_eq!(Rc::strong_count(&item), 1);
+            ring.push(item.clone());
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut ring: RingBuffer<u32, 2> = RingBuffer::new();
+        ring.push(1);
+        ring.push(2);
+        ring.push(3);
+        assert_eq!(std::format!("{ring:?}"), "[2, 3]");
+        assert_eq!((&ring).into_iter().count(), 2);
+    }
+}
@@ -123,6 +123,7 @@
 //!
 extern crate alloc;
 
+pub mod config_payload;
 pub mod hob;
 mod metadata;
 pub mod params;
@@ -0,0 +1,642 @@
+//! Configuration payloads.
+//!
+//! A configuration payload lets an OEM change the [`Config`](super::params::Config) values of components without
+//! recompiling them. The payload is a JSON5 or CBOR document in the RAW section of an FFS file named
+//! [`PLATFORM_CONFIG_FILE`](crate::guids::PLATFORM_CONFIG_FILE), mapping the name of each config to the fields it
+//! overrides:
+//!
+//! ```json5
+//! {
+//!     // Test all memory on the production line.
+//!     memory_test: { enable_component: true, coverage: "extensive", max_bytes: 0x100000000 },
+//! }
+//! ```
+//!
+//! A config type opts in by declaring its name and the schema of the fields a payload may set with
+//! [`payload_config!`](crate::payload_config!):
+//!
+//! ```rust
+//! #[derive(Default)]
+//! struct WatchdogConfig {
+//!     enabled: bool,
+//!     timeout_seconds: u32,
+//! }
+//!
+//! patina::payload_config! {
+//!     WatchdogConfig => "watchdog" {
+//!         enabled: bool,
+//!         timeout_seconds: u32 [10..=3600],
+//!     }
+//! }
+//! ```
+//!
+//! ## Layering
+//!
+//! The DXE core applies the payload after the platform's [`ComponentInfo`] configs are added and before any component
+//! is dispatched, so a field set by the payload overrides the platform value, which overrides the [`Default`]. Fields
+//! the payload does not name keep their value.
+//!
+//! Each config in the payload is validated against its schema before any of its fields are set. A config that does
+//! not validate, including one naming a field that is not in its schema, is logged and left unchanged, as is a config
+//! that no component declared.
+//!
+//! ## Formats
+//!
+//! The format is detected from the first byte: a CBOR map (major type 5) is decoded as CBOR, and anything else as
+//! JSON5. Each format is only supported when its feature is enabled:
+//!
+//! - `config-json5`: JSON5 text, with comments, unquoted keys, trailing commas, and hexadecimal integers.
+//! - `config-cbor`: CBOR ([RFC 8949](https://www.rfc-editor.org/rfc/rfc8949)) with definite lengths and text keys.
+//!
+//! Configs hold integers, booleans, and strings; floating point numbers, byte strings, and CBOR tags are rejected.
+//!
+//! [`ComponentInfo`]: https://docs.rs/patina_dxe_core/latest/patina_dxe_core/trait.ComponentInfo.html
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#[cfg(any(feature = "config-cbor", test))]
+mod cbor;
+#[cfg(any(feature = "config-json5", test))]
+mod json5;
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, marker::PhantomData};
+
+use super::Storage;
+
+#[doc(hidden)]
+pub use linkme;
+
+/// The deepest nesting of arrays and maps accepted in a payload.
+#[cfg(any(feature = "config-json5", feature = "config-cbor", test))]
+const MAX_DEPTH: usize = 16;
+
+/// A value decoded from a configuration payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValue {
+    /// `null`.
+    Null,
+    /// A boolean.
+    Bool(bool),
+    /// An integer, wide enough for every `u64` and `i64`.
+    Integer(i128),
+    /// A string.
+    String(String),
+    /// An array.
+    Array(Vec<ConfigValue>),
+    /// A map with string keys, in payload order.
+    Map(Vec<(String, ConfigValue)>),
+}
+
+impl ConfigValue {
+    /// Returns the value of `key`, if this is a map containing it.
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        match self {
+            ConfigValue::Map(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns a description of the type of the value, for error messages.
+    pub const fn type_name(&self) -> &'static str {
+        match self {
+            ConfigValue::Null => "null",
+            ConfigValue::Bool(_) => "a boolean",
+            ConfigValue::Integer(_) => "an integer",
+            ConfigValue::String(_) => "a string",
+            ConfigValue::Array(_) => "an array",
+            ConfigValue::Map(_) => "a map",
+        }
+    }
+}
+
+/// The encoding of a configuration payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// JSON5 text.
+    Json5,
+    /// CBOR.
+    Cbor,
+}
+
+impl PayloadFormat {
+    /// Detects the format of `payload` from its first byte.
+    pub fn detect(payload: &[u8]) -> Self {
+        match payload.first() {
+            Some(0xA0..=0xBF) => PayloadFormat::Cbor,
+            _ => PayloadFormat::Json5,
+        }
+    }
+}
+
+/// An error decoding a configuration payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadError {
+    /// The byte offset in the payload where decoding failed.
+    pub offset: usize,
+    /// What was wrong.
+    pub reason: &'static str,
+}
+
+impl PayloadError {
+    const fn new(offset: usize, reason: &'static str) -> Self {
+        Self { offset, reason }
+    }
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {:#x}", self.reason, self.offset)
+    }
+}
+
+/// Decodes a configuration payload, detecting its format with [`PayloadFormat::detect`].
+///
+/// Zero bytes after the document, left by section alignment padding, are ignored.
+pub fn parse(payload: &[u8]) -> Result<ConfigValue, PayloadError> {
+    match PayloadFormat::detect(payload) {
+        #[cfg(any(feature = "config-json5", test))]
+        PayloadFormat::Json5 => {
+            let end = payload.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+            json5::parse(&payload[..end])
+        }
+        #[cfg(any(feature = "config-cbor", test))]
+        PayloadFormat::Cbor => cbor::parse(payload),
+        #[allow(unreachable_patterns)]
+        _ => Err(PayloadError::new(0, "payload format is not enabled")),
+    }
+}
+
+/// The type of a config field, as declared in its schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// A boolean.
+    Bool,
+    /// An integer between `min` and `max`, inclusive.
+    Integer {
+        /// The smallest accepted value.
+        min: i128,
+        /// The largest accepted value.
+        max: i128,
+    },
+    /// Any string.
+    String,
+    /// One of the listed strings, compared without regard to ASCII case.
+    Choice(&'static [&'static str]),
+}
+
+/// The schema of one config field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSchema {
+    /// The name of the field in the payload.
+    pub name: &'static str,
+    /// The values the field accepts.
+    pub field_type: FieldType,
+}
+
+impl FieldSchema {
+    /// Creates the schema of a field.
+    pub const fn new(name: &'static str, field_type: FieldType) -> Self {
+        Self { name, field_type }
+    }
+
+    /// Narrows an integer field to values between `min` and `max`, inclusive. Other fields are unchanged.
+    pub const fn with_range(mut self, min: i128, max: i128) -> Self {
+        if let FieldType::Integer { min: type_min, max: type_max } = self.field_type {
+            let min = if min > type_min { min } else { type_min };
+            let max = if max < type_max { max } else { type_max };
+            self.field_type = FieldType::Integer { min, max };
+        }
+        self
+    }
+
+    /// Checks that `value` is accepted by the field.
+    pub fn validate(&self, value: &ConfigValue) -> Result<(), SchemaError> {
+        match (self.field_type, value) {
+            (FieldType::Bool, ConfigValue::Bool(_)) | (FieldType::String, ConfigValue::String(_)) => Ok(()),
+            (FieldType::Integer { min, max }, ConfigValue::Integer(value)) if !(min..=max).contains(value) => {
+                Err(SchemaError::OutOfRange { field: self.name, value: *value, min, max })
+            }
+            (FieldType::Integer { .. }, ConfigValue::Integer(_)) => Ok(()),
+            (FieldType::Choice(choices), ConfigValue::String(value))
+                if !choices.iter().any(|choice| choice.eq_ignore_ascii_case(value)) =>
+            {
+                Err(SchemaError::InvalidChoice { field: self.name, value: value.clone(), choices })
+            }
+            (FieldType::Choice(_), ConfigValue::String(_)) => Ok(()),
+            _ => Err(SchemaError::WrongType {
+                field: self.name,
+                expected: self.field_type.name(),
+                found: value.type_name(),
+            }),
+        }
+    }
+}
+
+impl FieldType {
+    /// Returns a description of the type, for error messages.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            FieldType::Bool => "a boolean",
+            FieldType::Integer { .. } => "an integer",
+            FieldType::String | FieldType::Choice(_) => "a string",
+        }
+    }
+}
+
+/// Why a config in a payload does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// The config is not a map of fields.
+    NotAMap,
+    /// The payload names a field that is not in the schema.
+    UnknownField(String),
+    /// A field has a value of the wrong type.
+    WrongType {
+        /// The field.
+        field: &'static str,
+        /// The type the schema declares.
+        expected: &'static str,
+        /// The type of the value in the payload.
+        found: &'static str,
+    },
+    /// An integer field is outside its range.
+    OutOfRange {
+        /// The field.
+        field: &'static str,
+        /// The value in the payload.
+        value: i128,
+        /// The smallest accepted value.
+        min: i128,
+        /// The largest accepted value.
+        max: i128,
+    },
+    /// A choice field is not one of its choices.
+    InvalidChoice {
+        /// The field.
+        field: &'static str,
+        /// The value in the payload.
+        value: String,
+        /// The accepted values.
+        choices: &'static [&'static str],
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::NotAMap => f.write_str("expected a map of fields"),
+            SchemaError::UnknownField(field) => write!(f, "unknown field {field:?}"),
+            SchemaError::WrongType { field, expected, found } => {
+                write!(f, "field {field:?} must be {expected}, found {found}")
+            }
+            SchemaError::OutOfRange { field, value, min, max } => {
+                write!(f, "field {field:?} must be between {min} and {max}, found {value}")
+            }
+            SchemaError::InvalidChoice { field, value, choices } => {
+                write!(f, "field {field:?} must be one of {choices:?}, found {value:?}")
+            }
+        }
+    }
+}
+
+/// Checks every field of `table` against `schema`.
+pub fn validate(schema: &[FieldSchema], table: &ConfigValue) -> Result<(), SchemaError> {
+    let ConfigValue::Map(fields) = table else {
+        return Err(SchemaError::NotAMap);
+    };
+    for (name, value) in fields {
+        let field =
+            schema.iter().find(|field| field.name == name).ok_or_else(|| SchemaError::UnknownField(name.clone()))?;
+        field.validate(value)?;
+    }
+    Ok(())
+}
+
+/// A type that can be a field of a [`PayloadConfig`].
+pub trait PayloadField: Sized {
+    /// The values the field accepts, before any range declared in the schema.
+    const FIELD_TYPE: FieldType;
+
+    /// Converts a value that has been validated against [`PayloadField::FIELD_TYPE`].
+    fn from_value(value: &ConfigValue) -> Option<Self>;
+}
+
+macro_rules! impl_payload_field_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl PayloadField for $ty {
+                const FIELD_TYPE: FieldType = FieldType::Integer { min: <$ty>::MIN as i128, max: <$ty>::MAX as i128 };
+
+                fn from_value(value: &ConfigValue) -> Option<Self> {
+                    match value {
+                        ConfigValue::Integer(value) => <$ty>::try_from(*value).ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_payload_field_for_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl PayloadField for bool {
+    const FIELD_TYPE: FieldType = FieldType::Bool;
+
+    fn from_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl PayloadField for String {
+    const FIELD_TYPE: FieldType = FieldType::String;
+
+    fn from_value(value: &ConfigValue) -> Option<Self> {
+        match value {
+            ConfigValue::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// A config type whose fields can be set from a configuration payload. Implement it with
+/// [`payload_config!`](crate::payload_config!), which also registers the config with the DXE core.
+pub trait PayloadConfig: Default + 'static {
+    /// The name of the config in the payload.
+    const NAME: &'static str;
+
+    /// The fields a payload may set.
+    const SCHEMA: &'static [FieldSchema];
+
+    /// Sets the field `name` from a value that has been validated against [`PayloadConfig::SCHEMA`].
+    fn set_field(&mut self, name: &str, value: &ConfigValue);
+}
+
+/// Declares the payload name and schema of a config type, implementing [`PayloadConfig`].
+///
+/// Each field is listed with its type, which must implement [`PayloadField`]. An integer field may be narrowed with
+/// an inclusive range in brackets. Fields that are not listed cannot be set from a payload. See the
+/// [module documentation](crate::component::config_payload) for an example.
+#[macro_export]
+macro_rules! payload_config {
+    (
+        $config:ty => $name:literal {
+            $($field:ident : $field_ty:ty $([$min:literal ..= $max:literal])?),* $(,)?
+        }
+    ) => {
+        impl $crate::component::config_payload::PayloadConfig for $config {
+            const NAME: &'static str = $name;
+            const SCHEMA: &'static [$crate::component::config_payload::FieldSchema] = &[
+                $(
+                    $crate::component::config_payload::FieldSchema::new(
+                        stringify!($field),
+                        <$field_ty as $crate::component::config_payload::PayloadField>::FIELD_TYPE,
+                    )
+                    $(.with_range($min, $max))?
+                ),*
+            ];
+
+            fn set_field(&mut self, name: &str, value: &$crate::component::config_payload::ConfigValue) {
+                match name {
+                    $(
+                        stringify!($field) => {
+                            if let Some(value) =
+                                <$field_ty as $crate::component::config_payload::PayloadField>::from_value(value)
+                            {
+                                self.$field = value;
+                            }
+                        }
+                    )*
+                    _ => {}
+                }
+            }
+        }
+
+        const _: () = {
+            #[$crate::component::config_payload::linkme::distributed_slice(
+                $crate::component::config_payload::PAYLOAD_CONFIGS
+            )]
+            #[linkme(crate = $crate::component::config_payload::linkme)]
+            static REGISTRATION: Option<&'static dyn $crate::component::config_payload::PayloadConfigInfo> =
+                Some(&$crate::component::config_payload::Registration::<$config>::new());
+        };
+    };
+}
+
+/// Type-erased access to a [`PayloadConfig`], as registered by [`payload_config!`](crate::payload_config!).
+pub trait PayloadConfigInfo: Sync {
+    /// Returns the name of the config in the payload.
+    fn name(&self) -> &'static str;
+
+    /// Returns the fields a payload may set.
+    fn schema(&self) -> &'static [FieldSchema];
+
+    /// Validates `table` and sets the fields it names on the config in `storage`, adding the default config if it is
+    /// not in storage yet. Nothing is set if `table` does not validate.
+    fn apply(&self, storage: &mut Storage, table: &ConfigValue) -> Result<(), SchemaError>;
+}
+
+/// The registration of a [`PayloadConfig`]. Use [`payload_config!`](crate::payload_config!) instead.
+#[doc(hidden)]
+pub struct Registration<C>(PhantomData<fn() -> C>);
+
+impl<C> Registration<C> {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C: PayloadConfig> PayloadConfigInfo for Registration<C> {
+    fn name(&self) -> &'static str {
+        C::NAME
+    }
+
+    fn schema(&self) -> &'static [FieldSchema] {
+        C::SCHEMA
+    }
+
+    fn apply(&self, storage: &mut Storage, table: &ConfigValue) -> Result<(), SchemaError> {
+        validate(C::SCHEMA, table)?;
+        storage.add_config_default_if_not_present::<C>();
+        if let (Some(mut config), ConfigValue::Map(fields)) = (storage.get_config_mut::<C>(), table) {
+            for (name, value) in fields {
+                config.set_field(name, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The configs that can be set from a payload, populated by [`payload_config!`](crate::payload_config!).
+///
+/// The entries are optional so that the SDK can register an empty one; the linker requires the slice to have at
+/// least one entry.
+#[linkme::distributed_slice]
+pub static PAYLOAD_CONFIGS: [Option<&'static dyn PayloadConfigInfo>];
+
+#[linkme::distributed_slice(PAYLOAD_CONFIGS)]
+static EMPTY_REGISTRATION: Option<&'static dyn PayloadConfigInfo> = None;
+
+/// Returns every config in the image that can be set from a payload.
+pub fn configs() -> impl Iterator<Item = &'static dyn PayloadConfigInfo> {
+    PAYLOAD_CONFIGS.iter().flatten().copied()
+}
+
+/// Applies a decoded payload to the configs in `storage`, returning the number of configs changed.
+///
+/// Configs that no component declared and configs that do not match their schema are logged and skipped.
+///
+/// ## Errors
+///
+/// Returns [`SchemaError::NotAMap`] if the payload is not a map of configs.
+pub fn apply(payload: &ConfigValue, storage: &mut Storage) -> Result<usize, SchemaError> {
+    let ConfigValue::Map(tables) = payload else {
+        return Err(SchemaError::NotAMap);
+    };
+    let mut applied = 0;
+    for (name, table) in tables {
+        let Some(config) = configs().find(|config| config.name() == name) else {
+            log::warn!("Configuration payload sets unknown config {}", name);
+            continue;
+        };
+        match config.apply(storage, table) {
+            Ok(()) => applied += 1,
+            Err(err) => log::error!("Ignoring configuration payload for config {}: {}", name, err),
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[derive(Debug, Default, PartialEq)]
+    struct TestConfig {
+        enabled: bool,
+        timeout: u32,
+        name: String,
+        unlisted: u8,
+    }
+
+    crate::payload_config! {
+        TestConfig => "test_config" {
+            enabled: bool,
+            timeout: u32 [10..=3600],
+            name: String,
+        }
+    }
+
+    fn map(entries: &[(&str, ConfigValue)]) -> ConfigValue {
+        ConfigValue::Map(entries.iter().map(|(name, value)| (name.to_string(), value.clone())).collect())
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(
+            TestConfig::SCHEMA,
+            [
+                FieldSchema::new("enabled", FieldType::Bool),
+                FieldSchema::new("timeout", FieldType::Integer { min: 10, max: 3600 }),
+                FieldSchema::new("name", FieldType::String),
+            ]
+        );
+
+        // A range cannot widen the type.
+        assert_eq!(
+            FieldSchema::new("byte", u8::FIELD_TYPE).with_range(-1, 1000).field_type,
+            FieldType::Integer { min: 0, max: 255 }
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = TestConfig::SCHEMA;
+        assert_eq!(validate(schema, &map(&[("enabled", ConfigValue::Bool(true))])), Ok(()));
+        assert_eq!(validate(schema, &ConfigValue::Bool(true)), Err(SchemaError::NotAMap));
+        assert_eq!(
+            validate(schema, &map(&[("unlisted", ConfigValue::Integer(1))])),
+            Err(SchemaError::UnknownField("unlisted".to_string()))
+        );
+        assert_eq!(
+            validate(schema, &map(&[("timeout", ConfigValue::Integer(5))])),
+            Err(SchemaError::OutOfRange { field: "timeout", value: 5, min: 10, max: 3600 })
+        );
+        assert_eq!(
+            validate(schema, &map(&[("enabled", ConfigValue::Integer(1))])).unwrap_err().to_string(),
+            "field \"enabled\" must be a boolean, found an integer"
+        );
+
+        let choice = FieldSchema::new("coverage", FieldType::Choice(&["sparse", "quick"]));
+        assert_eq!(choice.validate(&ConfigValue::String("Quick".to_string())), Ok(()));
+        assert!(matches!(
+            choice.validate(&ConfigValue::String("full".to_string())),
+            Err(SchemaError::InvalidChoice { field: "coverage", .. })
+        ));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut storage = Storage::new();
+        storage.add_config(TestConfig { timeout: 60, unlisted: 7, ..Default::default() });
+
+        let payload = map(&[
+            ("test_config", map(&[("enabled", ConfigValue::Bool(true)), ("name", ConfigValue::String("oem".into()))])),
+            ("unknown_config", map(&[])),
+        ]);
+        assert_eq!(apply(&payload, &mut storage), Ok(1));
+        assert_eq!(
+            *storage.get_config::<TestConfig>().unwrap(),
+            TestConfig { enabled: true, timeout: 60, name: "oem".to_string(), unlisted: 7 }
+        );
+
+        // A config that does not validate is left unchanged.
+        let payload = map(&[(
+            "test_config",
+            map(&[("enabled", ConfigValue::Bool(false)), ("timeout", ConfigValue::Integer(1))]),
+        )]);
+        assert_eq!(apply(&payload, &mut storage), Ok(0));
+        assert!(storage.get_config::<TestConfig>().unwrap().enabled);
+
+        assert_eq!(apply(&ConfigValue::Array(vec![]), &mut storage), Err(SchemaError::NotAMap));
+    }
+
+    #[test]
+    fn test_apply_adds_default_config() {
+        let mut storage = Storage::new();
+        let payload = map(&[("test_config", map(&[("timeout", ConfigValue::Integer(30))]))]);
+        assert_eq!(apply(&payload, &mut storage), Ok(1));
+        assert_eq!(storage.get_config::<TestConfig>().unwrap().timeout, 30);
+    }
+
+    #[test]
+    fn test_parse_detects_format() {
+        assert_eq!(PayloadFormat::detect(b"{}"), PayloadFormat::Json5);
+        assert_eq!(PayloadFormat::detect(&[0xA1]), PayloadFormat::Cbor);
+
+        let expected = map(&[("test_config", map(&[("enabled", ConfigValue::Bool(true))]))]);
+        assert_eq!(parse(b"{ test_config: { enabled: true } }\0\0\0"), Ok(expected.clone()));
+        let cbor = [&[0xA1, 0x6B][..], b"test_config", &[0xA1, 0x67], b"enabled", &[0xF5]].concat();
+        assert_eq!(parse(&cbor), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_cbor_ending_in_zero() {
+        // {"a": 0} and {"a": 0x100} end in zero bytes that belong to the item, not to padding.
+        assert_eq!(parse(&[0xA1, 0x61, b'a', 0x00]), Ok(map(&[("a", ConfigValue::Integer(0))])));
+        assert_eq!(parse(&[0xA1, 0x61, b'a', 0x19, 0x01, 0x00]), Ok(map(&[("a", ConfigValue::Integer(0x100))])));
+        assert_eq!(parse(&[0xA1, 0x61, b'a', 0x00, 0x00, 0x00]), Ok(map(&[("a", ConfigValue::Integer(0))])));
+        assert_eq!(parse(&[0xA1, 0x61, b'a', 0x19, 0x01]), Err(PayloadError::new(4, "unexpected end of data")));
+    }
+}
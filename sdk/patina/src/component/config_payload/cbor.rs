@@ -0,0 +1,175 @@
+//! CBOR decoding of configuration payloads.
+//!
+//! Decodes the CBOR data items a configuration needs: unsigned and negative integers, text strings, arrays, maps with
+//! text keys, booleans, and null. Indefinite lengths, byte strings, tags, and floating point numbers are rejected.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{string::String, vec::Vec};
+
+use super::{ConfigValue, MAX_DEPTH, PayloadError};
+
+/// Decodes a CBOR data item, followed by nothing but zero padding.
+///
+/// Padding cannot be trimmed before decoding, as a CBOR item can itself end in zero bytes.
+pub(super) fn parse(data: &[u8]) -> Result<ConfigValue, PayloadError> {
+    let mut decoder = Decoder { data, position: 0 };
+    let value = decoder.item(0)?;
+    if data[decoder.position..].iter().any(|&byte| byte != 0) {
+        return Err(decoder.error("unexpected data after the item"));
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn error(&self, reason: &'static str) -> PayloadError {
+        PayloadError::new(self.position, reason)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], PayloadError> {
+        let bytes = self
+            .position
+            .checked_add(length)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| self.error("unexpected end of data"))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Reads the initial byte and argument of a data item, returning its major type and argument.
+    fn head(&mut self) -> Result<(u8, u64), PayloadError> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1F);
+        let argument = match info {
+            0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap_or_default()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap_or_default()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap_or_default()),
+            31 => return Err(PayloadError::new(self.position - 1, "indefinite lengths are not supported")),
+            _ => return Err(PayloadError::new(self.position - 1, "invalid additional information")),
+        };
+        Ok((major, argument))
+    }
+
+    /// Converts a length argument, which must fit in the remaining data since every entry takes at least one byte.
+    fn length(&self, argument: u64) -> Result<usize, PayloadError> {
+        usize::try_from(argument)
+            .ok()
+            .filter(|&length| length <= self.data.len() - self.position)
+            .ok_or_else(|| self.error("length exceeds the data"))
+    }
+
+    fn item(&mut self, depth: usize) -> Result<ConfigValue, PayloadError> {
+        let start = self.position;
+        let (major, argument) = self.head()?;
+        match major {
+            0 => Ok(ConfigValue::Integer(argument as i128)),
+            1 => Ok(ConfigValue::Integer(-1 - argument as i128)),
+            3 => Ok(ConfigValue::String(self.text(argument)?)),
+            4 | 5 if depth == MAX_DEPTH => Err(PayloadError::new(start, "too deeply nested")),
+            4 => {
+                let length = self.length(argument)?;
+                let values = (0..length).map(|_| self.item(depth + 1)).collect::<Result<Vec<_>, _>>()?;
+                Ok(ConfigValue::Array(values))
+            }
+            5 => {
+                let length = self.length(argument)?;
+                let mut entries: Vec<(String, ConfigValue)> = Vec::with_capacity(length);
+                for _ in 0..length {
+                    let key_start = self.position;
+                    let key = match self.head()? {
+                        (3, length) => self.text(length)?,
+                        _ => return Err(PayloadError::new(key_start, "map keys must be text")),
+                    };
+                    if entries.iter().any(|(name, _)| *name == key) {
+                        return Err(PayloadError::new(key_start, "duplicate key"));
+                    }
+                    let value = self.item(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(ConfigValue::Map(entries))
+            }
+            // Floating point numbers are distinguished by the additional information, not the argument.
+            7 => match self.data[start] & 0x1F {
+                20 => Ok(ConfigValue::Bool(false)),
+                21 => Ok(ConfigValue::Bool(true)),
+                22 => Ok(ConfigValue::Null),
+                25..=27 => Err(PayloadError::new(start, "floating point numbers are not supported")),
+                _ => Err(PayloadError::new(start, "unsupported simple value")),
+            },
+            2 => Err(PayloadError::new(start, "byte strings are not supported")),
+            _ => Err(PayloadError::new(start, "tags are not supported")),
+        }
+    }
+
+    fn text(&mut self, length: u64) -> Result<String, PayloadError> {
+        let start = self.position;
+        let length = self.length(length)?;
+        let bytes = self.take(length)?;
+        core::str::from_utf8(bytes).map(String::from).map_err(|_| PayloadError::new(start, "text is not valid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn test_parse() {
+        // {"a": [0, 23, 24, 0x1234, 0x12345678, 0x1_0000_0000, -1, -500], "b": true, "c": null, "d": "é"}
+        let data = [
+            &[0xA4, 0x61, b'a', 0x88, 0x00, 0x17, 0x18, 0x18, 0x19, 0x12, 0x34, 0x1A, 0x12, 0x34, 0x56, 0x78][..],
+            &[0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x20, 0x39, 0x01, 0xF3],
+            &[0x61, b'b', 0xF5, 0x61, b'c', 0xF6, 0x61, b'd', 0x62, 0xC3, 0xA9],
+        ]
+        .concat();
+        let expected = ConfigValue::Map(vec![
+            (
+                "a".to_string(),
+                ConfigValue::Array(
+                    [0, 23, 24, 0x1234, 0x12345678, 0x1_0000_0000, -1, -500].map(ConfigValue::Integer).to_vec(),
+                ),
+            ),
+            ("b".to_string(), ConfigValue::Bool(true)),
+            ("c".to_string(), ConfigValue::Null),
+            ("d".to_string(), ConfigValue::String("é".to_string())),
+        ]);
+        assert_eq!(parse(&data), Ok(expected));
+
+        // The most negative 64-bit CBOR integer does not fit an i64.
+        let data = [0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(parse(&data), Ok(ConfigValue::Integer(-1 - u64::MAX as i128)));
+
+        // Zero padding may follow the item.
+        assert_eq!(parse(&[0x18, 0x2A, 0x00, 0x00]), Ok(ConfigValue::Integer(42)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(&[0xA1, 0x01, 0x01]), Err(PayloadError::new(1, "map keys must be text")));
+        assert_eq!(parse(&[0xBF]).unwrap_err().reason, "indefinite lengths are not supported");
+        assert_eq!(parse(&[0xF9, 0x3C, 0x00]).unwrap_err().reason, "floating point numbers are not supported");
+        assert_eq!(parse(&[0x41, 0x00]).unwrap_err().reason, "byte strings are not supported");
+        assert_eq!(parse(&[0xC1, 0x00]).unwrap_err().reason, "tags are not supported");
+        assert_eq!(parse(&[0x62, b'a']).unwrap_err().reason, "length exceeds the data");
+        assert_eq!(
+            parse(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).unwrap_err().reason,
+            "length exceeds the data"
+        );
+        assert_eq!(parse(&[0xA2, 0x61, b'a', 0x00, 0x61, b'a', 0x00]).unwrap_err().reason, "duplicate key");
+        assert_eq!(parse(&[0x00, 0x00, 0x01]), Err(PayloadError::new(1, "unexpected data after the item")));
+        assert_eq!(parse(&[0x81; MAX_DEPTH + 1]).unwrap_err().reason, "too deeply nested");
+    }
+}
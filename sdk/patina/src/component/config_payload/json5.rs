@@ -0,0 +1,284 @@
+//! JSON5 decoding of configuration payloads.
+//!
+//! Supports the JSON5 syntax a configuration needs: objects with quoted or identifier keys, arrays, trailing commas,
+//! `//` and `/* */` comments, single or double quoted strings, and decimal or hexadecimal integers with an optional
+//! sign. Floating point numbers, `Infinity`, and `NaN` are rejected.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{string::String, vec::Vec};
+
+use super::{ConfigValue, MAX_DEPTH, PayloadError};
+
+/// Decodes a JSON5 document.
+pub(super) fn parse(text: &[u8]) -> Result<ConfigValue, PayloadError> {
+    let mut parser = Parser { text, position: 0 };
+    let value = parser.value(0)?;
+    parser.skip_whitespace()?;
+    if parser.position < text.len() {
+        return Err(parser.error("unexpected text after the document"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &'static str) -> PayloadError {
+        PayloadError::new(self.position, reason)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn expect(&mut self, byte: u8, reason: &'static str) -> Result<(), PayloadError> {
+        match self.peek() {
+            Some(next) if next == byte => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(self.error(reason)),
+        }
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_whitespace(&mut self) -> Result<(), PayloadError> {
+        loop {
+            match (self.peek(), self.text.get(self.position + 1)) {
+                (Some(b' ' | b'\t' | b'\n' | b'\r'), _) => self.position += 1,
+                (Some(b'/'), Some(b'/')) => while !matches!(self.next(), Some(b'\n') | None) {},
+                (Some(b'/'), Some(b'*')) => {
+                    let end = self.text[self.position + 2..]
+                        .windows(2)
+                        .position(|pair| pair == b"*/")
+                        .ok_or_else(|| self.error("unterminated comment"))?;
+                    self.position += end + 4;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<ConfigValue, PayloadError> {
+        self.skip_whitespace()?;
+        match self.peek() {
+            Some(b'{' | b'[') if depth == MAX_DEPTH => Err(self.error("too deeply nested")),
+            Some(b'{') => self.map(depth + 1),
+            Some(b'[') => self.array(depth + 1),
+            Some(quote @ (b'"' | b'\'')) => self.string(quote).map(ConfigValue::String),
+            Some(b'+' | b'-' | b'0'..=b'9' | b'.') => self.integer(),
+            Some(byte) if is_identifier_start(byte) => match self.identifier().as_str() {
+                "true" => Ok(ConfigValue::Bool(true)),
+                "false" => Ok(ConfigValue::Bool(false)),
+                "null" => Ok(ConfigValue::Null),
+                "Infinity" | "NaN" => Err(self.error("floating point numbers are not supported")),
+                _ => Err(self.error("unexpected identifier")),
+            },
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of document")),
+        }
+    }
+
+    fn map(&mut self, depth: usize) -> Result<ConfigValue, PayloadError> {
+        self.position += 1;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            let key = match self.peek() {
+                Some(b'}') => break,
+                Some(quote @ (b'"' | b'\'')) => self.string(quote)?,
+                Some(byte) if is_identifier_start(byte) => self.identifier(),
+                _ => return Err(self.error("expected a key")),
+            };
+            if entries.iter().any(|(name, _)| *name == key) {
+                return Err(self.error("duplicate key"));
+            }
+            self.skip_whitespace()?;
+            self.expect(b':', "expected ':' after a key")?;
+            let value = self.value(depth)?;
+            entries.push((key, value));
+
+            self.skip_whitespace()?;
+            match self.next() {
+                Some(b',') => {}
+                Some(b'}') => return Ok(ConfigValue::Map(entries)),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+        self.position += 1;
+        Ok(ConfigValue::Map(entries))
+    }
+
+    fn array(&mut self, depth: usize) -> Result<ConfigValue, PayloadError> {
+        self.position += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            if self.peek() == Some(b']') {
+                break;
+            }
+            values.push(self.value(depth)?);
+
+            self.skip_whitespace()?;
+            match self.next() {
+                Some(b',') => {}
+                Some(b']') => return Ok(ConfigValue::Array(values)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+        self.position += 1;
+        Ok(ConfigValue::Array(values))
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| is_identifier_start(byte) || byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+        // Identifiers are ASCII.
+        String::from_utf8_lossy(&self.text[start..self.position]).into_owned()
+    }
+
+    fn string(&mut self, quote: u8) -> Result<String, PayloadError> {
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next() {
+                None | Some(b'\n' | b'\r') => return Err(self.error("unterminated string")),
+                Some(byte) if byte == quote => break,
+                Some(b'\\') => match self.next() {
+                    Some(b'n') => bytes.push(b'\n'),
+                    Some(b't') => bytes.push(b'\t'),
+                    Some(b'r') => bytes.push(b'\r'),
+                    Some(b'0') => bytes.push(0),
+                    Some(b'u') => {
+                        let digits = self.text.get(self.position..self.position + 4).unwrap_or_default();
+                        let character = core::str::from_utf8(digits)
+                            .ok()
+                            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        self.position += 4;
+                        bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    // A line continuation.
+                    Some(b'\n') => {}
+                    Some(b'\r') => {
+                        if self.peek() == Some(b'\n') {
+                            self.position += 1;
+                        }
+                    }
+                    Some(byte @ (b'\\' | b'"' | b'\'' | b'/')) => bytes.push(byte),
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(byte) => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("string is not valid UTF-8"))
+    }
+
+    fn integer(&mut self) -> Result<ConfigValue, PayloadError> {
+        let negative = match self.peek() {
+            Some(sign @ (b'+' | b'-')) => {
+                self.position += 1;
+                sign == b'-'
+            }
+            _ => false,
+        };
+        let hex = self.text[self.position..].starts_with(b"0x") || self.text[self.position..].starts_with(b"0X");
+        if hex {
+            self.position += 2;
+        }
+
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.') {
+            self.position += 1;
+        }
+        let digits = &self.text[start..self.position];
+        if !hex && digits.iter().any(|&byte| matches!(byte, b'.' | b'e' | b'E')) {
+            return Err(PayloadError::new(start, "floating point numbers are not supported"));
+        }
+
+        let radix = if hex { 16 } else { 10 };
+        let digits = core::str::from_utf8(digits).unwrap_or_default();
+        let magnitude = i128::from_str_radix(digits, radix)
+            .ok()
+            .filter(|_| !digits.starts_with('+'))
+            .ok_or(PayloadError::new(start, "invalid integer"))?;
+        Ok(ConfigValue::Integer(if negative { -magnitude } else { magnitude }))
+    }
+}
+
+fn is_identifier_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'_' || byte == b'$'
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+            // A comment.
+            {
+                memory_test: { enable_component: true, max_bytes: 0x10000000, },
+                'quoted': "a \"string\"é",
+                "list": [1, -2, +3, null, /* inline */ false,],
+            }
+        "#
+        .as_bytes();
+        let expected = ConfigValue::Map(vec![
+            (
+                "memory_test".to_string(),
+                ConfigValue::Map(vec![
+                    ("enable_component".to_string(), ConfigValue::Bool(true)),
+                    ("max_bytes".to_string(), ConfigValue::Integer(0x1000_0000)),
+                ]),
+            ),
+            ("quoted".to_string(), ConfigValue::String("a \"string\"é".to_string())),
+            (
+                "list".to_string(),
+                ConfigValue::Array(vec![
+                    ConfigValue::Integer(1),
+                    ConfigValue::Integer(-2),
+                    ConfigValue::Integer(3),
+                    ConfigValue::Null,
+                    ConfigValue::Bool(false),
+                ]),
+            ),
+        ]);
+        assert_eq!(parse(text), Ok(expected));
+        assert_eq!(parse(b"{}"), Ok(ConfigValue::Map(vec![])));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse(b"{ a: 1.5 }"), Err(PayloadError::new(5, "floating point numbers are not supported")));
+        assert_eq!(parse(b"{ a: 1 b: 2 }").unwrap_err().reason, "expected ',' or '}'");
+        assert_eq!(parse(b"{ a: 1, a: 2 }").unwrap_err().reason, "duplicate key");
+        assert_eq!(parse(b"{ a: 'open }").unwrap_err().reason, "unterminated string");
+        assert_eq!(parse(b"{ a: 0xZZ }").unwrap_err().reason, "invalid integer");
+        assert_eq!(parse(b"{ a: NaN }").unwrap_err().reason, "floating point numbers are not supported");
+        assert_eq!(parse(b"{} {}").unwrap_err().reason, "unexpected text after the document");
+        assert_eq!(parse(b"{ /* a: 1 }").unwrap_err().reason, "unterminated comment");
+        assert_eq!(parse(&[b'['; MAX_DEPTH + 1]).unwrap_err().reason, "too deeply nested");
+        assert_eq!(parse(b"").unwrap_err().reason, "unexpected end of document");
+    }
+}
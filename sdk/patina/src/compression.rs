@@ -0,0 +1,72 @@
+//! Pluggable compression for diagnostic data.
+//!
+//! Diagnostic data such as the memory log or a crash dump is often kept in reserved memory or sent over a slow
+//! serial link, where every byte counts. [`Compressor`] lets the code producing such data compress it without
+//! depending on a particular codec. The platform chooses the codec, trading CPU time for space, and host tooling
+//! restores the data with the matching [`Decompressor`].
+//!
+//! Compressed output is self-describing: a decompressor can tell from the data itself which codec produced it. The
+//! codecs are implemented outside of the SDK, for example by `patina_ffs_extractors`, which encodes the data as an
+//! LZMA or Brotli GUID-defined section so that the section extractors can restore it.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+
+use crate::error::EfiError;
+
+/// Compresses diagnostic data before it is stored or transferred.
+pub trait Compressor {
+    /// Compresses `data` into a self-describing buffer.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`EfiError::Unsupported`] if the codec is not available in this build, or another error if `data`
+    /// could not be compressed.
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, EfiError>;
+}
+
+/// Restores data compressed by a [`Compressor`].
+pub trait Decompressor {
+    /// Decompresses a buffer produced by a [`Compressor`].
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`EfiError::Unsupported`] if `data` was produced by a codec this decompressor does not support, or
+    /// another error if `data` is malformed.
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, EfiError>;
+}
+
+/// A [`Compressor`] and [`Decompressor`] that copies the data unchanged, for platforms that do not compress.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoCompression;
+
+impl Compressor for NoCompression {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, EfiError> {
+        Ok(data.to_vec())
+    }
+}
+
+impl Decompressor for NoCompression {
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, EfiError> {
+        Ok(data.to_vec())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_compression_round_trip() {
+        let data = b"patina";
+        let compressed = NoCompression.compress(data).unwrap();
+        assert_eq!(compressed, data);
+        assert_eq!(NoCompression.decompress(&compressed).unwrap(), data);
+    }
+}
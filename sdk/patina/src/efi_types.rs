@@ -9,6 +9,11 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
+use core::{
+    fmt,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not},
+};
+
 use r_efi::efi;
 
 use crate::error::EfiError;
@@ -122,3 +127,353 @@ impl From<EfiMemoryType> for efi::MemoryType {
         }
     }
 }
+
+/// The attributes of a memory region, as reported in the memory map and the GCD memory space map.
+///
+/// In a memory descriptor, the attributes describe the capabilities of the region, so more than one cacheability
+/// attribute may be set. When applied to a region, at most one cacheability attribute is expected.
+///
+/// <https://uefi.org/specs/UEFI/2.11/07_Services_Boot_Services.html#efi-boot-services-getmemorymap>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+#[repr(transparent)]
+pub struct EfiMemoryAttributes(u64);
+
+impl EfiMemoryAttributes {
+    /// Memory cacheability attribute: The memory region is not cacheable.
+    pub const UC: Self = Self(efi::MEMORY_UC);
+    /// Memory cacheability attribute: The memory region is write combined.
+    pub const WC: Self = Self(efi::MEMORY_WC);
+    /// Memory cacheability attribute: The memory region is cacheable with a “write through” policy. Writes that hit in
+    /// the cache will also be written to main memory.
+    pub const WT: Self = Self(efi::MEMORY_WT);
+    /// Memory cacheability attribute: The memory region is cacheable with a “write back” policy. Reads and writes that
+    /// hit in the cache do not propagate to main memory. Dirty data is written back to main memory when a new cache
+    /// line is allocated.
+    pub const WB: Self = Self(efi::MEMORY_WB);
+    /// Memory cacheability attribute: The memory region is cacheable, exported, and supports the “fetch and add”
+    /// semaphore mechanism.
+    pub const UCE: Self = Self(efi::MEMORY_UCE);
+    /// Physical memory protection attribute: The memory region is write-protected by system hardware. This is
+    /// typically used as a cacheability attribute today. The memory region is cacheable with a “write protected”
+    /// policy. Reads come from cache lines when possible, and read misses cause cache fills. Writes are propagated to
+    /// the system bus and cause corresponding cache lines on all processors on the bus to be invalidated.
+    pub const WP: Self = Self(efi::MEMORY_WP);
+    /// Physical memory protection attribute: The memory region is read-protected by system hardware.
+    pub const RP: Self = Self(efi::MEMORY_RP);
+    /// Physical memory protection attribute: The memory region supports is protected by system hardware from executing code.
+    pub const XP: Self = Self(efi::MEMORY_XP);
+    /// Runtime memory attribute: The memory region refers to persistent memory
+    pub const NV: Self = Self(efi::MEMORY_NV);
+    /// The memory region provides higher reliability relative to other memory in the system. If all memory has the
+    /// same reliability, then this bit is not used.
+    pub const MORE_RELIABLE: Self = Self(efi::MEMORY_MORE_RELIABLE);
+    /// Physical memory protection attribute: The memory region supports making this memory range read-only by system
+    /// hardware.
+    pub const RO: Self = Self(efi::MEMORY_RO);
+    /// Specific-purpose memory (SPM). The memory is earmarked for specific purposes such as for specific device
+    /// drivers or applications. The SPM attribute serves as a hint to the OS to avoid allocating this memory for core
+    /// OS data or code that can not be relocated. Prolonged use of this memory for purposes other than the intended
+    /// purpose may result in suboptimal platform performance.
+    pub const SP: Self = Self(efi::MEMORY_SP);
+    /// The memory region is protected with the CPU’s memory cryptographic capabilities. If this flag is clear, the
+    /// memory region is not capable of being protected with the CPU’s memory cryptographic capabilities or the CPU
+    /// does not support CPU memory cryptographic capabilities.
+    pub const CPU_CRYPTO: Self = Self(efi::MEMORY_CPU_CRYPTO);
+    /// Runtime memory attribute: The memory region needs to be given a virtual mapping by the operating system when
+    /// SetVirtualAddressMap() is called.
+    pub const RUNTIME: Self = Self(efi::MEMORY_RUNTIME);
+    /// The memory region is described with additional ISA-specific memory attributes as specified in
+    /// EFI_MEMORY_ISA_MASK.
+    pub const ISA_VALID: Self = Self(efi::MEMORY_ISA_VALID);
+    /// Bits reserved for describing optional ISA-specific cacheability attributes that are not covered by
+    /// the standard UEFI Memory Attributes cacheability bits (EFI_MEMORY_UC, EFI_MEMORY_WC, EFI_MEMORY_WT,
+    /// EFI_MEMORY_WB and EFI_MEMORY_UCE). See Calling Conventions for further ISA-specific enumeration of these bits.
+    pub const ISA_MASK: Self = Self(efi::MEMORY_ISA_MASK);
+
+    /// The cacheability attributes: UC, WC, WT, WB, UCE, and WP.
+    pub const CACHE_MASK: Self = Self(efi::CACHE_ATTRIBUTE_MASK);
+    /// The memory protection attributes: RP, XP, and RO.
+    pub const ACCESS_MASK: Self = Self(efi::MEMORY_ACCESS_MASK);
+
+    // Every attribute defined by the specification.
+    const ALL: Self = Self(
+        efi::CACHE_ATTRIBUTE_MASK
+            | efi::MEMORY_ACCESS_MASK
+            | efi::MEMORY_NV
+            | efi::MEMORY_MORE_RELIABLE
+            | efi::MEMORY_SP
+            | efi::MEMORY_CPU_CRYPTO
+            | efi::MEMORY_RUNTIME
+            | efi::MEMORY_ISA_VALID
+            | efi::MEMORY_ISA_MASK,
+    );
+
+    // Attribute names in display order.
+    const NAMES: [(Self, &'static str); 16] = [
+        (Self::UC, "UC"),
+        (Self::WC, "WC"),
+        (Self::WT, "WT"),
+        (Self::WB, "WB"),
+        (Self::UCE, "UCE"),
+        (Self::WP, "WP"),
+        (Self::RP, "RP"),
+        (Self::XP, "XP"),
+        (Self::NV, "NV"),
+        (Self::MORE_RELIABLE, "MORE_RELIABLE"),
+        (Self::RO, "RO"),
+        (Self::SP, "SP"),
+        (Self::CPU_CRYPTO, "CPU_CRYPTO"),
+        (Self::RUNTIME, "RUNTIME"),
+        (Self::ISA_VALID, "ISA_VALID"),
+        (Self::ISA_MASK, "ISA_MASK"),
+    ];
+
+    /// Returns attributes with no bits set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the raw attribute bits.
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Converts raw attribute bits, returning `None` if any bit is not defined by the UEFI specification.
+    ///
+    /// Use `EfiMemoryAttributes::from` to keep undefined bits instead.
+    pub const fn from_bits(bits: u64) -> Option<Self> {
+        if bits & !Self::ALL.0 == 0 { Some(Self(bits)) } else { None }
+    }
+
+    /// Returns true if no attribute is set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if every attribute in `other` is set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if any attribute in `other` is set.
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Sets the attributes in `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clears the attributes in `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Returns only the cacheability attributes.
+    pub const fn cache_attributes(self) -> Self {
+        Self(self.0 & Self::CACHE_MASK.0)
+    }
+
+    /// Returns only the memory protection attributes.
+    pub const fn access_attributes(self) -> Self {
+        Self(self.0 & Self::ACCESS_MASK.0)
+    }
+
+    /// Returns true if a cacheable policy (WT, WB, UCE, or WP) is set. UC and WC regions are not cached.
+    pub const fn is_cacheable(self) -> bool {
+        self.intersects(Self(efi::MEMORY_WT | efi::MEMORY_WB | efi::MEMORY_UCE | efi::MEMORY_WP))
+    }
+
+    /// Returns true if the region may hold code, that is, XP is not set.
+    pub const fn is_executable(self) -> bool {
+        !self.contains(Self::XP)
+    }
+
+    /// Returns true if the region is mapped by the operating system for runtime services.
+    pub const fn is_runtime(self) -> bool {
+        self.contains(Self::RUNTIME)
+    }
+
+    /// Checks that the attributes are consistent with a region of `memory_type`.
+    ///
+    /// Returns [`EfiError::InvalidParameter`] if:
+    /// - a bit not defined by the UEFI specification is set.
+    /// - RUNTIME is missing from runtime services code or data.
+    /// - RUNTIME is set on memory that is freed or reused by the OS after ExitBootServices(): loader, boot services,
+    ///   conventional, and unaccepted memory.
+    /// - XP is set on a code type together with RP, so the region can neither be read nor executed.
+    pub fn validate_for_type(self, memory_type: EfiMemoryType) -> Result<(), EfiError> {
+        if Self::from_bits(self.0).is_none() {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        match memory_type {
+            EfiMemoryType::RuntimeServicesCode | EfiMemoryType::RuntimeServicesData if !self.is_runtime() => {
+                return Err(EfiError::InvalidParameter);
+            }
+            EfiMemoryType::LoaderCode
+            | EfiMemoryType::LoaderData
+            | EfiMemoryType::BootServicesCode
+            | EfiMemoryType::BootServicesData
+            | EfiMemoryType::ConventionalMemory
+            | EfiMemoryType::UnacceptedMemoryType
+                if self.is_runtime() =>
+            {
+                return Err(EfiError::InvalidParameter);
+            }
+            _ => {}
+        }
+
+        let code = matches!(
+            memory_type,
+            EfiMemoryType::LoaderCode | EfiMemoryType::BootServicesCode | EfiMemoryType::RuntimeServicesCode
+        );
+        if code && self.contains(Self::XP | Self::RP) {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        Ok(())
+    }
+}
+
+impl From<u64> for EfiMemoryAttributes {
+    /// Converts raw attribute bits, keeping any bits not defined by the UEFI specification.
+    fn from(bits: u64) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<EfiMemoryAttributes> for u64 {
+    fn from(attributes: EfiMemoryAttributes) -> Self {
+        attributes.0
+    }
+}
+
+impl BitOr for EfiMemoryAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for EfiMemoryAttributes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
+
+impl BitAnd for EfiMemoryAttributes {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for EfiMemoryAttributes {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0
+    }
+}
+
+impl Not for EfiMemoryAttributes {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self(!self.0)
+    }
+}
+
+impl fmt::Display for EfiMemoryAttributes {
+    /// Displays the attribute names separated by `|`, followed by any undefined bits in hexadecimal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        for (attribute, name) in Self::NAMES {
+            // The ISA mask covers several bits, which are shown with the undefined ones.
+            if attribute != Self::ISA_MASK && self.contains(attribute) {
+                write!(f, "{separator}{name}")?;
+                separator = "|";
+            }
+        }
+        let remaining = self.0 & !(Self::ALL.0 & !Self::ISA_MASK.0);
+        if remaining != 0 || separator.is_empty() {
+            write!(f, "{separator}{remaining:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_memory_attributes_bits() {
+        let attributes = EfiMemoryAttributes::WB | EfiMemoryAttributes::XP | EfiMemoryAttributes::RUNTIME;
+        assert_eq!(u64::from(attributes), efi::MEMORY_WB | efi::MEMORY_XP | efi::MEMORY_RUNTIME);
+        assert_eq!(EfiMemoryAttributes::from(attributes.bits()), attributes);
+        assert_eq!(EfiMemoryAttributes::from_bits(attributes.bits()), Some(attributes));
+        assert_eq!(EfiMemoryAttributes::from_bits(1 << 40), None);
+        assert_eq!(EfiMemoryAttributes::from(1 << 40).bits(), 1 << 40);
+
+        assert!(attributes.contains(EfiMemoryAttributes::WB | EfiMemoryAttributes::XP));
+        assert!(!attributes.contains(EfiMemoryAttributes::WB | EfiMemoryAttributes::RO));
+        assert!(attributes.intersects(EfiMemoryAttributes::ACCESS_MASK));
+        assert_eq!(attributes.cache_attributes(), EfiMemoryAttributes::WB);
+        assert_eq!(attributes.access_attributes(), EfiMemoryAttributes::XP);
+        assert_eq!(attributes & !EfiMemoryAttributes::RUNTIME, EfiMemoryAttributes::WB | EfiMemoryAttributes::XP);
+
+        let mut attributes = attributes;
+        attributes.remove(EfiMemoryAttributes::XP);
+        attributes.insert(EfiMemoryAttributes::RO);
+        assert_eq!(attributes, EfiMemoryAttributes::WB | EfiMemoryAttributes::RO | EfiMemoryAttributes::RUNTIME);
+        assert!(EfiMemoryAttributes::empty().is_empty());
+    }
+
+    #[test]
+    fn test_memory_attributes_predicates() {
+        assert!(EfiMemoryAttributes::WB.is_cacheable());
+        assert!(EfiMemoryAttributes::WP.is_cacheable());
+        assert!(!EfiMemoryAttributes::UC.is_cacheable());
+        assert!(!(EfiMemoryAttributes::WC | EfiMemoryAttributes::XP).is_cacheable());
+        assert!(EfiMemoryAttributes::WB.is_executable());
+        assert!(!(EfiMemoryAttributes::WB | EfiMemoryAttributes::XP).is_executable());
+        assert!((EfiMemoryAttributes::UC | EfiMemoryAttributes::RUNTIME).is_runtime());
+    }
+
+    #[test]
+    fn test_validate_for_type() {
+        let runtime = EfiMemoryAttributes::WB | EfiMemoryAttributes::RUNTIME;
+        assert_eq!(runtime.validate_for_type(EfiMemoryType::RuntimeServicesData), Ok(()));
+        assert_eq!(runtime.validate_for_type(EfiMemoryType::MemoryMappedIO), Ok(()));
+        assert_eq!(runtime.validate_for_type(EfiMemoryType::BootServicesData), Err(EfiError::InvalidParameter));
+        assert_eq!(runtime.validate_for_type(EfiMemoryType::ConventionalMemory), Err(EfiError::InvalidParameter));
+        assert_eq!(
+            EfiMemoryAttributes::WB.validate_for_type(EfiMemoryType::RuntimeServicesCode),
+            Err(EfiError::InvalidParameter)
+        );
+        assert_eq!(EfiMemoryAttributes::WB.validate_for_type(EfiMemoryType::MemoryMappedIO), Ok(()));
+
+        let guard = EfiMemoryAttributes::XP | EfiMemoryAttributes::RP;
+        assert_eq!(guard.validate_for_type(EfiMemoryType::BootServicesData), Ok(()));
+        assert_eq!(guard.validate_for_type(EfiMemoryType::BootServicesCode), Err(EfiError::InvalidParameter));
+
+        assert_eq!(
+            EfiMemoryAttributes::from(1 << 40).validate_for_type(EfiMemoryType::ReservedMemoryType),
+            Err(EfiError::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn test_memory_attributes_display() {
+        let attributes = EfiMemoryAttributes::WB | EfiMemoryAttributes::XP | EfiMemoryAttributes::RUNTIME;
+        assert_eq!(attributes.to_string(), "WB|XP|RUNTIME");
+        assert_eq!((EfiMemoryAttributes::UC | EfiMemoryAttributes::from(1 << 40)).to_string(), "UC|0x10000000000");
+        assert_eq!(EfiMemoryAttributes::empty().to_string(), "0x0");
+    }
+}
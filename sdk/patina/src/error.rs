@@ -1,5 +1,8 @@
 //! Module for converting UEFI errors to rusty errors.
 //!
+//! [`EfiError`] covers the error status codes, and [`EfiWarning`] the warning status codes. [`EfiStatus`] classifies
+//! any status code as success, a warning, or an error, for code that has to tell them apart at an FFI boundary.
+//!
 //! ## License
 //!
 //! Copyright (c) Microsoft Corporation.
@@ -11,7 +14,6 @@
 pub type Result<T> = core::result::Result<T, EfiError>;
 
 use r_efi::efi;
-// TODO: Handle difference between warning and error
 
 /// EDK II Error Code equivalent as a Rust Error enum
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -84,6 +86,20 @@ pub enum EfiError {
     IpAddressConflict,
     /// A HTTP error occurred during the network operation.
     HttpError,
+    /// The network is unreachable.
+    NetworkUnreachable,
+    /// The host is unreachable.
+    HostUnreachable,
+    /// The protocol is unreachable.
+    ProtocolUnreachable,
+    /// The port is unreachable.
+    PortUnreachable,
+    /// The connection was closed by the remote host.
+    ConnectionFin,
+    /// The connection was reset by the remote host.
+    ConnectionReset,
+    /// The connection was refused by the remote host.
+    ConnectionRefused,
     /// An unknown EFI status code was encountered.
     Unknown(efi::Status),
 }
@@ -130,9 +146,27 @@ impl EfiError {
             efi::Status::COMPROMISED_DATA => Err(EfiError::CompromisedData),
             efi::Status::IP_ADDRESS_CONFLICT => Err(EfiError::IpAddressConflict),
             efi::Status::HTTP_ERROR => Err(EfiError::HttpError),
+            efi::Status::NETWORK_UNREACHABLE => Err(EfiError::NetworkUnreachable),
+            efi::Status::HOST_UNREACHABLE => Err(EfiError::HostUnreachable),
+            efi::Status::PROTOCOL_UNREACHABLE => Err(EfiError::ProtocolUnreachable),
+            efi::Status::PORT_UNREACHABLE => Err(EfiError::PortUnreachable),
+            efi::Status::CONNECTION_FIN => Err(EfiError::ConnectionFin),
+            efi::Status::CONNECTION_RESET => Err(EfiError::ConnectionReset),
+            efi::Status::CONNECTION_REFUSED => Err(EfiError::ConnectionRefused),
             _ => Err(EfiError::Unknown(status)),
         }
     }
+
+    /// Converts a `Result` to an `r_efi::efi::Status` to return across an FFI boundary.
+    ///
+    /// If the result is `Ok`, it returns `SUCCESS`, discarding the value.
+    /// Otherwise, it returns the status code of the error.
+    pub fn result_to_status<T>(result: Result<T>) -> efi::Status {
+        match result {
+            Ok(_) => efi::Status::SUCCESS,
+            Err(err) => err.into(),
+        }
+    }
 }
 
 impl From<EfiError> for efi::Status {
@@ -171,6 +205,13 @@ impl From<EfiError> for efi::Status {
             EfiError::CompromisedData => efi::Status::COMPROMISED_DATA,
             EfiError::IpAddressConflict => efi::Status::IP_ADDRESS_CONFLICT,
             EfiError::HttpError => efi::Status::HTTP_ERROR,
+            EfiError::NetworkUnreachable => efi::Status::NETWORK_UNREACHABLE,
+            EfiError::HostUnreachable => efi::Status::HOST_UNREACHABLE,
+            EfiError::ProtocolUnreachable => efi::Status::PROTOCOL_UNREACHABLE,
+            EfiError::PortUnreachable => efi::Status::PORT_UNREACHABLE,
+            EfiError::ConnectionFin => efi::Status::CONNECTION_FIN,
+            EfiError::ConnectionReset => efi::Status::CONNECTION_RESET,
+            EfiError::ConnectionRefused => efi::Status::CONNECTION_REFUSED,
             EfiError::Unknown(status) => status,
         }
     }
@@ -181,3 +222,214 @@ impl From<efi::Status> for EfiError {
         EfiError::status_to_result(status).unwrap_err()
     }
 }
+
+/// EDK II Warning Code equivalent as a Rust enum. A warning reports that an operation completed, but not entirely as
+/// expected.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EfiWarning {
+    /// The string contained one or more characters that the device could not render and were skipped.
+    UnknownGlyph,
+    /// The handle was closed, but the file was not deleted.
+    DeleteFailure,
+    /// The handle was closed, but the data to the file was not flushed properly.
+    WriteFailure,
+    /// The resulting buffer was too small, and the data was truncated to the buffer size.
+    BufferTooSmall,
+    /// The data has not been updated within the timeframe set by local policy for this type of data.
+    StaleData,
+    /// The resulting buffer contains a UEFI-compliant file system.
+    FileSystem,
+    /// The operation will be processed across a system reset.
+    ResetRequired,
+    /// An unknown EFI warning status code was encountered.
+    Unknown(efi::Status),
+}
+
+impl From<EfiWarning> for efi::Status {
+    fn from(warning: EfiWarning) -> efi::Status {
+        match warning {
+            EfiWarning::UnknownGlyph => efi::Status::WARN_UNKNOWN_GLYPH,
+            EfiWarning::DeleteFailure => efi::Status::WARN_DELETE_FAILURE,
+            EfiWarning::WriteFailure => efi::Status::WARN_WRITE_FAILURE,
+            EfiWarning::BufferTooSmall => efi::Status::WARN_BUFFER_TOO_SMALL,
+            EfiWarning::StaleData => efi::Status::WARN_STALE_DATA,
+            EfiWarning::FileSystem => efi::Status::WARN_FILE_SYSTEM,
+            EfiWarning::ResetRequired => efi::Status::WARN_RESET_REQUIRED,
+            EfiWarning::Unknown(status) => status,
+        }
+    }
+}
+
+/// An EFI status code, classified as success, a warning, or an error.
+///
+/// Unlike [`EfiError::status_to_result`], which treats any status other than `SUCCESS` as an error, this keeps
+/// warnings apart, so they can be reported without failing the operation.
+///
+/// ## Example
+///
+/// ```rust
+/// use patina::error::{EfiError, EfiStatus, EfiWarning};
+/// use r_efi::efi;
+///
+/// let status = EfiStatus::from(efi::Status::WARN_STALE_DATA);
+/// assert_eq!(status, EfiStatus::Warning(EfiWarning::StaleData));
+/// assert!(status.is_warning());
+/// assert_eq!(status.to_result(), Ok(()));
+///
+/// let status = EfiStatus::from(efi::Status::NOT_FOUND);
+/// assert!(status.is_error());
+/// assert_eq!(efi::Status::from(status), efi::Status::NOT_FOUND);
+/// assert_eq!(status.to_result(), Err(EfiError::NotFound));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EfiStatus {
+    /// The operation completed successfully.
+    Success,
+    /// The operation completed with a warning.
+    Warning(EfiWarning),
+    /// The operation failed.
+    Error(EfiError),
+}
+
+impl EfiStatus {
+    /// Returns `true` if the status is `SUCCESS`.
+    pub fn is_success(&self) -> bool {
+        matches!(self, EfiStatus::Success)
+    }
+
+    /// Returns `true` if the status is a warning.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, EfiStatus::Warning(_))
+    }
+
+    /// Returns `true` if the status is an error.
+    pub fn is_error(&self) -> bool {
+        matches!(self, EfiStatus::Error(_))
+    }
+
+    /// Converts the status to a `Result`, treating warnings as success.
+    pub fn to_result(self) -> Result<()> {
+        match self {
+            EfiStatus::Error(err) => Err(err),
+            EfiStatus::Success | EfiStatus::Warning(_) => Ok(()),
+        }
+    }
+}
+
+impl From<efi::Status> for EfiStatus {
+    fn from(status: efi::Status) -> EfiStatus {
+        if status == efi::Status::SUCCESS {
+            EfiStatus::Success
+        } else if status.is_error() {
+            EfiStatus::Error(EfiError::from(status))
+        } else {
+            EfiStatus::Warning(match status {
+                efi::Status::WARN_UNKNOWN_GLYPH => EfiWarning::UnknownGlyph,
+                efi::Status::WARN_DELETE_FAILURE => EfiWarning::DeleteFailure,
+                efi::Status::WARN_WRITE_FAILURE => EfiWarning::WriteFailure,
+                efi::Status::WARN_BUFFER_TOO_SMALL => EfiWarning::BufferTooSmall,
+                efi::Status::WARN_STALE_DATA => EfiWarning::StaleData,
+                efi::Status::WARN_FILE_SYSTEM => EfiWarning::FileSystem,
+                efi::Status::WARN_RESET_REQUIRED => EfiWarning::ResetRequired,
+                _ => EfiWarning::Unknown(status),
+            })
+        }
+    }
+}
+
+impl From<EfiStatus> for efi::Status {
+    fn from(status: EfiStatus) -> efi::Status {
+        match status {
+            EfiStatus::Success => efi::Status::SUCCESS,
+            EfiStatus::Warning(warning) => warning.into(),
+            EfiStatus::Error(err) => err.into(),
+        }
+    }
+}
+
+impl From<EfiError> for EfiStatus {
+    fn from(err: EfiError) -> EfiStatus {
+        EfiStatus::Error(err)
+    }
+}
+
+impl From<EfiWarning> for EfiStatus {
+    fn from(warning: EfiWarning) -> EfiStatus {
+        EfiStatus::Warning(warning)
+    }
+}
+
+impl<T> From<Result<T>> for EfiStatus {
+    fn from(result: Result<T>) -> EfiStatus {
+        match result {
+            Ok(_) => EfiStatus::Success,
+            Err(err) => EfiStatus::Error(err),
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const ERROR_BIT: usize = 1 << (usize::BITS - 1);
+
+    #[test]
+    fn test_status_round_trip() {
+        // Every error code defined by the UEFI specification.
+        let errors = (1..=28).chain(31..=35).chain(100..=106);
+        for code in errors {
+            let status = efi::Status::from_usize(code | ERROR_BIT);
+            let err = EfiError::from(status);
+            assert!(!matches!(err, EfiError::Unknown(_)), "{status:?} is not covered");
+            assert_eq!(efi::Status::from(err), status);
+            assert_eq!(EfiStatus::from(status), EfiStatus::Error(err));
+        }
+
+        for code in 1..=7 {
+            let status = efi::Status::from_usize(code);
+            let warning = match EfiStatus::from(status) {
+                EfiStatus::Warning(warning) => warning,
+                other => panic!("{status:?} classified as {other:?}"),
+            };
+            assert!(!matches!(warning, EfiWarning::Unknown(_)), "{status:?} is not covered");
+            assert_eq!(efi::Status::from(warning), status);
+        }
+
+        assert_eq!(EfiStatus::from(efi::Status::SUCCESS), EfiStatus::Success);
+        assert_eq!(efi::Status::from(EfiStatus::Success), efi::Status::SUCCESS);
+    }
+
+    #[test]
+    fn test_unknown_status() {
+        let unknown_error = efi::Status::from_usize(0x7F | ERROR_BIT);
+        assert_eq!(EfiStatus::from(unknown_error), EfiStatus::Error(EfiError::Unknown(unknown_error)));
+        assert_eq!(efi::Status::from(EfiStatus::from(unknown_error)), unknown_error);
+
+        let unknown_warning = efi::Status::from_usize(0x7F);
+        assert_eq!(EfiStatus::from(unknown_warning), EfiStatus::Warning(EfiWarning::Unknown(unknown_warning)));
+        assert_eq!(efi::Status::from(EfiStatus::from(unknown_warning)), unknown_warning);
+    }
+
+    #[test]
+    fn test_status_classification() {
+        let warning = EfiStatus::from(efi::Status::WARN_RESET_REQUIRED);
+        assert!(warning.is_warning() && !warning.is_error() && !warning.is_success());
+        assert_eq!(warning.to_result(), Ok(()));
+
+        let error = EfiStatus::from(EfiError::DeviceError);
+        assert!(error.is_error() && !error.is_warning() && !error.is_success());
+        assert_eq!(error.to_result(), Err(EfiError::DeviceError));
+
+        assert!(EfiStatus::Success.is_success());
+        assert_eq!(EfiStatus::from(Ok::<u32, EfiError>(5)), EfiStatus::Success);
+        assert_eq!(EfiStatus::from(Err::<(), _>(EfiError::Timeout)), EfiStatus::Error(EfiError::Timeout));
+    }
+
+    #[test]
+    fn test_result_to_status() {
+        assert_eq!(EfiError::result_to_status(Ok(7)), efi::Status::SUCCESS);
+        assert_eq!(EfiError::result_to_status::<()>(Err(EfiError::AccessDenied)), efi::Status::ACCESS_DENIED);
+    }
+}
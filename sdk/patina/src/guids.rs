@@ -140,6 +140,18 @@ pub const PERFORMANCE_PROTOCOL: efi::Guid =
 pub const SMM_COMMUNICATION_PROTOCOL: efi::Guid =
     efi::Guid::from_fields(0xc68ed8e2, 0x9dc6, 0x4cbd, 0x9d, 0x94, &[0xdb, 0x65, 0xac, 0xc5, 0xc3, 0x32]);
 
+/// EFI Variable Write Architectural Protocol GUID as defined in the PI specification.
+///
+/// This protocol is installed once the SetVariable runtime service can write non-volatile variables.
+///
+/// (`6441F818-6362-4E44-B570-7DBA31DD2453`)
+/// ```
+/// # use patina::{Guid, guids::VARIABLE_WRITE_ARCH_PROTOCOL};
+/// # assert_eq!("6441F818-6362-4E44-B570-7DBA31DD2453", format!("{:?}", Guid::from_ref(&VARIABLE_WRITE_ARCH_PROTOCOL)));
+/// ```
+pub const VARIABLE_WRITE_ARCH_PROTOCOL: efi::Guid =
+    efi::Guid::from_fields(0x6441f818, 0x6362, 0x4e44, 0xb5, 0x70, &[0x7d, 0xba, 0x31, 0xdd, 0x24, 0x53]);
+
 /// Zero GUID
 ///
 /// All-zero GUID, used as a marker or placeholder.
@@ -163,3 +175,29 @@ pub const ZERO: efi::Guid = efi::Guid::from_fields(0, 0, 0, 0, 0, &[0, 0, 0, 0,
 /// ```
 pub const HOB_MEMORY_ALLOC_STACK: efi::Guid =
     efi::Guid::from_fields(0x4ed4bf27, 0x4092, 0x42e9, 0x80, 0x7d, &[0x52, 0x7b, 0x1d, 0x00, 0xc9, 0xbd]);
+
+/// Platform Constant HOB GUID
+///
+/// The GUID of a GUID HOB carrying overrides for platform constants declared `hob_overridable`. See
+/// [`platform_constant`](crate::platform_constant) for the format of the HOB data.
+///
+/// (`4B25C4A3-D624-4A0C-993F-B36C093D95BE`)
+/// ```
+/// # use patina::{Guid, guids::PLATFORM_CONSTANT_HOB};
+/// # assert_eq!("4B25C4A3-D624-4A0C-993F-B36C093D95BE", format!("{:?}", Guid::from_ref(&PLATFORM_CONSTANT_HOB)));
+/// ```
+pub const PLATFORM_CONSTANT_HOB: efi::Guid =
+    efi::Guid::from_fields(0x4b25c4a3, 0xd624, 0x4a0c, 0x99, 0x3f, &[0xb3, 0x6c, 0x09, 0x3d, 0x95, 0xbe]);
+
+/// Platform Configuration File GUID
+///
+/// The name of the FFS file whose RAW section holds the configuration payload applied to component configs. See
+/// [`config_payload`](crate::component::config_payload) for the payload format.
+///
+/// (`DE233930-E8F2-4AF3-B558-2E040B4292E6`)
+/// ```
+/// # use patina::{Guid, guids::PLATFORM_CONFIG_FILE};
+/// # assert_eq!("DE233930-E8F2-4AF3-B558-2E040B4292E6", format!("{:?}", Guid::from_ref(&PLATFORM_CONFIG_FILE)));
+/// ```
+pub const PLATFORM_CONFIG_FILE: efi::Guid =
+    efi::Guid::from_fields(0xde233930, 0xe8f2, 0x4af3, 0xb5, 0x58, &[0x2e, 0x04, 0x0b, 0x42, 0x92, 0xe6]);
@@ -0,0 +1,417 @@
+//! Versioned handoff structures.
+//!
+//! Rust-defined state passed between phases, such as from a pre-DXE phase to DXE in a GUID HOB or from DXE to MM
+//! in a communicate buffer, must survive a firmware image where the producer and consumer were built from different
+//! versions of the structure. A handoff structure is the structure's bytes preceded by a [`HandoffHeader`] holding a
+//! magic number, the structure's GUID and version, the payload length, and a CRC32:
+//!
+//! ```rust
+//! use patina::handoff::{self, Compatibility, HandoffData};
+//! use r_efi::efi;
+//!
+//! /// Memory training results handed from pre-DXE to DXE.
+//! #[derive(Default, zerocopy_derive::FromBytes, zerocopy_derive::IntoBytes, zerocopy_derive::Immutable)]
+//! #[repr(C)]
+//! struct MemoryTraining {
+//!     channels: u32,
+//!     speed_mts: u32,
+//!     /// Added in version 2.
+//!     voltage_mv: u32,
+//! }
+//!
+//! patina::assert_handoff_layout!(MemoryTraining, size = 12, channels = 0, speed_mts = 4, voltage_mv = 8);
+//!
+//! impl HandoffData for MemoryTraining {
+//!     const GUID: efi::Guid =
+//!         efi::Guid::from_fields(0x9d6a4f61, 0x5c33, 0x4b0e, 0x8f, 0x27, &[0x61, 0x0a, 0x4c, 0x5e, 0x2b, 0x90]);
+//!     const VERSION: u16 = 2;
+//! }
+//!
+//! let bytes = handoff::serialize(&MemoryTraining { channels: 2, speed_mts: 4800, voltage_mv: 1100 });
+//! let training = handoff::deserialize::<MemoryTraining>(&bytes).unwrap();
+//! assert_eq!(training.compatibility(), Compatibility::Exact);
+//! assert_eq!(training.data().speed_mts, 4800);
+//! ```
+//!
+//! ## Versioning
+//!
+//! A structure evolves only by appending fields, incrementing [`HandoffData::VERSION`] each time. Reordering,
+//! resizing, or removing a field requires a new GUID. [`assert_handoff_layout!`](crate::assert_handoff_layout!)
+//! checks the size and field offsets of a structure at compile time, so that an accidental layout change fails the
+//! build instead of corrupting the handoff.
+//!
+//! When the versions differ, [`deserialize`] downgrades gracefully instead of failing:
+//!
+//! - If the producer is older, the fields it did not know about keep their [`Default`] values.
+//! - If the producer is newer, the fields the consumer does not know about are ignored.
+//!
+//! [`Handoff::compatibility`] reports which happened, so that a consumer can reject a handoff that lacks fields it
+//! cannot do without.
+//!
+//! ## Layout
+//!
+//! All fields are little-endian. The CRC32 covers the header, with the `crc32` field taken as zero, and the payload.
+//! A consumer skips `header_length` bytes to find the payload, so the header can grow in the same way as the
+//! structures it carries.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::{cmp::Ordering, mem};
+
+use r_efi::efi;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+use zerocopy_derive::*;
+
+use crate::{
+    error::EfiError,
+    pi::hob::HobList,
+};
+
+/// The magic number at the start of every handoff structure, `PHND` in ASCII.
+pub const HANDOFF_MAGIC: u32 = u32::from_le_bytes(*b"PHND");
+
+/// Offset of the `crc32` field in [`HandoffHeader`].
+const CRC32_OFFSET: usize = mem::offset_of!(HandoffHeader, crc32);
+
+/// The header preceding the payload of a handoff structure.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+pub struct HandoffHeader {
+    /// Always [`HANDOFF_MAGIC`].
+    pub magic: u32,
+    /// The [`HandoffData::VERSION`] of the producer.
+    pub version: u16,
+    /// The length of the header in bytes, and so the offset of the payload.
+    pub header_length: u16,
+    /// The [`HandoffData::GUID`] of the structure, in its in-memory byte order.
+    pub guid: [u8; 16],
+    /// The length of the payload in bytes.
+    pub length: u32,
+    /// The CRC32 of the header and the payload.
+    pub crc32: u32,
+}
+
+crate::assert_handoff_layout!(
+    HandoffHeader,
+    size = 32,
+    magic = 0,
+    version = 4,
+    header_length = 6,
+    guid = 8,
+    length = 24,
+    crc32 = 28
+);
+
+/// A structure that can be passed between phases as a handoff structure.
+///
+/// The structure must be `#[repr(C)]` without padding, as enforced by [`IntoBytes`], so that its bytes are the same
+/// for every build. Its [`Default`] value supplies the fields missing from a handoff produced by an older version.
+pub trait HandoffData: FromBytes + IntoBytes + Immutable + Default {
+    /// Identifies the structure. It is recorded in the header and is the name of the GUID HOB carrying the structure.
+    const GUID: efi::Guid;
+
+    /// The version of the structure, starting at 1 and incremented each time fields are appended.
+    const VERSION: u16;
+}
+
+/// How the version of a handoff structure's producer relates to the consumer's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    /// The producer and consumer use the same version.
+    Exact,
+    /// The producer is older; the fields it did not know about hold their default values.
+    OlderProducer,
+    /// The producer is newer; the fields the consumer does not know about were ignored.
+    NewerProducer,
+}
+
+/// A structure read from a handoff structure.
+#[derive(Debug, Clone)]
+pub struct Handoff<T> {
+    data: T,
+    producer_version: u16,
+}
+
+impl<T: HandoffData> Handoff<T> {
+    /// The structure.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Consumes the handoff, returning the structure.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    /// The version of the structure the producer was built with.
+    pub fn producer_version(&self) -> u16 {
+        self.producer_version
+    }
+
+    /// How the producer's version relates to this consumer's.
+    pub fn compatibility(&self) -> Compatibility {
+        match self.producer_version.cmp(&T::VERSION) {
+            Ordering::Equal => Compatibility::Exact,
+            Ordering::Less => Compatibility::OlderProducer,
+            Ordering::Greater => Compatibility::NewerProducer,
+        }
+    }
+}
+
+/// Declares the expected size and field offsets of a handoff structure, failing the build if they change.
+///
+/// ```rust
+/// #[repr(C)]
+/// struct BootState {
+///     flags: u32,
+///     count: u32,
+/// }
+///
+/// patina::assert_handoff_layout!(BootState, size = 8, flags = 0, count = 4);
+/// ```
+///
+/// ```rust,compile_fail
+/// #[repr(C)]
+/// struct BootState {
+///     flags: u64,
+///     count: u32,
+/// }
+///
+/// patina::assert_handoff_layout!(BootState, size = 8, flags = 0, count = 4);
+/// ```
+#[macro_export]
+macro_rules! assert_handoff_layout {
+    ($ty:ty, size = $size:expr $(, $field:ident = $offset:expr)* $(,)?) => {
+        const _: () = {
+            assert!(
+                ::core::mem::size_of::<$ty>() == $size,
+                concat!("the size of ", stringify!($ty), " has changed")
+            );
+            $(
+                assert!(
+                    ::core::mem::offset_of!($ty, $field) == $offset,
+                    concat!("the offset of ", stringify!($ty), "::", stringify!($field), " has changed")
+                );
+            )*
+        };
+    };
+}
+
+/// Serializes `data` as a handoff structure.
+pub fn serialize<T: HandoffData>(data: &T) -> Vec<u8> {
+    let payload = data.as_bytes();
+    let header = HandoffHeader {
+        magic: HANDOFF_MAGIC,
+        version: T::VERSION,
+        header_length: mem::size_of::<HandoffHeader>() as u16,
+        guid: *T::GUID.as_bytes(),
+        length: payload.len() as u32,
+        crc32: 0,
+    };
+
+    let mut bytes = header.as_bytes().to_vec();
+    bytes.extend_from_slice(payload);
+    let crc32 = checksum(&bytes);
+    bytes[CRC32_OFFSET..CRC32_OFFSET + 4].copy_from_slice(&crc32.to_le_bytes());
+    bytes
+}
+
+/// Deserializes the handoff structure at the start of `bytes`.
+///
+/// ## Errors
+///
+/// - [`EfiError::BufferTooSmall`]: `bytes` is too short to hold the header.
+/// - [`EfiError::InvalidParameter`]: the magic number or GUID does not match.
+/// - [`EfiError::BadBufferSize`]: the lengths in the header exceed `bytes`.
+/// - [`EfiError::CrcError`]: the CRC32 does not match.
+/// - [`EfiError::IncompatibleError`]: the header length, version, or payload length is not consistent with a structure
+///   that evolved by appending fields.
+pub fn deserialize<T: HandoffData>(bytes: &[u8]) -> Result<Handoff<T>, EfiError> {
+    let (header, _) = HandoffHeader::read_from_prefix(bytes).map_err(|_| EfiError::BufferTooSmall)?;
+    if header.magic != HANDOFF_MAGIC || header.guid != *T::GUID.as_bytes() {
+        Err(EfiError::InvalidParameter)?;
+    }
+
+    let header_length = header.header_length as usize;
+    if header_length < mem::size_of::<HandoffHeader>() || header.version == 0 {
+        Err(EfiError::IncompatibleError)?;
+    }
+    let record = bytes.get(..header_length + header.length as usize).ok_or(EfiError::BadBufferSize)?;
+    if checksum(record) != header.crc32 {
+        Err(EfiError::CrcError)?;
+    }
+
+    // Fields are only ever appended, so an older producer's payload can be no longer than this version's, and a
+    // newer producer's no shorter.
+    let payload = &record[header_length..];
+    let size = mem::size_of::<T>();
+    let consistent = match header.version.cmp(&T::VERSION) {
+        Ordering::Equal => payload.len() == size,
+        Ordering::Less => payload.len() <= size,
+        Ordering::Greater => payload.len() >= size,
+    };
+    if !consistent {
+        log::error!(
+            "Handoff {:?} version {} has a {} byte payload, inconsistent with version {} of {} bytes",
+            crate::Guid::from_ref(&T::GUID),
+            header.version,
+            payload.len(),
+            T::VERSION,
+            size
+        );
+        Err(EfiError::IncompatibleError)?;
+    }
+
+    let mut data = T::default();
+    let known = payload.len().min(size);
+    data.as_mut_bytes()[..known].copy_from_slice(&payload[..known]);
+    Ok(Handoff { data, producer_version: header.version })
+}
+
+/// Deserializes the structure from the first GUID HOB named [`HandoffData::GUID`] in `hob_list`.
+///
+/// Returns `None` if there is no such HOB.
+pub fn find_in_hob_list<T: HandoffData>(hob_list: &HobList) -> Option<Result<Handoff<T>, EfiError>> {
+    hob_list.find_guid_hob(T::GUID).map(deserialize)
+}
+
+/// Computes the CRC32 of a handoff structure, taking the `crc32` field as zero.
+fn checksum(record: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&record[..CRC32_OFFSET]);
+    hasher.update(&[0; 4]);
+    hasher.update(&record[CRC32_OFFSET + 4..]);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const STATE_GUID: efi::Guid =
+        efi::Guid::from_fields(0x1f0b9c2e, 0x6a4d, 0x4e57, 0x9b, 0x1c, &[0x3e, 0x52, 0x7a, 0x08, 0xd4, 0x61]);
+
+    #[derive(Debug, Default, PartialEq, FromBytes, IntoBytes, Immutable)]
+    #[repr(C)]
+    struct StateV1 {
+        flags: u32,
+        count: u32,
+    }
+
+    impl HandoffData for StateV1 {
+        const GUID: efi::Guid = STATE_GUID;
+        const VERSION: u16 = 1;
+    }
+
+    #[derive(Debug, PartialEq, FromBytes, IntoBytes, Immutable)]
+    #[repr(C)]
+    struct StateV2 {
+        flags: u32,
+        count: u32,
+        limit: u64,
+    }
+
+    impl Default for StateV2 {
+        fn default() -> Self {
+            Self { flags: 0, count: 0, limit: 0x1000 }
+        }
+    }
+
+    impl HandoffData for StateV2 {
+        const GUID: efi::Guid = STATE_GUID;
+        const VERSION: u16 = 2;
+    }
+
+    crate::assert_handoff_layout!(StateV2, size = 16, flags = 0, count = 4, limit = 8);
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = serialize(&StateV2 { flags: 1, count: 2, limit: 3 });
+        assert_eq!(bytes.len(), mem::size_of::<HandoffHeader>() + 16);
+        assert_eq!(&bytes[..4], b"PHND");
+
+        let handoff = deserialize::<StateV2>(&bytes).unwrap();
+        assert_eq!(handoff.compatibility(), Compatibility::Exact);
+        assert_eq!(handoff.producer_version(), 2);
+        assert_eq!(handoff.into_inner(), StateV2 { flags: 1, count: 2, limit: 3 });
+    }
+
+    #[test]
+    fn test_older_producer_gets_defaults() {
+        let bytes = serialize(&StateV1 { flags: 1, count: 2 });
+        let handoff = deserialize::<StateV2>(&bytes).unwrap();
+        assert_eq!(handoff.compatibility(), Compatibility::OlderProducer);
+        assert_eq!(handoff.data(), &StateV2 { flags: 1, count: 2, limit: 0x1000 });
+    }
+
+    #[test]
+    fn test_newer_producer_is_truncated() {
+        let bytes = serialize(&StateV2 { flags: 1, count: 2, limit: 3 });
+        let handoff = deserialize::<StateV1>(&bytes).unwrap();
+        assert_eq!(handoff.compatibility(), Compatibility::NewerProducer);
+        assert_eq!(handoff.data(), &StateV1 { flags: 1, count: 2 });
+    }
+
+    #[test]
+    fn test_corruption_is_detected() {
+        let bytes = serialize(&StateV2 { flags: 1, count: 2, limit: 3 });
+
+        let mut corrupt = bytes.clone();
+        corrupt[mem::size_of::<HandoffHeader>()] ^= 1;
+        assert_eq!(deserialize::<StateV2>(&corrupt).err(), Some(EfiError::CrcError));
+
+        // The header is covered too.
+        let mut corrupt = bytes.clone();
+        corrupt[4] = 3;
+        assert_eq!(deserialize::<StateV2>(&corrupt).err(), Some(EfiError::CrcError));
+
+        let mut corrupt = bytes.clone();
+        corrupt[0] = b'X';
+        assert_eq!(deserialize::<StateV2>(&corrupt).err(), Some(EfiError::InvalidParameter));
+
+        assert_eq!(deserialize::<StateV2>(&bytes[..bytes.len() - 1]).err(), Some(EfiError::BadBufferSize));
+        assert_eq!(deserialize::<StateV2>(&bytes[..8]).err(), Some(EfiError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_wrong_guid_is_rejected() {
+        #[derive(Default, FromBytes, IntoBytes, Immutable)]
+        #[repr(C)]
+        struct Other {
+            flags: u32,
+            count: u32,
+        }
+
+        impl HandoffData for Other {
+            const GUID: efi::Guid = efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]);
+            const VERSION: u16 = 1;
+        }
+
+        let bytes = serialize(&Other::default());
+        assert_eq!(deserialize::<StateV1>(&bytes).err(), Some(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn test_inconsistent_version_is_rejected() {
+        // A layout change without a version change.
+        #[derive(Default, FromBytes, IntoBytes, Immutable)]
+        #[repr(C)]
+        struct Changed {
+            flags: u32,
+        }
+
+        impl HandoffData for Changed {
+            const GUID: efi::Guid = STATE_GUID;
+            const VERSION: u16 = 1;
+        }
+
+        let bytes = serialize(&Changed::default());
+        assert_eq!(deserialize::<StateV1>(&bytes).err(), Some(EfiError::IncompatibleError));
+    }
+}
@@ -36,16 +36,22 @@ pub mod macros;
 pub mod arch;
 pub mod base;
 pub mod boot_services;
+pub mod collections;
 pub mod component;
+pub mod compression;
 pub mod driver_binding;
 pub mod efi_types;
 pub mod error;
 pub mod guids;
+pub mod handoff;
 pub mod log;
 pub mod performance;
 pub mod pi;
+pub mod platform_constant;
 pub mod runtime_services;
 pub mod serial;
+pub mod sync;
 pub mod test;
+pub mod time;
 pub mod tpl_mutex;
 pub mod uefi_protocol;
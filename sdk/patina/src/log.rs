@@ -36,7 +36,10 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
+mod early_buffer;
+pub mod raw;
 mod serial_logger;
+pub use early_buffer::EarlyLogBuffer;
 pub use serial_logger::Logger as SerialLogger;
 
 /// Enum to describe the format of the log message.
@@ -57,7 +60,10 @@ impl Format {
         //       allocated on the heap. It is avoided below in favor of directly writing to the target or preparing
         //       the formatting arguments with `format_args!()` to pass to another function that performs the actual
         //       writing.
-        match self {
+        //
+        //       A failing `Display` implementation must not panic here, since the logger is also used by panic and
+        //       exception handlers. The failure is reported with a fixed marker instead.
+        let result = match self {
             Format::Standard if record.level() == log::Level::Trace => {
                 writeln!(
                     target,
@@ -66,34 +72,33 @@ impl Format {
                     record.line().unwrap_or(0),
                     record.args()
                 )
-                .expect("Printing to serial failed");
-            }
-            Format::Standard => {
-                writeln!(target, "{} - {}", record.level(), record.args()).expect("Printing to serial failed");
             }
+            Format::Standard => writeln!(target, "{} - {}", record.level(), record.args()),
             Format::Json => {
                 write!(
                     target,
                     "{}",
                     format_args!("{{\"level\": \"{}\" \"message\": \"{}\"}}\n", record.level(), record.args())
                 )
-                .expect("Printing to serial failed");
             }
             Format::VerboseJson => {
                 write!(
                     target,
                     "{}",
                     format_args!(
-            "{{\"level\": \"{}\", \"target\": \"{}\", \"message\": \"{}\", \"file\": \"{}\", \"line\": \"{}\"}}\n",
-            record.level(),
-            record.target(),
-            record.args(),
-            record.file().unwrap_or("unknown"),
-            record.line().unwrap_or(0)
-          )
+                        "{{\"level\": \"{}\", \"target\": \"{}\", \"message\": \"{}\", \"file\": \"{}\", \"line\": \"{}\"}}\n",
+                        record.level(),
+                        record.target(),
+                        record.args(),
+                        record.file().unwrap_or("unknown"),
+                        record.line().unwrap_or(0)
+                    )
                 )
-                .expect("Printing to serial failed");
             }
+        };
+
+        if result.is_err() {
+            let _ = target.write_str("<log formatting failed>\n");
         }
     }
 }
@@ -0,0 +1,250 @@
+//! An early-boot log accumulator.
+//!
+//! Logging usually starts before any serial port or console has been initialized. [`EarlyLogBuffer`] is a
+//! [`SerialIO`] implementation that stores everything written to it in a fixed-size buffer until a real sink is
+//! attached. Attaching a sink replays the buffered output in order, after which writes are forwarded directly.
+//!
+//! The buffer does not allocate, so it can be used from the very first log message. Output that does not fit in the
+//! buffer is dropped and the number of dropped bytes is reported when the buffer is replayed.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use patina::log::{EarlyLogBuffer, Format, SerialLogger};
+//! use patina::serial::uart::UartNull;
+//!
+//! static EARLY_LOG: EarlyLogBuffer<4096> = EarlyLogBuffer::new();
+//! static LOGGER: SerialLogger<&EarlyLogBuffer<4096>> =
+//!     SerialLogger::new(Format::Standard, &[], log::LevelFilter::Info, &EARLY_LOG);
+//! static UART: UartNull = UartNull {};
+//!
+//! // Later, once the UART has been initialized:
+//! EARLY_LOG.attach(&UART);
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::fmt::Write;
+
+use crate::{collections::ArrayVec, serial::SerialIO};
+
+/// A [`SerialIO`] that buffers output until a sink is attached.
+///
+/// `N` is the size of the buffer in bytes.
+pub struct EarlyLogBuffer<const N: usize> {
+    state: spin::Mutex<State<N>>,
+}
+
+struct State<const N: usize> {
+    buffer: ArrayVec<u8, N>,
+    dropped: usize,
+    sink: Option<&'static dyn SerialIO>,
+}
+
+impl<const N: usize> EarlyLogBuffer<N> {
+    /// Creates an empty buffer with no sink attached.
+    pub const fn new() -> Self {
+        Self { state: spin::Mutex::new(State { buffer: ArrayVec::new(), dropped: 0, sink: None }) }
+    }
+
+    /// Attaches the sink, replaying any buffered output to it.
+    ///
+    /// All subsequent writes are forwarded to the sink. Attaching a new sink replaces the previous one without
+    /// replaying anything.
+    pub fn attach(&self, sink: &'static dyn SerialIO) {
+        let mut state = self.state.lock();
+        if state.sink.is_none() {
+            sink.write(&state.buffer);
+            if state.dropped > 0 {
+                let mut writer = SinkWriter(sink);
+                let _ = writeln!(writer, "WARN - early log buffer full, {} bytes dropped", state.dropped);
+            }
+            state.buffer.clear();
+            state.dropped = 0;
+        }
+        state.sink = Some(sink);
+    }
+
+    /// Returns true if a sink has been attached.
+    pub fn is_attached(&self) -> bool {
+        self.state.lock().sink.is_some()
+    }
+
+    /// Returns the number of bytes currently held in the buffer.
+    pub fn buffered_len(&self) -> usize {
+        self.state.lock().buffer.len()
+    }
+}
+
+impl<const N: usize> Default for EarlyLogBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> SerialIO for EarlyLogBuffer<N> {
+    fn init(&self) {
+        if let Some(sink) = self.state.lock().sink {
+            sink.init();
+        }
+    }
+
+    fn write(&self, buffer: &[u8]) {
+        let mut state = self.state.lock();
+        if let Some(sink) = state.sink {
+            sink.write(buffer);
+            return;
+        }
+
+        let count = state.buffer.extend_from_slice_truncated(buffer);
+        state.dropped += buffer.len() - count;
+    }
+
+    /// Reads from the attached sink.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if no sink has been attached, as nothing could ever be read.
+    fn read(&self) -> u8 {
+        let sink = self.state.lock().sink.expect("Read from an early log buffer with no sink attached");
+        sink.read()
+    }
+
+    fn try_read(&self) -> Option<u8> {
+        let sink = self.state.lock().sink?;
+        sink.try_read()
+    }
+}
+
+impl<const N: usize> SerialIO for &EarlyLogBuffer<N> {
+    fn init(&self) {
+        (*self).init()
+    }
+
+    fn write(&self, buffer: &[u8]) {
+        (*self).write(buffer)
+    }
+
+    fn read(&self) -> u8 {
+        (*self).read()
+    }
+
+    fn try_read(&self) -> Option<u8> {
+        (*self).try_read()
+    }
+}
+
+struct SinkWriter(&'static dyn SerialIO);
+
+impl Write for SinkWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::{boxed::Box, vec::Vec};
+
+    #[derive(Default)]
+    struct CaptureSerial {
+        output: spin::Mutex<Vec<u8>>,
+    }
+
+    impl SerialIO for CaptureSerial {
+        fn init(&self) {}
+
+        fn write(&self, buffer: &[u8]) {
+            self.output.lock().extend_from_slice(buffer);
+        }
+
+        fn read(&self) -> u8 {
+            b'r'
+        }
+
+        fn try_read(&self) -> Option<u8> {
+            Some(b't')
+        }
+    }
+
+    fn leak_capture() -> &'static CaptureSerial {
+        Box::leak(Box::new(CaptureSerial::default()))
+    }
+
+    #[test]
+    fn output_should_be_replayed_when_sink_attached() {
+        let early = EarlyLogBuffer::<64>::new();
+        early.write(b"first ");
+        early.write(b"second ");
+        assert!(!early.is_attached());
+        assert_eq!(early.buffered_len(), 13);
+
+        let sink = leak_capture();
+        early.attach(sink);
+        assert!(early.is_attached());
+        assert_eq!(early.buffered_len(), 0);
+        assert_eq!(sink.output.lock().as_slice(), b"first second ");
+
+        early.write(b"third");
+        assert_eq!(sink.output.lock().as_slice(), b"first second third");
+    }
+
+    #[test]
+    fn overflow_should_be_dropped_and_reported() {
+        let early = EarlyLogBuffer::<8>::new();
+        early.write(b"0123456789");
+        early.write(b"abc");
+        assert_eq!(early.buffered_len(), 8);
+
+        let sink = leak_capture();
+        early.attach(sink);
+        assert_eq!(
+            sink.output.lock().as_slice(),
+            b"01234567WARN - early log buffer full, 5 bytes dropped\n".as_slice()
+        );
+    }
+
+    #[test]
+    fn reattaching_should_not_replay() {
+        let early = EarlyLogBuffer::<16>::new();
+        early.write(b"boot");
+
+        let first = leak_capture();
+        let second = leak_capture();
+        early.attach(first);
+        early.attach(second);
+        early.write(b"!");
+
+        assert_eq!(first.output.lock().as_slice(), b"boot");
+        assert_eq!(second.output.lock().as_slice(), b"!");
+    }
+
+    #[test]
+    fn reads_should_go_to_sink() {
+        let early = EarlyLogBuffer::<16>::new();
+        assert_eq!(early.try_read(), None);
+
+        early.attach(leak_capture());
+        assert_eq!(early.try_read(), Some(b't'));
+        assert_eq!(early.read(), b'r');
+    }
+
+    #[test]
+    fn logger_should_write_through_buffer_reference() {
+        static EARLY: EarlyLogBuffer<128> = EarlyLogBuffer::new();
+        let logger = crate::log::SerialLogger::new(crate::log::Format::Standard, &[], log::LevelFilter::Info, &EARLY);
+        log::Log::log(&logger, &log::Record::builder().level(log::Level::Info).args(format_args!("hello")).build());
+
+        let sink = leak_capture();
+        EARLY.attach(sink);
+        assert_eq!(sink.output.lock().as_slice(), b"INFO - hello\n");
+    }
+}
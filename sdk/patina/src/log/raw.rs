@@ -0,0 +1,203 @@
+//! Panic-free, allocation-free formatting for crash and early-boot code.
+//!
+//! `core::fmt` runs arbitrary `Display` and `Debug` implementations, any of which may panic or fail, and a panic
+//! handler that formats with it can recurse into another failure. [`RawBuffer`] formats only strings and integers
+//! into a fixed buffer on the stack, and cannot panic or allocate: text that does not fit is truncated.
+//!
+//! ```rust
+//! use patina::log::raw::RawBuffer;
+//!
+//! let mut line = RawBuffer::<64>::new();
+//! line.str("RIP: 0x").hex(0x7E8D4A10, 16).str(" exception ").dec(14);
+//! assert_eq!(line.as_str(), "RIP: 0x000000007E8D4A10 exception 14");
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::panic::{Location, PanicInfo};
+
+/// Upper case hexadecimal digits.
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// A fixed-size text buffer that formats strings and integers without panicking or allocating.
+///
+/// Every method appends to the buffer and returns it, so that calls can be chained. Once the buffer is full, further
+/// text is dropped and [`RawBuffer::is_truncated`] returns `true`.
+#[derive(Clone)]
+pub struct RawBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> RawBuffer<N> {
+    /// Creates an empty buffer.
+    pub const fn new() -> Self {
+        Self { bytes: [0; N], len: 0, truncated: false }
+    }
+
+    /// Appends a string. If it does not fit, as much of it as fits is appended, ending on a character boundary.
+    pub fn str(&mut self, text: &str) -> &mut Self {
+        let mut end = text.len().min(N - self.len);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        for &byte in &text.as_bytes()[..end] {
+            self.push(byte);
+        }
+        self.truncated |= end < text.len();
+        self
+    }
+
+    /// Appends a string right-aligned in a field of `width` characters, as `{:>width}` does.
+    pub fn str_right(&mut self, text: &str, width: usize) -> &mut Self {
+        for _ in text.chars().count()..width {
+            self.push(b' ');
+        }
+        self.str(text)
+    }
+
+    /// Appends an unsigned integer in decimal.
+    pub fn dec(&mut self, value: u64) -> &mut Self {
+        // u64::MAX has 20 decimal digits.
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut value = value;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        for &digit in &digits[start..] {
+            self.push(digit);
+        }
+        self
+    }
+
+    /// Appends a signed integer in decimal.
+    pub fn signed(&mut self, value: i64) -> &mut Self {
+        if value < 0 {
+            self.push(b'-');
+        }
+        self.dec(value.unsigned_abs())
+    }
+
+    /// Appends an integer in upper case hexadecimal, without a prefix, zero-padded to `width` digits. Widths above 16
+    /// are treated as 16.
+    pub fn hex(&mut self, value: u64, width: usize) -> &mut Self {
+        let significant = (16 - value.leading_zeros() as usize / 4).max(1);
+        for digit in (0..significant.max(width.min(16))).rev() {
+            self.push(HEX_DIGITS[(value >> (digit * 4)) as usize & 0xF]);
+        }
+        self
+    }
+
+    /// Appends a source location as `<file>:<line>:<column>`.
+    pub fn location(&mut self, location: &Location<'_>) -> &mut Self {
+        self.str(location.file()).str(":").dec(location.line() as u64).str(":").dec(location.column() as u64)
+    }
+
+    /// Appends the location of a panic as `panicked at <file>:<line>:<column>`. The panic message is not included,
+    /// since formatting it runs arbitrary code.
+    pub fn panic_location(&mut self, info: &PanicInfo<'_>) -> &mut Self {
+        self.str("panicked at ");
+        match info.location() {
+            Some(location) => self.location(location),
+            None => self.str("<unknown location>"),
+        }
+    }
+
+    /// The text in the buffer.
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are appended, so the text is always valid UTF-8.
+        core::str::from_utf8(self.as_bytes()).unwrap_or_default()
+    }
+
+    /// The text in the buffer, as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.get(..self.len).unwrap_or_default()
+    }
+
+    /// Whether text was dropped because the buffer was full.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Empties the buffer.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+
+    fn push(&mut self, byte: u8) {
+        match self.bytes.get_mut(self.len) {
+            Some(slot) => {
+                *slot = byte;
+                self.len += 1;
+            }
+            None => self.truncated = true,
+        }
+    }
+}
+
+impl<const N: usize> Default for RawBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integers() {
+        let mut buffer = RawBuffer::<128>::new();
+        buffer.dec(0).str(" ").dec(u64::MAX).str(" ").signed(-42).str(" ").signed(i64::MIN);
+        assert_eq!(buffer.as_str(), "0 18446744073709551615 -42 -9223372036854775808");
+
+        buffer.clear();
+        buffer.hex(0, 0).str(" ").hex(0xABC, 0).str(" ").hex(0xABC, 8).str(" ").hex(u64::MAX, 32);
+        assert_eq!(buffer.as_str(), "0 ABC 00000ABC FFFFFFFFFFFFFFFF");
+    }
+
+    #[test]
+    fn test_alignment() {
+        let mut buffer = RawBuffer::<32>::new();
+        buffer.str_right("RIP", 4).str(":").str_right("RFLAGS", 4);
+        assert_eq!(buffer.as_str(), " RIP:RFLAGS");
+    }
+
+    #[test]
+    fn test_truncation() {
+        let mut buffer = RawBuffer::<8>::new();
+        buffer.str("0x").hex(0x1234, 4);
+        assert!(!buffer.is_truncated());
+        buffer.str("é€");
+        assert_eq!(buffer.as_str(), "0x1234é");
+        assert!(buffer.is_truncated());
+
+        buffer.dec(12345).hex(0xFF, 2).str_right("x", 4);
+        assert_eq!(buffer.as_bytes().len(), 8);
+
+        buffer.clear();
+        assert_eq!(buffer.as_str(), "");
+        assert!(!buffer.is_truncated());
+    }
+
+    #[test]
+    fn test_location() {
+        let location = Location::caller();
+        let mut buffer = RawBuffer::<256>::new();
+        buffer.location(location);
+        assert_eq!(buffer.as_str(), std::format!("{}:{}:{}", location.file(), location.line(), location.column()));
+    }
+}
@@ -70,6 +70,26 @@ macro_rules! uefi_pages_to_size {
     };
 }
 
+/// Creates an `efi::Guid` from its registry format string at compile time.
+///
+/// An invalid GUID string fails the build.
+///
+/// # Example
+///
+/// ```rust
+/// use patina::guid;
+/// use r_efi::efi;
+///
+/// const EFI_LOADED_IMAGE_PROTOCOL_GUID: efi::Guid = guid!("5B1B31A1-9562-11D2-8E3F-00A0C969723B");
+/// assert_eq!(EFI_LOADED_IMAGE_PROTOCOL_GUID, efi::protocols::loaded_image::PROTOCOL_GUID);
+/// ```
+#[macro_export]
+macro_rules! guid {
+    ($guid:literal) => {
+        const { $crate::BinaryGuid::from_string($guid).into_inner() }
+    };
+}
+
 /// Macro definitions for working with PCI devices.
 pub mod pci {
     /// Constructs a PCI library address from the given bus, device, function, and register values.
@@ -69,6 +69,12 @@ pub mod guid {
     /// GUID for LZMA parallel compressed sections.
     pub const LZMA_PARALLEL_SECTION: efi::Guid =
         efi::Guid::from_fields(0xBD9921EA, 0xED91, 0x404A, 0x8B, 0x2F, &[0xB4, 0xD7, 0x24, 0x74, 0x7C, 0x8C]);
+    /// GUID for sections whose contents are signed (`EFI_FIRMWARE_CONTENTS_SIGNED_GUID`).
+    ///
+    /// The GUID-specific header of the section is a `WIN_CERTIFICATE_UEFI_GUID` holding the signature of the section
+    /// contents.
+    pub const FIRMWARE_CONTENTS_SIGNED: efi::Guid =
+        efi::Guid::from_fields(0x0F9D89E8, 0x9259, 0x4F76, 0xA5, 0xAF, &[0x0C, 0x89, 0xE3, 0x40, 0x23, 0xDF]);
     /// GUID for Tiano decompression sections.
     pub const TIANO_DECOMPRESS_SECTION: efi::Guid =
         efi::Guid::from_fields(0xA31280AD, 0x481E, 0x41B6, 0x95, 0xE8, &[0x12, 0x7F, 0x4C, 0x98, 0x47, 0x79]);
@@ -172,6 +172,11 @@ pub mod header {
         // Guid-specific header fields.
     }
 
+    /// GUID-defined section attribute: the section content must be processed before it can be used.
+    pub const GUIDED_SECTION_PROCESSING_REQUIRED: u16 = 0x01;
+    /// GUID-defined section attribute: the section carries an authentication status.
+    pub const GUIDED_SECTION_AUTH_STATUS_VALID: u16 = 0x02;
+
     /// EFI_VERSION_SECTION per PI spec 1.8A 3.2.5.15
     #[repr(C)]
     #[derive(Debug, Clone, Copy)]
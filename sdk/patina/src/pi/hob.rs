@@ -948,6 +948,33 @@ impl<'a> HobList<'a> {
         self.0.iter()
     }
 
+    /// Iterates over the HOBs of type `hob_type`, such as [`RESOURCE_DESCRIPTOR`] or [`FV`].
+    pub fn iter_type(&self, hob_type: u16) -> impl Iterator<Item = &Hob<'a>> {
+        self.0.iter().filter(move |hob| hob.header().r#type == hob_type)
+    }
+
+    /// Iterates over the data of the GUID extension HOBs named `guid`.
+    ///
+    /// # Example(s)
+    ///
+    /// ```no_run
+    /// use patina::pi::hob::{HobList, MEMORY_TYPE_INFO_HOB_GUID};
+    ///
+    /// fn example(hob_list: &HobList) {
+    ///     for data in hob_list.guid_hobs(MEMORY_TYPE_INFO_HOB_GUID) {
+    ///         // ... parse the HOB data
+    ///     }
+    /// }
+    /// ```
+    pub fn guid_hobs(&self, guid: r_efi::efi::Guid) -> impl Iterator<Item = &'a [u8]> {
+        self.0.iter().filter_map(move |hob| hob.guid_data(&guid))
+    }
+
+    /// Returns the data of the first GUID extension HOB named `guid`.
+    pub fn find_guid_hob(&self, guid: r_efi::efi::Guid) -> Option<&'a [u8]> {
+        self.guid_hobs(guid).next()
+    }
+
     /// Returns a mutable pointer to the underlying data.
     ///
     /// # Example(s)
@@ -1408,7 +1435,26 @@ impl fmt::Debug for HobList<'_> {
     }
 }
 
-impl Hob<'_> {
+impl<'a> Hob<'a> {
+    /// Returns the data of this HOB if it is a GUID extension HOB named `guid`.
+    ///
+    /// # Example(s)
+    ///
+    /// ```no_run
+    /// use patina::pi::hob::{Hob, MEMORY_TYPE_INFO_HOB_GUID};
+    ///
+    /// fn example(hob: &Hob) {
+    ///     // Walk the HOB list in place, starting at `hob`.
+    ///     let memory_type_info = hob.into_iter().find_map(|hob| hob.guid_data(&MEMORY_TYPE_INFO_HOB_GUID));
+    /// }
+    /// ```
+    pub fn guid_data(&self, guid: &r_efi::efi::Guid) -> Option<&'a [u8]> {
+        match self {
+            Hob::GuidHob(hob, data) if hob.name == *guid => Some(data),
+            _ => None,
+        }
+    }
+
     /// Returns the HOB header for this Hand-Off Block
     pub fn header(&self) -> header::Hob {
         match self {
@@ -1484,7 +1530,7 @@ impl<'a> Iterator for HobIter<'a> {
                 CPU => Hob::Cpu((self.hob_ptr as *const Cpu).as_ref().expect(NOT_NULL)),
                 UEFI_CAPSULE => Hob::Capsule((self.hob_ptr as *const Capsule).as_ref().expect(NOT_NULL)),
                 RESOURCE_DESCRIPTOR2 => {
-                    Hob::ResourceDescriptorV2((self.hob_ptr as *const &ResourceDescriptorV2).as_ref().expect(NOT_NULL))
+                    Hob::ResourceDescriptorV2((self.hob_ptr as *const ResourceDescriptorV2).as_ref().expect(NOT_NULL))
                 }
                 END_OF_HOB_LIST => return None,
                 hob_type => Hob::Misc(hob_type),
@@ -2075,6 +2121,61 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hob_iterator_raw_memory() {
+        let (mut guid_hob, _) = gen_guid_hob();
+        let name = guid_hob.name;
+        let resource_v2 = gen_resource_descriptor_v2();
+        let end_of_hob_list = gen_end_of_hoblist();
+
+        // Lay the HOBs out contiguously, as firmware does, in an 8-byte aligned buffer.
+        let mut buffer = [0_u64; 32];
+        let bytes: &mut [u8] = u64_buffer_as_bytes(&mut buffer);
+        let mut offset = 0;
+        let mut put = |data: &[u8]| {
+            bytes[offset..offset + data.len()].copy_from_slice(data);
+            offset += data.len();
+        };
+        // SAFETY: Test code - viewing plain HOB structures as bytes.
+        let as_bytes = |hob: *const u8, size: usize| unsafe { from_raw_parts(hob, size) };
+        put(as_bytes(ptr::from_ref(&guid_hob).cast(), size_of::<hob::GuidHob>()));
+        put(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        put(as_bytes(ptr::from_ref(&resource_v2).cast(), size_of::<hob::ResourceDescriptorV2>()));
+        guid_hob.name = r_efi::efi::Guid::from_fields(9, 9, 9, 9, 9, &[9; 6]);
+        guid_hob.header.length = size_of::<hob::GuidHob>() as u16;
+        put(as_bytes(ptr::from_ref(&guid_hob).cast(), size_of::<hob::GuidHob>()));
+        put(as_bytes(ptr::from_ref(&end_of_hob_list).cast(), size_of::<hob::PhaseHandoffInformationTable>()));
+
+        // SAFETY: Test code - the buffer starts with a GUID HOB.
+        let first = Hob::GuidHob(unsafe { (buffer.as_ptr() as *const hob::GuidHob).as_ref().unwrap() }, &[]);
+        let hobs: Vec<Hob> = first.into_iter().collect();
+        assert_eq!(hobs.len(), 3);
+        assert_eq!(hobs[0].guid_data(&name), Some(&[1, 2, 3, 4, 5, 6, 7, 8][..]));
+        assert_eq!(hobs[2].guid_data(&name), None);
+        match hobs[1] {
+            Hob::ResourceDescriptorV2(hob) => {
+                assert_eq!(hob.attributes, 8);
+                assert_eq!(hob.v1.physical_start, resource_v2.v1.physical_start);
+            }
+            ref hob => panic!("Expected a resource descriptor v2 HOB, got {hob:?}"),
+        }
+
+        let mut hob_list = HobList::new();
+        for hob in hobs {
+            hob_list.push(hob);
+        }
+        assert_eq!(hob_list.iter_type(hob::RESOURCE_DESCRIPTOR2).count(), 1);
+        assert_eq!(hob_list.iter_type(hob::GUID_EXTENSION).count(), 2);
+        assert_eq!(hob_list.guid_hobs(name).count(), 1);
+        assert_eq!(hob_list.find_guid_hob(guid_hob.name), Some(&[][..]));
+        assert_eq!(hob_list.find_guid_hob(r_efi::efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6])), None);
+    }
+
+    fn u64_buffer_as_bytes(buffer: &mut [u64]) -> &mut [u8] {
+        // SAFETY: Test code - any u64 buffer is a valid byte buffer of eight times the length.
+        unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr().cast(), buffer.len() * 8) }
+    }
+
     #[test]
     fn test_relocate_hobs() {
         // generate some test hobs
@@ -20,6 +20,17 @@ use r_efi::efi;
 pub const PROTOCOL_GUID: efi::Guid =
     efi::Guid::from_fields(0xA46423E3, 0x4617, 0x49f1, 0xB9, 0xFF, &[0xD1, 0xBF, 0xA9, 0x11, 0x58, 0x39]);
 
+/// Authentication status bit: the platform has overridden the authentication result of the file.
+pub const AUTH_STATUS_PLATFORM_OVERRIDE: u32 = 0x01;
+/// Authentication status bit: the file is signed.
+pub const AUTH_STATUS_IMAGE_SIGNED: u32 = 0x02;
+/// Authentication status bit: the signature of the file has not been verified.
+pub const AUTH_STATUS_NOT_TESTED: u32 = 0x04;
+/// Authentication status bit: the signature of the file failed verification.
+pub const AUTH_STATUS_TEST_FAILED: u32 = 0x08;
+/// Mask of all authentication status bits.
+pub const AUTH_STATUS_ALL: u32 = 0x0f;
+
 /// The EFI_SECURITY_ARCH_PROTOCOL (SAP) is used to abstract platform-specific
 /// policy from the DXE core response to an attempt to use a file that returns a
 /// given status for the authentication check from the section extraction protocol.
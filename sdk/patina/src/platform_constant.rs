@@ -0,0 +1,460 @@
+//! Typed platform constants.
+//!
+//! Platform crates have many tuning knobs, such as timeouts, buffer sizes, and feature switches. Declaring them with
+//! [`platform_constant!`](crate::platform_constant!) instead of a plain `const` gives each one a typed default that
+//! can be overridden without editing the source, and records it in a manifest that can be logged at boot:
+//!
+//! ```rust
+//! patina::platform_constant! {
+//!     /// Seconds before the watchdog resets the system.
+//!     pub static WATCHDOG_TIMEOUT_SECONDS: u32 = 300;
+//!     /// Whether the boot splash is shown. Can be overridden from PEI.
+//!     pub static SHOW_BOOT_SPLASH: bool = true, hob_overridable;
+//! }
+//!
+//! assert_eq!(WATCHDOG_TIMEOUT_SECONDS.get(), 300);
+//! ```
+//!
+//! ## Overrides
+//!
+//! The effective value of a constant is fixed the first time it is read, from the first of these that applies:
+//!
+//! 1. A HOB override, for constants declared `hob_overridable`. See [`apply_hob_overrides`].
+//! 2. A build override: the `PATINA_CONST_<NAME>` environment variable at build time, such as
+//!    `PATINA_CONST_WATCHDOG_TIMEOUT_SECONDS=600`. Integers may be decimal or `0x` prefixed hexadecimal, and booleans
+//!    are `true`, `false`, `1`, or `0`. A build override that does not parse is logged and ignored.
+//! 3. The default given in the declaration.
+//!
+//! HOB overrides must be applied before the constant is first read. The DXE core applies them as soon as the HOB
+//! list is available, so only code running before that point, such as the logger setup, should avoid reading
+//! `hob_overridable` constants.
+//!
+//! ## HOB Format
+//!
+//! A GUID HOB named [`PLATFORM_CONSTANT_HOB`](crate::guids::PLATFORM_CONSTANT_HOB) carries a sequence of records,
+//! each a little-endian `u16` name length, a little-endian `u16` value length, the constant's name in UTF-8, and the
+//! value in little-endian. Booleans are a single byte. String constants cannot be overridden from a HOB.
+//!
+//! ## Manifest
+//!
+//! Every declared constant is registered in a manifest, available from [`constants`]. [`log_manifest`] logs the
+//! effective value and source of each, so that support logs record exactly how a platform was configured.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{
+    error::EfiError,
+    guids,
+    pi::hob::HobList,
+};
+
+#[doc(hidden)]
+pub use linkme;
+
+/// Declares one or more typed platform constants.
+///
+/// Each declaration is a `static` with a type implementing [`ConstantValue`] and a default value, optionally
+/// followed by `, hob_overridable` to accept overrides from the platform constant HOB. See the
+/// [module documentation](crate::platform_constant) for how overrides are applied.
+#[macro_export]
+macro_rules! platform_constant {
+    () => {};
+    (
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident : $ty:ty = $default:expr $(, $hob:ident)? ;
+        $($rest:tt)*
+    ) => {
+        $(#[$attr])*
+        $vis static $name: $crate::platform_constant::PlatformConstant<$ty> =
+            $crate::platform_constant::PlatformConstant::new(
+                stringify!($name),
+                module_path!(),
+                $default,
+                option_env!(concat!("PATINA_CONST_", stringify!($name))),
+                $crate::platform_constant!(@hob_overridable $($hob)?),
+            );
+
+        const _: () = {
+            #[$crate::platform_constant::linkme::distributed_slice($crate::platform_constant::PLATFORM_CONSTANTS)]
+            #[linkme(crate = $crate::platform_constant::linkme)]
+            static REGISTRATION: Option<&'static dyn $crate::platform_constant::ConstantInfo> = Some(&$name);
+        };
+
+        $crate::platform_constant!($($rest)*);
+    };
+    (@hob_overridable) => { false };
+    (@hob_overridable hob_overridable) => { true };
+}
+
+/// Where the effective value of a platform constant came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstantSource {
+    /// The default given in the declaration.
+    Default,
+    /// The `PATINA_CONST_<NAME>` environment variable at build time.
+    Build,
+    /// The platform constant HOB.
+    Hob,
+}
+
+impl fmt::Display for ConstantSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::Build => f.write_str("build"),
+            Self::Hob => f.write_str("HOB"),
+        }
+    }
+}
+
+/// A type that can be used as a platform constant.
+pub trait ConstantValue: Copy + fmt::Display + Send + Sync + 'static {
+    /// Parses a build override, returning `None` if it is not a valid value.
+    fn parse(text: &'static str) -> Option<Self>;
+
+    /// Decodes a HOB override, returning `None` if it is not a valid value or the type cannot be overridden from a
+    /// HOB.
+    fn from_le_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_constant_value_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl ConstantValue for $ty {
+                fn parse(text: &'static str) -> Option<Self> {
+                    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                        Some(hex) => <$ty>::from_str_radix(hex, 16).ok(),
+                        None => text.parse().ok(),
+                    }
+                }
+
+                fn from_le_bytes(bytes: &[u8]) -> Option<Self> {
+                    Some(<$ty>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_constant_value_for_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl ConstantValue for bool {
+    fn parse(text: &'static str) -> Option<Self> {
+        match text {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl ConstantValue for &'static str {
+    fn parse(text: &'static str) -> Option<Self> {
+        Some(text)
+    }
+
+    fn from_le_bytes(_bytes: &[u8]) -> Option<Self> {
+        None
+    }
+}
+
+/// A platform constant declared with [`platform_constant!`](crate::platform_constant!).
+pub struct PlatformConstant<T: ConstantValue> {
+    name: &'static str,
+    module: &'static str,
+    default: T,
+    build_override: Option<&'static str>,
+    hob_overridable: bool,
+    value: spin::Once<(T, ConstantSource)>,
+}
+
+impl<T: ConstantValue> PlatformConstant<T> {
+    /// Creates a platform constant. Use [`platform_constant!`](crate::platform_constant!) instead, which also
+    /// registers the constant in the manifest.
+    #[doc(hidden)]
+    pub const fn new(
+        name: &'static str,
+        module: &'static str,
+        default: T,
+        build_override: Option<&'static str>,
+        hob_overridable: bool,
+    ) -> Self {
+        Self { name, module, default, build_override, hob_overridable, value: spin::Once::new() }
+    }
+
+    /// Returns the effective value of the constant, fixing it if this is the first read.
+    pub fn get(&self) -> T {
+        self.resolve().0
+    }
+
+    /// Returns the default given in the declaration.
+    pub const fn default_value(&self) -> T {
+        self.default
+    }
+
+    fn resolve(&self) -> (T, ConstantSource) {
+        *self.value.call_once(|| match self.build_override {
+            Some(text) => match T::parse(text) {
+                Some(value) => (value, ConstantSource::Build),
+                None => {
+                    log::error!("Ignoring invalid build override {:?} for platform constant {}", text, self.name);
+                    (self.default, ConstantSource::Default)
+                }
+            },
+            None => (self.default, ConstantSource::Default),
+        })
+    }
+}
+
+/// Type-erased access to a platform constant, as listed in the manifest.
+pub trait ConstantInfo: Sync {
+    /// Returns the name of the constant.
+    fn name(&self) -> &'static str;
+
+    /// Returns the path of the module declaring the constant.
+    fn module(&self) -> &'static str;
+
+    /// Returns where the effective value came from, fixing the value if it has not been read yet.
+    fn source(&self) -> ConstantSource;
+
+    /// Returns `true` if the constant accepts overrides from the platform constant HOB.
+    fn hob_overridable(&self) -> bool;
+
+    /// Writes the effective value, fixing it if it has not been read yet.
+    fn write_value(&self, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// Overrides the value with a value from the platform constant HOB.
+    ///
+    /// ## Errors
+    ///
+    /// - [`EfiError::AccessDenied`] if the constant is not `hob_overridable`.
+    /// - [`EfiError::InvalidParameter`] if `bytes` is not a valid value of the constant's type.
+    /// - [`EfiError::AlreadyStarted`] if the constant was read before the override was applied.
+    fn apply_override(&self, bytes: &[u8]) -> Result<(), EfiError>;
+}
+
+impl<T: ConstantValue> ConstantInfo for PlatformConstant<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn module(&self) -> &'static str {
+        self.module
+    }
+
+    fn source(&self) -> ConstantSource {
+        self.resolve().1
+    }
+
+    fn hob_overridable(&self) -> bool {
+        self.hob_overridable
+    }
+
+    fn write_value(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        write!(out, "{}", self.get())
+    }
+
+    fn apply_override(&self, bytes: &[u8]) -> Result<(), EfiError> {
+        if !self.hob_overridable {
+            return Err(EfiError::AccessDenied);
+        }
+        let value = T::from_le_bytes(bytes).ok_or(EfiError::InvalidParameter)?;
+        match self.value.call_once(|| (value, ConstantSource::Hob)) {
+            (_, ConstantSource::Hob) => Ok(()),
+            _ => Err(EfiError::AlreadyStarted),
+        }
+    }
+}
+
+/// The manifest of platform constants, populated by [`platform_constant!`](crate::platform_constant!).
+///
+/// The entries are optional so that the SDK can register an empty one; the linker requires the slice to have at
+/// least one entry.
+#[linkme::distributed_slice]
+pub static PLATFORM_CONSTANTS: [Option<&'static dyn ConstantInfo>];
+
+#[linkme::distributed_slice(PLATFORM_CONSTANTS)]
+static EMPTY_REGISTRATION: Option<&'static dyn ConstantInfo> = None;
+
+/// Returns every platform constant declared in the image.
+pub fn constants() -> impl Iterator<Item = &'static dyn ConstantInfo> {
+    PLATFORM_CONSTANTS.iter().flatten().copied()
+}
+
+/// Applies the overrides in the data of a platform constant HOB, returning the number applied.
+///
+/// Overrides for unknown constants and overrides that cannot be applied are logged and skipped.
+///
+/// ## Errors
+///
+/// Returns [`EfiError::InvalidParameter`] if `data` is not a well formed sequence of records. Records before the
+/// malformed one are still applied.
+pub fn apply_hob_overrides(mut data: &[u8]) -> Result<usize, EfiError> {
+    let mut applied = 0;
+    while !data.is_empty() {
+        let (name, value, rest) = split_record(data).ok_or(EfiError::InvalidParameter)?;
+        data = rest;
+
+        let Some(constant) = constants().find(|constant| constant.name() == name) else {
+            log::warn!("Platform constant HOB overrides unknown constant {}", name);
+            continue;
+        };
+        match constant.apply_override(value) {
+            Ok(()) => applied += 1,
+            Err(err) => log::error!("Failed to apply HOB override for platform constant {}: {:?}", name, err),
+        }
+    }
+    Ok(applied)
+}
+
+/// Applies the overrides of every platform constant HOB in `hob_list`.
+pub fn apply_hob_list_overrides(hob_list: &HobList) {
+    for data in hob_list.guid_hobs(guids::PLATFORM_CONSTANT_HOB) {
+        if let Err(err) = apply_hob_overrides(data) {
+            log::error!("Malformed platform constant HOB: {:?}", err);
+        }
+    }
+}
+
+/// Splits the first record off `data`, returning its name, value, and the remaining data.
+fn split_record(data: &[u8]) -> Option<(&str, &[u8], &[u8])> {
+    let name_len = u16::from_le_bytes(data.get(0..2)?.try_into().ok()?) as usize;
+    let value_len = u16::from_le_bytes(data.get(2..4)?.try_into().ok()?) as usize;
+    let name = core::str::from_utf8(data.get(4..4 + name_len)?).ok()?;
+    let value = data.get(4 + name_len..4 + name_len + value_len)?;
+    Some((name, value, &data[4 + name_len + value_len..]))
+}
+
+/// Logs the effective value and source of every platform constant, sorted by module and name.
+pub fn log_manifest() {
+    let mut constants: Vec<_> = constants().collect();
+    constants.sort_by_key(|constant| (constant.module(), constant.name()));
+
+    log::info!("Platform constants:");
+    for constant in constants {
+        log::info!("  {}::{} = {} ({})", constant.module(), constant.name(), Value(constant), constant.source());
+    }
+}
+
+/// Displays the effective value of a platform constant.
+struct Value<'a>(&'a dyn ConstantInfo);
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write_value(f)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use std::{format, string::String, vec};
+
+    crate::platform_constant! {
+        /// A constant that is never overridden.
+        static TEST_TIMEOUT: u32 = 300;
+        static TEST_HOB_VALUE: u16 = 1, hob_overridable;
+        static TEST_HOB_FLAG: bool = false, hob_overridable;
+        static TEST_READ_EARLY: u8 = 7, hob_overridable;
+        static TEST_NOT_OVERRIDABLE: u64 = 5;
+    }
+
+    fn record(name: &str, value: &[u8]) -> Vec<u8> {
+        let mut record = vec![];
+        record.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        record.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        record.extend_from_slice(name.as_bytes());
+        record.extend_from_slice(value);
+        record
+    }
+
+    fn manifest_entry(name: &str) -> &'static dyn ConstantInfo {
+        constants().find(|constant| constant.name() == name).unwrap()
+    }
+
+    #[test]
+    fn test_default_value() {
+        assert_eq!(TEST_TIMEOUT.get(), 300);
+        assert_eq!(TEST_TIMEOUT.default_value(), 300);
+
+        let constant = manifest_entry("TEST_TIMEOUT");
+        assert_eq!(constant.module(), module_path!());
+        assert_eq!(constant.source(), ConstantSource::Default);
+        assert!(!constant.hob_overridable());
+        assert_eq!(format!("{}", Value(constant)), "300");
+    }
+
+    #[test]
+    fn test_build_override() {
+        let constant = PlatformConstant::<u32>::new("BUILD", "test", 1, Some("0x20"), false);
+        assert_eq!(constant.get(), 0x20);
+        assert_eq!(constant.source(), ConstantSource::Build);
+
+        let constant = PlatformConstant::<bool>::new("BUILD", "test", false, Some("1"), false);
+        assert!(constant.get());
+
+        let constant = PlatformConstant::<&str>::new("BUILD", "test", "a", Some("b"), false);
+        assert_eq!(constant.get(), "b");
+
+        // An invalid override falls back to the default.
+        let constant = PlatformConstant::<u8>::new("BUILD", "test", 3, Some("256"), false);
+        assert_eq!(constant.get(), 3);
+        assert_eq!(constant.source(), ConstantSource::Default);
+    }
+
+    #[test]
+    fn test_hob_overrides() {
+        let mut data = record("TEST_HOB_VALUE", &0x1234u16.to_le_bytes());
+        data.extend(record("TEST_HOB_FLAG", &[1]));
+        data.extend(record("UNKNOWN", &[1]));
+        data.extend(record("TEST_NOT_OVERRIDABLE", &9u64.to_le_bytes()));
+
+        assert_eq!(apply_hob_overrides(&data), Ok(2));
+        assert_eq!(TEST_HOB_VALUE.get(), 0x1234);
+        assert!(TEST_HOB_FLAG.get());
+        assert_eq!(manifest_entry("TEST_HOB_VALUE").source(), ConstantSource::Hob);
+        assert_eq!(TEST_NOT_OVERRIDABLE.get(), 5);
+    }
+
+    #[test]
+    fn test_hob_override_after_read() {
+        assert_eq!(TEST_READ_EARLY.get(), 7);
+        assert_eq!(manifest_entry("TEST_READ_EARLY").apply_override(&[8]), Err(EfiError::AlreadyStarted));
+        assert_eq!(manifest_entry("TEST_READ_EARLY").apply_override(&[8, 0]), Err(EfiError::InvalidParameter));
+        assert_eq!(TEST_READ_EARLY.get(), 7);
+    }
+
+    #[test]
+    fn test_malformed_hob() {
+        let mut data = record("TEST_NOT_OVERRIDABLE", &[]);
+        data.truncate(data.len() - 1);
+        assert_eq!(apply_hob_overrides(&data), Err(EfiError::InvalidParameter));
+        assert_eq!(apply_hob_overrides(&[1]), Err(EfiError::InvalidParameter));
+        assert_eq!(apply_hob_overrides(&[]), Ok(0));
+    }
+
+    #[test]
+    fn test_manifest_lists_constants() {
+        let names: Vec<String> = constants().map(|constant| String::from(constant.name())).collect();
+        for name in ["TEST_TIMEOUT", "TEST_HOB_VALUE", "TEST_HOB_FLAG", "TEST_READ_EARLY", "TEST_NOT_OVERRIDABLE"] {
+            assert!(names.iter().any(|entry| entry == name));
+        }
+        log_manifest();
+    }
+}
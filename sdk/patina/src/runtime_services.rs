@@ -14,6 +14,8 @@ extern crate alloc;
 
 /// Variable-services-specific structs and utilities
 pub mod variable_services;
+/// Safe, typed access to UEFI variables
+pub mod variables;
 
 #[cfg(any(test, feature = "mockall"))]
 use mockall::automock;
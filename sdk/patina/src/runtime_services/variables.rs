@@ -0,0 +1,472 @@
+//! Safe, typed access to UEFI variables.
+//!
+//! The functions in this module take variable names as `&str` and variable data as
+//! [zerocopy](https://docs.rs/zerocopy) types, and handle the `EFI_BUFFER_TOO_SMALL` retry loop of `GetVariable()` and
+//! `GetNextVariableName()` internally, so callers never size buffers or touch raw pointers.
+//!
+//! ```ignore
+//! use patina::runtime_services::variables::{self, VariableAttributes};
+//!
+//! let attributes = VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS;
+//! variables::set_variable(&runtime_services, "BootCount", &VENDOR_GUID, attributes, &1u32)?;
+//! let (count, _) = variables::get_variable::<u32>(&runtime_services, "BootCount", &VENDOR_GUID)?;
+//!
+//! for variable in variables::variable_names(&runtime_services) {
+//!     let variable = variable?;
+//!     log::info!("{} {}", Guid::from_ref(&variable.namespace), variable.name);
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{string::String, vec, vec::Vec};
+use core::{
+    fmt,
+    ops::{BitAnd, BitOr, BitOrAssign},
+};
+
+use r_efi::efi;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+use super::{RuntimeServices, variable_services::GetVariableStatus};
+use crate::error::EfiError;
+
+/// The attributes of a UEFI variable.
+///
+/// <https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#getvariable>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+#[repr(transparent)]
+pub struct VariableAttributes(u32);
+
+impl VariableAttributes {
+    /// The variable is stored in non-volatile storage and persists across resets.
+    pub const NON_VOLATILE: Self = Self(efi::VARIABLE_NON_VOLATILE);
+    /// The variable is accessible while boot services are available.
+    pub const BOOTSERVICE_ACCESS: Self = Self(efi::VARIABLE_BOOTSERVICE_ACCESS);
+    /// The variable is accessible after ExitBootServices(). Requires [`Self::BOOTSERVICE_ACCESS`].
+    pub const RUNTIME_ACCESS: Self = Self(efi::VARIABLE_RUNTIME_ACCESS);
+    /// The variable is a hardware error record.
+    pub const HARDWARE_ERROR_RECORD: Self = Self(efi::VARIABLE_HARDWARE_ERROR_RECORD);
+    /// Deprecated by the UEFI specification. Writes to the variable require a count-based authentication descriptor.
+    pub const AUTHENTICATED_WRITE_ACCESS: Self = Self(efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS);
+    /// Writes to the variable require a time-based authentication descriptor.
+    pub const TIME_BASED_AUTHENTICATED_WRITE_ACCESS: Self = Self(efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS);
+    /// A write appends the data to the variable instead of replacing it. Only valid when setting a variable.
+    pub const APPEND_WRITE: Self = Self(efi::VARIABLE_APPEND_WRITE);
+    /// Writes to the variable require an enhanced authentication descriptor.
+    pub const ENHANCED_AUTHENTICATED_ACCESS: Self = Self(efi::VARIABLE_ENHANCED_AUTHENTICATED_ACCESS);
+
+    // Attribute names in display order.
+    const NAMES: [(Self, &'static str); 8] = [
+        (Self::NON_VOLATILE, "NV"),
+        (Self::BOOTSERVICE_ACCESS, "BS"),
+        (Self::RUNTIME_ACCESS, "RT"),
+        (Self::HARDWARE_ERROR_RECORD, "HR"),
+        (Self::AUTHENTICATED_WRITE_ACCESS, "AW"),
+        (Self::TIME_BASED_AUTHENTICATED_WRITE_ACCESS, "AT"),
+        (Self::APPEND_WRITE, "AP"),
+        (Self::ENHANCED_AUTHENTICATED_ACCESS, "EA"),
+    ];
+
+    /// Returns attributes with no bits set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the raw attribute bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Converts raw attribute bits, returning `None` if any bit is not defined by the UEFI specification.
+    ///
+    /// Use `VariableAttributes::from` to keep undefined bits instead.
+    pub const fn from_bits(bits: u32) -> Option<Self> {
+        let mut all = 0;
+        let mut i = 0;
+        while i < Self::NAMES.len() {
+            all |= Self::NAMES[i].0.0;
+            i += 1;
+        }
+        if bits & !all == 0 { Some(Self(bits)) } else { None }
+    }
+
+    /// Returns true if every attribute in `other` is set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets the attributes in `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clears the attributes in `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl From<u32> for VariableAttributes {
+    /// Converts raw attribute bits, keeping any bits not defined by the UEFI specification.
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<VariableAttributes> for u32 {
+    fn from(attributes: VariableAttributes) -> Self {
+        attributes.0
+    }
+}
+
+impl BitOr for VariableAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for VariableAttributes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
+
+impl BitAnd for VariableAttributes {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl fmt::Display for VariableAttributes {
+    /// Displays the attribute abbreviations used by the UEFI shell separated by `|`, followed by any undefined bits in
+    /// hexadecimal.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        let mut remaining = self.0;
+        for (attribute, name) in Self::NAMES {
+            if self.contains(attribute) {
+                write!(f, "{separator}{name}")?;
+                separator = "|";
+                remaining &= !attribute.0;
+            }
+        }
+        if remaining != 0 || separator.is_empty() {
+            write!(f, "{separator}{remaining:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The name and vendor GUID of a UEFI variable, as returned by [`variable_names`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableName {
+    /// The name of the variable. Characters that are not valid UTF-16 are replaced with `U+FFFD`.
+    pub name: String,
+    /// The vendor GUID of the variable.
+    pub namespace: efi::Guid,
+}
+
+/// Reads a variable whose data is a `T`.
+///
+/// Returns [`EfiError::NotFound`] if the variable does not exist, and [`EfiError::BadBufferSize`] if its size is not
+/// the size of `T`.
+pub fn get_variable<T: FromBytes>(
+    runtime_services: &impl RuntimeServices,
+    name: &str,
+    namespace: &efi::Guid,
+) -> Result<(T, VariableAttributes), EfiError> {
+    let (data, attributes) = get_variable_bytes(runtime_services, name, namespace)?;
+    let value = T::read_from_bytes(&data).map_err(|_| EfiError::BadBufferSize)?;
+    Ok((value, attributes))
+}
+
+/// Reads the data of a variable of any size.
+///
+/// Returns [`EfiError::NotFound`] if the variable does not exist.
+pub fn get_variable_bytes(
+    runtime_services: &impl RuntimeServices,
+    name: &str,
+    namespace: &efi::Guid,
+) -> Result<(Vec<u8>, VariableAttributes), EfiError> {
+    let mut name = ucs2_name(name)?;
+    let mut data = Vec::new();
+    loop {
+        // SAFETY: `name` is a null-terminated UCS-2 string and `data` is a valid buffer of its length.
+        let status = unsafe {
+            runtime_services.get_variable_unchecked(
+                &mut name,
+                namespace,
+                if data.is_empty() { None } else { Some(&mut data) },
+            )
+        };
+        match status {
+            GetVariableStatus::Success { data_size, attributes } => {
+                data.truncate(data_size);
+                return Ok((data, attributes.into()));
+            }
+            // The variable may have grown since its size was read, so retry with the new size.
+            GetVariableStatus::BufferTooSmall { data_size, .. } if data_size > data.len() => data.resize(data_size, 0),
+            GetVariableStatus::BufferTooSmall { .. } => return Err(EfiError::BufferTooSmall),
+            GetVariableStatus::Error(status) => return Err(status.into()),
+        }
+    }
+}
+
+/// Writes a variable whose data is `value`.
+///
+/// Writing an empty value without [`VariableAttributes::APPEND_WRITE`] deletes the variable.
+pub fn set_variable<T: IntoBytes + Immutable + ?Sized>(
+    runtime_services: &impl RuntimeServices,
+    name: &str,
+    namespace: &efi::Guid,
+    attributes: VariableAttributes,
+    value: &T,
+) -> Result<(), EfiError> {
+    let mut name = ucs2_name(name)?;
+    // SAFETY: `name` is a null-terminated UCS-2 string.
+    unsafe { runtime_services.set_variable_unchecked(&mut name, namespace, attributes.bits(), value.as_bytes()) }
+        .map_err(Into::into)
+}
+
+/// Deletes a variable.
+///
+/// Returns [`EfiError::NotFound`] if the variable does not exist.
+pub fn delete_variable(
+    runtime_services: &impl RuntimeServices,
+    name: &str,
+    namespace: &efi::Guid,
+) -> Result<(), EfiError> {
+    set_variable(runtime_services, name, namespace, VariableAttributes::empty(), &[0u8; 0])
+}
+
+/// Returns an iterator over the names of all variables.
+///
+/// The iterator ends after the last variable, or after the first error.
+pub fn variable_names<R: RuntimeServices>(runtime_services: &R) -> VariableNames<'_, R> {
+    VariableNames { runtime_services, name: vec![0], namespace: efi::Guid::from_bytes(&[0; 16]), finished: false }
+}
+
+/// An iterator over the names of all variables, created by [`variable_names`].
+pub struct VariableNames<'a, R: RuntimeServices> {
+    runtime_services: &'a R,
+    name: Vec<u16>,
+    namespace: efi::Guid,
+    finished: bool,
+}
+
+impl<R: RuntimeServices> Iterator for VariableNames<'_, R> {
+    type Item = Result<VariableName, EfiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let (mut next_name, mut next_namespace) = (Vec::new(), self.namespace);
+        // SAFETY: `name` is the null-terminated name of the previous variable, or an empty name to start from the
+        // first one.
+        let status = unsafe {
+            self.runtime_services.get_next_variable_name_unchecked(
+                &self.name,
+                &self.namespace,
+                &mut next_name,
+                &mut next_namespace,
+            )
+        };
+        match status {
+            Ok(()) => {
+                let len = next_name.iter().position(|&c| c == 0).unwrap_or(next_name.len());
+                next_name.truncate(len + 1);
+                next_name.resize(len + 1, 0);
+                let name = String::from_utf16_lossy(&next_name[..len]);
+                self.name = next_name;
+                self.namespace = next_namespace;
+                Some(Ok(VariableName { name, namespace: next_namespace }))
+            }
+            Err(status) => {
+                self.finished = true;
+                if status == efi::Status::NOT_FOUND { None } else { Some(Err(status.into())) }
+            }
+        }
+    }
+}
+
+/// Converts a variable name to a null-terminated UCS-2 string.
+fn ucs2_name(name: &str) -> Result<Vec<u16>, EfiError> {
+    if name.is_empty() || name.contains('\0') {
+        return Err(EfiError::InvalidParameter);
+    }
+    Ok(name.encode_utf16().chain([0]).collect())
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::runtime_services::variable_services::VariableInfo;
+    use core::cell::RefCell;
+    use std::string::ToString;
+
+    const NAMESPACE: efi::Guid =
+        efi::Guid::from_fields(0x5c3a1e2d, 0x7b4f, 0x4a96, 0x8d, 0x0e, &[0x21, 0x6f, 0x93, 0xa4, 0xc7, 0x58]);
+
+    /// A stored variable: its name without the null terminator, namespace, attributes, and data.
+    type Variable = (Vec<u16>, efi::Guid, u32, Vec<u8>);
+
+    /// Variable services backed by a list of variables, in the order they were created.
+    #[derive(Default)]
+    struct MemoryVariables {
+        variables: RefCell<Vec<Variable>>,
+    }
+
+    impl MemoryVariables {
+        fn position(&self, name: &[u16], namespace: &efi::Guid) -> Option<usize> {
+            let name = &name[..name.iter().position(|&c| c == 0).unwrap()];
+            self.variables.borrow().iter().position(|(n, g, _, _)| n == name && g == namespace)
+        }
+    }
+
+    impl RuntimeServices for MemoryVariables {
+        unsafe fn set_variable_unchecked(
+            &self,
+            name: &mut [u16],
+            namespace: &efi::Guid,
+            attributes: u32,
+            data: &[u8],
+        ) -> Result<(), efi::Status> {
+            let position = self.position(name, namespace);
+            let mut variables = self.variables.borrow_mut();
+            match position {
+                Some(i) if data.is_empty() => {
+                    variables.remove(i);
+                }
+                Some(i) => variables[i].3 = data.to_vec(),
+                None if data.is_empty() => return Err(efi::Status::NOT_FOUND),
+                None => {
+                    let name = name[..name.iter().position(|&c| c == 0).unwrap()].to_vec();
+                    variables.push((name, *namespace, attributes, data.to_vec()));
+                }
+            }
+            Ok(())
+        }
+
+        unsafe fn get_variable_unchecked(
+            &self,
+            name: &mut [u16],
+            namespace: &efi::Guid,
+            data: Option<&mut [u8]>,
+        ) -> GetVariableStatus {
+            let Some(i) = self.position(name, namespace) else {
+                return GetVariableStatus::Error(efi::Status::NOT_FOUND);
+            };
+            let (_, _, attributes, value) = &self.variables.borrow()[i];
+            match data {
+                Some(data) if data.len() >= value.len() => {
+                    data[..value.len()].copy_from_slice(value);
+                    GetVariableStatus::Success { data_size: value.len(), attributes: *attributes }
+                }
+                _ => GetVariableStatus::BufferTooSmall { data_size: value.len(), attributes: *attributes },
+            }
+        }
+
+        unsafe fn get_next_variable_name_unchecked(
+            &self,
+            prev_name: &[u16],
+            prev_namespace: &efi::Guid,
+            next_name: &mut Vec<u16>,
+            next_namespace: &mut efi::Guid,
+        ) -> Result<(), efi::Status> {
+            let next = if prev_name[0] == 0 {
+                0
+            } else {
+                self.position(prev_name, prev_namespace).ok_or(efi::Status::INVALID_PARAMETER)? + 1
+            };
+            let variables = self.variables.borrow();
+            let (name, namespace, _, _) = variables.get(next).ok_or(efi::Status::NOT_FOUND)?;
+            // Like firmware, leave the buffer larger than the name.
+            *next_name = name.iter().copied().chain([0, 0x4141]).collect();
+            *next_namespace = *namespace;
+            Ok(())
+        }
+
+        fn query_variable_info(&self, _attributes: u32) -> Result<VariableInfo, efi::Status> {
+            Err(efi::Status::UNSUPPORTED)
+        }
+    }
+
+    #[test]
+    fn test_typed_get_and_set() {
+        let rs = MemoryVariables::default();
+        let attributes = VariableAttributes::NON_VOLATILE | VariableAttributes::BOOTSERVICE_ACCESS;
+        set_variable(&rs, "BootCount", &NAMESPACE, attributes, &0x1234_5678u32).unwrap();
+        assert_eq!(get_variable::<u32>(&rs, "BootCount", &NAMESPACE), Ok((0x1234_5678, attributes)));
+        assert_eq!(get_variable::<[u8; 4]>(&rs, "BootCount", &NAMESPACE).unwrap().0, [0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(get_variable::<u64>(&rs, "BootCount", &NAMESPACE), Err(EfiError::BadBufferSize));
+        assert_eq!(get_variable::<u32>(&rs, "Missing", &NAMESPACE), Err(EfiError::NotFound));
+
+        set_variable(&rs, "Description", &NAMESPACE, attributes, b"A longer variable value".as_slice()).unwrap();
+        assert_eq!(get_variable_bytes(&rs, "Description", &NAMESPACE).unwrap().0, b"A longer variable value");
+
+        delete_variable(&rs, "BootCount", &NAMESPACE).unwrap();
+        assert_eq!(get_variable::<u32>(&rs, "BootCount", &NAMESPACE), Err(EfiError::NotFound));
+        assert_eq!(delete_variable(&rs, "BootCount", &NAMESPACE), Err(EfiError::NotFound));
+
+        assert_eq!(set_variable(&rs, "", &NAMESPACE, attributes, &0u8), Err(EfiError::InvalidParameter));
+        assert_eq!(get_variable_bytes(&rs, "Bad\0Name", &NAMESPACE), Err(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn test_variable_names() {
+        let rs = MemoryVariables::default();
+        assert_eq!(variable_names(&rs).count(), 0);
+
+        let other = efi::Guid::from_bytes(&[0x11; 16]);
+        set_variable(&rs, "Boot0000", &NAMESPACE, VariableAttributes::BOOTSERVICE_ACCESS, &1u8).unwrap();
+        set_variable(&rs, "Lang", &other, VariableAttributes::BOOTSERVICE_ACCESS, &2u8).unwrap();
+        let names: Result<Vec<_>, _> = variable_names(&rs).collect();
+        assert_eq!(
+            names.unwrap(),
+            [
+                VariableName { name: "Boot0000".into(), namespace: NAMESPACE },
+                VariableName { name: "Lang".into(), namespace: other },
+            ]
+        );
+
+        // The iterator ends after an error.
+        let mut names = variable_names(&rs);
+        names.next();
+        names.name = vec![b'X' as u16, 0];
+        assert_eq!(names.next(), Some(Err(EfiError::InvalidParameter)));
+        assert_eq!(names.next(), None);
+    }
+
+    #[test]
+    fn test_variable_attributes() {
+        let attributes = VariableAttributes::NON_VOLATILE
+            | VariableAttributes::BOOTSERVICE_ACCESS
+            | VariableAttributes::RUNTIME_ACCESS;
+        assert_eq!(attributes.bits(), 0x7);
+        assert_eq!(attributes.to_string(), "NV|BS|RT");
+        assert!(attributes.contains(VariableAttributes::NON_VOLATILE | VariableAttributes::RUNTIME_ACCESS));
+        assert_eq!(VariableAttributes::from_bits(0x7), Some(attributes));
+        assert_eq!(VariableAttributes::from_bits(0x1000), None);
+        assert_eq!(VariableAttributes::from(0x1001).to_string(), "NV|0x1000");
+        assert_eq!(VariableAttributes::empty().to_string(), "0x0");
+
+        let mut attributes = attributes;
+        attributes.remove(VariableAttributes::RUNTIME_ACCESS);
+        attributes.insert(VariableAttributes::APPEND_WRITE);
+        assert_eq!(u32::from(attributes), 0x43);
+        assert_eq!(attributes & VariableAttributes::APPEND_WRITE, VariableAttributes::APPEND_WRITE);
+    }
+}
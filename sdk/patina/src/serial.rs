@@ -19,6 +19,7 @@ pub trait SerialIO: Sync {
     fn try_read(&self) -> Option<u8>;
 }
 
+pub mod ipmi;
 pub mod uart;
 
 #[cfg(feature = "std")]
@@ -0,0 +1,637 @@
+//! [SerialIO](crate::serial::SerialIO) over the serial-over-LAN (SOL) session of a BMC.
+//!
+//! [`IpmiSol`] carries serial data in IPMI messages through a KCS ([`Kcs`]) or BT ([`Bt`]) system interface, so that
+//! boards without a UART connected to the BMC still get logging and debugger connectivity through the BMC. IPMI
+//! defines SOL between the BMC and the remote console, but not how the host feeds it without a UART, so the commands
+//! that carry the data are BMC specific and given with [`SolCommands`].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use patina::serial::ipmi::{IoRegisters, IpmiSol, Kcs, SolCommands};
+//!
+//! static SOL: IpmiSol<Kcs<IoRegisters>> = IpmiSol::new(
+//!     Kcs::new(IoRegisters { base: 0xCA2 }),
+//!     SolCommands { net_fn: 0x2E, write: 0x50, read: 0x51, max_write: 32 },
+//! );
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The largest IPMI message, in bytes, that the system interfaces exchange.
+const MAX_MESSAGE_LENGTH: usize = 256;
+
+/// The number of times a register is polled before a transaction times out.
+const POLL_LIMIT: usize = 1_000_000;
+
+/// The completion code of a BMC that cannot accept the request yet.
+const COMPLETION_CODE_BUSY: u8 = 0xC0;
+
+/// The number of times a write is retried while the BMC is busy.
+const WRITE_RETRIES: usize = 3;
+
+/// Errors of an IPMI transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpmiError {
+    /// The interface did not become ready in time.
+    Timeout,
+    /// The interface reported an error or was in an unexpected state.
+    InterfaceError,
+    /// The response does not match the request.
+    InvalidResponse,
+    /// The request or the response does not fit in a message or the response buffer.
+    BufferTooSmall,
+    /// The BMC completed the command with a completion code other than success.
+    CompletionCode(u8),
+}
+
+/// Access to the byte-wide registers of a BMC system interface.
+pub trait RegisterAccess: Sync {
+    /// Read the register at `index`.
+    fn read(&self, index: usize) -> u8;
+    /// Write `value` to the register at `index`.
+    fn write(&self, index: usize, value: u8);
+}
+
+/// System interface registers mapped in memory.
+#[derive(Debug)]
+pub struct MmioRegisters {
+    /// The address of the first register.
+    pub base: usize,
+    /// The number of bytes between consecutive registers.
+    pub reg_stride: usize,
+}
+
+impl RegisterAccess for MmioRegisters {
+    fn read(&self, index: usize) -> u8 {
+        // SAFETY: The base address and stride are provided during MmioRegisters construction and are assumed to be
+        // valid for MMIO access.
+        unsafe { ((self.base + index * self.reg_stride) as *const u8).read_volatile() }
+    }
+
+    fn write(&self, index: usize, value: u8) {
+        // SAFETY: The base address and stride are provided during MmioRegisters construction and are assumed to be
+        // valid for MMIO access.
+        unsafe { ((self.base + index * self.reg_stride) as *mut u8).write_volatile(value) }
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(any(feature = "doc", all(target_os = "uefi", target_arch = "x86_64")))] {
+        use x86_64::instructions::port::Port;
+
+        /// System interface registers at consecutive I/O ports.
+        #[derive(Debug)]
+        pub struct IoRegisters {
+            /// The I/O port of the first register.
+            pub base: u16,
+        }
+
+        impl RegisterAccess for IoRegisters {
+            fn read(&self, index: usize) -> u8 {
+                // SAFETY: The base port is provided during IoRegisters construction and is assumed to be valid for
+                // I/O port access.
+                unsafe { Port::<u8>::new(self.base + index as u16).read() }
+            }
+
+            fn write(&self, index: usize, value: u8) {
+                // SAFETY: The base port is provided during IoRegisters construction and is assumed to be valid for
+                // I/O port access.
+                unsafe { Port::<u8>::new(self.base + index as u16).write(value) }
+            }
+        }
+    }
+}
+
+/// A system interface to the BMC.
+pub trait IpmiInterface: Sync {
+    /// Reset the interface to a known state.
+    fn init(&self) {}
+
+    /// Send a request to the BMC and wait for its response.
+    ///
+    /// Returns the length of the response data written to `response`, which excludes the completion code.
+    fn send_command(&self, net_fn: u8, command: u8, request: &[u8], response: &mut [u8]) -> Result<usize, IpmiError>;
+}
+
+/// Polls `condition` until it returns a value.
+fn poll<T>(mut condition: impl FnMut() -> Option<T>) -> Result<T, IpmiError> {
+    for _ in 0..POLL_LIMIT {
+        if let Some(value) = condition() {
+            return Ok(value);
+        }
+        core::hint::spin_loop();
+    }
+    Err(IpmiError::Timeout)
+}
+
+/// Checks a response against its request and copies its data, given the response fields following the network
+/// function and command.
+fn complete(
+    (net_fn, command): (u8, u8),
+    (response_net_fn, response_command): (u8, u8),
+    fields: &[u8],
+    response: &mut [u8],
+) -> Result<usize, IpmiError> {
+    // The response network function is the odd function following the request's, with the LUN in the low bits.
+    if response_net_fn >> 2 != net_fn | 1 || response_command != command {
+        return Err(IpmiError::InvalidResponse);
+    }
+    let (&completion_code, data) = fields.split_first().ok_or(IpmiError::InvalidResponse)?;
+    if completion_code != 0 {
+        return Err(IpmiError::CompletionCode(completion_code));
+    }
+    response.get_mut(..data.len()).ok_or(IpmiError::BufferTooSmall)?.copy_from_slice(data);
+    Ok(data.len())
+}
+
+mod kcs {
+    pub const DATA: usize = 0;
+    pub const STATUS: usize = 1;
+    pub const COMMAND: usize = 1;
+
+    pub const STATUS_OBF: u8 = 1 << 0;
+    pub const STATUS_IBF: u8 = 1 << 1;
+    pub const STATE_SHIFT: u8 = 6;
+
+    pub const STATE_IDLE: u8 = 0;
+    pub const STATE_READ: u8 = 1;
+    pub const STATE_WRITE: u8 = 2;
+
+    pub const GET_STATUS_ABORT: u8 = 0x60;
+    pub const WRITE_START: u8 = 0x61;
+    pub const WRITE_END: u8 = 0x62;
+    pub const READ: u8 = 0x68;
+}
+
+/// The Keyboard Controller Style (KCS) system interface.
+///
+/// The data register is at index 0 and the status and command register at index 1.
+#[derive(Debug)]
+pub struct Kcs<R> {
+    registers: R,
+}
+
+impl<R: RegisterAccess> Kcs<R> {
+    /// Create a KCS interface on the given registers.
+    pub const fn new(registers: R) -> Self {
+        Self { registers }
+    }
+
+    /// Waits for the BMC to take the last byte written, returning the status.
+    fn wait_input_empty(&self) -> Result<u8, IpmiError> {
+        poll(|| Some(self.registers.read(kcs::STATUS)).filter(|status| status & kcs::STATUS_IBF == 0))
+    }
+
+    fn wait_output_full(&self) -> Result<(), IpmiError> {
+        poll(|| (self.registers.read(kcs::STATUS) & kcs::STATUS_OBF != 0).then_some(()))
+    }
+
+    fn clear_output(&self) {
+        if self.registers.read(kcs::STATUS) & kcs::STATUS_OBF != 0 {
+            self.registers.read(kcs::DATA);
+        }
+    }
+
+    /// Waits for the BMC to take the last byte written and checks that it is still in the write state.
+    fn wait_write_state(&self) -> Result<(), IpmiError> {
+        let status = self.wait_input_empty()?;
+        if status >> kcs::STATE_SHIFT != kcs::STATE_WRITE {
+            return Err(IpmiError::InterfaceError);
+        }
+        self.clear_output();
+        Ok(())
+    }
+
+    fn write_message(&self, message: &[u8]) -> Result<(), IpmiError> {
+        let (last, rest) = message.split_last().ok_or(IpmiError::InvalidResponse)?;
+        self.wait_input_empty()?;
+        self.clear_output();
+        self.registers.write(kcs::COMMAND, kcs::WRITE_START);
+        self.wait_write_state()?;
+        for &byte in rest {
+            self.registers.write(kcs::DATA, byte);
+            self.wait_write_state()?;
+        }
+        self.registers.write(kcs::COMMAND, kcs::WRITE_END);
+        self.wait_write_state()?;
+        self.registers.write(kcs::DATA, *last);
+        Ok(())
+    }
+
+    fn read_message(&self, message: &mut [u8]) -> Result<usize, IpmiError> {
+        let mut length = 0;
+        loop {
+            let status = self.wait_input_empty()?;
+            match status >> kcs::STATE_SHIFT {
+                kcs::STATE_READ => {
+                    self.wait_output_full()?;
+                    let byte = self.registers.read(kcs::DATA);
+                    *message.get_mut(length).ok_or(IpmiError::BufferTooSmall)? = byte;
+                    length += 1;
+                    self.registers.write(kcs::DATA, kcs::READ);
+                }
+                kcs::STATE_IDLE => {
+                    // The BMC ends the read phase with a dummy byte.
+                    self.wait_output_full()?;
+                    self.registers.read(kcs::DATA);
+                    return Ok(length);
+                }
+                _ => return Err(IpmiError::InterfaceError),
+            }
+        }
+    }
+
+    /// Returns the interface to the idle state after an error. Failures are ignored, the next transaction reports them.
+    fn abort(&self) {
+        let _ = self.wait_input_empty();
+        self.registers.write(kcs::COMMAND, kcs::GET_STATUS_ABORT);
+        let _ = self.wait_input_empty();
+        self.clear_output();
+        self.registers.write(kcs::DATA, 0);
+        if let Ok(status) = self.wait_input_empty()
+            && status >> kcs::STATE_SHIFT == kcs::STATE_READ
+            && self.wait_output_full().is_ok()
+        {
+            // Discard the error status code.
+            self.registers.read(kcs::DATA);
+            self.registers.write(kcs::DATA, kcs::READ);
+            let _ = self.wait_input_empty();
+            self.clear_output();
+        }
+    }
+
+    fn transact(&self, request: &[u8], response: &mut [u8]) -> Result<usize, IpmiError> {
+        self.write_message(request)?;
+        self.read_message(response)
+    }
+}
+
+impl<R: RegisterAccess> IpmiInterface for Kcs<R> {
+    fn init(&self) {
+        self.clear_output();
+    }
+
+    fn send_command(&self, net_fn: u8, command: u8, request: &[u8], response: &mut [u8]) -> Result<usize, IpmiError> {
+        let mut message = [0; MAX_MESSAGE_LENGTH];
+        let data = message.get_mut(2..2 + request.len()).ok_or(IpmiError::BufferTooSmall)?;
+        data.copy_from_slice(request);
+        message[0] = net_fn << 2;
+        message[1] = command;
+
+        let mut response_message = [0; MAX_MESSAGE_LENGTH];
+        let length =
+            self.transact(&message[..2 + request.len()], &mut response_message).inspect_err(|_| self.abort())?;
+        let [response_net_fn, response_command, fields @ ..] = &response_message[..length] else {
+            return Err(IpmiError::InvalidResponse);
+        };
+        complete((net_fn, command), (*response_net_fn, *response_command), fields, response)
+    }
+}
+
+mod bt {
+    pub const CONTROL: usize = 0;
+    pub const BUFFER: usize = 1;
+
+    pub const CLR_WR_PTR: u8 = 1 << 0;
+    pub const CLR_RD_PTR: u8 = 1 << 1;
+    pub const H2B_ATN: u8 = 1 << 2;
+    pub const B2H_ATN: u8 = 1 << 3;
+    pub const H_BUSY: u8 = 1 << 6;
+    pub const B_BUSY: u8 = 1 << 7;
+}
+
+/// The Block Transfer (BT) system interface.
+///
+/// The control register is at index 0, the buffer register at index 1, and the interrupt mask register at index 2.
+#[derive(Debug)]
+pub struct Bt<R> {
+    registers: R,
+    sequence: AtomicU8,
+}
+
+impl<R: RegisterAccess> Bt<R> {
+    /// Create a BT interface on the given registers.
+    pub const fn new(registers: R) -> Self {
+        Self { registers, sequence: AtomicU8::new(0) }
+    }
+
+    fn control(&self) -> u8 {
+        self.registers.read(bt::CONTROL)
+    }
+
+    /// Clears H_BUSY, which toggles when written.
+    fn clear_host_busy(&self) {
+        if self.control() & bt::H_BUSY != 0 {
+            self.registers.write(bt::CONTROL, bt::H_BUSY);
+        }
+    }
+}
+
+impl<R: RegisterAccess> IpmiInterface for Bt<R> {
+    fn init(&self) {
+        self.clear_host_busy();
+    }
+
+    fn send_command(&self, net_fn: u8, command: u8, request: &[u8], response: &mut [u8]) -> Result<usize, IpmiError> {
+        // The length byte counts the network function, sequence, command and data.
+        let length = u8::try_from(3 + request.len()).map_err(|_| IpmiError::BufferTooSmall)?;
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+
+        poll(|| (self.control() & (bt::H2B_ATN | bt::B_BUSY) == 0).then_some(()))?;
+        self.registers.write(bt::CONTROL, bt::CLR_WR_PTR);
+        for &byte in [length, net_fn << 2, sequence, command].iter().chain(request) {
+            self.registers.write(bt::BUFFER, byte);
+        }
+        self.registers.write(bt::CONTROL, bt::H2B_ATN);
+
+        poll(|| (self.control() & bt::B2H_ATN != 0).then_some(()))?;
+        self.registers.write(bt::CONTROL, bt::H_BUSY);
+        self.registers.write(bt::CONTROL, bt::B2H_ATN);
+        self.registers.write(bt::CONTROL, bt::CLR_RD_PTR);
+        let mut message = [0; MAX_MESSAGE_LENGTH];
+        let length = self.registers.read(bt::BUFFER) as usize;
+        for byte in &mut message[..length] {
+            *byte = self.registers.read(bt::BUFFER);
+        }
+        self.clear_host_busy();
+
+        let [response_net_fn, response_sequence, response_command, fields @ ..] = &message[..length] else {
+            return Err(IpmiError::InvalidResponse);
+        };
+        if *response_sequence != sequence {
+            return Err(IpmiError::InvalidResponse);
+        }
+        complete((net_fn, command), (*response_net_fn, *response_command), fields, response)
+    }
+}
+
+/// The BMC specific commands that carry serial-over-LAN data through the system interface.
+#[derive(Debug, Clone, Copy)]
+pub struct SolCommands {
+    /// The network function of the commands.
+    pub net_fn: u8,
+    /// The command whose request data is sent to the SOL session.
+    pub write: u8,
+    /// The command whose response data is the data received from the SOL session, empty if there is none.
+    pub read: u8,
+    /// The largest number of bytes the BMC accepts in one write.
+    pub max_write: usize,
+}
+
+/// Data received from the BMC and not yet read.
+struct Received {
+    buffer: [u8; MAX_MESSAGE_LENGTH],
+    start: usize,
+    end: usize,
+}
+
+/// A [SerialIO](crate::serial::SerialIO) that tunnels through the BMC's serial-over-LAN session.
+pub struct IpmiSol<I> {
+    interface: I,
+    commands: SolCommands,
+    // Also serializes the transactions on the interface.
+    received: spin::Mutex<Received>,
+}
+
+impl<I: IpmiInterface> IpmiSol<I> {
+    /// Create a SOL serial port on the given system interface.
+    pub const fn new(interface: I, commands: SolCommands) -> Self {
+        Self {
+            interface,
+            commands,
+            received: spin::Mutex::new(Received { buffer: [0; MAX_MESSAGE_LENGTH], start: 0, end: 0 }),
+        }
+    }
+}
+
+impl<I: IpmiInterface> super::SerialIO for IpmiSol<I> {
+    fn init(&self) {
+        let _lock = self.received.lock();
+        self.interface.init();
+    }
+
+    fn write(&self, buffer: &[u8]) {
+        let _lock = self.received.lock();
+        for chunk in buffer.chunks(self.commands.max_write.max(1)) {
+            for _ in 0..WRITE_RETRIES {
+                // Serial output is lossy, data the BMC does not take is dropped.
+                match self.interface.send_command(self.commands.net_fn, self.commands.write, chunk, &mut []) {
+                    Err(IpmiError::CompletionCode(COMPLETION_CODE_BUSY)) => continue,
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    fn read(&self) -> u8 {
+        loop {
+            if let Some(byte) = self.try_read() {
+                return byte;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn try_read(&self) -> Option<u8> {
+        let mut received = self.received.lock();
+        if received.start == received.end {
+            let length = self
+                .interface
+                .send_command(self.commands.net_fn, self.commands.read, &[], &mut received.buffer)
+                .ok()?;
+            received.start = 0;
+            received.end = length;
+        }
+        if received.start == received.end {
+            return None;
+        }
+        received.start += 1;
+        Some(received.buffer[received.start - 1])
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::serial::SerialIO;
+    use std::{vec, vec::Vec};
+
+    const SOL: SolCommands = SolCommands { net_fn: 0x2E, write: 0x50, read: 0x51, max_write: 4 };
+
+    /// A BMC that echoes SOL writes back to reads and answers Get Device ID.
+    #[derive(Default)]
+    struct Bmc {
+        sol: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Bmc {
+        /// Handles a request, returning the completion code and response data.
+        fn handle(&mut self, net_fn: u8, command: u8, data: &[u8]) -> (u8, Vec<u8>) {
+            match (net_fn, command) {
+                (0x06, 0x01) => (0, vec![0x20, 0x81, 0x02, 0x10, 0x02]),
+                (0x2E, 0x50) => {
+                    self.writes += 1;
+                    self.sol.extend_from_slice(data);
+                    (0, vec![])
+                }
+                (0x2E, 0x51) => (0, self.sol.drain(..self.sol.len().min(3)).collect()),
+                _ => (0xC1, vec![]),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct SimKcsState {
+        bmc: Bmc,
+        state: u8,
+        output: Option<u8>,
+        request: Vec<u8>,
+        write_end: bool,
+        response: Vec<u8>,
+        position: usize,
+    }
+
+    /// Simulates the BMC side of the KCS registers, taking each byte as soon as it is written.
+    #[derive(Default)]
+    struct SimKcs(spin::Mutex<SimKcsState>);
+
+    impl RegisterAccess for SimKcs {
+        fn read(&self, index: usize) -> u8 {
+            let mut sim = self.0.lock();
+            match index {
+                kcs::STATUS => (sim.state << kcs::STATE_SHIFT) | sim.output.is_some() as u8,
+                _ => sim.output.take().unwrap_or_default(),
+            }
+        }
+
+        fn write(&self, index: usize, value: u8) {
+            let sim = &mut *self.0.lock();
+            match (index, value, sim.state) {
+                (kcs::COMMAND, kcs::WRITE_START, _) => {
+                    sim.state = kcs::STATE_WRITE;
+                    sim.request.clear();
+                    sim.write_end = false;
+                }
+                (kcs::COMMAND, kcs::WRITE_END, kcs::STATE_WRITE) => sim.write_end = true,
+                (kcs::DATA, byte, kcs::STATE_WRITE) => {
+                    sim.request.push(byte);
+                    if sim.write_end {
+                        let (completion_code, data) =
+                            sim.bmc.handle(sim.request[0] >> 2, sim.request[1], &sim.request[2..]);
+                        sim.response = [&[sim.request[0] + 4, sim.request[1], completion_code][..], &data].concat();
+                        sim.position = 0;
+                        sim.state = kcs::STATE_READ;
+                        sim.output = Some(sim.response[0]);
+                    }
+                }
+                (kcs::DATA, kcs::READ, kcs::STATE_READ) => {
+                    sim.position += 1;
+                    match sim.response.get(sim.position) {
+                        Some(&byte) => sim.output = Some(byte),
+                        None => {
+                            sim.state = kcs::STATE_IDLE;
+                            sim.output = Some(0);
+                        }
+                    }
+                }
+                _ => sim.state = 3,
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct SimBtState {
+        bmc: Bmc,
+        control: u8,
+        request: Vec<u8>,
+        response: Vec<u8>,
+        position: usize,
+    }
+
+    /// Simulates the BMC side of the BT registers, answering each request as soon as it is sent.
+    #[derive(Default)]
+    struct SimBt(spin::Mutex<SimBtState>);
+
+    impl RegisterAccess for SimBt {
+        fn read(&self, index: usize) -> u8 {
+            let mut sim = self.0.lock();
+            match index {
+                bt::CONTROL => sim.control,
+                _ => {
+                    sim.position += 1;
+                    sim.response.get(sim.position - 1).copied().unwrap_or_default()
+                }
+            }
+        }
+
+        fn write(&self, index: usize, value: u8) {
+            let sim = &mut *self.0.lock();
+            match (index, value) {
+                (bt::BUFFER, byte) => sim.request.push(byte),
+                (_, bt::CLR_WR_PTR) => sim.request.clear(),
+                (_, bt::CLR_RD_PTR) => sim.position = 0,
+                (_, bt::H_BUSY) => sim.control ^= bt::H_BUSY,
+                (_, bt::B2H_ATN) => sim.control &= !bt::B2H_ATN,
+                (_, bt::H2B_ATN) => {
+                    let request = &sim.request;
+                    assert_eq!(request[0] as usize, request.len() - 1);
+                    let (completion_code, data) = sim.bmc.handle(request[1] >> 2, request[3], &request[4..]);
+                    let header = [4 + data.len() as u8, request[1] + 4, request[2], request[3], completion_code];
+                    sim.response = [&header[..], &data].concat();
+                    sim.control |= bt::B2H_ATN;
+                }
+                _ => panic!("unexpected control write {value:#x}"),
+            }
+        }
+    }
+
+    fn check_interface(interface: &impl IpmiInterface) {
+        interface.init();
+        let mut response = [0; 16];
+        assert_eq!(interface.send_command(0x06, 0x01, &[], &mut response), Ok(5));
+        assert_eq!(&response[..5], &[0x20, 0x81, 0x02, 0x10, 0x02]);
+        assert_eq!(interface.send_command(0x06, 0x01, &[], &mut response[..4]), Err(IpmiError::BufferTooSmall));
+        assert_eq!(interface.send_command(0x0A, 0x10, &[1, 2], &mut response), Err(IpmiError::CompletionCode(0xC1)));
+        assert_eq!(interface.send_command(0x06, 0x01, &[], &mut response), Ok(5));
+    }
+
+    #[test]
+    fn test_kcs_send_command() {
+        check_interface(&Kcs::new(SimKcs::default()));
+    }
+
+    #[test]
+    fn test_bt_send_command() {
+        check_interface(&Bt::new(SimBt::default()));
+    }
+
+    #[test]
+    fn test_sol_serial_io() {
+        let sol = IpmiSol::new(Kcs::new(SimKcs::default()), SOL);
+        sol.init();
+        assert_eq!(sol.try_read(), None);
+
+        sol.write(b"hello, bmc");
+        assert_eq!(sol.interface.registers.0.lock().bmc.writes, 3);
+        let echoed: Vec<u8> = core::iter::from_fn(|| sol.try_read()).collect();
+        assert_eq!(echoed, b"hello, bmc");
+
+        sol.write(b"x");
+        assert_eq!(sol.read(), b'x');
+
+        let sol = IpmiSol::new(Bt::new(SimBt::default()), SOL);
+        sol.write(b"over bt");
+        let echoed: Vec<u8> = core::iter::from_fn(|| sol.try_read()).collect();
+        assert_eq!(echoed, b"over bt");
+    }
+}
@@ -0,0 +1,665 @@
+//! Synchronization primitives for firmware.
+//!
+//! [`TplSpinLock`] and [`TplRwLock`] raise the TPL to [`Tpl::HIGH_LEVEL`] before acquiring the lock and restore it
+//! after releasing the lock. Raising the TPL first means no event notification or interrupt on the same CPU can run
+//! while the lock is held, so the lock cannot deadlock against a callback that takes the same lock. The lock itself is
+//! an atomic spin lock, so it also excludes other CPUs once application processors are running.
+//!
+//! [`OnceCell`] and [`Lazy`] are atomically initialized and can be placed in statics. They behave the same before
+//! and after other CPUs are started.
+//!
+//! ## Spin Detection
+//!
+//! A lock can be given a [`SpinDetector`] with `with_spin_detector`. When a CPU spins on the lock for more than the
+//! detector's threshold, the detector receives a [`SpinReport`] naming the lock, the source location of the waiting
+//! call, and the source location of the call that acquired the lock. The report is delivered on the waiting CPU, so
+//! the detector can also capture the waiter's stack trace, for example with `patina_stacktrace::StackTrace::dump`.
+//!
+//! No stack trace is captured for the holder. Only the source location of its `lock`, `read`, or `write` call is
+//! recorded, because walking the stack on every acquisition would be too costly, and the holder's stack cannot be
+//! walked from the waiting CPU. A holder that acquired the lock in a shared helper is reported at that helper.
+//!
+//! Use [`TplMutex`](crate::tpl_mutex::TplMutex) instead of [`TplSpinLock`] when the critical section is long or must
+//! call boot services that are not allowed at `TPL_HIGH_LEVEL`.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    panic::Location,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::boot_services::{BootServices, StandardBootServices, tpl::Tpl};
+
+/// A cell that is initialized at most once, atomically.
+///
+/// If several CPUs race to initialize the cell, one runs the initializer and the others wait for it to complete.
+/// The initializer must not access the same cell, and must not be interrupted by code that initializes the same cell,
+/// or it will deadlock. Reading an initialized cell never blocks.
+pub type OnceCell<T> = spin::Once<T>;
+
+/// A value that is initialized on first access, atomically.
+///
+/// The same restrictions as [`OnceCell`] apply to the initializer.
+pub type Lazy<T, F = fn() -> T> = spin::Lazy<T, F>;
+
+/// Describes a CPU that has spun on a lock for longer than the [`SpinDetector`] threshold.
+#[derive(Debug, Clone, Copy)]
+pub struct SpinReport {
+    /// The name given to the lock in `with_spin_detector`.
+    pub lock_name: &'static str,
+    /// The number of failed acquisition attempts so far.
+    pub spins: u64,
+    /// The source location of the call that is waiting for the lock.
+    pub waiter: &'static Location<'static>,
+    /// The source location of the call that acquired the lock, if known. This is the holder's only context; its stack
+    /// is not captured.
+    ///
+    /// This is `None` if the lock was released between the failed attempt and the report, or if it is held by
+    /// readers of a [`TplRwLock`], which are not tracked.
+    pub holder: Option<&'static Location<'static>>,
+}
+
+/// Receives reports of CPUs that spin on a lock for too long.
+///
+/// Detectors are called with the TPL at `TPL_HIGH_LEVEL` and must not acquire the lock being reported.
+pub trait SpinDetector: Sync {
+    /// Returns the number of failed acquisition attempts after which a wait is reported. A threshold of zero reports
+    /// a wait after its first failed attempt.
+    fn spin_threshold(&self) -> u64;
+
+    /// Reports a wait that exceeded the threshold. Called at most once per wait, on the waiting CPU.
+    fn report(&self, report: &SpinReport);
+}
+
+/// A spin detector attached to a lock, together with the location of the current holder.
+struct SpinDetection<'a> {
+    lock_name: &'static str,
+    detector: &'a dyn SpinDetector,
+    holder: AtomicPtr<Location<'static>>,
+}
+
+impl<'a> SpinDetection<'a> {
+    const fn new(lock_name: &'static str, detector: &'a dyn SpinDetector) -> Self {
+        Self { lock_name, detector, holder: AtomicPtr::new(ptr::null_mut()) }
+    }
+
+    /// Calls `try_acquire` until it succeeds, reporting the wait once it exceeds the threshold.
+    fn spin<G>(&self, waiter: &'static Location<'static>, mut try_acquire: impl FnMut() -> Option<G>) -> G {
+        let threshold = self.detector.spin_threshold();
+        let mut spins = 0u64;
+        let mut reported = false;
+        loop {
+            if let Some(guard) = try_acquire() {
+                return guard;
+            }
+            spins = spins.saturating_add(1);
+            if !reported && spins >= threshold {
+                reported = true;
+                self.detector.report(&SpinReport { lock_name: self.lock_name, spins, waiter, holder: self.holder() });
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn holder(&self) -> Option<&'static Location<'static>> {
+        // SAFETY: The pointer is either null or was created from a `&'static Location`.
+        unsafe { self.holder.load(Ordering::Acquire).as_ref() }
+    }
+
+    fn set_holder(&self, holder: Option<&'static Location<'static>>) {
+        self.holder.store(holder.map_or(ptr::null_mut(), |holder| ptr::from_ref(holder).cast_mut()), Ordering::Release);
+    }
+}
+
+/// A spin lock that raises the TPL to [`Tpl::HIGH_LEVEL`] while it is held.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let counter = TplSpinLock::new(&boot_services, 0u32);
+/// *counter.lock() += 1;
+/// ```
+pub struct TplSpinLock<'a, T: ?Sized, B: BootServices = StandardBootServices> {
+    boot_services: &'a B,
+    detection: Option<SpinDetection<'a>>,
+    lock: spin::Mutex<T>,
+}
+
+/// RAII implementation of a [`TplSpinLock`] lock. The lock is released and the TPL restored when it is dropped.
+#[must_use = "if unused the TplSpinLock will immediately unlock"]
+pub struct TplSpinLockGuard<'a, T: ?Sized, B: BootServices> {
+    guard: ManuallyDrop<spin::MutexGuard<'a, T>>,
+    boot_services: &'a B,
+    detection: Option<&'a SpinDetection<'a>>,
+    release_tpl: Tpl,
+}
+
+impl<'a, T, B: BootServices> TplSpinLock<'a, T, B> {
+    /// Creates a new unlocked `TplSpinLock`.
+    pub const fn new(boot_services: &'a B, data: T) -> Self {
+        Self { boot_services, detection: None, lock: spin::Mutex::new(data) }
+    }
+
+    /// Reports waits on this lock that exceed the threshold of `detector`, identifying the lock as `lock_name`.
+    pub const fn with_spin_detector(mut self, lock_name: &'static str, detector: &'a dyn SpinDetector) -> Self {
+        self.detection = Some(SpinDetection::new(lock_name, detector));
+        self
+    }
+
+    /// Consumes the lock, returning the protected data.
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+}
+
+impl<T: ?Sized, B: BootServices> TplSpinLock<'_, T, B> {
+    /// Raises the TPL to [`Tpl::HIGH_LEVEL`] and spins until the lock is acquired.
+    ///
+    /// Acquiring the lock again on the same CPU while it is held never completes. A spin detector, if attached, reports
+    /// this case.
+    #[track_caller]
+    pub fn lock(&self) -> TplSpinLockGuard<'_, T, B> {
+        let caller = Location::caller();
+        let release_tpl = self.boot_services.raise_tpl(Tpl::HIGH_LEVEL);
+        let guard = match &self.detection {
+            Some(detection) => detection.spin(caller, || self.lock.try_lock()),
+            None => self.lock.lock(),
+        };
+        self.guard(guard, caller, release_tpl)
+    }
+
+    /// Attempts to acquire the lock without spinning.
+    ///
+    /// Returns `None`, with the TPL unchanged, if the lock is held.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<TplSpinLockGuard<'_, T, B>> {
+        let caller = Location::caller();
+        let release_tpl = self.boot_services.raise_tpl(Tpl::HIGH_LEVEL);
+        match self.lock.try_lock() {
+            Some(guard) => Some(self.guard(guard, caller, release_tpl)),
+            None => {
+                self.boot_services.restore_tpl(release_tpl);
+                None
+            }
+        }
+    }
+
+    fn guard<'g>(
+        &'g self,
+        guard: spin::MutexGuard<'g, T>,
+        caller: &'static Location<'static>,
+        release_tpl: Tpl,
+    ) -> TplSpinLockGuard<'g, T, B> {
+        if let Some(detection) = &self.detection {
+            detection.set_holder(Some(caller));
+        }
+        TplSpinLockGuard {
+            guard: ManuallyDrop::new(guard),
+            boot_services: self.boot_services,
+            detection: self.detection.as_ref(),
+            release_tpl,
+        }
+    }
+
+    /// Returns a mutable reference to the protected data. No locking is needed because the borrow is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.lock.get_mut()
+    }
+}
+
+impl<T: ?Sized, B: BootServices> Drop for TplSpinLockGuard<'_, T, B> {
+    fn drop(&mut self) {
+        if let Some(detection) = self.detection {
+            detection.set_holder(None);
+        }
+        // SAFETY: The guard is not used after this point.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        self.boot_services.restore_tpl(self.release_tpl);
+    }
+}
+
+impl<T: ?Sized, B: BootServices> Deref for TplSpinLockGuard<'_, T, B> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized, B: BootServices> DerefMut for TplSpinLockGuard<'_, T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B: BootServices> fmt::Debug for TplSpinLock<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut dbg = f.debug_struct("TplSpinLock");
+        match self.try_lock() {
+            Some(guard) => dbg.field("data", &&*guard),
+            None => dbg.field("data", &format_args!("<locked>")),
+        };
+        dbg.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B: BootServices> fmt::Debug for TplSpinLockGuard<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// SAFETY: The data is only accessed through a guard, and the spin lock ensures only one guard exists at a time. The
+// boot services reference is only used to raise and restore the TPL, which boot services allow from any context.
+unsafe impl<T: ?Sized + Send, B: BootServices> Sync for TplSpinLock<'_, T, B> {}
+// SAFETY: The lock owns the data, which is Send.
+unsafe impl<T: ?Sized + Send, B: BootServices> Send for TplSpinLock<'_, T, B> {}
+
+/// A reader-writer spin lock that raises the TPL to [`Tpl::HIGH_LEVEL`] while it is held.
+///
+/// Any number of readers, or a single writer, may hold the lock at a time. Because interrupts are disabled while the
+/// lock is held, it is safe to take from interrupt and event notification context.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let config = TplRwLock::new(&boot_services, Config::default());
+/// let enabled = config.read().enabled;
+/// config.write().enabled = true;
+/// ```
+pub struct TplRwLock<'a, T: ?Sized, B: BootServices = StandardBootServices> {
+    boot_services: &'a B,
+    detection: Option<SpinDetection<'a>>,
+    lock: spin::RwLock<T>,
+}
+
+/// RAII implementation of shared access to a [`TplRwLock`].
+#[must_use = "if unused the TplRwLock will immediately unlock"]
+pub struct TplRwLockReadGuard<'a, T: ?Sized, B: BootServices> {
+    guard: ManuallyDrop<spin::RwLockReadGuard<'a, T>>,
+    boot_services: &'a B,
+    release_tpl: Tpl,
+}
+
+/// RAII implementation of exclusive access to a [`TplRwLock`].
+#[must_use = "if unused the TplRwLock will immediately unlock"]
+pub struct TplRwLockWriteGuard<'a, T: ?Sized, B: BootServices> {
+    guard: ManuallyDrop<spin::RwLockWriteGuard<'a, T>>,
+    boot_services: &'a B,
+    detection: Option<&'a SpinDetection<'a>>,
+    release_tpl: Tpl,
+}
+
+impl<'a, T, B: BootServices> TplRwLock<'a, T, B> {
+    /// Creates a new unlocked `TplRwLock`.
+    pub const fn new(boot_services: &'a B, data: T) -> Self {
+        Self { boot_services, detection: None, lock: spin::RwLock::new(data) }
+    }
+
+    /// Reports waits on this lock that exceed the threshold of `detector`, identifying the lock as `lock_name`.
+    ///
+    /// Writers are reported as holders. Readers are not tracked.
+    pub const fn with_spin_detector(mut self, lock_name: &'static str, detector: &'a dyn SpinDetector) -> Self {
+        self.detection = Some(SpinDetection::new(lock_name, detector));
+        self
+    }
+
+    /// Consumes the lock, returning the protected data.
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+}
+
+impl<T: ?Sized, B: BootServices> TplRwLock<'_, T, B> {
+    /// Raises the TPL to [`Tpl::HIGH_LEVEL`] and spins until shared access is acquired.
+    #[track_caller]
+    pub fn read(&self) -> TplRwLockReadGuard<'_, T, B> {
+        let caller = Location::caller();
+        let release_tpl = self.boot_services.raise_tpl(Tpl::HIGH_LEVEL);
+        let guard = match &self.detection {
+            Some(detection) => detection.spin(caller, || self.lock.try_read()),
+            None => self.lock.read(),
+        };
+        TplRwLockReadGuard { guard: ManuallyDrop::new(guard), boot_services: self.boot_services, release_tpl }
+    }
+
+    /// Raises the TPL to [`Tpl::HIGH_LEVEL`] and spins until exclusive access is acquired.
+    ///
+    /// Acquiring the lock again on the same CPU while it is held never completes. A spin detector, if attached, reports
+    /// this case.
+    #[track_caller]
+    pub fn write(&self) -> TplRwLockWriteGuard<'_, T, B> {
+        let caller = Location::caller();
+        let release_tpl = self.boot_services.raise_tpl(Tpl::HIGH_LEVEL);
+        let guard = match &self.detection {
+            Some(detection) => detection.spin(caller, || self.lock.try_write()),
+            None => self.lock.write(),
+        };
+        self.write_guard(guard, caller, release_tpl)
+    }
+
+    /// Attempts to acquire shared access without spinning.
+    ///
+    /// Returns `None`, with the TPL unchanged, if a writer holds the lock.
+    pub fn try_read(&self) -> Option<TplRwLockReadGuard<'_, T, B>> {
+        let release_tpl = self.boot_services.raise_tpl(Tpl::HIGH_LEVEL);
+        match self.lock.try_read() {
+            Some(guard) => Some(TplRwLockReadGuard {
+                guard: ManuallyDrop::new(guard),
+                boot_services: self.boot_services,
+                release_tpl,
+            }),
+            None => {
+                self.boot_services.restore_tpl(release_tpl);
+                None
+            }
+        }
+    }
+
+    /// Attempts to acquire exclusive access without spinning.
+    ///
+    /// Returns `None`, with the TPL unchanged, if the lock is held.
+    #[track_caller]
+    pub fn try_write(&self) -> Option<TplRwLockWriteGuard<'_, T, B>> {
+        let caller = Location::caller();
+        let release_tpl = self.boot_services.raise_tpl(Tpl::HIGH_LEVEL);
+        match self.lock.try_write() {
+            Some(guard) => Some(self.write_guard(guard, caller, release_tpl)),
+            None => {
+                self.boot_services.restore_tpl(release_tpl);
+                None
+            }
+        }
+    }
+
+    fn write_guard<'g>(
+        &'g self,
+        guard: spin::RwLockWriteGuard<'g, T>,
+        caller: &'static Location<'static>,
+        release_tpl: Tpl,
+    ) -> TplRwLockWriteGuard<'g, T, B> {
+        if let Some(detection) = &self.detection {
+            detection.set_holder(Some(caller));
+        }
+        TplRwLockWriteGuard {
+            guard: ManuallyDrop::new(guard),
+            boot_services: self.boot_services,
+            detection: self.detection.as_ref(),
+            release_tpl,
+        }
+    }
+
+    /// Returns a mutable reference to the protected data. No locking is needed because the borrow is exclusive.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.lock.get_mut()
+    }
+}
+
+impl<T: ?Sized, B: BootServices> Drop for TplRwLockReadGuard<'_, T, B> {
+    fn drop(&mut self) {
+        // SAFETY: The guard is not used after this point.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        self.boot_services.restore_tpl(self.release_tpl);
+    }
+}
+
+impl<T: ?Sized, B: BootServices> Drop for TplRwLockWriteGuard<'_, T, B> {
+    fn drop(&mut self) {
+        if let Some(detection) = self.detection {
+            detection.set_holder(None);
+        }
+        // SAFETY: The guard is not used after this point.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        self.boot_services.restore_tpl(self.release_tpl);
+    }
+}
+
+impl<T: ?Sized, B: BootServices> Deref for TplRwLockReadGuard<'_, T, B> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized, B: BootServices> Deref for TplRwLockWriteGuard<'_, T, B> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized, B: BootServices> DerefMut for TplRwLockWriteGuard<'_, T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, B: BootServices> fmt::Debug for TplRwLock<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut dbg = f.debug_struct("TplRwLock");
+        match self.try_read() {
+            Some(guard) => dbg.field("data", &&*guard),
+            None => dbg.field("data", &format_args!("<locked>")),
+        };
+        dbg.finish_non_exhaustive()
+    }
+}
+
+// SAFETY: The spin reader-writer lock ensures shared access is only granted while no writer exists, so T must be Sync
+// for shared access from several CPUs and Send for exclusive access from any CPU.
+unsafe impl<T: ?Sized + Send + Sync, B: BootServices> Sync for TplRwLock<'_, T, B> {}
+// SAFETY: The lock owns the data, which is Send.
+unsafe impl<T: ?Sized + Send, B: BootServices> Send for TplRwLock<'_, T, B> {}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::boot_services::MockBootServices;
+    use mockall::predicate::*;
+    use std::format;
+
+    /// Boot services that expect `count` raise and restore pairs at `TPL_HIGH_LEVEL`.
+    fn boot_services(count: usize) -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_raise_tpl().with(eq(Tpl::HIGH_LEVEL)).times(count).return_const(Tpl::APPLICATION);
+        boot_services.expect_restore_tpl().with(eq(Tpl::APPLICATION)).times(count).return_const(());
+        boot_services
+    }
+
+    #[test]
+    fn test_spin_lock_raises_and_restores_tpl() {
+        let boot_services = boot_services(2);
+        let lock = TplSpinLock::new(&boot_services, 0u32);
+        *lock.lock() += 1;
+        assert_eq!(*lock.lock(), 1);
+    }
+
+    #[test]
+    fn test_spin_lock_try_lock_fails_while_locked() {
+        // The failed attempt raises and restores the TPL as well.
+        let boot_services = boot_services(3);
+        let lock = TplSpinLock::new(&boot_services, 0u32);
+
+        let guard = lock.try_lock().expect("First lock should succeed.");
+        assert!(lock.try_lock().is_none());
+        drop(guard);
+        assert!(lock.try_lock().is_some());
+    }
+
+    #[test]
+    fn test_spin_lock_debug_output() {
+        let boot_services = boot_services(3);
+        let lock = TplSpinLock::new(&boot_services, 5u32);
+        assert_eq!(format!("{lock:?}"), "TplSpinLock { data: 5, .. }");
+        let _guard = lock.lock();
+        assert_eq!(format!("{lock:?}"), "TplSpinLock { data: <locked>, .. }");
+    }
+
+    #[test]
+    fn test_spin_lock_get_mut_and_into_inner_do_not_change_tpl() {
+        let boot_services = boot_services(0);
+        let mut lock = TplSpinLock::new(&boot_services, 1u32);
+        *lock.get_mut() = 2;
+        assert_eq!(lock.into_inner(), 2);
+    }
+
+    #[test]
+    fn test_rwlock_allows_several_readers() {
+        let boot_services = boot_services(4);
+        let lock = TplRwLock::new(&boot_services, 7u32);
+
+        let first = lock.read();
+        let second = lock.try_read().expect("Readers should share the lock.");
+        assert_eq!(*first + *second, 14);
+        assert!(lock.try_write().is_none());
+        drop((first, second));
+
+        *lock.write() = 8;
+        assert_eq!(lock.into_inner(), 8);
+    }
+
+    #[test]
+    fn test_rwlock_writer_excludes_readers() {
+        let boot_services = boot_services(4);
+        let lock = TplRwLock::new(&boot_services, 0u32);
+
+        let guard = lock.try_write().expect("First writer should succeed.");
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop(guard);
+        assert_eq!(format!("{lock:?}"), "TplRwLock { data: 0, .. }");
+    }
+
+    /// Records every report.
+    struct RecordingDetector {
+        threshold: u64,
+        reports: std::sync::Mutex<std::vec::Vec<SpinReport>>,
+    }
+
+    impl Default for RecordingDetector {
+        fn default() -> Self {
+            Self::with_threshold(100)
+        }
+    }
+
+    impl SpinDetector for RecordingDetector {
+        fn spin_threshold(&self) -> u64 {
+            self.threshold
+        }
+
+        fn report(&self, report: &SpinReport) {
+            self.reports.lock().unwrap().push(*report);
+        }
+    }
+
+    impl RecordingDetector {
+        fn with_threshold(threshold: u64) -> Self {
+            Self { threshold, reports: Default::default() }
+        }
+
+        fn wait_for_report(&self) {
+            while self.reports.lock().unwrap().is_empty() {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    #[test]
+    fn test_spin_lock_reports_long_wait() {
+        let boot_services = boot_services(2);
+        let detector = RecordingDetector::default();
+        let lock = TplSpinLock::new(&boot_services, 0u32).with_spin_detector("test", &detector);
+
+        let holder_line = std::cell::Cell::new(0);
+        std::thread::scope(|scope| {
+            let guard = lock.lock();
+            holder_line.set(line!() - 1);
+            scope.spawn(|| *lock.lock() += 1);
+            detector.wait_for_report();
+            drop(guard);
+        });
+
+        let reports = detector.reports.lock().unwrap();
+        assert_eq!(reports.len(), 1, "A wait is reported once.");
+        assert_eq!(reports[0].lock_name, "test");
+        assert_eq!(reports[0].spins, 100);
+        assert_eq!(reports[0].holder.map(Location::line), Some(holder_line.get()));
+        assert_eq!(reports[0].waiter.file(), file!());
+        assert_ne!(reports[0].waiter.line(), holder_line.get());
+        drop(reports);
+        assert_eq!(lock.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_spin_lock_reports_once_with_zero_threshold() {
+        let boot_services = boot_services(2);
+        let detector = RecordingDetector::with_threshold(0);
+        let lock = TplSpinLock::new(&boot_services, 0u32).with_spin_detector("zero", &detector);
+
+        std::thread::scope(|scope| {
+            let guard = lock.lock();
+            scope.spawn(|| *lock.lock() += 1);
+            detector.wait_for_report();
+            // Keep the waiter spinning well past the threshold.
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            drop(guard);
+        });
+
+        let reports = detector.reports.lock().unwrap();
+        assert_eq!(reports.len(), 1, "A wait is reported once.");
+        assert_eq!(reports[0].spins, 1);
+        drop(reports);
+        assert_eq!(lock.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_rwlock_reports_writer_as_holder() {
+        let boot_services = boot_services(4);
+        let detector = RecordingDetector::default();
+        let lock = TplRwLock::new(&boot_services, 0u32).with_spin_detector("rw", &detector);
+
+        // A reader waiting on a writer reports the writer.
+        std::thread::scope(|scope| {
+            let guard = lock.write();
+            let holder_line = line!() - 1;
+            scope.spawn(|| assert_eq!(*lock.read(), 0));
+            detector.wait_for_report();
+            assert_eq!(detector.reports.lock().unwrap()[0].holder.map(Location::line), Some(holder_line));
+            drop(guard);
+        });
+
+        // A writer waiting on readers reports no holder.
+        std::thread::scope(|scope| {
+            let guard = lock.read();
+            scope.spawn(|| *lock.write() = 1);
+            while detector.reports.lock().unwrap().len() < 2 {
+                std::thread::yield_now();
+            }
+            assert!(detector.reports.lock().unwrap()[1].holder.is_none());
+            drop(guard);
+        });
+        assert_eq!(lock.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_once_cell_and_lazy() {
+        static CELL: OnceCell<u32> = OnceCell::new();
+        static LAZY: Lazy<u32> = Lazy::new(|| 42);
+
+        assert!(CELL.get().is_none());
+        assert_eq!(*CELL.call_once(|| 1), 1);
+        assert_eq!(*CELL.call_once(|| 2), 1);
+        assert_eq!(*LAZY, 42);
+    }
+}
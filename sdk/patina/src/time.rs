@@ -0,0 +1,234 @@
+//! Time and delay abstractions for timeout logic.
+//!
+//! Code that waits for something to happen, such as a debugger waiting for a client to attach or a watchdog deciding
+//! whether a period has elapsed, should measure time through [`Clock`] and wait through [`Delay`] rather than reading
+//! the CPU counter directly. This keeps the timeout logic independent of the underlying timer so it can be driven by a
+//! [`ManualClock`] in host unit tests, where time only moves when the test says so.
+//!
+//! Any [`ArchTimerFunctionality`] service can be used as both a [`Clock`] and a busy-wait [`Delay`].
+//!
+//! ## Example
+//!
+//! ```rust
+//! use core::time::Duration;
+//! use patina::time::{Clock, Deadline, Delay, ManualClock};
+//!
+//! fn wait_for(ready: impl Fn() -> bool, clock: &(impl Clock + Delay), timeout: Duration) -> bool {
+//!     let deadline = Deadline::new(clock, timeout);
+//!     while !ready() {
+//!         if deadline.has_expired(clock) {
+//!             return false;
+//!         }
+//!         clock.delay(Duration::from_millis(1));
+//!     }
+//!     true
+//! }
+//!
+//! let clock = ManualClock::new();
+//! assert!(!wait_for(|| false, &clock, Duration::from_millis(10)));
+//! assert_eq!(clock.now(), Duration::from_millis(10));
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::component::service::perf_timer::ArchTimerFunctionality;
+
+/// A monotonic source of time.
+pub trait Clock: Send + Sync {
+    /// Returns the time elapsed since an arbitrary, fixed point in the past.
+    fn now(&self) -> Duration;
+}
+
+/// A way to wait for a period of time.
+pub trait Delay: Send + Sync {
+    /// Waits for at least `duration`.
+    fn delay(&self, duration: Duration);
+}
+
+/// A point in time after which a timeout has expired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    expiry: Duration,
+}
+
+impl Deadline {
+    /// Creates a deadline `timeout` after the current time of `clock`.
+    pub fn new(clock: &(impl Clock + ?Sized), timeout: Duration) -> Self {
+        Self { expiry: clock.now().saturating_add(timeout) }
+    }
+
+    /// Returns true if the deadline has been reached according to `clock`.
+    pub fn has_expired(&self, clock: &(impl Clock + ?Sized)) -> bool {
+        clock.now() >= self.expiry
+    }
+
+    /// Returns the time left before the deadline, or zero if it has expired.
+    pub fn remaining(&self, clock: &(impl Clock + ?Sized)) -> Duration {
+        self.expiry.saturating_sub(clock.now())
+    }
+}
+
+impl Clock for dyn ArchTimerFunctionality {
+    fn now(&self) -> Duration {
+        let frequency = self.perf_frequency();
+        if frequency == 0 {
+            return Duration::ZERO;
+        }
+
+        let ticks = self.cpu_count().wrapping_sub(self.cpu_count_start());
+        let nanos = (ticks as u128 * 1_000_000_000) / frequency as u128;
+        Duration::from_nanos(nanos.min(u64::MAX as u128) as u64)
+    }
+}
+
+impl Delay for dyn ArchTimerFunctionality {
+    /// Busy-waits on the performance counter. Returns immediately if the counter frequency is unknown.
+    fn delay(&self, duration: Duration) {
+        if self.perf_frequency() == 0 {
+            return;
+        }
+
+        let deadline = Deadline::new(self, duration);
+        while !deadline.has_expired(self) {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// A clock that only advances when told to.
+///
+/// Delaying on a manual clock advances it by the requested duration instead of waiting, so timeout logic runs to
+/// completion immediately and deterministically.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at zero.
+    pub const fn new() -> Self {
+        Self { nanos: AtomicU64::new(0) }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.nanos.fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Sets the current time of the clock.
+    pub fn set(&self, now: Duration) {
+        self.nanos.store(now.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+impl Delay for ManualClock {
+    fn delay(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    struct TestTimer {
+        count: AtomicU64,
+        frequency: u64,
+    }
+
+    impl ArchTimerFunctionality for TestTimer {
+        fn cpu_count(&self) -> u64 {
+            // Each read moves the counter forward so busy-waits terminate.
+            self.count.fetch_add(10, Ordering::SeqCst)
+        }
+
+        fn perf_frequency(&self) -> u64 {
+            self.frequency
+        }
+    }
+
+    #[test]
+    fn manual_clock_should_only_advance_when_told() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now(), Duration::ZERO);
+
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(clock.now(), Duration::from_millis(5));
+
+        clock.delay(Duration::from_micros(7));
+        assert_eq!(clock.now(), Duration::from_millis(5) + Duration::from_micros(7));
+
+        clock.set(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn deadline_should_expire_after_timeout() {
+        let clock = ManualClock::new();
+        let deadline = Deadline::new(&clock, Duration::from_millis(10));
+
+        assert!(!deadline.has_expired(&clock));
+        assert_eq!(deadline.remaining(&clock), Duration::from_millis(10));
+
+        clock.advance(Duration::from_millis(9));
+        assert!(!deadline.has_expired(&clock));
+        assert_eq!(deadline.remaining(&clock), Duration::from_millis(1));
+
+        clock.advance(Duration::from_millis(1));
+        assert!(deadline.has_expired(&clock));
+        assert_eq!(deadline.remaining(&clock), Duration::ZERO);
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(deadline.remaining(&clock), Duration::ZERO);
+    }
+
+    #[test]
+    fn deadline_should_saturate_on_overflow() {
+        let clock = ManualClock::new();
+        clock.advance(Duration::from_secs(1));
+        let deadline = Deadline::new(&clock, Duration::MAX);
+        assert!(!deadline.has_expired(&clock));
+    }
+
+    #[test]
+    fn arch_timer_should_convert_ticks_to_time() {
+        let timer = TestTimer { count: AtomicU64::new(2_000), frequency: 1_000 };
+        let timer: &dyn ArchTimerFunctionality = &timer;
+        assert_eq!(timer.now(), Duration::from_secs(2));
+
+        let timer = TestTimer { count: AtomicU64::new(2_000), frequency: 0 };
+        let timer: &dyn ArchTimerFunctionality = &timer;
+        assert_eq!(timer.now(), Duration::ZERO);
+    }
+
+    #[test]
+    fn arch_timer_delay_should_wait_for_duration() {
+        let timer = TestTimer { count: AtomicU64::new(0), frequency: 1_000 };
+        let timer: &dyn ArchTimerFunctionality = &timer;
+
+        let start = timer.now();
+        timer.delay(Duration::from_millis(100));
+        assert!(timer.now() - start >= Duration::from_millis(100));
+
+        // An unknown frequency must not spin forever.
+        let timer = TestTimer { count: AtomicU64::new(0), frequency: 0 };
+        let timer: &dyn ArchTimerFunctionality = &timer;
+        timer.delay(Duration::from_secs(1));
+    }
+}
@@ -2,6 +2,9 @@
 //!
 //! The primary purpose of a Device Path is to allow an application, such as an OS loader, to determine the physical device that the interfaces are abstracting.
 //!
+//! A [`DevicePath`] displays as the text representation defined in the UEFI specification, and
+//! [`DevicePathBuf::from_text`] parses that text back into a device path.
+//!
 //! ## License
 //!
 //! Copyright (c) Microsoft Corporation.
@@ -11,6 +14,7 @@
 
 pub mod device_path_node;
 pub mod nodes;
+mod text;
 
 use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
 use core::{
@@ -141,9 +141,12 @@ impl<'a> TryFromCtx<'a, scroll::Endian> for UnknownDevicePathNode<'a> {
 
 impl Display for UnknownDevicePathNode<'_> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.write_fmt(format_args!("Path({}, {},", &self.header.r#type, &self.header.sub_type))?;
+        f.write_fmt(format_args!("Path({},{}", &self.header.r#type, &self.header.sub_type))?;
+        if !self.data.is_empty() {
+            f.write_char(',')?;
+        }
         for b in self.data {
-            f.write_fmt(format_args!(" {b:02X}"))?;
+            f.write_fmt(format_args!("{b:02x}"))?;
         }
         f.write_char(')')
     }
@@ -153,6 +156,10 @@ impl Display for UnknownDevicePathNode<'_> {
 /// To configure the type and subtype of this node, use an attribute `@[DevicePathNode(DevicePathType::Type, SubType::MySubtype)]`
 /// where the Type and subtype are enum paths where the value can be expressed as u8.a
 ///
+/// The length of the node defaults to the size of the header plus the size of the struct. When the struct has padding
+/// that the node does not, give the length of the node in bytes, including the header, as a third argument:
+/// `@[DevicePathNode(DevicePathType::Type, SubType::MySubtype, 24)]`.
+///
 /// Some additional traits can be implemented with `@[DevicePathNodeDerive(...)]`
 /// Currently supported traits are: Debug and Display.
 #[macro_export]
@@ -160,7 +167,7 @@ macro_rules! device_path_node {
     // match a struct with fields.
     (
         $(#[$struct_attr_1:meta])*
-        @[DevicePathNode( $device_path_type:path, $device_path_sub_type:path)]
+        @[DevicePathNode( $device_path_type:path, $device_path_sub_type:path $(, $length:expr)?)]
         $(@[DevicePathNodeDerive( $($derive_trait:ident),* )])?
         $(#[$struct_attr_2:meta])*
         $struct_vis:vis struct $struct_name:ident {
@@ -179,7 +186,7 @@ macro_rules! device_path_node {
             ),*
         }
 
-        device_path_node!(@ImplDevicePathNode; $device_path_type, $device_path_sub_type, $struct_name);
+        device_path_node!(@ImplDevicePathNode; $device_path_type, $device_path_sub_type, $struct_name $(, $length)?);
         device_path_node!(@Derive; $struct_name, $($field_name),*; $($($derive_trait),*)?);
     };
     // Match an empty struct.
@@ -198,14 +205,14 @@ macro_rules! device_path_node {
         device_path_node!(@Derive; $struct_name, $($empty_field),*; $($($derive_trait),*)?);
     };
     // Internal Matching to implement the device path node trait.
-    (@ImplDevicePathNode; $device_path_type:path, $device_path_sub_type:path, $struct_name:ident) => {
+    (@ImplDevicePathNode; $device_path_type:path, $device_path_sub_type:path, $struct_name:ident $(, $length:expr)?) => {
         impl $crate::uefi_protocol::device_path::device_path_node::DevicePathNode for $struct_name
         {
             fn header(&self) -> $crate::uefi_protocol::device_path::device_path_node::Header {
                 $crate::uefi_protocol::device_path::device_path_node::Header {
                     r#type: $device_path_type as u8,
                     sub_type: $device_path_sub_type as u8,
-                    length: $crate::uefi_protocol::device_path::device_path_node::Header::size_of_header() + core::mem::size_of::<$struct_name>()
+                    length: device_path_node!(@Length; $struct_name $(, $length)?),
 
                 }
             }
@@ -225,6 +232,13 @@ macro_rules! device_path_node {
             }
         }
     };
+    // Internal Matching to compute the length of the node.
+    (@Length; $struct_name:ident) => {
+        $crate::uefi_protocol::device_path::device_path_node::Header::size_of_header() + core::mem::size_of::<$struct_name>()
+    };
+    (@Length; $struct_name:ident, $length:expr) => {
+        $length
+    };
     // Internal Matching to implement the debug trait.
     (@Derive; $struct_name:ident, $($field_name:ident),*; Debug) => {
         impl core::fmt::Debug for $struct_name {
@@ -1,4 +1,7 @@
 //! Spec-defined device path node types defined in this module.
+//!
+//! Every node displays as its text representation from the UEFI specification, for example `Pci(0x1,0x0)` or
+//! `HD(1,GPT,<guid>,0x800,0x100000)`.
 
 use core::{
     fmt::{Display, Write},
@@ -10,14 +13,16 @@ use alloc::{
     string::{String, ToString},
 };
 
+use r_efi::efi;
+
 use scroll::{
     Pread, Pwrite,
     ctx::{TryFromCtx, TryIntoCtx},
 };
 
-use super::device_path_node::{DevicePathNode, UnknownDevicePathNode};
+use super::device_path_node::{DevicePathNode, Header, UnknownDevicePathNode};
 
-use crate::device_path_node;
+use crate::{Guid, device_path_node};
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
 #[repr(u8)]
@@ -144,7 +149,15 @@ pub fn cast_to_dyn_device_path_node(unknown: UnknownDevicePathNode<'_>) -> Box<d
         // ACPI nodes.
         Acpi,
         // Messaging nodes.
+        Usb,
+        Sata,
+        NvmExpress,
+        MacAddress,
         // Media nodes.
+        HardDrive,
+        FilePath,
+        FirmwareFile,
+        FirmwareVolume,
         // BIOS nodes.
         Bios,
         // End nodes
@@ -159,7 +172,7 @@ pub fn cast_to_dyn_device_path_node(unknown: UnknownDevicePathNode<'_>) -> Box<d
 device_path_node! {
     /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#pci-device-path>
     @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::Pci)]
-    @[DevicePathNodeDerive(Debug, Display)]
+    @[DevicePathNodeDerive(Debug)]
     #[derive(Pwrite, Pread, Clone)]
     pub struct Pci {
         /// PCI Function Number.
@@ -169,10 +182,16 @@ device_path_node! {
     }
 }
 
+impl Display for Pci {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Pci({:#x},{:#x})", self.device, self.function)
+    }
+}
+
 device_path_node! {
     /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#pci-device-path>
     @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::Pccard)]
-    @[DevicePathNodeDerive(Debug, Display)]
+    @[DevicePathNodeDerive(Debug)]
     #[derive(Pwrite, Pread, Clone)]
     pub struct PcCard {
         /// Function Number, 0 is the first one.
@@ -180,10 +199,16 @@ device_path_node! {
     }
 }
 
+impl Display for PcCard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "PcCard({:#x})", self.function_number)
+    }
+}
+
 device_path_node! {
     /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#memory-mapped-device-path>
-    @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::MemoryMapped)]
-    @[DevicePathNodeDerive(Debug, Display)]
+    @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::MemoryMapped, 24)]
+    @[DevicePathNodeDerive(Debug)]
     #[derive(Pwrite, Pread, Clone)]
     pub struct MemoryMapped {
         // EFI memory type.
@@ -195,10 +220,16 @@ device_path_node! {
     }
 }
 
+impl Display for MemoryMapped {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "MemoryMapped({:#x},{:#x},{:#x})", self.memory_type, self.start_address, self.end_address)
+    }
+}
+
 device_path_node! {
     /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#controller-device-path>
     @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::Controller)]
-    @[DevicePathNodeDerive(Debug, Display)]
+    @[DevicePathNodeDerive(Debug)]
     #[derive(Pwrite, Pread, Clone)]
     pub struct Controller {
         // Controller Number.
@@ -206,10 +237,16 @@ device_path_node! {
     }
 }
 
+impl Display for Controller {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Ctrl({:#x})", self.number)
+    }
+}
+
 device_path_node! {
-    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#controller-device-path>
-    @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::Bmc)]
-    @[DevicePathNodeDerive(Debug, Display)]
+    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#bmc-device-path>
+    @[DevicePathNode(DevicePathType::Hardware, HardwareSubType::Bmc, 13)]
+    @[DevicePathNodeDerive(Debug)]
     #[derive(Pwrite, Pread, Clone)]
     pub struct Bmc {
         pub interface_type: u8,
@@ -217,6 +254,12 @@ device_path_node! {
     }
 }
 
+impl Display for Bmc {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "BMC({:#x},{:#x})", self.interface_type, self.base_address)
+    }
+}
+
 device_path_node! {
     @[DevicePathNode(DevicePathType::Acpi, AcpiSubType::Acpi)]
     @[DevicePathNodeDerive(Debug)]
@@ -240,6 +283,9 @@ impl Acpi {
 
     /// Converts and compresses the 7-character text argument into its corresponding 4-byte numeric EISA ID encoding.
     /// <https://uefi.org/specs/ACPI/6.5_A/19_ASL_Reference.html#asl-macros>
+    ///
+    /// The compressed vendor code is in the low word and the product number in the high word, so `PNP0A03` is
+    /// `0x0A0341D0`, matching the device paths produced by firmware.
     pub const fn eisa_id(hid: &str) -> u32 {
         let bytes = hid.as_bytes();
 
@@ -257,19 +303,302 @@ impl Acpi {
         let byte_2 = (h1 << 4) | h2;
         let byte_3 = (h3 << 4) | h4;
 
-        u32::from_le_bytes([byte_3, byte_2, byte_1, byte_0])
+        u32::from_le_bytes([byte_1, byte_0, byte_3, byte_2])
     }
 }
 
 impl Display for Acpi {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self.hid {
-            Acpi::PCI_ROOT_HID => f.debug_tuple("PciRoot").field(&self.uid).finish(),
-            _ => f.debug_tuple("Acpi").field(&self.hid).field(&self.uid).finish(),
+            Acpi::PCI_ROOT_HID => write!(f, "PciRoot({:#x})", self.uid),
+            Acpi::PCIE_ROOT_HID => write!(f, "PcieRoot({:#x})", self.uid),
+            // Compressed EISA IDs start with the "PNP" vendor code.
+            hid if hid & 0xFFFF == 0x41D0 => write!(f, "Acpi(PNP{:04X},{:#x})", hid >> 16, self.uid),
+            hid => write!(f, "Acpi({:#010x},{:#x})", hid, self.uid),
+        }
+    }
+}
+
+device_path_node! {
+    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#usb-device-paths>
+    @[DevicePathNode(DevicePathType::Messaging, MessagingSubType::Usb)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Pwrite, Pread, Clone)]
+    pub struct Usb {
+        /// USB parent port number.
+        pub parent_port_number: u8,
+        /// USB interface number.
+        pub interface_number: u8,
+    }
+}
+
+impl Display for Usb {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "USB({:#x},{:#x})", self.parent_port_number, self.interface_number)
+    }
+}
+
+device_path_node! {
+    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#sata-device-path>
+    @[DevicePathNode(DevicePathType::Messaging, MessagingSubType::Sata)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Pwrite, Pread, Clone)]
+    pub struct Sata {
+        /// The HBA port number that facilitates the connection to the device or a port multiplier.
+        pub hba_port_number: u16,
+        /// The port multiplier port number, 0xFFFF if the device is directly connected to the HBA.
+        pub port_multiplier_port_number: u16,
+        /// Logical unit number.
+        pub lun: u16,
+    }
+}
+
+impl Display for Sata {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Sata({:#x},{:#x},{:#x})", self.hba_port_number, self.port_multiplier_port_number, self.lun)
+    }
+}
+
+device_path_node! {
+    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#nvm-express-namespace-device-path>
+    @[DevicePathNode(DevicePathType::Messaging, MessagingSubType::NvmExpress, 16)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Pwrite, Pread, Clone)]
+    pub struct NvmExpress {
+        /// Namespace identifier.
+        pub namespace_id: u32,
+        /// IEEE Extended Unique Identifier, 0 if the namespace does not have one.
+        pub ieee_eui_64: u64,
+    }
+}
+
+impl Display for NvmExpress {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "NVMe({:#x},", self.namespace_id)?;
+        for (i, byte) in self.ieee_eui_64.to_be_bytes().iter().enumerate() {
+            if i != 0 {
+                f.write_char('-')?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        f.write_char(')')
+    }
+}
+
+device_path_node! {
+    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#mac-address-device-path>
+    @[DevicePathNode(DevicePathType::Messaging, MessagingSubType::MacAddress)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Pwrite, Pread, Clone)]
+    pub struct MacAddress {
+        /// The MAC address of the network interface, padded with 0s.
+        pub mac_address: [u8; 32],
+        /// Network interface type, as defined by RFC 3232.
+        pub if_type: u8,
+    }
+}
+
+impl MacAddress {
+    /// Returns the bytes of the MAC address that are in use: 6 for Ethernet, all 32 otherwise.
+    pub fn address(&self) -> &[u8] {
+        match self.if_type {
+            0 | 1 => &self.mac_address[..6],
+            _ => &self.mac_address,
+        }
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("MAC(")?;
+        for byte in self.address() {
+            write!(f, "{byte:02x}")?;
+        }
+        write!(f, ",{:#x})", self.if_type)
+    }
+}
+
+device_path_node! {
+    /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#hard-drive-media-device-path>
+    @[DevicePathNode(DevicePathType::Media, MediaSubType::HardDrive, 42)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Pwrite, Pread, Clone)]
+    pub struct HardDrive {
+        /// Entry in the partition table, starting at 1. 0 is the whole device.
+        pub partition_number: u32,
+        /// Starting LBA of the partition.
+        pub partition_start: u64,
+        /// Size of the partition in logical blocks.
+        pub partition_size: u64,
+        /// Partition signature, its format depends on `signature_type`.
+        pub partition_signature: [u8; 16],
+        /// Partition format, [`HardDrive::FORMAT_MBR`] or [`HardDrive::FORMAT_GPT`].
+        pub partition_format: u8,
+        /// Signature type, [`HardDrive::SIGNATURE_NONE`], [`HardDrive::SIGNATURE_MBR`] or [`HardDrive::SIGNATURE_GUID`].
+        pub signature_type: u8,
+    }
+}
+
+impl HardDrive {
+    /// PC-AT compatible legacy MBR.
+    pub const FORMAT_MBR: u8 = 0x01;
+    /// GUID Partition Table.
+    pub const FORMAT_GPT: u8 = 0x02;
+
+    /// No disk signature.
+    pub const SIGNATURE_NONE: u8 = 0x00;
+    /// 32-bit MBR signature in the first 4 bytes of the partition signature.
+    pub const SIGNATURE_MBR: u8 = 0x01;
+    /// GUID signature.
+    pub const SIGNATURE_GUID: u8 = 0x02;
+}
+
+impl Display for HardDrive {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "HD({},", self.partition_number)?;
+        match self.signature_type {
+            HardDrive::SIGNATURE_MBR => {
+                let signature = [
+                    self.partition_signature[0],
+                    self.partition_signature[1],
+                    self.partition_signature[2],
+                    self.partition_signature[3],
+                ];
+                write!(f, "MBR,{:#010x},", u32::from_le_bytes(signature))?;
+            }
+            HardDrive::SIGNATURE_GUID => write!(f, "GPT,{},", Guid::from_bytes(&self.partition_signature))?,
+            signature_type => write!(f, "{signature_type},0,")?,
+        }
+        write!(f, "{:#x},{:#x})", self.partition_start, self.partition_size)
+    }
+}
+
+/// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#file-path-media-device-path>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePath {
+    /// The path name, stored in the node as a null-terminated UCS-2 string.
+    pub path_name: String,
+}
+
+impl FilePath {
+    pub fn new(path_name: &str) -> Self {
+        Self { path_name: path_name.to_string() }
+    }
+}
+
+impl DevicePathNode for FilePath {
+    fn header(&self) -> Header {
+        let path_size = (self.path_name.encode_utf16().count() + 1) * 2;
+        Header::new(DevicePathType::Media as u8, MediaSubType::FilePath as u8, Header::size_of_header() + path_size)
+    }
+
+    fn is_type(r#type: u8, sub_type: u8) -> bool {
+        r#type == DevicePathType::Media as u8 && sub_type == MediaSubType::FilePath as u8
+    }
+
+    fn write_into(self, buffer: &mut [u8]) -> Result<usize, scroll::Error> {
+        let mut offset = 0;
+        buffer.gwrite_with(self.header(), &mut offset, scroll::LE)?;
+        buffer.gwrite_with(self, &mut offset, scroll::LE)?;
+        Ok(offset)
+    }
+}
+
+impl TryIntoCtx<scroll::Endian> for FilePath {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, dest: &mut [u8], ctx: scroll::Endian) -> Result<usize, Self::Error> {
+        let mut offset = 0;
+        for c in self.path_name.encode_utf16() {
+            dest.gwrite_with(c, &mut offset, ctx)?;
+        }
+        dest.gwrite_with(0_u16, &mut offset, ctx)?; // End of string
+        Ok(offset)
+    }
+}
+
+impl TryFromCtx<'_, scroll::Endian> for FilePath {
+    type Error = scroll::Error;
+
+    fn try_from_ctx(buffer: &[u8], ctx: scroll::Endian) -> Result<(Self, usize), Self::Error> {
+        let mut offset = 0;
+        let mut path_name = alloc::vec::Vec::new();
+        while offset < buffer.len() {
+            match buffer.gread_with::<u16>(&mut offset, ctx)? {
+                0 => break,
+                c => path_name.push(c),
+            }
         }
+        Ok((Self { path_name: String::from_utf16_lossy(&path_name) }, offset))
+    }
+}
+
+impl Display for FilePath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.path_name)
     }
 }
 
+device_path_node! {
+    /// <https://uefi.org/specs/PI/1.8/V3_Design_Discussion.html#firmware-file-media-device-path>
+    @[DevicePathNode(DevicePathType::Media, MediaSubType::PiwgFirmwareFile, 20)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Clone)]
+    pub struct FirmwareFile {
+        /// Name of the firmware file.
+        pub name: efi::Guid,
+    }
+}
+
+impl Display for FirmwareFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "FvFile({})", Guid::from_ref(&self.name))
+    }
+}
+
+device_path_node! {
+    /// <https://uefi.org/specs/PI/1.8/V3_Design_Discussion.html#firmware-volume-media-device-path>
+    @[DevicePathNode(DevicePathType::Media, MediaSubType::PiwgFirmwareVolume, 20)]
+    @[DevicePathNodeDerive(Debug)]
+    #[derive(Clone)]
+    pub struct FirmwareVolume {
+        /// Name of the firmware volume.
+        pub name: efi::Guid,
+    }
+}
+
+impl Display for FirmwareVolume {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Fv({})", Guid::from_ref(&self.name))
+    }
+}
+
+macro_rules! impl_guid_node_ctx {
+    ($($ty:ident),*) => {
+        $(
+            impl TryIntoCtx<scroll::Endian> for $ty {
+                type Error = scroll::Error;
+
+                fn try_into_ctx(self, dest: &mut [u8], _: scroll::Endian) -> Result<usize, Self::Error> {
+                    dest.pwrite_with(&self.name.as_bytes()[..], 0, ())
+                }
+            }
+
+            impl TryFromCtx<'_, scroll::Endian> for $ty {
+                type Error = scroll::Error;
+
+                fn try_from_ctx(buffer: &[u8], _: scroll::Endian) -> Result<(Self, usize), Self::Error> {
+                    let name = buffer.pread_with::<&[u8]>(0, 16)?;
+                    let name = efi::Guid::from_bytes(name.try_into().unwrap_or(&[0; 16]));
+                    Ok((Self { name }, 16))
+                }
+            }
+        )*
+    };
+}
+
+impl_guid_node_ctx!(FirmwareFile, FirmwareVolume);
+
 device_path_node! {
     /// <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#bios-boot-specification-device-path>
     @[DevicePathNode(DevicePathType::Bios, BiosSubType::BiosBootSpecification)]
@@ -318,8 +647,8 @@ device_path_node! {
 }
 
 impl Display for EndEntire {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.write_char('.')
+    fn fmt(&self, _: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Ok(())
     }
 }
 
@@ -348,7 +677,7 @@ device_path_node! {
 
 impl Display for EndInstance {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.write_char(';')
+        f.write_char(',')
     }
 }
 
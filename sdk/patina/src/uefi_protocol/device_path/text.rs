@@ -0,0 +1,243 @@
+//! Conversion of device path text to device paths.
+//!
+//! Parses the text representation defined in the UEFI specification, which is also what the [`Display`](core::fmt::Display)
+//! implementation of a device path produces. Nodes are separated by `/` and instances by `,`. A node is written as
+//! `Name(arg,...)`, text without parentheses is a file path, and `Path(type,subtype,hex)` describes any node.
+//!
+//! <https://uefi.org/specs/UEFI/2.10/10_Protocols_Device_Path_Protocol.html#text-device-node-reference>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use super::{
+    DevicePathBuf,
+    device_path_node::{Header, UnknownDevicePathNode},
+    nodes::{
+        Acpi, Bmc, Controller, EndEntire, EndInstance, FilePath, FirmwareFile, FirmwareVolume, HardDrive, MacAddress,
+        MemoryMapped, NvmExpress, PcCard, Pci, Sata, Usb,
+    },
+};
+use crate::BinaryGuid;
+
+impl DevicePathBuf {
+    /// Parse the text representation of a device path, as defined in the UEFI specification.
+    ///
+    /// The device path is terminated with an EndEntire node, and instances are separated by EndInstance nodes.
+    pub fn from_text(text: &str) -> Result<DevicePathBuf, &'static str> {
+        let mut device_path = DevicePathBuf::new_empty();
+        if !text.is_empty() {
+            for (i, instance) in split_top_level(text, ',').into_iter().enumerate() {
+                if i != 0 {
+                    device_path.append(EndInstance);
+                }
+                for node in split_top_level(instance, '/') {
+                    append_node(&mut device_path, node.trim())?;
+                }
+            }
+        }
+        device_path.append(EndEntire);
+        Ok(device_path)
+    }
+}
+
+impl FromStr for DevicePathBuf {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DevicePathBuf::from_text(s)
+    }
+}
+
+/// Split the text at the separators that are not inside parentheses.
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn append_node(device_path: &mut DevicePathBuf, text: &str) -> Result<(), &'static str> {
+    if text.is_empty() {
+        return Err("Device Path: empty node in the text.");
+    }
+    let Some((name, args)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) else {
+        device_path.append(FilePath::new(text));
+        return Ok(());
+    };
+    let args: Vec<&str> = if args.is_empty() { Vec::new() } else { args.split(',').map(str::trim).collect() };
+
+    match (name, args.as_slice()) {
+        ("Pci", [device, function]) => device_path.append(Pci { function: number(function)?, device: number(device)? }),
+        ("PcCard", [function_number]) => device_path.append(PcCard { function_number: number(function_number)? }),
+        ("MemoryMapped", [memory_type, start_address, end_address]) => device_path.append(MemoryMapped {
+            memory_type: number(memory_type)?,
+            start_address: number(start_address)?,
+            end_address: number(end_address)?,
+        }),
+        ("Ctrl", [controller]) => device_path.append(Controller { number: number(controller)? }),
+        ("BMC", [interface_type, base_address]) => {
+            device_path.append(Bmc { interface_type: number(interface_type)?, base_address: number(base_address)? })
+        }
+        ("PciRoot", [uid]) => device_path.append(Acpi { hid: Acpi::PCI_ROOT_HID, uid: number(uid)? }),
+        ("PcieRoot", [uid]) => device_path.append(Acpi { hid: Acpi::PCIE_ROOT_HID, uid: number(uid)? }),
+        ("Acpi", [hid, uid]) => device_path.append(Acpi { hid: acpi_hid(hid)?, uid: number(uid)? }),
+        ("USB", [parent_port_number, interface_number]) => device_path.append(Usb {
+            parent_port_number: number(parent_port_number)?,
+            interface_number: number(interface_number)?,
+        }),
+        ("Sata", [hba_port_number, port_multiplier_port_number, lun]) => device_path.append(Sata {
+            hba_port_number: number(hba_port_number)?,
+            port_multiplier_port_number: number(port_multiplier_port_number)?,
+            lun: number(lun)?,
+        }),
+        ("NVMe", [namespace_id, eui]) => {
+            let eui = eui.split('-').map(|byte| u8::from_str_radix(byte, 16)).collect::<Result<Vec<_>, _>>();
+            let eui: [u8; 8] = eui.ok().and_then(|eui| eui.try_into().ok()).ok_or("Device Path: invalid NVMe EUI.")?;
+            device_path.append(NvmExpress { namespace_id: number(namespace_id)?, ieee_eui_64: u64::from_be_bytes(eui) })
+        }
+        ("MAC", [address, if_type @ ..]) if if_type.len() <= 1 => {
+            let bytes = hex_bytes(address)?;
+            let mut mac_address = [0; 32];
+            mac_address.get_mut(..bytes.len()).ok_or("Device Path: MAC address too long.")?.copy_from_slice(&bytes);
+            let if_type = if_type.first().map(|if_type| number(if_type)).transpose()?.unwrap_or_default();
+            device_path.append(MacAddress { mac_address, if_type })
+        }
+        ("HD", [partition_number, signature_type, signature, partition_start, partition_size]) => {
+            let mut partition_signature = [0; 16];
+            let (partition_format, signature_type) = match *signature_type {
+                "MBR" => {
+                    partition_signature[..4].copy_from_slice(&number::<u32>(signature)?.to_le_bytes());
+                    (HardDrive::FORMAT_MBR, HardDrive::SIGNATURE_MBR)
+                }
+                "GPT" => {
+                    partition_signature = *guid(signature)?.as_bytes();
+                    (HardDrive::FORMAT_GPT, HardDrive::SIGNATURE_GUID)
+                }
+                signature_type => (0, number(signature_type)?),
+            };
+            device_path.append(HardDrive {
+                partition_number: number(partition_number)?,
+                partition_start: number(partition_start)?,
+                partition_size: number(partition_size)?,
+                partition_signature,
+                partition_format,
+                signature_type,
+            })
+        }
+        ("Fv", [name]) => device_path.append(FirmwareVolume { name: guid(name)?.into_inner() }),
+        ("FvFile", [name]) => device_path.append(FirmwareFile { name: guid(name)?.into_inner() }),
+        ("Path", [r#type, sub_type, data @ ..]) if data.len() <= 1 => {
+            let data = data.first().map(|data| hex_bytes(data)).transpose()?.unwrap_or_default();
+            let header = Header::new(number(r#type)?, number(sub_type)?, Header::size_of_header() + data.len());
+            device_path.append(UnknownDevicePathNode { header, data: &data })
+        }
+        _ => return Err("Device Path: unsupported node in the text."),
+    }
+    Ok(())
+}
+
+/// Parse a number, in hexadecimal when prefixed with `0x` and in decimal otherwise.
+fn number<T: TryFrom<u64>>(text: &str) -> Result<T, &'static str> {
+    let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse::<u64>(),
+    };
+    value.ok().and_then(|value| T::try_from(value).ok()).ok_or("Device Path: invalid number in the text.")
+}
+
+/// Parse a string of hexadecimal byte pairs.
+fn hex_bytes(text: &str) -> Result<Vec<u8>, &'static str> {
+    if !text.len().is_multiple_of(2) {
+        return Err("Device Path: odd number of hexadecimal digits.");
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<Vec<_>>>()
+        .ok_or("Device Path: invalid hexadecimal data.")
+}
+
+fn guid(text: &str) -> Result<BinaryGuid, &'static str> {
+    BinaryGuid::try_from_string(text).map_err(|_| "Device Path: invalid GUID.")
+}
+
+/// Parse an ACPI _HID, either a compressed EISA ID like `PNP0A03` or a number.
+fn acpi_hid(text: &str) -> Result<u32, &'static str> {
+    let bytes = text.as_bytes();
+    if bytes.len() == 7 && bytes[..3].iter().all(u8::is_ascii_uppercase) && bytes[3..].iter().all(u8::is_ascii_hexdigit)
+    {
+        Ok(Acpi::eisa_id(text))
+    } else {
+        number(text)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_device_path_text_round_trip() {
+        let texts = [
+            "PciRoot(0x0)/Pci(0x1f,0x2)/Sata(0x0,0xffff,0x0)/HD(1,GPT,5D9A6B4E-2C4F-4A8B-9E1D-3F6A7B8C9D0E,0x800,0x100000)/\\EFI\\BOOT\\BOOTX64.EFI",
+            "PcieRoot(0x1)/Pci(0x0,0x0)/NVMe(0x1,00-25-38-5B-71-B0-47-21)/HD(2,MBR,0x12345678,0x3f,0x2000)",
+            "PciRoot(0x0)/Pci(0x14,0x0)/USB(0x3,0x0),PciRoot(0x0)/Pci(0x2,0x0)/Ctrl(0x1)",
+            "Acpi(PNP0501,0x1)/MAC(001122334455,0x1)",
+            "Fv(7CB8BDC9-F8EB-4F34-AAEA-3EE4AF6516A1)/FvFile(462CAA21-7614-4503-836E-8AB6F4662331)",
+            "MemoryMapped(0xb,0xfe000000,0xfeffffff)/BMC(0x1,0xca2)/PcCard(0x0)",
+            "Path(3,99,deadbeef)/Path(1,7)",
+        ];
+        for text in texts {
+            let device_path = DevicePathBuf::from_text(text).unwrap();
+            assert_eq!(text, device_path.to_string());
+        }
+    }
+
+    #[test]
+    fn test_device_path_from_text_node_layout() {
+        let device_path: DevicePathBuf =
+            "HD(1,GPT,5D9A6B4E-2C4F-4A8B-9E1D-3F6A7B8C9D0E,0x800,0x100000)".parse().unwrap();
+        let hard_drive = device_path.iter().next().unwrap();
+        assert_eq!(42, hard_drive.header.length);
+
+        let device_path = DevicePathBuf::from_text("\\EFI\\x.efi").unwrap();
+        let file_path = device_path.iter().next().unwrap();
+        assert_eq!(4 + 11 * 2, file_path.header.length);
+        assert_eq!(&[b'\\', 0, b'E', 0], &file_path.data[..4]);
+        assert_eq!(&[0, 0], &file_path.data[file_path.data.len() - 2..]);
+
+        assert_eq!(1, DevicePathBuf::from_text("").unwrap().node_count());
+        assert_eq!(4, DevicePathBuf::from_text("PciRoot(0x0),Pci(0x0,0x0)").unwrap().node_count());
+    }
+
+    #[test]
+    fn test_device_path_from_text_errors() {
+        assert!(DevicePathBuf::from_text("PciRoot(0x0)//Pci(0x0,0x0)").is_err());
+        assert!(DevicePathBuf::from_text("Pci(0x100,0x0)").is_err());
+        assert!(DevicePathBuf::from_text("Pci(0x1)").is_err());
+        assert!(DevicePathBuf::from_text("Unknown(0x1)").is_err());
+        assert!(DevicePathBuf::from_text("Fv(not-a-guid)").is_err());
+        assert!(DevicePathBuf::from_text("Path(1,2,abc)").is_err());
+    }
+}
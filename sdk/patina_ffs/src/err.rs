@@ -6,7 +6,8 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 
-use patina::error::EfiError;
+use core::fmt;
+use patina::{Guid, error::EfiError};
 use r_efi::efi;
 
 /// Error definitions for Firmware File System
@@ -32,6 +33,10 @@ pub enum FirmwareFileSystemError {
     NotLeaf,
     /// Composing the FFS structure failed.
     ComposeFailed,
+    /// A caller-provided buffer is too small for the result.
+    BufferTooSmall,
+    /// The operation did not complete within its time limit and was abandoned.
+    Timeout,
 }
 
 impl From<FirmwareFileSystemError> for EfiError {
@@ -47,6 +52,8 @@ impl From<FirmwareFileSystemError> for EfiError {
             | FirmwareFileSystemError::InvalidState
             | FirmwareFileSystemError::DataCorrupt => EfiError::VolumeCorrupted,
             FirmwareFileSystemError::ComposeFailed => EfiError::DeviceError,
+            FirmwareFileSystemError::BufferTooSmall => EfiError::BufferTooSmall,
+            FirmwareFileSystemError::Timeout => EfiError::Timeout,
         }
     }
 }
@@ -57,3 +64,177 @@ impl From<FirmwareFileSystemError> for efi::Status {
         err.into()
     }
 }
+
+/// The specific reason an extraction failed, when the extractor can identify it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorReason {
+    /// The checksum recorded in the section does not match the checksum of its data.
+    CrcMismatch {
+        /// The checksum recorded in the section.
+        expected_crc: u32,
+        /// The checksum of the data.
+        actual_crc: u32,
+    },
+    /// The size recorded in the section does not match the size of the extracted data.
+    SizeMismatch {
+        /// The size recorded in the section.
+        expected_size: usize,
+        /// The size of the extracted data.
+        actual_size: usize,
+    },
+    /// The section content ends before a structure it must contain.
+    Truncated {
+        /// The number of bytes required.
+        required_size: usize,
+        /// The number of bytes available.
+        available_size: usize,
+    },
+    /// The compressed stream is malformed.
+    MalformedStream,
+}
+
+impl fmt::Display for ErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorReason::CrcMismatch { expected_crc, actual_crc } => {
+                write!(f, "CRC mismatch (expected {expected_crc:#010x}, actual {actual_crc:#010x})")
+            }
+            ErrorReason::SizeMismatch { expected_size, actual_size } => {
+                write!(f, "size mismatch (expected {expected_size:#x}, actual {actual_size:#x})")
+            }
+            ErrorReason::Truncated { required_size, available_size } => {
+                write!(f, "truncated ({required_size:#x} bytes required, {available_size:#x} available)")
+            }
+            ErrorReason::MalformedStream => write!(f, "malformed stream"),
+        }
+    }
+}
+
+/// A [`FirmwareFileSystemError`] with diagnostic context describing where and why an extraction failed.
+///
+/// Returned by [`SectionExtractor::extract_with_context`](crate::section::SectionExtractor::extract_with_context).
+/// Converts to and from the bare [`FirmwareFileSystemError`], so it can be propagated with `?` in either direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractionError {
+    /// The underlying error.
+    pub error: FirmwareFileSystemError,
+    /// The section definition GUID of the section being extracted, if it is GUID-defined.
+    pub section_guid: Option<efi::Guid>,
+    /// The offset into the section content at which the failure was detected, if known.
+    pub offset: Option<usize>,
+    /// The specific reason for the failure, if known.
+    pub reason: Option<ErrorReason>,
+}
+
+impl ExtractionError {
+    /// Creates an error without context.
+    pub const fn new(error: FirmwareFileSystemError) -> Self {
+        Self { error, section_guid: None, offset: None, reason: None }
+    }
+
+    /// Sets the section definition GUID.
+    pub const fn with_section_guid(mut self, section_guid: efi::Guid) -> Self {
+        self.section_guid = Some(section_guid);
+        self
+    }
+
+    /// Sets the offset into the section content at which the failure was detected.
+    pub const fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Sets the reason for the failure.
+    pub const fn with_reason(mut self, reason: ErrorReason) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+}
+
+impl From<FirmwareFileSystemError> for ExtractionError {
+    fn from(error: FirmwareFileSystemError) -> Self {
+        Self::new(error)
+    }
+}
+
+impl From<ExtractionError> for FirmwareFileSystemError {
+    fn from(value: ExtractionError) -> Self {
+        value.error
+    }
+}
+
+impl fmt::Display for ExtractionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.error)?;
+        if let Some(guid) = &self.section_guid {
+            write!(f, " in section {}", Guid::from_ref(guid))?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {offset:#x}")?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_GUID: efi::Guid =
+        efi::Guid::from_fields(0xfc1bcdb0, 0x7d31, 0x49aa, 0x93, 0x6a, &[0xa4, 0x60, 0x0d, 0x9d, 0xd0, 0x83]);
+
+    #[test]
+    fn test_extraction_error_display() {
+        let err = ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+            .with_section_guid(TEST_GUID)
+            .with_offset(0x20)
+            .with_reason(ErrorReason::CrcMismatch { expected_crc: 0xdeadbeef, actual_crc: 0x1234 });
+        assert_eq!(
+            format!("{err}"),
+            "DataCorrupt in section FC1BCDB0-7D31-49AA-936A-A4600D9DD083 at offset 0x20: \
+             CRC mismatch (expected 0xdeadbeef, actual 0x00001234)"
+        );
+        assert_eq!(format!("{}", ExtractionError::new(FirmwareFileSystemError::Unsupported)), "Unsupported");
+    }
+
+    #[test]
+    fn test_default_extract_with_context_attaches_guid() {
+        use crate::section::{Section, SectionExtractor, SectionHeader};
+        use alloc::vec::Vec;
+        use patina::pi::fw_fs::ffs::section::{self, header::GuidDefined};
+
+        struct FailingExtractor;
+        impl SectionExtractor for FailingExtractor {
+            fn extract(&self, _section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+                Err(FirmwareFileSystemError::DataCorrupt)
+            }
+        }
+
+        let guid_header = GuidDefined { section_definition_guid: TEST_GUID, data_offset: 24, attributes: 0x01 };
+        let section =
+            Section::new_from_header_with_data(SectionHeader::GuidDefined(guid_header, vec![], 1), vec![0]).unwrap();
+        assert_eq!(
+            FailingExtractor.extract_with_context(&section),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_section_guid(TEST_GUID))
+        );
+
+        let section =
+            Section::new_from_header_with_data(SectionHeader::Standard(section::raw_type::RAW, 1), vec![0]).unwrap();
+        assert_eq!(
+            FailingExtractor.extract_with_context(&section),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt))
+        );
+    }
+
+    #[test]
+    fn test_extraction_error_conversions() {
+        let err: ExtractionError = FirmwareFileSystemError::BufferTooSmall.into();
+        assert_eq!(err, ExtractionError::new(FirmwareFileSystemError::BufferTooSmall));
+
+        let err = err.with_reason(ErrorReason::MalformedStream);
+        assert_eq!(FirmwareFileSystemError::from(err), FirmwareFileSystemError::BufferTooSmall);
+    }
+}
@@ -73,6 +73,32 @@ impl<'a> FileRef<'a> {
     /// assert_eq!(file_ref.file_type_raw(), 0x07);
     /// ```
     pub fn new(buffer: &'a [u8]) -> Result<Self, FirmwareFileSystemError> {
+        let (header, size, content_offset) = Self::parse_size(buffer)?;
+
+        // Verify that the total size of the file fits within the buffer.
+        if size > buffer.len() {
+            Err(FirmwareFileSystemError::InvalidHeader)?;
+        }
+
+        let erase_polarity = Self::verify_header(&buffer[..content_offset], &header)?;
+
+        // Verify the file data checksum. integrity_check_file is chosen so that it and the data sum to zero.
+        if header.attributes & attributes::raw::CHECKSUM != 0 {
+            let sum = buffer[content_offset..size]
+                .iter()
+                .fold(header.integrity_check_file, |sum, val| sum.wrapping_add(*val));
+            if sum != 0 {
+                Err(FirmwareFileSystemError::DataCorrupt)?;
+            }
+        }
+        Ok(Self { data: &buffer[..size], header, erase_polarity, size, content_offset })
+    }
+
+    /// Parse the file header at the start of `buffer`, returning the header, the total file size, and the offset of
+    /// the file content.
+    ///
+    /// `buffer` must hold the file header, but need not hold the whole file.
+    pub(crate) fn parse_size(buffer: &[u8]) -> Result<(file::Header, usize, usize), FirmwareFileSystemError> {
         // Verify that buffer has enough storage for a file header.
         if buffer.len() < mem::size_of::<file::Header>() {
             Err(FirmwareFileSystemError::InvalidHeader)?;
@@ -99,12 +125,14 @@ impl<'a> FileRef<'a> {
                 (header.extended_size as usize, mem::size_of::<file::Header2>())
             }
         };
+        Ok((header, size, content_offset))
+    }
 
-        // Verify that the total size of the file fits within the buffer.
-        if size > buffer.len() {
-            Err(FirmwareFileSystemError::InvalidHeader)?;
-        }
-
+    /// Verify the state and header checksum of a file whose serialized header is `header_bytes`, returning the erase
+    /// polarity inferred from the state.
+    ///
+    /// The data checksum is not verified, as it requires the file content.
+    pub(crate) fn verify_header(header_bytes: &[u8], header: &file::Header) -> Result<bool, FirmwareFileSystemError> {
         // Verify the state field.
         // Interpreting the state field requires knowledge of the EFI_FVB_ERASE_POLARITY from the FV header, which is not
         // available here unless the constructor API is modified to specify it. So it is inferred based on the state of
@@ -128,25 +156,18 @@ impl<'a> FileRef<'a> {
         }
 
         // Verify the file header checksum.
-        let sum = buffer[..content_offset].iter().fold(0u8, |sum, val| sum.wrapping_add(*val));
+        let sum = header_bytes.iter().fold(0u8, |sum, val| sum.wrapping_add(*val));
         let sum = sum.wrapping_sub(header.state);
         let sum = sum.wrapping_sub(header.integrity_check_file);
         if sum != 0 {
             Err(FirmwareFileSystemError::InvalidHeader)?;
         }
 
-        // Verify the file data checksum.
-        if header.attributes & attributes::raw::CHECKSUM == 0 {
-            if header.integrity_check_file != 0xAA {
-                Err(FirmwareFileSystemError::InvalidHeader)?;
-            }
-        } else {
-            let sum = buffer[content_offset..size].iter().fold(0u8, |sum, val| sum.wrapping_add(*val));
-            if sum != 0 {
-                Err(FirmwareFileSystemError::DataCorrupt)?;
-            }
+        // Files without a data checksum must carry the fixed checksum value instead.
+        if header.attributes & attributes::raw::CHECKSUM == 0 && header.integrity_check_file != 0xAA {
+            Err(FirmwareFileSystemError::InvalidHeader)?;
         }
-        Ok(Self { data: &buffer[..size], header, erase_polarity, size, content_offset })
+        Ok(erase_polarity)
     }
 
     /// Total serialized size of the file in bytes (header + content).
@@ -16,6 +16,9 @@ extern crate alloc;
 pub mod err;
 pub mod file;
 pub mod section;
+pub mod signed;
+pub mod validate;
+pub mod verify;
 pub mod volume;
 
-pub use err::FirmwareFileSystemError;
+pub use err::{ErrorReason, ExtractionError, FirmwareFileSystemError};
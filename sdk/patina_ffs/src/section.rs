@@ -18,7 +18,7 @@ use patina::{
 
 use core::{fmt, iter, mem, ptr, slice::from_raw_parts};
 
-use crate::FirmwareFileSystemError;
+use crate::{ExtractionError, FirmwareFileSystemError, validate};
 
 const MAX_STANDARD_SECTION_SIZE: usize = 0x1000000;
 
@@ -34,6 +34,14 @@ pub trait SectionExtractor {
     /// Attempt to extract the content of `section` into a raw byte buffer that contains zero or
     /// more serialized sub-sections.
     fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError>;
+
+    /// Extract `section` as [`SectionExtractor::extract`] does, reporting failures with diagnostic context.
+    ///
+    /// The default implementation calls [`SectionExtractor::extract`] and attaches the section definition GUID of
+    /// GUID-defined sections. Implementations that can identify where and why a failure occurred should override it.
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        self.extract(section).map_err(|err| section.error_context(err))
+    }
 }
 
 /// Produces a composed header and content buffer for a section.
@@ -271,8 +279,10 @@ impl Section {
     ///
     /// Validates the common and variant-specific headers, sets the content size accordingly, and
     /// stores raw content bytes. Encapsulation sections start with `extracted = false` and no
-    /// populated sub-sections.
+    /// populated sub-sections. If [strict validation](crate::validate) is on, the section is validated first.
     pub fn new_from_buffer(buffer: &[u8]) -> Result<Self, FirmwareFileSystemError> {
+        validate::validate_if_strict(buffer)?;
+
         // Verify that the buffer has enough storage for a section header.
         if buffer.len() < mem::size_of::<section::Header>() {
             Err(FirmwareFileSystemError::InvalidHeader)?;
@@ -508,9 +518,13 @@ impl Section {
             return Ok(()); //nothing to do for non-encapsulation sections or already extracted encapsulation sections.
         }
 
-        let extracted_data = match extractor.extract(self) {
-            Err(FirmwareFileSystemError::Unsupported) => Vec::new(),
-            result => result?,
+        let extracted_data = match extractor.extract_with_context(self) {
+            Err(err) if err.error == FirmwareFileSystemError::Unsupported => Vec::new(),
+            Err(err) => {
+                log::error!("Section extraction failed: {err}");
+                Err(err)?
+            }
+            Ok(data) => data,
         };
 
         let mut sections: Vec<Section> =
@@ -530,6 +544,16 @@ impl Section {
         Ok(())
     }
 
+    /// Wrap `error` in an [`ExtractionError`] carrying the section definition GUID, if this section is GUID-defined.
+    pub fn error_context(&self, error: FirmwareFileSystemError) -> ExtractionError {
+        match &self.header {
+            SectionHeader::GuidDefined(guid_header, _, _) => {
+                ExtractionError::new(error).with_section_guid(guid_header.section_definition_guid)
+            }
+            _ => ExtractionError::new(error),
+        }
+    }
+
     /// Serialize the section into bytes (header + content).
     ///
     /// Returns `NotComposed` if this section or any extracted child is dirty.
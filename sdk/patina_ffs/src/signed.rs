@@ -0,0 +1,220 @@
+//! Support for signed firmware file contents as described in the UEFI Platform Initialization Specification.
+//!
+//! A file with signed contents, typically a signed firmware volume image, wraps its sections in a GUID-defined
+//! section with the `EFI_FIRMWARE_CONTENTS_SIGNED_GUID` section definition GUID. The GUID-specific header of that
+//! section is a `WIN_CERTIFICATE_UEFI_GUID` holding the signature, and its content is the signed sections.
+//!
+//! [`ContentsSignature`] locates the signature so that it can be passed to the security subsystem for verification,
+//! and [`SignedContentsExtractor`] strips the wrapper so that the signed sections can be used.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::mem;
+
+use patina::pi::fw_fs::guid;
+use r_efi::efi;
+
+use crate::{
+    FirmwareFileSystemError,
+    section::{Section, SectionExtractor, SectionHeader},
+};
+
+/// The `wCertificateType` of a `WIN_CERTIFICATE_UEFI_GUID`.
+pub const WIN_CERT_TYPE_EFI_GUID: u16 = 0x0EF1;
+
+/// Size of the `WIN_CERTIFICATE` header: `dwLength`, `wRevision`, and `wCertificateType`.
+const WIN_CERTIFICATE_SIZE: usize = mem::size_of::<u32>() + 2 * mem::size_of::<u16>();
+
+/// Size of the `WIN_CERTIFICATE_UEFI_GUID` header, which adds `CertType` to `WIN_CERTIFICATE`.
+const WIN_CERTIFICATE_UEFI_GUID_SIZE: usize = WIN_CERTIFICATE_SIZE + mem::size_of::<efi::Guid>();
+
+/// The signature of a signed contents section, along with the contents it signs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentsSignature {
+    cert_type: efi::Guid,
+    cert_data: Vec<u8>,
+    signed_content: Vec<u8>,
+}
+
+impl ContentsSignature {
+    /// Reads the signature of `section`.
+    ///
+    /// Returns `Ok(None)` if `section` is not a signed contents section.
+    ///
+    /// ## Errors
+    ///
+    /// - [`FirmwareFileSystemError::InvalidHeader`]: the `WIN_CERTIFICATE_UEFI_GUID` header is malformed.
+    /// - [`FirmwareFileSystemError::Unsupported`]: the certificate is not a `WIN_CERTIFICATE_UEFI_GUID`.
+    pub fn from_section(section: &Section) -> Result<Option<Self>, FirmwareFileSystemError> {
+        let Some(certificate) = certificate(section) else {
+            return Ok(None);
+        };
+        let (cert_type, cert_data) = parse_certificate(certificate)?;
+        Ok(Some(Self {
+            cert_type,
+            cert_data: cert_data.to_vec(),
+            signed_content: section.try_content_as_slice()?.to_vec(),
+        }))
+    }
+
+    /// The format of the signature, such as `EFI_CERT_TYPE_PKCS7_GUID`.
+    pub fn cert_type(&self) -> &efi::Guid {
+        &self.cert_type
+    }
+
+    /// The signature, in the format given by [`ContentsSignature::cert_type`].
+    pub fn cert_data(&self) -> &[u8] {
+        &self.cert_data
+    }
+
+    /// The signed contents: the serialized sections wrapped by the signed contents section.
+    pub fn signed_content(&self) -> &[u8] {
+        &self.signed_content
+    }
+}
+
+/// Extracts the sections wrapped by a signed contents section, without verifying the signature.
+///
+/// Verification is left to the security subsystem, which can obtain the signature with
+/// [`ContentsSignature::from_section`]. Sections other than signed contents sections return
+/// [`FirmwareFileSystemError::Unsupported`], so this extractor can be tried before others.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SignedContentsExtractor;
+
+impl SectionExtractor for SignedContentsExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        let certificate = certificate(section).ok_or(FirmwareFileSystemError::Unsupported)?;
+        parse_certificate(certificate)?;
+        Ok(section.try_content_as_slice()?.to_vec())
+    }
+}
+
+/// Returns the GUID-specific header of `section` if it is a signed contents section.
+fn certificate(section: &Section) -> Option<&[u8]> {
+    match section.header() {
+        SectionHeader::GuidDefined(guid_header, guid_specific_data, _)
+            if guid_header.section_definition_guid == guid::FIRMWARE_CONTENTS_SIGNED =>
+        {
+            Some(guid_specific_data)
+        }
+        _ => None,
+    }
+}
+
+/// Parses a `WIN_CERTIFICATE_UEFI_GUID`, returning its certificate type and data.
+fn parse_certificate(certificate: &[u8]) -> Result<(efi::Guid, &[u8]), FirmwareFileSystemError> {
+    if certificate.len() < WIN_CERTIFICATE_UEFI_GUID_SIZE {
+        Err(FirmwareFileSystemError::InvalidHeader)?;
+    }
+
+    let length = u32::from_le_bytes(certificate[0..4].try_into().unwrap()) as usize;
+    if length < WIN_CERTIFICATE_UEFI_GUID_SIZE || length > certificate.len() {
+        Err(FirmwareFileSystemError::InvalidHeader)?;
+    }
+
+    let certificate_type = u16::from_le_bytes(certificate[6..8].try_into().unwrap());
+    if certificate_type != WIN_CERT_TYPE_EFI_GUID {
+        Err(FirmwareFileSystemError::Unsupported)?;
+    }
+
+    let cert_type =
+        efi::Guid::from_bytes(certificate[WIN_CERTIFICATE_SIZE..WIN_CERTIFICATE_UEFI_GUID_SIZE].try_into().unwrap());
+    Ok((cert_type, &certificate[WIN_CERTIFICATE_UEFI_GUID_SIZE..length]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use patina::pi::fw_fs::ffs::section;
+
+    const PKCS7_GUID: efi::Guid =
+        efi::Guid::from_fields(0x4AAFD29D, 0x68DF, 0x49EE, 0x8A, 0xA9, &[0x34, 0x7D, 0x37, 0x56, 0x65, 0xA7]);
+
+    fn win_certificate(certificate_type: u16, cert_data: &[u8]) -> Vec<u8> {
+        let length = (WIN_CERTIFICATE_UEFI_GUID_SIZE + cert_data.len()) as u32;
+        let mut certificate = vec![];
+        certificate.extend_from_slice(&length.to_le_bytes());
+        certificate.extend_from_slice(&0x0200u16.to_le_bytes());
+        certificate.extend_from_slice(&certificate_type.to_le_bytes());
+        certificate.extend_from_slice(PKCS7_GUID.as_bytes());
+        certificate.extend_from_slice(cert_data);
+        certificate
+    }
+
+    fn guided_section(section_definition_guid: efi::Guid, guid_specific_data: Vec<u8>, content: &[u8]) -> Section {
+        let header = section::header::GuidDefined {
+            section_definition_guid,
+            data_offset: (mem::size_of::<section::Header>()
+                + mem::size_of::<section::header::GuidDefined>()
+                + guid_specific_data.len()) as u16,
+            attributes: section::header::GUIDED_SECTION_AUTH_STATUS_VALID,
+        };
+        Section::new_from_header_with_data(
+            SectionHeader::GuidDefined(header, guid_specific_data, content.len() as u32),
+            content.to_vec(),
+        )
+        .unwrap()
+    }
+
+    fn inner_section() -> Vec<u8> {
+        Section::new_from_header_with_data(SectionHeader::Standard(section::raw_type::RAW, 4), vec![1, 2, 3, 4])
+            .unwrap()
+            .serialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_signature_from_section() {
+        let content = inner_section();
+        let signed =
+            guided_section(guid::FIRMWARE_CONTENTS_SIGNED, win_certificate(WIN_CERT_TYPE_EFI_GUID, b"sig"), &content);
+
+        let signature = ContentsSignature::from_section(&signed).unwrap().unwrap();
+        assert_eq!(signature.cert_type(), &PKCS7_GUID);
+        assert_eq!(signature.cert_data(), b"sig");
+        assert_eq!(signature.signed_content(), content);
+    }
+
+    #[test]
+    fn test_other_sections_are_not_signed() {
+        let crc32 = guided_section(guid::CRC32_SECTION, vec![0; 4], &inner_section());
+        assert_eq!(ContentsSignature::from_section(&crc32), Ok(None));
+        assert_eq!(SignedContentsExtractor.extract(&crc32), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    fn test_malformed_certificate() {
+        let content = inner_section();
+
+        let truncated = guided_section(guid::FIRMWARE_CONTENTS_SIGNED, vec![0; 8], &content);
+        assert_eq!(ContentsSignature::from_section(&truncated), Err(FirmwareFileSystemError::InvalidHeader));
+
+        let mut overlong = win_certificate(WIN_CERT_TYPE_EFI_GUID, b"sig");
+        overlong[0] += 1;
+        let overlong = guided_section(guid::FIRMWARE_CONTENTS_SIGNED, overlong, &content);
+        assert_eq!(SignedContentsExtractor.extract(&overlong), Err(FirmwareFileSystemError::InvalidHeader));
+
+        let pkcs1 = guided_section(guid::FIRMWARE_CONTENTS_SIGNED, win_certificate(0x0002, b"sig"), &content);
+        assert_eq!(ContentsSignature::from_section(&pkcs1), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    fn test_extract_strips_wrapper() {
+        let content = inner_section();
+        let mut signed =
+            guided_section(guid::FIRMWARE_CONTENTS_SIGNED, win_certificate(WIN_CERT_TYPE_EFI_GUID, b"sig"), &content);
+        assert_eq!(SignedContentsExtractor.extract(&signed).unwrap(), content);
+
+        signed.extract(&SignedContentsExtractor).unwrap();
+        let sections: Vec<_> = signed.sub_sections().collect();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].section_type_raw(), section::raw_type::RAW);
+        assert_eq!(sections[0].try_content_as_slice().unwrap(), &[1, 2, 3, 4]);
+    }
+}
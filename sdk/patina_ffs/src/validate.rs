@@ -0,0 +1,364 @@
+//! Strict validation of section headers.
+//!
+//! [`Section::new_from_buffer`](crate::section::Section::new_from_buffer) checks only what it needs to parse a
+//! section. Strict validation also checks the invariants the UEFI Platform Initialization Specification places on
+//! section headers: section sizes against header sizes, the data offset of GUID-defined sections, reserved section
+//! types and attribute bits, the attributes required by well-known GUID-defined sections, and the termination of
+//! string sections. It is meant for debug builds, fuzzing, and triage of corrupt firmware reported from the field.
+//!
+//! Strict validation is off by default. [`set_strict_validation`] turns it on at runtime, after which every section
+//! parsed by this crate is validated first and rejected with [`FirmwareFileSystemError::InvalidHeader`] if it breaks
+//! an invariant. [`validate_section`] validates a single section regardless of the setting.
+//!
+//! [`replay_corpus`] runs a corpus of captured malformed sections through the validator, so that corruption cases
+//! found in the field can be kept as regression tests.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::{
+    fmt, mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use patina::pi::fw_fs::{
+    ffs::section::{self, header, raw_type},
+    guid,
+};
+use r_efi::efi;
+
+use crate::FirmwareFileSystemError;
+
+static STRICT_VALIDATION: AtomicBool = AtomicBool::new(false);
+
+/// Turns strict validation of parsed sections on or off.
+pub fn set_strict_validation(enabled: bool) {
+    STRICT_VALIDATION.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether strict validation of parsed sections is on.
+pub fn strict_validation() -> bool {
+    STRICT_VALIDATION.load(Ordering::Relaxed)
+}
+
+/// A section header invariant broken by a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The buffer ends before the section headers or the section.
+    Truncated,
+    /// The section size is smaller than the section headers.
+    SizeSmallerThanHeader,
+    /// The section type is reserved.
+    ReservedSectionType(u8),
+    /// The data offset of a GUID-defined section is within its headers or beyond the end of the section.
+    InvalidDataOffset(u16),
+    /// A GUID-defined section sets reserved attribute bits.
+    ReservedAttributes(u16),
+    /// A well-known GUID-defined section does not set the attributes its format requires.
+    MissingAttributes {
+        /// The section definition GUID.
+        section_definition_guid: efi::Guid,
+        /// The attributes the format requires.
+        required: u16,
+    },
+    /// A compression section uses an unknown compression type.
+    UnknownCompressionType(u8),
+    /// The uncompressed length of an uncompressed compression section does not match its content size.
+    UncompressedLengthMismatch,
+    /// A user interface or version section does not hold a null-terminated UCS-2 string.
+    UnterminatedString,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "section is truncated"),
+            Self::SizeSmallerThanHeader => write!(f, "section size is smaller than its headers"),
+            Self::ReservedSectionType(section_type) => write!(f, "section type {section_type:#x} is reserved"),
+            Self::InvalidDataOffset(data_offset) => {
+                write!(f, "data offset {data_offset:#x} is outside of the section content")
+            }
+            Self::ReservedAttributes(attributes) => write!(f, "attributes {attributes:#x} set reserved bits"),
+            Self::MissingAttributes { section_definition_guid, required } => write!(
+                f,
+                "section {} requires attributes {required:#x}",
+                patina::Guid::from_ref(section_definition_guid)
+            ),
+            Self::UnknownCompressionType(compression_type) => {
+                write!(f, "compression type {compression_type:#x} is unknown")
+            }
+            Self::UncompressedLengthMismatch => write!(f, "uncompressed length does not match the content size"),
+            Self::UnterminatedString => write!(f, "string is not a null-terminated UCS-2 string"),
+        }
+    }
+}
+
+/// Validates the headers of the section at the start of `buffer`.
+///
+/// Encapsulated sections are not validated; validate them as they are extracted.
+pub fn validate_section(buffer: &[u8]) -> Result<(), Violation> {
+    let common_header_size = mem::size_of::<section::Header>();
+    let common_header = buffer.get(..common_header_size).ok_or(Violation::Truncated)?;
+    let section_type = common_header[3];
+
+    let (section_size, header_size) = if common_header[..3] == [0xff; 3] {
+        let header_size = mem::size_of::<header::CommonSectionHeaderExtended>();
+        let extended_size = buffer.get(common_header_size..header_size).ok_or(Violation::Truncated)?;
+        (u32::from_le_bytes(extended_size.try_into().unwrap()) as usize, header_size)
+    } else {
+        (u32::from_le_bytes([common_header[0], common_header[1], common_header[2], 0]) as usize, common_header_size)
+    };
+
+    if section_size < header_size {
+        Err(Violation::SizeSmallerThanHeader)?;
+    }
+    let section = buffer.get(..section_size).ok_or(Violation::Truncated)?;
+
+    match section_type {
+        raw_type::encapsulated::COMPRESSION => validate_compression(section, header_size),
+        raw_type::encapsulated::GUID_DEFINED => validate_guid_defined(section, header_size),
+        raw_type::VERSION => {
+            let content = type_specific_content(section, header_size, mem::size_of::<header::Version>())?;
+            // The version string is optional.
+            if content.is_empty() { Ok(()) } else { validate_string(content) }
+        }
+        raw_type::USER_INTERFACE => validate_string(&section[header_size..]),
+        raw_type::FREEFORM_SUBTYPE_GUID => {
+            type_specific_content(section, header_size, mem::size_of::<header::FreeformSubtypeGuid>()).map(|_| ())
+        }
+        raw_type::encapsulated::DISPOSABLE
+        | raw_type::PE32
+        | raw_type::PIC
+        | raw_type::TE
+        | raw_type::DXE_DEPEX
+        | raw_type::COMPATIBILITY16
+        | raw_type::FIRMWARE_VOLUME_IMAGE
+        | raw_type::RAW
+        | raw_type::PEI_DEPEX
+        | raw_type::MM_DEPEX
+        | raw_type::OEM_MIN..=raw_type::OEM_MAX
+        | raw_type::DEBUG_MIN..=raw_type::DEBUG_MAX => Ok(()),
+        _ => Err(Violation::ReservedSectionType(section_type)),
+    }
+}
+
+/// Validates `buffer` as [`validate_section`] does if strict validation is on.
+pub(crate) fn validate_if_strict(buffer: &[u8]) -> Result<(), FirmwareFileSystemError> {
+    if !strict_validation() {
+        return Ok(());
+    }
+    validate_section(buffer).map_err(|violation| {
+        log::error!("Section failed strict validation: {violation}");
+        FirmwareFileSystemError::InvalidHeader
+    })
+}
+
+/// Returns the content of `section` following a type-specific header of `type_header_size` bytes.
+fn type_specific_content(section: &[u8], header_size: usize, type_header_size: usize) -> Result<&[u8], Violation> {
+    section.get(header_size + type_header_size..).ok_or(Violation::SizeSmallerThanHeader)
+}
+
+fn validate_compression(section: &[u8], header_size: usize) -> Result<(), Violation> {
+    let content = type_specific_content(section, header_size, mem::size_of::<header::Compression>())?;
+    let uncompressed_length = u32::from_le_bytes(section[header_size..header_size + 4].try_into().unwrap());
+    match section[header_size + 4] {
+        header::NOT_COMPRESSED if uncompressed_length as usize != content.len() => {
+            Err(Violation::UncompressedLengthMismatch)
+        }
+        header::NOT_COMPRESSED | header::STANDARD_COMPRESSION => Ok(()),
+        compression_type => Err(Violation::UnknownCompressionType(compression_type)),
+    }
+}
+
+fn validate_guid_defined(section: &[u8], header_size: usize) -> Result<(), Violation> {
+    let guid_header_size = mem::size_of::<header::GuidDefined>();
+    type_specific_content(section, header_size, guid_header_size)?;
+    let guid_header = &section[header_size..header_size + guid_header_size];
+    let section_definition_guid = efi::Guid::from_bytes(guid_header[..16].try_into().unwrap());
+    let data_offset = u16::from_le_bytes(guid_header[16..18].try_into().unwrap());
+    let attributes = u16::from_le_bytes(guid_header[18..20].try_into().unwrap());
+
+    if (data_offset as usize) < header_size + guid_header_size || data_offset as usize > section.len() {
+        Err(Violation::InvalidDataOffset(data_offset))?;
+    }
+
+    let defined_attributes = header::GUIDED_SECTION_PROCESSING_REQUIRED | header::GUIDED_SECTION_AUTH_STATUS_VALID;
+    if attributes & !defined_attributes != 0 {
+        Err(Violation::ReservedAttributes(attributes))?;
+    }
+
+    let required = match section_definition_guid {
+        guid::BROTLI_SECTION
+        | guid::LZMA_SECTION
+        | guid::LZMA_F86_SECTION
+        | guid::LZMA_PARALLEL_SECTION
+        | guid::TIANO_DECOMPRESS_SECTION => header::GUIDED_SECTION_PROCESSING_REQUIRED,
+        guid::CRC32_SECTION | guid::FIRMWARE_CONTENTS_SIGNED => header::GUIDED_SECTION_AUTH_STATUS_VALID,
+        _ => 0,
+    };
+    if attributes & required != required {
+        Err(Violation::MissingAttributes { section_definition_guid, required })?;
+    }
+    Ok(())
+}
+
+fn validate_string(content: &[u8]) -> Result<(), Violation> {
+    if content.len() < 2 || !content.len().is_multiple_of(2) || content[content.len() - 2..] != [0, 0] {
+        Err(Violation::UnterminatedString)?;
+    }
+    Ok(())
+}
+
+/// The outcome of replaying one case of a corpus through the validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayResult<'a> {
+    /// The name of the case, such as the file it was captured to.
+    pub name: &'a str,
+    /// The result of validating the case.
+    pub result: Result<(), Violation>,
+}
+
+/// Replays a corpus of captured sections through the validator, returning the result of each case in order.
+///
+/// Each case is a name and the serialized section. A corpus of malformed sections is expected to fail validation in
+/// every case; a case that passes is a corruption the validator does not catch.
+pub fn replay_corpus<'a>(cases: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Vec<ReplayResult<'a>> {
+    cases.into_iter().map(|(name, section)| ReplayResult { name, result: validate_section(section) }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{section::Section, volume::VolumeRef};
+    use alloc::{string::String, vec};
+    use std::{env, fs, path::Path};
+
+    fn raw_section(section_type: u8, content: &[u8]) -> Vec<u8> {
+        let size = (4 + content.len()) as u32;
+        let mut section = size.to_le_bytes()[..3].to_vec();
+        section.push(section_type);
+        section.extend_from_slice(content);
+        section
+    }
+
+    fn guided_section(section_definition_guid: efi::Guid, attributes: u16, data_offset: u16) -> Vec<u8> {
+        let mut content = section_definition_guid.as_bytes().to_vec();
+        content.extend_from_slice(&data_offset.to_le_bytes());
+        content.extend_from_slice(&attributes.to_le_bytes());
+        content.extend_from_slice(&[0; 8]);
+        raw_section(raw_type::encapsulated::GUID_DEFINED, &content)
+    }
+
+    #[test]
+    fn test_valid_sections() {
+        assert_eq!(validate_section(&raw_section(raw_type::RAW, &[1, 2, 3])), Ok(()));
+        assert_eq!(validate_section(&raw_section(raw_type::USER_INTERFACE, &[b'a', 0, 0, 0])), Ok(()));
+        assert_eq!(validate_section(&raw_section(raw_type::VERSION, &[1, 0])), Ok(()));
+        assert_eq!(
+            validate_section(&guided_section(guid::LZMA_SECTION, header::GUIDED_SECTION_PROCESSING_REQUIRED, 24)),
+            Ok(())
+        );
+        assert_eq!(validate_section(&raw_section(raw_type::encapsulated::COMPRESSION, &[2, 0, 0, 0, 0, 7, 7])), Ok(()));
+
+        let mut extended = vec![0xff, 0xff, 0xff, raw_type::RAW];
+        extended.extend_from_slice(&10u32.to_le_bytes());
+        extended.extend_from_slice(&[0, 0]);
+        assert_eq!(validate_section(&extended), Ok(()));
+    }
+
+    #[test]
+    fn test_violations() {
+        assert_eq!(validate_section(&[4, 0]), Err(Violation::Truncated));
+        assert_eq!(validate_section(&raw_section(raw_type::RAW, &[1])[..4]), Err(Violation::Truncated));
+        assert_eq!(validate_section(&[2, 0, 0, raw_type::RAW]), Err(Violation::SizeSmallerThanHeader));
+        assert_eq!(validate_section(&raw_section(0x1a, &[])), Err(Violation::ReservedSectionType(0x1a)));
+        assert_eq!(
+            validate_section(&guided_section(guid::CRC32_SECTION, 0x02, 8)),
+            Err(Violation::InvalidDataOffset(8))
+        );
+        assert_eq!(
+            validate_section(&guided_section(guid::CRC32_SECTION, 0x02, 40)),
+            Err(Violation::InvalidDataOffset(40))
+        );
+        assert_eq!(
+            validate_section(&guided_section(efi::Guid::from_bytes(&[0xa5; 16]), 0x8001, 24)),
+            Err(Violation::ReservedAttributes(0x8001))
+        );
+        assert_eq!(
+            validate_section(&guided_section(guid::LZMA_SECTION, header::GUIDED_SECTION_AUTH_STATUS_VALID, 24)),
+            Err(Violation::MissingAttributes {
+                section_definition_guid: guid::LZMA_SECTION,
+                required: header::GUIDED_SECTION_PROCESSING_REQUIRED
+            })
+        );
+        assert_eq!(
+            validate_section(&raw_section(raw_type::encapsulated::COMPRESSION, &[0, 0, 0, 0, 9])),
+            Err(Violation::UnknownCompressionType(9))
+        );
+        assert_eq!(
+            validate_section(&raw_section(raw_type::encapsulated::COMPRESSION, &[4, 0, 0, 0, 0, 7])),
+            Err(Violation::UncompressedLengthMismatch)
+        );
+        assert_eq!(
+            validate_section(&raw_section(raw_type::USER_INTERFACE, &[b'a', 0])),
+            Err(Violation::UnterminatedString)
+        );
+        assert_eq!(validate_section(&raw_section(raw_type::USER_INTERFACE, &[])), Err(Violation::UnterminatedString));
+    }
+
+    #[test]
+    fn test_strict_validation_rejects_parse() {
+        // Valid to parse, but sets reserved attribute bits.
+        let section = guided_section(efi::Guid::from_bytes(&[0xa5; 16]), 0x8000, 24);
+        assert!(!strict_validation());
+        assert!(Section::new_from_buffer(&section).is_ok());
+
+        set_strict_validation(true);
+        let result = Section::new_from_buffer(&section);
+        set_strict_validation(false);
+        assert_eq!(result.err(), Some(FirmwareFileSystemError::InvalidHeader));
+    }
+
+    #[test]
+    fn test_test_resources_pass_strict_validation() {
+        let root = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("test_resources");
+        for name in ["DXEFV.Fv", "FVMAIN_COMPACT.Fv", "GIGANTOR.Fv", "LZMATEST.Fv"] {
+            let fv = fs::read(root.join(name)).unwrap();
+            for file in VolumeRef::new(&fv).unwrap().files() {
+                let file = file.unwrap();
+                let mut content = file.content();
+                while !content.is_empty() {
+                    validate_section(content).unwrap_or_else(|violation| panic!("{name}: {violation}"));
+                    let size = Section::new_from_buffer(content).unwrap().size().unwrap();
+                    content = content.get(size.next_multiple_of(4)..).unwrap_or_default();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay_malformed_section_corpus() {
+        let root = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("test_resources/malformed_sections");
+        let mut corpus: Vec<(String, Vec<u8>)> = fs::read_dir(root)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read(&path).unwrap())
+            })
+            .collect();
+        corpus.sort();
+        assert!(!corpus.is_empty());
+
+        let results = replay_corpus(corpus.iter().map(|(name, section)| (name.as_str(), section.as_slice())));
+        for result in &results {
+            assert!(result.result.is_err(), "{} passed validation", result.name);
+        }
+        let violation = |name: &str| results.iter().find(|result| result.name == name).unwrap().result;
+        assert_eq!(violation("guid_data_offset_in_header.bin"), Err(Violation::InvalidDataOffset(0x8)));
+        assert_eq!(violation("size_smaller_than_header.bin"), Err(Violation::SizeSmallerThanHeader));
+    }
+}
@@ -0,0 +1,458 @@
+//! Incremental verification of Firmware Volumes (FVs) as they are read.
+//!
+//! [`VolumeVerifier`] checks the same FV header and FFS file header and data checksums as
+//! [`VolumeRef::new`](crate::volume::VolumeRef::new) and [`FileRef::new`](crate::file::FileRef::new), but consumes the
+//! FV in blocks as they are streamed from flash. Corruption is reported by the block that contains it, so a caller
+//! shadowing an FV to RAM can stop reading early, and only the FV header and the current file header are buffered.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::{mem, ptr};
+
+use patina::{
+    base::align_up,
+    pi::fw_fs::{
+        ffs::{attributes, file},
+        fv,
+    },
+};
+
+use crate::{FirmwareFileSystemError, file::FileRef, volume::VolumeRef};
+
+/// Where the verifier is within the FV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Buffering the FV header and extended header.
+    VolumeHeader,
+    /// Skipping bytes up to the next file, which starts at `file_offset`.
+    Padding { file_offset: u64 },
+    /// Buffering the header of the file that starts at `file_offset`.
+    FileHeader { file_offset: u64 },
+    /// Summing the data of the file that ends at `file_end`.
+    FileData { file_end: u64, checksum: bool, sum: u8, pad: bool },
+    /// Skipping the free space at the end of the FV.
+    FreeSpace,
+}
+
+/// Verifies a Firmware Volume incrementally as it is read, block by block.
+///
+/// Feed the FV to [`VolumeVerifier::update`] in order, in blocks of any size. Each call returns an error as soon as
+/// the data seen so far is known to be corrupt, and every later call returns the same error. Once the FV header has
+/// been consumed, [`VolumeVerifier::fv_length`] reports the length of the FV, so the caller can size the destination
+/// of a shadow copy. Bytes fed beyond the end of the FV are ignored.
+///
+/// After the last block, [`VolumeVerifier::finish`] confirms that the whole FV was received.
+///
+/// ## Example
+///
+/// ```rust no_run
+/// use patina_ffs::{verify::VolumeVerifier, volume::Volume};
+/// use patina::pi::fw_fs::fv::BlockMapEntry;
+///
+/// let fv_bytes = Volume::new(vec![BlockMapEntry { num_blocks: 4, length: 0x1000 }]).serialize().unwrap();
+/// let mut verifier = VolumeVerifier::new();
+/// for block in fv_bytes.chunks(0x1000) {
+///     verifier.update(block).unwrap();
+/// }
+/// verifier.finish().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct VolumeVerifier {
+    state: State,
+    /// Bytes of the FV consumed so far.
+    offset: u64,
+    /// The FV header or file header being buffered.
+    pending: Vec<u8>,
+    fv_length: Option<u64>,
+    erase_byte: u8,
+    files_verified: usize,
+    error: Option<FirmwareFileSystemError>,
+}
+
+impl Default for VolumeVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VolumeVerifier {
+    /// Creates a verifier for an FV that starts with the first block passed to [`VolumeVerifier::update`].
+    pub fn new() -> Self {
+        Self {
+            state: State::VolumeHeader,
+            offset: 0,
+            pending: Vec::new(),
+            fv_length: None,
+            erase_byte: 0xff,
+            files_verified: 0,
+            error: None,
+        }
+    }
+
+    /// The FV length from the FV header, once the header has been verified.
+    pub fn fv_length(&self) -> Option<u64> {
+        self.fv_length
+    }
+
+    /// The number of bytes of the FV consumed so far.
+    pub fn bytes_verified(&self) -> u64 {
+        self.offset
+    }
+
+    /// The number of files whose headers and data have been verified.
+    ///
+    /// As with [`VolumeRef::files`], PAD files are verified but not counted.
+    pub fn files_verified(&self) -> usize {
+        self.files_verified
+    }
+
+    /// Verifies the next block of the FV.
+    ///
+    /// ## Errors
+    ///
+    /// - [`FirmwareFileSystemError::InvalidHeader`]: the FV header or a file header is malformed, or its checksum does
+    ///   not match.
+    /// - [`FirmwareFileSystemError::InvalidBlockMap`]: the FV block map is malformed.
+    /// - [`FirmwareFileSystemError::Unsupported`]: the FV revision or file system is not supported.
+    /// - [`FirmwareFileSystemError::InvalidState`]: a file is not in the `EFI_FILE_DATA_VALID` state.
+    /// - [`FirmwareFileSystemError::DataCorrupt`]: a file data checksum does not match.
+    pub fn update(&mut self, block: &[u8]) -> Result<(), FirmwareFileSystemError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.consume(block).inspect_err(|err| self.error = Some(*err))
+    }
+
+    /// Confirms that the whole FV has been received and verified.
+    ///
+    /// Returns [`FirmwareFileSystemError::InvalidState`] if the FV ended early, or the error returned by
+    /// [`VolumeVerifier::update`] if verification failed.
+    pub fn finish(&self) -> Result<(), FirmwareFileSystemError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        match self.fv_length {
+            Some(fv_length) if self.offset == fv_length => Ok(()),
+            _ => Err(FirmwareFileSystemError::InvalidState),
+        }
+    }
+
+    fn consume(&mut self, mut block: &[u8]) -> Result<(), FirmwareFileSystemError> {
+        while !block.is_empty() {
+            if let Some(fv_length) = self.fv_length {
+                let remaining = fv_length - self.offset;
+                if remaining == 0 {
+                    return Ok(());
+                }
+                block = &block[..block.len().min(usize::try_from(remaining).unwrap_or(usize::MAX))];
+            }
+
+            let consumed = match self.state {
+                State::VolumeHeader => self.consume_volume_header(block)?,
+                State::Padding { file_offset } => {
+                    let consumed = block.len().min((file_offset - self.offset) as usize);
+                    if self.offset + consumed as u64 == file_offset {
+                        self.state = self.next_file_state(file_offset);
+                    }
+                    consumed
+                }
+                State::FileHeader { file_offset } => self.consume_file_header(file_offset, block)?,
+                State::FileData { file_end, checksum, sum, pad } => {
+                    let consumed = block.len().min((file_end - self.offset) as usize);
+                    let sum = block[..consumed].iter().fold(sum, |sum, val| sum.wrapping_add(*val));
+                    self.state = State::FileData { file_end, checksum, sum, pad };
+                    if self.offset + consumed as u64 == file_end {
+                        if checksum && sum != 0 {
+                            Err(FirmwareFileSystemError::DataCorrupt)?;
+                        }
+                        if !pad {
+                            self.files_verified += 1;
+                        }
+                        // Per the PI spec, the next file starts at the next 8-byte aligned offset after this one.
+                        let file_offset = align_up(file_end, 8).map_err(|_| FirmwareFileSystemError::DataCorrupt)?;
+                        self.state = State::Padding { file_offset };
+                        if file_offset == file_end {
+                            self.state = self.next_file_state(file_offset);
+                        }
+                    }
+                    consumed
+                }
+                State::FreeSpace => block.len(),
+            };
+            self.offset += consumed as u64;
+            block = &block[consumed..];
+        }
+        Ok(())
+    }
+
+    /// Buffers the FV header and extended header, and verifies them once they are complete.
+    fn consume_volume_header(&mut self, block: &[u8]) -> Result<usize, FirmwareFileSystemError> {
+        let required = self.volume_header_size()?;
+        let consumed = block.len().min(required - self.pending.len());
+        self.pending.extend_from_slice(&block[..consumed]);
+
+        // The extended header size is only known once the start of the extended header has been read.
+        if self.pending.len() < required || self.volume_header_size()? > required {
+            return Ok(consumed);
+        }
+
+        let volume = VolumeRef::parse(&self.pending, u64::MAX)?;
+        let content_offset = volume.content_offset() as u64;
+        // Bytes past the start of the first file have already been consumed as header bytes.
+        if content_offset < self.pending.len() as u64 {
+            Err(FirmwareFileSystemError::InvalidHeader)?;
+        }
+
+        self.fv_length = Some(volume.size());
+        self.erase_byte = volume.erase_byte();
+        self.pending.clear();
+        self.state = State::Padding { file_offset: content_offset };
+        if content_offset == self.offset + consumed as u64 {
+            self.state = self.next_file_state(content_offset);
+        }
+        Ok(consumed)
+    }
+
+    /// Returns the number of bytes of FV header and extended header to buffer, given the bytes buffered so far.
+    fn volume_header_size(&self) -> Result<usize, FirmwareFileSystemError> {
+        let fixed_size = mem::size_of::<fv::Header>();
+        if self.pending.len() < fixed_size {
+            return Ok(fixed_size);
+        }
+
+        // SAFETY: pending is large enough to contain the header.
+        let fv_header = unsafe { ptr::read_unaligned(self.pending.as_ptr() as *const fv::Header) };
+        // Check the signature before buffering more, so a block that is not an FV is rejected immediately.
+        if fv_header.signature != u32::from_le_bytes(*b"_FVH") {
+            Err(FirmwareFileSystemError::InvalidHeader)?;
+        }
+
+        let mut size = fixed_size.max(fv_header.header_length as usize);
+        if fv_header.ext_header_offset != 0 {
+            let ext_header_offset = fv_header.ext_header_offset as usize;
+            size = size.max(ext_header_offset + mem::size_of::<fv::ExtHeader>());
+            if let Some(ext_header) = self.pending.get(ext_header_offset..)
+                && ext_header.len() >= mem::size_of::<fv::ExtHeader>()
+            {
+                // SAFETY: pending is large enough to contain the extended header.
+                let ext_header = unsafe { ptr::read_unaligned(ext_header.as_ptr() as *const fv::ExtHeader) };
+                size = size.max(ext_header_offset + ext_header.ext_header_size as usize);
+            }
+        }
+
+        // The headers must lie within the FV, which also bounds how much is buffered.
+        if size as u64 > fv_header.fv_length {
+            Err(FirmwareFileSystemError::InvalidHeader)?;
+        }
+        Ok(size)
+    }
+
+    /// Returns the state for a file that may start at `file_offset`.
+    fn next_file_state(&self, file_offset: u64) -> State {
+        let fv_length = self.fv_length.unwrap_or(u64::MAX);
+        // As with VolumeRef::files, a trailing space too small for a file header ends the file list.
+        if fv_length.saturating_sub(file_offset) < mem::size_of::<file::Header>() as u64 {
+            State::FreeSpace
+        } else {
+            State::FileHeader { file_offset }
+        }
+    }
+
+    /// Buffers a file header, and verifies it once it is complete.
+    fn consume_file_header(&mut self, file_offset: u64, block: &[u8]) -> Result<usize, FirmwareFileSystemError> {
+        let mut required = mem::size_of::<file::Header>();
+        if let Some(&file_attributes) = self.pending.get(mem::offset_of!(file::Header, attributes))
+            && file_attributes & attributes::raw::LARGE_FILE != 0
+        {
+            required = mem::size_of::<file::Header2>();
+        }
+        let consumed = block.len().min(required - self.pending.len());
+        self.pending.extend_from_slice(&block[..consumed]);
+
+        if self.pending.len() == mem::size_of::<file::Header>()
+            && self.pending.iter().all(|&byte| byte == self.erase_byte)
+        {
+            // An erased header marks the start of free space.
+            self.pending.clear();
+            self.state = State::FreeSpace;
+            return Ok(consumed);
+        }
+
+        let fv_length = self.fv_length.unwrap_or(u64::MAX);
+        if self.pending.len() < required {
+            // A large file header that does not fit in the FV is malformed.
+            if file_offset + required as u64 > fv_length {
+                Err(FirmwareFileSystemError::InvalidHeader)?;
+            }
+            return Ok(consumed);
+        }
+        // A standard header may announce a large file, which needs more bytes.
+        let (header, size, content_offset) = match FileRef::parse_size(&self.pending) {
+            Err(FirmwareFileSystemError::InvalidHeader) if self.pending.len() < mem::size_of::<file::Header2>() => {
+                if file_offset + mem::size_of::<file::Header2>() as u64 > fv_length {
+                    Err(FirmwareFileSystemError::InvalidHeader)?;
+                }
+                return Ok(consumed);
+            }
+            result => result?,
+        };
+
+        if file_offset + size as u64 > fv_length || size < content_offset {
+            Err(FirmwareFileSystemError::InvalidHeader)?;
+        }
+        FileRef::verify_header(&self.pending[..content_offset], &header)?;
+
+        self.pending.clear();
+        let file_end = file_offset + size as u64;
+        let checksum = header.attributes & attributes::raw::CHECKSUM != 0;
+        let pad = header.file_type == file::raw::r#type::FFS_PAD;
+        self.state = State::FileData { file_end, checksum, sum: header.integrity_check_file, pad };
+        Ok(consumed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file::File,
+        section::{Section, SectionHeader},
+        volume::Volume,
+    };
+    use alloc::{vec, vec::Vec};
+    use patina::pi::fw_fs::{ffs::section, fv::BlockMapEntry};
+    use r_efi::efi;
+    use std::{env, fs, path::Path};
+
+    fn raw_file(name: u8, data: &[u8], checksum: bool) -> File {
+        let mut file = File::new(efi::Guid::from_bytes(&[name; 16]), file::raw::r#type::FREEFORM);
+        file.set_data_checksum(checksum);
+        let header = SectionHeader::Standard(section::raw_type::RAW, data.len() as u32);
+        file.sections_mut().push(Section::new_from_header_with_data(header, data.to_vec()).unwrap());
+        file
+    }
+
+    fn test_volume() -> Vec<u8> {
+        let mut volume = Volume::new(vec![BlockMapEntry { num_blocks: 4, length: 0x1000 }]);
+        volume.files_mut().push(raw_file(1, b"checksummed file data", true));
+        volume.files_mut().push(raw_file(2, &[0x5a; 0x123], false));
+        volume.files_mut().push(raw_file(3, &[0xa5; 0x801], true));
+        volume.serialize().unwrap()
+    }
+
+    fn verify(fv: &[u8], block_size: usize) -> Result<VolumeVerifier, FirmwareFileSystemError> {
+        let mut verifier = VolumeVerifier::new();
+        for block in fv.chunks(block_size) {
+            verifier.update(block)?;
+        }
+        verifier.finish()?;
+        Ok(verifier)
+    }
+
+    fn file_count(fv: &[u8]) -> usize {
+        VolumeRef::new(fv).unwrap().files().count()
+    }
+
+    #[test]
+    fn test_verify_in_blocks_of_any_size() {
+        let fv = test_volume();
+        let files = file_count(&fv);
+        assert!(files >= 3);
+        for block_size in [1, 3, 8, 0x30, 0x1000, fv.len()] {
+            let verifier = verify(&fv, block_size).unwrap();
+            assert_eq!(verifier.files_verified(), files, "block size {block_size:#x}");
+            assert_eq!(verifier.bytes_verified(), fv.len() as u64);
+            assert_eq!(verifier.fv_length(), Some(fv.len() as u64));
+        }
+    }
+
+    #[test]
+    fn test_verify_test_resources() {
+        let root = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("test_resources");
+        for name in ["DXEFV.Fv", "FVMAIN_COMPACT.Fv", "GIGANTOR.Fv", "LZMATEST.Fv"] {
+            let fv = fs::read(root.join(name)).unwrap();
+            let verifier = verify(&fv, 0x1000).unwrap_or_else(|err| panic!("{name}: {err:?}"));
+            assert_eq!(verifier.files_verified(), file_count(&fv), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_data_corruption_aborts_early() {
+        let mut fv = test_volume();
+        // Corrupt the data of the first (checksummed) file.
+        let volume = VolumeRef::new(&fv).unwrap();
+        let first_file = volume.content_offset();
+        fv[first_file + 0x30] ^= 0x01;
+
+        let mut verifier = VolumeVerifier::new();
+        let blocks = fv.chunks(0x100).collect::<Vec<_>>();
+        let failed_block = blocks.iter().position(|block| verifier.update(block).is_err()).unwrap();
+        assert_eq!(failed_block, (first_file + 0x30) / 0x100);
+        assert_eq!(verifier.update(blocks[failed_block + 1]), Err(FirmwareFileSystemError::DataCorrupt));
+        assert_eq!(verifier.finish(), Err(FirmwareFileSystemError::DataCorrupt));
+        assert_eq!(
+            VolumeRef::new(&fv).unwrap().files().find_map(Result::err),
+            Some(FirmwareFileSystemError::DataCorrupt)
+        );
+    }
+
+    #[test]
+    fn test_unchecksummed_data_is_not_verified() {
+        let mut fv = test_volume();
+        let volume = VolumeRef::new(&fv).unwrap();
+        let second_file = volume.files().nth(1).unwrap().unwrap();
+        let offset = second_file.data().as_ptr() as usize - fv.as_ptr() as usize + second_file.size() - 1;
+        fv[offset] ^= 0x01;
+        assert!(verify(&fv, 0x200).is_ok());
+    }
+
+    #[test]
+    fn test_header_corruption() {
+        let fv = test_volume();
+
+        let mut bad_volume_header = fv.clone();
+        bad_volume_header[mem::offset_of!(fv::Header, checksum)] ^= 0x01;
+        assert_eq!(verify(&bad_volume_header, 0x10).err(), Some(FirmwareFileSystemError::InvalidHeader));
+
+        let mut bad_signature = fv.clone();
+        bad_signature[mem::offset_of!(fv::Header, signature)] = b'X';
+        let mut verifier = VolumeVerifier::new();
+        assert_eq!(verifier.update(&bad_signature[..0x40]), Err(FirmwareFileSystemError::InvalidHeader));
+
+        let content_offset = VolumeRef::new(&fv).unwrap().content_offset();
+        let mut bad_file_header = fv.clone();
+        bad_file_header[content_offset + mem::offset_of!(file::Header, integrity_check_header)] ^= 0x01;
+        assert_eq!(verify(&bad_file_header, 0x7).err(), Some(FirmwareFileSystemError::InvalidHeader));
+
+        let mut bad_state = fv.clone();
+        bad_state[content_offset + mem::offset_of!(file::Header, state)] ^= 0x04;
+        assert_eq!(verify(&bad_state, 0x1000).err(), Some(FirmwareFileSystemError::InvalidState));
+    }
+
+    #[test]
+    fn test_truncated_volume() {
+        let fv = test_volume();
+        let mut verifier = VolumeVerifier::new();
+        verifier.update(&fv[..fv.len() - 1]).unwrap();
+        assert_eq!(verifier.finish(), Err(FirmwareFileSystemError::InvalidState));
+
+        let mut verifier = VolumeVerifier::new();
+        verifier.update(&fv[..0x10]).unwrap();
+        assert_eq!(verifier.fv_length(), None);
+        assert_eq!(verifier.finish(), Err(FirmwareFileSystemError::InvalidState));
+    }
+
+    #[test]
+    fn test_trailing_bytes_are_ignored() {
+        let mut fv = test_volume();
+        let fv_length = fv.len() as u64;
+        fv.extend_from_slice(&[0x12; 0x40]);
+        let verifier = verify(&fv, 0x1000).unwrap();
+        assert_eq!(verifier.bytes_verified(), fv_length);
+    }
+}
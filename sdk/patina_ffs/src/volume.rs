@@ -3,6 +3,7 @@
 //! This module provides:
 //! - `VolumeRef`: a zero-copy, read-only view over a serialized FV backed by a byte slice.
 //! - `Volume`: an owned builder for assembling FVs from a block map and FFS files and serializing.
+//! - `SpaceUsage`: header, file, pad, and free space accounting for an FV or one of its blocks.
 //!
 //! It validates FV headers and block maps, iterates contained files, and serializes with proper
 //! alignment, optional extended headers, and checksum calculation per the PI specification.
@@ -15,7 +16,9 @@
 //!
 use alloc::vec::Vec;
 use core::{
-    fmt, iter, mem, ptr,
+    fmt, iter, mem,
+    ops::Range,
+    ptr,
     slice::{self, from_raw_parts},
 };
 use patina::base::align_up;
@@ -66,6 +69,14 @@ impl<'a> VolumeRef<'a> {
     /// assert!(fv_ref.size() >= 4096);
     /// ```
     pub fn new(buffer: &'a [u8]) -> Result<Self, FirmwareFileSystemError> {
+        Self::parse(buffer, buffer.len() as u64)
+    }
+
+    /// Parse the FV metadata at the start of `buffer`, accepting FV lengths up to `fv_length_limit`.
+    ///
+    /// `buffer` must hold the FV header and any extended header, but need not hold the whole FV. This allows the
+    /// metadata to be validated before the rest of the FV has been read.
+    pub(crate) fn parse(buffer: &'a [u8], fv_length_limit: u64) -> Result<Self, FirmwareFileSystemError> {
         // Verify that buffer has enough storage for a volume header.
         if buffer.len() < mem::size_of::<fv::Header>() {
             Err(FirmwareFileSystemError::InvalidHeader)?;
@@ -122,7 +133,7 @@ impl<'a> VolumeRef<'a> {
         }
 
         // fv_length: must be less than or equal to fv_data buffer length
-        if fv_header.fv_length > buffer.len() as u64 {
+        if fv_header.fv_length > fv_length_limit {
             Err(FirmwareFileSystemError::InvalidHeader)?;
         }
 
@@ -339,6 +350,102 @@ impl<'a> VolumeRef<'a> {
         })
     }
 
+    /// Account for the space used by the FV header, files, and padding, and the free space left for new files.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust no_run
+    /// use patina_ffs::volume::VolumeRef;
+    ///
+    /// let fv_bytes = std::fs::read("DXEFV.Fv").unwrap();
+    /// let fv_ref = VolumeRef::new(&fv_bytes).unwrap();
+    /// let usage = fv_ref.space_usage().unwrap();
+    /// println!("DXEFV: {usage}");
+    /// ```
+    pub fn space_usage(&self) -> Result<SpaceUsage, FirmwareFileSystemError> {
+        let mut usage = SpaceUsage::default();
+        for (range, region) in self.regions()? {
+            usage.add(region, range.len());
+        }
+        Ok(usage)
+    }
+
+    /// Account for the space in each block of the FV, indexed by Logical Block Address (LBA).
+    ///
+    /// A file spanning several blocks is counted in each block by the bytes it occupies there.
+    pub fn block_usage(&self) -> Result<Vec<SpaceUsage>, FirmwareFileSystemError> {
+        let regions = self.regions()?;
+        let mut blocks = Vec::new();
+        let mut block_start = 0usize;
+        for entry in &self.block_map {
+            for _ in 0..entry.num_blocks {
+                let block = block_start..block_start + entry.length as usize;
+                let mut usage = SpaceUsage::default();
+                for (range, region) in &regions {
+                    let overlap = range.end.min(block.end).saturating_sub(range.start.max(block.start));
+                    usage.add(*region, overlap);
+                }
+                blocks.push(usage);
+                block_start = block.end;
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Returns `true` if `file` fits in the free space of the FV.
+    ///
+    /// Accounts for the PAD file needed to align the file content as [`Volume::serialize`] would place the file after
+    /// the last file of the FV, and for the file size limit of FFSv2 volumes.
+    pub fn fits(&self, file: &File) -> Result<bool, FirmwareFileSystemError> {
+        let file_buffer = file.serialize()?;
+        if file_buffer.len() >= fv::FFS_V2_MAX_FILE_SIZE
+            && self.file_system_guid() != ffs::guid::EFI_FIRMWARE_FILE_SYSTEM3_GUID
+        {
+            return Ok(false);
+        }
+        let file_ref = FileRef::new(&file_buffer)?;
+
+        let size = self.size() as usize;
+        let free_offset = self.regions()?.last().map_or(size, |(range, _)| range.start);
+        let pad_size = match alignment_pad_file(free_offset, &file_ref)? {
+            Some(pad_file) => pad_file.serialize()?.len(),
+            None => 0,
+        };
+        Ok(free_offset + pad_size + file_buffer.len() <= size)
+    }
+
+    /// Split the FV into consecutive regions of header, files, padding, and the free space after the last file.
+    fn regions(&self) -> Result<Vec<(Range<usize>, Region)>, FirmwareFileSystemError> {
+        let size = (self.size() as usize).min(self.data.len());
+        let content_offset = self.content_offset.min(size);
+
+        let mut regions = Vec::new();
+        regions.push((0..content_offset, Region::Header));
+        let mut offset = content_offset;
+        for file in FileRefIter::new(&self.data[content_offset..size], self.erase_byte()) {
+            let file = file?;
+            let end = offset + file.size();
+            let region =
+                if file.file_type_raw() == ffs::file::raw::r#type::FFS_PAD { Region::Pad } else { Region::File };
+            regions.push((offset..end, region));
+
+            // The next file starts at the next 8-byte aligned offset, so the bytes up to it are padding.
+            let next = align_up(end as u64, 8).map_err(|_| FirmwareFileSystemError::DataCorrupt)? as usize;
+            let next = next.min(size);
+            if next > end {
+                regions.push((end..next, Region::Pad));
+            }
+            offset = next;
+        }
+        regions.push((offset..size, Region::Free));
+        Ok(regions)
+    }
+
+    /// Offset of the first file from the start of the FV.
+    pub(crate) fn content_offset(&self) -> usize {
+        self.content_offset
+    }
+
     fn revision(&self) -> u8 {
         self.fv_header.revision
     }
@@ -420,6 +527,100 @@ impl<'a> Iterator for FileRefIter<'a> {
     }
 }
 
+/// The kind of space a region of an FV holds.
+#[derive(Clone, Copy)]
+enum Region {
+    Header,
+    File,
+    Pad,
+    Free,
+}
+
+/// Space accounting for a Firmware Volume, or for one of its blocks.
+///
+/// Every byte is counted exactly once, so the fields add up to the size of the FV or block.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SpaceUsage {
+    /// Bytes used by the FV header, block map, and extended header.
+    pub header: usize,
+    /// Bytes used by files, including their headers.
+    pub files: usize,
+    /// Bytes used by PAD files and by the alignment padding between files.
+    pub pad: usize,
+    /// Bytes after the last file, available for new files.
+    pub free: usize,
+}
+
+impl SpaceUsage {
+    /// Bytes used by the header and files.
+    pub fn used(&self) -> usize {
+        self.header + self.files
+    }
+
+    /// Total bytes accounted for.
+    pub fn total(&self) -> usize {
+        self.header + self.files + self.pad + self.free
+    }
+
+    fn add(&mut self, region: Region, length: usize) {
+        match region {
+            Region::Header => self.header += length,
+            Region::File => self.files += length,
+            Region::Pad => self.pad += length,
+            Region::Free => self.free += length,
+        }
+    }
+}
+
+impl fmt::Display for SpaceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = if self.total() == 0 { 0 } else { self.used() * 100 / self.total() };
+        write!(
+            f,
+            "{:#x} of {:#x} bytes used ({}%), {:#x} pad, {:#x} free",
+            self.used(),
+            self.total(),
+            percent,
+            self.pad,
+            self.free
+        )
+    }
+}
+
+/// The PAD file to insert at the 8-byte aligned `offset` before `file_ref` so that its content meets the alignment in
+/// its attributes, or `None` if the content is already aligned.
+fn alignment_pad_file(offset: usize, file_ref: &FileRef) -> Result<Option<File>, FirmwareFileSystemError> {
+    let required_content_alignment = file_ref.fv_attributes() & fv::file::raw::attribute::ALIGNMENT;
+    let required_content_alignment: usize = 1 << required_content_alignment;
+
+    if (offset + file_ref.content_offset()).is_multiple_of(required_content_alignment) {
+        return Ok(None);
+    }
+
+    //Per spec, max required_content_alignment is pad files is 16M (2^24). That means that pad file size
+    //will always be less than 16M so we can always use Header (instead of Header2) for pad header.
+    assert!(required_content_alignment < 0x1000000);
+
+    let pad_len_base = offset + mem::size_of::<ffs::file::Header>() + file_ref.content_offset();
+    let rem = pad_len_base % required_content_alignment;
+    let pad_len = if rem == 0 { 0 } else { required_content_alignment - rem };
+
+    // check the padding math.
+    debug_assert_eq!(
+        (offset + mem::size_of::<ffs::file::Header>() + pad_len + file_ref.content_offset())
+            % required_content_alignment,
+        0
+    );
+
+    let mut pad_file = File::new(efi::Guid::from_bytes(&[0xffu8; 16]), ffs::file::raw::r#type::FFS_PAD);
+    let pad_section = Section::new_from_header_with_data(
+        section::SectionHeader::Pad(pad_len.try_into().map_err(|_| FirmwareFileSystemError::InvalidHeader)?),
+        iter::repeat_n(0xffu8, pad_len).collect(),
+    )?;
+    pad_file.sections_mut().push(pad_section);
+    Ok(Some(pad_file))
+}
+
 enum Capacity {
     Unbounded,
     Size(usize),
@@ -593,38 +794,9 @@ impl Volume {
 
             let file_ref = FileRef::new(file_buffer)?;
 
-            //check if a pad file needs to be inserted to align the file content.
-            let required_content_alignment = file_ref.fv_attributes() & fv::file::raw::attribute::ALIGNMENT;
-            let required_content_alignment: usize = 1 << required_content_alignment;
-
-            if (fv_buffer.len() + file_ref.content_offset()) % required_content_alignment != 0 {
-                //need to insert a pad file to ensure content is aligned to the required alignment specified in the
-                //file attributes.
-
-                //Per spec, max required_content_alignment is pad files is 16M (2^24). That means that pad file size
-                //will always be less than 16M so we can always use Header (instead of Header2) for pad header.
-                assert!(required_content_alignment < 0x1000000);
-
-                let pad_len_base = fv_buffer.len() + mem::size_of::<ffs::file::Header>() + file_ref.content_offset();
-                let rem = pad_len_base % required_content_alignment;
-                let pad_len = if rem == 0 { 0 } else { required_content_alignment - rem };
-
-                // check the padding math.
-                debug_assert_eq!(
-                    (fv_buffer.len() + mem::size_of::<ffs::file::Header>() + pad_len + file_ref.content_offset())
-                        % required_content_alignment,
-                    0
-                );
-
-                let mut pad_file = File::new(efi::Guid::from_bytes(&[0xffu8; 16]), ffs::file::raw::r#type::FFS_PAD);
-                let pad_section = Section::new_from_header_with_data(
-                    section::SectionHeader::Pad(
-                        pad_len.try_into().map_err(|_| FirmwareFileSystemError::InvalidHeader)?,
-                    ),
-                    iter::repeat_n(0xffu8, pad_len).collect(),
-                )?;
-                pad_file.sections_mut().push(pad_section);
-
+            //insert a pad file if needed to ensure content is aligned to the required alignment specified in the
+            //file attributes.
+            if let Some(pad_file) = alignment_pad_file(fv_buffer.len(), &file_ref)? {
                 fv_buffer.extend(pad_file.serialize()?);
             }
 
@@ -676,6 +848,23 @@ impl Volume {
         Ok(fv_buffer)
     }
 
+    /// Account for the space the Firmware Volume would use if serialized now.
+    ///
+    /// A volume without a fixed capacity, such as one created with [`Volume::new`], has no free space.
+    pub fn space_usage(&self) -> Result<SpaceUsage, FirmwareFileSystemError> {
+        VolumeRef::new(&self.serialize()?)?.space_usage()
+    }
+
+    /// Returns `true` if `file` can be added to the Firmware Volume without exceeding its capacity.
+    ///
+    /// A volume without a fixed capacity, such as one created with [`Volume::new`], fits any file.
+    pub fn fits(&self, file: &File) -> Result<bool, FirmwareFileSystemError> {
+        match self.capacity {
+            Capacity::Unbounded => Ok(true),
+            Capacity::Size(_) => VolumeRef::new(&self.serialize()?)?.fits(file),
+        }
+    }
+
     /// Compose all sections for all files in the volume using the provided composer.
     ///
     /// Useful after editing encapsulated sections so that serialization has the
@@ -782,7 +971,7 @@ mod test {
     use crate::{
         FirmwareFileSystemError,
         section::{Section, SectionComposer, SectionExtractor, SectionHeader},
-        volume::{Volume, VolumeRef},
+        volume::{SpaceUsage, Volume, VolumeRef},
     };
 
     #[derive(Debug, Deserialize, Clone)]
@@ -1483,4 +1672,61 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_space_usage() -> Result<(), Box<dyn Error>> {
+        let root = Path::new(&env::var("CARGO_MANIFEST_DIR")?).join("test_resources");
+        let fv_bytes: Vec<u8> = fs::read(root.join("LZMATEST.Fv"))?;
+        let fv_ref = VolumeRef::new(&fv_bytes).map_err(stringify)?;
+
+        let usage = fv_ref.space_usage().map_err(stringify)?;
+        assert_eq!(usage.total(), fv_ref.size() as usize);
+        assert_eq!(usage.header, fv_ref.content_offset());
+        let file_bytes: usize =
+            fv_ref.files().map(|file| file.map(|file| file.size())).sum::<Result<_, _>>().map_err(stringify)?;
+        assert_eq!(usage.files, file_bytes);
+        assert!(usage.free > 0);
+
+        // Blocks account for the same space as the whole FV.
+        let blocks = fv_ref.block_usage().map_err(stringify)?;
+        let block_count: u32 = fv_ref.block_map().iter().map(|entry| entry.num_blocks).sum();
+        assert_eq!(blocks.len(), block_count as usize);
+        let mut sum = SpaceUsage::default();
+        for block in &blocks {
+            sum.header += block.header;
+            sum.files += block.files;
+            sum.pad += block.pad;
+            sum.free += block.free;
+        }
+        assert_eq!(sum, usage);
+
+        // A file that takes exactly the free space fits, and one a byte larger does not.
+        let raw_file = |content_size: usize| {
+            let mut file = crate::file::File::new(efi::Guid::from_bytes(&[0x5A; 16]), ffs::file::raw::r#type::RAW);
+            let section = Section::new_from_header_with_data(
+                SectionHeader::Standard(ffs::section::raw_type::RAW, content_size as u32),
+                vec![0x5A; content_size],
+            )
+            .unwrap();
+            file.sections_mut().push(section);
+            file
+        };
+        let headers = mem::size_of::<ffs::file::Header>() + mem::size_of::<ffs::section::Header>();
+        let largest = raw_file(usage.free - headers);
+        assert!(fv_ref.fits(&largest).map_err(stringify)?);
+        assert!(!fv_ref.fits(&raw_file(usage.free - headers + 1)).map_err(stringify)?);
+
+        // The owned volume agrees, and adding the largest file uses up the free space.
+        let mut fv: Volume = (&fv_ref).try_into().map_err(stringify)?;
+        assert_eq!(fv.space_usage().map_err(stringify)?, usage);
+        assert!(fv.fits(&largest).map_err(stringify)?);
+        fv.files_mut().push(largest);
+        let full = fv.space_usage().map_err(stringify)?;
+        assert_eq!(full.free, 0);
+        assert_eq!(full.total(), usage.total());
+        assert!(!fv.fits(&raw_file(0)).map_err(stringify)?);
+        assert!(full.to_string().starts_with(&format!("{:#x} of {:#x} bytes used", full.used(), full.total())));
+
+        Ok(())
+    }
 }
@@ -1,5 +1,10 @@
 //! Module for Brotli decompression.
 //!
+//! The content of a Brotli section is usually a single frame: the 64-bit decompressed size and the 64-bit scratch
+//! size, followed by a Brotli stream. Some toolchains split large payloads into several such frames and concatenate
+//! them within one section. The frames are decoded in turn and their output concatenated; trailing zero bytes after the
+//! last frame are treated as padding.
+//!
 //! ## License
 //!
 //! Copyright (c) Microsoft Corporation.
@@ -11,9 +16,12 @@ use alloc_no_stdlib::{self, SliceWrapper, SliceWrapperMut, define_index_ops_mut}
 use brotli_decompressor::{BrotliDecompressStream, BrotliResult, BrotliState, HuffmanCode};
 use patina::pi::fw_fs;
 use patina_ffs::{
-    FirmwareFileSystemError,
+    ErrorReason, ExtractionError, FirmwareFileSystemError,
     section::{Section, SectionExtractor, SectionHeader},
 };
+use r_efi::efi;
+
+use crate::{ExtractionTimeLimit, time_limit::POLL_INTERVAL};
 
 //Rebox and HeapAllocator exist to satisfy BrotliDecompress custom allocation requirements.
 //They essentially wrap Box for heap allocations.
@@ -50,7 +58,23 @@ impl<T: Clone> alloc_no_stdlib::Allocator<T> for HeapAllocator<T> {
     fn free_cell(self: &mut HeapAllocator<T>, _data: Rebox<T>) {}
 }
 
+/// Size of the decompressed size and scratch size fields that precede the Brotli stream.
+const BROTLI_HEADER_SIZE: usize = 16;
+
+/// Size of the GUID-specific header data of a Brotli section compressed with a custom dictionary.
+///
+/// The GUID-specific header data of such a section is the GUID that identifies the dictionary. Brotli sections
+/// without GUID-specific header data are plain Brotli streams.
+pub const BROTLI_DICTIONARY_ID_SIZE: usize = size_of::<efi::Guid>();
+
+/// A custom Brotli dictionary and the GUID that identifies it in the GUID-specific header data of a section.
+type BrotliDictionary = (efi::Guid, &'static [u8]);
+
 /// Provides decompression for Brotli GUIDed sections.
+///
+/// Only plain Brotli sections are decompressed. Sections compressed with a custom dictionary are reported as
+/// [`FirmwareFileSystemError::Unsupported`]; use [`BrotliDictionarySectionExtractor`] to decompress them. Use
+/// [`TimeLimitedBrotliSectionExtractor`] to bound the time spent decompressing a single section.
 #[derive(Default, Clone, Copy)]
 pub struct BrotliSectionExtractor;
 
@@ -62,48 +86,320 @@ impl BrotliSectionExtractor {
     }
 }
 
-impl SectionExtractor for BrotliSectionExtractor {
+/// Provides decompression for Brotli GUIDed sections, including sections compressed with a platform dictionary.
+///
+/// Sections whose GUID-specific header data carries the ID of the dictionary are decompressed with it; plain Brotli
+/// sections are decompressed without it. Sections that require a different dictionary are reported as
+/// [`FirmwareFileSystemError::Unsupported`], so another extractor registered for the Brotli GUID can handle them.
+#[derive(Clone, Copy)]
+pub struct BrotliDictionarySectionExtractor {
+    dictionary: BrotliDictionary,
+}
+
+impl BrotliDictionarySectionExtractor {
+    /// Creates an extractor that uses `dictionary` for sections that carry `dictionary_id`.
+    pub const fn new(dictionary_id: efi::Guid, dictionary: &'static [u8]) -> Self {
+        Self { dictionary: (dictionary_id, dictionary) }
+    }
+
+    /// Extracts a Brotli section into `out`, returning the number of bytes written.
+    ///
+    /// See [`BrotliSectionExtractor::extract_to_slice`]. Sections that require a dictionary other than the one
+    /// provided to this extractor fail with [`FirmwareFileSystemError::Unsupported`].
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        BrotliSectionExtractor::extract_to_slice_with(section, Some(self.dictionary), out, &mut || false)
+    }
+}
+
+impl SectionExtractor for BrotliDictionarySectionExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        BrotliSectionExtractor::extract_with(section, Some(self.dictionary), &mut || false)
+    }
+}
+
+/// Provides decompression for Brotli GUIDed sections, giving up on a section once a time limit has elapsed.
+///
+/// Sections that take longer than the limit to decompress fail with [`FirmwareFileSystemError::Timeout`]. A custom
+/// dictionary can be provided with [`Self::with_dictionary`], as for [`BrotliDictionarySectionExtractor`].
+#[derive(Clone, Copy)]
+pub struct TimeLimitedBrotliSectionExtractor {
+    time_limit: ExtractionTimeLimit,
+    dictionary: Option<BrotliDictionary>,
+}
+
+impl TimeLimitedBrotliSectionExtractor {
+    /// Creates an extractor that spends at most `time_limit` decompressing a single section.
+    pub const fn new(time_limit: ExtractionTimeLimit) -> Self {
+        Self { time_limit, dictionary: None }
+    }
+
+    /// Sets the custom dictionary used for sections that carry `dictionary_id`.
+    pub const fn with_dictionary(mut self, dictionary_id: efi::Guid, dictionary: &'static [u8]) -> Self {
+        self.dictionary = Some((dictionary_id, dictionary));
+        self
+    }
+
+    /// Extracts a Brotli section into `out`, returning the number of bytes written.
+    ///
+    /// See [`BrotliSectionExtractor::extract_to_slice`]. Fails with [`FirmwareFileSystemError::Timeout`] if the time
+    /// limit elapsed.
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        BrotliSectionExtractor::extract_to_slice_with(section, self.dictionary, out, &mut self.time_limit.start())
+    }
+}
+
+impl SectionExtractor for TimeLimitedBrotliSectionExtractor {
     fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
-        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        BrotliSectionExtractor::extract_with(section, self.dictionary, &mut self.time_limit.start())
+    }
+}
+
+impl BrotliSectionExtractor {
+    /// Extracts a Brotli section into `out`, returning the number of bytes written.
+    ///
+    /// The decompressed data is written directly to `out` instead of an allocated buffer. This is not an
+    /// allocation-free mode: the decoder still allocates its state and a ring buffer of up to the stream's window size
+    /// (up to 16 MiB) from the global allocator, so it cannot be used before an allocator is available.
+    ///
+    /// ## Errors
+    ///
+    /// - [`FirmwareFileSystemError::Unsupported`] if `section` is not a Brotli section, or requires a custom
+    ///   dictionary.
+    /// - [`FirmwareFileSystemError::BufferTooSmall`] if `out` cannot hold the decompressed data.
+    /// - [`FirmwareFileSystemError::DataCorrupt`] if the section content is not valid Brotli data.
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        Self::extract_to_slice_with(section, None, out, &mut || false)
+    }
+
+    /// Extracts a Brotli section into `out` with an optional custom dictionary, giving up once `abort` returns `true`.
+    fn extract_to_slice_with(
+        section: &Section,
+        dictionary: Option<BrotliDictionary>,
+        out: &mut [u8],
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, guid_data, _) = section.header()
             && guid_header.section_definition_guid == fw_fs::guid::BROTLI_SECTION
         {
+            let dictionary = Self::dictionary_for(guid_data, dictionary)?;
             let data = section.try_content_as_slice()?;
-            let out_size = u64::from_le_bytes(data[0..8].try_into().unwrap());
-            let _scratch_size = u64::from_le_bytes(data[8..16].try_into().unwrap());
+            return Self::decompress(data, dictionary, out, abort).map_err(Into::into);
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
 
-            let mut brotli_state = BrotliState::new(
-                HeapAllocator::<u8> { default_value: 0 },
-                HeapAllocator::<u32> { default_value: 0 },
-                HeapAllocator::<HuffmanCode> { default_value: Default::default() },
-            );
-            let in_data = &data[16..];
-            let mut out_data = vec![0u8; out_size as usize];
-            let mut out_data_size = 0;
+    /// Extracts a Brotli section into a new buffer with an optional custom dictionary, giving up once `abort` returns
+    /// `true`.
+    fn extract_with(
+        section: &Section,
+        dictionary: Option<BrotliDictionary>,
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<Vec<u8>, ExtractionError> {
+        if let SectionHeader::GuidDefined(guid_header, guid_data, _) = section.header()
+            && guid_header.section_definition_guid == fw_fs::guid::BROTLI_SECTION
+        {
+            let data = section.try_content_as_slice().map_err(|err| section.error_context(err))?;
+            return Self::dictionary_for(guid_data, dictionary)
+                .and_then(|dictionary| Self::decompress_to_vec(data, dictionary, abort))
+                .map_err(|err| err.with_section_guid(fw_fs::guid::BROTLI_SECTION));
+        }
+        Err(section.error_context(FirmwareFileSystemError::Unsupported))
+    }
+
+    /// Decompresses raw Brotli section content into `out`, returning the number of bytes written.
+    ///
+    /// This is the slice-based form of [`Self::extract_to_slice`] for callers that have the section content but not a
+    /// parsed [`Section`]. The content must be a plain Brotli stream, compressed without a custom dictionary.
+    pub fn decompress_to_slice(data: &[u8], out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        Self::decompress_to_slice_with_abort(data, out, &mut || false)
+    }
+
+    /// Decompresses raw Brotli section content into `out`, giving up once `abort` returns `true`.
+    ///
+    /// `abort` is called periodically while decoding. Once it returns `true`, decompression stops with
+    /// [`FirmwareFileSystemError::Timeout`].
+    pub fn decompress_to_slice_with_abort(
+        data: &[u8],
+        out: &mut [u8],
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, FirmwareFileSystemError> {
+        Self::decompress(data, None, out, abort).map_err(Into::into)
+    }
+
+    /// Returns the custom dictionary required by a section with the given GUID-specific header data.
+    ///
+    /// Returns `None` for plain Brotli sections, and [`FirmwareFileSystemError::Unsupported`] if the section requires
+    /// a dictionary other than `available`.
+    fn dictionary_for(
+        guid_data: &[u8],
+        available: Option<BrotliDictionary>,
+    ) -> Result<Option<&'static [u8]>, ExtractionError> {
+        if guid_data.is_empty() {
+            return Ok(None);
+        }
+        let dictionary_id = guid_data.first_chunk::<BROTLI_DICTIONARY_ID_SIZE>().ok_or_else(|| {
+            ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_reason(ErrorReason::Truncated {
+                required_size: BROTLI_DICTIONARY_ID_SIZE,
+                available_size: guid_data.len(),
+            })
+        })?;
+        match available {
+            Some((id, dictionary)) if id == efi::Guid::from_bytes(dictionary_id) => Ok(Some(dictionary)),
+            _ => Err(FirmwareFileSystemError::Unsupported.into()),
+        }
+    }
+
+    /// Decompresses raw Brotli section content into `out`, reporting failures with diagnostic context.
+    ///
+    /// A malformed stream, or one abandoned because `abort` returned `true`, is reported with the offset into the
+    /// content at which the decoder stopped. The decoder produces at most [`POLL_INTERVAL`] bytes between calls to
+    /// `abort`.
+    fn decompress(
+        data: &[u8],
+        dictionary: Option<&[u8]>,
+        out: &mut [u8],
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, ExtractionError> {
+        let mut input_offset = 0;
+        let mut written: usize = 0;
+        loop {
+            let frame = &data[input_offset..];
+            let out_size = Self::out_size(frame).map_err(at_frame(input_offset))?;
+            let out = written
+                .checked_add(out_size)
+                .and_then(|end| out.get_mut(written..end))
+                .ok_or(FirmwareFileSystemError::BufferTooSmall)?;
+            input_offset += Self::decompress_frame(frame, dictionary, out, abort).map_err(at_frame(input_offset))?;
+            written += out_size;
+            if is_padding(&data[input_offset..]) {
+                return Ok(written);
+            }
+        }
+    }
+
+    /// Decompresses raw Brotli section content into a new buffer.
+    fn decompress_to_vec(
+        data: &[u8],
+        dictionary: Option<&[u8]>,
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<Vec<u8>, ExtractionError> {
+        let mut input_offset = 0;
+        let mut out_data = Vec::new();
+        loop {
+            let frame = &data[input_offset..];
+            let written = out_data.len();
+            let out_size = Self::out_size(frame).map_err(at_frame(input_offset))?;
+            // A size that cannot be allocated is not a size any firmware image could have been built with.
+            out_data.try_reserve(out_size).map_err(|_| FirmwareFileSystemError::DataCorrupt)?;
+            out_data.resize(written + out_size, 0);
+            input_offset += Self::decompress_frame(frame, dictionary, &mut out_data[written..], abort)
+                .map_err(at_frame(input_offset))?;
+            if is_padding(&data[input_offset..]) {
+                return Ok(out_data);
+            }
+        }
+    }
+
+    /// Decompresses the frame at the start of `data` into `out`, which holds exactly the decompressed size of the
+    /// frame. Returns the size of the frame.
+    fn decompress_frame(
+        data: &[u8],
+        dictionary: Option<&[u8]>,
+        out: &mut [u8],
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, ExtractionError> {
+        let alloc_u8 = HeapAllocator::<u8> { default_value: 0 };
+        let alloc_u32 = HeapAllocator::<u32> { default_value: 0 };
+        let alloc_hc = HeapAllocator::<HuffmanCode> { default_value: Default::default() };
+        let mut brotli_state = match dictionary {
+            Some(dictionary) => {
+                BrotliState::new_with_custom_dictionary(alloc_u8, alloc_u32, alloc_hc, Rebox(dictionary.into()))
+            }
+            None => BrotliState::new(alloc_u8, alloc_u32, alloc_hc),
+        };
+        let in_data = &data[BROTLI_HEADER_SIZE..];
+        let mut available_in = in_data.len();
+        let mut input_offset = 0;
+        let mut output_offset = 0;
+        let mut out_data_size = 0;
+        loop {
+            let mut available_out = POLL_INTERVAL.min(out.len() - output_offset);
             let result = BrotliDecompressStream(
-                &mut in_data.len(),
-                &mut 0,
-                &data[16..],
-                &mut out_data.len(),
-                &mut 0,
-                out_data.as_mut_slice(),
+                &mut available_in,
+                &mut input_offset,
+                in_data,
+                &mut available_out,
+                &mut output_offset,
+                out,
                 &mut out_data_size,
                 &mut brotli_state,
             );
-
-            if matches!(result, BrotliResult::ResultSuccess) {
-                return Ok(out_data);
-            } else {
-                return Err(FirmwareFileSystemError::DataCorrupt);
+            match result {
+                BrotliResult::ResultSuccess => return Ok(data.len() - available_in),
+                // The output window is full but the buffer is not, so check for an abort before decoding the next window.
+                BrotliResult::NeedsMoreOutput if output_offset < out.len() => {
+                    if abort() {
+                        return Err(ExtractionError::new(FirmwareFileSystemError::Timeout)
+                            .with_offset(data.len() - available_in));
+                    }
+                }
+                _ => {
+                    return Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                        .with_offset(data.len() - available_in)
+                        .with_reason(ErrorReason::MalformedStream));
+                }
             }
         }
-        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    /// Returns the decompressed size from the section content header.
+    ///
+    /// The content starts with the 64-bit decompressed size followed by the 64-bit scratch size.
+    fn out_size(data: &[u8]) -> Result<usize, ExtractionError> {
+        let header = data.get(..BROTLI_HEADER_SIZE).ok_or_else(|| {
+            ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_reason(ErrorReason::Truncated { required_size: BROTLI_HEADER_SIZE, available_size: data.len() })
+        })?;
+        usize::try_from(u64::from_le_bytes(header[0..8].try_into().unwrap()))
+            .map_err(|_| FirmwareFileSystemError::DataCorrupt.into())
+    }
+}
+
+/// Returns `true` if no frame follows the input consumed so far.
+fn is_padding(remaining: &[u8]) -> bool {
+    remaining.iter().all(|&byte| byte == 0)
+}
+
+/// Makes the offset of an error in the frame that starts at `frame_offset` relative to the section content.
+fn at_frame(frame_offset: usize) -> impl FnOnce(ExtractionError) -> ExtractionError {
+    move |mut err| {
+        err.offset = err.offset.map(|offset| frame_offset + offset);
+        err
+    }
+}
+
+impl SectionExtractor for BrotliSectionExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        Self::extract_with(section, None, &mut || false)
     }
 }
 
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
-    use crate::tests::create_brotli_section;
+    use crate::testing::{create_brotli_section, create_guid_defined_section};
 
     use super::*;
 
@@ -114,10 +410,194 @@ mod tests {
             0x21, 0x30, 0x00, 0x04, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x21, 0x03,
         ];
         let section = create_brotli_section(&brotli_compressed_data, 13);
-        let extractor = BrotliSectionExtractor;
+        let extractor = BrotliSectionExtractor::new();
         let result = extractor.extract(&section);
         assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result, b"Hello, World!");
     }
+
+    #[test]
+    fn test_brotli_extract_to_slice() {
+        let brotli_compressed_data: [u8; 18] = [
+            0x21, 0x30, 0x00, 0x04, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x21, 0x03,
+        ];
+        let section = create_brotli_section(&brotli_compressed_data, 13);
+        let extractor = BrotliSectionExtractor::new();
+
+        let mut out = [0u8; 32];
+        let size = extractor.extract_to_slice(&section, &mut out).expect("Brotli extraction should succeed");
+        assert_eq!(&out[..size], b"Hello, World!");
+
+        let mut small = [0u8; 12];
+        assert_eq!(extractor.extract_to_slice(&section, &mut small), Err(FirmwareFileSystemError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_brotli_decompress_to_slice_truncated_header() {
+        let mut out = [0u8; 16];
+        assert_eq!(
+            BrotliSectionExtractor::decompress_to_slice(&[0u8; 8], &mut out),
+            Err(FirmwareFileSystemError::DataCorrupt)
+        );
+    }
+
+    #[test]
+    fn test_brotli_dictionary_selection() {
+        const DICTIONARY_ID: efi::Guid =
+            efi::Guid::from_fields(0x1a2b3c4d, 0x5e6f, 0x7081, 0x92, 0xa3, &[0xb4, 0xc5, 0xd6, 0xe7, 0xf8, 0x09]);
+        const DICTIONARY: &[u8] = b"Patina custom Brotli dictionary!";
+        const PAYLOAD: &[u8] = b"Patina custom Brotli dictionary! Patina custom Brotli dictionary!";
+        // PAYLOAD compressed against DICTIONARY: the stream copies both halves of the payload from the dictionary.
+        let dictionary_compressed_data: [u8; 13] =
+            [0x1b, 0x40, 0x00, 0x00, 0x24, 0x40, 0xaa, 0x98, 0x66, 0xaa, 0xb0, 0xe8, 0x14];
+        let brotli_compressed_data: [u8; 18] = [
+            0x21, 0x30, 0x00, 0x04, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x21, 0x03,
+        ];
+        let extractor = BrotliDictionarySectionExtractor::new(DICTIONARY_ID, DICTIONARY);
+
+        // Plain sections do not use the dictionary.
+        let plain = create_brotli_section(&brotli_compressed_data, 13);
+        assert_eq!(extractor.extract(&plain).unwrap(), b"Hello, World!");
+
+        let content = create_brotli_section(&dictionary_compressed_data, PAYLOAD.len() as u64)
+            .try_content_as_slice()
+            .unwrap()
+            .to_vec();
+        let section =
+            create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, DICTIONARY_ID.as_bytes().to_vec(), &content);
+        assert_eq!(extractor.extract(&section).unwrap(), PAYLOAD);
+        let mut out = [0u8; PAYLOAD.len()];
+        assert_eq!(extractor.extract_to_slice(&section, &mut out), Ok(PAYLOAD.len()));
+        assert_eq!(out, PAYLOAD);
+
+        // The stream references the dictionary, so it cannot be decoded without it.
+        assert_eq!(
+            BrotliSectionExtractor::decompress_to_slice(&content, &mut out),
+            Err(FirmwareFileSystemError::DataCorrupt)
+        );
+
+        // Sections that need a dictionary the extractor does not have are left to other extractors.
+        assert_eq!(BrotliSectionExtractor::new().extract(&section), Err(FirmwareFileSystemError::Unsupported));
+        assert_eq!(
+            BrotliSectionExtractor::new().extract_to_slice(&section, &mut out),
+            Err(FirmwareFileSystemError::Unsupported)
+        );
+        let other_id = efi::Guid::from_bytes(&[0x11; 16]);
+        let section = create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, other_id.as_bytes().to_vec(), &content);
+        assert_eq!(extractor.extract(&section), Err(FirmwareFileSystemError::Unsupported));
+        assert_eq!(extractor.extract_to_slice(&section, &mut out), Err(FirmwareFileSystemError::Unsupported));
+
+        let truncated = create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, vec![0x11; 8], &[]);
+        assert_eq!(
+            extractor.extract_with_context(&truncated),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_section_guid(fw_fs::guid::BROTLI_SECTION)
+                .with_reason(ErrorReason::Truncated { required_size: BROTLI_DICTIONARY_ID_SIZE, available_size: 8 }))
+        );
+    }
+
+    #[test]
+    fn test_brotli_extractor_error_context() {
+        let truncated = create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, vec![], &[0; 8]);
+        assert_eq!(
+            BrotliSectionExtractor::new().extract_with_context(&truncated),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_section_guid(fw_fs::guid::BROTLI_SECTION)
+                .with_reason(ErrorReason::Truncated { required_size: BROTLI_HEADER_SIZE, available_size: 8 }))
+        );
+
+        // The stream header is valid, but the data that follows is not.
+        let section = create_brotli_section(&[0x21, 0x30, 0x00, 0x04, 0x48, 0xff, 0xff, 0xff, 0xff, 0xff], 13);
+        let err = BrotliSectionExtractor::new().extract_with_context(&section).unwrap_err();
+        assert_eq!(err.reason, Some(ErrorReason::MalformedStream));
+        assert!(err.offset.is_some_and(|offset| offset > BROTLI_HEADER_SIZE), "{err}");
+    }
+
+    #[test]
+    fn test_brotli_extractor_multiple_frames() {
+        let brotli_compressed_data: [u8; 18] = [
+            0x21, 0x30, 0x00, 0x04, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x21, 0x03,
+        ];
+        // A frame of a single uncompressed meta-block of four bytes and an empty last meta-block.
+        let second: [u8; 8] = [0x30, 0x00, 0x10, b'R', b'u', b's', b't', 0x03];
+        let frame = create_brotli_section(&brotli_compressed_data, 13).try_content_as_slice().unwrap().to_vec();
+        let mut content = frame.clone();
+        content.extend_from_slice(create_brotli_section(&second, 4).try_content_as_slice().unwrap());
+        let extractor = BrotliSectionExtractor::new();
+
+        for padding in [0, 5] {
+            let mut padded = content.clone();
+            padded.resize(content.len() + padding, 0);
+            let section = create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, vec![], &padded);
+            assert_eq!(extractor.extract(&section).unwrap(), b"Hello, World!Rust");
+            let mut out = [0u8; 20];
+            assert_eq!(extractor.extract_to_slice(&section, &mut out), Ok(17));
+            assert_eq!(&out[..17], b"Hello, World!Rust");
+            assert_eq!(
+                extractor.extract_to_slice(&section, &mut out[..16]),
+                Err(FirmwareFileSystemError::BufferTooSmall)
+            );
+        }
+
+        // Errors in later frames are reported relative to the section content.
+        let mut corrupt = frame.clone();
+        corrupt.extend_from_slice(
+            create_brotli_section(&[0x21, 0x30, 0x00, 0x04, 0x48, 0xff, 0xff], 13).try_content_as_slice().unwrap(),
+        );
+        let section = create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, vec![], &corrupt);
+        let err = extractor.extract_with_context(&section).unwrap_err();
+        assert_eq!(err.reason, Some(ErrorReason::MalformedStream));
+        assert!(err.offset.is_some_and(|offset| offset > frame.len() + BROTLI_HEADER_SIZE), "{err}");
+
+        let mut trailing = frame;
+        trailing.push(0x01);
+        let section = create_guid_defined_section(fw_fs::guid::BROTLI_SECTION, vec![], &trailing);
+        assert_eq!(
+            extractor.extract_with_context(&section).unwrap_err().reason,
+            Some(ErrorReason::Truncated { required_size: BROTLI_HEADER_SIZE, available_size: 1 })
+        );
+    }
+
+    #[test]
+    fn test_brotli_time_limit() {
+        struct FrozenClock;
+        impl crate::TimestampSource for FrozenClock {
+            fn timestamp(&self) -> u64 {
+                0
+            }
+        }
+        static CLOCK: FrozenClock = FrozenClock;
+
+        // A 16-bit window followed by a single uncompressed meta-block of 0x20000 bytes and an empty last meta-block.
+        let payload = (0..0x20000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut stream = vec![0xf4, 0xff, 0x1f, 0x01];
+        stream.extend_from_slice(&payload);
+        stream.push(0x03);
+        let section = create_brotli_section(&stream, payload.len() as u64);
+        let data = section.try_content_as_slice().unwrap();
+        let mut out = vec![0u8; payload.len()];
+
+        let mut polls = 0;
+        assert_eq!(
+            BrotliSectionExtractor::decompress_to_slice_with_abort(data, &mut out, &mut || {
+                polls += 1;
+                false
+            }),
+            Ok(payload.len())
+        );
+        assert_eq!(polls, 1);
+        assert_eq!(out, payload);
+
+        let err = BrotliSectionExtractor::decompress(data, None, &mut out, &mut || true).unwrap_err();
+        assert_eq!(err.error, FirmwareFileSystemError::Timeout);
+        assert!(err.offset.is_some_and(|offset| offset > BROTLI_HEADER_SIZE + POLL_INTERVAL), "{err}");
+
+        let expired = TimeLimitedBrotliSectionExtractor::new(ExtractionTimeLimit::from_ticks(&CLOCK, 0));
+        assert_eq!(expired.extract(&section), Err(FirmwareFileSystemError::Timeout));
+        assert_eq!(expired.extract_to_slice(&section, &mut out), Err(FirmwareFileSystemError::Timeout));
+
+        let generous = TimeLimitedBrotliSectionExtractor::new(ExtractionTimeLimit::from_ticks(&CLOCK, 1));
+        assert_eq!(generous.extract(&section).unwrap(), payload);
+    }
 }
@@ -0,0 +1,417 @@
+//! Module for building GUID-defined sections from raw payloads.
+//!
+//! [`SectionBuilder`] is the inverse of the extractors in this crate: it wraps a payload in a CRC32, LZMA, or Brotli
+//! GUID-defined section whose header matches what the EDK2 `GenSec` tool produces, and implements
+//! [`SectionComposer`] so a modified [`Section`] tree can be re-encoded before it is serialized.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use patina::pi::fw_fs::{self, ffs::section::header::GuidDefined};
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionComposer, SectionHeader},
+};
+use r_efi::efi;
+
+/// `EFI_GUIDED_SECTION_PROCESSING_REQUIRED`
+#[cfg(any(feature = "lzma", feature = "brotli-encoder"))]
+const PROCESSING_REQUIRED: u16 = 0x01;
+
+/// `EFI_GUIDED_SECTION_AUTH_STATUS_VALID`
+#[cfg(feature = "crc32")]
+const AUTH_STATUS_VALID: u16 = 0x02;
+
+/// Size of the common section header.
+const COMMON_HEADER_SIZE: usize = 4;
+
+/// Size of the extended-size field that follows the common section header of large sections.
+const EXTENDED_SIZE_FIELD_SIZE: usize = 4;
+
+/// Sections of this size or larger use the extended-size header, as serialized by `patina_ffs`.
+const MAX_STANDARD_SECTION_SIZE: usize = 0x1000000;
+
+/// Brotli quality used for Brotli sections.
+#[cfg(feature = "brotli-encoder")]
+const BROTLI_QUALITY: i32 = 9;
+
+/// Base-2 logarithm of the Brotli window size used for Brotli sections.
+#[cfg(feature = "brotli-encoder")]
+const BROTLI_WINDOW_BITS: i32 = 22;
+
+/// A GUID-defined section format that [`SectionBuilder`] can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuidedSectionFormat {
+    /// The payload followed by its CRC32 in the GUID-specific header. Requires the `crc32` feature.
+    Crc32,
+    /// The payload compressed as an LZMA stream with the uncompressed size in its header. Requires the `lzma`
+    /// feature.
+    Lzma,
+    /// The payload compressed as a Brotli stream, preceded by the uncompressed and scratch sizes. Requires the
+    /// `brotli-encoder` feature.
+    Brotli,
+}
+
+impl GuidedSectionFormat {
+    /// Returns the section definition GUID of the format.
+    pub const fn guid(self) -> efi::Guid {
+        match self {
+            Self::Crc32 => fw_fs::guid::CRC32_SECTION,
+            Self::Lzma => fw_fs::guid::LZMA_SECTION,
+            Self::Brotli => fw_fs::guid::BROTLI_SECTION,
+        }
+    }
+
+    /// Returns the format with the given section definition GUID, if there is one.
+    pub fn from_guid(guid: &efi::Guid) -> Option<Self> {
+        [Self::Crc32, Self::Lzma, Self::Brotli].into_iter().find(|format| format.guid() == *guid)
+    }
+}
+
+/// Builds GUID-defined sections from raw payloads.
+///
+/// The payload of an encapsulation section is the serialized sequence of its sub-sections. [`SectionBuilder::build`]
+/// takes that payload directly, while the [`SectionComposer`] implementation serializes the sub-sections of an
+/// existing section, so `section.compose(&SectionBuilder::new())` re-encodes every CRC32, LZMA, and Brotli section
+/// in the tree. Other encapsulation sections are reported as [`FirmwareFileSystemError::Unsupported`].
+///
+/// A format whose feature is not enabled is also reported as [`FirmwareFileSystemError::Unsupported`].
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let section = SectionBuilder::new().build(GuidedSectionFormat::Lzma, &raw_section.serialize()?)?;
+/// assert_eq!(LzmaSectionExtractor::new().extract(&section)?, raw_section.serialize()?);
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SectionBuilder {
+    brotli_scratch_size: u64,
+    brotli_dictionary: Option<(efi::Guid, &'static [u8])>,
+}
+
+impl SectionBuilder {
+    /// Creates a new `SectionBuilder` instance.
+    #[coverage(off)]
+    pub const fn new() -> Self {
+        Self { brotli_scratch_size: 0, brotli_dictionary: None }
+    }
+
+    /// Sets the scratch size written ahead of the Brotli stream in Brotli sections.
+    ///
+    /// The decoder in this crate does not use the scratch size, so it is zero by default. EDK2 decoders size their
+    /// scratch buffer from it, so sections built for EDK2 consumers should set it.
+    pub const fn with_brotli_scratch_size(mut self, scratch_size: u64) -> Self {
+        self.brotli_scratch_size = scratch_size;
+        self
+    }
+
+    /// Compresses Brotli sections with a custom dictionary, recording `dictionary_id` in the GUID-specific header data.
+    ///
+    /// Such sections can only be extracted by a
+    /// [`BrotliDictionarySectionExtractor`](crate::BrotliDictionarySectionExtractor) provided the same dictionary.
+    pub const fn with_brotli_dictionary(mut self, dictionary_id: efi::Guid, dictionary: &'static [u8]) -> Self {
+        self.brotli_dictionary = Some((dictionary_id, dictionary));
+        self
+    }
+
+    /// Builds a section of the given format whose extracted content is `payload`.
+    pub fn build(&self, format: GuidedSectionFormat, payload: &[u8]) -> Result<Section, FirmwareFileSystemError> {
+        let (header, content) = self.encode(format, payload)?;
+        Section::new_from_header_with_data(header, content)
+    }
+
+    /// Encodes `payload` in the given format, returning the section header and content.
+    pub fn encode(
+        &self,
+        format: GuidedSectionFormat,
+        payload: &[u8],
+    ) -> Result<(SectionHeader, Vec<u8>), FirmwareFileSystemError> {
+        let (attributes, guid_data, content) = match format {
+            GuidedSectionFormat::Crc32 => Self::encode_crc32(payload)?,
+            GuidedSectionFormat::Lzma => Self::encode_lzma(payload)?,
+            GuidedSectionFormat::Brotli => self.encode_brotli(payload)?,
+        };
+        Ok((guid_defined_header(format.guid(), attributes, guid_data, content.len())?, content))
+    }
+
+    #[cfg(feature = "crc32")]
+    fn encode_crc32(payload: &[u8]) -> Result<(u16, Vec<u8>, Vec<u8>), FirmwareFileSystemError> {
+        Ok((AUTH_STATUS_VALID, crc32fast::hash(payload).to_le_bytes().to_vec(), payload.to_vec()))
+    }
+
+    #[cfg(not(feature = "crc32"))]
+    fn encode_crc32(_payload: &[u8]) -> Result<(u16, Vec<u8>, Vec<u8>), FirmwareFileSystemError> {
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    #[cfg(feature = "lzma")]
+    fn encode_lzma(payload: &[u8]) -> Result<(u16, Vec<u8>, Vec<u8>), FirmwareFileSystemError> {
+        use patina_lzma_rs::{
+            compress::{Options, UnpackedSize},
+            io::Cursor,
+        };
+
+        let options = Options { unpacked_size: UnpackedSize::WriteToHeader(Some(payload.len() as u64)) };
+        let mut compressed = Vec::new();
+        patina_lzma_rs::lzma_compress_with_options(&mut Cursor::new(payload), &mut compressed, &options)
+            .map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+        Ok((PROCESSING_REQUIRED, Vec::new(), compressed))
+    }
+
+    #[cfg(not(feature = "lzma"))]
+    fn encode_lzma(_payload: &[u8]) -> Result<(u16, Vec<u8>, Vec<u8>), FirmwareFileSystemError> {
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    #[cfg(feature = "brotli-encoder")]
+    fn encode_brotli(&self, payload: &[u8]) -> Result<(u16, Vec<u8>, Vec<u8>), FirmwareFileSystemError> {
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: BROTLI_QUALITY,
+            lgwin: BROTLI_WINDOW_BITS,
+            size_hint: payload.len(),
+            ..Default::default()
+        };
+
+        let mut content = Vec::new();
+        content.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        content.extend_from_slice(&self.brotli_scratch_size.to_le_bytes());
+
+        let Some((dictionary_id, dictionary)) = self.brotli_dictionary else {
+            brotli::BrotliCompress(&mut &payload[..], &mut content, &params)
+                .map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+            return Ok((PROCESSING_REQUIRED, Vec::new(), content));
+        };
+
+        let mut input_buffer = [0u8; 4096];
+        let mut output_buffer = [0u8; 4096];
+        brotli::BrotliCompressCustomIoCustomDict(
+            &mut brotli::IoReaderWrapper(&mut &payload[..]),
+            &mut brotli::IoWriterWrapper(&mut content),
+            &mut input_buffer,
+            &mut output_buffer,
+            &params,
+            brotli::enc::StandardAlloc::default(),
+            &mut |_: &mut _, _: &mut _, _, _: &mut _| (),
+            dictionary,
+            std::io::Error::from(std::io::ErrorKind::UnexpectedEof),
+        )
+        .map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+        Ok((PROCESSING_REQUIRED, dictionary_id.as_bytes().to_vec(), content))
+    }
+
+    #[cfg(not(feature = "brotli-encoder"))]
+    fn encode_brotli(&self, _payload: &[u8]) -> Result<(u16, Vec<u8>, Vec<u8>), FirmwareFileSystemError> {
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+}
+
+impl SectionComposer for SectionBuilder {
+    fn compose(&self, section: &Section) -> Result<(SectionHeader, Vec<u8>), FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && let Some(format) = GuidedSectionFormat::from_guid(&guid_header.section_definition_guid)
+        {
+            return self.encode(format, &serialize_sub_sections(section)?);
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+}
+
+/// Constructs a GUID-defined section header, using the extended-size header when the section requires it.
+fn guid_defined_header(
+    guid: efi::Guid,
+    attributes: u16,
+    guid_data: Vec<u8>,
+    content_size: usize,
+) -> Result<SectionHeader, FirmwareFileSystemError> {
+    let mut data_offset = COMMON_HEADER_SIZE + size_of::<GuidDefined>() + guid_data.len();
+    if data_offset + content_size >= MAX_STANDARD_SECTION_SIZE {
+        data_offset += EXTENDED_SIZE_FIELD_SIZE;
+    }
+
+    let guid_header = GuidDefined {
+        section_definition_guid: guid,
+        data_offset: u16::try_from(data_offset).map_err(|_| FirmwareFileSystemError::InvalidParameter)?,
+        attributes,
+    };
+    let content_size = u32::try_from(content_size).map_err(|_| FirmwareFileSystemError::InvalidParameter)?;
+    Ok(SectionHeader::GuidDefined(guid_header, guid_data, content_size))
+}
+
+/// Serializes the sub-sections of `section`, zero-padding between them so each starts at a 4-byte aligned offset.
+///
+/// No padding is added after the last sub-section.
+fn serialize_sub_sections(section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+    let mut payload = Vec::new();
+    for sub_section in section.sub_sections() {
+        // Per PI 1.8A volume 3 section 2.2.4, pad bytes are always zero.
+        payload.resize(payload.len().next_multiple_of(4), 0);
+        payload.extend(sub_section.serialize()?);
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use patina::pi::fw_fs::ffs::section;
+    #[cfg(any(feature = "crc32", feature = "lzma", feature = "brotli-encoder"))]
+    use patina_ffs::section::SectionExtractor;
+
+    fn raw_section(data: &[u8]) -> Section {
+        Section::new_from_header_with_data(
+            SectionHeader::Standard(section::raw_type::RAW, data.len() as u32),
+            data.to_vec(),
+        )
+        .unwrap()
+    }
+
+    #[cfg(any(feature = "lzma", feature = "brotli-encoder"))]
+    fn payload() -> Vec<u8> {
+        let mut payload = raw_section(b"payload").serialize().unwrap();
+        payload.resize(payload.len().next_multiple_of(4), 0);
+        payload.extend(raw_section(&[0x5a; 300]).serialize().unwrap());
+        payload
+    }
+
+    #[test]
+    fn test_format_guid_round_trip() {
+        for format in [GuidedSectionFormat::Crc32, GuidedSectionFormat::Lzma, GuidedSectionFormat::Brotli] {
+            assert_eq!(GuidedSectionFormat::from_guid(&format.guid()), Some(format));
+        }
+        assert_eq!(GuidedSectionFormat::from_guid(&efi::Guid::from_bytes(&[0; 16])), None);
+    }
+
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_build_crc32_header_is_exact() {
+        let section = SectionBuilder::new().build(GuidedSectionFormat::Crc32, b"abcd").unwrap();
+        let bytes = section.serialize().unwrap();
+
+        let mut expected = vec![0x20, 0x00, 0x00, section::raw_type::encapsulated::GUID_DEFINED];
+        expected.extend_from_slice(fw_fs::guid::CRC32_SECTION.as_bytes());
+        expected.extend_from_slice(&[0x1c, 0x00, 0x02, 0x00]);
+        expected.extend_from_slice(&crc32fast::hash(b"abcd").to_le_bytes());
+        expected.extend_from_slice(b"abcd");
+        assert_eq!(bytes, expected);
+        assert_eq!(crate::Crc32SectionExtractor::new().extract(&section).unwrap(), b"abcd");
+    }
+
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_build_large_section_uses_extended_header() {
+        let payload = vec![0xa5; MAX_STANDARD_SECTION_SIZE];
+        let section = SectionBuilder::new().build(GuidedSectionFormat::Crc32, &payload).unwrap();
+        let bytes = section.serialize().unwrap();
+
+        let total_size = COMMON_HEADER_SIZE + EXTENDED_SIZE_FIELD_SIZE + size_of::<GuidDefined>() + 4 + payload.len();
+        assert_eq!(bytes.len(), total_size);
+        assert_eq!(bytes[0..3], [0xff; 3]);
+        assert_eq!(bytes[4..8], (total_size as u32).to_le_bytes());
+        assert_eq!(bytes[24..26], 0x20u16.to_le_bytes());
+
+        let reparsed = Section::new_from_buffer(&bytes).unwrap();
+        assert_eq!(crate::Crc32SectionExtractor::new().extract(&reparsed).unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "lzma")]
+    fn test_build_lzma_round_trip() {
+        let payload = payload();
+        let section = SectionBuilder::new().build(GuidedSectionFormat::Lzma, &payload).unwrap();
+        let SectionHeader::GuidDefined(guid_header, guid_data, _) = section.header() else {
+            panic!("Expected a GUID-defined section");
+        };
+        assert_eq!(guid_header.data_offset, 0x18);
+        assert_eq!(guid_header.attributes, PROCESSING_REQUIRED);
+        assert!(guid_data.is_empty());
+        assert_eq!(crate::LzmaSectionExtractor::new().extract(&section).unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "brotli-encoder")]
+    fn test_build_brotli_round_trip() {
+        let payload = payload();
+        let section = SectionBuilder::new()
+            .with_brotli_scratch_size(0x1234)
+            .build(GuidedSectionFormat::Brotli, &payload)
+            .unwrap();
+        let content = section.try_content_as_slice().unwrap();
+        assert_eq!(content[0..8], (payload.len() as u64).to_le_bytes());
+        assert_eq!(content[8..16], 0x1234u64.to_le_bytes());
+        assert_eq!(crate::BrotliSectionExtractor::new().extract(&section).unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "brotli-encoder")]
+    fn test_build_brotli_with_dictionary() {
+        const DICTIONARY_ID: efi::Guid =
+            efi::Guid::from_fields(0x1a2b3c4d, 0x5e6f, 0x7081, 0x92, 0xa3, &[0xb4, 0xc5, 0xd6, 0xe7, 0xf8, 0x09]);
+        static DICTIONARY: [u8; 64] = *b"This program cannot be run in DOS mode. OS loader payload v1.0.0";
+        let payload = [&DICTIONARY[..], b" and the payload that follows it"].concat();
+
+        let plain = SectionBuilder::new().build(GuidedSectionFormat::Brotli, &payload).unwrap();
+        let section = SectionBuilder::new()
+            .with_brotli_dictionary(DICTIONARY_ID, &DICTIONARY)
+            .build(GuidedSectionFormat::Brotli, &payload)
+            .unwrap();
+        let SectionHeader::GuidDefined(guid_header, guid_data, _) = section.header() else {
+            panic!("Expected a GUID-defined section");
+        };
+        assert_eq!(guid_data, DICTIONARY_ID.as_bytes());
+        assert_eq!(guid_header.data_offset as usize, 0x18 + crate::BROTLI_DICTIONARY_ID_SIZE);
+        assert!(section.try_content_as_slice().unwrap().len() < plain.try_content_as_slice().unwrap().len());
+
+        let extractor = crate::BrotliDictionarySectionExtractor::new(DICTIONARY_ID, &DICTIONARY);
+        assert_eq!(extractor.extract(&section).unwrap(), payload);
+        let reparsed = Section::new_from_buffer(&section.serialize().unwrap()).unwrap();
+        assert_eq!(extractor.extract(&reparsed).unwrap(), payload);
+        assert_eq!(crate::BrotliSectionExtractor::new().extract(&section), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    #[cfg(not(feature = "brotli-encoder"))]
+    fn test_build_brotli_without_encoder_is_unsupported() {
+        assert_eq!(
+            SectionBuilder::new().build(GuidedSectionFormat::Brotli, b"data").unwrap_err(),
+            FirmwareFileSystemError::Unsupported
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "crc32", feature = "lzma"))]
+    fn test_compose_re_encodes_modified_sections() {
+        let extractor = crate::CompositeSectionExtractor::default();
+        let builder = SectionBuilder::new();
+        let inner = builder.build(GuidedSectionFormat::Crc32, &payload()).unwrap();
+        let mut section = builder.build(GuidedSectionFormat::Lzma, &inner.serialize().unwrap()).unwrap();
+        section.extract(&extractor).unwrap();
+
+        let leaf = section.sub_sections_mut().next().unwrap().sub_sections_mut().next().unwrap();
+        leaf.set_section_data(b"modified".to_vec()).unwrap();
+        assert_eq!(section.serialize(), Err(FirmwareFileSystemError::NotComposed));
+        section.compose(&builder).unwrap();
+
+        let mut reparsed = Section::new_from_buffer(&section.serialize().unwrap()).unwrap();
+        reparsed.extract(&extractor).unwrap();
+        let inner = reparsed.sub_sections().next().unwrap();
+        let leaf = inner.sub_sections().next().unwrap();
+        assert_eq!(leaf.try_content_as_slice().unwrap(), b"modified");
+        assert_eq!(inner.sub_sections().count(), 2);
+    }
+
+    #[test]
+    fn test_compose_unsupported_section() {
+        let section = crate::testing::create_guid_defined_section(efi::Guid::from_bytes(&[1; 16]), vec![], b"data");
+        assert_eq!(SectionBuilder::new().compose(&section).unwrap_err(), FirmwareFileSystemError::Unsupported);
+        assert_eq!(
+            SectionBuilder::new().compose(&raw_section(b"data")).unwrap_err(),
+            FirmwareFileSystemError::Unsupported
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Module for caching extraction results.
+//!
+//! The [`CachingSectionExtractor`] wraps another extractor and memoizes successful extractions, keyed by the section
+//! header and content. Repeated requests for the same section (common when drivers read the same file
+//! through the firmware volume protocol several times) return the cached payload instead of decompressing again.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use patina_ffs::{
+    ExtractionError, FirmwareFileSystemError,
+    section::{Section, SectionExtractor},
+};
+use spin::Mutex;
+
+/// Selects which cached entry is evicted when a new entry does not fit in the byte budget.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the entry that was least recently returned or inserted.
+    #[default]
+    LeastRecentlyUsed,
+    /// Evict the entry that was inserted first.
+    FirstInFirstOut,
+}
+
+/// Cache hit, miss, and eviction counts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of extractions answered from the cache.
+    pub hits: u64,
+    /// Number of extractions passed to the inner extractor.
+    pub misses: u64,
+    /// Number of entries evicted to stay within the byte budget.
+    pub evictions: u64,
+}
+
+/// Identity of a section: its serialized header and content, with a 64-bit FNV-1a hash of them.
+///
+/// The hash only speeds up comparisons; two keys are equal only if their bytes are equal, so a hash collision can
+/// never return the payload of another section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SectionKey {
+    hash: u64,
+    bytes: Vec<u8>,
+}
+
+impl SectionKey {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new(section: &Section) -> Result<Self, FirmwareFileSystemError> {
+        let mut bytes = section.header().serialize();
+        bytes.extend_from_slice(section.try_content_as_slice()?);
+        let hash =
+            bytes.iter().fold(Self::FNV_OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(Self::FNV_PRIME));
+        Ok(Self { hash, bytes })
+    }
+}
+
+struct CacheEntry {
+    key: SectionKey,
+    data: Vec<u8>,
+    /// Insertion order for FIFO eviction, or last use for LRU eviction.
+    stamp: u64,
+}
+
+struct CacheState {
+    entries: Vec<CacheEntry>,
+    cached_bytes: usize,
+    clock: u64,
+    stats: CacheStats,
+}
+
+/// A section extractor that caches the results of an inner extractor.
+///
+/// Only successful extractions are cached, and the total size of the cached payloads never exceeds the configured
+/// byte budget. Payloads larger than the budget are returned but not cached. Sections are identified by their header
+/// and content, which are kept alongside each cached payload so that a hit is only returned for identical bytes.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let extractor = CachingSectionExtractor::new(CompositeSectionExtractor::new(), 0x20_0000)
+///     .with_eviction_policy(EvictionPolicy::FirstInFirstOut);
+/// ```
+pub struct CachingSectionExtractor<E: SectionExtractor> {
+    inner: E,
+    budget: usize,
+    policy: EvictionPolicy,
+    state: Mutex<CacheState>,
+}
+
+impl<E: SectionExtractor> CachingSectionExtractor<E> {
+    /// Creates a caching extractor around `inner` that caches at most `budget` bytes of extracted payloads.
+    pub const fn new(inner: E, budget: usize) -> Self {
+        Self {
+            inner,
+            budget,
+            policy: EvictionPolicy::LeastRecentlyUsed,
+            state: Mutex::new(CacheState {
+                entries: Vec::new(),
+                cached_bytes: 0,
+                clock: 0,
+                stats: CacheStats { hits: 0, misses: 0, evictions: 0 },
+            }),
+        }
+    }
+
+    /// Sets the eviction policy. The default is [`EvictionPolicy::LeastRecentlyUsed`].
+    pub const fn with_eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Returns the total size of the cached payloads in bytes.
+    pub fn cached_bytes(&self) -> usize {
+        self.state.lock().cached_bytes
+    }
+
+    /// Returns the cache hit, miss, and eviction counts.
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().stats
+    }
+
+    /// Removes all cached entries.
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.cached_bytes = 0;
+    }
+
+    fn lookup(&self, key: &SectionKey) -> Option<Vec<u8>> {
+        let mut state = self.state.lock();
+        state.clock += 1;
+        let clock = state.clock;
+        let policy = self.policy;
+
+        let Some(entry) = state.entries.iter_mut().find(|entry| entry.key == *key) else {
+            state.stats.misses += 1;
+            return None;
+        };
+        if policy == EvictionPolicy::LeastRecentlyUsed {
+            entry.stamp = clock;
+        }
+        let data = entry.data.clone();
+        state.stats.hits += 1;
+        Some(data)
+    }
+
+    fn insert(&self, key: SectionKey, data: &[u8]) {
+        if data.len() > self.budget {
+            return;
+        }
+
+        let mut state = self.state.lock();
+        // Another caller may have inserted the same section while the lock was released.
+        if state.entries.iter().any(|entry| entry.key == key) {
+            return;
+        }
+
+        while state.cached_bytes + data.len() > self.budget {
+            let Some(oldest) = state.entries.iter().enumerate().min_by_key(|(_, entry)| entry.stamp).map(|(i, _)| i)
+            else {
+                break;
+            };
+            let evicted = state.entries.swap_remove(oldest);
+            state.cached_bytes -= evicted.data.len();
+            state.stats.evictions += 1;
+        }
+
+        state.clock += 1;
+        let stamp = state.clock;
+        state.cached_bytes += data.len();
+        state.entries.push(CacheEntry { key, data: data.to_vec(), stamp });
+    }
+}
+
+impl<E: SectionExtractor> SectionExtractor for CachingSectionExtractor<E> {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        let key = SectionKey::new(section).map_err(|err| section.error_context(err))?;
+        if let Some(data) = self.lookup(&key) {
+            return Ok(data);
+        }
+
+        let data = self.inner.extract_with_context(section)?;
+        self.insert(key, &data);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::testing::create_guid_defined_section;
+    use alloc::vec;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use r_efi::efi;
+
+    const TEST_GUID: efi::Guid =
+        efi::Guid::from_fields(0x4b3e1a2c, 0x7d55, 0x4e0f, 0x9b, 0x21, &[0x64, 0xc8, 0x0e, 0x3d, 0x5f, 0x92]);
+
+    /// Returns the section content repeated twice, counting calls.
+    #[derive(Default)]
+    struct DoublingExtractor {
+        calls: AtomicUsize,
+    }
+
+    impl SectionExtractor for DoublingExtractor {
+        fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let content = section.try_content_as_slice()?;
+            if content.is_empty() {
+                return Err(FirmwareFileSystemError::DataCorrupt);
+            }
+            Ok([content, content].concat())
+        }
+    }
+
+    fn section(content: &[u8]) -> Section {
+        create_guid_defined_section(TEST_GUID, vec![], content)
+    }
+
+    #[test]
+    fn test_cache_hit_skips_inner_extractor() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 0x100);
+
+        assert_eq!(extractor.extract(&section(b"abc")).unwrap(), b"abcabc");
+        assert_eq!(extractor.extract(&section(b"abc")).unwrap(), b"abcabc");
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(extractor.stats(), CacheStats { hits: 1, misses: 1, evictions: 0 });
+        assert_eq!(extractor.cached_bytes(), 6);
+
+        assert_eq!(extractor.extract(&section(b"abd")).unwrap(), b"abdabd");
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_errors_are_not_cached() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 0x100);
+
+        assert_eq!(extractor.extract(&section(b"")), Err(FirmwareFileSystemError::DataCorrupt));
+        assert_eq!(extractor.extract(&section(b"")), Err(FirmwareFileSystemError::DataCorrupt));
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(extractor.cached_bytes(), 0);
+    }
+
+    #[test]
+    fn test_payload_larger_than_budget_is_not_cached() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 4);
+
+        assert_eq!(extractor.extract(&section(b"abc")).unwrap(), b"abcabc");
+        assert_eq!(extractor.cached_bytes(), 0);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 4);
+
+        extractor.extract(&section(b"a")).unwrap();
+        extractor.extract(&section(b"b")).unwrap();
+        // Touch "a" so "b" is the least recently used.
+        extractor.extract(&section(b"a")).unwrap();
+        extractor.extract(&section(b"c")).unwrap();
+        assert_eq!(extractor.stats().evictions, 1);
+        assert_eq!(extractor.cached_bytes(), 4);
+
+        let calls = extractor.inner.calls.load(Ordering::SeqCst);
+        extractor.extract(&section(b"a")).unwrap();
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), calls);
+        extractor.extract(&section(b"b")).unwrap();
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), calls + 1);
+    }
+
+    #[test]
+    fn test_fifo_eviction() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 4)
+            .with_eviction_policy(EvictionPolicy::FirstInFirstOut);
+
+        extractor.extract(&section(b"a")).unwrap();
+        extractor.extract(&section(b"b")).unwrap();
+        extractor.extract(&section(b"a")).unwrap();
+        extractor.extract(&section(b"c")).unwrap();
+
+        // "a" was inserted first, so it was evicted despite the recent use.
+        let calls = extractor.inner.calls.load(Ordering::SeqCst);
+        extractor.extract(&section(b"b")).unwrap();
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), calls);
+        extractor.extract(&section(b"a")).unwrap();
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), calls + 1);
+    }
+
+    #[test]
+    fn test_hash_collision_is_not_a_hit() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 0x100);
+        let key = SectionKey::new(&section(b"abc")).unwrap();
+        extractor.insert(key.clone(), b"abcabc");
+
+        // A different section whose hash happens to collide must not return the cached payload.
+        let colliding = SectionKey { hash: key.hash, bytes: SectionKey::new(&section(b"abd")).unwrap().bytes };
+        assert_eq!(extractor.lookup(&colliding), None);
+        assert_eq!(extractor.lookup(&key).as_deref(), Some(&b"abcabc"[..]));
+    }
+
+    #[test]
+    fn test_clear() {
+        let extractor = CachingSectionExtractor::new(DoublingExtractor::default(), 0x100);
+        extractor.extract(&section(b"abc")).unwrap();
+        extractor.clear();
+        assert_eq!(extractor.cached_bytes(), 0);
+        extractor.extract(&section(b"abc")).unwrap();
+        assert_eq!(extractor.inner.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,4 +1,8 @@
-//! Module for a composite of brotli, uefi, and crc32 decompression.
+//! Module for a composite of brotli, crc32, lzma, lz4, and deflate decompression.
+//!
+//! The composite extractor dispatches GUID-defined sections to extractors through a lookup table keyed on the section
+//! definition GUID, rather than attempting each extractor in turn. When several extractors are registered for the same
+//! GUID, they are attempted in priority order until one does not return [`FirmwareFileSystemError::Unsupported`].
 //!
 //! ## License
 //!
@@ -6,10 +10,12 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+use alloc::{boxed::Box, vec::Vec};
 use patina_ffs::{
-    FirmwareFileSystemError,
+    ExtractionError, FirmwareFileSystemError,
     section::{Section, SectionExtractor},
 };
+use r_efi::efi;
 
 #[cfg(feature = "brotli")]
 use crate::BrotliSectionExtractor;
@@ -17,16 +23,51 @@ use crate::BrotliSectionExtractor;
 use crate::Crc32SectionExtractor;
 #[cfg(feature = "lzma")]
 use crate::LzmaSectionExtractor;
+use crate::observer::{ExtractionRecord, ExtractionStart, ExtractorObserver, TimestampSource, section_guid};
 
-/// Provides a composite section extractor that combines all section extractors based on enabled feature flags.
+/// Priority of the extractors registered by [`CompositeSectionExtractorBuilder::with_default_extractors`].
+pub const DEFAULT_EXTRACTOR_PRIORITY: u32 = 100;
+
+/// An extractor registered for a section definition GUID.
 #[derive(Clone, Copy)]
-pub struct CompositeSectionExtractor {
+struct DispatchEntry {
+    guid: efi::Guid,
+    priority: u32,
+    extractor: &'static (dyn SectionExtractor + Sync),
+}
+
+/// Dispatch table for the extractors enabled by feature flags, sorted by GUID.
+const DEFAULT_DISPATCH_TABLE: &[DispatchEntry] = &[
     #[cfg(feature = "brotli")]
-    brotli: BrotliSectionExtractor,
-    #[cfg(feature = "crc32")]
-    crc32: Crc32SectionExtractor,
+    DispatchEntry {
+        guid: patina::pi::fw_fs::guid::BROTLI_SECTION,
+        priority: DEFAULT_EXTRACTOR_PRIORITY,
+        extractor: &BrotliSectionExtractor::new(),
+    },
     #[cfg(feature = "lzma")]
-    lzma: LzmaSectionExtractor,
+    DispatchEntry {
+        guid: crate::lzma::LZMA_SECTION_GUID,
+        priority: DEFAULT_EXTRACTOR_PRIORITY,
+        extractor: &LzmaSectionExtractor::new(),
+    },
+    #[cfg(feature = "crc32")]
+    DispatchEntry {
+        guid: patina::pi::fw_fs::guid::CRC32_SECTION,
+        priority: DEFAULT_EXTRACTOR_PRIORITY,
+        extractor: &Crc32SectionExtractor {},
+    },
+];
+
+/// Provides a composite section extractor that combines all section extractors based on enabled feature flags.
+///
+/// [`CompositeSectionExtractor::new`] uses the extractors enabled by feature flags. Use
+/// [`CompositeSectionExtractor::builder`] to register additional extractors or change their priority.
+#[derive(Clone, Copy)]
+pub struct CompositeSectionExtractor {
+    table: &'static [DispatchEntry],
+    fallback: Option<&'static (dyn SectionExtractor + Sync)>,
+    observer: Option<&'static dyn ExtractorObserver>,
+    timestamp_source: Option<&'static dyn TimestampSource>,
 }
 
 impl Default for CompositeSectionExtractor {
@@ -38,47 +79,158 @@ impl Default for CompositeSectionExtractor {
 impl CompositeSectionExtractor {
     /// Creates a new instance of the composite section extractor.
     pub const fn new() -> Self {
-        Self {
-            #[cfg(feature = "brotli")]
-            brotli: BrotliSectionExtractor {},
-            #[cfg(feature = "crc32")]
-            crc32: Crc32SectionExtractor {},
-            #[cfg(feature = "lzma")]
-            lzma: LzmaSectionExtractor {},
-        }
+        Self { table: DEFAULT_DISPATCH_TABLE, fallback: None, observer: None, timestamp_source: None }
     }
-}
 
-impl SectionExtractor for CompositeSectionExtractor {
-    fn extract(&self, _section: &Section) -> Result<alloc::vec::Vec<u8>, FirmwareFileSystemError> {
-        #[cfg(feature = "brotli")]
-        {
-            match self.brotli.extract(_section) {
-                Err(FirmwareFileSystemError::Unsupported) => (),
-                Err(err) => return Err(err),
-                Ok(buffer) => return Ok(buffer),
+    /// Creates a builder for a composite section extractor with a custom dispatch table.
+    pub fn builder() -> CompositeSectionExtractorBuilder {
+        CompositeSectionExtractorBuilder::new()
+    }
+
+    /// Sets an observer that is notified before and after every extraction.
+    pub const fn with_observer(mut self, observer: &'static dyn ExtractorObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Sets the timestamp source used to time extractions reported to the observer.
+    ///
+    /// If no timestamp source is set, all reported timestamps are `0`.
+    pub const fn with_timestamp_source(mut self, timestamp_source: &'static dyn TimestampSource) -> Self {
+        self.timestamp_source = Some(timestamp_source);
+        self
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp_source.map_or(0, |source| source.timestamp())
+    }
+
+    /// Extracts the section, notifying `observer` before and after the extraction.
+    fn observed_extract(
+        &self,
+        observer: &dyn ExtractorObserver,
+        section: &Section,
+    ) -> Result<Vec<u8>, ExtractionError> {
+        let section_guid = section_guid(section);
+        let compressed_size = section.header().content_size();
+
+        let start_timestamp = self.timestamp();
+        observer.extraction_started(&ExtractionStart { section_guid, compressed_size, start_timestamp });
+
+        let result = self.extract_inner(section_guid, section);
+
+        let end_timestamp = self.timestamp();
+        observer.extraction_finished(&ExtractionRecord {
+            section_guid,
+            compressed_size,
+            result: result.as_ref().map(|buffer| buffer.len()).map_err(|err| err.error),
+            start_timestamp,
+            end_timestamp,
+            frequency: self.timestamp_source.map_or(0, |source| source.frequency()),
+        });
+
+        result
+    }
+
+    /// Returns the extractors registered for `guid`, in priority order.
+    fn extractors_for(&self, guid: &efi::Guid) -> impl Iterator<Item = &'static (dyn SectionExtractor + Sync)> {
+        let table = self.table;
+        let start = table.partition_point(|entry| entry.guid < *guid);
+        table[start..].iter().take_while(move |entry| entry.guid == *guid).map(|entry| entry.extractor)
+    }
+
+    fn extract_inner(&self, section_guid: Option<efi::Guid>, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        if let Some(guid) = section_guid {
+            for extractor in self.extractors_for(&guid) {
+                match extractor.extract_with_context(section) {
+                    Err(err) if err.error == FirmwareFileSystemError::Unsupported => (),
+                    result => return result,
+                }
             }
         }
 
-        #[cfg(feature = "crc32")]
-        {
-            match self.crc32.extract(_section) {
-                Err(FirmwareFileSystemError::Unsupported) => (),
-                Err(err) => return Err(err),
-                Ok(buffer) => return Ok(buffer),
-            }
+        match self.fallback {
+            Some(fallback) => fallback.extract_with_context(section),
+            None => Err(section.error_context(FirmwareFileSystemError::Unsupported)),
         }
+    }
+}
 
-        #[cfg(feature = "lzma")]
-        {
-            match self.lzma.extract(_section) {
-                Err(FirmwareFileSystemError::Unsupported) => (),
-                Err(err) => return Err(err),
-                Ok(buffer) => return Ok(buffer),
-            }
+impl SectionExtractor for CompositeSectionExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        match self.observer {
+            Some(observer) => self.observed_extract(observer, section),
+            None => self.extract_inner(section_guid(section), section),
         }
+    }
+}
+
+/// Builds a [`CompositeSectionExtractor`] with a custom dispatch table.
+///
+/// Extractors are registered for a section definition GUID with a priority. When several extractors are registered
+/// for the same GUID, higher priorities are attempted first, and extractors with equal priority are attempted in
+/// registration order.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let extractor = CompositeSectionExtractor::builder()
+///     .with_default_extractors()
+///     .with_extractor(LZMA_SECTION_GUID, &MyFastLzmaExtractor, DEFAULT_EXTRACTOR_PRIORITY + 1)
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct CompositeSectionExtractorBuilder {
+    entries: Vec<DispatchEntry>,
+    fallback: Option<&'static (dyn SectionExtractor + Sync)>,
+}
 
-        Err(FirmwareFileSystemError::Unsupported)
+impl CompositeSectionExtractorBuilder {
+    /// Creates a builder with an empty dispatch table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the extractors enabled by feature flags with [`DEFAULT_EXTRACTOR_PRIORITY`].
+    pub fn with_default_extractors(mut self) -> Self {
+        self.entries.extend_from_slice(DEFAULT_DISPATCH_TABLE);
+        self
+    }
+
+    /// Registers `extractor` for sections whose section definition GUID is `guid`.
+    pub fn with_extractor(
+        mut self,
+        guid: efi::Guid,
+        extractor: &'static (dyn SectionExtractor + Sync),
+        priority: u32,
+    ) -> Self {
+        self.entries.push(DispatchEntry { guid, priority, extractor });
+        self
+    }
+
+    /// Sets an extractor that is attempted for sections no registered extractor supports, including sections that
+    /// are not GUID-defined.
+    pub fn with_fallback(mut self, extractor: &'static (dyn SectionExtractor + Sync)) -> Self {
+        self.fallback = Some(extractor);
+        self
+    }
+
+    /// Builds the composite section extractor.
+    ///
+    /// The dispatch table is leaked, as section extractors are registered for the lifetime of the core.
+    pub fn build(mut self) -> CompositeSectionExtractor {
+        // Stable sort, so equal priorities keep their registration order.
+        self.entries.sort_by(|a, b| a.guid.cmp(&b.guid).then(b.priority.cmp(&a.priority)));
+        CompositeSectionExtractor {
+            table: Box::leak(self.entries.into_boxed_slice()),
+            fallback: self.fallback,
+            observer: None,
+            timestamp_source: None,
+        }
     }
 }
 
@@ -90,7 +242,7 @@ mod tests {
     #[test]
     #[cfg(feature = "crc32")]
     fn test_composite_extracts_crc32() {
-        use crate::tests::create_crc32_section;
+        use crate::testing::create_crc32_section;
 
         let content = b"Test CRC32 content";
         let crc32 = crc32fast::hash(content);
@@ -102,12 +254,31 @@ mod tests {
         assert_eq!(result, content);
     }
 
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_composite_forwards_error_context() {
+        use crate::testing::create_crc32_section;
+        use patina_ffs::ErrorReason;
+
+        let content = b"Test CRC32 content";
+        let section = create_crc32_section(content, 0u32.to_le_bytes().to_vec());
+        let extractor = CompositeSectionExtractor::default();
+
+        let err = extractor.extract_with_context(&section).unwrap_err();
+        assert_eq!(err.section_guid, Some(patina::pi::fw_fs::guid::CRC32_SECTION));
+        assert_eq!(
+            err.reason,
+            Some(ErrorReason::CrcMismatch { expected_crc: 0, actual_crc: crc32fast::hash(content) })
+        );
+        assert_eq!(extractor.extract(&section), Err(FirmwareFileSystemError::DataCorrupt));
+    }
+
     #[test]
     #[cfg(feature = "brotli")]
     fn test_composite_extracts_brotli() {
         // Pre-compressed "Hello, World!" using Brotli
 
-        use crate::tests::create_brotli_section;
+        use crate::testing::create_brotli_section;
         let brotli_compressed_data: [u8; 18] = [
             0x21, 0x30, 0x00, 0x04, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x57, 0x6F, 0x72, 0x6C, 0x64, 0x21, 0x03,
         ];
@@ -124,7 +295,7 @@ mod tests {
     fn test_composite_extracts_lzma() {
         // Pre-compressed "Hello, World!" using LZMA
 
-        use crate::tests::create_lzma_section;
+        use crate::testing::create_lzma_section;
         let lzma_compressed_data: &[u8] = &[
             0x5D, 0x00, 0x00, 0x80, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x24, 0x19, 0x49, 0x98,
             0x6F, 0x16, 0x02, 0x89, 0x0A, 0x98, 0xE7, 0x3F, 0xA8, 0xC3, 0x95, 0x48, 0x4D, 0xFF, 0xFF, 0x75, 0xF0, 0x00,
@@ -136,4 +307,220 @@ mod tests {
 
         assert_eq!(result, b"Hello, World!");
     }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn test_composite_extracts_lz4() {
+        use crate::{
+            Lz4SectionExtractor,
+            testing::{LZ4_TEST_SECTION_GUID, create_lz4_section},
+        };
+
+        static LZ4: Lz4SectionExtractor = Lz4SectionExtractor::new(LZ4_TEST_SECTION_GUID);
+
+        let content = b"Hello, World! Hello, World!";
+        let section = create_lz4_section(&lz4_flex::block::compress_prepend_size(content));
+        // LZ4 has no standard GUID, so it is not part of the default dispatch table.
+        assert_eq!(CompositeSectionExtractor::default().extract(&section), Err(FirmwareFileSystemError::Unsupported));
+
+        let extractor = CompositeSectionExtractor::builder()
+            .with_default_extractors()
+            .with_extractor(LZ4_TEST_SECTION_GUID, &LZ4, DEFAULT_EXTRACTOR_PRIORITY)
+            .build();
+        let result = extractor.extract(&section).expect("LZ4 extraction should succeed");
+
+        assert_eq!(result, content);
+    }
+
+    #[test]
+    #[cfg(feature = "deflate")]
+    fn test_composite_extracts_deflate() {
+        use crate::{
+            DeflateSectionExtractor,
+            testing::{DEFLATE_TEST_SECTION_GUID, create_deflate_section},
+        };
+
+        static DEFLATE: DeflateSectionExtractor = DeflateSectionExtractor::new(DEFLATE_TEST_SECTION_GUID);
+
+        let content = b"Hello, World! Hello, World!";
+        let section = create_deflate_section(&miniz_oxide::deflate::compress_to_vec_zlib(content, 6));
+        // DEFLATE has no standard GUID, so it is not part of the default dispatch table.
+        assert_eq!(CompositeSectionExtractor::default().extract(&section), Err(FirmwareFileSystemError::Unsupported));
+
+        let extractor = CompositeSectionExtractor::builder()
+            .with_default_extractors()
+            .with_extractor(DEFLATE_TEST_SECTION_GUID, &DEFLATE, DEFAULT_EXTRACTOR_PRIORITY)
+            .build();
+        let result = extractor.extract(&section).expect("DEFLATE extraction should succeed");
+
+        assert_eq!(result, content);
+    }
+
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_composite_notifies_observer() {
+        use crate::testing::create_crc32_section;
+        use alloc::boxed::Box;
+        use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+        use patina::pi::fw_fs::guid::CRC32_SECTION;
+
+        struct TestTimestampSource(AtomicU64);
+
+        impl TimestampSource for TestTimestampSource {
+            fn timestamp(&self) -> u64 {
+                self.0.fetch_add(10, Ordering::SeqCst)
+            }
+
+            fn frequency(&self) -> u64 {
+                1_000_000_000
+            }
+        }
+
+        #[derive(Default)]
+        struct TestObserver {
+            started: AtomicUsize,
+            finished: AtomicUsize,
+            compressed_size: AtomicUsize,
+            decompressed_size: AtomicUsize,
+            elapsed_ns: AtomicU64,
+        }
+
+        impl ExtractorObserver for TestObserver {
+            fn extraction_started(&self, start: &ExtractionStart) {
+                assert_eq!(start.section_guid, Some(CRC32_SECTION));
+                self.started.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn extraction_finished(&self, record: &ExtractionRecord) {
+                assert_eq!(record.section_guid, Some(CRC32_SECTION));
+                self.finished.fetch_add(1, Ordering::SeqCst);
+                self.compressed_size.store(record.compressed_size, Ordering::SeqCst);
+                self.decompressed_size.store(record.decompressed_size().unwrap_or(usize::MAX), Ordering::SeqCst);
+                self.elapsed_ns.store(record.elapsed_ns().unwrap(), Ordering::SeqCst);
+            }
+        }
+
+        let observer: &'static TestObserver = Box::leak(Box::default());
+        let timestamp_source: &'static TestTimestampSource =
+            Box::leak(Box::new(TestTimestampSource(AtomicU64::new(0))));
+        let extractor =
+            CompositeSectionExtractor::new().with_observer(observer).with_timestamp_source(timestamp_source);
+
+        let content = b"Observed CRC32 content";
+        let section = create_crc32_section(content, crc32fast::hash(content).to_le_bytes().to_vec());
+        assert_eq!(extractor.extract(&section).unwrap(), content);
+
+        let bad_section = create_crc32_section(content, 0xDEADBEEFu32.to_le_bytes().to_vec());
+        assert_eq!(extractor.extract(&bad_section), Err(FirmwareFileSystemError::DataCorrupt));
+
+        assert_eq!(observer.started.load(Ordering::SeqCst), 2);
+        assert_eq!(observer.finished.load(Ordering::SeqCst), 2);
+        assert_eq!(observer.compressed_size.load(Ordering::SeqCst), content.len());
+        assert_eq!(observer.decompressed_size.load(Ordering::SeqCst), usize::MAX);
+        assert_eq!(observer.elapsed_ns.load(Ordering::SeqCst), 10);
+    }
+
+    struct TestExtractor {
+        output: &'static [u8],
+        calls: core::sync::atomic::AtomicUsize,
+        result: Option<FirmwareFileSystemError>,
+    }
+
+    impl TestExtractor {
+        const fn new(output: &'static [u8], result: Option<FirmwareFileSystemError>) -> Self {
+            Self { output, calls: core::sync::atomic::AtomicUsize::new(0), result }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(core::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl SectionExtractor for TestExtractor {
+        fn extract(&self, _section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+            self.calls.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+            match self.result {
+                Some(err) => Err(err),
+                None => Ok(self.output.to_vec()),
+            }
+        }
+    }
+
+    const TEST_GUID: efi::Guid =
+        efi::Guid::from_fields(0x0d3fb176, 0x9569, 0x4d51, 0xa3, 0xef, &[0x7d, 0x61, 0xc6, 0x4f, 0xea, 0xba]);
+
+    fn test_section(guid: efi::Guid) -> Section {
+        crate::testing::create_guid_defined_section(guid, alloc::vec![], b"payload")
+    }
+
+    #[test]
+    fn test_default_dispatch_table_is_sorted() {
+        assert!(DEFAULT_DISPATCH_TABLE.windows(2).all(|pair| pair[0].guid < pair[1].guid));
+    }
+
+    #[test]
+    fn test_builder_dispatches_by_priority() {
+        static LOW: TestExtractor = TestExtractor::new(b"low", None);
+        static HIGH: TestExtractor = TestExtractor::new(b"high", None);
+
+        let extractor = CompositeSectionExtractor::builder()
+            .with_extractor(TEST_GUID, &LOW, 1)
+            .with_extractor(TEST_GUID, &HIGH, 2)
+            .build();
+
+        assert_eq!(extractor.extract(&test_section(TEST_GUID)).unwrap(), b"high");
+        assert_eq!(HIGH.calls(), 1);
+        assert_eq!(LOW.calls(), 0);
+    }
+
+    #[test]
+    fn test_builder_skips_unsupported_and_short_circuits() {
+        static UNSUPPORTED: TestExtractor = TestExtractor::new(b"", Some(FirmwareFileSystemError::Unsupported));
+        static CORRUPT: TestExtractor = TestExtractor::new(b"", Some(FirmwareFileSystemError::DataCorrupt));
+        static NEVER: TestExtractor = TestExtractor::new(b"never", None);
+
+        let extractor = CompositeSectionExtractor::builder()
+            .with_extractor(TEST_GUID, &UNSUPPORTED, 3)
+            .with_extractor(TEST_GUID, &CORRUPT, 2)
+            .with_extractor(TEST_GUID, &NEVER, 1)
+            .build();
+
+        assert_eq!(extractor.extract(&test_section(TEST_GUID)), Err(FirmwareFileSystemError::DataCorrupt));
+        assert_eq!(UNSUPPORTED.calls(), 1);
+        assert_eq!(CORRUPT.calls(), 1);
+        assert_eq!(NEVER.calls(), 0);
+    }
+
+    #[test]
+    fn test_builder_only_calls_matching_extractors() {
+        static OTHER: TestExtractor = TestExtractor::new(b"other", None);
+        static FALLBACK: TestExtractor = TestExtractor::new(b"fallback", None);
+
+        let other_guid =
+            efi::Guid::from_fields(0x6c2b3cd8, 0x1d0f, 0x4a61, 0x8f, 0x43, &[0x2e, 0x5a, 0x11, 0x90, 0x7b, 0x04]);
+        let extractor =
+            CompositeSectionExtractor::builder().with_extractor(other_guid, &OTHER, 1).with_fallback(&FALLBACK).build();
+
+        assert_eq!(extractor.extract(&test_section(TEST_GUID)).unwrap(), b"fallback");
+        assert_eq!(OTHER.calls(), 0);
+        assert_eq!(FALLBACK.calls(), 1);
+
+        let empty = CompositeSectionExtractor::builder().build();
+        assert_eq!(empty.extract(&test_section(TEST_GUID)), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_builder_overrides_default_extractor() {
+        use patina::pi::fw_fs::guid::CRC32_SECTION;
+
+        static OVERRIDE: TestExtractor = TestExtractor::new(b"override", None);
+
+        let extractor = CompositeSectionExtractor::builder()
+            .with_default_extractors()
+            .with_extractor(CRC32_SECTION, &OVERRIDE, DEFAULT_EXTRACTOR_PRIORITY + 1)
+            .build();
+
+        assert_eq!(extractor.extract(&test_section(CRC32_SECTION)).unwrap(), b"override");
+    }
 }
@@ -0,0 +1,132 @@
+//! Module for compressing diagnostic data as GUID-defined sections.
+//!
+//! [`SectionCompressor`] implements the SDK [`Compressor`] trait with the encoders of [`SectionBuilder`], wrapping the
+//! compressed data in a serialized GUID-defined section. The section definition GUID identifies the codec, so
+//! [`SectionDecompressor`] can restore the data with the same extractors used for firmware volumes. This lets the
+//! memory log or a crash dump be compressed on the target and decompressed by host tooling built on this crate.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use patina::{
+    compression::{Compressor, Decompressor},
+    error::EfiError,
+};
+use patina_ffs::section::{Section, SectionExtractor, SectionHeader};
+
+use crate::{CompositeSectionExtractor, GuidedSectionFormat, SectionBuilder};
+
+/// A [`Compressor`] that produces serialized GUID-defined sections.
+///
+/// LZMA compression is available wherever the `lzma` feature is enabled. The Brotli encoder requires `std`, so Brotli
+/// compression is only available with the `brotli-encoder` feature, typically in host tools; elsewhere it fails with
+/// [`EfiError::Unsupported`].
+#[derive(Debug, Clone, Copy)]
+pub struct SectionCompressor {
+    format: GuidedSectionFormat,
+    builder: SectionBuilder,
+}
+
+impl SectionCompressor {
+    /// Creates a compressor producing sections of the given format.
+    pub const fn new(format: GuidedSectionFormat) -> Self {
+        Self { format, builder: SectionBuilder::new() }
+    }
+
+    /// Uses `builder` to build the sections, for example to compress Brotli sections with a custom dictionary.
+    pub const fn with_builder(mut self, builder: SectionBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+}
+
+impl Compressor for SectionCompressor {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, EfiError> {
+        Ok(self.builder.build(self.format, data)?.serialize()?)
+    }
+}
+
+/// A [`Decompressor`] for data produced by a [`SectionCompressor`].
+///
+/// The data is extracted with a [`CompositeSectionExtractor`] by default, which supports every format enabled in
+/// this crate. Sections compressed with a custom Brotli dictionary need an extractor provided the same dictionary.
+pub struct SectionDecompressor<E: SectionExtractor = CompositeSectionExtractor> {
+    extractor: E,
+}
+
+impl SectionDecompressor {
+    /// Creates a decompressor using the default [`CompositeSectionExtractor`].
+    pub const fn new() -> Self {
+        Self { extractor: CompositeSectionExtractor::new() }
+    }
+}
+
+impl Default for SectionDecompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: SectionExtractor> SectionDecompressor<E> {
+    /// Creates a decompressor using `extractor`.
+    pub const fn with_extractor(extractor: E) -> Self {
+        Self { extractor }
+    }
+}
+
+impl<E: SectionExtractor> Decompressor for SectionDecompressor<E> {
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, EfiError> {
+        let section = Section::new_from_buffer(data)?;
+        match section.header() {
+            SectionHeader::GuidDefined(guid_header, _, _)
+                if GuidedSectionFormat::from_guid(&guid_header.section_definition_guid).is_some() =>
+            {
+                Ok(self.extractor.extract(&section)?)
+            }
+            _ => Err(EfiError::Unsupported),
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const LOG: &[u8] = b"INFO - Advanced logger buffer initialized.\nINFO - Advanced logger buffer initialized.\n";
+
+    #[test]
+    #[cfg(feature = "lzma")]
+    fn test_lzma_round_trip() {
+        let compressed = SectionCompressor::new(GuidedSectionFormat::Lzma).compress(LOG).unwrap();
+        assert_ne!(compressed, LOG);
+        assert_eq!(SectionDecompressor::new().decompress(&compressed).unwrap(), LOG);
+    }
+
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_crc32_round_trip() {
+        let compressed = SectionCompressor::new(GuidedSectionFormat::Crc32).compress(LOG).unwrap();
+        assert_eq!(SectionDecompressor::default().decompress(&compressed).unwrap(), LOG);
+    }
+
+    #[test]
+    #[cfg(not(feature = "brotli-encoder"))]
+    fn test_brotli_requires_encoder() {
+        let result = SectionCompressor::new(GuidedSectionFormat::Brotli).compress(LOG);
+        assert_eq!(result, Err(EfiError::Unsupported));
+    }
+
+    #[test]
+    fn test_decompress_rejects_other_data() {
+        assert!(SectionDecompressor::new().decompress(&[0u8; 2]).is_err());
+
+        // A raw section is valid, but not compressed data.
+        let raw = [0x0A, 0x00, 0x00, 0x19, b'a', b'b', b'c', b'd', b'e', b'f'];
+        assert_eq!(SectionDecompressor::new().decompress(&raw), Err(EfiError::Unsupported));
+    }
+}
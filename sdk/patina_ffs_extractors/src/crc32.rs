@@ -6,10 +6,11 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+use alloc::vec::Vec;
 use patina::pi::fw_fs;
 use patina_ffs::{
-    FirmwareFileSystemError,
-    section::{SectionExtractor, SectionHeader},
+    ErrorReason, ExtractionError, FirmwareFileSystemError,
+    section::{Section, SectionExtractor, SectionHeader},
 };
 
 /// Provides extraction for CRC32 sections.
@@ -25,34 +26,43 @@ impl Crc32SectionExtractor {
 }
 
 impl SectionExtractor for Crc32SectionExtractor {
-    fn extract(&self, section: &patina_ffs::section::Section) -> Result<alloc::vec::Vec<u8>, FirmwareFileSystemError> {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
         if let SectionHeader::GuidDefined(guid_header, crc_header, _) = section.header()
             && guid_header.section_definition_guid == fw_fs::guid::CRC32_SECTION
         {
-            if crc_header.len() < 4 {
-                Err(FirmwareFileSystemError::DataCorrupt)?;
-            }
-            let crc32 = u32::from_le_bytes((**crc_header).try_into().unwrap());
-            let content = section.try_content_as_slice()?;
-            if crc32 != crc32fast::hash(content) {
+            let Some(crc_bytes) = crc_header.first_chunk::<4>() else {
+                return Err(section
+                    .error_context(FirmwareFileSystemError::DataCorrupt)
+                    .with_reason(ErrorReason::Truncated { required_size: 4, available_size: crc_header.len() }));
+            };
+            let expected_crc = u32::from_le_bytes(*crc_bytes);
+            let content = section.try_content_as_slice().map_err(|err| section.error_context(err))?;
+            let actual_crc = crc32fast::hash(content);
+            if expected_crc != actual_crc {
                 //TODO: in EDK2 C reference implementation, data is returned along with EFI_AUTH_STATUS_TEST_FAILED.
                 //For now, just return an error if the CRC fails to check.
-                Err(FirmwareFileSystemError::DataCorrupt)?;
+                return Err(section
+                    .error_context(FirmwareFileSystemError::DataCorrupt)
+                    .with_reason(ErrorReason::CrcMismatch { expected_crc, actual_crc }));
             }
             return Ok(content.to_vec());
         }
-        Err(FirmwareFileSystemError::Unsupported)
+        Err(section.error_context(FirmwareFileSystemError::Unsupported))
     }
 }
 
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
-    use crate::tests::create_crc32_section;
+    use crate::testing::create_crc32_section;
 
     use super::*;
+    use alloc::vec;
     use patina::pi::fw_fs::ffs::section::header::GuidDefined;
-    use patina_ffs::section::Section;
     use r_efi::efi;
 
     #[test]
@@ -79,6 +89,27 @@ mod tests {
         assert!(matches!(result, Err(FirmwareFileSystemError::DataCorrupt)));
     }
 
+    #[test]
+    fn test_crc32_extractor_error_context() {
+        let content = b"Hello, CRC32!";
+        let section = create_crc32_section(content, 0xDEADBEEFu32.to_le_bytes().to_vec());
+        assert_eq!(
+            Crc32SectionExtractor.extract_with_context(&section),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_section_guid(fw_fs::guid::CRC32_SECTION)
+                .with_reason(ErrorReason::CrcMismatch {
+                    expected_crc: 0xDEADBEEF,
+                    actual_crc: crc32fast::hash(content)
+                }))
+        );
+
+        let section = create_crc32_section(content, vec![0x12, 0x34]);
+        assert_eq!(
+            Crc32SectionExtractor.extract_with_context(&section).unwrap_err().reason,
+            Some(ErrorReason::Truncated { required_size: 4, available_size: 2 })
+        );
+    }
+
     #[test]
     fn test_crc32_extractor_empty_content() {
         let content = b"";
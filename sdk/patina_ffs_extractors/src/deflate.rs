@@ -0,0 +1,339 @@
+//! Module for DEFLATE (gzip and zlib) decompression.
+//!
+//! The content of a DEFLATE GUIDed section is a single gzip member (RFC 1952) or a zlib stream (RFC 1950). The format
+//! is detected from the first two bytes, so build tooling can emit either without a separate GUID.
+//!
+//! The PI specification does not assign a section definition GUID to DEFLATE, so the platform provides the GUID its
+//! build tooling uses when constructing the extractor.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::result::Result;
+use miniz_oxide::inflate::{
+    TINFLStatus,
+    core::{DecompressorOxide, decompress, inflate_flags},
+};
+use patina_ffs::{
+    ErrorReason, ExtractionError, FirmwareFileSystemError,
+    section::{Section, SectionExtractor, SectionHeader},
+};
+use r_efi::efi;
+
+/// gzip member magic bytes.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// gzip compression method for DEFLATE.
+const GZIP_CM_DEFLATE: u8 = 8;
+/// Size of the fixed part of the gzip member header.
+const GZIP_HEADER_SIZE: usize = 10;
+/// Size of the gzip member trailer (CRC32 and ISIZE).
+const GZIP_TRAILER_SIZE: usize = 8;
+/// Upper bound on the DEFLATE compression ratio, used to reject implausible gzip sizes before allocating and to bound
+/// the output of zlib streams, which do not record their decompressed size.
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+/// gzip header flag bits.
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+const FRESERVED: u8 = 0xE0;
+
+/// Provides decompression for gzip and zlib GUIDed sections.
+#[derive(Clone, Copy)]
+pub struct DeflateSectionExtractor {
+    guid: efi::Guid,
+}
+
+impl DeflateSectionExtractor {
+    /// Creates a new `DeflateSectionExtractor` for sections with the section definition GUID `guid`.
+    pub const fn new(guid: efi::Guid) -> Self {
+        Self { guid }
+    }
+
+    /// Returns the section definition GUID this extractor handles.
+    pub const fn guid(&self) -> efi::Guid {
+        self.guid
+    }
+
+    /// Extracts a DEFLATE section into `out`, returning the number of bytes written.
+    ///
+    /// ## Errors
+    ///
+    /// - [`FirmwareFileSystemError::Unsupported`] if `section` is not a DEFLATE section.
+    /// - [`FirmwareFileSystemError::BufferTooSmall`] if `out` cannot hold the decompressed data.
+    /// - [`FirmwareFileSystemError::DataCorrupt`] if the section content is not a valid gzip member or zlib stream,
+    ///   or its checksum does not match.
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == self.guid
+        {
+            return Self::decompress_to_slice(section.try_content_as_slice()?, out);
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    /// Decompresses a raw gzip member or zlib stream into `out`, returning the number of bytes written.
+    ///
+    /// This is the slice-based form of [`Self::extract_to_slice`] for callers that have the section content but not a
+    /// parsed [`Section`]. The only allocation made is the decompressor state.
+    pub fn decompress_to_slice(data: &[u8], out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        Self::decompress(data, out).map_err(Into::into)
+    }
+
+    /// Decompresses a raw gzip member or zlib stream into `out`, reporting failures with diagnostic context.
+    fn decompress(data: &[u8], out: &mut [u8]) -> Result<usize, ExtractionError> {
+        if data.starts_with(&GZIP_MAGIC) {
+            Self::gunzip_into(data, out)
+        } else {
+            let (size, _) = inflate_into(data, out, inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER)?;
+            Ok(size)
+        }
+    }
+
+    /// Decompresses a gzip member into `out`, verifying the CRC32 and size in the trailer.
+    fn gunzip_into(data: &[u8], out: &mut [u8]) -> Result<usize, ExtractionError> {
+        let header_size = gzip_header_size(data)?;
+        let body = data.get(header_size..).ok_or(FirmwareFileSystemError::DataCorrupt)?;
+        let (size, consumed) = inflate_into(body, out, 0).map_err(|err| match err.offset {
+            Some(offset) => err.with_offset(header_size + offset),
+            None => err,
+        })?;
+
+        let trailer_offset = header_size + consumed;
+        let trailer = data.get(trailer_offset..trailer_offset + GZIP_TRAILER_SIZE).ok_or_else(|| {
+            ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_offset(trailer_offset).with_reason(
+                ErrorReason::Truncated {
+                    required_size: GZIP_TRAILER_SIZE,
+                    available_size: data.len() - trailer_offset,
+                },
+            )
+        })?;
+        let expected_crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let isize = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+        let actual_crc = crc32fast::hash(&out[..size]);
+        let reason = if expected_crc != actual_crc {
+            ErrorReason::CrcMismatch { expected_crc, actual_crc }
+        } else if isize != size as u32 {
+            ErrorReason::SizeMismatch { expected_size: isize as usize, actual_size: size }
+        } else {
+            return Ok(size);
+        };
+        Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_offset(trailer_offset).with_reason(reason))
+    }
+
+    /// Returns the decompressed size recorded in a gzip trailer, modulo 2^32.
+    fn gzip_out_size(data: &[u8]) -> Result<usize, FirmwareFileSystemError> {
+        let isize = data.last_chunk::<4>().ok_or(FirmwareFileSystemError::DataCorrupt)?;
+        let size = u32::from_le_bytes(*isize) as usize;
+        if size > data.len().saturating_mul(MAX_DEFLATE_RATIO) {
+            return Err(FirmwareFileSystemError::DataCorrupt);
+        }
+        Ok(size)
+    }
+
+    /// Decompresses a raw gzip member or zlib stream into a new buffer.
+    fn decompress_to_vec(data: &[u8]) -> Result<Vec<u8>, ExtractionError> {
+        if !data.starts_with(&GZIP_MAGIC) {
+            // zlib streams do not record the decompressed size, so the output grows up to the largest plausible size.
+            return inflate_zlib_to_vec(data, data.len().saturating_mul(MAX_DEFLATE_RATIO));
+        }
+        let mut decompressed = vec![0u8; Self::gzip_out_size(data)?];
+        Self::gunzip_into(data, &mut decompressed)?;
+        Ok(decompressed)
+    }
+}
+
+impl SectionExtractor for DeflateSectionExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == self.guid
+        {
+            let data = section.try_content_as_slice().map_err(|err| section.error_context(err))?;
+            return Self::decompress_to_vec(data).map_err(|err| err.with_section_guid(self.guid));
+        }
+        Err(section.error_context(FirmwareFileSystemError::Unsupported))
+    }
+}
+
+/// Returns the size of the gzip member header, including the optional fields selected by its flags.
+fn gzip_header_size(data: &[u8]) -> Result<usize, FirmwareFileSystemError> {
+    let header = data.get(..GZIP_HEADER_SIZE).ok_or(FirmwareFileSystemError::DataCorrupt)?;
+    let flags = header[3];
+    if header[2] != GZIP_CM_DEFLATE || flags & FRESERVED != 0 {
+        return Err(FirmwareFileSystemError::DataCorrupt);
+    }
+
+    let mut offset = GZIP_HEADER_SIZE;
+    if flags & FEXTRA != 0 {
+        let xlen = data.get(offset..offset + 2).ok_or(FirmwareFileSystemError::DataCorrupt)?;
+        offset += 2 + u16::from_le_bytes(xlen.try_into().unwrap()) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let field = data.get(offset..).ok_or(FirmwareFileSystemError::DataCorrupt)?;
+            let terminator = field.iter().position(|&b| b == 0).ok_or(FirmwareFileSystemError::DataCorrupt)?;
+            offset += terminator + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        offset += 2;
+    }
+    Ok(offset)
+}
+
+/// Inflates the zlib stream `data` into a new buffer of at most `limit` bytes.
+///
+/// A stream that decompresses to more than `limit` bytes is reported as corrupt.
+fn inflate_zlib_to_vec(data: &[u8], limit: usize) -> Result<Vec<u8>, ExtractionError> {
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, limit).map_err(|_| {
+        ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_reason(ErrorReason::MalformedStream)
+    })
+}
+
+/// Inflates `data` into `out`, returning the number of bytes written and consumed.
+///
+/// A malformed stream is reported with the offset into `data` at which the decompressor stopped.
+fn inflate_into(data: &[u8], out: &mut [u8], flags: u32) -> Result<(usize, usize), ExtractionError> {
+    let mut decompressor = Box::<DecompressorOxide>::default();
+    let flags = flags | inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+    match decompress(&mut decompressor, data, out, 0, flags) {
+        (TINFLStatus::Done, consumed, written) => Ok((written, consumed)),
+        (TINFLStatus::HasMoreOutput, _, _) => Err(FirmwareFileSystemError::BufferTooSmall.into()),
+        (_, consumed, _) => Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+            .with_offset(consumed)
+            .with_reason(ErrorReason::MalformedStream)),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use crate::testing::{DEFLATE_TEST_SECTION_GUID, create_deflate_section};
+
+    use super::*;
+
+    const EXTRACTOR: DeflateSectionExtractor = DeflateSectionExtractor::new(DEFLATE_TEST_SECTION_GUID);
+
+    const CONTENT: &[u8] = b"Hello, DEFLATE! Hello, DEFLATE! Hello, DEFLATE! Hello, DEFLATE!";
+
+    fn zlib(content: &[u8]) -> Vec<u8> {
+        miniz_oxide::deflate::compress_to_vec_zlib(content, 6)
+    }
+
+    fn gzip(content: &[u8], flags: u8, optional_fields: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x1f, 0x8b, GZIP_CM_DEFLATE, flags, 0, 0, 0, 0, 0, 0xFF];
+        data.extend_from_slice(optional_fields);
+        data.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(content, 6));
+        data.extend_from_slice(&crc32fast::hash(content).to_le_bytes());
+        data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_deflate_extractor_zlib() {
+        let section = create_deflate_section(&zlib(CONTENT));
+        assert_eq!(EXTRACTOR.extract(&section).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_deflate_extractor_gzip() {
+        let section = create_deflate_section(&gzip(CONTENT, 0, &[]));
+        assert_eq!(EXTRACTOR.extract(&section).unwrap(), CONTENT);
+
+        // FEXTRA, FNAME, FCOMMENT, and FHCRC fields are skipped.
+        let optional_fields = [&[3, 0, 1, 2, 3][..], b"name\0", b"comment\0", &[0xAB, 0xCD]].concat();
+        let section = create_deflate_section(&gzip(CONTENT, FEXTRA | FNAME | FCOMMENT | FHCRC, &optional_fields));
+        assert_eq!(EXTRACTOR.extract(&section).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn test_deflate_extractor_zlib_size_limit() {
+        let data = zlib(CONTENT);
+        assert_eq!(inflate_zlib_to_vec(&data, CONTENT.len()).unwrap(), CONTENT);
+        assert_eq!(
+            inflate_zlib_to_vec(&data, CONTENT.len() - 1),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_reason(ErrorReason::MalformedStream))
+        );
+
+        // Highly compressible content stays within the ratio bound.
+        let zeros = vec![0u8; 0x10000];
+        assert_eq!(EXTRACTOR.extract(&create_deflate_section(&zlib(&zeros))).unwrap(), zeros);
+    }
+
+    #[test]
+    fn test_deflate_extract_to_slice() {
+        for data in [zlib(CONTENT), gzip(CONTENT, 0, &[])] {
+            let section = create_deflate_section(&data);
+
+            let mut out = [0u8; 128];
+            let size = EXTRACTOR.extract_to_slice(&section, &mut out).unwrap();
+            assert_eq!(&out[..size], CONTENT);
+
+            let mut small = [0u8; 8];
+            assert_eq!(EXTRACTOR.extract_to_slice(&section, &mut small), Err(FirmwareFileSystemError::BufferTooSmall));
+        }
+    }
+
+    #[test]
+    fn test_deflate_extractor_checksum_mismatch() {
+        let mut data = zlib(CONTENT);
+        *data.last_mut().unwrap() ^= 0xFF;
+        assert_eq!(EXTRACTOR.extract(&create_deflate_section(&data)), Err(FirmwareFileSystemError::DataCorrupt));
+
+        let mut data = gzip(CONTENT, 0, &[]);
+        let crc_offset = data.len() - GZIP_TRAILER_SIZE;
+        data[crc_offset] ^= 0xFF;
+        assert_eq!(EXTRACTOR.extract(&create_deflate_section(&data)), Err(FirmwareFileSystemError::DataCorrupt));
+        let actual_crc = crc32fast::hash(CONTENT);
+        assert_eq!(
+            EXTRACTOR.extract_with_context(&create_deflate_section(&data)),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_section_guid(DEFLATE_TEST_SECTION_GUID)
+                .with_offset(crc_offset)
+                .with_reason(ErrorReason::CrcMismatch { expected_crc: actual_crc ^ 0xFF, actual_crc }))
+        );
+    }
+
+    #[test]
+    fn test_deflate_extractor_invalid_data() {
+        for data in [&[][..], &[0x1f, 0x8b, 0x07, 0, 0, 0, 0, 0, 0, 0], &[0x1f, 0x8b, 8, FNAME, 0, 0, 0, 0, 0, 0, b'x']]
+        {
+            assert_eq!(EXTRACTOR.extract(&create_deflate_section(data)), Err(FirmwareFileSystemError::DataCorrupt));
+        }
+
+        // Truncated trailer.
+        let data = gzip(CONTENT, 0, &[]);
+        assert_eq!(
+            DeflateSectionExtractor::decompress_to_slice(&data[..data.len() - 4], &mut [0u8; 128]),
+            Err(FirmwareFileSystemError::DataCorrupt)
+        );
+    }
+
+    #[test]
+    fn test_deflate_extractor_unsupported_guid() {
+        let section = crate::testing::create_lzma_section(&[0u8; 16]);
+        assert_eq!(EXTRACTOR.extract(&section), Err(FirmwareFileSystemError::Unsupported));
+        assert_eq!(EXTRACTOR.extract_to_slice(&section, &mut [0u8; 16]), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    fn test_deflate_extractor_custom_guid() {
+        let content = zlib(CONTENT);
+        let section = create_deflate_section(&content);
+        let other = DeflateSectionExtractor::new(efi::Guid::from_bytes(&[0x5A; 16]));
+        assert_eq!(other.guid(), efi::Guid::from_bytes(&[0x5A; 16]));
+        assert_eq!(other.extract(&section), Err(FirmwareFileSystemError::Unsupported));
+
+        let section = crate::testing::create_guid_defined_section(other.guid(), vec![], &content);
+        assert_eq!(other.extract(&section).unwrap(), CONTENT);
+    }
+}
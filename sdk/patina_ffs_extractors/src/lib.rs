@@ -8,10 +8,90 @@
 //! implementation of the `SectionExtractorLib` trait. The crate is configured in this manner to
 //! reduce compilation times, by only compiling the necessary implementations.
 //! - `brotli`: Enables the `SectionExtractorLibBrotli` implementation.
+//! - `brotli-encoder`: Enables Brotli sections in `SectionBuilder`. The encoder requires `std`, so this feature
+//!   also enables the `std` feature. This feature is not enabled by default.
 //! - `crc32`: Enables the `Crc32SectionExtractor` implementation to validate CRC32 GUID-defined
 //!   sections and return the verified payload.
 //! - `lzma`: Enables the `LzmaSectionExtractor` implementation for GUID-defined LZMA compressed
 //!   sections.
+//! - `lz4`: Enables the `Lz4SectionExtractor` implementation for GUID-defined LZ4 compressed
+//!   sections. LZ4 has no standard section definition GUID, so the platform passes the GUID its tooling uses to
+//!   `Lz4SectionExtractor::new` and registers the extractor with `CompositeSectionExtractor::builder()`. This
+//!   feature is not enabled by default.
+//! - `deflate`: Enables the `DeflateSectionExtractor` implementation for GUID-defined gzip and zlib
+//!   compressed sections. As with `lz4`, the platform passes its section definition GUID to
+//!   `DeflateSectionExtractor::new`. This feature is not enabled by default.
+//! - `test-util`: Enables the `testing` module, which constructs well-formed GUID-defined sections
+//!   for tests and fuzz targets. This feature is not enabled by default.
+//!
+//! ## Dispatch
+//!
+//! The `CompositeSectionExtractor` dispatches GUID-defined sections to the extractor registered for the section
+//! definition GUID. `CompositeSectionExtractor::builder()` can register additional extractors, including several for
+//! the same GUID with different priorities.
+//!
+//! ## Building Sections
+//!
+//! `SectionBuilder` is the inverse of the extractors: it builds CRC32, LZMA, and Brotli GUID-defined sections from
+//! raw payloads, and implements `SectionComposer` so modified sections can be re-encoded before serialization. This
+//! keeps tooling that generates firmware volumes in step with the formats the extractors accept.
+//!
+//! ## Compressing Diagnostic Data
+//!
+//! `SectionCompressor` implements `patina::compression::Compressor` by building a GUID-defined section, so the memory
+//! log or a crash dump can be LZMA or Brotli compressed before it is stored or sent to the host. `SectionDecompressor`
+//! restores such data with the extractors in this crate, for use in host tooling.
+//!
+//! ## Brotli Dictionaries
+//!
+//! Payloads that share content, such as OS loaders, compress better against a shared dictionary. A Brotli section
+//! compressed with a custom dictionary carries the GUID of the dictionary as its GUID-specific header data.
+//! `BrotliDictionarySectionExtractor` decompresses such sections with the platform's dictionary, as well as plain
+//! Brotli sections, and `SectionBuilder::with_brotli_dictionary` builds them. `BrotliSectionExtractor` only decompresses
+//! plain Brotli sections.
+//!
+//! ## Error Context
+//!
+//! Every extractor in this crate implements `SectionExtractor::extract_with_context`, which reports failures as an
+//! `ExtractionError` carrying the section definition GUID, the offset into the section content where the failure was
+//! detected, and a reason such as a CRC or size mismatch, where known.
+//!
+//! ## Observing Extractions
+//!
+//! The `CompositeSectionExtractor` accepts an optional `ExtractorObserver` that is notified before and after each
+//! extraction with the section GUID, compressed and decompressed sizes, and timestamps from a pluggable
+//! `TimestampSource`. This can be used to produce boot performance records for slow decompression.
+//!
+//! ## Time Limits
+//!
+//! `TimeLimitedLzmaSectionExtractor` and `TimeLimitedBrotliSectionExtractor` bound the time spent decompressing a
+//! single section with an `ExtractionTimeLimit`. The decoders check the limit periodically and fail with
+//! `FirmwareFileSystemError::Timeout` once it has elapsed, so that a pathological section cannot stall boot.
+//! `decompress_to_slice_with_abort` accepts an arbitrary abort callback instead.
+//!
+//! ## Caching
+//!
+//! `CachingSectionExtractor` wraps any extractor and memoizes successful extractions keyed by a hash of the section,
+//! within a configurable byte budget and eviction policy.
+//!
+//! ## Parallel Extraction
+//!
+//! `extract_all` extracts a set of independent sections through a platform-provided `ExtractionExecutor`, so platforms
+//! that start application processors early can decompress sections in parallel. It falls back to sequential
+//! extraction when only one CPU is available.
+//!
+//! ## Caller-Provided Output Buffers
+//!
+//! `LzmaSectionExtractor`, `BrotliSectionExtractor`, `Lz4SectionExtractor`, and `DeflateSectionExtractor` provide
+//! `extract_to_slice` and `decompress_to_slice`, which write the decompressed data into a caller-provided slice instead
+//! of allocating the output. Only the output is caller-provided. This is not an allocation-free extraction mode, and
+//! the LZMA and Brotli extractors cannot be used in environments without a global allocator:
+//!
+//! - The LZMA decoder allocates its dictionary, which grows up to the dictionary size recorded in the stream header
+//!   (commonly several MiB).
+//! - The Brotli decoder allocates its state and a ring buffer of up to the stream's window size (up to 16 MiB).
+//! - The DEFLATE decoder allocates its fixed-size decompressor state.
+//! - The LZ4 decoder does not allocate.
 //!
 //! ## License
 //!
@@ -26,77 +106,54 @@ extern crate alloc;
 #[cfg(feature = "brotli")]
 mod brotli;
 #[cfg(feature = "brotli")]
-pub use brotli::BrotliSectionExtractor;
+pub use brotli::{
+    BROTLI_DICTIONARY_ID_SIZE, BrotliDictionarySectionExtractor, BrotliSectionExtractor,
+    TimeLimitedBrotliSectionExtractor,
+};
 
 #[cfg(feature = "crc32")]
 mod crc32;
 #[cfg(feature = "crc32")]
 pub use crc32::Crc32SectionExtractor;
 
+#[cfg(feature = "deflate")]
+mod deflate;
+#[cfg(feature = "deflate")]
+pub use deflate::DeflateSectionExtractor;
+
 #[cfg(feature = "lzma")]
 mod lzma;
 #[cfg(feature = "lzma")]
-pub use lzma::LzmaSectionExtractor;
+pub use lzma::{LzmaSectionExtractor, TimeLimitedLzmaSectionExtractor};
 
-mod composite;
-pub use composite::CompositeSectionExtractor;
-
-mod null;
-pub use null::NullSectionExtractor;
+#[cfg(feature = "lz4")]
+mod lz4;
+#[cfg(feature = "lz4")]
+pub use lz4::Lz4SectionExtractor;
 
-#[cfg(test)]
-#[coverage(off)]
-mod tests {
-    use alloc::{vec, vec::Vec};
-    use patina::pi::fw_fs::{
-        ffs::section::header::GuidDefined,
-        guid::{BROTLI_SECTION, CRC32_SECTION, LZMA_SECTION},
-    };
-    use patina_ffs::section::{Section, SectionHeader};
+mod builder;
+pub use builder::{GuidedSectionFormat, SectionBuilder};
 
-    /// Constructs a section with the specified GUID and payload, prepending
-    /// the required 16-byte header (out_size + scratch_size) for Brotli sections.
-    pub(crate) fn create_brotli_section(payload: &[u8], out_size: u64) -> Section {
-        // Brotli section payload format: [out_size: u64, scratch_size: u64, compressed_data...]
-        let scratch_size = 0u64;
+mod compression;
+pub use compression::{SectionCompressor, SectionDecompressor};
 
-        let mut content = Vec::new();
-        content.extend_from_slice(&out_size.to_le_bytes());
-        content.extend_from_slice(&scratch_size.to_le_bytes());
-        content.extend_from_slice(payload);
+mod cache;
+pub use cache::{CacheStats, CachingSectionExtractor, EvictionPolicy};
 
-        let guid_header = GuidDefined {
-            section_definition_guid: BROTLI_SECTION,
-            data_offset: (core::mem::size_of::<GuidDefined>() + 4) as u16, // common header + guid header
-            attributes: 0x01,                                              // EFI_GUIDED_SECTION_PROCESSING_REQUIRED
-        };
+mod composite;
+pub use composite::{CompositeSectionExtractor, CompositeSectionExtractorBuilder, DEFAULT_EXTRACTOR_PRIORITY};
 
-        let header = SectionHeader::GuidDefined(guid_header, vec![], content.len() as u32);
-        Section::new_from_header_with_data(header, content).expect("Failed to create test section")
-    }
+mod null;
+pub use null::NullSectionExtractor;
 
-    /// Helper to create an LZMA GUID-defined section for testing.
-    /// Constructs a section with the LZMA GUID and the provided compressed payload.
-    pub(crate) fn create_lzma_section(compressed_data: &[u8]) -> Section {
-        let guid_header = GuidDefined {
-            section_definition_guid: LZMA_SECTION,
-            data_offset: (core::mem::size_of::<GuidDefined>() + 4) as u16, // common header + guid header
-            attributes: 0x01,                                              // EFI_GUIDED_SECTION_PROCESSING_REQUIRED
-        };
+mod parallel;
+pub use parallel::{ExtractionExecutor, extract_all};
 
-        let header = SectionHeader::GuidDefined(guid_header, vec![], compressed_data.len() as u32);
-        Section::new_from_header_with_data(header, compressed_data.to_vec()).expect("Failed to create test section")
-    }
+mod observer;
+pub use observer::{ExtractionRecord, ExtractionStart, ExtractorObserver, TimestampSource};
 
-    /// Helper to create a GUID-defined section for testing.
-    pub(crate) fn create_crc32_section(content: &[u8], guid_data: Vec<u8>) -> Section {
-        let guid_header = GuidDefined {
-            section_definition_guid: CRC32_SECTION,
-            data_offset: (core::mem::size_of::<GuidDefined>() + 4 + guid_data.len()) as u16,
-            attributes: 0x01,
-        };
+mod time_limit;
+pub use time_limit::ExtractionTimeLimit;
 
-        let header = SectionHeader::GuidDefined(guid_header, guid_data, content.len() as u32);
-        Section::new_from_header_with_data(header, content.to_vec()).expect("Failed to create test section")
-    }
-}
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
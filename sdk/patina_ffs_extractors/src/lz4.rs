@@ -0,0 +1,191 @@
+//! Module for LZ4 decompression.
+//!
+//! The content of an LZ4 GUIDed section is the 32-bit little-endian decompressed size followed by a single raw LZ4
+//! block, matching the output of `LZ4_compress_default` prefixed with the input size.
+//!
+//! The PI specification does not assign a section definition GUID to LZ4, so the platform provides the GUID its
+//! build tooling uses when constructing the extractor.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+use core::result::Result;
+use patina_ffs::{
+    ErrorReason, ExtractionError, FirmwareFileSystemError,
+    section::{Section, SectionExtractor, SectionHeader},
+};
+use r_efi::efi;
+
+/// Size of the decompressed size field that precedes the LZ4 block.
+const LZ4_HEADER_SIZE: usize = 4;
+
+/// Provides decompression for LZ4 GUIDed sections.
+#[derive(Clone, Copy)]
+pub struct Lz4SectionExtractor {
+    guid: efi::Guid,
+}
+
+impl Lz4SectionExtractor {
+    /// Creates a new `Lz4SectionExtractor` for sections with the section definition GUID `guid`.
+    pub const fn new(guid: efi::Guid) -> Self {
+        Self { guid }
+    }
+
+    /// Returns the section definition GUID this extractor handles.
+    pub const fn guid(&self) -> efi::Guid {
+        self.guid
+    }
+
+    /// Extracts an LZ4 section into `out`, returning the number of bytes written.
+    ///
+    /// ## Errors
+    ///
+    /// - [`FirmwareFileSystemError::Unsupported`] if `section` is not an LZ4 section.
+    /// - [`FirmwareFileSystemError::BufferTooSmall`] if `out` cannot hold the decompressed data.
+    /// - [`FirmwareFileSystemError::DataCorrupt`] if the section content is not valid LZ4 data.
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == self.guid
+        {
+            return Self::decompress_to_slice(section.try_content_as_slice()?, out);
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    /// Decompresses raw LZ4 section content into `out`, returning the number of bytes written.
+    ///
+    /// This is the slice-based form of [`Self::extract_to_slice`] for callers that have the section content but not a
+    /// parsed [`Section`]. No allocations are made.
+    pub fn decompress_to_slice(data: &[u8], out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        Self::decompress(data, out).map_err(Into::into)
+    }
+
+    /// Decompresses raw LZ4 section content into `out`, reporting failures with diagnostic context.
+    fn decompress(data: &[u8], out: &mut [u8]) -> Result<usize, ExtractionError> {
+        let out_size = Self::out_size(data)?;
+        let out = out.get_mut(..out_size).ok_or(FirmwareFileSystemError::BufferTooSmall)?;
+
+        // A block that decompresses to more or fewer bytes than the recorded size is corrupt.
+        let reason = match lz4_flex::block::decompress_into(&data[LZ4_HEADER_SIZE..], out) {
+            Ok(size) if size == out_size => return Ok(size),
+            Ok(size) => ErrorReason::SizeMismatch { expected_size: out_size, actual_size: size },
+            Err(_) => ErrorReason::MalformedStream,
+        };
+        Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_reason(reason))
+    }
+
+    /// Decompresses raw LZ4 section content into a new buffer.
+    fn decompress_to_vec(data: &[u8]) -> Result<Vec<u8>, ExtractionError> {
+        let mut decompressed = vec![0u8; Self::out_size(data)?];
+        Self::decompress(data, &mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    /// Returns the decompressed size from the section content header.
+    fn out_size(data: &[u8]) -> Result<usize, ExtractionError> {
+        let header = data.get(..LZ4_HEADER_SIZE).ok_or_else(|| {
+            ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_reason(ErrorReason::Truncated { required_size: LZ4_HEADER_SIZE, available_size: data.len() })
+        })?;
+        Ok(u32::from_le_bytes(header.try_into().unwrap()) as usize)
+    }
+}
+
+impl SectionExtractor for Lz4SectionExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == self.guid
+        {
+            let data = section.try_content_as_slice().map_err(|err| section.error_context(err))?;
+            return Self::decompress_to_vec(data).map_err(|err| err.with_section_guid(self.guid));
+        }
+        Err(section.error_context(FirmwareFileSystemError::Unsupported))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use crate::testing::{LZ4_TEST_SECTION_GUID, create_lz4_section};
+
+    use super::*;
+
+    const EXTRACTOR: Lz4SectionExtractor = Lz4SectionExtractor::new(LZ4_TEST_SECTION_GUID);
+
+    const CONTENT: &[u8] = b"Hello, LZ4! Hello, LZ4! Hello, LZ4! Hello, LZ4!";
+
+    #[test]
+    fn test_lz4_extractor_valid() {
+        let section = create_lz4_section(&lz4_flex::block::compress_prepend_size(CONTENT));
+        let result = EXTRACTOR.extract(&section).expect("LZ4 extraction should succeed");
+        assert_eq!(result, CONTENT);
+    }
+
+    #[test]
+    fn test_lz4_extract_to_slice() {
+        let section = create_lz4_section(&lz4_flex::block::compress_prepend_size(CONTENT));
+
+        let mut out = [0u8; 64];
+        let size = EXTRACTOR.extract_to_slice(&section, &mut out).expect("LZ4 extraction should succeed");
+        assert_eq!(&out[..size], CONTENT);
+
+        let mut small = [0u8; 8];
+        assert_eq!(EXTRACTOR.extract_to_slice(&section, &mut small), Err(FirmwareFileSystemError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_lz4_extractor_size_mismatch() {
+        let mut data = lz4_flex::block::compress_prepend_size(CONTENT);
+        // Record a larger size than the block decompresses to.
+        data[..LZ4_HEADER_SIZE].copy_from_slice(&(CONTENT.len() as u32 + 1).to_le_bytes());
+        assert_eq!(EXTRACTOR.extract(&create_lz4_section(&data)), Err(FirmwareFileSystemError::DataCorrupt));
+        assert_eq!(
+            EXTRACTOR.extract_with_context(&create_lz4_section(&data)),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_section_guid(LZ4_TEST_SECTION_GUID)
+                .with_reason(ErrorReason::SizeMismatch {
+                    expected_size: CONTENT.len() + 1,
+                    actual_size: CONTENT.len()
+                }))
+        );
+
+        // Record a smaller size than the block decompresses to.
+        data[..LZ4_HEADER_SIZE].copy_from_slice(&(CONTENT.len() as u32 - 1).to_le_bytes());
+        assert_eq!(EXTRACTOR.extract(&create_lz4_section(&data)), Err(FirmwareFileSystemError::DataCorrupt));
+    }
+
+    #[test]
+    fn test_lz4_extractor_invalid_data() {
+        assert_eq!(EXTRACTOR.extract(&create_lz4_section(&[0x01])), Err(FirmwareFileSystemError::DataCorrupt));
+
+        let invalid_block = [0x10, 0x00, 0x00, 0x00, 0xF0, 0xFF];
+        assert_eq!(EXTRACTOR.extract(&create_lz4_section(&invalid_block)), Err(FirmwareFileSystemError::DataCorrupt));
+    }
+
+    #[test]
+    fn test_lz4_extractor_unsupported_guid() {
+        let section = crate::testing::create_lzma_section(&[0u8; 16]);
+        assert_eq!(EXTRACTOR.extract(&section), Err(FirmwareFileSystemError::Unsupported));
+        assert_eq!(EXTRACTOR.extract_to_slice(&section, &mut [0u8; 16]), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    fn test_lz4_extractor_custom_guid() {
+        let content = lz4_flex::block::compress_prepend_size(CONTENT);
+        let section = crate::testing::create_guid_defined_section(LZ4_TEST_SECTION_GUID, alloc::vec![], &content);
+        let other = Lz4SectionExtractor::new(efi::Guid::from_bytes(&[0xA5; 16]));
+        assert_eq!(other.guid(), efi::Guid::from_bytes(&[0xA5; 16]));
+        assert_eq!(other.extract(&section), Err(FirmwareFileSystemError::Unsupported));
+
+        let section = crate::testing::create_guid_defined_section(other.guid(), alloc::vec![], &content);
+        assert_eq!(other.extract(&section).expect("LZ4 extraction should succeed"), CONTENT);
+    }
+}
@@ -1,5 +1,11 @@
 //! Module for LZMA decompression.
 //!
+//! The content of an LZMA section is usually a single LZMA stream: the 13-byte header carrying the properties and the
+//! unpacked size, followed by the compressed data. Some toolchains split large payloads into several such frames and
+//! concatenate them within one section. The frames are decoded in turn and their output concatenated; trailing zero
+//! bytes after the last frame are treated as padding. A frame with an unknown unpacked size ends with an end-of-stream
+//! marker, which the decoder only accepts at the end of the content, so such a frame must be the last one.
+//!
 //! ## License
 //!
 //! Copyright (c) Microsoft Corporation.
@@ -7,14 +13,16 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 use alloc::vec::Vec;
-use core::result::Result;
+use core::{cell::RefCell, result::Result};
 use patina_ffs::{
-    FirmwareFileSystemError,
+    ErrorReason, ExtractionError, FirmwareFileSystemError,
     section::{Section, SectionExtractor, SectionHeader},
 };
 use r_efi::efi;
 
-use patina_lzma_rs::io::Cursor;
+use patina_lzma_rs::io::{self, Cursor};
+
+use crate::{ExtractionTimeLimit, time_limit::POLL_INTERVAL};
 
 pub const LZMA_SECTION_GUID: efi::Guid =
     efi::Guid::from_fields(0xEE4E5898, 0x3914, 0x4259, 0x9D, 0x6E, &[0xDC, 0x7B, 0xD7, 0x94, 0x03, 0xCF]);
@@ -22,6 +30,8 @@ pub const LZMA_SECTION_GUID: efi::Guid =
 pub const LZMA_UNKNOWN_UNPACKED_SIZE_MAGIC_VALUE: u64 = 0xFFFF_FFFF_FFFF_FFFF;
 
 /// Provides decompression for LZMA GUIDed sections.
+///
+/// Use [`TimeLimitedLzmaSectionExtractor`] to bound the time spent decompressing a single section.
 #[derive(Default, Clone, Copy)]
 pub struct LzmaSectionExtractor;
 
@@ -33,36 +43,281 @@ impl LzmaSectionExtractor {
     }
 }
 
-impl SectionExtractor for LzmaSectionExtractor {
+/// Provides decompression for LZMA GUIDed sections, giving up on a section once a time limit has elapsed.
+///
+/// Sections that take longer than the limit to decompress fail with [`FirmwareFileSystemError::Timeout`].
+#[derive(Clone, Copy, Debug)]
+pub struct TimeLimitedLzmaSectionExtractor {
+    time_limit: ExtractionTimeLimit,
+}
+
+impl TimeLimitedLzmaSectionExtractor {
+    /// Creates an extractor that spends at most `time_limit` decompressing a single section.
+    pub const fn new(time_limit: ExtractionTimeLimit) -> Self {
+        Self { time_limit }
+    }
+
+    /// Extracts an LZMA section into `out`, returning the number of bytes written.
+    ///
+    /// See [`LzmaSectionExtractor::extract_to_slice`]. Fails with [`FirmwareFileSystemError::Timeout`] if the time
+    /// limit elapsed.
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        LzmaSectionExtractor::extract_to_slice_with_abort(section, out, &mut self.time_limit.start())
+    }
+}
+
+impl SectionExtractor for TimeLimitedLzmaSectionExtractor {
     fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        LzmaSectionExtractor::extract_with_abort(section, &mut self.time_limit.start())
+    }
+}
+
+impl LzmaSectionExtractor {
+    /// Extracts an LZMA section into `out`, returning the number of bytes written.
+    ///
+    /// The decompressed data is written directly to `out` instead of an allocated buffer. This is not an
+    /// allocation-free mode: the decoder still allocates its dictionary from the global allocator, and the dictionary
+    /// grows up to the size recorded in the stream header, commonly several MiB. It cannot be used before an allocator
+    /// is available.
+    ///
+    /// ## Errors
+    ///
+    /// - [`FirmwareFileSystemError::Unsupported`] if `section` is not an LZMA section.
+    /// - [`FirmwareFileSystemError::BufferTooSmall`] if `out` cannot hold the decompressed data.
+    /// - [`FirmwareFileSystemError::DataCorrupt`] if the section content is not valid LZMA data.
+    pub fn extract_to_slice(&self, section: &Section, out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        Self::extract_to_slice_with_abort(section, out, &mut || false)
+    }
+
+    /// Extracts an LZMA section into `out`, giving up once `abort` returns `true`.
+    fn extract_to_slice_with_abort(
+        section: &Section,
+        out: &mut [u8],
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == LZMA_SECTION_GUID
+        {
+            return Self::decompress_to_slice_with_abort(section.try_content_as_slice()?, out, abort);
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    /// Extracts an LZMA section into a new buffer, giving up once `abort` returns `true`.
+    fn extract_with_abort(section: &Section, abort: &mut dyn FnMut() -> bool) -> Result<Vec<u8>, ExtractionError> {
         if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
             && guid_header.section_definition_guid == LZMA_SECTION_GUID
         {
-            let data = section.try_content_as_slice()?;
+            let data = section.try_content_as_slice().map_err(|err| section.error_context(err))?;
+            return Self::decompress_to_vec(data, abort).map_err(|err| err.with_section_guid(LZMA_SECTION_GUID));
+        }
+        Err(section.error_context(FirmwareFileSystemError::Unsupported))
+    }
+
+    /// Decompresses raw LZMA section content into `out`, returning the number of bytes written.
+    ///
+    /// This is the slice-based form of [`Self::extract_to_slice`] for callers that have the section content but not a
+    /// parsed [`Section`].
+    pub fn decompress_to_slice(data: &[u8], out: &mut [u8]) -> Result<usize, FirmwareFileSystemError> {
+        Self::decompress_to_slice_with_abort(data, out, &mut || false)
+    }
 
+    /// Decompresses raw LZMA section content into `out`, giving up once `abort` returns `true`.
+    ///
+    /// `abort` is called periodically while decoding. Once it returns `true`, decompression stops with
+    /// [`FirmwareFileSystemError::Timeout`].
+    pub fn decompress_to_slice_with_abort(
+        data: &[u8],
+        out: &mut [u8],
+        abort: &mut dyn FnMut() -> bool,
+    ) -> Result<usize, FirmwareFileSystemError> {
+        Self::decompress(data, out, abort).map_err(Into::into)
+    }
+
+    /// Decompresses raw LZMA section content into `out`, reporting failures with diagnostic context.
+    fn decompress(data: &[u8], out: &mut [u8], abort: &mut dyn FnMut() -> bool) -> Result<usize, ExtractionError> {
+        let budget = RefCell::new(Budget::new(abort));
+        let mut input = data;
+        let mut written = 0;
+        loop {
+            let out = &mut out[written..];
+            let unpacked_size = Self::unpacked_size(input)?;
+            if unpacked_size != LZMA_UNKNOWN_UNPACKED_SIZE_MAGIC_VALUE && unpacked_size > out.len() as u64 {
+                return Err(FirmwareFileSystemError::BufferTooSmall.into());
+            }
+
+            let mut reader = Cursor::new(input);
+            let mut writer = Cursor::new(out);
+            let result = patina_lzma_rs::lzma_decompress(
+                &mut Abortable::new(&mut reader, &budget),
+                &mut Abortable::new(&mut writer, &budget),
+            );
+            match result {
+                Ok(()) => written += writer.position() as usize,
+                Err(_) if budget.borrow().expired => return Err(FirmwareFileSystemError::Timeout.into()),
+                // With an unknown unpacked size, running out of output space is the only indication the buffer is
+                // small.
+                Err(patina_lzma_rs::error::Error::IoError(io::Error::OutOfSpace)) => {
+                    return Err(FirmwareFileSystemError::BufferTooSmall.into());
+                }
+                Err(_) => return Err(malformed_stream()),
+            }
+
+            input = &input[reader.position() as usize..];
+            if is_padding(input) {
+                return Ok(written);
+            }
+        }
+    }
+
+    /// Decompresses raw LZMA section content into a new buffer.
+    fn decompress_to_vec(data: &[u8], abort: &mut dyn FnMut() -> bool) -> Result<Vec<u8>, ExtractionError> {
+        let budget = RefCell::new(Budget::new(abort));
+        let mut input = data;
+        let mut decompressed = Vec::<u8>::new();
+        loop {
             // Get unpacked size to pre-allocate vector, if available
-            // See https://github.com/tukaani-project/xz/blob/dd4a1b259936880e04669b43e778828b60619860/doc/lzma-file-format.txt#L131
-            let unpacked_size =
-                u64::from_le_bytes(data.get(5..13).ok_or(FirmwareFileSystemError::DataCorrupt)?.try_into().unwrap());
-            let mut decompressed = if unpacked_size == LZMA_UNKNOWN_UNPACKED_SIZE_MAGIC_VALUE {
-                Vec::<u8>::new()
-            } else {
-                Vec::<u8>::with_capacity(unpacked_size as usize)
-            };
-
-            patina_lzma_rs::lzma_decompress(&mut Cursor::new(data), &mut decompressed)
-                .map_err(|_| FirmwareFileSystemError::DataCorrupt)?;
-
-            return Ok(decompressed);
+            let unpacked_size = Self::unpacked_size(input)?;
+            // The vector grows as needed if the size is unknown or cannot be reserved up front.
+            if unpacked_size != LZMA_UNKNOWN_UNPACKED_SIZE_MAGIC_VALUE {
+                let _ = decompressed.try_reserve(usize::try_from(unpacked_size).unwrap_or(usize::MAX));
+            }
+
+            let mut reader = Cursor::new(input);
+            let result = patina_lzma_rs::lzma_decompress(
+                &mut Abortable::new(&mut reader, &budget),
+                &mut Abortable::new(&mut decompressed, &budget),
+            );
+            match result {
+                Ok(()) => (),
+                Err(_) if budget.borrow().expired => return Err(FirmwareFileSystemError::Timeout.into()),
+                Err(_) => return Err(malformed_stream()),
+            }
+
+            input = &input[reader.position() as usize..];
+            if is_padding(input) {
+                return Ok(decompressed);
+            }
         }
-        Err(FirmwareFileSystemError::Unsupported)
+    }
+
+    /// Returns the unpacked size from the LZMA header, which may be [`LZMA_UNKNOWN_UNPACKED_SIZE_MAGIC_VALUE`].
+    ///
+    /// See https://github.com/tukaani-project/xz/blob/dd4a1b259936880e04669b43e778828b60619860/doc/lzma-file-format.txt#L131
+    fn unpacked_size(data: &[u8]) -> Result<u64, ExtractionError> {
+        let size = data.get(5..13).ok_or_else(|| {
+            ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_reason(ErrorReason::Truncated { required_size: 13, available_size: data.len() })
+        })?;
+        Ok(u64::from_le_bytes(size.try_into().unwrap()))
+    }
+}
+
+impl SectionExtractor for LzmaSectionExtractor {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_context(section).map_err(Into::into)
+    }
+
+    fn extract_with_context(&self, section: &Section) -> Result<Vec<u8>, ExtractionError> {
+        Self::extract_with_abort(section, &mut || false)
+    }
+}
+
+/// Tracks the input and output processed since the abort callback was last called.
+struct Budget<'a> {
+    abort: &'a mut dyn FnMut() -> bool,
+    processed: usize,
+    expired: bool,
+}
+
+impl<'a> Budget<'a> {
+    fn new(abort: &'a mut dyn FnMut() -> bool) -> Self {
+        Self { abort, processed: 0, expired: false }
+    }
+
+    /// Accounts for `amount` bytes of progress, returning an error once the abort callback has fired.
+    fn consume(&mut self, amount: usize) -> io::Result<()> {
+        self.processed += amount;
+        if !self.expired && self.processed >= POLL_INTERVAL {
+            self.processed = 0;
+            self.expired = (self.abort)();
+        }
+        // The decoder only propagates the error; `decompress` reports it as a timeout.
+        if self.expired { Err(io::Error::OutOfSpace) } else { Ok(()) }
+    }
+}
+
+/// Wraps the decoder's input or output, calling the abort callback as data passes through.
+///
+/// Highly compressible data is read slowly, and the decoder writes its output only as its dictionary fills, so both
+/// directions are accounted against the same [`Budget`].
+struct Abortable<'a, 'b, T> {
+    inner: T,
+    budget: &'a RefCell<Budget<'b>>,
+}
+
+impl<'a, 'b, T> Abortable<'a, 'b, T> {
+    fn new(inner: T, budget: &'a RefCell<Budget<'b>>) -> Self {
+        Self { inner, budget }
+    }
+}
+
+impl<T: io::Read> io::Read for Abortable<'_, '_, T> {
+    fn reader_position(&self) -> io::Result<usize> {
+        self.inner.reader_position()
+    }
+
+    fn advance_reader_position(&mut self, amt: usize) -> io::Result<()> {
+        self.budget.borrow_mut().consume(amt)?;
+        self.inner.advance_reader_position(amt)
+    }
+
+    fn get_remaining(&self) -> io::Result<&[u8]> {
+        self.inner.get_remaining()
+    }
+
+    fn read(&mut self, dest: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(dest)?;
+        self.budget.borrow_mut().consume(read)?;
+        Ok(read)
+    }
+
+    fn read_exact(&mut self, dest: &mut [u8]) -> io::Result<()> {
+        self.budget.borrow_mut().consume(dest.len())?;
+        self.inner.read_exact(dest)
     }
 }
 
+impl<T: io::Write> io::Write for Abortable<'_, '_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.budget.borrow_mut().consume(buf.len())?;
+        self.inner.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.budget.borrow_mut().consume(buf.len())?;
+        self.inner.write_all(buf)
+    }
+}
+
+/// Returns `true` if no frame follows the input consumed so far.
+fn is_padding(remaining: &[u8]) -> bool {
+    remaining.iter().all(|&byte| byte == 0)
+}
+
+/// Returns the error reported when the LZMA decoder rejects the stream.
+fn malformed_stream() -> ExtractionError {
+    ExtractionError::new(FirmwareFileSystemError::DataCorrupt).with_reason(ErrorReason::MalformedStream)
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
-    use crate::tests::create_lzma_section;
+    use crate::testing::create_lzma_section;
 
     use super::*;
     use alloc::vec;
@@ -78,7 +333,7 @@ mod tests {
             0x00,
         ];
         let section = create_lzma_section(lzma_compressed_data);
-        let extractor = LzmaSectionExtractor;
+        let extractor = LzmaSectionExtractor::new();
         let result = extractor.extract(&section).expect("LZMA extraction should succeed");
 
         assert_eq!(result, b"Hello, World!");
@@ -94,7 +349,7 @@ mod tests {
         ];
 
         let section = create_lzma_section(lzma_compressed_data);
-        let extractor = LzmaSectionExtractor;
+        let extractor = LzmaSectionExtractor::new();
         // Should succeed even with unknown size (vector grows dynamically)
         let result = extractor.extract(&section);
 
@@ -108,10 +363,104 @@ mod tests {
         let invalid_data: &[u8] = &[0x00, 0x01, 0x02, 0x03];
 
         let section = create_lzma_section(invalid_data);
-        let extractor = LzmaSectionExtractor;
+        let extractor = LzmaSectionExtractor::new();
         let result = extractor.extract(&section);
 
         assert!(matches!(result, Err(FirmwareFileSystemError::DataCorrupt)));
+        assert_eq!(
+            extractor.extract_with_context(&section),
+            Err(ExtractionError::new(FirmwareFileSystemError::DataCorrupt)
+                .with_section_guid(LZMA_SECTION_GUID)
+                .with_reason(ErrorReason::Truncated { required_size: 13, available_size: 4 }))
+        );
+
+        let mut corrupt = vec![0x5d, 0x00, 0x00, 0x80, 0x00, 0x0d, 0, 0, 0, 0, 0, 0, 0];
+        corrupt.extend_from_slice(&[0xff; 16]);
+        assert_eq!(
+            extractor.extract_with_context(&create_lzma_section(&corrupt)).unwrap_err().reason,
+            Some(ErrorReason::MalformedStream)
+        );
+    }
+
+    #[test]
+    fn test_lzma_extract_to_slice() {
+        let lzma_compressed_data: &[u8] = &[
+            0x5D, 0x00, 0x00, 0x80, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x24, 0x19, 0x49, 0x98,
+            0x6F, 0x16, 0x02, 0x89, 0x0A, 0x98, 0xE7, 0x3F, 0xA8, 0xC3, 0x95, 0x48, 0x4D, 0xFF, 0xFF, 0x75, 0xF0, 0x00,
+            0x00,
+        ];
+        let section = create_lzma_section(lzma_compressed_data);
+        let extractor = LzmaSectionExtractor::new();
+
+        let mut out = [0u8; 32];
+        let size = extractor.extract_to_slice(&section, &mut out).expect("LZMA extraction should succeed");
+        assert_eq!(&out[..size], b"Hello, World!");
+
+        let mut small = [0u8; 4];
+        assert_eq!(extractor.extract_to_slice(&section, &mut small), Err(FirmwareFileSystemError::BufferTooSmall));
+    }
+
+    #[test]
+    fn test_lzma_extract_to_slice_known_size_too_small() {
+        let mut data = vec![0x5D, 0x00, 0x00, 0x80, 0x00];
+        data.extend_from_slice(&64u64.to_le_bytes());
+        let mut out = [0u8; 16];
+        assert_eq!(
+            LzmaSectionExtractor::decompress_to_slice(&data, &mut out),
+            Err(FirmwareFileSystemError::BufferTooSmall)
+        );
+        assert_eq!(
+            LzmaSectionExtractor::decompress_to_slice(&data[..4], &mut out),
+            Err(FirmwareFileSystemError::DataCorrupt)
+        );
+    }
+
+    #[test]
+    fn test_lzma_extractor_multiple_frames() {
+        let first = (0..0x3000).map(|i| (i % 97) as u8).collect::<Vec<_>>();
+        let second = b"Hello, World!";
+        let frame = |payload: &[u8]| {
+            let section = crate::SectionBuilder::new().build(crate::GuidedSectionFormat::Lzma, payload).unwrap();
+            section.try_content_as_slice().unwrap().to_vec()
+        };
+        let mut content = frame(&first);
+        content.extend_from_slice(&frame(second));
+        let mut expected = first.clone();
+        expected.extend_from_slice(second);
+
+        let extractor = LzmaSectionExtractor::new();
+        let mut out = vec![0u8; expected.len() + second.len()];
+        for padding in [0, 3] {
+            let mut padded = content.clone();
+            padded.resize(content.len() + padding, 0);
+            let section = create_lzma_section(&padded);
+            assert_eq!(extractor.extract(&section).unwrap(), expected);
+            assert_eq!(extractor.extract_to_slice(&section, &mut out), Ok(expected.len()));
+            assert_eq!(out[..expected.len()], expected);
+        }
+        assert_eq!(
+            extractor.extract_to_slice(&create_lzma_section(&content), &mut out[..first.len() + 4]),
+            Err(FirmwareFileSystemError::BufferTooSmall)
+        );
+
+        // Anything other than padding after a frame must be another frame.
+        let mut trailing = content.clone();
+        trailing.push(0x5D);
+        assert_eq!(
+            extractor.extract_with_context(&create_lzma_section(&trailing)).unwrap_err().reason,
+            Some(ErrorReason::Truncated { required_size: 13, available_size: 1 })
+        );
+
+        // A frame with an unknown unpacked size ends with an end-of-stream marker and must be the last.
+        let options = patina_lzma_rs::compress::Options {
+            unpacked_size: patina_lzma_rs::compress::UnpackedSize::WriteToHeader(None),
+        };
+        patina_lzma_rs::lzma_compress_with_options(&mut Cursor::new(second), &mut content, &options).unwrap();
+        expected.extend_from_slice(second);
+        let section = create_lzma_section(&content);
+        assert_eq!(extractor.extract(&section).unwrap(), expected);
+        assert_eq!(extractor.extract_to_slice(&section, &mut out), Ok(expected.len()));
+        assert_eq!(out, expected);
     }
 
     #[test]
@@ -130,9 +479,58 @@ mod tests {
         let section =
             Section::new_from_header_with_data(header, dummy_data.to_vec()).expect("Failed to create test section");
 
-        let extractor = LzmaSectionExtractor;
+        let extractor = LzmaSectionExtractor::new();
         let result = extractor.extract(&section);
 
         assert!(matches!(result, Err(FirmwareFileSystemError::Unsupported)));
+        assert_eq!(extractor.extract_to_slice(&section, &mut [0u8; 16]), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    fn test_lzma_time_limit() {
+        struct FrozenClock;
+        impl crate::TimestampSource for FrozenClock {
+            fn timestamp(&self) -> u64 {
+                0
+            }
+        }
+        static CLOCK: FrozenClock = FrozenClock;
+
+        let payload = (0..4 * POLL_INTERVAL).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+        let section = crate::SectionBuilder::new().build(crate::GuidedSectionFormat::Lzma, &payload).unwrap();
+        let data = section.try_content_as_slice().unwrap();
+        let mut out = vec![0u8; payload.len()];
+
+        let mut polls = 0;
+        assert_eq!(
+            LzmaSectionExtractor::decompress_to_slice_with_abort(data, &mut out, &mut || {
+                polls += 1;
+                false
+            }),
+            Ok(payload.len())
+        );
+        assert!(polls > 0);
+        assert_eq!(out, payload);
+
+        // The decoder stops at the first poll once the callback asks it to.
+        polls = 0;
+        assert_eq!(
+            LzmaSectionExtractor::decompress_to_slice_with_abort(data, &mut out, &mut || {
+                polls += 1;
+                true
+            }),
+            Err(FirmwareFileSystemError::Timeout)
+        );
+        assert_eq!(polls, 1);
+
+        let expired = TimeLimitedLzmaSectionExtractor::new(ExtractionTimeLimit::from_ticks(&CLOCK, 0));
+        assert_eq!(
+            expired.extract_with_context(&section),
+            Err(ExtractionError::new(FirmwareFileSystemError::Timeout).with_section_guid(LZMA_SECTION_GUID))
+        );
+        assert_eq!(expired.extract_to_slice(&section, &mut out), Err(FirmwareFileSystemError::Timeout));
+
+        let generous = TimeLimitedLzmaSectionExtractor::new(ExtractionTimeLimit::from_ticks(&CLOCK, 1));
+        assert_eq!(generous.extract(&section).unwrap(), payload);
     }
 }
@@ -0,0 +1,109 @@
+//! Module for observing section extractions performed by the composite extractor.
+//!
+//! An [`ExtractorObserver`] is notified before and after every extraction attempted by the
+//! [`CompositeSectionExtractor`](crate::CompositeSectionExtractor), and receives the section GUID, the compressed
+//! and decompressed sizes, and the timestamps bracketing the extraction. Timestamps come from a pluggable
+//! [`TimestampSource`], which allows the results to be fed into FPDT-style boot performance records.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::component::service::{Service, perf_timer::ArchTimerFunctionality};
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionHeader},
+};
+use r_efi::efi;
+
+/// A source of monotonic timestamps used to time section extractions.
+pub trait TimestampSource: Sync {
+    /// Returns the current value of the timestamp counter, in ticks.
+    fn timestamp(&self) -> u64;
+
+    /// Returns the frequency of the timestamp counter in Hz, or `0` if unknown.
+    fn frequency(&self) -> u64 {
+        0
+    }
+}
+
+impl TimestampSource for Service<dyn ArchTimerFunctionality> {
+    fn timestamp(&self) -> u64 {
+        self.cpu_count()
+    }
+
+    fn frequency(&self) -> u64 {
+        self.perf_frequency()
+    }
+}
+
+/// Describes a section that is about to be extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractionStart {
+    /// The section definition GUID, for GUID-defined sections.
+    pub section_guid: Option<efi::Guid>,
+    /// Size of the section content (the compressed payload) in bytes.
+    pub compressed_size: usize,
+    /// Timestamp taken immediately before the extraction began.
+    pub start_timestamp: u64,
+}
+
+/// Describes the outcome of a section extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractionRecord {
+    /// The section definition GUID, for GUID-defined sections.
+    pub section_guid: Option<efi::Guid>,
+    /// Size of the section content (the compressed payload) in bytes.
+    pub compressed_size: usize,
+    /// The decompressed size in bytes on success, or the error returned by the extractor.
+    pub result: Result<usize, FirmwareFileSystemError>,
+    /// Timestamp taken immediately before the extraction began.
+    pub start_timestamp: u64,
+    /// Timestamp taken immediately after the extraction completed.
+    pub end_timestamp: u64,
+    /// Frequency of the timestamp counter in Hz, or `0` if unknown.
+    pub frequency: u64,
+}
+
+impl ExtractionRecord {
+    /// Returns the decompressed size, if the extraction succeeded.
+    pub fn decompressed_size(&self) -> Option<usize> {
+        self.result.ok()
+    }
+
+    /// Returns the number of timestamp ticks elapsed during the extraction.
+    pub fn elapsed_ticks(&self) -> u64 {
+        self.end_timestamp.wrapping_sub(self.start_timestamp)
+    }
+
+    /// Returns the elapsed time in nanoseconds, or `None` if the timestamp frequency is unknown.
+    pub fn elapsed_ns(&self) -> Option<u64> {
+        if self.frequency == 0 {
+            return None;
+        }
+        Some(((self.elapsed_ticks() as u128 * 1_000_000_000) / self.frequency as u128) as u64)
+    }
+}
+
+/// Receives notifications for every extraction performed by the composite extractor.
+///
+/// Both methods have empty default implementations so observers only need to implement the events they are
+/// interested in. Observers are invoked for every section handed to the composite extractor, including sections
+/// that no extractor supports; such sections complete with `Err(FirmwareFileSystemError::Unsupported)`.
+pub trait ExtractorObserver: Sync {
+    /// Called immediately before an extraction is attempted.
+    fn extraction_started(&self, _start: &ExtractionStart) {}
+
+    /// Called immediately after an extraction completes, successfully or not.
+    fn extraction_finished(&self, _record: &ExtractionRecord) {}
+}
+
+/// Returns the section definition GUID of `section`, if it is a GUID-defined section.
+pub(crate) fn section_guid(section: &Section) -> Option<efi::Guid> {
+    match section.header() {
+        SectionHeader::GuidDefined(guid_header, _, _) => Some(guid_header.section_definition_guid),
+        _ => None,
+    }
+}
@@ -0,0 +1,180 @@
+//! Module for extracting several sections in parallel.
+//!
+//! [`extract_all`] extracts a set of independent sections through an [`ExtractionExecutor`], which a platform with
+//! application processors running early can implement to spread the work across CPUs. When the executor reports a
+//! single CPU, or no executor is provided, the sections are extracted one after another on the calling CPU.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionExtractor},
+};
+use spin::Once;
+
+/// Runs extraction jobs, possibly concurrently on several CPUs.
+pub trait ExtractionExecutor: Sync {
+    /// Returns the number of CPUs available to run jobs, including the calling CPU.
+    ///
+    /// [`extract_all`] does not call [`ExtractionExecutor::run`] when this returns `1` or less.
+    fn cpu_count(&self) -> usize;
+
+    /// Calls `job` once for each index in `0..job_count` and returns once every call has returned.
+    ///
+    /// Calls may run concurrently and in any order. The calling CPU may run jobs itself.
+    fn run(&self, job_count: usize, job: &(dyn Fn(usize) + Sync));
+}
+
+/// Extracts every section in `sections` with `extractor`, returning the results in the same order.
+///
+/// If `executor` is provided and reports more than one CPU, the sections are extracted through it. Otherwise, they
+/// are extracted sequentially on the calling CPU. A failed extraction does not stop the others.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// let results = extract_all(&CompositeSectionExtractor::new(), file.sections(), Some(&ap_executor));
+/// ```
+pub fn extract_all<'a, E>(
+    extractor: &E,
+    sections: impl IntoIterator<Item = &'a Section>,
+    executor: Option<&dyn ExtractionExecutor>,
+) -> Vec<Result<Vec<u8>, FirmwareFileSystemError>>
+where
+    E: SectionExtractor + Sync + ?Sized,
+{
+    let sections: Vec<&Section> = sections.into_iter().collect();
+
+    let Some(executor) = executor.filter(|executor| executor.cpu_count() > 1 && sections.len() > 1) else {
+        return sections.into_iter().map(|section| extractor.extract(section)).collect();
+    };
+
+    let results: Vec<Once<Result<Vec<u8>, FirmwareFileSystemError>>> = sections.iter().map(|_| Once::new()).collect();
+    executor.run(sections.len(), &|index| {
+        results[index].call_once(|| extractor.extract(sections[index]));
+    });
+
+    // An executor that skipped a job is treated as a failed extraction rather than a panic.
+    results
+        .into_iter()
+        .map(|result| result.try_into_inner().unwrap_or(Err(FirmwareFileSystemError::NotExtracted)))
+        .collect()
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::testing::create_guid_defined_section;
+    use alloc::vec;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use r_efi::efi;
+
+    const TEST_GUID: efi::Guid =
+        efi::Guid::from_fields(0x8f2d6b31, 0x4c1e, 0x47a9, 0xb3, 0x5e, &[0x0a, 0x7c, 0x92, 0xd1, 0x46, 0xe8]);
+
+    /// Returns the section content reversed, failing on empty content.
+    struct ReversingExtractor;
+
+    impl SectionExtractor for ReversingExtractor {
+        fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+            let content = section.try_content_as_slice()?;
+            if content.is_empty() {
+                return Err(FirmwareFileSystemError::DataCorrupt);
+            }
+            Ok(content.iter().rev().copied().collect())
+        }
+    }
+
+    /// Runs jobs on scoped threads, or in reverse order on the calling thread when `threads` is false.
+    struct TestExecutor {
+        cpus: usize,
+        threads: bool,
+        runs: AtomicUsize,
+        skip: Option<usize>,
+    }
+
+    impl TestExecutor {
+        fn new(cpus: usize) -> Self {
+            Self { cpus, threads: true, runs: AtomicUsize::new(0), skip: None }
+        }
+    }
+
+    impl ExtractionExecutor for TestExecutor {
+        fn cpu_count(&self) -> usize {
+            self.cpus
+        }
+
+        fn run(&self, job_count: usize, job: &(dyn Fn(usize) + Sync)) {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            let indices = (0..job_count).rev().filter(|&index| Some(index) != self.skip);
+            if self.threads {
+                std::thread::scope(|scope| {
+                    for index in indices {
+                        scope.spawn(move || job(index));
+                    }
+                });
+            } else {
+                indices.for_each(job);
+            }
+        }
+    }
+
+    fn sections() -> Vec<Section> {
+        [&b"abc"[..], b"", b"0123456789", b"x"]
+            .into_iter()
+            .map(|content| create_guid_defined_section(TEST_GUID, vec![], content))
+            .collect()
+    }
+
+    fn expected() -> Vec<Result<Vec<u8>, FirmwareFileSystemError>> {
+        vec![
+            Ok(b"cba".to_vec()),
+            Err(FirmwareFileSystemError::DataCorrupt),
+            Ok(b"9876543210".to_vec()),
+            Ok(b"x".to_vec()),
+        ]
+    }
+
+    #[test]
+    fn test_extract_all_sequential_without_executor() {
+        assert_eq!(extract_all(&ReversingExtractor, &sections(), None), expected());
+    }
+
+    #[test]
+    fn test_extract_all_parallel_preserves_order() {
+        let executor = TestExecutor::new(4);
+        assert_eq!(extract_all(&ReversingExtractor, &sections(), Some(&executor)), expected());
+        assert_eq!(executor.runs.load(Ordering::SeqCst), 1);
+
+        let executor = TestExecutor { threads: false, ..TestExecutor::new(4) };
+        assert_eq!(extract_all(&ReversingExtractor, &sections(), Some(&executor)), expected());
+    }
+
+    #[test]
+    fn test_extract_all_single_cpu_falls_back_to_sequential() {
+        let executor = TestExecutor::new(1);
+        assert_eq!(extract_all(&ReversingExtractor, &sections(), Some(&executor)), expected());
+        assert_eq!(executor.runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_extract_all_skipped_job_is_reported() {
+        let executor = TestExecutor { skip: Some(2), ..TestExecutor::new(2) };
+        let results = extract_all(&ReversingExtractor, &sections(), Some(&executor));
+        assert_eq!(results[2], Err(FirmwareFileSystemError::NotExtracted));
+        assert_eq!(results[3], Ok(b"x".to_vec()));
+    }
+
+    #[test]
+    fn test_extract_all_empty() {
+        assert!(extract_all(&ReversingExtractor, &[], Some(&TestExecutor::new(4))).is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! Helpers for constructing GUID-defined sections in tests and fuzz targets.
+//!
+//! This module is available with the `test-util` feature. It provides constructors for well-formed sections of each
+//! GUID-defined type supported by this crate, and [`arbitrary_section`], which turns arbitrary bytes (such as a fuzzer
+//! input) into a well-formed GUID-defined section so the extractors are exercised beyond header parsing.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+use patina::pi::fw_fs::{
+    ffs::section::header::GuidDefined,
+    guid::{BROTLI_SECTION, CRC32_SECTION, LZMA_SECTION},
+};
+use patina_ffs::section::{Section, SectionHeader};
+use r_efi::efi;
+
+/// `EFI_GUIDED_SECTION_PROCESSING_REQUIRED`
+const PROCESSING_REQUIRED: u16 = 0x01;
+
+/// Size of the common section header.
+const COMMON_HEADER_SIZE: usize = 4;
+
+/// Constructs a GUID-defined section with the given section definition GUID, GUID-specific header data, and content.
+pub fn create_guid_defined_section(guid: efi::Guid, guid_data: Vec<u8>, content: &[u8]) -> Section {
+    let guid_header = GuidDefined {
+        section_definition_guid: guid,
+        data_offset: (COMMON_HEADER_SIZE + core::mem::size_of::<GuidDefined>() + guid_data.len()) as u16,
+        attributes: PROCESSING_REQUIRED,
+    };
+
+    let header = SectionHeader::GuidDefined(guid_header, guid_data, content.len() as u32);
+    Section::new_from_header_with_data(header, content.to_vec()).expect("Failed to create test section")
+}
+
+/// Constructs a section with the specified GUID and payload, prepending
+/// the required 16-byte header (out_size + scratch_size) for Brotli sections.
+pub fn create_brotli_section(payload: &[u8], out_size: u64) -> Section {
+    // Brotli section payload format: [out_size: u64, scratch_size: u64, compressed_data...]
+    let scratch_size = 0u64;
+
+    let mut content = Vec::new();
+    content.extend_from_slice(&out_size.to_le_bytes());
+    content.extend_from_slice(&scratch_size.to_le_bytes());
+    content.extend_from_slice(payload);
+
+    create_guid_defined_section(BROTLI_SECTION, vec![], &content)
+}
+
+/// Constructs a section with the LZMA GUID and the provided compressed payload.
+pub fn create_lzma_section(compressed_data: &[u8]) -> Section {
+    create_guid_defined_section(LZMA_SECTION, vec![], compressed_data)
+}
+
+/// Constructs a CRC32 section with the provided content and GUID-specific header data (the CRC32 value).
+pub fn create_crc32_section(content: &[u8], guid_data: Vec<u8>) -> Section {
+    create_guid_defined_section(CRC32_SECTION, guid_data, content)
+}
+
+/// Section definition GUID of the sections built by [`create_lz4_section`].
+///
+/// LZ4 has no standard section definition GUID, so this is an arbitrary GUID for tests. Register
+/// `Lz4SectionExtractor::new(LZ4_TEST_SECTION_GUID)` to extract these sections.
+#[cfg(feature = "lz4")]
+pub const LZ4_TEST_SECTION_GUID: efi::Guid =
+    efi::Guid::from_fields(0x9c1f7e45, 0x23a8, 0x4d0b, 0x8e, 0x61, &[0x3b, 0x52, 0xd4, 0x0f, 0xa9, 0x17]);
+
+/// Constructs a section with [`LZ4_TEST_SECTION_GUID`] and the provided size-prefixed LZ4 block.
+#[cfg(feature = "lz4")]
+pub fn create_lz4_section(content: &[u8]) -> Section {
+    create_guid_defined_section(LZ4_TEST_SECTION_GUID, vec![], content)
+}
+
+/// Section definition GUID of the sections built by [`create_deflate_section`].
+///
+/// DEFLATE has no standard section definition GUID, so this is an arbitrary GUID for tests. Register
+/// `DeflateSectionExtractor::new(DEFLATE_TEST_SECTION_GUID)` to extract these sections.
+#[cfg(feature = "deflate")]
+pub const DEFLATE_TEST_SECTION_GUID: efi::Guid =
+    efi::Guid::from_fields(0x2e7a4c91, 0xb6d3, 0x4f58, 0x8a, 0x0e, &[0x71, 0xc4, 0x95, 0x3b, 0xd2, 0x6f]);
+
+/// Constructs a section with [`DEFLATE_TEST_SECTION_GUID`] and the provided gzip member or zlib stream.
+#[cfg(feature = "deflate")]
+pub fn create_deflate_section(content: &[u8]) -> Section {
+    create_guid_defined_section(DEFLATE_TEST_SECTION_GUID, vec![], content)
+}
+
+/// Largest decompressed size [`arbitrary_section`] leaves in a size field.
+pub const MAX_ARBITRARY_OUTPUT_SIZE: u64 = 0x10000;
+
+/// Constructs a well-formed GUID-defined section from arbitrary bytes.
+///
+/// The first byte selects the section definition GUID: one of the GUIDs handled by this crate, or a GUID read from
+/// the input. The remaining bytes form the section content. CRC32 sections receive the correct CRC32 of the content
+/// when the selector byte is even and a CRC32 read from the input when it is odd, so both the valid and invalid paths
+/// are reachable. Decompressed size fields in Brotli, LZMA, and LZ4 content are reduced modulo
+/// [`MAX_ARBITRARY_OUTPUT_SIZE`] + 1, so a fuzz target does not spend its time on allocation failures.
+///
+/// The same input always produces the same section.
+pub fn arbitrary_section(input: &[u8]) -> Section {
+    let (&selector, rest) = input.split_first().unwrap_or((&0, &[]));
+    let mut content = rest.to_vec();
+
+    match selector % 7 {
+        0 => {
+            clamp_size_field::<8>(&mut content, 0);
+            create_guid_defined_section(BROTLI_SECTION, vec![], &content)
+        }
+        1 => {
+            // An unpacked size of all ones means "unknown" and is left as is.
+            if content.get(5..13).is_some_and(|size| size.iter().any(|&b| b != 0xFF)) {
+                clamp_size_field::<8>(&mut content, 5);
+            }
+            create_guid_defined_section(LZMA_SECTION, vec![], &content)
+        }
+        2 => {
+            let crc32 = if selector % 2 == 0 {
+                crc32_of(rest)
+            } else {
+                let (crc32, _) = split_array::<4>(rest);
+                u32::from_le_bytes(crc32)
+            };
+            create_crc32_section(rest, crc32.to_le_bytes().to_vec())
+        }
+        #[cfg(feature = "lz4")]
+        3 => {
+            clamp_size_field::<4>(&mut content, 0);
+            create_lz4_section(&content)
+        }
+        #[cfg(feature = "deflate")]
+        4 => create_deflate_section(&content),
+        _ => {
+            let (guid, content) = split_array::<16>(rest);
+            create_guid_defined_section(efi::Guid::from_bytes(&guid), vec![], content)
+        }
+    }
+}
+
+/// Reduces the little-endian size field of `N` bytes at `offset` to at most [`MAX_ARBITRARY_OUTPUT_SIZE`].
+fn clamp_size_field<const N: usize>(content: &mut [u8], offset: usize) {
+    let Some(field) = content.get_mut(offset..offset + N) else {
+        return;
+    };
+    let mut bytes = [0u8; 8];
+    bytes[..N].copy_from_slice(field);
+    let size = u64::from_le_bytes(bytes) % (MAX_ARBITRARY_OUTPUT_SIZE + 1);
+    field.copy_from_slice(&size.to_le_bytes()[..N]);
+}
+
+/// Splits the first `N` bytes off `input`, zero-padding if `input` is shorter.
+fn split_array<const N: usize>(input: &[u8]) -> ([u8; N], &[u8]) {
+    let mut array = [0u8; N];
+    let len = input.len().min(N);
+    array[..len].copy_from_slice(&input[..len]);
+    (array, &input[len..])
+}
+
+#[cfg(feature = "crc32")]
+fn crc32_of(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+#[cfg(not(feature = "crc32"))]
+fn crc32_of(_data: &[u8]) -> u32 {
+    0
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{CompositeSectionExtractor, observer::section_guid};
+    use patina_ffs::section::SectionExtractor;
+
+    #[test]
+    fn test_create_guid_defined_section() {
+        let guid = efi::Guid::from_fields(0x1, 0x2, 0x3, 0x4, 0x5, &[0x6; 6]);
+        let section = create_guid_defined_section(guid, vec![0xAA; 4], b"content");
+
+        let SectionHeader::GuidDefined(guid_header, guid_data, _) = section.header() else {
+            panic!("Expected a GUID-defined section");
+        };
+        assert_eq!(guid_header.section_definition_guid, guid);
+        assert_eq!(guid_header.data_offset as usize, COMMON_HEADER_SIZE + core::mem::size_of::<GuidDefined>() + 4);
+        assert_eq!(guid_data.as_slice(), &[0xAA; 4]);
+        assert_eq!(section.try_content_as_slice().unwrap(), b"content");
+    }
+
+    #[test]
+    fn test_arbitrary_section_is_deterministic() {
+        let input = [2u8, 1, 2, 3, 4, 5, 6, 7, 8];
+        let a = arbitrary_section(&input);
+        let b = arbitrary_section(&input);
+        assert_eq!(section_guid(&a), section_guid(&b));
+        assert_eq!(a.try_content_as_slice().unwrap(), b.try_content_as_slice().unwrap());
+    }
+
+    #[test]
+    fn test_arbitrary_sections_do_not_panic_extractors() {
+        let extractor = CompositeSectionExtractor::new();
+        for selector in 0..=u8::MAX {
+            for len in [0usize, 1, 4, 13, 16, 17, 40] {
+                let mut input = vec![selector];
+                input.extend((0..len).map(|i| (i as u8).wrapping_mul(selector).wrapping_add(0x5D)));
+                let _ = extractor.extract(&arbitrary_section(&input));
+            }
+        }
+    }
+
+    #[test]
+    fn test_arbitrary_section_clamps_size_fields() {
+        let section = arbitrary_section(&[0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]);
+        let size = u64::from_le_bytes(section.try_content_as_slice().unwrap()[..8].try_into().unwrap());
+        assert!(size <= MAX_ARBITRARY_OUTPUT_SIZE);
+
+        // Unknown LZMA unpacked size is preserved.
+        let mut input = vec![1, 0x5D, 0, 0, 0x80, 0];
+        input.extend_from_slice(&[0xFF; 8]);
+        let section = arbitrary_section(&input);
+        assert_eq!(section.try_content_as_slice().unwrap()[5..13], [0xFF; 8]);
+    }
+
+    #[test]
+    #[cfg(feature = "crc32")]
+    fn test_arbitrary_crc32_section_valid_and_invalid() {
+        let extractor = CompositeSectionExtractor::new();
+        // Even selector: correct CRC32.
+        assert_eq!(extractor.extract(&arbitrary_section(&[2, b'a', b'b', b'c'])).unwrap(), b"abc");
+        // Odd selector: CRC32 taken from the input.
+        assert!(extractor.extract(&arbitrary_section(&[9, b'a', b'b', b'c', b'd'])).is_err());
+    }
+}
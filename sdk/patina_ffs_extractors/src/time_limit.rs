@@ -0,0 +1,105 @@
+//! Module for bounding the time spent decompressing a section.
+//!
+//! There are no threads to cancel a decoder from, so the LZMA and Brotli extractors cooperate instead: their decode
+//! loops periodically call an abort callback, and give up with
+//! [`FirmwareFileSystemError::Timeout`](patina_ffs::FirmwareFileSystemError::Timeout) once it returns
+//! `true`. [`ExtractionTimeLimit`] builds such a callback from a [`TimestampSource`], so that a pathological section
+//! cannot stall boot indefinitely.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::TimestampSource;
+
+/// Number of bytes a decoder processes between calls to its abort callback.
+#[cfg(any(feature = "brotli", feature = "lzma"))]
+pub(crate) const POLL_INTERVAL: usize = 0x10000;
+
+/// A limit on the time a single section extraction may take.
+#[derive(Clone, Copy)]
+pub struct ExtractionTimeLimit {
+    source: &'static dyn TimestampSource,
+    ticks: u64,
+}
+
+impl ExtractionTimeLimit {
+    /// Creates a limit of `ticks` ticks of `source`.
+    pub const fn from_ticks(source: &'static dyn TimestampSource, ticks: u64) -> Self {
+        Self { source, ticks }
+    }
+
+    /// Creates a limit of `millis` milliseconds, measured with `source`.
+    ///
+    /// Returns `None` if the frequency of `source` is unknown.
+    pub fn from_millis(source: &'static dyn TimestampSource, millis: u64) -> Option<Self> {
+        match source.frequency() {
+            0 => None,
+            frequency => Some(Self::from_ticks(source, frequency.saturating_mul(millis) / 1000)),
+        }
+    }
+
+    /// Starts timing an extraction, returning an abort callback that returns `true` once the limit has elapsed.
+    pub fn start(&self) -> impl FnMut() -> bool + use<> {
+        let (source, ticks) = (self.source, self.ticks);
+        let start = source.timestamp();
+        move || source.timestamp().wrapping_sub(start) >= ticks
+    }
+}
+
+impl core::fmt::Debug for ExtractionTimeLimit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ExtractionTimeLimit").field("ticks", &self.ticks).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    struct TestClock(AtomicU64);
+
+    impl TimestampSource for TestClock {
+        fn timestamp(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+
+        fn frequency(&self) -> u64 {
+            1_000_000
+        }
+    }
+
+    struct UnknownFrequency;
+
+    impl TimestampSource for UnknownFrequency {
+        fn timestamp(&self) -> u64 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_time_limit_expires() {
+        static CLOCK: TestClock = TestClock(AtomicU64::new(u64::MAX - 100));
+        let limit = ExtractionTimeLimit::from_millis(&CLOCK, 2).unwrap();
+        assert_eq!(limit.ticks, 2000);
+
+        let mut expired = limit.start();
+        assert!(!expired());
+        // The counter wrapping does not end the limit early.
+        CLOCK.0.store(1898, Ordering::Relaxed);
+        assert!(!expired());
+        CLOCK.0.store(1899, Ordering::Relaxed);
+        assert!(expired());
+    }
+
+    #[test]
+    fn test_time_limit_requires_frequency() {
+        static UNKNOWN: UnknownFrequency = UnknownFrequency;
+        assert!(ExtractionTimeLimit::from_millis(&UNKNOWN, 10).is_none());
+        assert!(ExtractionTimeLimit::from_ticks(&UNKNOWN, 0).start()());
+    }
+}